@@ -20,20 +20,39 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
-use ii_cgminer_api::command::{DEVDETAILS, FANS, TEMPCTRL, TEMPS};
+use ii_logging::macros::*;
+
+use ii_async_compat::tokio;
+
+use ii_cgminer_api::command::{
+    ASCSET, ASC_DISABLE, ASC_ENABLE, DEVDETAILS, FANCTRL, FANS, LOCATE, TEMPCTRL, TEMPS,
+    TUNERSTATUS,
+};
+use ii_cgminer_api::support::{
+    self, AscSetParameter, FanCtrlSetting, LocateSetting, TempCtrlParameter,
+};
 use ii_cgminer_api::{command, commands, response};
 
+use bosminer::tuning;
+
 use serde::Serialize;
 
 use std::sync::Arc;
+use std::time::Duration;
 
+use crate::config;
+use crate::fan;
+use crate::locate;
 use crate::monitor;
+use crate::profile;
 use crate::sensor;
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 #[repr(u32)]
 pub enum StatusCode {
     NotReady = 1,
+    TempCtrlDisabled = 2,
+    TargetTempNotAuto = 3,
 }
 
 impl From<StatusCode> for u32 {
@@ -44,12 +63,22 @@ impl From<StatusCode> for u32 {
 
 pub enum ErrorCode {
     NotReady,
+    TempCtrlDisabled,
+    TargetTempNotAuto,
 }
 
 impl From<ErrorCode> for response::Error {
     fn from(code: ErrorCode) -> Self {
         let (code, msg) = match code {
             ErrorCode::NotReady => (StatusCode::NotReady, "Not ready".to_string()),
+            ErrorCode::TempCtrlDisabled => (
+                StatusCode::TempCtrlDisabled,
+                "Temperature control is disabled".to_string(),
+            ),
+            ErrorCode::TargetTempNotAuto => (
+                StatusCode::TargetTempNotAuto,
+                "Target temperature requires automatic fan control".to_string(),
+            ),
         };
 
         Self::from_custom_error(code, msg)
@@ -66,6 +95,8 @@ pub struct DevDetailInfo {
     pub chips: u32,
     #[serde(rename = "Cores")]
     pub cores: u32,
+    #[serde(rename = "Profile")]
+    pub profile: String,
 }
 
 #[derive(Serialize, PartialEq, Clone, Debug)]
@@ -78,20 +109,27 @@ pub struct TempInfo {
 
 pub struct Handler {
     model: String,
+    profile: &'static profile::HardwareProfile,
     managers: Vec<Arc<crate::Manager>>,
     monitor: Arc<monitor::Monitor>,
+    /// Front LED used for locating the miner is not available when its pin cannot be opened
+    locator: Option<locate::Locator>,
 }
 
 impl Handler {
     pub fn new(
         model: String,
+        profile: &'static profile::HardwareProfile,
         managers: Vec<Arc<crate::Manager>>,
         monitor: Arc<monitor::Monitor>,
+        locator: Option<locate::Locator>,
     ) -> Self {
         Self {
             model,
+            profile,
             managers,
             monitor,
+            locator,
         }
     }
 
@@ -102,9 +140,19 @@ impl Handler {
         }
     }
 
-    async fn handle_dev_details(&self) -> command::Result<response::DevDetails<DevDetailInfo>> {
+    async fn handle_dev_details(
+        &self,
+        idx: Option<i32>,
+    ) -> command::Result<response::DevDetails<DevDetailInfo>> {
+        if let Some(idx) = idx {
+            self.get_manager(idx)?;
+        }
+
         let mut list = vec![];
-        for manager in self.managers.iter() {
+        for (manager_idx, manager) in self.managers.iter().enumerate() {
+            if idx.map_or(false, |idx| idx as usize != manager_idx) {
+                continue;
+            }
             let inner = manager.inner.lock().await;
             let mut chip_count = 0;
             let mut voltage = 0.0;
@@ -115,7 +163,7 @@ impl Handler {
                 frequency = hash_chain.get_frequency().await.avg() as u32;
             }
             list.push(response::DevDetail {
-                idx: list.len() as i32,
+                idx: manager_idx as i32,
                 name: manager.to_string(),
                 id: manager.hashboard_idx as i32,
                 driver: "".to_string(),
@@ -127,6 +175,7 @@ impl Handler {
                     frequency,
                     chips: chip_count as u32,
                     cores: (chip_count * crate::bm1387::NUM_CORES_ON_CHIP) as u32,
+                    profile: self.profile.name.to_string(),
                 },
             });
         }
@@ -134,32 +183,110 @@ impl Handler {
         Ok(response::DevDetails { list })
     }
 
-    async fn handle_temp_ctrl(&self) -> command::Result<response::ext::TempCtrl> {
-        let config = self.get_monitor_status()?.config;
+    /// There is no autotuner so each chain stays at the operating point it has been started with
+    async fn handle_tuner_status(&self) -> command::Result<response::TunerStatus> {
+        let mut chains = vec![];
+        for (manager_idx, manager) in self.managers.iter().enumerate() {
+            let inner = manager.inner.lock().await;
+            let mut voltage = 0.0;
+            let mut frequency = 0.0;
+            if let Some(hash_chain) = inner.hash_chain.as_ref() {
+                voltage = hash_chain.get_voltage().await.as_volts() as f64;
+                frequency = hash_chain.get_frequency().await.avg() as f64;
+            }
+            chains.push(response::TunerChain {
+                idx: manager_idx as i32,
+                id: manager.hashboard_idx as i32,
+                stage: response::TunerStage::Disabled,
+                iterations_done: 0,
+                iterations_total: 0,
+                estimated_finish: 0,
+                best_frequency: frequency,
+                best_voltage: voltage,
+            });
+        }
+
+        Ok(response::TunerStatus {
+            power_limit: 0,
+            dynamic_power_scaling: response::Bool::N,
+            chains,
+        })
+    }
 
+    fn temp_ctrl(config: &monitor::Config) -> response::ext::TempCtrl {
         let mut mode = response::ext::TempCtrlMode::Disabled;
         let mut target = None;
         let mut hot = None;
         let mut dangerous = None;
 
-        if let Some(temp_config) = config.temp_config {
+        if let Some(temp_config) = config.temp_config.as_ref() {
             mode = response::ext::TempCtrlMode::Manual;
             hot.replace(temp_config.hot_temp);
             dangerous.replace(temp_config.dangerous_temp);
         }
-        if let Some(fan_config) = config.fan_config {
+        if let Some(fan_config) = config.fan_config.as_ref() {
             if let monitor::FanControlMode::TargetTemperature(target_temp) = fan_config.mode {
                 mode = response::ext::TempCtrlMode::Automatic;
                 target.replace(target_temp);
             }
         }
 
-        Ok(response::ext::TempCtrl {
+        response::ext::TempCtrl {
             mode,
             target,
             hot,
             dangerous,
-        })
+        }
+    }
+
+    async fn handle_temp_ctrl_get(&self) -> command::Result<response::ext::TempCtrl> {
+        Ok(Self::temp_ctrl(&self.get_monitor_status()?.config))
+    }
+
+    async fn handle_temp_ctrl_set(
+        &self,
+        parameter: TempCtrlParameter,
+    ) -> command::Result<response::ext::TempCtrl> {
+        let config = self
+            .monitor
+            .with_configuration(|config| -> command::Result<_> {
+                let current = Self::temp_ctrl(config);
+                if parameter.target.is_some() && current.target.is_none() {
+                    return Err(ErrorCode::TargetTempNotAuto.into());
+                }
+                if (parameter.hot.is_some() || parameter.dangerous.is_some())
+                    && config.temp_config.is_none()
+                {
+                    return Err(ErrorCode::TempCtrlDisabled.into());
+                }
+                // Omitted thresholds are also checked to keep the ordering of the final ones
+                let parameter = TempCtrlParameter {
+                    target: parameter.target.or(current.target),
+                    hot: parameter.hot.or(current.hot),
+                    dangerous: parameter.dangerous.or(current.dangerous),
+                };
+                parameter.check()?;
+
+                if let (Some(fan_config), Some(target)) =
+                    (config.fan_config.as_mut(), parameter.target)
+                {
+                    fan_config.mode = monitor::FanControlMode::TargetTemperature(target);
+                }
+                if let Some(temp_config) = config.temp_config.as_mut() {
+                    temp_config.hot_temp = parameter.hot.unwrap_or(temp_config.hot_temp);
+                    temp_config.dangerous_temp =
+                        parameter.dangerous.unwrap_or(temp_config.dangerous_temp);
+                }
+                Ok(config.clone())
+            })
+            .await?;
+        let temp_ctrl = Self::temp_ctrl(&config);
+        info!(
+            "Temperature control changed by API to target={:?} hot={:?} dangerous={:?}",
+            temp_ctrl.target, temp_ctrl.hot, temp_ctrl.dangerous
+        );
+
+        Ok(temp_ctrl)
     }
 
     async fn handle_temps(&self) -> command::Result<response::ext::Temps<TempInfo>> {
@@ -184,6 +311,120 @@ impl Handler {
         Ok(response::ext::Temps { list: list })
     }
 
+    fn get_manager(&self, idx: i32) -> command::Result<&Arc<crate::Manager>> {
+        self.managers
+            .get(idx as usize)
+            .filter(|_| idx >= 0)
+            .ok_or_else(|| {
+                response::Error::from(response::ErrorCode::InvalidAscId(
+                    idx,
+                    self.managers.len() as i32 - 1,
+                ))
+            })
+    }
+
+    async fn handle_asc_set(
+        &self,
+        parameter: AscSetParameter,
+    ) -> command::Result<response::AscSet> {
+        let idx = parameter.idx;
+        let error = |msg: String| response::ErrorCode::AscSetErr(idx, msg).into();
+
+        let manager = self.get_manager(idx)?;
+        let changes = tuning::parse_changes(&parameter.options).map_err(|e| match e {
+            tuning::Error::UnknownOption(option) => {
+                let supported = tuning::Knob::ALL
+                    .iter()
+                    .map(|knob| knob.to_string())
+                    .collect();
+                response::ErrorCode::InvalidAscOption(idx, option, supported).into()
+            }
+            e => error(e.to_string()),
+        })?;
+
+        let chain = match manager.clone().acquire(ASCSET).await {
+            Ok(crate::ChainStatus::Running(chain)) => chain,
+            Ok(crate::ChainStatus::Stopped(_)) => {
+                return Err(error("hash chain is not running".to_string()))
+            }
+            Err(owner) => return Err(error(format!("hash chain is owned by '{}'", owner))),
+        };
+        let outcome = tuning::apply(&chain, &config::tuning_envelope(), &changes)
+            .await
+            .map_err(|e| error(e.to_string()))?;
+
+        Ok(response::AscSet {
+            idx,
+            options: outcome
+                .steps
+                .iter()
+                .zip(1..)
+                .map(|(step, order)| response::AscSetOption {
+                    option: step.knob.to_string(),
+                    value: step.value.to_string(),
+                    order: if step.result == tuning::StepResult::Skipped {
+                        0
+                    } else {
+                        order
+                    },
+                    result: match step.result {
+                        tuning::StepResult::Applied => response::AscSetResult::Applied,
+                        tuning::StepResult::Failed => response::AscSetResult::Failed,
+                        tuning::StepResult::RolledBack => response::AscSetResult::RolledBack,
+                        tuning::StepResult::Skipped => response::AscSetResult::Skipped,
+                    },
+                })
+                .collect(),
+            error: outcome.error,
+        })
+    }
+
+    async fn handle_asc_enable(&self, idx: i32) -> command::Result<response::AscEnable> {
+        let manager = self.get_manager(idx)?;
+        let chain = match manager.clone().acquire(ASC_ENABLE).await {
+            Ok(crate::ChainStatus::Stopped(chain)) => chain,
+            Ok(crate::ChainStatus::Running(_)) => {
+                return Ok(response::AscEnable::AlreadyEnabled { idx })
+            }
+            Err(owner) => Err(response::ErrorCode::AscSwitchErr(
+                idx,
+                format!("hash chain is owned by '{}'", owner),
+            ))?,
+        };
+
+        // Hash chain initialization takes a while so it is only initiated as in cgminer
+        let frequency = manager.chain_config.frequency.clone();
+        let voltage = manager.chain_config.voltage;
+        tokio::spawn(async move {
+            if let Err((_, e)) = chain
+                .start(&frequency, voltage, config::DEFAULT_ASIC_DIFFICULTY)
+                .await
+            {
+                error!("Cannot enable hash chain {}: {}", idx, e);
+            }
+        });
+
+        Ok(response::AscEnable::Enabled { idx })
+    }
+
+    async fn handle_asc_disable(&self, idx: i32) -> command::Result<response::AscDisable> {
+        let manager = self.get_manager(idx)?;
+        match manager.clone().acquire(ASC_DISABLE).await {
+            Ok(crate::ChainStatus::Running(chain)) => {
+                chain.stop().await;
+            }
+            Ok(crate::ChainStatus::Stopped(_)) => {
+                return Ok(response::AscDisable::AlreadyDisabled { idx })
+            }
+            Err(owner) => Err(response::ErrorCode::AscSwitchErr(
+                idx,
+                format!("hash chain is owned by '{}'", owner),
+            ))?,
+        }
+
+        Ok(response::AscDisable::Disabled { idx })
+    }
+
     async fn handle_fans(&self) -> command::Result<response::ext::Fans> {
         let status = self.get_monitor_status()?;
         let speed = status.fan_speed.map(|speed| speed.to_pwm()).unwrap_or(0);
@@ -202,21 +443,175 @@ impl Handler {
                 .collect(),
         })
     }
+
+    fn fan_ctrl(
+        fan_config: Option<&monitor::FanControlConfig>,
+        status: &monitor::Status,
+    ) -> response::ext::FanCtrl {
+        let (mode, speed) = match fan_config.map(|fan_config| &fan_config.mode) {
+            Some(monitor::FanControlMode::FixedSpeed(speed)) => {
+                (response::ext::FanCtrlMode::Manual, Some(speed.to_pwm()))
+            }
+            Some(monitor::FanControlMode::TargetTemperature(_)) => (
+                response::ext::FanCtrlMode::Auto,
+                status.fan_speed.as_ref().map(|speed| speed.to_pwm()),
+            ),
+            None => (response::ext::FanCtrlMode::Disabled, None),
+        };
+        response::ext::FanCtrl {
+            mode,
+            speed: speed.map(|speed| speed as u32),
+            rpm: status
+                .fan_feedback
+                .rpm
+                .iter()
+                .map(|rpm| *rpm as u32)
+                .collect(),
+        }
+    }
+
+    async fn handle_fan_ctrl_get(&self) -> command::Result<response::ext::FanCtrl> {
+        let status = self.get_monitor_status()?;
+        Ok(Self::fan_ctrl(status.config.fan_config.as_ref(), &status))
+    }
+
+    async fn handle_fan_ctrl_set(
+        &self,
+        setting: FanCtrlSetting,
+    ) -> command::Result<response::ext::FanCtrl> {
+        let status = self.get_monitor_status()?;
+        let profile = self.profile;
+        let fan_config = self
+            .monitor
+            .with_configuration(|config| {
+                let fan_config =
+                    config
+                        .fan_config
+                        .get_or_insert_with(|| monitor::FanControlConfig {
+                            mode: monitor::FanControlMode::FixedSpeed(fan::Speed::FULL_SPEED),
+                            min_fans: profile.min_fans,
+                            speed_limits: profile.fan_curve.speed_limits.clone(),
+                        });
+                fan_config.mode = match (setting, &fan_config.mode) {
+                    // Keep the configured target temperature when already in automatic mode
+                    (FanCtrlSetting::Auto, monitor::FanControlMode::TargetTemperature(target)) => {
+                        monitor::FanControlMode::TargetTemperature(*target)
+                    }
+                    (FanCtrlSetting::Auto, _) => monitor::FanControlMode::TargetTemperature(
+                        profile.fan_curve.target_temp as f32,
+                    ),
+                    (FanCtrlSetting::Manual(speed), _) => {
+                        monitor::FanControlMode::FixedSpeed(fan::Speed::new(speed as usize))
+                    }
+                };
+                fan_config.clone()
+            })
+            .await;
+        info!("Fan control changed by API to {:?}", fan_config.mode);
+
+        Ok(Self::fan_ctrl(Some(&fan_config), &status))
+    }
+
+    /// Replaces `locate` command of the generic handler which cannot blink the front LED
+    async fn handle_locate(
+        &self,
+        setting: Option<LocateSetting>,
+    ) -> command::Result<response::Locate> {
+        let locator = self.locator.as_ref().ok_or_else(|| {
+            response::ErrorCode::LocateErr("front LED is not available".to_string())
+        })?;
+        match setting {
+            Some(LocateSetting::On) => locator.start(None).await,
+            Some(LocateSetting::Timed(duration)) => {
+                locator
+                    .start(Some(Duration::from_secs(duration as u64)))
+                    .await
+            }
+            Some(LocateSetting::Off) => locator.stop().await,
+            None => {}
+        }
+        if let Some(setting) = setting {
+            info!("Locate mode changed by API to {:?}", setting);
+        }
+
+        let status = locator.status().await;
+        Ok(response::Locate {
+            active: status.active.into(),
+            // Round up so that running locate mode never reports zero remaining time
+            remaining: status
+                .remaining
+                .map(|remaining| (remaining.as_millis() as u32 + 999) / 1000),
+        })
+    }
 }
 
 pub fn create_custom_commands(
     backend: Arc<crate::Backend>,
+    profile: &'static profile::HardwareProfile,
     managers: Vec<Arc<crate::Manager>>,
     monitor: Arc<monitor::Monitor>,
+    locator: Option<locate::Locator>,
 ) -> Option<command::Map> {
-    let handler = Arc::new(Handler::new(backend.to_string(), managers, monitor));
+    let handler = Arc::new(Handler::new(
+        backend.to_string(),
+        profile,
+        managers,
+        monitor,
+        locator,
+    ));
 
-    let custom_commands = commands![
-        (DEVDETAILS: ParameterLess -> handler.handle_dev_details),
-        (TEMPCTRL: ParameterLess -> handler.handle_temp_ctrl),
-        (TEMPS: ParameterLess -> handler.handle_temps),
-        (FANS: ParameterLess -> handler.handle_fans)
+    let parse_dev_details = support::parse_optional_asc_id;
+    let parse_asc_set = AscSetParameter::parse;
+    let parse_asc_enable = support::parse_asc_id;
+    let parse_asc_disable = support::parse_asc_id;
+    let parse_fan_ctrl = support::parse_fan_ctrl;
+    let parse_temp_ctrl = TempCtrlParameter::parse;
+    let parse_locate = support::parse_locate;
+    let mut custom_commands = commands![
+        (TUNERSTATUS: ParameterLess -> handler.handle_tuner_status, "Autotuner state of hash chains"),
+        (TEMPS: ParameterLess -> handler.handle_temps, "Temperatures of hash chains"),
+        (FANS: ParameterLess -> handler.handle_fans, "Fan speeds")
     ];
+    custom_commands.insert(
+        DEVDETAILS,
+        command!(DEVDETAILS: Parsed(parse_dev_details) -> handler.handle_dev_details)
+            .description("Details of all devices or device N"),
+    );
+    custom_commands.insert(
+        TEMPCTRL,
+        command!(TEMPCTRL: Parsed(parse_temp_ctrl) -> handler.handle_temp_ctrl_get | handle_temp_ctrl_set)
+            .description("Show or set temperature limits target,hot,dangerous"),
+    );
+    custom_commands.insert(
+        FANCTRL,
+        command!(FANCTRL: Parsed(parse_fan_ctrl) -> handler.handle_fan_ctrl_get | handle_fan_ctrl_set)
+            .description("Show or set fan speed auto|N"),
+    );
+    custom_commands.insert(
+        LOCATE,
+        command!(LOCATE: Parsed(parse_locate) -> handler.handle_locate)
+            .description("Show or set locate mode true|false|N seconds")
+            .privileged_parameter(),
+    );
+    // Hash chains can be tuned and switched on and off only by the backend
+    custom_commands.insert(
+        ASCSET,
+        command!(ASCSET: Parsed(parse_asc_set) -> handler.handle_asc_set)
+            .description("Set options of ASC device N,opt[=val][:opt=val]")
+            .privileged(),
+    );
+    custom_commands.insert(
+        ASC_ENABLE,
+        command!(ASC_ENABLE: Parsed(parse_asc_enable) -> handler.handle_asc_enable)
+            .description("Enable ASC device N")
+            .privileged(),
+    );
+    custom_commands.insert(
+        ASC_DISABLE,
+        command!(ASC_DISABLE: Parsed(parse_asc_disable) -> handler.handle_asc_disable)
+            .description("Disable ASC device N")
+            .privileged(),
+    );
 
     Some(custom_commands)
 }
@@ -38,12 +38,15 @@ use crate::fan;
 use crate::hooks;
 use crate::monitor;
 use crate::power;
+use crate::profile;
 use crate::FrequencySettings;
 
 use support::OptionDefault;
 
-use bosminer::client;
+use bosminer::client::{self, dead_pools};
 use bosminer::hal::{self, BackendConfig as _};
+use bosminer::sharelog;
+use bosminer::tuning;
 
 use bosminer_config::{ClientDescriptor, ClientUserInfo};
 
@@ -121,6 +124,24 @@ pub const FREQUENCY_MHZ_MAX: f64 = 900.0;
 pub const VOLTAGE_V_MIN: f64 = 7.95;
 pub const VOLTAGE_V_MAX: f64 = 9.4;
 
+/// Minimal voltage required for running hash chain above given frequency in MHz
+pub const TUNING_RULES: [(f32, f32); 3] = [(500.0, 8.3), (650.0, 8.8), (750.0, 9.1)];
+
+/// Safe envelope for changing frequency and voltage of running hash chain
+pub fn tuning_envelope() -> tuning::Envelope {
+    tuning::Envelope {
+        voltage: VOLTAGE_V_MIN as f32..=VOLTAGE_V_MAX as f32,
+        frequency: FREQUENCY_MHZ_MIN as f32..=FREQUENCY_MHZ_MAX as f32,
+        rules: TUNING_RULES
+            .iter()
+            .map(|&(frequency, min_voltage)| tuning::Rule {
+                frequency,
+                min_voltage,
+            })
+            .collect(),
+    }
+}
+
 /// Range of monitored temperature
 pub const TEMPERATURE_C_MIN: f64 = 0.0;
 pub const TEMPERATURE_C_MAX: f64 = 200.0;
@@ -167,6 +188,13 @@ impl std::string::ToString for TempControlMode {
     }
 }
 
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadPoolsPolicy {
+    Park,
+    Spin,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Format {
     pub version: String,
@@ -219,6 +247,39 @@ pub struct FanControl {
     min_fans: Option<usize>,
 }
 
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ShareLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    /// Maximal size of one file in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_file_size: Option<u64>,
+    /// Number of rotated files kept besides the current one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_files: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct DeadPools {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy: Option<DeadPoolsPolicy>,
+    /// Time in seconds of all pools being dead before the policy is applied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grace_period: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Hardware {
+    /// Name of built-in hardware profile used instead of the detected one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Default, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct Backend {
@@ -238,6 +299,12 @@ pub struct Backend {
     temp_control: Option<TempControl>,
     #[serde(skip_serializing_if = "Option::is_none")]
     fan_control: Option<FanControl>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sharelog: Option<ShareLog>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dead_pools: Option<DeadPools>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hardware: Option<Hardware>,
     #[serde(rename = "group")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub groups: Option<Vec<bosminer_config::GroupConfig>>,
@@ -245,6 +312,9 @@ pub struct Backend {
     pub hooks: Option<Arc<dyn hooks::Hooks>>,
     #[serde(skip)]
     pub fans_on_while_warming_up: Option<bool>,
+    /// Hardware profile providing defaults for anything missing in the configuration
+    #[serde(skip)]
+    pub profile: Option<&'static profile::HardwareProfile>,
 }
 
 pub trait ConfigBody
@@ -354,6 +424,27 @@ impl Backend {
         }
     }
 
+    /// Selected hardware profile or the default one when no detection has been done
+    pub fn profile(&self) -> &'static profile::HardwareProfile {
+        self.profile.unwrap_or(profile::DEFAULT)
+    }
+
+    /// Select built-in profile requested by the configuration or detect the hardware and select
+    /// its profile
+    pub fn detect_profile(&mut self) -> Result<(), profile::Error> {
+        let profile = match self.hardware.as_ref().and_then(|v| v.profile.as_ref()) {
+            Some(name) => {
+                let profile = profile::find(name)?;
+                info!("Hardware detection overridden by profile '{}'", name);
+                profile
+            }
+            None => profile::detect()?,
+        };
+        self.profile.replace(profile);
+        self.info.hw_profile.replace(profile.name.to_string());
+        Ok(())
+    }
+
     pub fn resolve_chain_config(&self, hash_chain_idx: usize) -> ResolvedChainConfig {
         // Take global hash chain configuration or default value
         let overridable = self
@@ -362,11 +453,11 @@ impl Backend {
             .and_then(|v| v.overridable.as_ref());
         let mut frequency = OptionDefault::new(
             overridable.as_ref().and_then(|v| v.frequency),
-            DEFAULT_FREQUENCY_MHZ,
+            self.profile().frequency,
         );
         let mut voltage = OptionDefault::new(
             overridable.as_ref().and_then(|v| v.voltage),
-            DEFAULT_VOLTAGE_V,
+            self.profile().voltage,
         );
         let mut enabled = DEFAULT_HASH_CHAIN_ENABLED;
 
@@ -406,15 +497,15 @@ impl Backend {
         );
         let target_temp = OptionDefault::new(
             self.temp_control.as_ref().and_then(|v| v.target_temp),
-            DEFAULT_TARGET_TEMP_C,
+            self.profile().fan_curve.target_temp,
         );
         let hot_temp = OptionDefault::new(
             self.temp_control.as_ref().and_then(|v| v.hot_temp),
-            DEFAULT_HOT_TEMP_C,
+            self.profile().fan_curve.hot_temp,
         );
         let dangerous_temp = OptionDefault::new(
             self.temp_control.as_ref().and_then(|v| v.dangerous_temp),
-            DEFAULT_DANGEROUS_TEMP_C,
+            self.profile().fan_curve.dangerous_temp,
         );
 
         // Get fan control settings
@@ -424,7 +515,7 @@ impl Backend {
         );
         let min_fans = OptionDefault::new(
             self.fan_control.as_ref().and_then(|v| v.min_fans),
-            DEFAULT_MIN_FANS,
+            self.profile().min_fans,
         );
 
         let temp_config;
@@ -462,6 +553,7 @@ impl Backend {
                 fan_config = Some(monitor::FanControlConfig {
                     mode: monitor::FanControlMode::TargetTemperature(*target_temp as f32),
                     min_fans: *min_fans,
+                    speed_limits: self.profile().fan_curve.speed_limits.clone(),
                 });
                 // do sanity checks
                 if fan_speed.is_some() {
@@ -479,6 +571,7 @@ impl Backend {
                     Some(monitor::FanControlConfig {
                         mode: monitor::FanControlMode::FixedSpeed(fan::Speed::new(*fan_speed)),
                         min_fans: *min_fans,
+                        speed_limits: self.profile().fan_curve.speed_limits.clone(),
                     })
                 };
                 // do sanity checks
@@ -542,6 +635,16 @@ impl ConfigBody for Backend {
             }
         }
 
+        if let Some(name) = self.hardware.as_ref().and_then(|v| v.profile.as_ref()) {
+            profile::find(name).map_err(|e| e.to_string())?;
+        }
+
+        if let Some(sharelog) = &self.sharelog {
+            if sharelog.max_file_size == Some(0) {
+                Err("share log 'max_file_size' has to be greater than zero")?;
+            }
+        }
+
         // Analyze group configuration, make sure the groups are unique, and build descriptor
         // topology out of the configuration data
         // Don't worry if is this section missing, maybe there are some pools on command line
@@ -600,4 +703,40 @@ impl hal::BackendConfig for Backend {
     fn info(&self) -> Option<hal::BackendInfo> {
         Some(self.info.clone())
     }
+
+    fn sharelog(&self) -> Option<sharelog::Config> {
+        self.sharelog.as_ref().map(|sharelog| {
+            let default = sharelog::Config::default();
+            sharelog::Config {
+                path: sharelog
+                    .path
+                    .as_ref()
+                    .map(|path| path.into())
+                    .unwrap_or(default.path),
+                max_file_size: sharelog.max_file_size.unwrap_or(default.max_file_size),
+                max_files: sharelog.max_files.unwrap_or(default.max_files),
+                enabled: sharelog.enabled.unwrap_or(default.enabled),
+                ..default
+            }
+        })
+    }
+
+    fn dead_pools(&self) -> dead_pools::Config {
+        let default = dead_pools::Config::default();
+        match &self.dead_pools {
+            Some(dead_pools) => dead_pools::Config {
+                policy: match dead_pools.policy {
+                    Some(DeadPoolsPolicy::Park) => dead_pools::Policy::Park,
+                    Some(DeadPoolsPolicy::Spin) => dead_pools::Policy::Spin,
+                    None => default.policy,
+                },
+                grace_period: dead_pools
+                    .grace_period
+                    .map(Duration::from_secs)
+                    .unwrap_or(default.grace_period),
+                ..default
+            },
+            None => default,
+        }
+    }
 }
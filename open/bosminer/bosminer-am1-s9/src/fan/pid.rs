@@ -25,8 +25,10 @@
 mod offset_pid;
 
 use super::Speed;
+use crate::monitor::FanSpeedLimits;
 use offset_pid::OffsetPIDController;
 use pid_control::Controller;
+use std::ops::RangeInclusive;
 use std::time::Instant;
 
 pub struct TempControl {
@@ -44,18 +46,14 @@ impl TempControl {
             pid,
             last_update: Instant::now(),
         };
-        temp_control.set_warm_up_limits();
+        temp_control.set_limits(&FanSpeedLimits::DEFAULT.warm_up);
         return temp_control;
     }
 
-    /// set fan limits when warming up
-    pub fn set_warm_up_limits(&mut self) {
-        self.pid.set_limits(60.0, 100.0);
-    }
-
-    /// set fan limits when in operation
-    pub fn set_normal_limits(&mut self) {
-        self.pid.set_limits(1.0, 100.0);
+    /// set range of fan speeds the controller may use
+    pub fn set_limits(&mut self, limits: &RangeInclusive<usize>) {
+        self.pid
+            .set_limits(*limits.start() as f64, *limits.end() as f64);
     }
 
     pub fn set_target(&mut self, target: f64) {
@@ -34,9 +34,11 @@ pub mod halt;
 pub mod hooks;
 pub mod i2c;
 pub mod io;
+pub mod locate;
 pub mod monitor;
 pub mod null_work;
 pub mod power;
+pub mod profile;
 pub mod registry;
 pub mod sensor;
 pub mod utils;
@@ -47,14 +49,17 @@ pub mod test;
 use ii_logging::macros::*;
 
 use bosminer::async_trait;
+use bosminer::client::dead_pools;
 use bosminer::hal::{self, BackendConfig as _};
 use bosminer::node;
 use bosminer::stats;
+use bosminer::tuning;
 use bosminer::work;
 
 use bosminer_macros::WorkSolverNode;
 
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex as StdMutex};
 use std::time::{Duration, Instant};
 
@@ -77,7 +82,7 @@ use embedded_hal::digital::v2::OutputPin;
 use ii_bitcoin::MeetsTarget;
 
 use ii_async_compat::tokio;
-use tokio::sync::watch;
+use tokio::sync::{broadcast, watch};
 use tokio::time::delay_for;
 
 /// Timing constants
@@ -103,9 +108,6 @@ const INIT_CHIP_BAUD_RATE: usize = 115740;
 /// Exact desired target baud rate when hashing at full speed (matches the divisor, too)
 const TARGET_CHIP_BAUD_RATE: usize = 1562500;
 
-/// Address of chip with connected temp sensor
-const TEMP_CHIP: ChipAddress = ChipAddress::One(61);
-
 /// Timeout for completion of haschain halt
 const HALT_TIMEOUT: Duration = Duration::from_secs(30);
 
@@ -198,6 +200,8 @@ pub struct HashChain {
     /// Do not send open-core work if this is true (some tests that test chip initialization may
     /// want to do this).
     disable_init_work: bool,
+    /// Location of temperature sensor on the hashboard
+    sensor_map: &'static profile::SensorMap,
     /// channels through which temperature status is sent
     temperature_sender: Mutex<Option<watch::Sender<Option<sensor::Temperature>>>>,
     temperature_receiver: watch::Receiver<Option<sensor::Temperature>>,
@@ -261,6 +265,7 @@ impl HashChain {
             work_tx_io: Mutex::new(Some(work_tx_io)),
             monitor_tx,
             disable_init_work: false,
+            sensor_map: &profile::DEFAULT.sensor_map,
             temperature_sender: Mutex::new(Some(temperature_sender)),
             temperature_receiver,
             counter: Arc::new(Mutex::new(counters::HashChain::new(
@@ -739,14 +744,16 @@ impl HashChain {
 
     async fn try_to_initialize_sensor(
         command_context: command::Context,
+        sensor_map: &profile::SensorMap,
     ) -> error::Result<Box<dyn sensor::Sensor>> {
         // construct I2C bus via command interface
-        let i2c_bus = bm1387::i2c::Bus::new_and_init(command_context, TEMP_CHIP)
-            .await
-            .with_context(|_| ErrorKind::Sensors("bus construction failed".into()))?;
+        let i2c_bus =
+            bm1387::i2c::Bus::new_and_init(command_context, ChipAddress::One(sensor_map.temp_chip))
+                .await
+                .with_context(|_| ErrorKind::Sensors("bus construction failed".into()))?;
 
         // try to probe sensor
-        let sensor = sensor::probe_i2c_sensors(i2c_bus)
+        let sensor = sensor::probe_i2c_sensors(i2c_bus, sensor_map.i2c_addresses)
             .await
             .with_context(|_| ErrorKind::Sensors("error when probing sensors".into()))?;
 
@@ -792,7 +799,8 @@ impl HashChain {
 
         // Try to probe sensor
         // This may fail - in which case we put `None` into `sensor`
-        let mut sensor = match Self::try_to_initialize_sensor(self.command_context.clone())
+        let command_context = self.command_context.clone();
+        let mut sensor = match Self::try_to_initialize_sensor(command_context, self.sensor_map)
             .await
             .with_context(|_| ErrorKind::Hashboard(self.hashboard_idx, "sensor error".into()))
             .map_err(|e| e.into())
@@ -1269,6 +1277,30 @@ impl RunningChain {
     }
 }
 
+#[async_trait]
+impl tuning::Tunable for RunningChain {
+    async fn get_point(&self) -> tuning::Point {
+        tuning::Point {
+            voltage: self.get_voltage().await.as_volts(),
+            frequency: self.get_frequency().await.avg() as f32 / 1_000_000.0,
+        }
+    }
+
+    async fn set(&self, knob: tuning::Knob, value: f32) -> Result<(), String> {
+        match knob {
+            tuning::Knob::Voltage => {
+                let voltage = power::Voltage::from_volts(value).map_err(|e| e.to_string())?;
+                self.set_voltage(voltage).await
+            }
+            tuning::Knob::Frequency => {
+                let frequency = FrequencySettings::from_frequency((value * 1_000_000.0) as usize);
+                self.set_frequency(&frequency).await
+            }
+        }
+        .map_err(|e| e.to_string())
+    }
+}
+
 pub enum ChainStatus {
     Running(RunningChain),
     Stopped(StoppedChain),
@@ -1303,6 +1335,7 @@ pub struct Manager {
     reset_pin: ResetPin,
     voltage_ctrl_backend: Arc<power::I2cBackend>,
     midstate_count: MidstateCount,
+    sensor_map: &'static profile::SensorMap,
     /// channel to report to the monitor
     monitor_tx: mpsc::UnboundedSender<monitor::Message>,
     /// TODO: wrap this type in a structure (in Monitor)
@@ -1370,6 +1403,7 @@ impl Manager {
             self.monitor_tx.clone(),
         )
         .expect("BUG: hashchain instantiation failed");
+        hash_chain.sensor_map = self.sensor_map;
 
         // initialize it
         let work_registry = match hash_chain
@@ -1521,11 +1555,13 @@ impl Backend {
         }
     }
 
-    /// Enumerate present hashboards by querying the plug pin
-    pub fn detect_hashboards(gpio_mgr: &gpio::ControlPinManager) -> error::Result<Vec<usize>> {
+    /// Enumerate present hashboards by querying the plug pin of all `hash_chains` connectors
+    pub fn detect_hashboards(
+        gpio_mgr: &gpio::ControlPinManager,
+        hash_chains: &[usize],
+    ) -> error::Result<Vec<usize>> {
         let mut detected = vec![];
-        // TODO: configure this range somewhere
-        for hashboard_idx in 1..=8 {
+        for &hashboard_idx in hash_chains {
             let plug_pin = PlugPin::open(gpio_mgr, hashboard_idx)?;
             if plug_pin.hashboard_present()? {
                 detected.push(hashboard_idx);
@@ -1534,6 +1570,68 @@ impl Backend {
         Ok(detected)
     }
 
+    /// Park running hash chains when all pools are dead (and parking policy is configured) and
+    /// resume them with the pre-park operating point on the first pool recovery
+    async fn dead_pools_handler(
+        managers: Vec<Arc<Manager>>,
+        mut event_receiver: broadcast::Receiver<dead_pools::Event>,
+    ) {
+        let envelope = config::tuning_envelope();
+        let mut parked = vec![];
+
+        loop {
+            let event = match event_receiver.recv().await {
+                Ok(event) => event,
+                // Events overwritten while lagging behind are lost and the receiving continues with
+                // the oldest retained one. The handler may see the same event repeated then.
+                Err(broadcast::RecvError::Lagged(_)) => continue,
+                Err(broadcast::RecvError::Closed) => break,
+            };
+            match event {
+                dead_pools::Event::AllDead(dead_pools::Policy::Park) if !parked.is_empty() => {
+                    info!("Hash chains are already parked while all pools are dead");
+                }
+                dead_pools::Event::AllDead(dead_pools::Policy::Park) => {
+                    for manager in managers.iter() {
+                        match manager.clone().acquire("park").await {
+                            Ok(ChainStatus::Running(chain)) => {
+                                match tuning::park(chain, &envelope).await {
+                                    Ok(chain) => {
+                                        info!("Hash chain {} parked", manager.hashboard_idx);
+                                        parked.push(chain);
+                                    }
+                                    Err(e) => error!(
+                                        "Cannot park hash chain {}: {}",
+                                        manager.hashboard_idx, e
+                                    ),
+                                }
+                            }
+                            // Stopped chains do not consume any power
+                            Ok(ChainStatus::Stopped(_)) => {}
+                            Err(owner) => warn!(
+                                "Cannot park hash chain {} owned by '{}'",
+                                manager.hashboard_idx, owner
+                            ),
+                        }
+                    }
+                }
+                dead_pools::Event::AllDead(dead_pools::Policy::Spin) => {
+                    info!("Keeping hash chains warm while all pools are dead");
+                }
+                dead_pools::Event::Recovered(_) => {
+                    for chain in parked.drain(..) {
+                        match chain.resume(&envelope).await {
+                            Ok(chain) => {
+                                info!("Hash chain {} resumed", chain.manager.hashboard_idx)
+                            }
+                            Err(e) => error!("Cannot resume parked hash chain: {}", e),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     /// Miner termination handler called when app is shutdown.
     /// Just propagate the shutdown to all hashchain managers
     async fn termination_handler(halt_sender: Arc<halt::Sender>) {
@@ -1582,7 +1680,9 @@ impl Backend {
         .await;
         hooks.monitor_started(monitor.clone()).await;
 
-        let voltage_ctrl_backend = Arc::new(power::I2cBackend::new(0));
+        let voltage_ctrl_backend = Arc::new(power::I2cBackend::new(
+            backend_config.profile().voltage_ctrl_i2c_bus,
+        ));
         let mut managers = Vec::new();
         info!(
             "Initializing miner, enabled_chains={:?}, midstate_count={}",
@@ -1613,6 +1713,7 @@ impl Backend {
                         voltage_ctrl_backend: voltage_ctrl_backend.clone(),
                         hashboard_idx,
                         midstate_count: chain_config.midstate_count,
+                        sensor_map: &backend_config.profile().sensor_map,
                         work_solver_stats: Default::default(),
                         solution_sender,
                         work_generator,
@@ -1693,27 +1794,51 @@ impl hal::Backend for Backend {
             .expect("BUG: missing client manager");
         let group_configs = backend_config.groups.take();
         let backend_info = backend_config.info();
+        let profile = backend_config.profile();
+        info!("Using hardware profile '{}'", profile.name);
 
         let backend = work_hub.to_node().clone();
         let gpio_mgr = gpio::ControlPinManager::new();
+        let locator = locate::Locator::open(&gpio_mgr)
+            .map_err(|e| warn!("Locate mode is not available: {}", e))
+            .ok();
         let (app_halt_sender, app_halt_receiver) = halt::make_pair(HALT_TIMEOUT);
         let (managers, monitor) = Self::start_miner(
             &gpio_mgr,
-            Self::detect_hashboards(&gpio_mgr).expect("failed detecting hashboards"),
+            Self::detect_hashboards(&gpio_mgr, profile.hash_chains)
+                .expect("failed detecting hashboards"),
             work_hub,
             backend_config,
             app_halt_receiver,
             app_halt_sender.clone(),
         )
         .await;
-
-        // On miner exit, halt the whole program
+        tokio::spawn(Self::dead_pools_handler(
+            managers.clone(),
+            client_manager.subscribe_to_dead_pools_events(),
+        ));
+
+        // On miner exit, halt the whole program. The halt requested by `quit` or `restart` API
+        // command is finished by the API which terminates the program by itself.
+        let api_halt = Arc::new(AtomicBool::new(false));
+        let exit_on_halt = api_halt.clone();
         app_halt_sender
-            .add_exit_hook(async {
-                println!("Exiting.");
-                std::process::exit(0);
+            .add_exit_hook(async move {
+                if !exit_on_halt.load(Ordering::Relaxed) {
+                    println!("Exiting.");
+                    std::process::exit(0);
+                }
             })
             .await;
+        let halt_sender = app_halt_sender.clone();
+        let halt_handler: hal::HaltHandler = Box::new(move || {
+            let halt_sender = halt_sender.clone();
+            let api_halt = api_halt.clone();
+            Box::pin(async move {
+                api_halt.store(true, Ordering::Relaxed);
+                halt_sender.send_halt().await;
+            })
+        });
         // Hook `Ctrl-C`, `SIGTERM` and other termination methods
         app_halt_sender.hook_termination_signals();
 
@@ -1731,7 +1856,10 @@ impl hal::Backend for Backend {
         }
 
         Ok(hal::FrontendConfig {
-            cgminer_custom_commands: cgminer::create_custom_commands(backend, managers, monitor),
+            cgminer_custom_commands: cgminer::create_custom_commands(
+                backend, profile, managers, monitor, locator,
+            ),
+            halt_handler: Some(halt_handler),
         })
     }
 
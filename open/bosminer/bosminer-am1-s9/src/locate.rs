@@ -0,0 +1,156 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Locate mode blinking the front LED to physically identify the miner
+
+use ii_logging::macros::*;
+
+use ii_async_compat::{futures, tokio};
+
+use embedded_hal::digital::v2::OutputPin;
+use failure::ResultExt;
+use futures::channel::oneshot;
+use futures::lock::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+
+use std::time::Duration;
+
+use crate::error::{self, ErrorKind};
+use crate::gpio;
+
+/// Time for which the LED is kept on and off while blinking
+const BLINK_PERIOD: Duration = Duration::from_millis(500);
+
+/// Current state of locate mode
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Status {
+    pub active: bool,
+    /// Remaining time of timed locate mode
+    pub remaining: Option<Duration>,
+}
+
+/// Running blinking task
+struct Blinking {
+    deadline: Option<Instant>,
+    /// Dropping the sender stops the task
+    stop_sender: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl Blinking {
+    async fn stop(self) {
+        drop(self.stop_sender);
+        // The task only switches the LED off when stopped
+        let _ = self.task.await;
+    }
+}
+
+/// Controls locate mode of the miner
+pub struct Locator {
+    led: gpio::PinOut,
+    blinking: Mutex<Option<Blinking>>,
+}
+
+impl Locator {
+    pub fn open(gpio_mgr: &gpio::ControlPinManager) -> error::Result<Self> {
+        Ok(Self {
+            led: gpio_mgr
+                .get_pin_out(gpio::PinOutName::LEDFrontRed)
+                .context(ErrorKind::General(
+                    "failed to initialize front LED pin".to_string(),
+                ))?,
+            blinking: Mutex::new(None),
+        })
+    }
+
+    async fn blink(
+        mut led: gpio::PinOut,
+        deadline: Option<Instant>,
+        mut stop_receiver: oneshot::Receiver<()>,
+    ) {
+        let mut on = false;
+        loop {
+            on = !on;
+            let result = if on { led.set_high() } else { led.set_low() };
+            if let Err(e) = result {
+                warn!("Locate: cannot switch front LED: {}", e);
+            }
+            if time::timeout(BLINK_PERIOD, &mut stop_receiver)
+                .await
+                .is_ok()
+                || deadline.map_or(false, |deadline| Instant::now() >= deadline)
+            {
+                break;
+            }
+        }
+        if let Err(e) = led.set_low() {
+            warn!("Locate: cannot switch off front LED: {}", e);
+        }
+    }
+
+    /// Starts blinking of the front LED for `duration` or until it is stopped explicitly when
+    /// the duration is missing. Already running locate mode is restarted.
+    pub async fn start(&self, duration: Option<Duration>) {
+        let mut blinking = self.blinking.lock().await;
+        if let Some(blinking) = blinking.take() {
+            blinking.stop().await;
+        }
+
+        let deadline = duration.map(|duration| Instant::now() + duration);
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        let task = tokio::spawn(Self::blink(self.led.clone(), deadline, stop_receiver));
+        *blinking = Some(Blinking {
+            deadline,
+            stop_sender,
+            task,
+        });
+    }
+
+    pub async fn stop(&self) {
+        if let Some(blinking) = self.blinking.lock().await.take() {
+            blinking.stop().await;
+        }
+    }
+
+    pub async fn status(&self) -> Status {
+        let now = Instant::now();
+        match &*self.blinking.lock().await {
+            Some(Blinking {
+                deadline: Some(deadline),
+                ..
+            }) if *deadline > now => Status {
+                active: true,
+                remaining: Some(*deadline - now),
+            },
+            Some(Blinking { deadline: None, .. }) => Status {
+                active: true,
+                remaining: None,
+            },
+            // Timed locate mode has already expired
+            _ => Status {
+                active: false,
+                remaining: None,
+            },
+        }
+    }
+}
@@ -80,6 +80,14 @@ async fn main() {
                 .required(false)
                 .takes_value(true),
         )
+        .arg(
+            clap::Arg::with_name("hw-profile")
+                .long("hw-profile")
+                .value_name("NAME")
+                .help("Use built-in hardware profile instead of detecting the hardware")
+                .required(false)
+                .takes_value(true),
+        )
         .subcommand(
             clap::SubCommand::with_name("config")
                 .about("Configuration backend API")
@@ -241,11 +249,22 @@ async fn main() {
             .voltage
             .replace(voltage);
     }
+    if let Some(name) = matches.value_of("hw-profile") {
+        backend_config
+            .hardware
+            .get_or_insert_with(|| Default::default())
+            .profile
+            .replace(name.to_string());
+    }
 
     if let Err(e) = backend_config.fill_info::<config::Backend>() {
         error!("Cannot get backend information: {}", e.to_string());
         return;
     }
+    if let Err(e) = backend_config.detect_profile() {
+        error!("Cannot select hardware profile: {}", e.to_string());
+        return;
+    }
 
     ii_async_compat::setup_panic_handling();
     bosminer::main::<bosminer_am1_s9::Backend>(backend_config, bosminer::SIGNATURE.to_string())
@@ -29,6 +29,7 @@ use crate::fan;
 use crate::halt;
 use crate::sensor::{self, Measurement};
 
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -215,6 +216,28 @@ pub enum FanControlMode {
     TargetTemperature(f32),
 }
 
+/// Range of fan speeds used by PID controller
+#[derive(Debug, Clone, PartialEq)]
+pub struct FanSpeedLimits {
+    /// Limits used while the miner is warming up
+    pub warm_up: RangeInclusive<usize>,
+    /// Limits used in operation
+    pub normal: RangeInclusive<usize>,
+}
+
+impl FanSpeedLimits {
+    pub const DEFAULT: Self = Self {
+        warm_up: 60..=100,
+        normal: 1..=100,
+    };
+}
+
+impl Default for FanSpeedLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
 /// Fan configuration
 #[derive(Debug, Clone)]
 pub struct FanControlConfig {
@@ -222,6 +245,7 @@ pub struct FanControlConfig {
     /// Minimal number of fans - miner will refuse to work until at least
     /// this number of fans is spinning.
     pub min_fans: usize,
+    pub speed_limits: FanSpeedLimits,
 }
 
 /// Temperature limit configuration
@@ -589,10 +613,16 @@ impl Monitor {
                 target_temp,
                 input_temp,
             } => {
+                let speed_limits = inner
+                    .config
+                    .fan_config
+                    .as_ref()
+                    .map(|fan_config| fan_config.speed_limits.clone())
+                    .expect("BUG: missing fan configuration");
                 if inner.config.fans_on_while_warming_up && miner_warming_up {
-                    inner.pid.set_warm_up_limits();
+                    inner.pid.set_limits(&speed_limits.warm_up);
                 } else {
-                    inner.pid.set_normal_limits();
+                    inner.pid.set_limits(&speed_limits.normal);
                 }
                 inner.pid.set_target(target_temp.into());
                 let speed = inner.pid.update(input_temp.into());
@@ -905,6 +935,7 @@ mod test {
         let fan_config = FanControlConfig {
             mode: FanControlMode::FixedSpeed(fan_speed),
             min_fans: 2,
+            speed_limits: Default::default(),
         };
         let fans_off = fan::Speed::STOPPED;
         let fans_off_config = Config {
@@ -912,6 +943,7 @@ mod test {
             fan_config: Some(FanControlConfig {
                 mode: FanControlMode::FixedSpeed(fans_off),
                 min_fans: 2,
+                speed_limits: Default::default(),
             }),
             temp_config: None,
         };
@@ -940,6 +972,7 @@ mod test {
             fan_config: Some(FanControlConfig {
                 mode: FanControlMode::TargetTemperature(75.0),
                 min_fans: 2,
+                speed_limits: Default::default(),
             }),
             temp_config: Some(temp_config.clone()),
         };
@@ -0,0 +1,240 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Initiative for Open-Source (BIOS).
+//
+// BIOS is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Common Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Common Public License for more details.
+//
+// You should have received a copy of the GNU Common Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BIOS or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Built-in hardware profiles and detection of the hardware the miner is running on
+//!
+//! The selected profile provides defaults that are merged underneath the user configuration so
+//! that a configuration with pools only is sufficient for a known unit.
+//!
+//! The hardware is identified by the control board only. Hashboards are not detected because S9
+//! hashboards have no ID registers readable before hash chain initialization.
+
+use crate::config;
+use crate::monitor;
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Board name provided by the system (OpenWrt sysinfo)
+pub const DEFAULT_BOARD_NAME_PATH: &'static str = "/tmp/sysinfo/board_name";
+
+/// Identification of the detected hardware
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Detected {
+    pub control_board: Option<String>,
+}
+
+impl Detected {
+    /// Read identification of the hardware from the board name file in `board_name_path`
+    pub fn read<P: AsRef<Path>>(board_name_path: P) -> Self {
+        Self {
+            control_board: fs::read_to_string(board_name_path)
+                .ok()
+                .map(|name| {
+                    name.trim_matches(|c: char| c.is_whitespace() || c == '\0')
+                        .to_string()
+                })
+                .filter(|name| !name.is_empty()),
+        }
+    }
+}
+
+impl fmt::Display for Detected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.control_board {
+            Some(control_board) => write!(f, "control board '{}'", control_board),
+            None => write!(f, "control board not detected"),
+        }
+    }
+}
+
+/// Hardware specific defaults of the miner
+#[derive(Clone, Debug, PartialEq)]
+pub struct HardwareProfile {
+    pub name: &'static str,
+    /// Control board names this profile applies to
+    pub control_boards: &'static [&'static str],
+    /// Indices of hash chain connectors populated on the control board
+    pub hash_chains: &'static [usize],
+    /// I2C bus of hash chain voltage controllers
+    pub voltage_ctrl_i2c_bus: usize,
+    /// Default PLL frequency in MHz
+    pub frequency: f64,
+    /// Default hash chain voltage in Volts
+    pub voltage: f64,
+    pub fan_curve: FanCurve,
+    pub sensor_map: SensorMap,
+    pub min_fans: usize,
+}
+
+impl HardwareProfile {
+    fn matches(&self, detected: &Detected) -> bool {
+        detected
+            .control_board
+            .as_ref()
+            .map(|control_board| self.control_boards.contains(&control_board.as_str()))
+            .unwrap_or(false)
+    }
+}
+
+/// Temperature thresholds and fan speeds used by the fan controller
+#[derive(Clone, Debug, PartialEq)]
+pub struct FanCurve {
+    /// Temperature maintained by the fan controller in automatic mode
+    pub target_temp: f64,
+    pub hot_temp: f64,
+    pub dangerous_temp: f64,
+    /// Fan speeds the fan controller may use in automatic mode
+    pub speed_limits: monitor::FanSpeedLimits,
+}
+
+/// Location of hashboard temperature sensors
+#[derive(Clone, Debug, PartialEq)]
+pub struct SensorMap {
+    /// Address of the hashing chip with temperature sensor connected to its I2C bus
+    pub temp_chip: usize,
+    /// I2C addresses (8-bit) probed for a known temperature sensor
+    pub i2c_addresses: &'static [u8],
+}
+
+/// Profile of Antminer S9 with Braiins control board firmware
+pub const AM1_S9: HardwareProfile = HardwareProfile {
+    name: "am1-s9",
+    control_boards: &["am1-s9"],
+    hash_chains: &[6, 7, 8],
+    voltage_ctrl_i2c_bus: 0,
+    frequency: config::DEFAULT_FREQUENCY_MHZ,
+    voltage: config::DEFAULT_VOLTAGE_V,
+    fan_curve: FanCurve {
+        target_temp: config::DEFAULT_TARGET_TEMP_C,
+        hot_temp: config::DEFAULT_HOT_TEMP_C,
+        dangerous_temp: config::DEFAULT_DANGEROUS_TEMP_C,
+        speed_limits: monitor::FanSpeedLimits::DEFAULT,
+    },
+    sensor_map: SensorMap {
+        temp_chip: 61,
+        i2c_addresses: &[0x98, 0x9a, 0x9c],
+    },
+    min_fans: config::DEFAULT_MIN_FANS,
+};
+
+/// All built-in profiles
+pub const PROFILES: &[HardwareProfile] = &[AM1_S9];
+
+/// Profile used when no detection has been done
+pub const DEFAULT: &HardwareProfile = &AM1_S9;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// No built-in profile matches the detected hardware
+    UnknownHardware(Detected),
+    /// Profile requested by configuration doesn't exist
+    UnknownProfile(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownHardware(detected) => write!(f, "unknown hardware ({})", detected)?,
+            Self::UnknownProfile(name) => write!(f, "unknown hardware profile '{}'", name)?,
+        }
+        write!(
+            f,
+            ", supported profiles: {}",
+            PROFILES
+                .iter()
+                .map(|profile| profile.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Select built-in profile for the `detected` hardware
+pub fn select(detected: Detected) -> Result<&'static HardwareProfile, Error> {
+    PROFILES
+        .iter()
+        .find(|profile| profile.matches(&detected))
+        .ok_or(Error::UnknownHardware(detected))
+}
+
+/// Find built-in profile by its `name`
+pub fn find(name: &str) -> Result<&'static HardwareProfile, Error> {
+    PROFILES
+        .iter()
+        .find(|profile| profile.name == name)
+        .ok_or_else(|| Error::UnknownProfile(name.to_string()))
+}
+
+/// Detect hardware the miner is running on and select its profile
+pub fn detect() -> Result<&'static HardwareProfile, Error> {
+    select(Detected::read(DEFAULT_BOARD_NAME_PATH))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_select_profile() {
+        let detected = |control_board: Option<&str>| Detected {
+            control_board: control_board.map(str::to_string),
+        };
+
+        assert_eq!(select(detected(Some("am1-s9"))), Ok(&AM1_S9));
+
+        let error = select(detected(Some("dm1-g9"))).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown hardware (control board 'dm1-g9'), supported profiles: am1-s9"
+        );
+        assert_eq!(
+            select(detected(None)).unwrap_err().to_string(),
+            "unknown hardware (control board not detected), supported profiles: am1-s9"
+        );
+    }
+
+    #[test]
+    fn test_find_profile() {
+        assert_eq!(find("am1-s9"), Ok(&AM1_S9));
+        assert_eq!(
+            find("am2-s17").unwrap_err().to_string(),
+            "unknown hardware profile 'am2-s17', supported profiles: am1-s9"
+        );
+    }
+
+    #[test]
+    fn test_read_board_name() {
+        let path = std::env::temp_dir().join(format!("bosminer-board-{}", std::process::id()));
+        fs::write(&path, "am1-s9\n").expect("BUG: cannot write board name");
+        assert_eq!(
+            Detected::read(&path).control_board.as_deref(),
+            Some("am1-s9")
+        );
+        fs::remove_file(&path).expect("BUG: cannot remove board name");
+
+        assert_eq!(Detected::read(&path), Detected::default());
+    }
+}
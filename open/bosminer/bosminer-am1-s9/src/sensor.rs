@@ -44,7 +44,6 @@ use crate::i2c;
 
 use async_trait::async_trait;
 use ii_logging::macros::*;
-use lazy_static::lazy_static;
 use std::boxed::Box;
 
 /// Generic sensor
@@ -92,15 +91,6 @@ pub struct Temperature {
     pub remote: Measurement,
 }
 
-lazy_static! {
-    /// List of all known I2C address where sensors are present
-    static ref SENSOR_I2C_ADDRESS: [i2c::Address; 3] = [
-        i2c::Address::new(0x98),
-        i2c::Address::new(0x9a),
-        i2c::Address::new(0x9c),
-    ];
-}
-
 pub const INVALID_TEMPERATURE_READING: Temperature = Temperature {
     local: Measurement::InvalidReading,
     remote: Measurement::InvalidReading,
@@ -143,14 +133,19 @@ pub async fn probe_i2c_device(
     Ok(sensor)
 }
 
-/// Probe for known addresses for supported sensors
+/// Probe I2C `addresses` where sensors may be present (given by hardware profile) for supported
+/// sensors
 pub async fn probe_i2c_sensors<T: 'static + i2c::AsyncBus + Clone>(
     i2c_bus: T,
+    addresses: &[u8],
 ) -> error::Result<Option<Box<dyn Sensor>>> {
     // Go through all known addresses
-    for address in SENSOR_I2C_ADDRESS.iter() {
+    for address in addresses.iter() {
         // Construct device at given i2c address
-        let i2c_device = Box::new(i2c::Device::new(i2c_bus.clone(), *address));
+        let i2c_device = Box::new(i2c::Device::new(
+            i2c_bus.clone(),
+            i2c::Address::new(*address),
+        ));
 
         // Try to probe this device
         match probe_i2c_device(i2c_device).await? {
@@ -167,6 +162,7 @@ pub async fn probe_i2c_sensors<T: 'static + i2c::AsyncBus + Clone>(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::profile;
     use i2c::test_utils;
     use ii_async_compat::tokio;
 
@@ -181,7 +177,9 @@ mod test {
             Some(0xff),
         );
         let bus = i2c::SharedBus::new(bus);
-        let result = probe_i2c_sensors(bus).await.unwrap();
+        let result = probe_i2c_sensors(bus, profile::DEFAULT.sensor_map.i2c_addresses)
+            .await
+            .unwrap();
         result.is_some()
     }
 
@@ -25,6 +25,7 @@ use tokio::time::delay_for;
 
 use bosminer_am1_s9::gpio;
 use bosminer_am1_s9::power;
+use bosminer_am1_s9::profile;
 use bosminer_am1_s9::{Backend, ResetPin};
 
 use std::sync::Arc;
@@ -71,7 +72,8 @@ async fn test_voltage_ctrl_all_hashboards() {
     let expected_tested_hashboards: usize = 1;
 
     let gpio_mgr = gpio::ControlPinManager::new();
-    for hashboard_idx in Backend::detect_hashboards(&gpio_mgr).expect("failed to detect hashboards")
+    for hashboard_idx in Backend::detect_hashboards(&gpio_mgr, profile::DEFAULT.hash_chains)
+        .expect("failed to detect hashboards")
     {
         test_voltage_ctrl_on_1_hashboard(&gpio_mgr, hashboard_idx).await;
         tested_hashboards += 1;
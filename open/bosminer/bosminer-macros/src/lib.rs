@@ -209,12 +209,14 @@ fn impl_derive_mining_stats(ast: &DeriveInput, derive_name: &str) -> proc_macro2
         member_start_time,
         member_valid_jobs,
         member_invalid_jobs,
+        member_redundant_jobs,
         member_generated_work,
         member_last_share,
         member_best_share,
         member_accepted,
         member_rejected,
         member_stale,
+        member_share_buckets,
         member_valid_network_diff,
         member_valid_job_diff,
         member_valid_backend_diff,
@@ -239,10 +241,12 @@ fn impl_derive_client_stats(
     let fields = get_fields(&ast, derive_name);
     let valid_jobs = find_member(&fields, "member_valid_jobs");
     let invalid_jobs = find_member(&fields, "member_invalid_jobs");
+    let redundant_jobs = find_member(&fields, "member_redundant_jobs");
     let generated_work = find_member(&fields, "member_generated_work");
     let accepted = find_member(&fields, "member_accepted");
     let rejected = find_member(&fields, "member_rejected");
     let stale = find_member(&fields, "member_stale");
+    let share_buckets = find_member(&fields, "member_share_buckets");
 
     stream.extend(quote! {
         impl#generics stats::Client for #name#generics {
@@ -256,6 +260,11 @@ fn impl_derive_client_stats(
                 &self.#invalid_jobs
             }
 
+            #[inline]
+            fn redundant_jobs(&self) -> &stats::CounterUsize {
+                &self.#redundant_jobs
+            }
+
             #[inline]
             fn generated_work(&self) -> &stats::CounterU64 {
                 &self.#generated_work
@@ -275,6 +284,11 @@ fn impl_derive_client_stats(
             fn stale(&self) -> &stats::Meter {
                 &self.#stale
             }
+
+            #[inline]
+            fn share_buckets(&self) -> &stats::ShareBuckets {
+                &self.#share_buckets
+            }
         }
     });
     stream
@@ -0,0 +1,114 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Measures the latency between receiving a job and having the first work of its engine available
+//! to backends. The inline build (the original `EngineSender::broadcast_job` path) is compared
+//! with handing the job over to the engine builder task by `job::Sender`. Besides the total
+//! latency it reports for how long the protocol handler is blocked by sending the job.
+//!
+//! Run with `cargo bench -p bosminer --bench job_broadcast` on the target hardware, the numbers
+//! from a development machine are not representative.
+
+use ii_async_compat::tokio;
+
+use bosminer::job;
+use bosminer::test_utils;
+use bosminer::work::{self, Engine as _};
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Number of midstates generated for each work (version rolling with 4 versions)
+const MIDSTATE_COUNT: usize = 4;
+const ITERATIONS: usize = 2000;
+
+#[derive(Default)]
+struct Measurement {
+    /// Time spent in the protocol handler when sending the job
+    send: Duration,
+    /// Time between sending the job and taking the first work from the new engine
+    first_work: Duration,
+}
+
+impl Measurement {
+    fn report(&self, path: &str) {
+        let iterations = ITERATIONS as u32;
+        println!(
+            "{:<14} {:>10.2?}/job send {:>10.2?}/job to first work",
+            path,
+            self.send / iterations,
+            self.first_work / iterations,
+        );
+    }
+}
+
+fn engine_channel() -> (Arc<work::EngineSender>, work::EngineReceiver) {
+    let (engine_sender, engine_receiver) = work::engine_channel(work::IgnoreEvents);
+    let _ = engine_sender.replace_engine_generator(Box::new(move |job| {
+        Arc::new(work::engine::VersionRolling::new(job, MIDSTATE_COUNT))
+    }));
+    (Arc::new(engine_sender), engine_receiver)
+}
+
+/// Takes the first work from the current engine the same way as backends do
+async fn first_work(engine_receiver: &mut work::EngineReceiver) {
+    let engine = engine_receiver
+        .get_engine()
+        .await
+        .expect("BUG: missing work engine");
+    let work = engine.next_work().unwrap();
+    assert_eq!(work.midstates.len(), MIDSTATE_COUNT);
+}
+
+async fn measure_inline() -> Measurement {
+    let (engine_sender, mut engine_receiver) = engine_channel();
+    let mut measurement = Measurement::default();
+    for block in test_utils::TEST_BLOCKS.iter().cycle().take(ITERATIONS) {
+        let start = Instant::now();
+        engine_sender.broadcast_job(Arc::new(*block));
+        measurement.send += start.elapsed();
+        first_work(&mut engine_receiver).await;
+        measurement.first_work += start.elapsed();
+    }
+    measurement
+}
+
+async fn measure_builder() -> Measurement {
+    let (engine_sender, mut engine_receiver) = engine_channel();
+    let job_sender = job::Sender::new(engine_sender);
+    let mut measurement = Measurement::default();
+    for block in test_utils::TEST_BLOCKS.iter().cycle().take(ITERATIONS) {
+        let start = Instant::now();
+        job_sender.send(Arc::new(*block));
+        measurement.send += start.elapsed();
+        job_sender.flush().await;
+        first_work(&mut engine_receiver).await;
+        measurement.first_work += start.elapsed();
+    }
+    measurement
+}
+
+fn main() {
+    let mut runtime = tokio::runtime::Runtime::new().expect("BUG: cannot start runtime");
+    runtime.block_on(measure_inline()).report("inline");
+    runtime.block_on(measure_builder()).report("engine builder");
+}
@@ -29,5 +29,12 @@ use std::sync::Arc;
 
 pub async fn run(core: Arc<hub::Core>, config: hal::FrontendConfig, signature: String) {
     let addr = "0.0.0.0:4028".parse().unwrap();
-    cgminer::run(core, addr, config.cgminer_custom_commands, signature).await;
+    cgminer::run(
+        core,
+        addr,
+        config.cgminer_custom_commands,
+        config.halt_handler,
+        signature,
+    )
+    .await;
 }
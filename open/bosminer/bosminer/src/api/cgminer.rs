@@ -25,19 +25,31 @@
 
 use crate::client;
 use crate::error;
+use crate::hal;
 use crate::hub;
 use crate::node::{self, Stats as _, WorkSolver, WorkSolverStats as _};
+use crate::sharelog;
 use crate::stats::{self, UnixTime as _};
 use crate::sync;
 use crate::version;
 
-use ii_cgminer_api::support::ValueExt as _;
-use ii_cgminer_api::{command, json, response};
+use ii_cgminer_api::command::{POOLSTATS, SHARELOG};
+use ii_cgminer_api::support;
+use ii_cgminer_api::{command, commands, json, response};
+
+use ii_logging::macros::*;
+use ii_logging::{self, Level};
+
+use ii_async_compat::futures;
+
+use futures::lock::Mutex;
 
 use bosminer_config::{ClientDescriptor, ClientUserInfo};
 
 use std::future::Future;
 use std::net::SocketAddr;
+use std::os::unix::process::CommandExt as _;
+use std::process;
 use std::sync::Arc;
 use std::time;
 
@@ -47,16 +59,34 @@ use stats::TIME_MEAN_INTERVAL_24H as INTERVAL_24H;
 use stats::TIME_MEAN_INTERVAL_5M as INTERVAL_5M;
 use stats::TIME_MEAN_INTERVAL_5S as INTERVAL_5S;
 
-/// Default interval used for computation of default rolling average.
-const DEFAULT_LOG_INTERVAL: u32 = 5;
-
 struct Handler {
     core: Arc<hub::Core>,
+    debug_settings: Mutex<support::DebugSettings>,
 }
 
 impl Handler {
     pub fn new(core: Arc<hub::Core>) -> Self {
-        Self { core }
+        Self {
+            core,
+            debug_settings: Mutex::new(Default::default()),
+        }
+    }
+
+    /// Maps cgminer debug settings to the logging level overriding the configured one
+    fn get_log_level(settings: &support::DebugSettings) -> Option<Level> {
+        if settings.silent {
+            Some(Level::Critical)
+        } else if settings.rpc_proto {
+            Some(Level::Trace)
+        } else if settings.debug {
+            Some(Level::Debug)
+        } else if settings.verbose {
+            Some(Level::Info)
+        } else if settings.quiet {
+            Some(Level::Warning)
+        } else {
+            None
+        }
     }
 
     async fn collect_data<C, F, T, U, V>(&self, container: C, base_idx: usize, f: F) -> Vec<T>
@@ -281,7 +311,48 @@ impl Handler {
         .await
     }
 
-    async fn get_pool_stats(idx: usize, _client: Arc<client::Handle>) -> response::PoolStats {
+    async fn get_notify(idx: usize, work_solver: Arc<dyn node::WorkSolver>) -> response::Notify {
+        let last_work_time = work_solver
+            .work_solver_stats()
+            .last_work_time()
+            .take_snapshot()
+            .await;
+
+        response::Notify {
+            idx: idx as i32,
+            // TODO: get actual ASIC name from work solver
+            name: "".to_string(),
+            id: work_solver.get_id().unwrap_or(idx) as i32,
+            // The device is considered well as long as it takes work
+            last_well: last_work_time.map_or(0, |time| time.get_unix_time().unwrap_or_default()),
+            // TODO: BOSminer does not account the following information
+            last_not_well: 0,
+            reason_not_well: "None".to_string(),
+            thread_fail_init: 0,
+            thread_zero_hash: 0,
+            thread_fail_queue: 0,
+            dev_sick_idle_60s: 0,
+            dev_dead_idle_600s: 0,
+            dev_nostart: 0,
+            dev_over_heat: 0,
+            dev_thermal_cutoff: 0,
+            dev_comms_error: 0,
+            dev_throttle: 0,
+        }
+    }
+
+    async fn collect_notifies(&self) -> Vec<response::Notify> {
+        self.collect_data(
+            self.core.get_work_solvers(),
+            0,
+            |idx, work_solver| async move { Self::get_notify(idx, work_solver).await },
+        )
+        .await
+    }
+
+    async fn get_pool_stats(idx: usize, client: Arc<client::Handle>) -> response::PoolStats {
+        let redundant_jobs = client.stats().redundant_jobs().take_snapshot();
+
         response::PoolStats {
             header: response::StatsHeader {
                 idx: idx as i32,
@@ -313,6 +384,7 @@ impl Handler {
             bytes_recv: 0,
             net_bytes_sent: 0,
             net_bytes_recv: 0,
+            redundant_jobs: *redundant_jobs as u64,
         }
     }
 
@@ -337,6 +409,7 @@ impl Handler {
                 max: 0.0,
                 min: 0.0,
             },
+            chips: Default::default(),
         }
     }
 
@@ -370,20 +443,34 @@ impl Handler {
             .map(|client| (client, clients))
     }
 
-    fn get_client_descriptor(&self, parameter: &str) -> Result<ClientDescriptor, ()> {
-        let parameters: Vec<_> = parameter
-            .split(ii_cgminer_api::PARAMETER_DELIMITER)
-            .collect();
+    async fn check_work_solver(&self, idx: i32) -> Result<(), response::ErrorCode> {
+        let work_solvers = self.core.get_work_solvers().await;
+        if idx < 0 || idx as usize >= work_solvers.len() {
+            return Err(response::ErrorCode::InvalidAscId(
+                idx,
+                work_solvers.len() as i32 - 1,
+            ));
+        }
+        Ok(())
+    }
 
-        assert_eq!(
-            parameters.len(),
-            3,
-            "BUG: invalid number of ADDPOOL parameters"
-        );
+    /// Generic work solvers cannot be switched on and off. Backends supporting it override
+    /// `ascenable` and `ascdisable` commands with their own implementation.
+    async fn switch_work_solver(&self, idx: i32) -> Result<(), response::ErrorCode> {
+        self.check_work_solver(idx).await?;
+        Err(response::ErrorCode::AscSwitchErr(
+            idx,
+            "not supported by the backend".to_string(),
+        ))
+    }
 
-        let url = parameters[0];
-        let user = parameters[1];
-        let password = parameters[2];
+    fn get_client_descriptor(
+        &self,
+        parameter: &support::AddPoolParameter,
+    ) -> Result<ClientDescriptor, ()> {
+        let url = parameter.url.as_str();
+        let user = parameter.user.as_str();
+        let password = parameter.password.as_str();
 
         // URL and user name is required
         if url.is_empty() || user.is_empty() {
@@ -397,6 +484,77 @@ impl Handler {
 
         ClientDescriptor::create(url, &ClientUserInfo::new(user, password), true).map_err(|_| ())
     }
+
+    fn parse_share_log_parameter(parameter: &str) -> Option<bool> {
+        match parameter {
+            "enable" | "on" | "1" => Some(true),
+            "disable" | "off" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn check_share_log(_command: &str, parameter: &Option<&json::Value>) -> command::Result<()> {
+        match parameter {
+            None => Ok(()),
+            Some(json::Value::String(value))
+                if Self::parse_share_log_parameter(value.as_str()).is_some() =>
+            {
+                Ok(())
+            }
+            Some(value) => {
+                Err(response::ErrorCode::InvalidShareLogParameter(value.to_string()).into())
+            }
+        }
+    }
+
+    /// Report share log state and optionally enable or disable it when the parameter is present
+    async fn handle_share_log(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> command::Result<response::ext::ShareLog> {
+        if let Some(parameter) = parameter {
+            let enabled = parameter
+                .as_str()
+                .and_then(Self::parse_share_log_parameter)
+                .expect("BUG: invalid SHARELOG parameter");
+            sharelog::LOGGER
+                .set_enabled(enabled)
+                .map_err(|_| response::ErrorCode::ShareLogNotConfigured)?;
+        }
+
+        let status = sharelog::LOGGER.status();
+        Ok(response::ext::ShareLog {
+            file: status
+                .file
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+            size: status.size,
+            dropped: status.dropped,
+            enabled: status.enabled.into(),
+        })
+    }
+
+    /// Report accepted shares of all pools in reconciliation buckets
+    async fn handle_pool_stats(&self) -> command::Result<response::ext::PoolStats> {
+        let mut list = vec![];
+        for (idx, client) in self.get_clients().await.drain(..).enumerate() {
+            let client_descriptor = client.descriptor().await;
+            let url = client_descriptor.get_url(true, true, false);
+            let share_buckets = client.stats().share_buckets().take_snapshot().await;
+            list.extend(share_buckets.iter().map(|bucket| response::ext::PoolStat {
+                idx: idx as i32,
+                url: url.clone(),
+                user: client_descriptor.user.clone(),
+                bucket_start: bucket.start,
+                bucket_duration: stats::SHARE_BUCKET_DURATION.as_secs() as u32,
+                accepted: bucket.solutions,
+                difficulty_accepted: bucket.shares,
+                pool_difficulty_accepted: bucket.pool_shares,
+                delta: bucket.delta(),
+            }));
+        }
+        Ok(response::ext::PoolStats { list })
+    }
 }
 
 #[async_trait::async_trait]
@@ -407,14 +565,28 @@ impl command::Handler for Handler {
         })
     }
 
-    async fn handle_devs(&self) -> command::Result<response::Devs> {
-        Ok(response::Devs {
-            list: self.collect_asc_statuses().await,
-        })
+    async fn handle_devs(&self, idx: Option<i32>) -> command::Result<response::Devs> {
+        let list = match idx {
+            Some(idx) => {
+                let work_solvers = self.core.get_work_solvers().await;
+                match work_solvers.get(idx as usize).filter(|_| idx >= 0).cloned() {
+                    Some(work_solver) => {
+                        vec![Handler::get_asc_status(idx as usize, work_solver).await]
+                    }
+                    None => Err(response::ErrorCode::InvalidAscId(
+                        idx,
+                        work_solvers.len() as i32 - 1,
+                    ))?,
+                }
+            }
+            None => self.collect_asc_statuses().await,
+        };
+
+        Ok(response::Devs { list })
     }
 
     async fn handle_edevs(&self) -> command::Result<response::Devs> {
-        self.handle_devs().await
+        self.handle_devs(None).await
     }
 
     async fn handle_summary(&self) -> command::Result<response::Summary> {
@@ -541,80 +713,113 @@ impl command::Handler for Handler {
             pool_rejected_ratio: pools_rejected_ratio,
             pool_stale_ratio: pools_stale_ratio,
             last_getwork: last_work_time,
+            pool_dead_park: self
+                .core
+                .get_client_manager()
+                .dead_pools_status()
+                .is_parked()
+                .into(),
+            paused: self.core.get_client_manager().is_paused().await.into(),
+            extras: Default::default(),
         })
     }
 
     async fn handle_config(&self) -> command::Result<response::Config> {
+        let client_manager = self.core.get_client_manager();
+        let failover_only = client_manager.is_failover_only().await;
+        // Work is balanced between groups while the clients in a group fail over
+        let strategy = if failover_only || client_manager.get_groups().await.len() <= 1 {
+            response::MultipoolStrategy::Failover
+        } else {
+            response::MultipoolStrategy::LoadBalance
+        };
+
         Ok(response::Config {
             asc_count: self.core.get_work_solvers().await.len() as i32,
             pga_count: 0,
             pool_count: self.get_clients().await.len() as i32,
-            // TODO: get actual multi-pool strategy
-            strategy: response::MultipoolStrategy::Failover,
-            log_interval: DEFAULT_LOG_INTERVAL as i32,
+            strategy,
+            failover_only,
+            log_interval: self.core.get_log_interval().as_secs() as i32,
             device_code: String::new(),
             // TODO: detect underlying operation system
             os: "Braiins OS".to_string(),
             hotplug: "None".to_string(),
+            hw_profile: self
+                .core
+                .backend_info
+                .as_ref()
+                .and_then(|info| info.hw_profile.clone()),
+            extras: Default::default(),
         })
     }
 
-    async fn handle_enable_pool(
+    async fn handle_set_config(
         &self,
-        parameter: Option<&json::Value>,
-    ) -> command::Result<response::EnablePool> {
-        let idx = parameter
-            .expect("BUG: missing ENABLEPOOL parameter")
-            .to_i32()
-            .expect("BUG: invalid ENABLEPOOL parameter type");
+        name: String,
+        value: String,
+    ) -> command::Result<response::SetConfig> {
+        // The log interval is the only option which can be changed in runtime
+        if name != "log" {
+            return Err(response::ErrorCode::UnknownConfig(name).into());
+        }
+        // The statistics are logged with the interval in seconds which has to be non-zero
+        let log_interval = value
+            .parse::<u32>()
+            .ok()
+            .filter(|&log_interval| log_interval > 0);
+        match log_interval {
+            Some(log_interval) => {
+                self.core
+                    .set_log_interval(time::Duration::from_secs(log_interval.into()));
+                Ok(response::SetConfig {
+                    name,
+                    value: log_interval.to_string(),
+                })
+            }
+            None => Err(response::ErrorCode::InvalidConfigValue(name, value))?,
+        }
+    }
+
+    async fn handle_enable_pool(&self, idx: i32) -> command::Result<response::EnablePool> {
         let (client, _) = self.get_client(idx).await?;
         let client_descriptor = client.descriptor().await;
         let url = client_descriptor.get_url(true, true, false);
 
-        client
-            .try_enable()
-            .map_err(|_| response::InfoCode::PoolAlreadyEnabled(idx, url.clone()))?;
-
-        Ok(response::EnablePool {
-            idx: idx as usize,
-            url,
+        let idx = idx as usize;
+        Ok(match client.try_enable() {
+            Ok(_) => response::EnablePool::Enabled { idx, url },
+            Err(_) => response::EnablePool::AlreadyEnabled { idx, url },
         })
     }
 
-    async fn handle_disable_pool(
-        &self,
-        parameter: Option<&json::Value>,
-    ) -> command::Result<response::DisablePool> {
-        let idx = parameter
-            .expect("BUG: missing DISABLEPOOL parameter")
-            .to_i32()
-            .expect("BUG: invalid DISABLEPOOL parameter type");
-        let (client, _) = self.get_client(idx).await?;
+    async fn handle_disable_pool(&self, idx: i32) -> command::Result<response::DisablePool> {
+        let (client, clients) = self.get_client(idx).await?;
         let client_descriptor = client.descriptor().await;
         let url = client_descriptor.get_url(true, true, false);
 
-        client
-            .try_disable()
-            .map_err(|_| response::InfoCode::PoolAlreadyDisabled(idx, url.clone()))?;
-
-        Ok(response::DisablePool {
-            idx: idx as usize,
-            url,
+        if client.is_enabled()
+            && clients
+                .iter()
+                .all(|other| other == &client || !other.is_enabled())
+        {
+            return Err(response::ErrorCode::DisableLastPool(idx, url).into());
+        }
+        let idx = idx as usize;
+        Ok(match client.try_disable() {
+            Ok(_) => response::DisablePool::Disabled { idx, url },
+            Err(_) => response::DisablePool::AlreadyDisabled { idx, url },
         })
     }
 
     async fn handle_add_pool(
         &self,
-        parameter: Option<&json::Value>,
+        parameter: support::AddPoolParameter,
     ) -> command::Result<response::AddPool> {
-        let parameter = parameter
-            .expect("BUG: missing ADDPOOL parameter")
-            .as_str()
-            .expect("BUG: invalid ADDPOOL parameter type");
-
+        // The password must not be leaked to the response
         let client_descriptor = self
-            .get_client_descriptor(parameter)
-            .map_err(|_| response::ErrorCode::InvalidAddPoolDetails(parameter.to_string()))?;
+            .get_client_descriptor(&parameter)
+            .map_err(|_| response::ErrorCode::InvalidAddPoolDetails(parameter.redacted()))?;
 
         let group = self
             .core
@@ -643,47 +848,42 @@ impl command::Handler for Handler {
         })
     }
 
-    async fn handle_remove_pool(
-        &self,
-        parameter: Option<&json::Value>,
-    ) -> command::Result<response::RemovePool> {
-        let idx = parameter
-            .expect("BUG: missing REMOVEPOOL parameter")
-            .to_i32()
-            .expect("BUG: invalid REMOVEPOOL parameter type");
-
-        let client = match self.core.get_client_manager().get_default_group().await {
-            Some(group) => {
-                let client_len = group.len().await;
-                group
-                    .remove_client_at(idx as usize)
-                    .await
-                    .map_err(|e| match e {
-                        error::Client::Missing => {
-                            response::ErrorCode::InvalidPoolId(idx, client_len as i32 - 1)
-                        }
-                        _ => panic!("BUG: unexpected remove client error"),
-                    })?
-            }
+    async fn handle_remove_pool(&self, idx: i32) -> command::Result<response::RemovePool> {
+        let group = match self.core.get_client_manager().get_default_group().await {
+            Some(group) => group,
             None => Err(response::ErrorCode::InvalidPoolId(idx, -1))?,
         };
-        let client_descriptor = client.descriptor().await;
+
+        let clients = group.get_clients().await;
+        let client = clients
+            .get(idx as usize)
+            .ok_or_else(|| response::ErrorCode::InvalidPoolId(idx, clients.len() as i32 - 1))?;
+        let url = client.descriptor().await.get_url(true, true, false);
+        if clients.len() == 1 {
+            return Err(response::ErrorCode::RemoveLastPool(idx, url).into());
+        }
+        // The scheduler mines on the first running client of the group
+        if clients.iter().position(|client| client.is_running()) == Some(idx as usize) {
+            return Err(response::ErrorCode::RemoveActivePool(idx, url).into());
+        }
+
+        group
+            .remove_client_at(idx as usize)
+            .await
+            .map_err(|e| match e {
+                error::Client::Missing => {
+                    response::ErrorCode::InvalidPoolId(idx, clients.len() as i32 - 2)
+                }
+                _ => panic!("BUG: unexpected remove client error"),
+            })?;
 
         Ok(response::RemovePool {
             idx: idx as usize,
-            url: client_descriptor.get_url(true, true, false),
+            url,
         })
     }
 
-    async fn handle_switch_pool(
-        &self,
-        parameter: Option<&json::Value>,
-    ) -> command::Result<response::SwitchPool> {
-        let idx = parameter
-            .expect("BUG: missing SWITCHPOOL parameter")
-            .to_i32()
-            .expect("BUG: invalid SWITCHPOOL parameter type");
-
+    async fn handle_switch_pool(&self, idx: i32) -> command::Result<response::SwitchPool> {
         let client = match self.core.get_client_manager().get_default_group().await {
             Some(group) => {
                 let client_len = group.len().await;
@@ -707,20 +907,155 @@ impl command::Handler for Handler {
         })
     }
 
-    async fn handle_stats(&self) -> command::Result<response::Stats> {
+    async fn handle_pool_priority(
+        &self,
+        order: Vec<usize>,
+    ) -> command::Result<response::PoolPriority> {
+        let group = match self.core.get_client_manager().get_default_group().await {
+            Some(group) => group,
+            None => Err(response::ErrorCode::InvalidPoolId(
+                order.first().cloned().unwrap_or_default() as i32,
+                -1,
+            ))?,
+        };
+
+        let client_len = group.len().await;
+        if let Some(idx) = order.iter().find(|idx| **idx >= client_len) {
+            Err(response::ErrorCode::InvalidPoolId(
+                *idx as i32,
+                client_len as i32 - 1,
+            ))?;
+        }
+        group.reorder_clients(&order).await.map_err(|e| match e {
+            error::Client::Missing => {
+                response::ErrorCode::InvalidPoolId(order[0] as i32, client_len as i32 - 1)
+            }
+            _ => panic!("BUG: unexpected reorder clients error"),
+        })?;
+
+        Ok(response::PoolPriority)
+    }
+
+    async fn handle_failover_only(&self, enabled: bool) -> command::Result<response::FailoverOnly> {
+        self.core
+            .get_client_manager()
+            .set_failover_only(enabled)
+            .await;
+        Ok(response::FailoverOnly { enabled })
+    }
+
+    async fn handle_pause(&self) -> command::Result<response::Pause> {
+        if !self.core.get_client_manager().set_paused(true).await {
+            return Ok(response::Pause::AlreadyPaused);
+        }
+        info!("Mining paused by API");
+        Ok(response::Pause::Paused)
+    }
+
+    async fn handle_resume(&self) -> command::Result<response::Resume> {
+        if !self.core.get_client_manager().set_paused(false).await {
+            return Ok(response::Resume::NotPaused);
+        }
+        info!("Mining resumed by API");
+        if !self
+            .get_clients()
+            .await
+            .iter()
+            .any(|client| client.is_enabled())
+        {
+            warn!("Mining resumed by API without any enabled pool");
+            return Ok(response::Resume::ResumedWithoutPool);
+        }
+        Ok(response::Resume::Resumed)
+    }
+
+    async fn handle_locate(
+        &self,
+        setting: Option<support::LocateSetting>,
+    ) -> command::Result<response::Locate> {
+        // Backends with a controllable LED override `locate` command with their own
+        // implementation
+        if setting.is_some() {
+            Err(response::ErrorCode::LocateErr(
+                "not supported by the backend".to_string(),
+            ))?;
+        }
+        Ok(response::Locate {
+            active: response::Bool::N,
+            remaining: None,
+        })
+    }
+
+    async fn handle_zero(
+        &self,
+        target: support::ZeroTarget,
+        return_summary: bool,
+    ) -> command::Result<response::Zero> {
+        // The summary describes the statistics before they are zeroed
+        let summary = if return_summary {
+            Some(self.handle_summary().await?)
+        } else {
+            None
+        };
+
+        let frontend_stats = self.core.frontend.mining_stats();
+        let clients = self.get_clients().await;
+        let work_solvers = self.core.get_work_solvers().await;
+        match target {
+            support::ZeroTarget::All => {
+                stats::zero_mining(frontend_stats).await;
+                for client in clients {
+                    stats::zero_client(client.stats()).await;
+                }
+                for work_solver in work_solvers {
+                    stats::zero_mining(work_solver.mining_stats()).await;
+                }
+            }
+            support::ZeroTarget::BestShare => {
+                frontend_stats.best_share().reset();
+                for client in clients {
+                    client.stats().best_share().reset();
+                }
+                for work_solver in work_solvers {
+                    work_solver.mining_stats().best_share().reset();
+                }
+            }
+        }
+
+        Ok(response::Zero { target, summary })
+    }
+
+    async fn handle_notify(&self) -> command::Result<response::Notifies> {
+        Ok(response::Notifies {
+            list: self.collect_notifies().await,
+        })
+    }
+
+    async fn handle_stats(
+        &self,
+        filter: Option<support::StatsFilter>,
+        // Per-chip details are not collected by the miner yet
+        _chips: bool,
+    ) -> command::Result<response::Stats> {
         let asc_stats = self.collect_asc_stats(0).await;
         let pool_stats = self.collect_pool_stats(asc_stats.len()).await;
-        Ok(response::Stats {
+        response::Stats {
             asc_stats,
             pool_stats,
-        })
+        }
+        .select(filter)
     }
 
-    async fn handle_estats(&self) -> command::Result<response::Stats> {
-        Ok(response::Stats {
+    async fn handle_estats(
+        &self,
+        filter: Option<support::StatsFilter>,
+        _chips: bool,
+    ) -> command::Result<response::Stats> {
+        response::Stats {
             asc_stats: self.collect_asc_stats(0).await,
             pool_stats: vec![],
-        })
+        }
+        .select(filter)
     }
 
     async fn handle_coin(&self) -> command::Result<response::Coin> {
@@ -742,57 +1077,187 @@ impl command::Handler for Handler {
         })
     }
 
-    async fn handle_asc(&self, parameter: Option<&json::Value>) -> command::Result<response::Asc> {
-        let idx = parameter
-            .expect("BUG: missing ASC parameter")
-            .to_i32()
-            .expect("BUG: invalid ASC parameter type");
+    async fn handle_asc(&self, idx: Option<i32>) -> command::Result<response::Ascs> {
+        // Devices are selected the same way as for DEVS
+        let list = self.handle_devs(idx).await?.list;
+        Ok(response::Ascs { list })
+    }
 
-        let work_solvers = self.core.get_work_solvers().await;
-        let work_solver = work_solvers.get(idx as usize).cloned();
+    async fn handle_asc_enable(&self, idx: i32) -> command::Result<response::AscEnable> {
+        self.switch_work_solver(idx).await?;
+        Ok(response::AscEnable::Enabled { idx })
+    }
 
-        match work_solver {
-            Some(work_solver) => Ok(Self::get_asc_status(idx as usize, work_solver).await),
-            None => {
-                Err(response::ErrorCode::InvalidAscId(idx, work_solvers.len() as i32 - 1).into())
-            }
+    async fn handle_asc_disable(&self, idx: i32) -> command::Result<response::AscDisable> {
+        self.switch_work_solver(idx).await?;
+        Ok(response::AscDisable::Disabled { idx })
+    }
+
+    async fn handle_asc_set(
+        &self,
+        parameter: support::AscSetParameter,
+    ) -> command::Result<response::AscSet> {
+        // Tunable backends override `ascset` command with their own implementation
+        self.check_work_solver(parameter.idx).await?;
+        Err(response::ErrorCode::AscSetErr(
+            parameter.idx,
+            "not supported by the backend".to_string(),
+        ))?
+    }
+
+    async fn handle_debug(
+        &self,
+        flag: Option<support::DebugFlag>,
+    ) -> command::Result<response::Debug> {
+        let mut settings = self.debug_settings.lock().await;
+        if let Some(flag) = flag {
+            settings.apply(flag);
+            // TODO: per device statistics and work time are not logged by BOSminer
+            ii_logging::set_level_override(Handler::get_log_level(&settings));
         }
+        Ok((*settings).into())
     }
 
-    async fn handle_lcd(&self) -> command::Result<response::Lcd> {
-        // TODO: implement response
-        Ok(response::Lcd {
-            elapsed: 0,
-            ghs_av: 0.0,
-            ghs_5m: 0.0,
-            ghs_5s: 0.0,
-            temperature: 0.0,
-            last_share_difficulty: 0.0,
-            last_share_time: 0,
-            best_share: 0,
-            last_valid_work: 0,
-            found_blocks: 0,
-            current_pool: "".to_string(),
-            user: "".to_string(),
+    async fn handle_tuner_status(&self) -> command::Result<response::TunerStatus> {
+        // TODO: BOSminer does not have any autotuner (backends may provide their own status)
+        Ok(response::TunerStatus {
+            power_limit: 0,
+            dynamic_power_scaling: response::Bool::N,
+            chains: vec![],
         })
     }
 }
 
+/// Terminates BOSminer after `quit` or `restart` command has been acknowledged to the client.
+/// The backend is halted first when it provides `halt_handler`.
+async fn shutdown(kind: command::ShutdownKind, halt_handler: Option<Arc<hal::HaltHandler>>) {
+    if let Some(halt_handler) = halt_handler {
+        info!("CGMiner API: halting the backend");
+        halt_handler().await;
+    }
+    match kind {
+        command::ShutdownKind::Quit => {
+            info!("CGMiner API: quitting on request");
+            process::exit(0);
+        }
+        command::ShutdownKind::Restart => {
+            info!("CGMiner API: restarting on request");
+            // Replace current process with a new instance started with the same arguments
+            let error = std::env::current_exe()
+                .map(|program| {
+                    process::Command::new(program)
+                        .args(std::env::args_os().skip(1))
+                        .exec()
+                })
+                .unwrap_or_else(|error| error);
+            error!("CGMiner API: cannot restart BOSminer ({})", error);
+            process::exit(1);
+        }
+    }
+}
+
 pub async fn run(
     core: Arc<hub::Core>,
     listen_addr: SocketAddr,
     custom_commands: Option<command::Map>,
+    halt_handler: Option<hal::HaltHandler>,
     signature: String,
 ) {
+    // BOSminer specific commands available for all backends
+    let handler = Arc::new(Handler::new(core.clone()));
+    let check_share_log: command::ParameterCheckHandler =
+        Box::new(|command, parameter| Handler::check_share_log(command, parameter));
+    let mut commands = commands![
+        (POOLSTATS: ParameterLess -> handler.handle_pool_stats, "Accepted shares of pools per hour")
+    ];
+    // Reporting the state of share log is allowed to anybody while enabling or disabling it
+    // requires privileged access
+    commands.insert(
+        SHARELOG,
+        command!(SHARELOG: Parameter(check_share_log) -> handler.handle_share_log)
+            .description("Show or control share log")
+            .privileged_parameter(),
+    );
+    if let Some(custom_commands) = custom_commands {
+        commands.extend(custom_commands.into_iter());
+    }
+
     let handler = Handler::new(core);
+    let halt_handler = halt_handler.map(Arc::new);
     let command_receiver = command::Receiver::new(
         handler,
         signature,
         version::STRING.to_string(),
-        custom_commands,
-    );
+        commands,
+    )
+    .with_shutdown_handler(Box::new(move |kind| {
+        Box::pin(shutdown(kind, halt_handler.clone()))
+    }));
 
     ii_cgminer_api::run(command_receiver, listen_addr)
         .await
         .unwrap();
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::backend;
+
+    use ii_async_compat::tokio;
+    use ii_cgminer_api::command::Handler as _;
+
+    /// Pool details of refused `addpool` command are reported without the password
+    #[tokio::test]
+    async fn test_add_pool_hides_password() {
+        let backend_registry = Arc::new(backend::Registry::new());
+        let handler = Handler::new(Arc::new(hub::Core::new(1, &backend_registry, None)));
+
+        for (url, user) in &[("stratum+tcp://pool:3333", ""), ("invalid url", "user")] {
+            let result = handler
+                .handle_add_pool(support::AddPoolParameter {
+                    url: url.to_string(),
+                    user: user.to_string(),
+                    password: "secret".to_string(),
+                })
+                .await;
+            match result {
+                Ok(_) => panic!("BUG: invalid pool accepted"),
+                Err(error) => assert_eq!(
+                    error.msg(),
+                    &format!("Invalid addpool details '{},{},***'", url, user)
+                ),
+            }
+        }
+    }
+
+    /// The log interval set by `setconfig` is passed on to the logging of mining statistics
+    #[tokio::test]
+    async fn test_set_log_interval() {
+        let backend_registry = Arc::new(backend::Registry::new());
+        let core = Arc::new(hub::Core::new(1, &backend_registry, None));
+        let handler = Handler::new(core.clone());
+        let mut log_interval_receiver = core.subscribe_to_log_interval();
+        assert_eq!(
+            log_interval_receiver.recv().await,
+            Some(hub::DEFAULT_LOG_INTERVAL)
+        );
+
+        for value in &["0", "-1", "fast"] {
+            let result = handler
+                .handle_set_config("log".to_string(), value.to_string())
+                .await;
+            assert!(result.is_err(), "BUG: invalid log interval accepted");
+        }
+        let result = handler
+            .handle_set_config("log".to_string(), "10".to_string())
+            .await;
+        assert!(result.is_ok(), "BUG: valid log interval refused");
+        assert_eq!(
+            log_interval_receiver.recv().await,
+            Some(time::Duration::from_secs(10))
+        );
+        let config = handler.handle_config().await;
+        assert_eq!(config.map(|config| config.log_interval).ok(), Some(10));
+    }
+}
@@ -23,6 +23,7 @@
 //! This module contains common functionality related to mining protocol client and allows
 //! executing a specific type of mining protocol client instance.
 
+pub mod dead_pools;
 mod scheduler;
 
 // Sub-modules with client implementation
@@ -30,6 +31,8 @@ pub mod drain;
 pub mod stratum_v2;
 pub mod stratum_v2_channels;
 
+use ii_logging::macros::*;
+
 use crate::error;
 use crate::hal;
 use crate::job;
@@ -48,11 +51,13 @@ use bosminer_config::{
 
 use futures::channel::mpsc;
 use futures::lock::Mutex;
-use ii_async_compat::futures;
+use ii_async_compat::prelude::*;
+use ii_async_compat::{futures, tokio};
+use tokio::sync::{broadcast, watch};
 
 use std::slice;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex};
 
 #[derive(Debug)]
 pub struct Handle {
@@ -62,6 +67,9 @@ pub struct Handle {
     enabled: AtomicBool,
     engine_sender: Arc<work::EngineSender>,
     solution_sender: mpsc::UnboundedSender<work::Solution>,
+    /// Requests of the remote server to switch the endpoint of the client, they are handled once
+    /// the client is pushed to a group (see `handle_reconnect_requests`)
+    reconnect_receiver: StdMutex<Option<mpsc::Receiver<stratum_v2::ReconnectRequest>>>,
 }
 
 impl Handle {
@@ -74,12 +82,34 @@ impl Handle {
             stratum_v2::ExtensionChannelToStratumReceiver,
             stratum_v2::ExtensionChannelFromStratumSender,
         )>,
+    ) -> Self {
+        Self::build(descriptor, backend_info, channel, None)
+    }
+
+    /// Builds a stratum V2 client which opens its connections with the `connector`
+    #[cfg(test)]
+    pub(crate) fn with_connector(
+        descriptor: ClientDescriptor,
+        connector: stratum_v2::Connector,
+    ) -> Self {
+        Self::build(descriptor, None, None, Some(connector))
+    }
+
+    fn build(
+        descriptor: ClientDescriptor,
+        backend_info: Option<hal::BackendInfo>,
+        channel: Option<(
+            stratum_v2::ExtensionChannelToStratumReceiver,
+            stratum_v2::ExtensionChannelFromStratumSender,
+        )>,
+        connector: Option<stratum_v2::Connector>,
     ) -> Self {
         let (solution_sender, solution_receiver) = mpsc::unbounded();
         // Initially register new client without ability to send work
         let engine_sender = Arc::new(work::EngineSender::new(None));
 
         let job_solver = job::Solver::new(engine_sender.clone(), solution_receiver);
+        let mut reconnect_receiver = None;
         let node: Arc<dyn node::Client> = match &descriptor.protocol {
             ClientProtocol::Drain => {
                 assert!(
@@ -98,18 +128,19 @@ impl Handle {
                     job_solver,
                 ))
             }
-            ClientProtocol::StratumV2(_) => Arc::new(stratum_v2::StratumClient::new(
-                stratum_v2::ConnectionDetails::from_descriptor(&descriptor),
-                backend_info,
-                job_solver,
-                channel,
-            )),
-            ClientProtocol::StratumV2Insecure => Arc::new(stratum_v2::StratumClient::new(
-                stratum_v2::ConnectionDetails::from_descriptor(&descriptor),
-                backend_info,
-                job_solver,
-                channel,
-            )),
+            ClientProtocol::StratumV2(_) | ClientProtocol::StratumV2Insecure => {
+                let mut client = stratum_v2::StratumClient::new(
+                    stratum_v2::ConnectionDetails::from_descriptor(&descriptor),
+                    backend_info,
+                    job_solver,
+                    channel,
+                );
+                if let Some(connector) = connector {
+                    client = client.with_connector(connector);
+                }
+                reconnect_receiver = client.take_reconnect_receiver();
+                Arc::new(client)
+            }
         };
 
         Self {
@@ -118,6 +149,46 @@ impl Handle {
             enabled: AtomicBool::new(false),
             engine_sender,
             solution_sender,
+            reconnect_receiver: StdMutex::new(reconnect_receiver),
+        }
+    }
+
+    /// Starts a task that switches the client to the endpoints requested by the remote server.
+    /// The task finishes together with the client.
+    fn handle_reconnect_requests(self: &Arc<Self>) {
+        let reconnect_receiver = self
+            .reconnect_receiver
+            .lock()
+            .expect("BUG: cannot lock reconnect receiver")
+            .take();
+        if let Some(mut reconnect_receiver) = reconnect_receiver {
+            let client_handle = Arc::downgrade(self);
+            tokio::spawn(async move {
+                while let Some(request) = reconnect_receiver.next().await {
+                    match client_handle.upgrade() {
+                        Some(client_handle) => client_handle.reconnect(request).await,
+                        None => break,
+                    }
+                }
+            });
+        }
+    }
+
+    /// Changes the endpoint of the client and restarts it when it is enabled. The current
+    /// connection is closed right away even when the server asks to keep it because the client
+    /// maintains only a single connection.
+    async fn reconnect(&self, request: stratum_v2::ReconnectRequest) {
+        let mut descriptor = self.descriptor().await;
+        descriptor.host = request.host;
+        descriptor.port = Some(request.port);
+        info!(
+            "Client: switching to {} as requested by the remote server",
+            descriptor.get_url(true, true, false)
+        );
+        self.change_descriptor(descriptor).await;
+        if self.is_enabled() {
+            self.stop();
+            self.start();
         }
     }
 
@@ -319,6 +390,7 @@ impl Group {
         client_handle.set_event_sender(self.event_sender.clone());
 
         let client_handle = Arc::new(client_handle);
+        client_handle.handle_reconnect_requests();
         let scheduler_client_handle = scheduler::ClientHandle::new(client_handle.clone());
         self.scheduler_client_handles
             .lock()
@@ -394,6 +466,36 @@ impl Group {
         Ok(client_handle)
     }
 
+    /// Moves clients at `order` indices to the front of the group in the given order. The rest of
+    /// the clients keeps its relative order behind them.
+    pub async fn reorder_clients(&self, order: &[usize]) -> Result<(), error::Client> {
+        let mut scheduler_client_handles = self.scheduler_client_handles.lock().await;
+        if order
+            .iter()
+            .any(|index| *index >= scheduler_client_handles.len())
+        {
+            return Err(error::Client::Missing);
+        }
+
+        let mut reordered_handles = Vec::with_capacity(scheduler_client_handles.len());
+        for index in order {
+            let scheduler_client_handle = &scheduler_client_handles[*index];
+            if !reordered_handles.contains(scheduler_client_handle) {
+                reordered_handles.push(scheduler_client_handle.clone());
+            }
+        }
+        for scheduler_client_handle in scheduler_client_handles.iter() {
+            if !reordered_handles.contains(scheduler_client_handle) {
+                reordered_handles.push(scheduler_client_handle.clone());
+            }
+        }
+        *scheduler_client_handles = reordered_handles;
+        // Immediately notify about new order of clients in the group
+        self.event_sender.notify();
+
+        Ok(())
+    }
+
     async fn find_client(&self, solution: &work::Solution) -> Option<Arc<Handle>> {
         self.scheduler_client_handles
             .lock()
@@ -415,6 +517,8 @@ pub struct GroupRegistry {
     total_quota: usize,
     fixed_share_ratio_count: usize,
     total_fixed_share_ratio: f64,
+    failover_only: bool,
+    paused: bool,
 }
 
 impl GroupRegistry {
@@ -425,6 +529,8 @@ impl GroupRegistry {
             total_quota: 0,
             fixed_share_ratio_count: 0,
             total_fixed_share_ratio: 0.0,
+            failover_only: false,
+            paused: false,
         }
     }
 
@@ -438,6 +544,40 @@ impl GroupRegistry {
         self.list.is_empty()
     }
 
+    #[inline]
+    pub fn is_failover_only(&self) -> bool {
+        self.failover_only
+    }
+
+    /// In failover-only mode all work is generated by the first group with an active client
+    /// and the other groups are used only as a backup
+    pub fn set_failover_only(&mut self, enabled: bool) {
+        if self.failover_only && !enabled {
+            // Work generated in failover-only mode must not be compensated by load balancing
+            for scheduler_group_handle in self.list.iter_mut() {
+                scheduler_group_handle.reset_generated_work();
+            }
+        }
+        self.failover_only = enabled;
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// No work is generated while paused. Returns `false` when the registry is already in the
+    /// requested state.
+    pub fn set_paused(&mut self, paused: bool) -> bool {
+        if self.paused == paused {
+            return false;
+        }
+        self.paused = paused;
+        // Let the scheduler apply the change immediately
+        self.event_monitor.publish().notify();
+        true
+    }
+
     #[inline]
     fn iter(&self) -> slice::Iter<scheduler::GroupHandle> {
         self.list.iter()
@@ -548,18 +688,50 @@ pub struct Manager {
     group_registry: Arc<Mutex<GroupRegistry>>,
     event_monitor: event::Monitor,
     midstate_count: usize,
+    /// Sender is taken by the monitor of dead pools once it is started
+    dead_pools_sender: Arc<StdMutex<Option<watch::Sender<dead_pools::Status>>>>,
+    dead_pools_receiver: watch::Receiver<dead_pools::Status>,
+    dead_pools_events: broadcast::Sender<dead_pools::Event>,
 }
 
 impl Manager {
     pub fn new(midstate_count: usize) -> Self {
         let event_monitor = event::Monitor::new();
+        let (dead_pools_sender, dead_pools_receiver) = watch::channel(Default::default());
+        // Throwaway the receiver, subscribers are created by `subscribe_to_dead_pools_events()`
+        let (dead_pools_events, _) = broadcast::channel(4);
         Self {
             group_registry: Arc::new(Mutex::new(GroupRegistry::new(event_monitor.clone()))),
             event_monitor,
             midstate_count,
+            dead_pools_sender: Arc::new(StdMutex::new(Some(dead_pools_sender))),
+            dead_pools_receiver,
+            dead_pools_events,
         }
     }
 
+    /// Run monitor of dead pools which broadcasts status to all subscribers
+    pub async fn monitor_dead_pools(self, config: dead_pools::Config) {
+        let status_sender = self
+            .dead_pools_sender
+            .lock()
+            .expect("BUG: cannot lock dead pools sender")
+            .take()
+            .expect("BUG: dead pools monitor has already been started");
+        let event_sender = self.dead_pools_events.clone();
+        dead_pools::run_monitor(self, config, status_sender, event_sender).await;
+    }
+
+    #[inline]
+    pub fn subscribe_to_dead_pools_events(&self) -> broadcast::Receiver<dead_pools::Event> {
+        self.dead_pools_events.subscribe()
+    }
+
+    #[inline]
+    pub fn dead_pools_status(&self) -> dead_pools::Status {
+        *self.dead_pools_receiver.borrow()
+    }
+
     pub async fn load_config<T>(
         &self,
         group_configs: T,
@@ -630,4 +802,24 @@ impl Manager {
     pub async fn get_groups(&self) -> Vec<Arc<Group>> {
         self.group_registry.lock().await.get_groups()
     }
+
+    #[inline]
+    pub async fn is_failover_only(&self) -> bool {
+        self.group_registry.lock().await.is_failover_only()
+    }
+
+    #[inline]
+    pub async fn set_failover_only(&self, enabled: bool) {
+        self.group_registry.lock().await.set_failover_only(enabled)
+    }
+
+    #[inline]
+    pub async fn is_paused(&self) -> bool {
+        self.group_registry.lock().await.is_paused()
+    }
+
+    #[inline]
+    pub async fn set_paused(&self, paused: bool) -> bool {
+        self.group_registry.lock().await.set_paused(paused)
+    }
 }
@@ -0,0 +1,332 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Detection of the state when none of the configured pools is alive
+//!
+//! The monitor periodically checks whether at least one pool is running. When all pools stay
+//! dead for longer than the grace period, the state is broadcast to subscribers and an event is
+//! emitted for backends which apply the configured policy. The park policy also pauses generation
+//! of work the same way as the `pause` API command does. Everything is reverted on the first pool
+//! recovery.
+
+use ii_logging::macros::*;
+
+use crate::client;
+
+use async_trait::async_trait;
+use ii_async_compat::tokio;
+use tokio::sync::{broadcast, watch};
+use tokio::time::delay_for;
+
+use std::time::{Duration, Instant};
+
+/// Default time of all pools being dead before the policy is applied
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(60);
+/// Default interval of checking pools state
+pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Policy {
+    /// Park hash chains at a low-power state and resume them when any pool recovers
+    Park,
+    /// Keep hash chains warm for the fastest resume
+    Spin,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub policy: Policy,
+    pub grace_period: Duration,
+    pub check_interval: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            policy: Policy::Spin,
+            grace_period: DEFAULT_GRACE_PERIOD,
+            check_interval: DEFAULT_CHECK_INTERVAL,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Status {
+    /// All pools have been dead for longer than the grace period
+    pub all_dead: bool,
+    pub policy: Policy,
+}
+
+impl Status {
+    #[inline]
+    pub fn is_parked(&self) -> bool {
+        self.all_dead && self.policy == Policy::Park
+    }
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self {
+            all_dead: false,
+            policy: Config::default().policy,
+        }
+    }
+}
+
+/// Event emitted when the state of all pools being dead is entered or left
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Event {
+    /// All pools have been dead for longer than the grace period and the policy is applied
+    AllDead(Policy),
+    /// Some pool has recovered and the policy is no longer applied
+    Recovered(Policy),
+}
+
+/// Source of information about pools liveness
+#[async_trait]
+pub trait PoolSource: Send + Sync {
+    async fn any_pool_alive(&self) -> bool;
+
+    /// Pause or resume generation of work. Returns `false` when it is already in the requested
+    /// state.
+    async fn set_paused(&self, paused: bool) -> bool;
+}
+
+#[async_trait]
+impl PoolSource for client::Manager {
+    async fn any_pool_alive(&self) -> bool {
+        for group in self.get_groups().await {
+            if group
+                .get_clients()
+                .await
+                .iter()
+                .any(|client| client.is_running())
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    async fn set_paused(&self, paused: bool) -> bool {
+        client::Manager::set_paused(self, paused).await
+    }
+}
+
+/// Monitor pools from the `source`, broadcast any change of status and emit the corresponding
+/// event until all status receivers are dropped
+pub async fn run_monitor<S: PoolSource>(
+    source: S,
+    config: Config,
+    status_sender: watch::Sender<Status>,
+    event_sender: broadcast::Sender<Event>,
+) {
+    let mut status = Status {
+        all_dead: false,
+        policy: config.policy,
+    };
+    let mut dead_since = None;
+    // Work generation has been paused by the park policy (and not by the user)
+    let mut paused = false;
+
+    if status_sender.broadcast(status).is_err() {
+        return;
+    }
+    loop {
+        let changed = if source.any_pool_alive().await {
+            dead_since = None;
+            if status.all_dead {
+                info!("Pools: pool has recovered, leaving all pools dead state");
+                status.all_dead = false;
+                if paused {
+                    source.set_paused(false).await;
+                    paused = false;
+                }
+                // there may be no subscribers
+                let _ = event_sender.send(Event::Recovered(config.policy));
+                true
+            } else {
+                false
+            }
+        } else {
+            let since = *dead_since.get_or_insert_with(Instant::now);
+            if !status.all_dead && since.elapsed() >= config.grace_period {
+                warn!(
+                    "Pools: all pools are dead for {:?}, applying {:?} policy",
+                    config.grace_period, config.policy
+                );
+                status.all_dead = true;
+                if config.policy == Policy::Park {
+                    paused = source.set_paused(true).await;
+                }
+                let _ = event_sender.send(Event::AllDead(config.policy));
+                true
+            } else {
+                false
+            }
+        };
+        if changed && status_sender.broadcast(status).is_err() {
+            break;
+        }
+        delay_for(config.check_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    /// Pool source controlled by the test script
+    #[derive(Default)]
+    struct ScriptedSource {
+        alive: AtomicBool,
+        paused: AtomicBool,
+    }
+
+    #[async_trait]
+    impl PoolSource for Arc<ScriptedSource> {
+        async fn any_pool_alive(&self) -> bool {
+            self.alive.load(Ordering::Relaxed)
+        }
+
+        async fn set_paused(&self, paused: bool) -> bool {
+            self.paused.swap(paused, Ordering::Relaxed) != paused
+        }
+    }
+
+    async fn next_status(receiver: &mut watch::Receiver<Status>) -> Status {
+        tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("BUG: missing status change")
+            .expect("BUG: monitor has been terminated")
+    }
+
+    async fn next_event(receiver: &mut broadcast::Receiver<Event>) -> Event {
+        tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("BUG: missing event")
+            .expect("BUG: monitor has been terminated")
+    }
+
+    fn start_monitor(
+        source: Arc<ScriptedSource>,
+        policy: Policy,
+        grace_period: Duration,
+    ) -> (watch::Receiver<Status>, broadcast::Receiver<Event>) {
+        let (status_sender, status_receiver) = watch::channel(Status::default());
+        let (event_sender, event_receiver) = broadcast::channel(4);
+        let config = Config {
+            policy,
+            grace_period,
+            check_interval: Duration::from_millis(5),
+        };
+        tokio::spawn(run_monitor(source, config, status_sender, event_sender));
+        (status_receiver, event_receiver)
+    }
+
+    #[tokio::test]
+    async fn test_kill_and_revive_pools() {
+        for &policy in [Policy::Park, Policy::Spin].iter() {
+            let source = Arc::new(ScriptedSource::default());
+            source.alive.store(true, Ordering::Relaxed);
+            let (mut status_receiver, mut event_receiver) =
+                start_monitor(source.clone(), policy, Duration::from_millis(50));
+
+            // initial value of the channel and the value broadcast by started monitor
+            next_status(&mut status_receiver).await;
+            assert!(!next_status(&mut status_receiver).await.all_dead);
+
+            for _ in 0..2 {
+                // kill all pools
+                let killed = Instant::now();
+                source.alive.store(false, Ordering::Relaxed);
+                let status = next_status(&mut status_receiver).await;
+                assert!(status.all_dead);
+                assert_eq!(status.is_parked(), policy == Policy::Park);
+                assert!(killed.elapsed() >= Duration::from_millis(50));
+                assert_eq!(
+                    next_event(&mut event_receiver).await,
+                    Event::AllDead(policy)
+                );
+                assert_eq!(
+                    source.paused.load(Ordering::Relaxed),
+                    policy == Policy::Park
+                );
+
+                // the state is entered only once while pools stay dead
+                delay_for(Duration::from_millis(50)).await;
+                assert!(status_receiver.borrow().all_dead);
+                assert_eq!(
+                    event_receiver.try_recv(),
+                    Err(broadcast::TryRecvError::Empty)
+                );
+
+                // revive them
+                source.alive.store(true, Ordering::Relaxed);
+                let status = next_status(&mut status_receiver).await;
+                assert!(!status.all_dead);
+                assert!(!status.is_parked());
+                assert_eq!(
+                    next_event(&mut event_receiver).await,
+                    Event::Recovered(policy)
+                );
+                assert!(!source.paused.load(Ordering::Relaxed));
+            }
+        }
+    }
+
+    /// Mining paused by the user stays paused after pools recovery
+    #[tokio::test]
+    async fn test_user_pause() {
+        let source = Arc::new(ScriptedSource::default());
+        source.paused.store(true, Ordering::Relaxed);
+        let (_status_receiver, mut event_receiver) =
+            start_monitor(source.clone(), Policy::Park, Duration::from_millis(10));
+
+        assert_eq!(
+            next_event(&mut event_receiver).await,
+            Event::AllDead(Policy::Park)
+        );
+        source.alive.store(true, Ordering::Relaxed);
+        assert_eq!(
+            next_event(&mut event_receiver).await,
+            Event::Recovered(Policy::Park)
+        );
+        assert!(source.paused.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_short_outage() {
+        let source = Arc::new(ScriptedSource::default());
+        let (status_receiver, _event_receiver) =
+            start_monitor(source.clone(), Policy::Spin, Duration::from_secs(60));
+
+        // pools are dead only for a time shorter than grace period
+        delay_for(Duration::from_millis(50)).await;
+        source.alive.store(true, Ordering::Relaxed);
+        delay_for(Duration::from_millis(20)).await;
+        assert!(!status_receiver.borrow().all_dead);
+    }
+}
@@ -259,6 +259,15 @@ impl JobDispatcher {
         }
     }
 
+    /// Detaches the active client from the work solvers which thus stop solving its jobs
+    fn pause(&mut self) {
+        if let ActiveClient::Some(client) = &self.active_client {
+            let engine_sender = Arc::new(work::EngineSender::new(None));
+            engine_sender.swap_sender(&client.engine_sender);
+            self.active_client = ActiveClient::None(engine_sender);
+        }
+    }
+
     async fn select_client(&self, generated_work_delta: u64) -> Option<Arc<client::Handle>> {
         let mut group_registry = self.group_registry.lock().await;
         if group_registry.is_empty() {
@@ -270,6 +279,11 @@ impl JobDispatcher {
             scheduler_group_handle.update_status().await;
             total_generated_work += scheduler_group_handle.generated_work;
         }
+        if group_registry.is_failover_only() {
+            return group_registry
+                .iter()
+                .find_map(|scheduler_group_handle| scheduler_group_handle.active_client.clone());
+        }
 
         let mut next_client = None;
         for scheduler_group_handle in group_registry.iter() {
@@ -292,6 +306,10 @@ impl JobDispatcher {
     }
 
     async fn schedule(&mut self, generated_work_delta: u64) {
+        if self.group_registry.lock().await.is_paused() {
+            self.pause();
+            return;
+        }
         match &self.active_client {
             ActiveClient::Some(client_handle) => {
                 if generated_work_delta == 0 && client_handle.is_running() {
@@ -29,6 +29,7 @@ use crate::error;
 use crate::hal;
 use crate::job;
 use crate::node;
+use crate::sharelog;
 use crate::stats;
 use crate::sync;
 use crate::work;
@@ -53,16 +54,17 @@ use std::sync::Mutex as StdMutex;
 use std::sync::{Arc, Weak};
 use std::time;
 
+use ii_stratum::v2::channel::{JobRegistry, JobStatus};
 use ii_stratum::v2::messages::{
-    NewMiningJob, OpenStandardMiningChannel, OpenStandardMiningChannelError,
-    OpenStandardMiningChannelSuccess, SetNewPrevHash, SetTarget, SetupConnection,
-    SetupConnectionError, SetupConnectionSuccess, SubmitSharesError, SubmitSharesStandard,
-    SubmitSharesSuccess,
+    ChannelEndpointChanged, CloseChannel, NewMiningJob, OpenStandardMiningChannel,
+    OpenStandardMiningChannelError, OpenStandardMiningChannelSuccess, Reconnect, SetGroupChannel,
+    SetNewPrevHash, SetTarget, SetupConnection, SetupConnectionError, SetupConnectionSuccess,
+    SubmitSharesError, SubmitSharesStandard, SubmitSharesSuccess,
 };
 use ii_stratum::v2::types::*;
 use ii_stratum::v2::{
     self,
-    framing::{Framing, Header},
+    framing::{dialect, Framing, Header},
 };
 use ii_stratum::v2::{build_message_from_frame, extensions, Handler};
 
@@ -96,6 +98,74 @@ impl ConnectionDetails {
     }
 }
 
+/// Request of the pool to continue mining on a different endpoint. The client doesn't act on it,
+/// it is passed on to the application (see `StratumClient::take_reconnect_receiver`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectRequest {
+    pub host: String,
+    pub port: u16,
+    /// The current connection should be kept until the new one is established
+    pub keep_connection: bool,
+}
+
+impl ReconnectRequest {
+    /// Maximum length of a (fully qualified) domain name
+    const MAX_HOST_LEN: usize = 253;
+
+    fn from_msg(reconnect_msg: &Reconnect) -> error::Result<Self> {
+        let host = reconnect_msg.new_host.to_string();
+        if host.is_empty() {
+            Err("Missing host")?;
+        }
+        if host.len() > Self::MAX_HOST_LEN {
+            Err(format!(
+                "Host longer than {} characters",
+                Self::MAX_HOST_LEN
+            ))?;
+        }
+        if host.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            Err(format!("Invalid host '{}'", host.escape_default()))?;
+        }
+        Ok(Self {
+            host,
+            port: reconnect_msg.new_port,
+            keep_connection: reconnect_msg.keep_connection,
+        })
+    }
+}
+
+/// Standard channel opened by the client and the group channel it is a member of. Jobs and other
+/// messages of the pool may be addressed to either of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Channel {
+    id: u32,
+    group_id: Option<u32>,
+}
+
+impl Channel {
+    fn from_msg(success_msg: &OpenStandardMiningChannelSuccess) -> Self {
+        Self {
+            id: success_msg.channel_id,
+            group_id: Some(success_msg.group_channel_id),
+        }
+    }
+
+    /// Checks whether a message sent to `channel_id` applies to this channel
+    fn is_addressed(&self, channel_id: u32) -> bool {
+        channel_id == self.id || Some(channel_id) == self.group_id
+    }
+
+    /// Updates the group of the channel. The message replaces all members of the group so the
+    /// channel leaves the group when it is not listed.
+    fn handle_set_group_channel(&mut self, group_msg: &SetGroupChannel) {
+        if group_msg.channel_ids.iter().any(|id| *id == self.id) {
+            self.group_id = Some(group_msg.group_channel_id);
+        } else if self.group_id == Some(group_msg.group_channel_id) {
+            self.group_id = None;
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StratumJob {
     client: Weak<StratumClient>,
@@ -110,8 +180,11 @@ pub struct StratumJob {
 }
 
 impl StratumJob {
+    /// Builds a job of the channel `channel_id`, which may differ from the channel the job
+    /// message has been sent to when the job has been sent to the group of the channel
     pub fn new(
         client: Arc<StratumClient>,
+        channel_id: u32,
         job_msg: &NewMiningJob,
         prevhash_msg: &SetNewPrevHash,
         target: ii_bitcoin::Target,
@@ -119,7 +192,7 @@ impl StratumJob {
         Self {
             client: Arc::downgrade(&client),
             id: job_msg.job_id,
-            channel_id: job_msg.channel_id,
+            channel_id,
             version: job_msg.version,
             prev_hash: ii_bitcoin::DHash::from_slice(prevhash_msg.prev_hash.as_ref())
                 .expect("BUG: Stratum: incorrect size of prev hash"),
@@ -184,19 +257,31 @@ type SolutionQueue = Mutex<VecDeque<(work::Solution, u32)>>;
 /// messages from remote server.
 struct StratumEventHandler {
     client: Arc<StratumClient>,
+    channel: Channel,
     all_jobs: HashMap<u32, NewMiningJob>,
+    /// Jobs of the channel that may be referenced by submitted shares
+    jobs: JobRegistry,
     current_prevhash_msg: Option<SetNewPrevHash>,
     /// Mining target for the next job that is to be solved
     current_target: ii_bitcoin::Target,
+    /// Reason why the pool has closed the channel, the session is finished once it is set
+    close_reason: Option<String>,
 }
 
 impl StratumEventHandler {
-    pub fn new(client: Arc<StratumClient>, current_target: ii_bitcoin::Target) -> Self {
+    pub fn new(
+        client: Arc<StratumClient>,
+        channel: Channel,
+        current_target: ii_bitcoin::Target,
+    ) -> Self {
         Self {
             client,
+            channel,
             all_jobs: Default::default(),
+            jobs: JobRegistry::new(),
             current_prevhash_msg: None,
             current_target,
+            close_reason: None,
         }
     }
 
@@ -206,6 +291,7 @@ impl StratumEventHandler {
     async fn update_job(&mut self, job_msg: &NewMiningJob) {
         let job = Arc::new(StratumJob::new(
             self.client.clone(),
+            self.channel.id,
             job_msg,
             self.current_prevhash_msg
                 .as_ref()
@@ -216,6 +302,18 @@ impl StratumEventHandler {
         self.client.job_sender.lock().await.send(job);
     }
 
+    /// Messages addressed to other channels are ignored because they cannot be mined on this one
+    fn is_addressed(&self, channel_id: u32, msg_name: &str) -> bool {
+        let addressed = self.channel.is_addressed(channel_id);
+        if !addressed {
+            warn!(
+                "Stratum: ignoring {} for channel {} (channel={:?})",
+                msg_name, channel_id, self.channel
+            );
+        }
+        addressed
+    }
+
     fn update_target(&mut self, value: Uint256Bytes) {
         let new_target: ii_bitcoin::Target = value.into();
         info!(
@@ -226,8 +324,32 @@ impl StratumEventHandler {
         self.current_target = new_target;
     }
 
+    /// Account solutions accepted by one pool response in share reconciliation buckets together
+    /// with the sum reported by the pool and record the bucket closed by them in the share log
+    async fn account_share_buckets(&self, solutions: u64, shares: u64, pool_shares: Option<u64>) {
+        let closed_bucket = self
+            .client
+            .client_stats
+            .share_buckets
+            .account(sharelog::Record::now(), solutions, shares, pool_shares)
+            .await;
+        if let Some(bucket) = closed_bucket {
+            sharelog::LOGGER.log_bucket(|| {
+                let connection_details = self.client.connection_details();
+                sharelog::BucketRecord {
+                    pool: connection_details.get_host_and_port(),
+                    worker: connection_details.user,
+                    bucket,
+                }
+            });
+        }
+    }
+
     async fn process_accepted_shares(&self, success_msg: &SubmitSharesSuccess) {
         let now = std::time::Instant::now();
+        let mut accepted_solutions = 0;
+        let mut accepted_shares = 0;
+        let mut last_found = false;
         while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
             info!(
                 "Stratum: accepted solution #{} with nonce={:08x}",
@@ -239,19 +361,35 @@ impl StratumEventHandler {
                 .accepted
                 .account_solution(&solution.job_target(), now)
                 .await;
+            self.client
+                .log_share(&solution, sharelog::ShareResult::Accepted, None);
+            accepted_solutions += 1;
+            accepted_shares += solution.job_target().get_difficulty() as u64;
             if success_msg.last_seq_num == seq_num {
                 // all accepted solutions have been found
-                return;
+                last_found = true;
+                break;
             }
         }
-        warn!(
-            "Stratum: last accepted solution #{} hasn't been found!",
-            success_msg.last_seq_num
-        );
+        self.account_share_buckets(
+            accepted_solutions,
+            accepted_shares,
+            Some(success_msg.new_shares_sum as u64),
+        )
+        .await;
+        if !last_found {
+            warn!(
+                "Stratum: last accepted solution #{} hasn't been found!",
+                success_msg.last_seq_num
+            );
+        }
     }
 
     async fn process_rejected_shares(&self, error_msg: &SubmitSharesError) {
         let now = std::time::Instant::now();
+        let mut accepted_solutions = 0;
+        let mut accepted_shares = 0;
+        let mut rejected_found = false;
         while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
             if error_msg.seq_num == seq_num {
                 info!(
@@ -264,8 +402,15 @@ impl StratumEventHandler {
                     .rejected
                     .account_solution(&solution.job_target(), now)
                     .await;
+                let reason = error_msg.code.to_string();
+                self.client.log_share(
+                    &solution,
+                    sharelog::ShareResult::from_reject_reason(&reason),
+                    Some(reason),
+                );
                 // the rejected solution has been found
-                return;
+                rejected_found = true;
+                break;
             } else {
                 // TODO: this is currently not according to stratum V2 specification
                 // preceding solutions are treated as accepted
@@ -279,6 +424,10 @@ impl StratumEventHandler {
                     .accepted
                     .account_solution(&solution.job_target(), now)
                     .await;
+                self.client
+                    .log_share(&solution, sharelog::ShareResult::Accepted, None);
+                accepted_solutions += 1;
+                accepted_shares += solution.job_target().get_difficulty() as u64;
                 warn!(
                     "Stratum: the solution #{} precedes rejected solution #{}!",
                     seq_num, error_msg.seq_num
@@ -289,10 +438,16 @@ impl StratumEventHandler {
                 );
             }
         }
-        warn!(
-            "Stratum: rejected solution #{} hasn't been found!",
-            error_msg.seq_num
-        );
+        if accepted_solutions > 0 {
+            self.account_share_buckets(accepted_solutions, accepted_shares, None)
+                .await;
+        }
+        if !rejected_found {
+            warn!(
+                "Stratum: rejected solution #{} hasn't been found!",
+                error_msg.seq_num
+            );
+        }
     }
 }
 
@@ -308,8 +463,12 @@ impl Handler for StratumEventHandler {
     //      - flush all other jobs
 
     async fn visit_new_mining_job(&mut self, _header: &Header, job_msg: &NewMiningJob) {
+        if !self.is_addressed(job_msg.channel_id, "mining job") {
+            return;
+        }
         // all jobs since last `prevmsg` have to be stored in job table
         self.all_jobs.insert(job_msg.job_id, job_msg.clone());
+        self.jobs.insert(ChannelId(self.channel.id), job_msg.job_id);
         // TODO: close connection when maximal capacity of `all_jobs` has been reached
 
         // When not marked as future job, we can start mining on it right away
@@ -325,16 +484,27 @@ impl Handler for StratumEventHandler {
     }
 
     async fn visit_set_new_prev_hash(&mut self, _header: &Header, prevhash_msg: &SetNewPrevHash) {
+        if !self.is_addressed(prevhash_msg.channel_id, "prevhash") {
+            return;
+        }
+        // find the future job with ID referenced in prevhash_msg, the job may have been dropped
+        // e.g. when the channel endpoint has changed
+        let mut future_job_msg = match self.all_jobs.remove(&prevhash_msg.job_id) {
+            Some(job_msg) => job_msg,
+            None => {
+                warn!(
+                    "Stratum: ignoring prevhash of unknown job {} (channel={:?})",
+                    prevhash_msg.job_id, self.channel
+                );
+                return;
+            }
+        };
         self.current_prevhash_msg.replace(prevhash_msg.clone());
 
-        // find the future job with ID referenced in prevhash_msg
-        let (_, mut future_job_msg) = self
-            .all_jobs
-            .remove_entry(&prevhash_msg.job_id)
-            .expect("TODO: requested job ID not found");
-
         // remove all other jobs (they are now invalid)
-        self.all_jobs.retain(|_, _| true);
+        self.all_jobs.clear();
+        self.jobs
+            .prune(ChannelId(self.channel.id), prevhash_msg.job_id);
         // turn the job into an immediate job
         future_job_msg.future_job = false;
         // reinsert the job
@@ -360,6 +530,47 @@ impl Handler for StratumEventHandler {
     async fn visit_submit_shares_error(&mut self, _header: &Header, error_msg: &SubmitSharesError) {
         self.process_rejected_shares(error_msg).await;
     }
+
+    async fn visit_reconnect(&mut self, _header: &Header, reconnect_msg: &Reconnect) {
+        self.client.handle_reconnect(reconnect_msg);
+    }
+
+    async fn visit_channel_endpoint_changed(
+        &mut self,
+        _header: &Header,
+        endpoint_msg: &ChannelEndpointChanged,
+    ) {
+        if !self.is_addressed(endpoint_msg.channel_id, "endpoint change") {
+            return;
+        }
+        let stale_jobs = self.jobs.mark_stale(ChannelId(self.channel.id));
+        info!(
+            "Stratum: channel endpoint changed, {} jobs are stale (channel={:?})",
+            stale_jobs, self.channel
+        );
+        // The new endpoint starts with its own jobs and prevhash
+        self.all_jobs.clear();
+        self.current_prevhash_msg = None;
+        self.client.job_sender.lock().await.invalidate();
+    }
+
+    async fn visit_close_channel(&mut self, _header: &Header, close_msg: &CloseChannel) {
+        // The channel id may be reused by the pool so the messages of unknown channels are
+        // tolerated
+        if close_msg.channel_id != self.channel.id {
+            info!(
+                "Stratum: ignoring close of channel {} (channel={:?})",
+                close_msg.channel_id, self.channel
+            );
+            return;
+        }
+        self.close_reason = Some(close_msg.reason_code.to_string());
+    }
+
+    async fn visit_set_group_channel(&mut self, _header: &Header, group_msg: &SetGroupChannel) {
+        self.channel.handle_set_group_channel(group_msg);
+        info!("Stratum: channel group updated: {:?}", self.channel);
+    }
 }
 
 trait FrameSink:
@@ -400,6 +611,31 @@ impl<T> FrameStream for T where
 {
 }
 
+/// Halves of an established connection to the remote server
+type Connection = (Box<dyn FrameStream + Send>, Box<dyn FrameSink + Send>);
+
+/// Opens connections to the remote server in place of the client (e.g. in-memory connections in
+/// tests)
+pub(crate) struct Connector(
+    Box<dyn Fn(&ConnectionDetails) -> error::Result<Connection> + Send + Sync>,
+);
+
+impl Connector {
+    #[cfg(test)]
+    fn new<F>(connect: F) -> Self
+    where
+        F: Fn(&ConnectionDetails) -> error::Result<Connection> + Send + Sync + 'static,
+    {
+        Self(Box::new(connect))
+    }
+}
+
+impl fmt::Debug for Connector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Connector")
+    }
+}
+
 struct StratumSolutionHandler<S> {
     client: Arc<StratumClient>,
     connection_tx: Arc<Mutex<S>>,
@@ -423,12 +659,15 @@ where
         }
     }
 
-    async fn process_solution(&mut self, solution: work::Solution) -> error::Result<()> {
+    /// Submits the solution unless its job is no longer active in `jobs`
+    async fn process_solution(
+        &mut self,
+        solution: work::Solution,
+        jobs: &JobRegistry,
+    ) -> error::Result<()> {
         let job: &StratumJob = solution.job();
 
         let seq_num = self.seq_num;
-        self.seq_num = self.seq_num.wrapping_add(1);
-
         let share_msg = SubmitSharesStandard {
             channel_id: job.channel_id,
             seq_num,
@@ -437,6 +676,34 @@ where
             ntime: solution.time(),
             version: solution.version(),
         };
+        let reason = match jobs.check_share(&share_msg) {
+            JobStatus::Active => None,
+            // The endpoint that has issued the job is gone
+            JobStatus::Stale => Some("channel-endpoint-changed"),
+            // The job has been replaced by new prevhash or it belongs to a former session
+            JobStatus::Unknown => Some("unknown-job"),
+        };
+        if let Some(reason) = reason {
+            info!(
+                "Stratum: discarding solution of stale job {} with nonce={:08x} ({})",
+                job.id,
+                solution.nonce(),
+                reason
+            );
+            self.client
+                .client_stats
+                .stale
+                .account_solution(&solution.job_target(), std::time::Instant::now())
+                .await;
+            self.client.log_share(
+                &solution,
+                sharelog::ShareResult::Stale,
+                Some(reason.to_string()),
+            );
+            return Ok(());
+        }
+        self.seq_num = self.seq_num.wrapping_add(1);
+
         // store solution with sequence number for future server acknowledge
         self.client
             .solutions
@@ -455,6 +722,10 @@ where
 struct StratumConnectionHandler {
     client: Arc<StratumClient>,
     init_target: ii_bitcoin::Target,
+    /// Channel opened by the pool
+    channel: Option<Channel>,
+    /// Selects the dialect of the connection codec after version negotiation
+    dialect: dialect::Selector,
     status: Option<error::Result<()>>,
 }
 
@@ -463,6 +734,8 @@ impl StratumConnectionHandler {
         Self {
             client,
             init_target: Default::default(),
+            channel: None,
+            dialect: Default::default(),
             status: None,
         }
     }
@@ -479,8 +752,8 @@ impl StratumConnectionHandler {
         let connection_details = self.client.connection_details();
         let setup_msg = SetupConnection {
             protocol: 0,
-            max_version: 2,
-            min_version: 2,
+            max_version: dialect::Dialect::max_version(),
+            min_version: dialect::Dialect::min_version(),
             flags: 0,
             endpoint_host: Str0_255::from_string(connection_details.host.clone()),
             endpoint_port: connection_details.port,
@@ -569,12 +842,13 @@ impl StratumConnectionHandler {
         Ok(client_framed_stream)
     }
 
-    /// Starts mining session and provides the initial target negotiated by the upstream endpoint
+    /// Starts mining session and provides the channel opened by the upstream endpoint together
+    /// with the initial target it has negotiated
     async fn init_mining_session<R, S>(
         mut self,
         connection_rx: &mut R,
         connection_tx: Arc<Mutex<S>>,
-    ) -> error::Result<ii_bitcoin::Target>
+    ) -> error::Result<(Channel, ii_bitcoin::Target)>
     where
         R: FrameStream,
         S: FrameSink,
@@ -586,7 +860,10 @@ impl StratumConnectionHandler {
             .await
             .context("Cannot open stratum channel")?;
 
-        Ok(self.init_target)
+        let channel = self
+            .channel
+            .expect("BUG: channel opened without success message");
+        Ok((channel, self.init_target))
     }
 }
 
@@ -595,9 +872,15 @@ impl Handler for StratumConnectionHandler {
     async fn visit_setup_connection_success(
         &mut self,
         _header: &Header,
-        _success_msg: &SetupConnectionSuccess,
+        success_msg: &SetupConnectionSuccess,
     ) {
-        self.status = Ok(()).into();
+        self.status = match self.dialect.select_version(success_msg.used_version) {
+            Some(_) => Ok(()),
+            None => {
+                Err(format!("Unsupported protocol version: {}", success_msg.used_version).into())
+            }
+        }
+        .into();
     }
 
     async fn visit_setup_connection_error(
@@ -615,6 +898,7 @@ impl Handler for StratumConnectionHandler {
         success_msg: &OpenStandardMiningChannelSuccess,
     ) {
         self.init_target = success_msg.target.into();
+        self.channel = Some(Channel::from_msg(success_msg));
         self.status = Ok(()).into();
     }
 
@@ -670,6 +954,11 @@ pub struct StratumClient {
     /// Frames intended for the specified extension will be forwarded into this channel (wrapped
     /// into ExtensionChannelMsg
     extension_channel_sender: Mutex<ExtensionChannelFromStratumSender>,
+    /// Reconnect requests of the pool passed on to the application
+    reconnect_sender: StdMutex<mpsc::Sender<ReconnectRequest>>,
+    reconnect_receiver: StdMutex<Option<mpsc::Receiver<ReconnectRequest>>>,
+    /// Replaces connections to the remote server when it is set
+    connector: Option<Connector>,
 }
 
 impl StratumClient {
@@ -723,6 +1012,7 @@ impl StratumClient {
         )>,
     ) -> Self {
         let (stop_sender, stop_receiver) = mpsc::channel(1);
+        let (reconnect_sender, reconnect_receiver) = mpsc::channel(1);
 
         // Extract the both channel endpoints that connect the client with the stratum extension
         // or populate it with dummy endpoints. That way we can handle the endpoints uniformly
@@ -749,9 +1039,17 @@ impl StratumClient {
             solution_receiver: Mutex::new(solver.solution_receiver),
             extension_channel_receiver: Mutex::new(extension_channel_receiver),
             extension_channel_sender: Mutex::new(extension_channel_sender),
+            reconnect_sender: StdMutex::new(reconnect_sender),
+            reconnect_receiver: StdMutex::new(Some(reconnect_receiver)),
+            connector: None,
         }
     }
 
+    pub(crate) fn with_connector(mut self, connector: Connector) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
     fn connection_details(&self) -> ConnectionDetails {
         self.connection_details
             .lock()
@@ -759,6 +1057,63 @@ impl StratumClient {
             .clone()
     }
 
+    /// Validates `Reconnect` message of the pool and passes it on to the application as
+    /// `ReconnectRequest`. Mining continues on the current connection until the application
+    /// switches the endpoint.
+    fn handle_reconnect(&self, reconnect_msg: &Reconnect) {
+        match ReconnectRequest::from_msg(reconnect_msg) {
+            Ok(request) => {
+                info!("Stratum: pool requests reconnect: {:?}", request);
+                if let Err(e) = self
+                    .reconnect_sender
+                    .lock()
+                    .expect("BUG: cannot lock reconnect sender")
+                    .try_send(request)
+                {
+                    warn!("Stratum: dropping reconnect request: {}", e);
+                }
+            }
+            Err(e) => warn!(
+                "Stratum: ignoring invalid reconnect request {:?}: {}",
+                reconnect_msg, e
+            ),
+        }
+    }
+
+    /// Hands reconnect requests of the pool over to the application which is responsible for
+    /// switching the endpoint. Requests are dropped while there is a pending one.
+    pub fn take_reconnect_receiver(&self) -> Option<mpsc::Receiver<ReconnectRequest>> {
+        self.reconnect_receiver
+            .lock()
+            .expect("BUG: cannot lock reconnect receiver")
+            .take()
+    }
+
+    /// Record the result of a solution in the share log
+    fn log_share(
+        &self,
+        solution: &work::Solution,
+        result: sharelog::ShareResult,
+        reason: Option<String>,
+    ) {
+        sharelog::LOGGER.log(|| {
+            let connection_details = self.connection_details();
+            let job: &StratumJob = solution.job();
+            sharelog::Record {
+                timestamp: sharelog::Record::now(),
+                pool: connection_details.get_host_and_port(),
+                worker: connection_details.user,
+                job_id: job.id,
+                nonce: solution.nonce(),
+                ntime: solution.time(),
+                version: solution.version(),
+                difficulty: solution.job_target().get_difficulty(),
+                result,
+                reason,
+            }
+        });
+    }
+
     async fn update_last_job(&self, job: Arc<StratumJob>) {
         self.last_job.lock().await.replace(job);
     }
@@ -800,6 +1155,12 @@ impl StratumClient {
             extensions::BASE => {
                 let event_msg = build_message_from_frame(frame)?;
                 event_msg.accept(event_handler).await;
+                if let Some(reason) = event_handler.close_reason.take() {
+                    Err(format!(
+                        "The remote stratum server closed the channel: {}",
+                        reason
+                    ))?;
+                }
             }
             // pass any other extension down the line
             _ => {
@@ -870,7 +1231,11 @@ impl StratumClient {
                 }
                 solution = solution_receiver.receive().fuse() => {
                     match solution {
-                        Some(solution) => solution_handler.process_solution(solution).await?,
+                        Some(solution) => {
+                            solution_handler
+                                .process_solution(solution, &event_handler.jobs)
+                                .await?
+                        }
                         None => {
                             // TODO: initiate Destroying and remove error
                             Err("Standard application shutdown")?;
@@ -886,12 +1251,13 @@ impl StratumClient {
         self: Arc<Self>,
         connection_rx: R,
         connection_tx: Arc<Mutex<S>>,
+        channel: Channel,
         init_target: ii_bitcoin::Target,
     ) where
         R: FrameStream,
         S: FrameSink,
     {
-        let event_handler = StratumEventHandler::new(self.clone(), init_target);
+        let event_handler = StratumEventHandler::new(self.clone(), channel, init_target);
         // TODO consider changing main_loop to accept Arc<Self> and build the solution_handler
         //  along with solution handler communication channels inside of the main_loop.
         let client = self.clone();
@@ -903,47 +1269,76 @@ impl StratumClient {
         }
     }
 
-    async fn run(self: Arc<Self>) {
-        let connection_handler = StratumConnectionHandler::new(self.clone());
-        let connection_details = connection_handler.client.connection_details();
-        let host_and_port = connection_details.get_host_and_port();
-        let user = connection_details.user.clone();
-
+    /// Runs mining session on an established connection to the remote server
+    async fn run_session<R, S>(
+        self: Arc<Self>,
+        connection_handler: StratumConnectionHandler,
+        mut connection_rx: R,
+        connection_tx: S,
+    ) where
+        R: FrameStream,
+        S: FrameSink,
+    {
+        let connection_tx = Arc::new(Mutex::new(connection_tx));
         match connection_handler
-            .connect()
+            .init_mining_session(&mut connection_rx, connection_tx.clone())
             .timeout(Self::CONNECTION_TIMEOUT)
             .await
-            .map_err(|_| error::ErrorKind::General("Connection timeout".to_string()).into())
-        {
-            Ok(Ok(framed_connection)) => {
-                let (framed_sink, mut framed_stream) = framed_connection.split();
-                let framed_sink = Arc::new(Mutex::new(framed_sink));
-                match connection_handler
-                    .init_mining_session(&mut framed_stream, framed_sink.clone())
-                    .timeout(Self::CONNECTION_TIMEOUT)
-                    .await
-                    .map_err(|_| {
-                        error::ErrorKind::General("Init mining session timeout".to_string()).into()
-                    }) {
-                    Ok(Ok(init_target)) => {
-                        if self.status.initiate_running() {
-                            self.clone()
-                                .run_job_solver(framed_stream, framed_sink, init_target)
-                                .await;
-                        }
-                    }
-                    Ok(Err(e)) | Err(e) => {
-                        info!(
-                            "Failed to negotiation initial V2 target: at {}, user={} ({:?}",
-                            host_and_port, user, e
-                        );
-                        // TODO consolidate this, so that we have exactly 1 place where we
-                        //  initiate failing
-                        self.status.initiate_failing();
-                    }
+            .map_err(|_| {
+                error::ErrorKind::General("Init mining session timeout".to_string()).into()
+            }) {
+            Ok(Ok((channel, init_target))) => {
+                if self.status.initiate_running() {
+                    self.clone()
+                        .run_job_solver(connection_rx, connection_tx, channel, init_target)
+                        .await;
                 }
             }
             Ok(Err(e)) | Err(e) => {
+                let connection_details = self.connection_details();
+                info!(
+                    "Failed to negotiation initial V2 target: at {}, user={} ({:?}",
+                    connection_details.get_host_and_port(),
+                    connection_details.user,
+                    e
+                );
+                // TODO consolidate this, so that we have exactly 1 place where we
+                //  initiate failing
+                self.status.initiate_failing();
+            }
+        }
+    }
+
+    /// Connects to the remote server with the connector of the client or over network
+    async fn open_connection(
+        &self,
+        connection_handler: &mut StratumConnectionHandler,
+    ) -> error::Result<Connection> {
+        if let Some(connector) = &self.connector {
+            return (connector.0)(&self.connection_details());
+        }
+        let framed_connection = connection_handler
+            .connect()
+            .timeout(Self::CONNECTION_TIMEOUT)
+            .await
+            .map_err(|_| error::ErrorKind::General("Connection timeout".to_string()))??;
+        connection_handler.dialect = framed_connection.codec().dialect();
+        let (framed_sink, framed_stream) = framed_connection.split();
+        Ok((Box::new(framed_stream), Box::new(framed_sink)))
+    }
+
+    async fn run(self: Arc<Self>) {
+        let mut connection_handler = StratumConnectionHandler::new(self.clone());
+        let connection_details = connection_handler.client.connection_details();
+        let host_and_port = connection_details.get_host_and_port();
+        let user = connection_details.user.clone();
+
+        match self.open_connection(&mut connection_handler).await {
+            Ok((connection_rx, connection_tx)) => {
+                self.run_session(connection_handler, connection_rx, connection_tx)
+                    .await;
+            }
+            Err(e) => {
                 info!(
                     "Failed to connect to {}, user={} {:?}",
                     host_and_port, user, e
@@ -953,6 +1348,32 @@ impl StratumClient {
         }
     }
 
+    /// Drops all state bound to the connection that has just been terminated
+    async fn finish_session(&self) {
+        // Notify the other end that uses the extension channel that it should restart its
+        // operation
+        // TODO Note that this error is triggered also when there is not extension channel.
+        //  It needs to be reworked once we eliminate the need for a dummy extension channel
+        //  pair
+        if let Err(e) = self
+            .extension_channel_sender
+            .lock()
+            .await
+            .try_send(ExtensionChannelMsg::Stop)
+        {
+            info!(
+                "Cannot send stop notification into the extension channel: {:?}",
+                e
+            );
+        }
+        // Invalidate current job to stop working on it
+        self.job_sender.lock().await.invalidate();
+        // Flush all unprocessed solutions to empty buffer
+        // TODO: Count as a discarded solution?
+        self.solution_receiver.lock().await.flush();
+        self.solutions.lock().await.clear();
+    }
+
     async fn main_task(self: Arc<Self>) {
         // TODO: Count as a discarded solution?
         // Flush all obsolete solutions from previous run
@@ -964,30 +1385,7 @@ impl StratumClient {
                 _ = self.clone().run().fuse() => {}
                 _ = stop_receiver.next() => {}
             }
-
-            // Notify the other end that uses the extension channel that it should restart its
-            // operation
-            // TODO Note that this error is triggered also when there is not extension channel.
-            //  It needs to be reworked once we eliminate the need for a dummy extension channel
-            //  pair
-
-            if let Err(e) = self
-                .extension_channel_sender
-                .lock()
-                .await
-                .try_send(ExtensionChannelMsg::Stop)
-            {
-                info!(
-                    "Cannot send stop notification into the extension channel: {:?}",
-                    e
-                );
-            }
-            // Invalidate current job to stop working on it
-            self.job_sender.lock().await.invalidate();
-            // Flush all unprocessed solutions to empty buffer
-            // TODO: Count as a discarded solution?
-            self.solution_receiver.lock().await.flush();
-            self.solutions.lock().await.clear();
+            self.finish_session().await;
 
             if self.status.can_stop() {
                 // NOTE: it is not safe to add here any code!
@@ -1043,3 +1441,572 @@ impl fmt::Display for StratumClient {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils;
+
+    use ii_async_compat::tokio;
+    use tokio::time::delay_for;
+
+    fn reconnect(host: &str) -> Reconnect {
+        Reconnect {
+            new_host: Str0_255::from_str(host),
+            new_port: 3336,
+            keep_connection: true,
+        }
+    }
+
+    #[test]
+    fn test_reconnect_request() {
+        assert_eq!(
+            ReconnectRequest::from_msg(&reconnect("backup.pool.example.com"))
+                .expect("BUG: valid host refused"),
+            ReconnectRequest {
+                host: "backup.pool.example.com".to_string(),
+                port: 3336,
+                keep_connection: true,
+            }
+        );
+        let longest = "a".repeat(ReconnectRequest::MAX_HOST_LEN);
+        assert!(ReconnectRequest::from_msg(&reconnect(&longest)).is_ok());
+
+        let too_long = "a".repeat(ReconnectRequest::MAX_HOST_LEN + 1);
+        for host in &["", "pool example.com", "pool\n", &too_long] {
+            assert!(
+                ReconnectRequest::from_msg(&reconnect(host)).is_err(),
+                "BUG: invalid host '{}' accepted",
+                host
+            );
+        }
+    }
+
+    type Frame = <Framing as ii_wire::Framing>::Tx;
+
+    /// Collects shares submitted to the test pool
+    #[derive(Default)]
+    struct TestPoolHandler {
+        submits: Vec<SubmitSharesStandard>,
+    }
+
+    #[async_trait]
+    impl Handler for TestPoolHandler {
+        async fn visit_submit_shares_standard(
+            &mut self,
+            _header: &Header,
+            submit_msg: &SubmitSharesStandard,
+        ) {
+            self.submits.push(submit_msg.clone());
+        }
+    }
+
+    /// Pool end of an in-memory connection with the client. Dropping it disconnects the client.
+    struct TestPool {
+        tx: mpsc::UnboundedSender<Frame>,
+        rx: mpsc::UnboundedReceiver<Frame>,
+    }
+
+    impl TestPool {
+        fn send<M>(&self, message: M)
+        where
+            M: TryInto<Frame, Error = <Framing as ii_wire::Framing>::Error>,
+        {
+            self.tx
+                .unbounded_send(message.try_into().expect("BUG: cannot convert to frame"))
+                .expect("BUG: cannot send message to miner");
+        }
+
+        async fn next_frame(&mut self) -> Frame {
+            self.rx.next().await.expect("BUG: miner disconnected")
+        }
+
+        /// Opens a channel with difficulty 1 and sends a job built from `block`
+        async fn accept_miner(&mut self, block: &test_utils::TestBlock, job_id: u32) {
+            // SetupConnection
+            self.next_frame().await;
+            self.send(SetupConnectionSuccess {
+                used_version: dialect::Dialect::default().version(),
+                flags: 0,
+            });
+            // OpenStandardMiningChannel
+            self.next_frame().await;
+            self.send(OpenStandardMiningChannelSuccess {
+                req_id: 10,
+                channel_id: 0,
+                target: ii_bitcoin::Target::default().into(),
+                extranonce_prefix: Bytes0_32::new(),
+                group_channel_id: 0,
+            });
+            self.send(new_mining_job(0, job_id, true, block));
+            self.send(set_new_prev_hash(0, job_id, block));
+        }
+
+        /// Returns the next share submitted by the client
+        async fn next_submit(&mut self) -> SubmitSharesStandard {
+            let mut handler = TestPoolHandler::default();
+            build_message_from_frame(self.next_frame().await)
+                .expect("BUG: cannot build message")
+                .accept(&mut handler)
+                .await;
+            handler.submits.pop().expect("BUG: missing submit")
+        }
+
+        fn accept_shares(&self, last_seq_num: u32, count: u32) {
+            self.send(SubmitSharesSuccess {
+                channel_id: 0,
+                last_seq_num,
+                new_submits_accepted_count: count,
+                // Shares of difficulty 1
+                new_shares_sum: count,
+            });
+        }
+    }
+
+    fn disconnected(_: mpsc::SendError) -> ii_stratum::error::Error {
+        ii_stratum::error::ErrorKind::General("Test pool disconnected".to_string()).into()
+    }
+
+    /// Creates an in-memory connection between the client and the test pool
+    fn in_memory_connection() -> (impl FrameStream + Send, impl FrameSink + Send, TestPool) {
+        let (pool_tx, client_rx) = mpsc::unbounded();
+        let (client_tx, pool_rx) = mpsc::unbounded();
+        (
+            client_rx.map(Ok),
+            client_tx.sink_map_err(disconnected as fn(_) -> _),
+            TestPool {
+                tx: pool_tx,
+                rx: pool_rx,
+            },
+        )
+    }
+
+    /// Starts the client on a new in-memory connection the same way as `main_task` does for
+    /// connections to the remote server
+    fn connect(client: &Arc<StratumClient>) -> TestPool {
+        let (connection_rx, connection_tx, pool) = in_memory_connection();
+
+        assert!(client.status.initiate_starting());
+        let client = client.clone();
+        tokio::spawn(async move {
+            client
+                .clone()
+                .run_session(
+                    StratumConnectionHandler::new(client.clone()),
+                    connection_rx,
+                    connection_tx,
+                )
+                .await;
+            client.finish_session().await;
+            assert!(client.status.can_stop());
+        });
+        pool
+    }
+
+    /// Waits for the client to start solving the job with `job_id`
+    async fn wait_for_job(client: &Arc<StratumClient>, job_id: u32) -> Arc<StratumJob> {
+        for _ in 0..500 {
+            let job = client
+                .last_job
+                .lock()
+                .await
+                .clone()
+                .filter(|job| job.id == job_id);
+            if let Some(job) = job {
+                return job;
+            }
+            delay_for(WAIT_INTERVAL).await;
+        }
+        panic!("BUG: the client hasn't received job {} in time", job_id);
+    }
+
+    /// Waits for the client to finish its session after a failure
+    async fn wait_for_failure(client: &Arc<StratumClient>) {
+        for _ in 0..500 {
+            if client.status.status() == sync::Status::Failed {
+                return;
+            }
+            delay_for(WAIT_INTERVAL).await;
+        }
+        panic!("BUG: the client hasn't failed in time");
+    }
+
+    /// Waits for the job with `job_id` and sends solution of `block` for it
+    async fn solve_job(
+        client: &Arc<StratumClient>,
+        solution_sender: &mpsc::UnboundedSender<work::Solution>,
+        block: &test_utils::TestBlock,
+        job_id: u32,
+    ) {
+        let job = wait_for_job(client, job_id).await;
+        send_solution(solution_sender, block, job);
+    }
+
+    /// Sends solution of `block` for the `job`
+    fn send_solution(
+        solution_sender: &mpsc::UnboundedSender<work::Solution>,
+        block: &test_utils::TestBlock,
+        job: Arc<StratumJob>,
+    ) {
+        let midstate = work::Midstate {
+            version: block.version,
+            state: block.midstate,
+        };
+        let work = work::Assignment::new(job, vec![midstate], block.time);
+        solution_sender
+            .unbounded_send(work::Solution::new(
+                work,
+                test_utils::TestSolution::new(block),
+                None,
+            ))
+            .expect("BUG: cannot send solution");
+    }
+
+    /// Waits for the pool to report `pool_shares` in total and returns totals of all share
+    /// reconciliation buckets: solutions, shares and pool shares
+    async fn wait_for_pool_shares(
+        client: &Arc<StratumClient>,
+        pool_shares: u64,
+    ) -> (u64, u64, u64) {
+        for _ in 0..500 {
+            let buckets = client.client_stats.share_buckets.take_snapshot().await;
+            let totals = buckets.iter().fold((0, 0, 0), |totals, bucket| {
+                (
+                    totals.0 + bucket.solutions,
+                    totals.1 + bucket.shares,
+                    totals.2 + bucket.pool_shares.unwrap_or_default(),
+                )
+            });
+            if totals.2 >= pool_shares {
+                return totals;
+            }
+            delay_for(WAIT_INTERVAL).await;
+        }
+        panic!(
+            "BUG: the pool hasn't accepted {} shares in time",
+            pool_shares
+        );
+    }
+
+    const WAIT_INTERVAL: time::Duration = time::Duration::from_millis(10);
+
+    /// Builds a client with a solver that accepts its jobs. The returned sender passes solutions
+    /// to the client.
+    fn build_client() -> (
+        Arc<StratumClient>,
+        mpsc::UnboundedSender<work::Solution>,
+        work::EngineReceiver,
+    ) {
+        let (engine_sender, engine_receiver) = work::engine_channel(work::IgnoreEvents);
+        let _ = engine_sender.replace_engine_generator(Box::new(move |job| {
+            Arc::new(work::engine::VersionRolling::new(job, 1))
+        }));
+        let (solution_sender, solution_receiver) = mpsc::unbounded();
+        let client = Arc::new(StratumClient::new(
+            ConnectionDetails {
+                protocol: ClientProtocol::StratumV2Insecure,
+                user: "test".to_string(),
+                host: "127.0.0.1".to_string(),
+                port: 3336,
+            },
+            None,
+            job::Solver::new(Arc::new(engine_sender), solution_receiver),
+            None,
+        ));
+        (client, solution_sender, engine_receiver)
+    }
+
+    fn new_mining_job(
+        channel_id: u32,
+        job_id: u32,
+        future_job: bool,
+        block: &test_utils::TestBlock,
+    ) -> NewMiningJob {
+        NewMiningJob {
+            channel_id,
+            job_id,
+            future_job,
+            version: block.version,
+            merkle_root: Uint256Bytes(block.merkle_root.into_inner()),
+        }
+    }
+
+    fn set_new_prev_hash(
+        channel_id: u32,
+        job_id: u32,
+        block: &test_utils::TestBlock,
+    ) -> SetNewPrevHash {
+        SetNewPrevHash {
+            channel_id,
+            job_id,
+            prev_hash: Uint256Bytes(block.previous_hash.into_inner()),
+            min_ntime: block.time,
+            nbits: block.bits,
+        }
+    }
+
+    /// Passes `message` to the `handler` the same way as messages received from the pool
+    async fn handle<M>(handler: &mut StratumEventHandler, message: M)
+    where
+        M: TryInto<Frame, Error = <Framing as ii_wire::Framing>::Error>,
+    {
+        build_message_from_frame(message.try_into().expect("BUG: cannot convert to frame"))
+            .expect("BUG: cannot build message")
+            .accept(handler)
+            .await;
+    }
+
+    /// Returns channel and job id of the job that is being solved
+    async fn last_job(client: &Arc<StratumClient>) -> Option<(u32, u32)> {
+        client
+            .last_job
+            .lock()
+            .await
+            .as_ref()
+            .map(|job| (job.channel_id, job.id))
+    }
+
+    fn set_group_channel(group_channel_id: u32, channel_ids: &[u32]) -> SetGroupChannel {
+        SetGroupChannel {
+            group_channel_id,
+            channel_ids: Seq0_64k::from_slice(channel_ids),
+        }
+    }
+
+    /// Jobs sent to the group of the channel are mined on the channel and the group is replaced
+    /// by `SetGroupChannel`
+    #[tokio::test]
+    async fn test_channel_group() {
+        let block = test_utils::TEST_BLOCKS[0];
+        let (client, _solution_sender, _engine_receiver) = build_client();
+        let channel = Channel {
+            id: 1,
+            group_id: Some(100),
+        };
+        let mut handler = StratumEventHandler::new(client.clone(), channel, Default::default());
+
+        handle(&mut handler, new_mining_job(100, 1, true, &block)).await;
+        handle(&mut handler, set_new_prev_hash(100, 1, &block)).await;
+        // Shares are submitted on the channel rather than on the group
+        assert_eq!(last_job(&client).await, Some((1, 1)));
+        handle(&mut handler, new_mining_job(1, 2, false, &block)).await;
+        assert_eq!(last_job(&client).await, Some((1, 2)));
+
+        // Jobs of other channels are ignored
+        handle(&mut handler, new_mining_job(2, 3, false, &block)).await;
+        assert!(!handler.all_jobs.contains_key(&3));
+        assert_eq!(last_job(&client).await, Some((1, 2)));
+
+        // The channel is moved to another group
+        handle(&mut handler, set_group_channel(200, &[3, 1])).await;
+        assert_eq!(handler.channel.group_id, Some(200));
+        handle(&mut handler, new_mining_job(100, 4, false, &block)).await;
+        assert_eq!(last_job(&client).await, Some((1, 2)));
+        handle(&mut handler, new_mining_job(200, 5, false, &block)).await;
+        assert_eq!(last_job(&client).await, Some((1, 5)));
+
+        // Other groups do not affect the channel
+        handle(&mut handler, set_group_channel(300, &[2])).await;
+        assert_eq!(handler.channel.group_id, Some(200));
+
+        // The channel leaves the group that no longer lists it
+        handle(&mut handler, set_group_channel(200, &[3])).await;
+        assert_eq!(handler.channel.group_id, None);
+        handle(&mut handler, new_mining_job(200, 6, false, &block)).await;
+        assert_eq!(last_job(&client).await, Some((1, 5)));
+        handle(&mut handler, set_group_channel(200, &[])).await;
+        assert_eq!(handler.channel.group_id, None);
+    }
+
+    /// Shares are accounted exactly once when the client reconnects to the pool: solutions
+    /// accepted on the first connection are kept and the solution lost together with the
+    /// connection isn't accounted on the next one.
+    #[tokio::test]
+    async fn test_reconnect_share_accounting() {
+        let block = test_utils::TEST_BLOCKS[0];
+        let (client, solution_sender, _engine_receiver) = build_client();
+
+        // First connection: two solutions are accepted and the third one is lost together with
+        // the connection
+        let mut pool = connect(&client);
+        pool.accept_miner(&block, 0).await;
+        solve_job(&client, &solution_sender, &block, 0).await;
+        solve_job(&client, &solution_sender, &block, 0).await;
+        assert_eq!(pool.next_submit().await.seq_num, 0);
+        assert_eq!(pool.next_submit().await.seq_num, 1);
+        pool.accept_shares(1, 2);
+        assert_eq!(wait_for_pool_shares(&client, 2).await, (2, 2, 2));
+
+        solve_job(&client, &solution_sender, &block, 0).await;
+        assert_eq!(pool.next_submit().await.seq_num, 2);
+        drop(pool);
+        wait_for_failure(&client).await;
+
+        // Second connection numbers submits from scratch and the pool acknowledges just the new
+        // solution
+        let mut pool = connect(&client);
+        pool.accept_miner(&block, 1).await;
+        solve_job(&client, &solution_sender, &block, 1).await;
+        assert_eq!(pool.next_submit().await.seq_num, 0);
+        pool.accept_shares(0, 1);
+        assert_eq!(wait_for_pool_shares(&client, 3).await, (3, 3, 3));
+    }
+
+    /// The session is finished when the pool closes the channel of the client while closing of
+    /// unknown channels is tolerated
+    #[tokio::test]
+    async fn test_close_channel() {
+        let block = test_utils::TEST_BLOCKS[0];
+        let (client, _solution_sender, _engine_receiver) = build_client();
+        let mut pool = connect(&client);
+        pool.accept_miner(&block, 0).await;
+        wait_for_job(&client, 0).await;
+
+        pool.send(CloseChannel {
+            channel_id: 7,
+            reason_code: Str0_32::from_str("unknown"),
+        });
+        pool.send(new_mining_job(0, 1, false, &block));
+        wait_for_job(&client, 1).await;
+        assert_eq!(client.status.status(), sync::Status::Running);
+
+        pool.send(CloseChannel {
+            channel_id: 0,
+            reason_code: Str0_32::from_str("shutdown"),
+        });
+        wait_for_failure(&client).await;
+    }
+
+    /// Client pushed to a group follows the `Reconnect` request of the pool and connects to the
+    /// new host
+    #[tokio::test]
+    async fn test_reconnect_to_new_host() {
+        let block = test_utils::TEST_BLOCKS[0];
+        let (connection_sender, mut connection_receiver) = mpsc::unbounded();
+        let connector = Connector::new(move |details| {
+            let (connection_rx, connection_tx, pool) = in_memory_connection();
+            connection_sender
+                .unbounded_send((details.get_host_and_port(), pool))
+                .expect("BUG: cannot pass connection to the test");
+            Ok((Box::new(connection_rx), Box::new(connection_tx)))
+        });
+        let descriptor = ClientDescriptor::create(
+            "stratum2+tcp+insecure://main.pool.example.com:3333",
+            &bosminer_config::ClientUserInfo::new("test", None),
+            true,
+        )
+        .expect("BUG: invalid client descriptor");
+
+        let manager = crate::client::Manager::new(1);
+        let group = manager.create_or_get_default_group().await;
+        let client_handle = group
+            .push_client(crate::client::Handle::with_connector(descriptor, connector))
+            .await;
+
+        let (host, mut pool) = connection_receiver
+            .next()
+            .await
+            .expect("BUG: client hasn't connected");
+        assert_eq!(host, "main.pool.example.com:3333");
+        pool.accept_miner(&block, 0).await;
+        pool.send(reconnect("backup.pool.example.com"));
+
+        let (host, _pool) = connection_receiver
+            .next()
+            .await
+            .expect("BUG: client hasn't reconnected");
+        assert_eq!(host, "backup.pool.example.com:3336");
+        assert_eq!(
+            client_handle.descriptor().await.host,
+            "backup.pool.example.com"
+        );
+    }
+
+    /// Jobs become stale when the endpoint of the channel changes and the new endpoint starts
+    /// with its own prevhash
+    #[tokio::test]
+    async fn test_channel_endpoint_changed() {
+        let block = test_utils::TEST_BLOCKS[0];
+        let (client, _solution_sender, _engine_receiver) = build_client();
+        let channel = Channel {
+            id: 1,
+            group_id: Some(100),
+        };
+        let mut handler = StratumEventHandler::new(client.clone(), channel, Default::default());
+        handle(&mut handler, new_mining_job(1, 1, true, &block)).await;
+        handle(&mut handler, set_new_prev_hash(1, 1, &block)).await;
+        handle(&mut handler, new_mining_job(1, 2, true, &block)).await;
+
+        // Endpoint change of other channels is ignored
+        handle(&mut handler, ChannelEndpointChanged { channel_id: 2 }).await;
+        assert_eq!(handler.jobs.status(ChannelId(1), 1), JobStatus::Active);
+
+        handle(&mut handler, ChannelEndpointChanged { channel_id: 100 }).await;
+        assert_eq!(handler.jobs.status(ChannelId(1), 1), JobStatus::Stale);
+        assert_eq!(handler.jobs.status(ChannelId(1), 2), JobStatus::Stale);
+        assert!(handler.all_jobs.is_empty());
+        assert!(handler.current_prevhash_msg.is_none());
+
+        // Jobs of the new endpoint wait for its prevhash
+        handle(&mut handler, new_mining_job(1, 3, false, &block)).await;
+        assert_eq!(last_job(&client).await, Some((1, 1)));
+        handle(&mut handler, set_new_prev_hash(1, 3, &block)).await;
+        assert_eq!(last_job(&client).await, Some((1, 3)));
+        assert_eq!(handler.jobs.status(ChannelId(1), 3), JobStatus::Active);
+        // Jobs replaced by the prevhash are forgotten
+        assert_eq!(handler.jobs.status(ChannelId(1), 1), JobStatus::Unknown);
+    }
+
+    /// Prevhash referencing a job issued before the endpoint change is ignored
+    #[tokio::test]
+    async fn test_prev_hash_after_channel_endpoint_changed() {
+        let block = test_utils::TEST_BLOCKS[0];
+        let (client, _solution_sender, _engine_receiver) = build_client();
+        let channel = Channel {
+            id: 1,
+            group_id: None,
+        };
+        let mut handler = StratumEventHandler::new(client.clone(), channel, Default::default());
+        handle(&mut handler, new_mining_job(1, 1, true, &block)).await;
+        handle(&mut handler, set_new_prev_hash(1, 1, &block)).await;
+        handle(&mut handler, new_mining_job(1, 2, true, &block)).await;
+        handle(&mut handler, ChannelEndpointChanged { channel_id: 1 }).await;
+
+        handle(&mut handler, set_new_prev_hash(1, 2, &block)).await;
+        assert_eq!(last_job(&client).await, Some((1, 1)));
+        assert!(handler.current_prevhash_msg.is_none());
+        // Jobs of the new endpoint wait for its own prevhash
+        handle(&mut handler, new_mining_job(1, 3, false, &block)).await;
+        assert_eq!(last_job(&client).await, Some((1, 1)));
+
+        handle(&mut handler, new_mining_job(1, 4, true, &block)).await;
+        handle(&mut handler, set_new_prev_hash(1, 4, &block)).await;
+        assert_eq!(last_job(&client).await, Some((1, 4)));
+    }
+
+    /// Solutions of jobs that are no longer active are accounted as stale instead of being
+    /// submitted
+    #[tokio::test]
+    async fn test_stale_solution() {
+        let block = test_utils::TEST_BLOCKS[0];
+        let (client, solution_sender, _engine_receiver) = build_client();
+        let mut pool = connect(&client);
+        pool.accept_miner(&block, 0).await;
+        let stale_job = wait_for_job(&client, 0).await;
+        solve_job(&client, &solution_sender, &block, 0).await;
+        let submit = pool.next_submit().await;
+        assert_eq!((submit.job_id, submit.seq_num), (0, 0));
+
+        pool.send(ChannelEndpointChanged { channel_id: 0 });
+        pool.send(new_mining_job(0, 1, true, &block));
+        pool.send(set_new_prev_hash(0, 1, &block));
+        wait_for_job(&client, 1).await;
+        send_solution(&solution_sender, &block, stale_job);
+        solve_job(&client, &solution_sender, &block, 1).await;
+
+        // The stale solution doesn't consume a sequence number
+        let submit = pool.next_submit().await;
+        assert_eq!((submit.job_id, submit.seq_num), (1, 1));
+        assert_eq!(client.client_stats.stale.take_snapshot().await.solutions, 1);
+    }
+}
@@ -27,6 +27,7 @@ use ii_logging::macros::*;
 use crate::error;
 use crate::job;
 use crate::node;
+use crate::sharelog;
 use crate::stats;
 use crate::sync;
 use crate::work;
@@ -61,7 +62,7 @@ use ii_stratum::v2::types::DeviceInfo;
 use ii_stratum::v2::types::*;
 use ii_stratum::v2::{build_message_from_frame, Handler};
 use ii_stratum::{v1, v2};
-use ii_stratum_proxy::translation::{V2ToV1Translation, V2ToV1TranslationOptions};
+use ii_stratum_proxy::translation::{NotifyFilter, V2ToV1Translation, V2ToV1TranslationOptions};
 use ii_wire::Connection;
 
 use std::collections::HashMap;
@@ -231,8 +232,58 @@ impl StratumEventHandler {
         self.current_target = new_target;
     }
 
+    /// Record the result of a submitted solution in the share log
+    fn log_share(
+        &self,
+        solution: &work::Solution,
+        result: sharelog::ShareResult,
+        reason: Option<String>,
+    ) {
+        sharelog::LOGGER.log(|| {
+            let connection_details = &self.client.connection_details;
+            let job: &StratumJob = solution.job();
+            sharelog::Record {
+                timestamp: sharelog::Record::now(),
+                pool: connection_details.get_host_and_port(),
+                worker: connection_details.user.clone(),
+                job_id: job.id,
+                nonce: solution.nonce(),
+                ntime: solution.time(),
+                version: solution.version(),
+                difficulty: solution.job_target().get_difficulty(),
+                result,
+                reason,
+            }
+        });
+    }
+
+    /// Account solutions accepted by one pool response in share reconciliation buckets and record
+    /// the bucket closed by them in the share log. Stratum V1 doesn't report accepted shares sum
+    /// (the success message is only synthesized by the translation).
+    async fn account_share_buckets(&self, solutions: u64, shares: u64) {
+        let closed_bucket = self
+            .client
+            .client_stats
+            .share_buckets
+            .account(sharelog::Record::now(), solutions, shares, None)
+            .await;
+        if let Some(bucket) = closed_bucket {
+            sharelog::LOGGER.log_bucket(|| {
+                let connection_details = &self.client.connection_details;
+                sharelog::BucketRecord {
+                    pool: connection_details.get_host_and_port(),
+                    worker: connection_details.user.clone(),
+                    bucket,
+                }
+            });
+        }
+    }
+
     async fn process_accepted_shares(&self, success_msg: &SubmitSharesSuccess) {
         let now = std::time::Instant::now();
+        let mut accepted_solutions = 0;
+        let mut accepted_shares = 0;
+        let mut last_found = false;
         while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
             info!(
                 "Stratum: accepted solution #{} with nonce={:08x}",
@@ -244,19 +295,32 @@ impl StratumEventHandler {
                 .accepted
                 .account_solution(&solution.job_target(), now)
                 .await;
+            self.log_share(&solution, sharelog::ShareResult::Accepted, None);
+            accepted_solutions += 1;
+            accepted_shares += solution.job_target().get_difficulty() as u64;
             if success_msg.last_seq_num == seq_num {
                 // all accepted solutions have been found
-                return;
+                last_found = true;
+                break;
             }
         }
-        warn!(
-            "Stratum: last accepted solution #{} hasn't been found!",
-            success_msg.last_seq_num
-        );
+        if accepted_solutions > 0 {
+            self.account_share_buckets(accepted_solutions, accepted_shares)
+                .await;
+        }
+        if !last_found {
+            warn!(
+                "Stratum: last accepted solution #{} hasn't been found!",
+                success_msg.last_seq_num
+            );
+        }
     }
 
     async fn process_rejected_shares(&self, error_msg: &SubmitSharesError) {
         let now = std::time::Instant::now();
+        let mut accepted_solutions = 0;
+        let mut accepted_shares = 0;
+        let mut rejected_found = false;
         while let Some((solution, seq_num)) = self.client.solutions.lock().await.pop_front() {
             if error_msg.seq_num == seq_num {
                 info!(
@@ -269,8 +333,15 @@ impl StratumEventHandler {
                     .rejected
                     .account_solution(&solution.job_target(), now)
                     .await;
+                let reason = error_msg.code.to_string();
+                self.log_share(
+                    &solution,
+                    sharelog::ShareResult::from_reject_reason(&reason),
+                    Some(reason),
+                );
                 // the rejected solution has been found
-                return;
+                rejected_found = true;
+                break;
             } else {
                 // TODO: this is currently not according to stratum V2 specification
                 // preceding solutions are treated as accepted
@@ -284,6 +355,9 @@ impl StratumEventHandler {
                     .accepted
                     .account_solution(&solution.job_target(), now)
                     .await;
+                self.log_share(&solution, sharelog::ShareResult::Accepted, None);
+                accepted_solutions += 1;
+                accepted_shares += solution.job_target().get_difficulty() as u64;
                 warn!(
                     "Stratum: the solution #{} precedes rejected solution #{}!",
                     seq_num, error_msg.seq_num
@@ -294,10 +368,16 @@ impl StratumEventHandler {
                 );
             }
         }
-        warn!(
-            "Stratum: rejected solution #{} hasn't been found!",
-            error_msg.seq_num
-        );
+        if accepted_solutions > 0 {
+            self.account_share_buckets(accepted_solutions, accepted_shares)
+                .await;
+        }
+        if !rejected_found {
+            warn!(
+                "Stratum: rejected solution #{} hasn't been found!",
+                error_msg.seq_num
+            );
+        }
     }
 }
 
@@ -754,8 +834,19 @@ impl StratumClient {
         {
             Ok(Ok(v1_framed_connection)) => {
                 if self.status.initiate_running() {
+                    // Account skipped notifications without keeping the client alive
+                    let client = Arc::downgrade(&self);
+                    let notify_filter = NotifyFilter {
+                        skip_hook: Some(Box::new(move || {
+                            if let Some(client) = client.upgrade() {
+                                client.client_stats.redundant_jobs.inc();
+                            }
+                        })),
+                        ..Default::default()
+                    };
                     let options = V2ToV1TranslationOptions {
                         try_enable_xnsub: self.connection_details.try_enable_xnsub(),
+                        notify_filter: Some(notify_filter),
                     };
                     let (translation_handler, v2_translation_rx, v2_translation_tx) =
                         TranslationHandler::new(v1_framed_connection, options);
@@ -27,6 +27,7 @@ use crate::api;
 use crate::backend;
 use crate::hal::{self, BackendConfig as _};
 use crate::hub;
+use crate::sharelog;
 use crate::stats;
 
 use ii_async_compat::tokio;
@@ -38,6 +39,13 @@ pub async fn main<T: hal::Backend>(backend_config: T::Config, signature: String)
     // Get frontend specific settings from backend config
     let backend_info = backend_config.info();
 
+    // Start share log before any client is created to record all shares
+    if let Some(sharelog_config) = backend_config.sharelog() {
+        sharelog::LOGGER.start(sharelog_config);
+    }
+
+    let dead_pools_config = backend_config.dead_pools();
+
     // Initialize hub core which manages all resources
     let core = Arc::new(hub::Core::new(
         backend_config.midstate_count(),
@@ -52,10 +60,16 @@ pub async fn main<T: hal::Backend>(backend_config: T::Config, signature: String)
         .expect("Backend initialization failed");
 
     tokio::spawn(core.clone().run());
+    tokio::spawn(
+        core.get_client_manager()
+            .clone()
+            .monitor_dead_pools(dead_pools_config),
+    );
     // start statistics processing
     tokio::spawn(stats::mining_task(
         core.frontend.clone(),
         T::DEFAULT_HASHRATE_INTERVAL,
+        core.subscribe_to_log_interval(),
     ));
 
     // the bosminer is controlled with API which also controls when the miner will end
@@ -23,6 +23,7 @@
 use crate::client;
 use crate::error;
 use crate::node;
+use crate::sharelog;
 use crate::work;
 
 use ii_cgminer_api::command;
@@ -61,6 +62,8 @@ pub struct BackendInfo {
     pub hw_rev: String,
     pub fw_ver: String,
     pub dev_id: String,
+    /// Name of hardware profile selected for the detected hardware
+    pub hw_profile: Option<String>,
 }
 
 impl Default for BackendInfo {
@@ -74,6 +77,7 @@ impl Default for BackendInfo {
                 crate::version::STRING.to_string()
             ),
             dev_id: Default::default(),
+            hw_profile: None,
         }
     }
 }
@@ -110,10 +114,24 @@ pub trait BackendConfig: Debug + Send + Sync {
     fn info(&self) -> Option<BackendInfo> {
         None
     }
+    /// Optional share log settings; the share log is not available when `None` is returned
+    fn sharelog(&self) -> Option<sharelog::Config> {
+        None
+    }
+    /// Policy applied when all pools are dead
+    fn dead_pools(&self) -> client::dead_pools::Config {
+        Default::default()
+    }
 }
 
+/// Halts the backend before the miner quits or restarts on request of the API client
+pub type HaltHandler = Box<dyn Fn() -> command::DeferredAction + Send + Sync>;
+
 pub struct FrontendConfig {
     pub cgminer_custom_commands: Option<command::Map>,
+    /// The miner terminates right away on `quit` and `restart` commands when the backend doesn't
+    /// provide any halt handler. Otherwise it terminates after the halt has completed.
+    pub halt_handler: Option<HaltHandler>,
 }
 
 /// Minimal interface for running compatible backend with BOSminer crate
@@ -36,8 +36,13 @@ use futures::channel::mpsc;
 use futures::lock::Mutex;
 use futures::stream::StreamExt;
 use ii_async_compat::{futures, tokio};
+use tokio::sync::watch;
 
 use std::sync::{Arc, Weak};
+use std::time;
+
+/// Default interval of logging mining statistics
+pub const DEFAULT_LOG_INTERVAL: time::Duration = time::Duration::from_secs(5);
 
 /// Handle external events. Currently it is used only wor handling exhausted work from work engine.
 /// It usually signals some serious problem in backend.
@@ -92,6 +97,9 @@ pub struct Core {
     solution_router: Mutex<Option<SolutionRouter>>,
     /// Registry of clients that are able to supply new jobs for mining
     client_manager: client::Manager,
+    /// Interval of logging mining statistics which can be changed in runtime
+    log_interval_sender: watch::Sender<time::Duration>,
+    log_interval_receiver: watch::Receiver<time::Duration>,
 }
 
 /// Concentrates handles to all nodes associated with mining (backends, clients, work solvers)
@@ -107,6 +115,7 @@ impl Core {
         let (solution_sender, solution_receiver) = mpsc::unbounded();
 
         let client_manager = client::Manager::new(midstate_count);
+        let (log_interval_sender, log_interval_receiver) = watch::channel(DEFAULT_LOG_INTERVAL);
         let job_executor = Arc::new(client::JobExecutor::new(
             frontend.clone(),
             engine_sender,
@@ -122,9 +131,27 @@ impl Core {
             solution_sender,
             solution_router: Mutex::new(Some(SolutionRouter::new(job_executor, solution_receiver))),
             client_manager,
+            log_interval_sender,
+            log_interval_receiver,
         }
     }
 
+    #[inline]
+    pub fn get_log_interval(&self) -> time::Duration {
+        *self.log_interval_receiver.borrow()
+    }
+
+    /// Changes the interval of logging mining statistics (see `stats::mining_task`)
+    pub fn set_log_interval(&self, log_interval: time::Duration) {
+        self.log_interval_sender
+            .broadcast(log_interval)
+            .expect("BUG: log interval receiver dropped");
+    }
+
+    pub fn subscribe_to_log_interval(&self) -> watch::Receiver<time::Duration> {
+        self.log_interval_receiver.clone()
+    }
+
     /// Builds a new backend for a specified `backend_config`.
     /// The resulting `hal::FrontendConfig` is then available for starting additional BOSminer
     /// components
@@ -270,6 +297,8 @@ pub mod test {
 
             // send prepared testing block to job solver
             job_solver.job_sender.send(job);
+            // wait for the engine builder task to broadcast a new work engine
+            job_solver.job_sender.flush().await;
             // work generator receives this job and prepares work from it
             let work = work_generator.generate().await.unwrap();
             // initial value for version rolling is 0 so midstate should match with expected one
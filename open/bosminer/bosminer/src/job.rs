@@ -31,12 +31,16 @@ use crate::work;
 
 use futures::channel::mpsc;
 use futures::stream::StreamExt;
-use ii_async_compat::futures;
+use ii_async_compat::{futures, tokio};
+use tokio::sync::watch;
+use tokio::task;
 
 use std::convert::TryInto;
 use std::fmt::Debug;
 use std::mem;
-use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex, Weak};
+use std::time;
 
 use downcast_rs::{impl_downcast, Downcast};
 
@@ -101,15 +105,101 @@ impl Solver {
     }
 }
 
+/// Job tagged with the generation in which it has been sent
+type BuildRequest = Option<(usize, Arc<dyn job::Bitcoin>)>;
+
+/// Builds work engines in a dedicated task so that the protocol handler is not delayed by engine
+/// construction. Only the latest job is built, an in-progress build superseded by a newer job (or
+/// by invalidation) is abandoned without being broadcast.
+#[derive(Clone)]
+struct EngineBuilder {
+    engine_sender: Arc<work::EngineSender>,
+    /// Incremented with each new job and invalidation
+    generation: Arc<AtomicUsize>,
+    /// Generation of the last build request which has been processed (broadcast or abandoned)
+    processed_sender: Arc<watch::Sender<usize>>,
+}
+
+impl EngineBuilder {
+    fn is_current(&self, generation: usize) -> bool {
+        self.generation.load(Ordering::SeqCst) == generation
+    }
+
+    /// Generates the engine and precomputes its first work. It is CPU bound so it is run on
+    /// a thread where blocking is acceptable.
+    fn prepare(&self, generation: usize, job: Arc<dyn job::Bitcoin>) -> Option<work::DynEngine> {
+        if !self.is_current(generation) {
+            return None;
+        }
+        let engine = self.engine_sender.generate_engine(job);
+        if !engine.prepare(&|| !self.is_current(generation)) {
+            trace!("Abandoning preparation of work engine of superseded job");
+            engine.terminate();
+            return None;
+        }
+        Some(engine)
+    }
+
+    async fn build(&self, generation: usize, job: Arc<dyn job::Bitcoin>) {
+        let start = time::Instant::now();
+        let builder = self.clone();
+        let engine = match task::spawn_blocking(move || builder.prepare(generation, job)).await {
+            Ok(Some(engine)) => engine,
+            Ok(None) => return,
+            Err(e) => {
+                error!("Preparation of work engine failed: {}", e);
+                return;
+            }
+        };
+
+        // the engine is swapped in under the lock of the engine sender only when the job hasn't
+        // been superseded while the engine was being prepared
+        if self
+            .engine_sender
+            .broadcast_engine_if(engine.clone(), || self.is_current(generation))
+        {
+            trace!("Work engine built and broadcast in {:?}", start.elapsed());
+        } else {
+            trace!("Abandoning work engine of superseded job");
+            engine.terminate();
+        }
+    }
+
+    async fn run(self, mut request_receiver: watch::Receiver<BuildRequest>) {
+        while let Some(request) = request_receiver.recv().await {
+            if let Some((generation, job)) = request {
+                self.build(generation, job).await;
+                // requests are processed in order so the reported generation never decreases
+                let _ = self.processed_sender.broadcast(generation);
+            }
+        }
+    }
+}
+
 /// This is the entrypoint for new jobs and updates into processing.
 /// Typically the mining protocol handler will inject new jobs through it
 pub struct Sender {
     engine_sender: Arc<work::EngineSender>,
+    generation: Arc<AtomicUsize>,
+    /// Generation of the last job handed over to the engine builder
+    requested: AtomicUsize,
+    /// Channel to the engine builder task which is started with the first job
+    request_sender: StdMutex<Option<watch::Sender<BuildRequest>>>,
+    processed_sender: Arc<watch::Sender<usize>>,
+    processed_receiver: watch::Receiver<usize>,
 }
 
 impl Sender {
     pub fn new(engine_sender: Arc<work::EngineSender>) -> Self {
-        Self { engine_sender }
+        let (processed_sender, processed_receiver) = watch::channel(0);
+        Self {
+            engine_sender,
+            generation: Arc::new(AtomicUsize::new(0)),
+            requested: AtomicUsize::new(0),
+            request_sender: StdMutex::new(None),
+            processed_sender: Arc::new(processed_sender),
+            processed_receiver,
+        }
     }
 
     /// Check if the job has valid attributes
@@ -143,15 +233,49 @@ impl Sender {
         if let Some(origin) = origin {
             origin.client_stats().valid_jobs().inc();
             info!("--- broadcasting new job ---");
-            self.engine_sender.broadcast_job(job);
+            self.build_engine(job);
         } else {
             // Origin has been removed and no one will receive any solution
             info!("--- discarding job ---");
         }
     }
 
+    /// Hand the `job` over to the engine builder task
+    fn build_engine(&self, job: Arc<dyn job::Bitcoin>) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut request_sender = self.request_sender.lock().expect("BUG: cannot lock sender");
+        let request_sender = request_sender.get_or_insert_with(|| {
+            let (request_sender, request_receiver) = watch::channel(None);
+            let builder = EngineBuilder {
+                engine_sender: self.engine_sender.clone(),
+                generation: self.generation.clone(),
+                processed_sender: self.processed_sender.clone(),
+            };
+            tokio::spawn(builder.run(request_receiver));
+            request_sender
+        });
+        request_sender
+            .broadcast(Some((generation, job)))
+            .expect("BUG: engine builder has been terminated");
+        self.requested.store(generation, Ordering::SeqCst);
+    }
+
+    /// Waits until the engine builder processes the last job sent, i.e. its work engine has been
+    /// broadcast or abandoned because it has been superseded
+    pub async fn flush(&self) {
+        let requested = self.requested.load(Ordering::SeqCst);
+        let mut processed_receiver = self.processed_receiver.clone();
+        while let Some(processed) = processed_receiver.recv().await {
+            if processed >= requested {
+                break;
+            }
+        }
+    }
+
     #[inline]
     pub fn invalidate(&self) {
+        // abandon any pending build
+        self.generation.fetch_add(1, Ordering::SeqCst);
         self.engine_sender.invalidate();
     }
 }
@@ -229,3 +353,38 @@ impl SolutionReceiver {
         while let Ok(Some(_)) = self.solution_channel.try_next() {}
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::test_utils;
+
+    /// Jobs sent in a row before the engine builder gets to them are superseded and only the
+    /// latest one is built and broadcast
+    #[tokio::test]
+    async fn test_superseded_jobs() {
+        let (engine_sender, mut engine_receiver) = work::engine_channel(work::IgnoreEvents);
+        let built_engines = Arc::new(AtomicUsize::new(0));
+        let built_engines_counter = built_engines.clone();
+        let _ = engine_sender.replace_engine_generator(Box::new(move |job| {
+            built_engines_counter.fetch_add(1, Ordering::SeqCst);
+            Arc::new(work::engine::VersionRolling::new(job, 4))
+        }));
+        let job_sender = Sender::new(Arc::new(engine_sender));
+
+        // the builder task doesn't run until the test yields
+        for block in test_utils::TEST_BLOCKS.iter() {
+            job_sender.send(Arc::new(*block));
+        }
+        job_sender.flush().await;
+        assert_eq!(built_engines.load(Ordering::SeqCst), 1);
+
+        let last_block = test_utils::TEST_BLOCKS.last().expect("BUG: no test blocks");
+        let engine = engine_receiver
+            .get_engine()
+            .await
+            .expect("BUG: missing work engine");
+        let work = engine.next_work().unwrap();
+        assert_eq!(last_block.midstate, work.midstates[0].state);
+    }
+}
@@ -34,8 +34,10 @@ pub mod hal;
 pub mod hub;
 pub mod job;
 pub mod node;
+pub mod sharelog;
 pub mod stats;
 pub mod sync;
+pub mod tuning;
 pub mod version;
 pub mod work;
 
@@ -0,0 +1,557 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Optional log of all shares submitted to pools and their results.
+//!
+//! The log is a plain CSV file without a header. Every share is recorded as one line once the
+//! pool decides about it:
+//!
+//! ```text
+//! timestamp,pool,worker,job_id,nonce,ntime,version,difficulty,result,reason
+//! ```
+//!
+//! * `timestamp` - UNIX time (seconds) when the result of the share has been received
+//! * `pool` - pool address in the form `host:port`
+//! * `worker` - user name used for pool authorization
+//! * `job_id` - pool job identifier (decimal)
+//! * `nonce`, `ntime`, `version` - submitted values as 8 digit lowercase hexadecimal numbers
+//! * `difficulty` - share (job target) difficulty
+//! * `result` - one of `accepted`, `rejected` or `stale`
+//! * `reason` - reject reason reported by the pool, empty for accepted shares
+//!
+//! Text fields never contain a comma or a line break: these characters are replaced with space.
+//!
+//! Once a share reconciliation bucket of a pool is closed (see `stats::ShareBuckets`), its summary
+//! is recorded as a line starting with `#bucket`:
+//!
+//! ```text
+//! #bucket,start,pool,worker,solutions,shares,pool_shares,delta
+//! ```
+//!
+//! * `start` - UNIX time (seconds) of the bucket start aligned to the hour
+//! * `solutions` - number of shares accepted within the bucket
+//! * `shares` - sum of difficulties of accepted shares
+//! * `pool_shares` - sum of difficulties reported by the pool, empty when the protocol doesn't
+//!   provide it
+//! * `delta` - `shares` minus `pool_shares`, empty when `pool_shares` is unknown
+//!
+//! When the current file would exceed `max_file_size`, it is rotated to `<path>.1`, older files
+//! are shifted up to `<path>.<max_files>` and the oldest one is removed. The log thus never
+//! occupies more than `max_file_size * (max_files + 1)` bytes on disk.
+//!
+//! Lines are passed to a background writer through a bounded queue. Logging never blocks the
+//! caller and lines that do not fit into the queue (or cannot be written) are only counted as
+//! dropped.
+
+use ii_logging::macros::*;
+
+use crate::stats::{self, UnixTime as _};
+
+use futures::channel::mpsc;
+use ii_async_compat::prelude::*;
+use tokio::fs;
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time;
+
+use once_cell::sync::Lazy;
+
+/// Default path of the current share log file
+pub const DEFAULT_PATH: &str = "/tmp/bosminer-sharelog.csv";
+/// Default size limit of one share log file in bytes
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 256 * 1024;
+/// Default number of rotated files kept besides the current one
+pub const DEFAULT_MAX_FILES: usize = 3;
+/// Default number of lines waiting for the writer before they start being dropped
+pub const DEFAULT_QUEUE_SIZE: usize = 256;
+
+/// Global share log used by all clients
+pub static LOGGER: Lazy<Logger> = Lazy::new(Logger::new);
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub path: PathBuf,
+    pub max_file_size: u64,
+    pub max_files: usize,
+    pub queue_size: usize,
+    /// Initial state of the share log which can be changed at runtime
+    pub enabled: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            path: DEFAULT_PATH.into(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_files: DEFAULT_MAX_FILES,
+            queue_size: DEFAULT_QUEUE_SIZE,
+            enabled: true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ShareResult {
+    Accepted,
+    Rejected,
+    Stale,
+}
+
+impl ShareResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Accepted => "accepted",
+            Self::Rejected => "rejected",
+            Self::Stale => "stale",
+        }
+    }
+
+    /// Classify rejected share by the reason reported by the pool
+    pub fn from_reject_reason(reason: &str) -> Self {
+        if reason.to_ascii_lowercase().contains("stale") {
+            Self::Stale
+        } else {
+            Self::Rejected
+        }
+    }
+}
+
+/// One line of the share log
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub timestamp: u32,
+    pub pool: String,
+    pub worker: String,
+    pub job_id: u32,
+    pub nonce: u32,
+    pub ntime: u32,
+    pub version: u32,
+    pub difficulty: usize,
+    pub result: ShareResult,
+    pub reason: Option<String>,
+}
+
+impl Record {
+    /// Current UNIX time suitable for `timestamp` field
+    pub fn now() -> u32 {
+        time::SystemTime::now().get_unix_time().unwrap_or_default()
+    }
+
+    fn sanitize(value: &str) -> String {
+        value
+            .chars()
+            .map(|c| match c {
+                ',' | '\n' | '\r' => ' ',
+                c => c,
+            })
+            .collect()
+    }
+
+    /// Format the record as a line described in module documentation (including line ending)
+    pub fn to_line(&self) -> String {
+        format!(
+            "{},{},{},{},{:08x},{:08x},{:08x},{},{},{}\n",
+            self.timestamp,
+            Self::sanitize(&self.pool),
+            Self::sanitize(&self.worker),
+            self.job_id,
+            self.nonce,
+            self.ntime,
+            self.version,
+            self.difficulty,
+            self.result.as_str(),
+            Self::sanitize(self.reason.as_deref().unwrap_or_default()),
+        )
+    }
+}
+
+/// Summary of a closed share reconciliation bucket
+#[derive(Clone, Debug)]
+pub struct BucketRecord {
+    pub pool: String,
+    pub worker: String,
+    pub bucket: stats::ShareBucket,
+}
+
+impl BucketRecord {
+    /// Format the record as a line described in module documentation (including line ending)
+    pub fn to_line(&self) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        format!(
+            "#bucket,{},{},{},{},{},{},{}\n",
+            self.bucket.start,
+            Record::sanitize(&self.pool),
+            Record::sanitize(&self.worker),
+            self.bucket.solutions,
+            self.bucket.shares,
+            optional(self.bucket.pool_shares.map(|value| value.to_string())),
+            optional(self.bucket.delta().map(|value| value.to_string())),
+        )
+    }
+}
+
+/// Any line of the share log
+#[derive(Clone, Debug)]
+enum Entry {
+    Share(Record),
+    Bucket(BucketRecord),
+}
+
+impl Entry {
+    fn to_line(&self) -> String {
+        match self {
+            Self::Share(record) => record.to_line(),
+            Self::Bucket(record) => record.to_line(),
+        }
+    }
+}
+
+/// Snapshot of the share log state reported by the API
+#[derive(Clone, Debug)]
+pub struct Status {
+    /// Current file or `None` when the share log hasn't been configured
+    pub file: Option<PathBuf>,
+    pub size: u64,
+    pub dropped: u64,
+    pub enabled: bool,
+}
+
+#[derive(Debug)]
+struct Channel {
+    path: PathBuf,
+    sender: mpsc::Sender<Entry>,
+}
+
+#[derive(Debug)]
+pub struct Logger {
+    enabled: AtomicBool,
+    /// Size of the current file maintained by the writer task
+    size: Arc<AtomicU64>,
+    dropped: Arc<stats::CounterU64>,
+    channel: StdMutex<Option<Channel>>,
+}
+
+impl Logger {
+    fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            size: Arc::new(AtomicU64::new(0)),
+            dropped: Arc::new(Default::default()),
+            channel: StdMutex::new(None),
+        }
+    }
+
+    /// Configure the share log and start its writer task. Any previously started writer is
+    /// stopped after it flushes all queued lines.
+    pub fn start(&self, config: Config) {
+        let (sender, receiver) = mpsc::channel(config.queue_size);
+        let enabled = config.enabled;
+        let path = config.path.clone();
+
+        let writer = Writer::new(config, self.size.clone(), self.dropped.clone());
+        tokio::spawn(writer.run(receiver));
+
+        self.channel
+            .lock()
+            .expect("BUG: cannot lock share log channel")
+            .replace(Channel { path, sender });
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_configured(&self) -> bool {
+        self.channel
+            .lock()
+            .expect("BUG: cannot lock share log channel")
+            .is_some()
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enable or disable logging at runtime. The share log cannot be enabled before it has been
+    /// configured with `start`.
+    pub fn set_enabled(&self, enabled: bool) -> Result<(), ()> {
+        if enabled && !self.is_configured() {
+            return Err(());
+        }
+        self.enabled.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Queue a record built by `f` for writing. The closure is called only when the share log is
+    /// enabled. The call never blocks and a record that doesn't fit into the queue is dropped.
+    pub fn log<F>(&self, f: F)
+    where
+        F: FnOnce() -> Record,
+    {
+        self.queue(|| Entry::Share(f()))
+    }
+
+    /// Queue a summary of closed share bucket built by `f` the same way as `log` does
+    pub fn log_bucket<F>(&self, f: F)
+    where
+        F: FnOnce() -> BucketRecord,
+    {
+        self.queue(|| Entry::Bucket(f()))
+    }
+
+    fn queue<F>(&self, f: F)
+    where
+        F: FnOnce() -> Entry,
+    {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut channel = self
+            .channel
+            .lock()
+            .expect("BUG: cannot lock share log channel");
+        if let Some(channel) = channel.as_mut() {
+            if channel.sender.try_send(f()).is_err() {
+                self.dropped.inc();
+            }
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        let file = self
+            .channel
+            .lock()
+            .expect("BUG: cannot lock share log channel")
+            .as_ref()
+            .map(|channel| channel.path.clone());
+
+        Status {
+            file,
+            size: self.size.load(Ordering::Relaxed),
+            dropped: *self.dropped.take_snapshot(),
+            enabled: self.is_enabled(),
+        }
+    }
+}
+
+/// Background task appending records to the current file and rotating it
+struct Writer {
+    config: Config,
+    file: Option<fs::File>,
+    size: Arc<AtomicU64>,
+    dropped: Arc<stats::CounterU64>,
+}
+
+impl Writer {
+    fn new(config: Config, size: Arc<AtomicU64>, dropped: Arc<stats::CounterU64>) -> Self {
+        Self {
+            config,
+            file: None,
+            size,
+            dropped,
+        }
+    }
+
+    fn rotated_path(path: &Path, idx: usize) -> PathBuf {
+        let mut path: OsString = path.into();
+        path.push(format!(".{}", idx));
+        path.into()
+    }
+
+    async fn open(&mut self) -> std::io::Result<()> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.path)
+            .await?;
+        let size = file.metadata().await?.len();
+        self.size.store(size, Ordering::Relaxed);
+        self.file.replace(file);
+        Ok(())
+    }
+
+    async fn rotate(&mut self) -> std::io::Result<()> {
+        // Close the current file before it is renamed
+        self.file.take();
+        let path = &self.config.path;
+
+        if self.config.max_files == 0 {
+            fs::remove_file(path).await?;
+        } else {
+            let oldest = Self::rotated_path(path, self.config.max_files);
+            // The oldest file does not have to exist yet
+            let _ = fs::remove_file(&oldest).await;
+            for idx in (1..self.config.max_files).rev() {
+                let _ = fs::rename(
+                    Self::rotated_path(path, idx),
+                    Self::rotated_path(path, idx + 1),
+                )
+                .await;
+            }
+            fs::rename(path, Self::rotated_path(path, 1)).await?;
+        }
+        self.size.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn write(&mut self, line: &str) -> std::io::Result<()> {
+        if self.file.is_none() {
+            self.open().await?;
+        }
+        let len = line.len() as u64;
+        let size = self.size.load(Ordering::Relaxed);
+        if size > 0 && size + len > self.config.max_file_size {
+            self.rotate().await?;
+            self.open().await?;
+        }
+
+        let file = self.file.as_mut().expect("BUG: missing share log file");
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        self.size.fetch_add(len, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn run(mut self, mut receiver: mpsc::Receiver<Entry>) {
+        while let Some(entry) = receiver.next().await {
+            if let Err(e) = self.write(entry.to_line().as_str()).await {
+                warn!(
+                    "Share log: cannot write to '{}': {}",
+                    self.config.path.display(),
+                    e
+                );
+                self.dropped.inc();
+                // Try to reopen the file with next record
+                self.file.take();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(result: ShareResult, reason: Option<&str>) -> Record {
+        Record {
+            timestamp: 1_580_000_000,
+            pool: "stratum.slushpool.com:3333".to_string(),
+            worker: "braiins.worker1".to_string(),
+            job_id: 42,
+            nonce: 0xdead_beef,
+            ntime: 0x5e1f_0000,
+            version: 0x2000_0000,
+            difficulty: 8192,
+            result,
+            reason: reason.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_record_line() {
+        assert_eq!(
+            record(ShareResult::Accepted, None).to_line(),
+            "1580000000,stratum.slushpool.com:3333,braiins.worker1,42,deadbeef,5e1f0000,20000000,\
+             8192,accepted,\n"
+        );
+        assert_eq!(
+            record(ShareResult::Rejected, Some("low,\ndiff")).to_line(),
+            "1580000000,stratum.slushpool.com:3333,braiins.worker1,42,deadbeef,5e1f0000,20000000,\
+             8192,rejected,low  diff\n"
+        );
+    }
+
+    #[test]
+    fn test_bucket_line() {
+        let mut bucket = BucketRecord {
+            pool: "stratum.slushpool.com:3333".to_string(),
+            worker: "braiins.worker1".to_string(),
+            bucket: stats::ShareBucket {
+                start: 1_579_996_800,
+                solutions: 3,
+                shares: 24576,
+                pool_shares: None,
+            },
+        };
+        assert_eq!(
+            bucket.to_line(),
+            "#bucket,1579996800,stratum.slushpool.com:3333,braiins.worker1,3,24576,,\n"
+        );
+        bucket.bucket.pool_shares = Some(32768);
+        assert_eq!(
+            bucket.to_line(),
+            "#bucket,1579996800,stratum.slushpool.com:3333,braiins.worker1,3,24576,32768,-8192\n"
+        );
+    }
+
+    #[test]
+    fn test_reject_reason() {
+        assert_eq!(
+            ShareResult::from_reject_reason("stale-share"),
+            ShareResult::Stale
+        );
+        assert_eq!(
+            ShareResult::from_reject_reason("ShareRjct:Stale job"),
+            ShareResult::Stale
+        );
+        assert_eq!(
+            ShareResult::from_reject_reason("difficulty-too-low"),
+            ShareResult::Rejected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rotation() {
+        let dir = std::env::temp_dir().join(format!("bosminer-sharelog-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("cannot create test directory");
+        let path = dir.join("sharelog.csv");
+
+        let line = record(ShareResult::Accepted, None).to_line();
+        let config = Config {
+            path: path.clone(),
+            // Every file can hold just two lines
+            max_file_size: 2 * line.len() as u64,
+            max_files: 2,
+            ..Default::default()
+        };
+        let mut writer = Writer::new(config, Default::default(), Default::default());
+        for _ in 0..9 {
+            writer.write(line.as_str()).await.expect("cannot write line");
+        }
+
+        let file_len = |path: &Path| std::fs::metadata(path).map(|m| m.len()).ok();
+        assert_eq!(file_len(&path), Some(line.len() as u64));
+        assert_eq!(
+            file_len(&Writer::rotated_path(&path, 1)),
+            Some(2 * line.len() as u64)
+        );
+        assert_eq!(
+            file_len(&Writer::rotated_path(&path, 2)),
+            Some(2 * line.len() as u64)
+        );
+        assert_eq!(file_len(&Writer::rotated_path(&path, 3)), None);
+
+        std::fs::remove_dir_all(&dir).expect("cannot remove test directory");
+    }
+}
@@ -31,9 +31,12 @@ use bosminer_macros::{ClientStats, MiningStats, WorkSolverStats};
 use ii_stats::WindowedTimeMean;
 
 use futures::lock::Mutex;
-use ii_async_compat::{futures, tokio};
+use ii_async_compat::prelude::*;
+use ii_async_compat::{futures, select, tokio};
+use tokio::sync::watch;
 use tokio::time::delay_for;
 
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time;
@@ -184,6 +187,14 @@ impl Meter {
             time_mean.insert(kilo_hashes, time);
         }
     }
+
+    /// Zeroes accumulated solutions and shares. Time means are kept because they age out
+    /// naturally.
+    pub(crate) async fn reset(&self) {
+        let mut meter = self.inner.lock().await;
+        meter.solutions = 0;
+        meter.shares = Default::default();
+    }
 }
 
 impl Default for Meter {
@@ -224,6 +235,10 @@ impl LastShare {
             difficulty: target.get_difficulty(),
         });
     }
+
+    pub(crate) async fn reset(&self) {
+        self.inner.lock().await.take();
+    }
 }
 
 impl Default for LastShare {
@@ -265,6 +280,11 @@ impl BestShare {
             }
         }
     }
+
+    pub(crate) fn reset(&self) {
+        self.inner
+            .store(Self::INVALID_DIFFICULTY, Ordering::Relaxed);
+    }
 }
 
 impl Default for BestShare {
@@ -405,6 +425,103 @@ impl UnixTime for time::SystemTime {
     }
 }
 
+/// Duration of share reconciliation buckets. Buckets are aligned to multiples of the duration
+/// since the UNIX epoch, i.e. to whole hours in UTC.
+pub const SHARE_BUCKET_DURATION: time::Duration = time::Duration::from_secs(60 * 60);
+/// Number of the most recent share reconciliation buckets kept per client
+pub const SHARE_BUCKET_COUNT: usize = 24;
+
+/// Accepted shares within one time bucket that are compared with the values reported by the pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShareBucket {
+    /// UNIX time of the bucket start
+    pub start: u32,
+    /// Number of accepted solutions
+    pub solutions: u64,
+    /// Sum of difficulties of accepted solutions
+    pub shares: u64,
+    /// Sum of accepted share difficulties reported by the pool when the protocol provides it
+    pub pool_shares: Option<u64>,
+}
+
+impl ShareBucket {
+    fn new(start: u32) -> Self {
+        Self {
+            start,
+            solutions: 0,
+            shares: 0,
+            pool_shares: None,
+        }
+    }
+
+    /// Difference between locally accounted shares and shares reported by the pool
+    pub fn delta(&self) -> Option<i64> {
+        self.pool_shares
+            .map(|pool_shares| self.shares as i64 - pool_shares as i64)
+    }
+}
+
+/// Accepted shares split into time buckets for reconciliation with the pool.
+/// The buckets are kept by the client so they survive reconnects to the pool.
+#[derive(Debug)]
+pub struct ShareBuckets {
+    inner: Mutex<VecDeque<ShareBucket>>,
+}
+
+impl ShareBuckets {
+    fn bucket_start(timestamp: u32) -> u32 {
+        let duration = SHARE_BUCKET_DURATION.as_secs() as u32;
+        timestamp - timestamp % duration
+    }
+
+    /// Account accepted solutions together with the sum of their difficulties (`shares`) and
+    /// the corresponding sum reported by the pool (if any). All values are accounted to a single
+    /// bucket selected by `timestamp` of the pool response. A timestamp preceding the current
+    /// bucket (the clock has been adjusted backwards) is accounted to the current bucket because
+    /// already reported buckets are never changed.
+    ///
+    /// Returns the previous bucket when it has just been closed by this call.
+    pub(crate) async fn account(
+        &self,
+        timestamp: u32,
+        solutions: u64,
+        shares: u64,
+        pool_shares: Option<u64>,
+    ) -> Option<ShareBucket> {
+        let start = Self::bucket_start(timestamp);
+        let mut buckets = self.inner.lock().await;
+
+        let mut closed = None;
+        if buckets.back().map_or(true, |bucket| bucket.start < start) {
+            closed = buckets.back().cloned();
+            buckets.push_back(ShareBucket::new(start));
+            if buckets.len() > SHARE_BUCKET_COUNT {
+                buckets.pop_front();
+            }
+        }
+        let bucket = buckets.back_mut().expect("BUG: missing share bucket");
+        bucket.solutions += solutions;
+        bucket.shares += shares;
+        if let Some(pool_shares) = pool_shares {
+            bucket.pool_shares = Some(bucket.pool_shares.unwrap_or_default() + pool_shares);
+        }
+        closed
+    }
+
+    /// Buckets ordered from the oldest one
+    pub async fn take_snapshot(&self) -> Snapshot<Vec<ShareBucket>> {
+        Snapshot::new(self.inner.lock().await.iter().cloned().collect())
+    }
+}
+
+impl Default for ShareBuckets {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(VecDeque::with_capacity(SHARE_BUCKET_COUNT + 1)),
+        }
+    }
+}
+
 pub trait Mining: Send + Sync {
     /// The time all statistics are measured from
     fn start_time(&self) -> &time::Instant;
@@ -426,6 +543,9 @@ pub trait Client: Mining {
     fn valid_jobs(&self) -> &CounterUsize;
     /// Number of invalid jobs received from remote server
     fn invalid_jobs(&self) -> &CounterUsize;
+    /// Number of job notifications from remote server skipped because they didn't bring
+    /// any material change of the current job
+    fn redundant_jobs(&self) -> &CounterUsize;
     /// Number of work generated from jobs by rolling or with extra nonce
     fn generated_work(&self) -> &CounterU64;
     /// Shares accepted by remote server
@@ -434,6 +554,8 @@ pub trait Client: Mining {
     fn rejected(&self) -> &Meter;
     /// Valid shares rejected by remote server or discarded due to some error
     fn stale(&self) -> &Meter;
+    /// Accepted shares in time buckets for reconciliation with remote server
+    fn share_buckets(&self) -> &ShareBuckets;
 }
 
 pub trait WorkSolver: Mining {
@@ -489,6 +611,8 @@ pub struct BasicClient {
     pub valid_jobs: stats::CounterUsize,
     #[member_invalid_jobs]
     pub invalid_jobs: stats::CounterUsize,
+    #[member_redundant_jobs]
+    pub redundant_jobs: stats::CounterUsize,
     #[member_generated_work]
     pub generated_work: CounterU64,
     #[member_last_share]
@@ -501,6 +625,8 @@ pub struct BasicClient {
     pub rejected: stats::Meter,
     #[member_stale]
     pub stale: stats::Meter,
+    #[member_share_buckets]
+    pub share_buckets: ShareBuckets,
     #[member_valid_network_diff]
     pub valid_network_diff: Meter,
     #[member_valid_job_diff]
@@ -517,12 +643,14 @@ impl BasicClient {
             start_time,
             valid_jobs: Default::default(),
             invalid_jobs: Default::default(),
+            redundant_jobs: Default::default(),
             generated_work: Default::default(),
             last_share: Default::default(),
             best_share: Default::default(),
             accepted: Meter::new(&intervals),
             rejected: Meter::new(&intervals),
             stale: Default::default(),
+            share_buckets: Default::default(),
             valid_network_diff: Meter::new(&intervals),
             valid_job_diff: Meter::new(&intervals),
             valid_backend_diff: Meter::new(&intervals),
@@ -650,9 +778,49 @@ pub async fn account_valid_solution(
     }
 }
 
-pub async fn mining_task(node: node::DynInfo, interval: time::Duration) {
+/// Zeroes all accumulated share statistics of a node (e.g. on request of `zero` API command)
+pub async fn zero_mining<T: Mining + ?Sized>(stats: &T) {
+    stats.last_share().reset().await;
+    stats.best_share().reset();
+    stats.valid_network_diff().reset().await;
+    stats.valid_job_diff().reset().await;
+    stats.valid_backend_diff().reset().await;
+    stats.error_backend_diff().reset().await;
+}
+
+/// Zeroes all accumulated share statistics of a client including shares reported by a pool
+pub async fn zero_client<T: Client + ?Sized>(stats: &T) {
+    zero_mining(stats).await;
+    stats.accepted().reset().await;
+    stats.rejected().reset().await;
+    stats.stale().reset().await;
+}
+
+/// Logs hash rate of the `node` averaged over `interval` periodically. The period is taken from
+/// `log_interval_receiver` and a change of it takes effect immediately. The task finishes when
+/// the sender of the log interval is dropped.
+pub async fn mining_task(
+    node: node::DynInfo,
+    interval: time::Duration,
+    mut log_interval_receiver: watch::Receiver<time::Duration>,
+) {
+    // The first received value is the current one
+    let mut log_interval = match log_interval_receiver.recv().await {
+        Some(log_interval) => log_interval,
+        None => return,
+    };
     loop {
-        delay_for(time::Duration::from_secs(1)).await;
+        select! {
+            _ = delay_for(log_interval).fuse() => {}
+            new_log_interval = log_interval_receiver.recv().fuse() => {
+                match new_log_interval {
+                    // Start waiting for the new interval from the beginning
+                    Some(new_log_interval) => log_interval = new_log_interval,
+                    None => break,
+                }
+                continue;
+            }
+        }
         let valid_job_diff = node.mining_stats().valid_job_diff().take_snapshot().await;
         let valid_backend_diff = node
             .mining_stats()
@@ -669,3 +837,124 @@ pub async fn mining_task(node: node::DynInfo, interval: time::Duration) {
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Bucket start aligned to the hour (2020-01-26 00:00:00 UTC)
+    const HOUR: u32 = 1_579_996_800;
+
+    /// Shares straddling a reconnect within one bucket are accounted exactly once: the buckets
+    /// are kept by the client while a solution that hasn't been acknowledged before disconnect is
+    /// flushed without being accounted.
+    #[tokio::test]
+    async fn test_reconnect_mid_bucket() {
+        let share_buckets = ShareBuckets::default();
+
+        // First connection: two responses accepting 3 shares of difficulty 1024
+        assert_eq!(
+            share_buckets.account(HOUR + 10, 2, 2048, Some(2048)).await,
+            None
+        );
+        assert_eq!(
+            share_buckets.account(HOUR + 20, 1, 1024, Some(1024)).await,
+            None
+        );
+        // Connection is lost, the pending solution is flushed. Second connection accepts
+        // 2 shares and the pool reports only one of them.
+        assert_eq!(
+            share_buckets.account(HOUR + 600, 2, 2048, Some(1024)).await,
+            None
+        );
+
+        let closed_bucket = share_buckets
+            .account(HOUR + 3600 + 5, 1, 1024, Some(1024))
+            .await
+            .expect("BUG: bucket hasn't been closed");
+        let expected_bucket = ShareBucket {
+            start: HOUR,
+            solutions: 5,
+            shares: 5120,
+            pool_shares: Some(4096),
+        };
+        assert_eq!(closed_bucket, expected_bucket);
+        assert_eq!(closed_bucket.delta(), Some(1024));
+
+        let snapshot = share_buckets.take_snapshot().await;
+        assert_eq!(
+            *snapshot,
+            vec![
+                expected_bucket,
+                ShareBucket {
+                    start: HOUR + 3600,
+                    solutions: 1,
+                    shares: 1024,
+                    pool_shares: Some(1024),
+                }
+            ]
+        );
+    }
+
+    /// Closed buckets are never changed even when the clock goes backwards
+    #[tokio::test]
+    async fn test_clock_skew() {
+        let share_buckets = ShareBuckets::default();
+
+        assert_eq!(
+            share_buckets.account(HOUR + 3599, 1, 1024, None).await,
+            None
+        );
+        assert!(share_buckets
+            .account(HOUR + 3600, 1, 1024, None)
+            .await
+            .is_some());
+        assert_eq!(
+            share_buckets.account(HOUR + 3590, 1, 1024, None).await,
+            None
+        );
+
+        let snapshot = share_buckets.take_snapshot().await;
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].solutions, 1);
+        assert_eq!(snapshot[1].solutions, 2);
+        assert_eq!(snapshot[1].delta(), None);
+    }
+
+    #[tokio::test]
+    async fn test_bucket_count() {
+        let share_buckets = ShareBuckets::default();
+        let duration = SHARE_BUCKET_DURATION.as_secs() as u32;
+
+        for i in 0..SHARE_BUCKET_COUNT as u32 + 2 {
+            share_buckets.account(HOUR + i * duration, 1, 1, None).await;
+        }
+        let snapshot = share_buckets.take_snapshot().await;
+        assert_eq!(snapshot.len(), SHARE_BUCKET_COUNT);
+        assert_eq!(snapshot[0].start, HOUR + 2 * duration);
+    }
+
+    /// Zeroing drops accumulated shares together with the best and last share
+    #[tokio::test]
+    async fn test_zero_client() {
+        let client = BasicClient::default();
+        let target = ii_bitcoin::Target::from_pool_difficulty(1024);
+        let now = time::Instant::now();
+
+        client.valid_job_diff().account_solution(&target, now).await;
+        client.accepted().account_solution(&target, now).await;
+        client
+            .last_share()
+            .account_solution(&target, time::SystemTime::now())
+            .await;
+        client.best_share().account_solution(&target);
+        assert_eq!(client.valid_job_diff().take_snapshot().await.solutions, 1);
+        assert!(client.best_share().take_snapshot().is_some());
+
+        zero_client(&client).await;
+        assert_eq!(client.valid_job_diff().take_snapshot().await.solutions, 0);
+        assert_eq!(client.accepted().take_snapshot().await.solutions, 0);
+        assert!(client.last_share().take_snapshot().await.is_none());
+        assert!(client.best_share().take_snapshot().is_none());
+    }
+}
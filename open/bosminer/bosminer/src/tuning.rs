@@ -0,0 +1,541 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Support for changing several tuning knobs (voltage, frequency) of one device at once
+//!
+//! All requested values are validated against a backend declared safe envelope before anything
+//! is touched. The knobs are then applied in an order that keeps the hardware safe during the
+//! transition: voltage is raised before frequency goes up and frequency is lowered before voltage
+//! goes down. When some step fails, the already applied steps are rolled back in reverse order.
+
+use ii_logging::macros::*;
+
+use async_trait::async_trait;
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Knob {
+    /// Voltage in Volts
+    Voltage,
+    /// Frequency in MHz
+    Frequency,
+}
+
+impl Knob {
+    /// All knobs supported by tunable devices
+    pub const ALL: [Knob; 2] = [Knob::Voltage, Knob::Frequency];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "volt" | "voltage" => Some(Knob::Voltage),
+            "freq" | "frequency" => Some(Knob::Frequency),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Knob::Voltage => "volt",
+            Knob::Frequency => "freq",
+        }
+    }
+}
+
+impl fmt::Display for Knob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Operating point of a device
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Point {
+    /// Voltage in Volts
+    pub voltage: f32,
+    /// Frequency in MHz
+    pub frequency: f32,
+}
+
+impl Point {
+    pub fn get(&self, knob: Knob) -> f32 {
+        match knob {
+            Knob::Voltage => self.voltage,
+            Knob::Frequency => self.frequency,
+        }
+    }
+
+    pub fn with(mut self, knob: Knob, value: f32) -> Self {
+        match knob {
+            Knob::Voltage => self.voltage = value,
+            Knob::Frequency => self.frequency = value,
+        }
+        self
+    }
+}
+
+/// Running above `frequency` requires at least `min_voltage`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Rule {
+    pub frequency: f32,
+    pub min_voltage: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    UnknownOption(String),
+    MissingValue(Knob),
+    InvalidValue(Knob, String),
+    DuplicateOption(Knob),
+    OutOfRange {
+        knob: Knob,
+        value: f32,
+        range: RangeInclusive<f32>,
+    },
+    Unsafe {
+        point: Point,
+        rule: Rule,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownOption(name) => write!(f, "unknown option '{}'", name),
+            Error::MissingValue(knob) => write!(f, "missing {} value", knob),
+            Error::InvalidValue(knob, value) => write!(f, "invalid {} value '{}'", knob, value),
+            Error::DuplicateOption(knob) => write!(f, "option '{}' specified twice", knob),
+            Error::OutOfRange { knob, value, range } => write!(
+                f,
+                "{} {} is out of range {}-{}",
+                knob,
+                value,
+                range.start(),
+                range.end()
+            ),
+            Error::Unsafe { point, rule } => write!(
+                f,
+                "{} MHz at {} V is unsafe (above {} MHz requires at least {} V)",
+                point.frequency, point.voltage, rule.frequency, rule.min_voltage
+            ),
+        }
+    }
+}
+
+/// Safe operating envelope declared by a backend
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope {
+    pub voltage: RangeInclusive<f32>,
+    pub frequency: RangeInclusive<f32>,
+    pub rules: Vec<Rule>,
+}
+
+impl Envelope {
+    fn range(&self, knob: Knob) -> &RangeInclusive<f32> {
+        match knob {
+            Knob::Voltage => &self.voltage,
+            Knob::Frequency => &self.frequency,
+        }
+    }
+
+    /// Verify that the device can safely run at `point`
+    pub fn check(&self, point: Point) -> Result<(), Error> {
+        for &knob in &[Knob::Voltage, Knob::Frequency] {
+            let range = self.range(knob);
+            let value = point.get(knob);
+            if !range.contains(&value) {
+                return Err(Error::OutOfRange {
+                    knob,
+                    value,
+                    range: range.clone(),
+                });
+            }
+        }
+        match self
+            .rules
+            .iter()
+            .find(|rule| point.frequency > rule.frequency && point.voltage < rule.min_voltage)
+        {
+            Some(&rule) => Err(Error::Unsafe { point, rule }),
+            None => Ok(()),
+        }
+    }
+
+    /// Order the `changes` so that every intermediate point between `current` and the target point
+    /// stays inside the envelope
+    pub fn plan(&self, current: Point, changes: &[(Knob, f32)]) -> Result<Vec<(Knob, f32)>, Error> {
+        let mut plan = changes.to_vec();
+        let target = plan
+            .iter()
+            .fold(current, |point, &(knob, value)| point.with(knob, value));
+        self.check(target)?;
+
+        // Frequency goes first unless the voltage is being raised
+        let voltage_first = target.voltage > current.voltage;
+        plan.sort_by_key(|&(knob, _)| (knob == Knob::Voltage) != voltage_first);
+
+        let mut point = current;
+        for &(knob, value) in &plan {
+            point = point.with(knob, value);
+            self.check(point)?;
+        }
+        Ok(plan)
+    }
+}
+
+/// Convert textual options to a list of changes (values are not validated against any envelope)
+pub fn parse_changes(options: &[(String, Option<String>)]) -> Result<Vec<(Knob, f32)>, Error> {
+    let mut changes: Vec<(Knob, f32)> = Vec::with_capacity(options.len());
+    for (name, value) in options {
+        let knob = Knob::from_name(name).ok_or_else(|| Error::UnknownOption(name.clone()))?;
+        if changes.iter().any(|&(other, _)| other == knob) {
+            return Err(Error::DuplicateOption(knob));
+        }
+        let value = value.as_ref().ok_or(Error::MissingValue(knob))?;
+        let value = value
+            .parse::<f32>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| Error::InvalidValue(knob, value.clone()))?;
+        changes.push((knob, value));
+    }
+    Ok(changes)
+}
+
+/// Device which supports changing of its operating point
+#[async_trait]
+pub trait Tunable: Send + Sync {
+    async fn get_point(&self) -> Point;
+
+    async fn set(&self, knob: Knob, value: f32) -> Result<(), String>;
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StepResult {
+    Applied,
+    Failed,
+    RolledBack,
+    Skipped,
+}
+
+/// One change in the order in which it has been (or would have been) applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub knob: Knob,
+    pub value: f32,
+    pub result: StepResult,
+}
+
+/// Outcome of a transaction. Steps are listed in the order used for applying them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub steps: Vec<Step>,
+    pub error: Option<String>,
+}
+
+/// Apply all `changes` to the `device` or none of them. Validation errors are returned before
+/// anything is applied, errors during applying are reported in the `Outcome`.
+pub async fn apply<T>(
+    device: &T,
+    envelope: &Envelope,
+    changes: &[(Knob, f32)],
+) -> Result<Outcome, Error>
+where
+    T: Tunable + ?Sized,
+{
+    let original = device.get_point().await;
+    let plan = envelope.plan(original, changes)?;
+
+    let mut steps: Vec<_> = plan
+        .into_iter()
+        .map(|(knob, value)| Step {
+            knob,
+            value,
+            result: StepResult::Skipped,
+        })
+        .collect();
+
+    let mut error = None;
+    for step in steps.iter_mut() {
+        match device.set(step.knob, step.value).await {
+            Ok(()) => step.result = StepResult::Applied,
+            Err(e) => {
+                step.result = StepResult::Failed;
+                error = Some(format!(
+                    "setting {} to {} failed: {}",
+                    step.knob, step.value, e
+                ));
+                break;
+            }
+        }
+    }
+
+    if let Some(error) = error.as_mut() {
+        for step in steps
+            .iter_mut()
+            .rev()
+            .filter(|step| step.result == StepResult::Applied)
+        {
+            let value = original.get(step.knob);
+            match device.set(step.knob, value).await {
+                Ok(()) => step.result = StepResult::RolledBack,
+                Err(e) => {
+                    error!("Tuning: cannot roll {} back to {}: {}", step.knob, value, e);
+                    error.push_str(&format!("; rollback of {} failed: {}", step.knob, e));
+                }
+            }
+        }
+    }
+
+    Ok(Outcome { steps, error })
+}
+
+/// Device parked at the lowest operating point of an envelope which remembers the operating point
+/// used before parking
+#[derive(Debug)]
+pub struct Parked<T> {
+    device: T,
+    point: Point,
+}
+
+impl<T: Tunable> Parked<T> {
+    /// Operating point used before parking
+    #[inline]
+    pub fn point(&self) -> Point {
+        self.point
+    }
+
+    /// Re-apply the operating point used before parking. The point has been in use before so only
+    /// ranges of the envelope are checked.
+    pub async fn resume(self, envelope: &Envelope) -> Result<T, String> {
+        let envelope = Envelope {
+            rules: vec![],
+            ..envelope.clone()
+        };
+        let changes = [
+            (Knob::Voltage, self.point.voltage),
+            (Knob::Frequency, self.point.frequency),
+        ];
+        match apply(&self.device, &envelope, &changes).await {
+            Ok(Outcome { error: None, .. }) => Ok(self.device),
+            Ok(Outcome {
+                error: Some(error), ..
+            }) => Err(error),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Move the `device` to the lowest operating point of the `envelope`
+pub async fn park<T: Tunable>(device: T, envelope: &Envelope) -> Result<Parked<T>, String> {
+    let point = device.get_point().await;
+    let changes = [
+        (Knob::Frequency, *envelope.frequency.start()),
+        (Knob::Voltage, *envelope.voltage.start()),
+    ];
+    match apply(&device, envelope, &changes).await {
+        Ok(Outcome { error: None, .. }) => Ok(Parked { device, point }),
+        Ok(Outcome {
+            error: Some(error), ..
+        }) => Err(error),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ii_async_compat::tokio;
+
+    use std::sync::Mutex;
+
+    fn envelope() -> Envelope {
+        Envelope {
+            voltage: 7.95..=9.4,
+            frequency: 200.0..=900.0,
+            rules: vec![
+                Rule {
+                    frequency: 500.0,
+                    min_voltage: 8.3,
+                },
+                Rule {
+                    frequency: 650.0,
+                    min_voltage: 8.8,
+                },
+            ],
+        }
+    }
+
+    fn point(voltage: f32, frequency: f32) -> Point {
+        Point { voltage, frequency }
+    }
+
+    #[test]
+    fn test_envelope_check() {
+        let envelope = envelope();
+
+        assert_eq!(envelope.check(point(8.0, 500.0)), Ok(()));
+        assert_eq!(envelope.check(point(8.8, 900.0)), Ok(()));
+        assert!(match envelope.check(point(9.5, 600.0)) {
+            Err(Error::OutOfRange { knob, .. }) => knob == Knob::Voltage,
+            _ => false,
+        });
+        assert!(match envelope.check(point(9.0, 100.0)) {
+            Err(Error::OutOfRange { knob, .. }) => knob == Knob::Frequency,
+            _ => false,
+        });
+        assert!(match envelope.check(point(8.5, 700.0)) {
+            Err(Error::Unsafe { rule, .. }) => rule.min_voltage == 8.8,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn test_envelope_plan() {
+        use Knob::*;
+
+        let envelope = envelope();
+        let changes = [(Frequency, 700.0), (Voltage, 8.9)];
+
+        // Going up: voltage first
+        assert_eq!(
+            envelope.plan(point(8.2, 450.0), &changes),
+            Ok(vec![(Voltage, 8.9), (Frequency, 700.0)])
+        );
+        // Going down: frequency first
+        assert_eq!(
+            envelope.plan(point(9.2, 800.0), &[(Voltage, 8.2), (Frequency, 450.0)]),
+            Ok(vec![(Frequency, 450.0), (Voltage, 8.2)])
+        );
+        // Frequency alone would leave the device under-volted
+        assert!(envelope
+            .plan(point(8.2, 450.0), &[(Frequency, 700.0)])
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_changes() {
+        let options = |list: &[(&str, &str)]| {
+            list.iter()
+                .map(|(name, value)| (name.to_string(), Some(value.to_string())))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            parse_changes(&options(&[("freq", "650"), ("volt", "8.9")])),
+            Ok(vec![(Knob::Frequency, 650.0), (Knob::Voltage, 8.9)])
+        );
+        assert_eq!(
+            parse_changes(&options(&[("fan", "50")])),
+            Err(Error::UnknownOption("fan".to_string()))
+        );
+        assert_eq!(
+            parse_changes(&options(&[("freq", "650"), ("frequency", "600")])),
+            Err(Error::DuplicateOption(Knob::Frequency))
+        );
+        assert_eq!(
+            parse_changes(&options(&[("volt", "high")])),
+            Err(Error::InvalidValue(Knob::Voltage, "high".to_string()))
+        );
+        assert_eq!(
+            parse_changes(&[("freq".to_string(), None)]),
+            Err(Error::MissingValue(Knob::Frequency))
+        );
+    }
+
+    struct Device {
+        point: Mutex<Point>,
+        broken: Option<Knob>,
+    }
+
+    #[async_trait]
+    impl Tunable for Device {
+        async fn get_point(&self) -> Point {
+            *self.point.lock().expect("BUG: cannot lock device")
+        }
+
+        async fn set(&self, knob: Knob, value: f32) -> Result<(), String> {
+            if self.broken == Some(knob) {
+                return Err("broken".to_string());
+            }
+            let mut point = self.point.lock().expect("BUG: cannot lock device");
+            *point = point.with(knob, value);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_rollback() {
+        let changes = [(Knob::Frequency, 700.0), (Knob::Voltage, 8.9)];
+        let device = Device {
+            point: Mutex::new(point(8.2, 450.0)),
+            broken: Some(Knob::Frequency),
+        };
+
+        let outcome = apply(&device, &envelope(), &changes)
+            .await
+            .expect("BUG: valid changes rejected");
+        assert!(outcome.error.is_some());
+        assert_eq!(
+            outcome
+                .steps
+                .iter()
+                .map(|step| step.result)
+                .collect::<Vec<_>>(),
+            vec![StepResult::RolledBack, StepResult::Failed]
+        );
+        assert_eq!(device.get_point().await, point(8.2, 450.0));
+
+        let device = Device {
+            point: Mutex::new(point(8.2, 450.0)),
+            broken: None,
+        };
+        let outcome = apply(&device, &envelope(), &changes)
+            .await
+            .expect("BUG: valid changes rejected");
+        assert_eq!(outcome.error, None);
+        assert_eq!(device.get_point().await, point(8.9, 700.0));
+    }
+
+    #[tokio::test]
+    async fn test_park_and_resume() {
+        let device = Device {
+            // operating point violating rules of the envelope
+            point: Mutex::new(point(8.5, 700.0)),
+            broken: None,
+        };
+
+        let parked = park(device, &envelope())
+            .await
+            .expect("BUG: cannot park device");
+        assert_eq!(parked.device.get_point().await, point(7.95, 200.0));
+        assert_eq!(parked.point(), point(8.5, 700.0));
+
+        let device = parked
+            .resume(&envelope())
+            .await
+            .expect("BUG: cannot resume device");
+        assert_eq!(device.get_point().await, point(8.5, 700.0));
+    }
+}
@@ -284,6 +284,13 @@ pub trait Engine: Debug + Send + Sync {
     fn is_exhausted(&self) -> bool;
 
     fn next_work(&self) -> LoopState<Assignment>;
+
+    /// Precompute anything needed for generating the first work before the engine is broadcast.
+    /// The preparation is abandoned as soon as `is_superseded` reports that the engine won't be
+    /// used anymore, `false` is returned in such case.
+    fn prepare(&self, _is_superseded: &dyn Fn() -> bool) -> bool {
+        true
+    }
 }
 
 /// Shared work engine type
@@ -340,12 +347,15 @@ impl EngineSenderInner {
         self.re_broadcast();
     }
 
+    fn generate_engine(&self, job: Arc<dyn job::Bitcoin>) -> DynEngine {
+        self.engine_generator
+            .as_ref()
+            .expect("BUG: missing engine generator")(job)
+    }
+
     /// Generates a new work engine for the specified `job` and broadcasts it to its subscribers
     fn broadcast_job(&mut self, job: Arc<dyn job::Bitcoin>) {
-        let engine = self
-            .engine_generator
-            .as_ref()
-            .expect("BUG: missing engine generator")(job);
+        let engine = self.generate_engine(job);
         self.broadcast_engine(engine);
     }
 
@@ -412,6 +422,26 @@ impl EngineSender {
         self.lock_inner().broadcast_engine(engine)
     }
 
+    /// Generates a new work engine for the specified `job` without broadcasting it
+    #[inline]
+    pub fn generate_engine(&self, job: Arc<dyn job::Bitcoin>) -> DynEngine {
+        self.lock_inner().generate_engine(job)
+    }
+
+    /// Broadcasts the `engine` only when `is_current` confirms it hasn't been superseded. The check
+    /// is done under the same lock as the broadcast so it cannot race with `invalidate`.
+    pub fn broadcast_engine_if<F>(&self, engine: DynEngine, is_current: F) -> bool
+    where
+        F: FnOnce() -> bool,
+    {
+        let mut inner = self.lock_inner();
+        let current = is_current();
+        if current {
+            inner.broadcast_engine(engine);
+        }
+        current
+    }
+
     #[inline]
     pub fn broadcast_job(&self, job: Arc<dyn job::Bitcoin>) {
         self.lock_inner().broadcast_job(job)
@@ -147,6 +147,8 @@ pub struct VersionRolling {
     curr_range: AtomicRange,
     /// Base Bitcoin block header version with BIP320 bits cleared
     base_version: u32,
+    /// Midstates of the first work precomputed before the engine is broadcast
+    first_midstates: OnceCell<Vec<Midstate>>,
 }
 
 impl VersionRolling {
@@ -166,6 +168,7 @@ impl VersionRolling {
                 midstate_count as u32,
             ),
             base_version,
+            first_midstates: OnceCell::new(),
         }
     }
 
@@ -184,6 +187,51 @@ impl VersionRolling {
         assert!(ntime_offset < ROLL_NTIME_SECONDS);
         ntime_offset
     }
+
+    /// Generate midstates for the allocated range of indexes <current, next)
+    fn generate_midstates(&self, current: u32, next: u32) -> Vec<Midstate> {
+        self.try_generate_midstates(current, next, &|| false)
+            .expect("BUG: midstate generation cannot be abandoned")
+    }
+
+    /// Generate midstates for the allocated range of indexes <current, next). The generation is
+    /// abandoned before each midstate when `is_superseded` returns true.
+    fn try_generate_midstates(
+        &self,
+        current: u32,
+        next: u32,
+        is_superseded: &dyn Fn() -> bool,
+    ) -> Option<Vec<Midstate>> {
+        let mut midstates = Vec::with_capacity(self.midstate_count);
+
+        // prepare block chunk1 with all invariants
+        let mut block_chunk1 = ii_bitcoin::BlockHeader {
+            previous_hash: self.job.previous_hash().into_inner(),
+            merkle_root: self.job.merkle_root().into_inner(),
+            ..Default::default()
+        };
+
+        // generate all midstates from given range of indexes
+        for index in current..next {
+            if is_superseded() {
+                return None;
+            }
+            // use index for generation compatible header version
+            let version = self.get_block_version(index);
+            block_chunk1.version = version;
+            midstates.push(Midstate {
+                version,
+                state: block_chunk1.midstate(),
+            })
+        }
+        Some(midstates)
+    }
+
+    #[inline]
+    fn first_midstates(&self) -> &Vec<Midstate> {
+        self.first_midstates
+            .get_or_init(|| self.generate_midstates(0, self.midstate_count as u32))
+    }
 }
 
 impl Engine for VersionRolling {
@@ -191,6 +239,20 @@ impl Engine for VersionRolling {
         self.curr_range.terminate();
     }
 
+    fn prepare(&self, is_superseded: &dyn Fn() -> bool) -> bool {
+        if self.first_midstates.get().is_some() {
+            return true;
+        }
+        match self.try_generate_midstates(0, self.midstate_count as u32, is_superseded) {
+            Some(midstates) => {
+                // the engine hasn't been broadcast yet so nobody else can initialize the cell
+                let _ = self.first_midstates.set(midstates);
+                true
+            }
+            None => false,
+        }
+    }
+
     fn is_exhausted(&self) -> bool {
         self.curr_range.is_exhausted(None)
     }
@@ -206,26 +268,12 @@ impl Engine for VersionRolling {
 
         // check if given range is the same as number of midstates
         assert_eq!(self.midstate_count, (next - current) as usize);
-        let mut midstates = Vec::with_capacity(self.midstate_count);
-
-        // prepare block chunk1 with all invariants
-        let mut block_chunk1 = ii_bitcoin::BlockHeader {
-            previous_hash: self.job.previous_hash().into_inner(),
-            merkle_root: self.job.merkle_root().into_inner(),
-            ..Default::default()
+        let midstates = if current == 0 {
+            self.first_midstates().clone()
+        } else {
+            self.generate_midstates(current, next)
         };
 
-        // generate all midstates from given range of indexes
-        for index in current..next {
-            // use index for generation compatible header version
-            let version = self.get_block_version(index);
-            block_chunk1.version = version;
-            midstates.push(Midstate {
-                version,
-                state: block_chunk1.midstate(),
-            })
-        }
-
         // Once we exhaust version-rolling-space, we start rolling ntime.
         // We can be sure ntime offset is common for all blocks, because `midstate_count`
         // divides the size of range we roll.
@@ -306,6 +354,43 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_prepared_midstate() {
+        for block in test_utils::TEST_BLOCKS.iter() {
+            let job = Arc::new(*block);
+            let engine = VersionRolling::new(job, 4);
+            assert!(engine.prepare(&|| false));
+
+            let work = engine.next_work().unwrap();
+            assert_eq!(block.midstate, work.midstates[0].state);
+            assert_eq!(work.midstates.len(), 4);
+            // the precomputed midstates are used only for the first work
+            let work = engine.next_work().unwrap();
+            assert_eq!(
+                work.midstates[0].version,
+                get_block_version(&Arc::new(*block), 4)
+            );
+        }
+    }
+
+    #[test]
+    fn test_abandoned_prepare() {
+        let job = Arc::new(test_utils::TEST_BLOCKS[0]);
+        let engine = VersionRolling::new(job, 4);
+        // supersede the engine after the second midstate
+        let checks = std::cell::Cell::new(0);
+        assert!(!engine.prepare(&|| {
+            checks.set(checks.get() + 1);
+            checks.get() > 2
+        }));
+        assert_eq!(checks.get(), 3);
+        assert!(engine.first_midstates.get().is_none());
+
+        // abandoned preparation doesn't affect the generated work
+        let work = engine.next_work().unwrap();
+        assert_eq!(test_utils::TEST_BLOCKS[0].midstate, work.midstates[0].state);
+    }
+
     fn get_block_version(job: &Arc<test_utils::TestBlock>, version_index: u32) -> u32 {
         job.version() | (version_index << ii_bitcoin::BIP320_VERSION_SHIFT)
     }
@@ -0,0 +1,302 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Compares encoding of responses by the current encoder, which serializes sections once when
+//! the response is built and copies them into a reused `support::ResponseBuffer`, with the former
+//! encoder which converted the sections into an `ordered::Value` tree serialized by `serde_json`.
+//! Both encoders start with the typed response returned by the handler so that the conversion of
+//! the sections is measured too. Besides the time per response it reports the number of
+//! allocations and allocated bytes per response counted by the global allocator.
+//!
+//! Run with `cargo bench`.
+
+use ii_cgminer_api::support::{ResponseBuffer, ResponseType};
+use ii_cgminer_api::{json, ordered, response, Format};
+
+use serde::{Serialize, Serializer};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Number of devices and pools in the benchmarked responses
+const DEVICE_COUNT: usize = 64;
+/// Number of chips reported by each device in `stats` (i.e. a hash board of Antminer S9)
+const CHIP_COUNT: usize = 63;
+const ITERATIONS: usize = 2000;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[derive(Default)]
+struct Measurement {
+    elapsed: Duration,
+    allocations: usize,
+    allocated: usize,
+}
+
+impl Measurement {
+    fn report(&self, command: &str, encoder: &str) {
+        let iterations = ITERATIONS as u32;
+        println!(
+            "{:<10} {:<15} {:>10.2?}/response {:>8} allocations {:>10} bytes",
+            command,
+            encoder,
+            self.elapsed / iterations,
+            self.allocations / ITERATIONS,
+            self.allocated / ITERATIONS,
+        );
+    }
+}
+
+/// Measures encoding of the response by `encode` excluding the handling of the request
+fn measure<F>(mut encode: F) -> Measurement
+where
+    F: FnMut() -> usize,
+{
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let mut len = 0;
+    for _ in 0..ITERATIONS {
+        len += encode();
+    }
+    let elapsed = start.elapsed();
+    assert!(len > 0);
+    Measurement {
+        elapsed,
+        allocations: ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        allocated: ALLOCATED.load(Ordering::Relaxed) - allocated,
+    }
+}
+
+/// Response encoded by the former encoder
+struct TreeResponse<'a> {
+    status_info: &'a response::StatusInfo,
+    name: &'static str,
+    sections: Vec<ordered::Value>,
+}
+
+impl Serialize for TreeResponse<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("STATUS", &[self.status_info])?;
+        map.serialize_entry(self.name, &self.sections)?;
+        map.serialize_entry("id", &1)?;
+        map.end()
+    }
+}
+
+/// Converts typed sections into the tree as the former encoder did when the response was built
+fn tree<S: Serialize>(sections: &[S]) -> Vec<ordered::Value> {
+    sections
+        .iter()
+        .map(|section| ordered::to_value(section).expect("BUG: cannot convert section"))
+        .collect()
+}
+
+fn single_response<R>(typed: R) -> ResponseType
+where
+    R: Into<response::Dispatch>,
+{
+    let signature = "Bench".to_string();
+    let description = "Bench v1.0".to_string();
+    ResponseType::Single(
+        typed
+            .into()
+            .into_response(0, None, &signature, &description),
+    )
+}
+
+/// Measures both encoders with the `typed` response of the `command`. The `sections` of the
+/// typed response are converted by the former encoder.
+fn compare<R, F>(command: &str, typed: &R, sections: F)
+where
+    R: Into<response::Dispatch> + Clone,
+    F: Fn(&R) -> Vec<ordered::Value>,
+{
+    let response = single_response(typed.clone());
+    let (status_info, name) = match &response {
+        ResponseType::Single(response) => (
+            response.status_info.clone(),
+            response.body.as_ref().expect("BUG: missing body").0,
+        ),
+        _ => unreachable!("BUG: unexpected response type"),
+    };
+    let encode_tree = |typed: &R| {
+        let response = TreeResponse {
+            status_info: &status_info,
+            name,
+            sections: sections(typed),
+        };
+        let mut encoded = json::to_vec(&response).expect("BUG: cannot serialize response");
+        encoded.push(0);
+        encoded
+    };
+    let mut buffer = ResponseBuffer::new();
+    assert_eq!(
+        encode_tree(typed),
+        buffer
+            .encode(&response, Format::Json)
+            .expect("BUG: cannot encode response"),
+        "BUG: encoders differ"
+    );
+
+    let former = measure(|| encode_tree(&typed.clone()).len());
+    let current = measure(|| {
+        buffer
+            .encode(&single_response(typed.clone()), Format::Json)
+            .expect("BUG: cannot encode response")
+            .len()
+    });
+    former.report(command, "ordered::Value");
+    current.report(command, "ResponseBuffer");
+}
+
+fn sections<T>(sample: json::Value) -> Vec<T>
+where
+    T: serde::de::DeserializeOwned + Clone,
+{
+    let section = json::from_value(sample).expect("BUG: invalid sample section");
+    vec![section; DEVICE_COUNT]
+}
+
+fn stats_header(idx: usize) -> response::StatsHeader {
+    response::StatsHeader {
+        idx: idx as i32,
+        id: format!("BC5{}", idx),
+        elapsed: 86_400,
+        calls: 0,
+        wait: 0.0,
+        max: 0.0,
+        min: 99_999_999.0,
+    }
+}
+
+fn asc_stats() -> Vec<response::AscStats> {
+    (0..DEVICE_COUNT)
+        .map(|idx| response::AscStats {
+            header: stats_header(idx),
+            chips: response::Chips(
+                (0..CHIP_COUNT)
+                    .map(|chip| response::ChipStats {
+                        frequency: 650.0,
+                        mhs: 222.5 + chip as f64,
+                        hardware_errors: chip as u32,
+                        temperature: Some(65.5),
+                    })
+                    .collect(),
+            ),
+        })
+        .collect()
+}
+
+fn pool_stats() -> Vec<response::PoolStats> {
+    (0..DEVICE_COUNT)
+        .map(|idx| response::PoolStats {
+            header: stats_header(DEVICE_COUNT + idx),
+            pool_calls: 0,
+            pool_attempts: 0,
+            pool_wait: 0.0,
+            pool_max: 0.0,
+            pool_min: 99_999_999.0,
+            pool_av: 0.0,
+            work_had_roll_time: false,
+            work_can_roll: false,
+            work_had_expire: false,
+            work_roll_time: 0,
+            work_diff: 65_536.0,
+            min_diff: 65_536.0,
+            max_diff: 65_536.0,
+            min_diff_count: 1200,
+            max_diff_count: 1200,
+            times_sent: 1500,
+            bytes_sent: 150_000,
+            times_recv: 3000,
+            bytes_recv: 900_000,
+            net_bytes_sent: 150_000,
+            net_bytes_recv: 900_000,
+            redundant_jobs: 12,
+        })
+        .collect()
+}
+
+fn main() {
+    let dev: json::Value =
+        json::from_str(include_str!("../src/test/compat/cgminer-4.11.1/devs.json"))
+            .expect("BUG: invalid sample");
+    let pool: json::Value =
+        json::from_str(include_str!("../src/test/compat/cgminer-4.11.1/pools.json"))
+            .expect("BUG: invalid sample");
+
+    let pools = response::Pools {
+        list: sections(pool["POOLS"][0].clone()),
+    };
+    compare("pools", &pools, |pools| tree(&pools.list));
+    let devs = response::Devs {
+        list: sections(dev["DEVS"][0].clone()),
+    };
+    compare("devs", &devs, |devs| tree(&devs.list));
+    // Sections of ASC devices are followed by sections of pools as in `response::Stats`
+    let stats_sections = |stats: &response::Stats| {
+        let mut sections = tree(&stats.asc_stats);
+        sections.extend(tree(&stats.pool_stats));
+        sections
+    };
+    let stats = response::Stats {
+        asc_stats: asc_stats(),
+        pool_stats: pool_stats(),
+    };
+    compare("stats", &stats, stats_sections);
+    let estats = response::Stats {
+        asc_stats: asc_stats(),
+        pool_stats: vec![],
+    };
+    compare("estats", &estats, stats_sections);
+}
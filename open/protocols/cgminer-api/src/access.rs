@@ -0,0 +1,301 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Access control of API clients based on their IP address compatible with `--api-allow` option
+//! of CGMiner
+//!
+//! Clients which cannot be distinguished by their address (e.g. behind NAT) can be granted
+//! privilege by shared secret `Tokens` sent in the `token` field of JSON requests. Clients with
+//! persistent connections can send the token just once with the `auth` command instead (see
+//! `Session`).
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Privilege level of a client
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+pub enum Privilege {
+    /// Only commands which do not change the state of the miner are allowed
+    Read,
+    /// All commands are allowed
+    Write,
+}
+
+/// Error of parsing network or access control rules
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid access rule '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Range of IP addresses described by the network address and length of the prefix
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Network {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Network {
+    /// Builds a network from any address in it. Host bits of the `addr` are cleared. Returns
+    /// `None` when the prefix is longer than the address.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<Self> {
+        let addr = match addr {
+            IpAddr::V4(addr) => {
+                let mask = match prefix_len {
+                    0 => 0,
+                    1..=32 => !0u32 << (32 - prefix_len),
+                    _ => return None,
+                };
+                IpAddr::V4((u32::from(addr) & mask).into())
+            }
+            IpAddr::V6(addr) => {
+                let mask = match prefix_len {
+                    0 => 0,
+                    1..=128 => !0u128 << (128 - prefix_len),
+                    _ => return None,
+                };
+                IpAddr::V6((u128::from(addr) & mask).into())
+            }
+        };
+        Some(Self { addr, prefix_len })
+    }
+
+    /// IPv4 clients connected to IPv6 socket have IPv4-mapped address (`::ffff:a.b.c.d`)
+    fn unmap(addr: IpAddr) -> IpAddr {
+        match addr {
+            IpAddr::V6(v6) => match v6.segments() {
+                [0, 0, 0, 0, 0, 0xffff, high, low] => {
+                    IpAddr::V4(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low)))
+                }
+                _ => addr,
+            },
+            _ => addr,
+        }
+    }
+
+    /// Checks whether `addr` belongs to the network
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, Self::unmap(addr)) {
+            (IpAddr::V4(_), addr @ IpAddr::V4(_)) | (IpAddr::V6(_), addr @ IpAddr::V6(_)) => {
+                Self::new(addr, self.prefix_len) == Some(*self)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parses `IP[/Prefix]`. Missing prefix describes a single address.
+impl FromStr for Network {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseError(s.to_string());
+        let mut parts = s.trim().splitn(2, '/');
+        let addr: IpAddr = parts
+            .next()
+            .unwrap_or_default()
+            .parse()
+            .map_err(|_| error())?;
+        let prefix_len = match parts.next() {
+            Some(prefix_len) => prefix_len.parse().map_err(|_| error())?,
+            None => match addr {
+                IpAddr::V4(_) => 32,
+                IpAddr::V6(_) => 128,
+            },
+        };
+        Self::new(addr, prefix_len).ok_or_else(error)
+    }
+}
+
+/// List of networks allowed to access the API. Each network grants read or write privilege.
+#[derive(Default, Clone, Debug)]
+pub struct AccessControl {
+    rules: Vec<(Network, Privilege)>,
+}
+
+impl AccessControl {
+    /// Special rule of CGMiner matching all addresses
+    const ANY: &'static str = "0/0";
+    const WRITE_GROUP: &'static str = "W:";
+    const READ_GROUP: &'static str = "R:";
+
+    /// Creates access control which refuses all clients
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows clients from the `network` to access the API with the `privilege`
+    pub fn allow(mut self, network: Network, privilege: Privilege) -> Self {
+        self.rules.push((network, privilege));
+        self
+    }
+
+    /// Returns the highest privilege granted to a client at `addr` or `None` when the client is
+    /// not allowed at all
+    pub fn privilege(&self, addr: IpAddr) -> Option<Privilege> {
+        self.rules
+            .iter()
+            .filter(|(network, _)| network.contains(addr))
+            .map(|(_, privilege)| *privilege)
+            .max()
+    }
+}
+
+/// Shared secrets granting privilege to clients which send them with the request (see
+/// `command::Receiver::with_tokens`). The tokens are never printed.
+#[derive(Default, Clone)]
+pub struct Tokens {
+    tokens: Vec<(Vec<u8>, Privilege)>,
+}
+
+impl Tokens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grants the `privilege` to clients sending the `token`
+    pub fn add<T: Into<String>>(mut self, token: T, privilege: Privilege) -> Self {
+        self.tokens.push((token.into().into_bytes(), privilege));
+        self
+    }
+
+    /// Returns the highest privilege granted by the `token` or `None` when it is not valid. All
+    /// tokens are compared in constant time so that the response time does not reveal how much
+    /// of a token has been guessed.
+    pub fn privilege(&self, token: &str) -> Option<Privilege> {
+        let token = token.as_bytes();
+        self.tokens
+            .iter()
+            .filter(|(valid, _)| constant_time_eq(valid, token))
+            .map(|(_, privilege)| *privilege)
+            .max()
+    }
+}
+
+impl fmt::Debug for Tokens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Tokens({} configured)", self.tokens.len())
+    }
+}
+
+/// Privilege granted to a persistent connection by the `auth` command so that the client does not
+/// have to send the token with every request (see `command::Context::with_session`). The granted
+/// privilege expires when the connection has been idle for longer than the idle timeout of the
+/// receiver.
+#[derive(Default, Debug)]
+pub struct Session {
+    granted: Mutex<Option<Grant>>,
+}
+
+#[derive(Debug)]
+struct Grant {
+    privilege: Privilege,
+    last_used: Instant,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grants the `privilege` to the following requests of the connection
+    pub(crate) fn grant(&self, privilege: Privilege) {
+        *self.granted.lock().expect("BUG: poisoned session") = Some(Grant {
+            privilege,
+            last_used: Instant::now(),
+        });
+    }
+
+    /// Drops the granted privilege
+    pub fn revoke(&self) {
+        *self.granted.lock().expect("BUG: poisoned session") = None;
+    }
+
+    /// Returns the granted privilege unless the session has been idle for `idle_timeout`. Using
+    /// the privilege postpones its expiration.
+    pub fn privilege(&self, idle_timeout: Duration) -> Option<Privilege> {
+        self.privilege_at(idle_timeout, Instant::now())
+    }
+
+    pub(crate) fn privilege_at(&self, idle_timeout: Duration, now: Instant) -> Option<Privilege> {
+        let mut granted = self.granted.lock().expect("BUG: poisoned session");
+        match granted.as_mut() {
+            Some(grant) if now.saturating_duration_since(grant.last_used) < idle_timeout => {
+                grant.last_used = now;
+                Some(grant.privilege)
+            }
+            _ => {
+                *granted = None;
+                None
+            }
+        }
+    }
+}
+
+/// Compares byte strings without exiting early on the first difference. Only the length of the
+/// strings may be revealed.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Parses comma separated list of `[W:|R:]IP[/Prefix]` rules as found in `--api-allow` option of
+/// CGMiner. Networks without a group grant read privilege and `0/0` matches all addresses.
+impl FromStr for AccessControl {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut access_control = Self::new();
+        for rule in s.split(',').map(str::trim) {
+            let (privilege, network) = match rule.strip_prefix(Self::WRITE_GROUP) {
+                Some(network) => (Privilege::Write, network),
+                None => (
+                    Privilege::Read,
+                    rule.strip_prefix(Self::READ_GROUP).unwrap_or(rule),
+                ),
+            };
+
+            if network == Self::ANY {
+                access_control = access_control
+                    .allow(
+                        Network::new(Ipv4Addr::UNSPECIFIED.into(), 0).unwrap(),
+                        privilege,
+                    )
+                    .allow(
+                        Network::new(Ipv6Addr::UNSPECIFIED.into(), 0).unwrap(),
+                        privilege,
+                    );
+            } else {
+                let network = network.parse().map_err(|_| ParseError(rule.to_string()))?;
+                access_control = access_control.allow(network, privilege);
+            }
+        }
+        Ok(access_control)
+    }
+}
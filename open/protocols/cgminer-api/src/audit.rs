@@ -0,0 +1,245 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Audit log of commands changing state of the miner (see `command::Receiver::with_audit_sink`)
+//!
+//! Every invocation of a privileged command (see `command::Descriptor::privileged`) is recorded
+//! after it has been handled including the invocations refused by access control. Read-only
+//! commands are never recorded.
+//!
+//! `FileAuditSink` appends the entries as JSON lines to a file rotated by its size. The file is
+//! written by a background task so that the responses are not delayed. Entries which do not fit
+//! into its bounded queue are dropped and counted.
+
+use crate::json;
+use crate::observer::ResponseStatus;
+use crate::response;
+
+use ii_logging::macros::*;
+
+use ii_async_compat::tokio;
+use tokio::io::AsyncWriteExt as _;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use serde::Serialize;
+
+use std::ffi::OsString;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Record of a single invocation of a mutating command
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct AuditEntry {
+    pub when: response::Time,
+    /// Address of the client or `None` for local clients
+    pub client: Option<IpAddr>,
+    pub command: String,
+    /// Parameter with credentials replaced (see `command::Descriptor::redacted`)
+    pub parameter: Option<json::Value>,
+    pub status: response::Status,
+    /// Status code and message as reported to the client. `quit` and `restart` are responded
+    /// just with their action (e.g. `BYE`) which is recorded as the message with zero code.
+    pub code: u32,
+    pub msg: String,
+}
+
+impl AuditEntry {
+    pub(crate) fn new(
+        when: response::Time,
+        client: Option<IpAddr>,
+        command: &str,
+        parameter: Option<&json::Value>,
+        status: &ResponseStatus,
+    ) -> Self {
+        Self {
+            when,
+            client,
+            command: command.to_string(),
+            parameter: parameter.cloned(),
+            status: status.status,
+            code: status.code,
+            msg: status.msg.clone(),
+        }
+    }
+}
+
+/// Destination of audit entries. It is invoked from the task handling the request so it must
+/// not block.
+pub trait AuditSink: Send + Sync {
+    fn record(&self, entry: AuditEntry);
+}
+
+/// Shared audit sink as registered in the receiver
+pub type SharedAuditSink = Arc<dyn AuditSink>;
+
+/// Limits of the audit log file
+#[derive(Copy, Clone, Debug)]
+pub struct Rotation {
+    /// The file is rotated before it would exceed this size in bytes
+    pub max_size: u64,
+    /// Number of rotated files kept as `<path>.1` (the newest) to `<path>.<max_files>`
+    pub max_files: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self {
+            max_size: 1024 * 1024,
+            max_files: 4,
+        }
+    }
+}
+
+/// Audit sink appending entries as JSON lines to a file
+pub struct FileAuditSink {
+    sender: Mutex<mpsc::Sender<AuditEntry>>,
+    dropped: AtomicU64,
+    writer: JoinHandle<()>,
+}
+
+impl FileAuditSink {
+    /// Default number of entries waiting for the writer
+    pub const DEFAULT_QUEUE_CAPACITY: usize = 256;
+
+    /// Opens the file at `path` for appending and starts its writer on the current runtime
+    pub async fn open<P>(path: P, rotation: Rotation, queue_capacity: usize) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let writer = Writer::open(path.as_ref().to_path_buf(), rotation).await?;
+        let (sender, receiver) = mpsc::channel(queue_capacity);
+        Ok(Self {
+            sender: Mutex::new(sender),
+            dropped: AtomicU64::new(0),
+            writer: tokio::spawn(writer.run(receiver)),
+        })
+    }
+
+    /// Number of entries dropped because the queue has been full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Writes all queued entries and stops the writer
+    pub async fn close(self) {
+        drop(self.sender);
+        if self.writer.await.is_err() {
+            error!("CGMiner API: audit log writer panicked");
+        }
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn record(&self, entry: AuditEntry) {
+        let sent = self
+            .sender
+            .lock()
+            .expect("BUG: audit sink lock poisoned")
+            .try_send(entry);
+        if sent.is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Background writer of the audit log file
+struct Writer {
+    path: PathBuf,
+    rotation: Rotation,
+    file: tokio::fs::File,
+    size: u64,
+}
+
+impl Writer {
+    async fn open(path: PathBuf, rotation: Rotation) -> io::Result<Self> {
+        let file = Self::open_file(&path).await?;
+        let size = file.metadata().await?.len();
+        Ok(Self {
+            path,
+            rotation,
+            file,
+            size,
+        })
+    }
+
+    async fn open_file(path: &Path) -> io::Result<tokio::fs::File> {
+        tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+    }
+
+    /// Path of the rotated file with `index` starting from 1
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = OsString::from(self.path.as_os_str());
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    /// Shifts the rotated files dropping the oldest one and starts a new file
+    async fn rotate(&mut self) -> io::Result<()> {
+        if self.rotation.max_files == 0 {
+            tokio::fs::remove_file(&self.path).await?;
+        } else {
+            for index in (1..self.rotation.max_files).rev() {
+                let from = self.rotated_path(index);
+                if tokio::fs::metadata(&from).await.is_ok() {
+                    tokio::fs::rename(&from, self.rotated_path(index + 1)).await?;
+                }
+            }
+            tokio::fs::rename(&self.path, self.rotated_path(1)).await?;
+        }
+        self.file = Self::open_file(&self.path).await?;
+        self.size = 0;
+        Ok(())
+    }
+
+    async fn write(&mut self, entry: &AuditEntry) -> io::Result<()> {
+        let mut line = json::to_vec(entry).expect("BUG: cannot serialize audit entry");
+        line.push(b'\n');
+        let len = line.len() as u64;
+        if self.size > 0 && self.size + len > self.rotation.max_size {
+            self.rotate().await?;
+        }
+        self.file.write_all(&line).await?;
+        self.size += len;
+        Ok(())
+    }
+
+    async fn run(mut self, mut receiver: mpsc::Receiver<AuditEntry>) {
+        while let Some(entry) = receiver.recv().await {
+            if let Err(e) = self.write(&entry).await {
+                error!(
+                    "CGMiner API: cannot write audit log '{}' ({})",
+                    self.path.display(),
+                    e
+                );
+            }
+        }
+        let _ = self.file.flush().await;
+    }
+}
@@ -0,0 +1,305 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Client of the CGMiner API
+//!
+//! Every request is sent over a new connection because the server closes the connection after
+//! the response which is terminated by a NUL byte.
+
+use crate::response;
+
+use futures::FutureExt as _;
+use ii_async_compat::{futures, tokio};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use serde::de::DeserializeOwned;
+use serde_json as json;
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Responses are never this large even with many devices and pools
+const MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Error of a command sent by `Client`
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The response is not a valid CGMiner API response
+    InvalidResponse(String),
+    /// The command failed with error status
+    Status {
+        code: u32,
+        msg: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "connection failed ({})", e),
+            Error::InvalidResponse(reason) => write!(f, "invalid response ({})", reason),
+            Error::Status { code, msg } => write!(f, "command failed with code {}: {}", code, msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Failure of a single exchange of a request and its response
+enum ExchangeError {
+    /// The request has not been written completely
+    Write(io::Error),
+    Failed(Error),
+}
+
+impl From<ExchangeError> for Error {
+    fn from(e: ExchangeError) -> Self {
+        match e {
+            ExchangeError::Write(e) => Error::Io(e),
+            ExchangeError::Failed(e) => e,
+        }
+    }
+}
+
+/// Successful response of a single command
+#[derive(Clone, Debug)]
+pub struct Reply {
+    value: json::Value,
+}
+
+impl Reply {
+    /// Checks the status of a single response
+    fn new(value: json::Value) -> Result<Self> {
+        let status = &value["STATUS"][0];
+        let code = status["Code"]
+            .as_u64()
+            .ok_or_else(|| Error::InvalidResponse("missing status".to_string()))?;
+        match status["STATUS"].as_str() {
+            Some("S") | Some("I") | Some("W") => Ok(Self { value }),
+            _ => Err(Error::Status {
+                code: code as u32,
+                msg: status["Msg"].as_str().unwrap_or_default().to_string(),
+            }),
+        }
+    }
+
+    /// The whole response
+    pub fn value(&self) -> &json::Value {
+        &self.value
+    }
+
+    /// Status code of the response
+    pub fn code(&self) -> u32 {
+        self.value["STATUS"][0]["Code"].as_u64().unwrap_or_default() as u32
+    }
+
+    /// Deserializes all items of the section `name` (e.g. `POOLS`)
+    pub fn section<T: DeserializeOwned>(&self, name: &str) -> Result<Vec<T>> {
+        json::from_value(self.value.get(name).cloned().unwrap_or_default())
+            .map_err(|e| Error::InvalidResponse(format!("section {}: {}", name, e)))
+    }
+
+    /// Deserializes the only item of the section `name` (e.g. `SUMMARY`)
+    pub fn single<T: DeserializeOwned>(&self, name: &str) -> Result<T> {
+        self.section(name)?
+            .pop()
+            .ok_or_else(|| Error::InvalidResponse(format!("missing section {}", name)))
+    }
+}
+
+/// Client sending commands to the API server listening on a TCP address
+pub struct Client {
+    addr: SocketAddr,
+    timeout: Duration,
+    /// Connection established in advance for the next command
+    stream: Option<TcpStream>,
+}
+
+impl Client {
+    /// Time limit of connecting and of obtaining the whole response
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Connects to the server at `addr` so that unreachable servers are detected right away
+    pub async fn connect(addr: SocketAddr) -> Result<Self> {
+        let mut client = Self {
+            addr,
+            timeout: Self::DEFAULT_TIMEOUT,
+            stream: None,
+        };
+        client.stream = Some(client.open().await?);
+        Ok(client)
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn open(&self) -> Result<TcpStream> {
+        tokio::time::timeout(self.timeout, TcpStream::connect(self.addr))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+            .map_err(Into::into)
+    }
+
+    /// Returns the connection established in advance unless it has been closed by the server
+    /// (e.g. because of its read timeout) or it is unusable for another reason. The server never
+    /// sends anything before it gets a request.
+    fn usable(mut stream: TcpStream) -> Option<TcpStream> {
+        let mut byte = [0u8; 1];
+        match stream.read(&mut byte).now_or_never() {
+            Some(_) => None,
+            None => Some(stream),
+        }
+    }
+
+    /// Sends the `request` over the `stream` and reads the response terminated by NUL byte or by
+    /// closing the connection
+    async fn exchange(
+        &self,
+        mut stream: TcpStream,
+        request: &[u8],
+    ) -> std::result::Result<Vec<u8>, ExchangeError> {
+        let exchange = async move {
+            stream
+                .write_all(request)
+                .await
+                .map_err(ExchangeError::Write)?;
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 4096];
+            loop {
+                let len = stream
+                    .read(&mut chunk)
+                    .await
+                    .map_err(|e| ExchangeError::Failed(e.into()))?;
+                if len == 0 {
+                    break;
+                }
+                buf.extend_from_slice(&chunk[..len]);
+                if buf.contains(&0) || buf.len() > MAX_RESPONSE_SIZE {
+                    break;
+                }
+            }
+            Ok(buf)
+        };
+        tokio::time::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| ExchangeError::Failed(io::Error::from(io::ErrorKind::TimedOut).into()))?
+    }
+
+    async fn request(&mut self, request: json::Value) -> Result<json::Value> {
+        let request = request.to_string();
+        // Connection established in advance may have been closed by the server meanwhile
+        let (stream, reused) = match self.stream.take().and_then(Self::usable) {
+            Some(stream) => (stream, true),
+            None => (self.open().await?, false),
+        };
+        let mut buf = match self.exchange(stream, request.as_bytes()).await {
+            // The server has not got the request over the old connection so it is safe to send
+            // it again. The request is never repeated once it could have been handled (e.g.
+            // after a timeout).
+            Err(ExchangeError::Write(_)) if reused => {
+                let stream = self.open().await?;
+                self.exchange(stream, request.as_bytes()).await
+            }
+            result => result,
+        }
+        .map_err(Error::from)?;
+        if let Some(end) = buf.iter().position(|&byte| byte == 0) {
+            buf.truncate(end);
+        }
+        json::from_slice(&buf).map_err(|e| Error::InvalidResponse(e.to_string()))
+    }
+
+    async fn send(&mut self, name: &str, parameter: Option<json::Value>) -> Result<json::Value> {
+        let mut request = json::json!({ "command": name });
+        if let Some(parameter) = parameter {
+            request["parameter"] = parameter;
+        }
+        self.request(request).await
+    }
+
+    /// Sends the command `name` with optional `parameter`
+    pub async fn command(&mut self, name: &str, parameter: Option<json::Value>) -> Result<Reply> {
+        Reply::new(self.send(name, parameter).await?)
+    }
+
+    /// Sends multiple commands in one request with the shared `parameter`. The results keep
+    /// the order of the `commands` which have to be canonical names (not aliases). Note that
+    /// the server refuses privileged commands in batched requests.
+    pub async fn batch(
+        &mut self,
+        commands: &[&str],
+        parameter: Option<json::Value>,
+    ) -> Result<Vec<Result<Reply>>> {
+        let value = self.send(&commands.join("+"), parameter).await?;
+        // Request with a single command is responded as a plain command
+        if commands.len() == 1 {
+            return Ok(vec![Reply::new(value)]);
+        }
+        // The whole request has been refused
+        if value.get("STATUS").is_some() {
+            Reply::new(value)?;
+            return Err(Error::InvalidResponse(
+                "missing responses of commands".to_string(),
+            ));
+        }
+        Ok(commands
+            .iter()
+            .map(|command| match value[command].get(0) {
+                Some(value) => Reply::new(value.clone()),
+                None => Err(Error::InvalidResponse(format!(
+                    "missing response of {}",
+                    command
+                ))),
+            })
+            .collect())
+    }
+
+    pub async fn summary(&mut self) -> Result<response::Summary> {
+        self.command("summary", None).await?.single("SUMMARY")
+    }
+
+    pub async fn devs(&mut self) -> Result<Vec<response::Asc>> {
+        self.command("devs", None).await?.section("DEVS")
+    }
+
+    pub async fn pools(&mut self) -> Result<Vec<response::Pool>> {
+        self.command("pools", None).await?.section("POOLS")
+    }
+
+    pub async fn asc(&mut self, idx: i32) -> Result<response::Asc> {
+        self.command("asc", Some(idx.into())).await?.single("ASC")
+    }
+}
@@ -22,18 +22,40 @@
 
 //! Defines the API command handler (`Handler`)
 
+use crate::access::{AccessControl, Privilege, Session, Tokens};
+use crate::audit::{AuditEntry, SharedAuditSink};
+use crate::delta::{self, Snapshots};
+use crate::events::{EventSink, Subscription};
+use crate::history::{self, History};
+use crate::observer::{self, LoggingObserver, SharedObserver};
 use crate::response;
-use crate::support::ValueExt as _;
-use crate::support::{MultiResponse, ResponseType, UnixTime, When};
+use crate::schema;
+use crate::server;
+use crate::support::{
+    self, ActionResponse, AddPoolParameter, AscSetParameter, MultiResponse, ResponseType,
+    StatsFilter, UnixTime, When, REDACTED,
+};
+use crate::text;
+use crate::{Codec, Format};
 
+use serde::Deserialize;
 use serde_json as json;
 
-use ii_async_compat::futures::Future;
+use ii_logging::macros::*;
+
+use ii_async_compat::futures::{self, Future, FutureExt as _};
+use ii_async_compat::tokio;
+use ii_async_compat::{bytes::BytesMut, tokio_util::codec::Encoder as _};
 
 use std::collections::HashMap;
+use std::fmt;
 use std::marker;
+use std::net::IpAddr;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
 
 /// List of all supported commands.
 const POOLS: &str = "pools";
@@ -43,17 +65,41 @@ const SUMMARY: &str = "summary";
 const VERSION: &str = "version";
 const SWITCH_POOL: &str = "switchpool";
 const CONFIG: &str = "config";
+const SET_CONFIG: &str = "setconfig";
+const RELOAD_CONFIG: &str = "reloadconfig";
 const ENABLE_POOL: &str = "enablepool";
 const DISABLE_POOL: &str = "disablepool";
 const ADD_POOL: &str = "addpool";
 const REMOVE_POOL: &str = "removepool";
+const POOL_PRIORITY: &str = "poolpriority";
+const FAILOVER_ONLY: &str = "failover-only";
+const ZERO: &str = "zero";
+const PAUSE: &str = "pause";
+const RESUME: &str = "resume";
+pub const LOCATE: &str = "locate";
+const NOTIFY: &str = "notify";
 const STATS: &str = "stats";
 const ESTATS: &str = "estats";
 const CHECK: &str = "check";
+const HELP: &str = "help";
+const API_STATS: &str = "apistats";
+const SCHEMA: &str = "schema";
 const COIN: &str = "coin";
+const COIN_MINE: &str = "coinmine";
 const ASC_COUNT: &str = "asccount";
 const ASC: &str = "asc";
+pub const ASC_ENABLE: &str = "ascenable";
+pub const ASC_DISABLE: &str = "ascdisable";
+pub const ASCSET: &str = "ascset";
 const LCD: &str = "lcd";
+pub const TUNERSTATUS: &str = "tunerstatus";
+const DEBUG: &str = "debug";
+const EVENTS: &str = "events";
+const CONNECTIONS: &str = "connections";
+const LAST_COMMANDS: &str = "lastcommands";
+const AUTH: &str = "auth";
+const QUIT: &str = "quit";
+const RESTART: &str = "restart";
 
 // List of all standard commands which can be optionally implemented.
 pub const DEVDETAILS: &str = "devdetails";
@@ -62,53 +108,450 @@ pub const DEVDETAILS: &str = "devdetails";
 pub const TEMPCTRL: &str = "tempctrl";
 pub const TEMPS: &str = "temps";
 pub const FANS: &str = "fans";
+pub const FANCTRL: &str = "fanctrl";
+pub const SHARELOG: &str = "sharelog";
+pub const POOLSTATS: &str = "poolstats";
+
+/// Field of the request with shared secret of the client
+const TOKEN_FIELD: &str = "token";
+
+/// Delimiter of commands of a batched request (e.g. `summary+pools`)
+const BATCH_DELIMITER: char = '+';
+
+/// Commands supporting delta mode (see `Receiver::with_delta_mode`)
+const DELTA_COMMANDS: [&str; 2] = [DEVS, SUMMARY];
 
 pub type Result<T> = std::result::Result<T, response::Error>;
 /// Type describing command table
 pub type Map = HashMap<&'static str, Descriptor>;
+/// Type describing alternative names of commands mapped to their canonical names
+pub type AliasMap = HashMap<&'static str, &'static str>;
 
-/// A handler to be implemented by the API implementation,
-/// takes care of producing a response for each command.
-#[async_trait::async_trait]
-pub trait Handler: Send + Sync {
-    async fn handle_pools(&self) -> Result<response::Pools>;
-    async fn handle_devs(&self) -> Result<response::Devs>;
-    async fn handle_edevs(&self) -> Result<response::Devs>;
-    async fn handle_summary(&self) -> Result<response::Summary>;
-    async fn handle_switch_pool(
-        &self,
-        parameter: Option<&json::Value>,
-    ) -> Result<response::SwitchPool>;
-    async fn handle_config(&self) -> Result<response::Config>;
-    async fn handle_add_pool(&self, parameter: Option<&json::Value>) -> Result<response::AddPool>;
-    async fn handle_enable_pool(
-        &self,
-        parameter: Option<&json::Value>,
-    ) -> Result<response::EnablePool>;
-    async fn handle_disable_pool(
-        &self,
-        parameter: Option<&json::Value>,
-    ) -> Result<response::DisablePool>;
-    async fn handle_remove_pool(
-        &self,
-        parameter: Option<&json::Value>,
-    ) -> Result<response::RemovePool>;
-    async fn handle_stats(&self) -> Result<response::Stats>;
-    async fn handle_estats(&self) -> Result<response::Stats>;
-    async fn handle_coin(&self) -> Result<response::Coin>;
-    async fn handle_asc_count(&self) -> Result<response::AscCount>;
-    async fn handle_asc(&self, parameter: Option<&json::Value>) -> Result<response::Asc>;
-    async fn handle_lcd(&self) -> Result<response::Lcd>;
+/// Invokes `$callback` with all methods of `Handler` split into groups of related commands. The
+/// methods are declared only here and both `Handler` and the groups of `composite` module are
+/// generated from the list. Each group also names the field and the type parameter of
+/// `composite::CompositeHandler` which provides it. Methods with default implementation name an
+/// async function which takes the handler.
+macro_rules! handler_methods {
+    ($callback:ident) => {
+        $callback! {
+            /// Pool commands (`pools`, `switchpool`, `addpool`, ...)
+            PoolHandler(pools: P) {
+                fn handle_pools(&self) -> Result<$crate::response::Pools>;
+                fn handle_switch_pool(&self, idx: i32) -> Result<$crate::response::SwitchPool>;
+                fn handle_add_pool(
+                    &self,
+                    parameter: $crate::support::AddPoolParameter
+                ) -> Result<$crate::response::AddPool>;
+                fn handle_enable_pool(&self, idx: i32) -> Result<$crate::response::EnablePool>;
+                fn handle_disable_pool(&self, idx: i32) -> Result<$crate::response::DisablePool>;
+                fn handle_remove_pool(&self, idx: i32) -> Result<$crate::response::RemovePool>;
+                fn handle_pool_priority(
+                    &self,
+                    order: Vec<usize>
+                ) -> Result<$crate::response::PoolPriority>;
+                fn handle_failover_only(
+                    &self,
+                    enabled: bool
+                ) -> Result<$crate::response::FailoverOnly>;
+            }
+            /// Commands of the whole miner (`summary`, `config`, `reloadconfig`, `pause`, ...)
+            MinerHandler(miner: M) {
+                fn handle_summary(&self) -> Result<$crate::response::Summary>;
+                fn handle_config(&self) -> Result<$crate::response::Config>;
+                fn handle_set_config(
+                    &self,
+                    name: String,
+                    value: String
+                ) -> Result<$crate::response::SetConfig>;
+                /// Reads the configuration of the miner again and applies the changed settings.
+                /// Miners reading the configuration only on start do not support it by default
+                /// (see `default_reload_config`).
+                fn handle_reload_config(&self) -> Result<$crate::response::ReloadConfig> =
+                    $crate::command::default_reload_config;
+                fn handle_zero(
+                    &self,
+                    target: $crate::support::ZeroTarget,
+                    return_summary: bool
+                ) -> Result<$crate::response::Zero>;
+                fn handle_pause(&self) -> Result<$crate::response::Pause>;
+                fn handle_resume(&self) -> Result<$crate::response::Resume>;
+                fn handle_coin(&self) -> Result<$crate::response::Coin>;
+                fn handle_notify(&self) -> Result<$crate::response::Notifies>;
+                fn handle_debug(
+                    &self,
+                    flag: Option<$crate::support::DebugFlag>
+                ) -> Result<$crate::response::Debug>;
+                /// Named versions of miner components (e.g. firmware or FPGA bitstream) which are
+                /// appended to the `version` response in the returned order (see
+                /// `response::VersionInfo::merge_fields`)
+                fn handle_version_extra(&self) -> Result<Vec<(String, String)>> =
+                    $crate::command::no_version_extra;
+            }
+            /// Commands of the hashing devices (`devs`, `stats`, `asc`, `locate`, ...)
+            DeviceHandler(devices: D) {
+                fn handle_devs(&self, idx: Option<i32>) -> Result<$crate::response::Devs>;
+                fn handle_edevs(&self) -> Result<$crate::response::Devs>;
+                /// The `filter` selects sections of the response (see `response::Stats::select`).
+                /// Per-chip details (see `response::Chips`) need to be collected only when
+                /// `chips` is set (see `Receiver::with_chip_stats`).
+                fn handle_stats(
+                    &self,
+                    filter: Option<$crate::support::StatsFilter>,
+                    chips: bool
+                ) -> Result<$crate::response::Stats>;
+                fn handle_estats(
+                    &self,
+                    filter: Option<$crate::support::StatsFilter>,
+                    chips: bool
+                ) -> Result<$crate::response::Stats>;
+                fn handle_asc_count(&self) -> Result<$crate::response::AscCount>;
+                /// Returns the device `idx` or all devices when it is `None`
+                fn handle_asc(&self, idx: Option<i32>) -> Result<$crate::response::Ascs>;
+                fn handle_asc_enable(&self, idx: i32) -> Result<$crate::response::AscEnable>;
+                fn handle_asc_disable(&self, idx: i32) -> Result<$crate::response::AscDisable>;
+                fn handle_asc_set(
+                    &self,
+                    parameter: $crate::support::AscSetParameter
+                ) -> Result<$crate::response::AscSet>;
+                fn handle_locate(
+                    &self,
+                    setting: Option<$crate::support::LocateSetting>
+                ) -> Result<$crate::response::Locate>;
+                /// Condensed status of the miner. It is assembled from `summary`, `pools` and
+                /// `devs` by default (see `default_lcd`).
+                fn handle_lcd(&self) -> Result<$crate::response::Lcd> =
+                    $crate::command::default_lcd;
+            }
+            /// Commands of the tuner (`tunerstatus`)
+            TunerHandler(tuner: U) {
+                fn handle_tuner_status(&self) -> Result<$crate::response::TunerStatus>;
+            }
+        }
+    };
+}
+
+pub(crate) use handler_methods;
+
+/// Declares `Handler` with all the methods of `handler_methods`. The methods are collected one by
+/// one because only some of them have the default implementation. The `self` keyword and the
+/// `async_trait` attribute are passed along from the first step because `async_trait` resolves
+/// `self` of default methods only when both come from the same expansion of the macro.
+macro_rules! handler_trait {
+    (@methods $this:tt $hash:tt $async_trait:tt [$($methods:tt)*]) => {
+        /// A handler to be implemented by the API implementation,
+        /// takes care of producing a response for each command.
+        ///
+        /// The returned futures have to be safe to drop at any await point because the receiver
+        /// drops them when the command times out and the server drops them when the client
+        /// disconnects. Handlers should therefore not leave shared state inconsistent across an
+        /// await.
+        $hash $async_trait
+        pub trait Handler: Send + Sync {
+            $($methods)*
+        }
+    };
+    (
+        @methods $this:tt $hash:tt $async_trait:tt [$($methods:tt)*]
+        $(#[$attr:meta])*
+        fn $method:ident(&self $(, $arg:ident: $type:ty)* $(,)?) -> Result<$response:ty>
+            = $default:expr;
+        $($rest:tt)*
+    ) => {
+        handler_trait! {
+            @methods $this $hash $async_trait [
+                $($methods)*
+                $(#[$attr])*
+                async fn $method(&$this $(, $arg: $type)*) -> Result<$response> {
+                    ($default)($this).await
+                }
+            ]
+            $($rest)*
+        }
+    };
+    (
+        @methods $this:tt $hash:tt $async_trait:tt [$($methods:tt)*]
+        $(#[$attr:meta])*
+        fn $method:ident(&self $(, $arg:ident: $type:ty)* $(,)?) -> Result<$response:ty>;
+        $($rest:tt)*
+    ) => {
+        handler_trait! {
+            @methods $this $hash $async_trait [
+                $($methods)*
+                $(#[$attr])*
+                async fn $method(&$this $(, $arg: $type)*) -> Result<$response>;
+            ]
+            $($rest)*
+        }
+    };
+    (
+        $(
+            $(#[$group_attr:meta])*
+            $group:ident($field:ident: $param:ident) {
+                $($methods:tt)*
+            }
+        )+
+    ) => {
+        handler_trait! { @methods self #[async_trait::async_trait] [] $($($methods)*)+ }
+    };
+}
+
+handler_methods!(handler_trait);
+
+/// Default implementation of `Handler::handle_lcd` which obtains the responses of `summary`,
+/// `pools` and `devs` from the `handler` and maps them to the fields of `lcd` (see
+/// `response::Lcd::from_responses`). Failure of any of the commands fails the `lcd` as well.
+pub async fn default_lcd<H>(handler: &H) -> Result<response::Lcd>
+where
+    H: Handler + ?Sized,
+{
+    let summary = handler.handle_summary().await?;
+    let pools = handler.handle_pools().await?;
+    let devs = handler.handle_devs(None).await?;
+    Ok(response::Lcd::from_responses(&summary, &pools, &devs))
+}
+
+/// Default implementation of `Handler::handle_reload_config` which fails with
+/// `ErrorCode::CommandNotSupported`
+pub async fn default_reload_config<H>(_handler: &H) -> Result<response::ReloadConfig>
+where
+    H: Handler + ?Sized,
+{
+    Err(response::ErrorCode::CommandNotSupported(RELOAD_CONFIG.to_string()).into())
+}
+
+/// Default implementation of `Handler::handle_version_extra` which reports no components
+async fn no_version_extra<H>(_handler: &H) -> Result<Vec<(String, String)>>
+where
+    H: Handler + ?Sized,
+{
+    Ok(vec![])
+}
+
+/// Deserializes a present field as is so that `null` is distinguished from a missing field
+fn deserialize_present<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<json::Value>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    json::Value::deserialize(deserializer).map(Some)
+}
+
+/// Body of a JSON request
+#[derive(Deserialize, Clone)]
+pub struct RequestBody {
+    /// One command or multiple commands joined by `+`
+    pub command: String,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub parameter: Option<json::Value>,
+    /// All other fields of the request which are passed to the dispatch `Context`. Field `token`
+    /// may carry a shared secret granting privilege to the client (see `Receiver::with_tokens`).
+    #[serde(flatten)]
+    pub extras: json::Map<String, json::Value>,
+}
+
+impl fmt::Debug for RequestBody {
+    /// The shared secret is never printed
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut extras = self.extras.clone();
+        if let Some(token) = extras.get_mut(TOKEN_FIELD) {
+            *token = json::Value::String(REDACTED.to_string());
+        }
+        f.debug_struct("RequestBody")
+            .field("command", &self.command)
+            .field("parameter", &self.parameter)
+            .field("extras", &extras)
+            .finish()
+    }
 }
 
 /// Holds an incoming API command
+#[derive(Debug)]
 pub struct Request {
-    value: json::Value,
+    /// Body or `None` when the request has no valid command
+    body: Option<RequestBody>,
 }
 
 impl Request {
+    /// Builds a request from a JSON `value`. A value which is not an object with string field
+    /// `command` is responded with `ErrorCode::MissingCommand`.
     pub fn new(value: json::Value) -> Self {
-        Self { value }
+        Self {
+            body: json::from_value(value).ok(),
+        }
+    }
+
+    pub fn from_body(body: RequestBody) -> Self {
+        Self { body: Some(body) }
+    }
+
+    pub fn body(&self) -> Option<&RequestBody> {
+        self.body.as_ref()
+    }
+
+    /// Parses a raw request limited to the default size (see `from_bytes_limited`)
+    pub fn from_bytes(data: &[u8]) -> std::result::Result<(Self, Format), ParseError> {
+        Self::from_bytes_limited(data, server::DEFAULT_MAX_REQUEST_SIZE)
+    }
+
+    /// Parses a raw request in either format which must not exceed `max_size` bytes. The `data`
+    /// has to contain exactly one request (see `parse` for the accepted terminators).
+    pub fn from_bytes_limited(
+        data: &[u8],
+        max_size: usize,
+    ) -> std::result::Result<(Self, Format), ParseError> {
+        if data.len() > max_size {
+            return Err(ParseError::TooLarge(max_size));
+        }
+        match Self::parse(data, true, max_size)? {
+            Some((request, format, len)) if len == data.len() => Ok((request, format)),
+            Some(_) => Err(ParseError::StrayData),
+            None => Err(ParseError::Empty),
+        }
+    }
+
+    /// Parses a request from the beginning of `data` and returns it with its format and the
+    /// number of bytes it occupies. `None` is returned when more data is needed. This is the only
+    /// parser of requests shared by the server, the codec and the WebSocket server.
+    ///
+    /// CGMiner clients terminate the request with a NUL byte, a new line or nothing at all so NUL
+    /// bytes and whitespace surrounding the request are consumed with it. Any other data after
+    /// JSON is refused. Plain-text request ends with a NUL byte or a new line or at the end of
+    /// `data` when it is `complete` and no more data can follow.
+    pub(crate) fn parse(
+        data: &[u8],
+        complete: bool,
+        max_size: usize,
+    ) -> std::result::Result<Option<(Self, Format, usize)>, ParseError> {
+        let padding_len = |data: &[u8]| {
+            data.iter()
+                .take_while(|&&byte| byte == 0 || byte.is_ascii_whitespace())
+                .count()
+        };
+
+        let skipped = padding_len(data);
+        let data = &data[skipped..];
+        let format = match Format::detect(data) {
+            Some(format) => format,
+            None => return Ok(None),
+        };
+        let (request, end) = match format {
+            Format::Text => {
+                let terminator = data.iter().position(|&byte| byte == b'\n' || byte == 0);
+                match terminator {
+                    Some(position) => (Some(text::parse_request(&data[..position])), position),
+                    None if complete => (Some(text::parse_request(data)), data.len()),
+                    None => (None, data.len()),
+                }
+            }
+            Format::Json => {
+                let mut stream = json::Deserializer::from_slice(data).into_iter::<json::Value>();
+                match stream.next() {
+                    Some(Ok(value)) => (Some(Self::new(value)), stream.byte_offset()),
+                    Some(Err(e)) if e.is_eof() && !complete => (None, data.len()),
+                    Some(Err(e)) => return Err(ParseError::InvalidJson(e)),
+                    None => (None, data.len()),
+                }
+            }
+        };
+
+        match request {
+            Some(_) if skipped + end > max_size => Err(ParseError::TooLarge(max_size)),
+            None if skipped + end >= max_size => Err(ParseError::TooLarge(max_size)),
+            None => Ok(None),
+            Some(request) => {
+                let len = end + padding_len(&data[end..]);
+                match (format, data.get(len)) {
+                    (Format::Json, Some(_)) => Err(ParseError::StrayData),
+                    _ => Ok(Some((request, format, skipped + len))),
+                }
+            }
+        }
+    }
+}
+
+/// Error of parsing a raw request
+#[derive(Debug)]
+pub enum ParseError {
+    /// The request exceeds the maximum size in bytes
+    TooLarge(usize),
+    InvalidJson(json::Error),
+    /// Data other than NUL bytes and whitespace follows the request
+    StrayData,
+    /// There is no request at all
+    Empty,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::TooLarge(max_size) => {
+                write!(f, "request exceeds maximum size of {} bytes", max_size)
+            }
+            ParseError::InvalidJson(e) => write!(f, "invalid JSON request ({})", e),
+            ParseError::StrayData => write!(f, "stray data following request"),
+            ParseError::Empty => write!(f, "empty request"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The client is responded with a standard error as CGMiner does for any malformed request
+impl From<ParseError> for response::ErrorCode {
+    fn from(_: ParseError) -> Self {
+        response::ErrorCode::InvalidJSON
+    }
+}
+
+/// Describes the client which sent a request
+#[derive(Default, Clone, Debug)]
+pub struct Context {
+    peer_addr: Option<IpAddr>,
+    extras: json::Map<String, json::Value>,
+    session: Option<Arc<Session>>,
+}
+
+impl Context {
+    /// Context of a remote client connected from `peer_addr`
+    pub fn new(peer_addr: IpAddr) -> Self {
+        Self {
+            peer_addr: Some(peer_addr),
+            ..Default::default()
+        }
+    }
+
+    /// Context of a local client (e.g. connected over Unix domain socket) which is not subject
+    /// to access control
+    pub fn local() -> Self {
+        Self::default()
+    }
+
+    pub fn peer_addr(&self) -> Option<IpAddr> {
+        self.peer_addr
+    }
+
+    /// Fields of the request other than `command` and `parameter` (see `RequestBody::extras`)
+    pub fn extras(&self) -> &json::Map<String, json::Value> {
+        &self.extras
+    }
+
+    /// Attaches the `session` of a persistent connection which is shared by all its requests so
+    /// that the `auth` command can grant privilege to the following ones
+    pub fn with_session(self, session: Arc<Session>) -> Self {
+        Self {
+            session: Some(session),
+            ..self
+        }
+    }
+
+    pub fn session(&self) -> Option<&Arc<Session>> {
+        self.session.as_ref()
+    }
+
+    fn with_extras(self, extras: json::Map<String, json::Value>) -> Self {
+        Self { extras, ..self }
     }
 }
 
@@ -120,11 +563,58 @@ pub type ParameterHandler = Box<dyn Fn(Option<&json::Value>) -> AsyncHandler + S
 pub type ParameterCheckHandler =
     Box<dyn Fn(&str, &Option<&json::Value>) -> Result<()> + Send + Sync>;
 
+/// Replaces credentials in a parameter before it is stored (see `Descriptor::redacted`)
+pub type RedactHandler = fn(&json::Value) -> json::Value;
+
+/// Action executed after the response has been sent to the client
+pub type DeferredAction = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Continuation of a request after its response has been sent to the client
+pub enum Continuation {
+    /// Action such as shutdown of the miner
+    Action(DeferredAction),
+    /// Events have to be sent to the client until it disconnects
+    Events(Subscription),
+}
+
+pub type ShutdownHandler = Box<dyn Fn(ShutdownKind) -> DeferredAction + Send + Sync>;
+
+type VersionExtraHandler = Box<
+    dyn Fn() -> Pin<Box<dyn Future<Output = Result<Vec<(String, String)>>> + Send + 'static>>
+        + Send
+        + Sync,
+>;
+
+/// Describes what the miner should do after `quit` or `restart` command
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ShutdownKind {
+    Quit,
+    Restart,
+}
+
+impl ShutdownKind {
+    /// Acknowledgement sent to the client before the shutdown
+    pub fn action(&self) -> &'static str {
+        match self {
+            ShutdownKind::Quit => "BYE",
+            ShutdownKind::Restart => "RESTART",
+        }
+    }
+}
+
 pub enum HandlerType {
     ParameterLess(ParameterLessHandler),
     Parameter(ParameterHandler),
     Version,
     Check,
+    Help,
+    ApiStats,
+    Schema,
+    Events,
+    Connections,
+    LastCommands,
+    Auth,
+    Shutdown(ShutdownKind),
 }
 
 impl HandlerType {
@@ -134,14 +624,152 @@ impl HandlerType {
             HandlerType::Parameter(_) => true,
             HandlerType::Version => false,
             HandlerType::Check => true,
+            HandlerType::Help => false,
+            HandlerType::ApiStats => false,
+            HandlerType::Schema => true,
+            HandlerType::Events => false,
+            HandlerType::Connections => false,
+            HandlerType::LastCommands => false,
+            HandlerType::Auth => true,
+            HandlerType::Shutdown(_) => false,
+        }
+    }
+
+    /// Privileged commands change the state of the whole miner
+    pub fn is_privileged(&self) -> bool {
+        matches!(self, HandlerType::Shutdown(_))
+    }
+}
+
+/// Statistics of command invocations which are updated without any locking. Times are kept in
+/// microseconds.
+#[derive(Debug)]
+pub struct Metrics {
+    calls: AtomicU64,
+    errors: AtomicU64,
+    cancelled: AtomicU64,
+    total_time: AtomicU64,
+    min_time: AtomicU64,
+    max_time: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            cancelled: AtomicU64::new(0),
+            total_time: AtomicU64::new(0),
+            min_time: AtomicU64::new(u64::MAX),
+            max_time: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Metrics {
+    fn record(&self, time: Duration, error: bool) {
+        let time = time.as_micros().min(u64::MAX.into()) as u64;
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        if error {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.total_time.fetch_add(time, Ordering::Relaxed);
+        self.min_time.fetch_min(time, Ordering::Relaxed);
+        self.max_time.fetch_max(time, Ordering::Relaxed);
+    }
+
+    fn record_cancelled(&self) {
+        self.cancelled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Number of all completed invocations of the command
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// Number of invocations responded with an error
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Number of invocations dropped before completion, typically because the client has
+    /// disconnected. These are not included in `calls`.
+    pub fn cancelled(&self) -> u64 {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// The following times are `None` until the command has been invoked
+    pub fn min_time(&self) -> Option<Duration> {
+        Some(self.min_time.load(Ordering::Relaxed))
+            .filter(|_| self.calls() > 0)
+            .map(Duration::from_micros)
+    }
+
+    pub fn avg_time(&self) -> Option<Duration> {
+        match self.calls() {
+            0 => None,
+            calls => Some(Duration::from_micros(
+                self.total_time.load(Ordering::Relaxed) / calls,
+            )),
+        }
+    }
+
+    pub fn max_time(&self) -> Option<Duration> {
+        Some(self.max_time.load(Ordering::Relaxed))
+            .filter(|_| self.calls() > 0)
+            .map(Duration::from_micros)
+    }
+}
+
+/// Counts an invocation as cancelled unless it has been completed before the guard is dropped
+struct CancellationGuard<'a>(Option<&'a Metrics>);
+
+impl<'a> CancellationGuard<'a> {
+    fn complete(mut self) {
+        self.0 = None;
+    }
+}
+
+impl<'a> Drop for CancellationGuard<'a> {
+    fn drop(&mut self) {
+        if let Some(metrics) = self.0 {
+            metrics.record_cancelled();
         }
     }
 }
 
+/// Permit of command concurrency which is not bound to the lifetime of the semaphore so that it
+/// can be moved into the task of a handler which is not cancelled
+struct Permit(Arc<tokio::sync::Semaphore>);
+
+impl Permit {
+    async fn acquire(semaphore: Arc<tokio::sync::Semaphore>) -> Self {
+        semaphore.acquire().await.forget();
+        Self(semaphore)
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.add_permits(1);
+    }
+}
+
 /// Describes individual commands and async handler associated with this command
 pub struct Descriptor {
     handler: HandlerType,
     parameter_check: Option<ParameterCheckHandler>,
+    privileged: bool,
+    privileged_parameter: bool,
+    description: Option<&'static str>,
+    aliases: Vec<&'static str>,
+    timeout: Option<Duration>,
+    cache_ttl: Option<Duration>,
+    /// Permits of concurrently running handlers
+    concurrency: Option<Arc<tokio::sync::Semaphore>>,
+    schema: Option<&'static [schema::Section]>,
+    redact: Option<RedactHandler>,
+    metrics: Metrics,
 }
 
 impl Descriptor {
@@ -150,15 +778,175 @@ impl Descriptor {
         T: Into<Option<ParameterCheckHandler>>,
     {
         Self {
+            privileged: handler.is_privileged(),
             handler,
             parameter_check: parameter_check.into(),
+            privileged_parameter: false,
+            description: None,
+            aliases: vec![],
+            timeout: None,
+            cache_ttl: None,
+            concurrency: None,
+            schema: None,
+            redact: None,
+            metrics: Default::default(),
         }
     }
 
+    /// Sets short description of the command reported by `help` command
+    pub fn description(mut self, description: &'static str) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    /// Registers an alternative name of the command. Responses are always reported under the name
+    /// which the command has been registered with.
+    pub fn alias(mut self, alias: &'static str) -> Self {
+        self.aliases.push(alias);
+        self
+    }
+
+    /// Overrides the default time limit of the handler set in the receiver (see
+    /// `Receiver::with_command_timeout`)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Enables caching of successful responses for `cache_ttl`. It is meant for expensive
+    /// commands polled frequently by multiple clients. Responses with different parameters are
+    /// cached separately.
+    pub fn cache_ttl(mut self, cache_ttl: Duration) -> Self {
+        self.cache_ttl = Some(cache_ttl);
+        self
+    }
+
+    /// Limits the number of concurrently running handlers of the command to `max_concurrency`
+    /// (at least one). It is meant for commands reading the hardware which must not be accessed
+    /// in parallel. Further invocations wait in the order of arrival and the waiting counts into
+    /// the timeout of the command. Other commands and responses from the cache (see `cache_ttl`)
+    /// are not affected.
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        let semaphore = tokio::sync::Semaphore::new(max_concurrency.max(1));
+        self.concurrency = Some(Arc::new(semaphore));
+        self
+    }
+
+    /// Describes sections of the response reported by `schema` command. Standard commands are
+    /// described by default.
+    pub fn schema(mut self, sections: &'static [schema::Section]) -> Self {
+        self.schema = Some(sections);
+        self
+    }
+
+    /// Replaces the parameter with the result of `redact` before it is stored in the history of
+    /// commands (see `Receiver::with_command_history`). It is meant for parameters containing
+    /// credentials.
+    pub fn redacted(mut self, redact: RedactHandler) -> Self {
+        self.redact = Some(redact);
+        self
+    }
+
+    /// Marks the command as privileged. Privileged commands are refused in multi-command requests.
+    pub fn privileged(mut self) -> Self {
+        self.privileged = true;
+        self
+    }
+
+    /// Marks the command as privileged only when it is invoked with a parameter. It is meant for
+    /// commands which report the current state and change it when the parameter is present. Such
+    /// command is allowed in multi-command requests as long as no parameter is present.
+    pub fn privileged_parameter(mut self) -> Self {
+        self.privileged_parameter = true;
+        self
+    }
+
     #[inline]
     pub fn has_parameters(&self) -> bool {
         self.handler.has_parameters()
     }
+
+    #[inline]
+    pub fn is_privileged(&self) -> bool {
+        self.privileged
+    }
+
+    #[inline]
+    pub fn is_privileged_parameter(&self) -> bool {
+        self.privileged_parameter
+    }
+
+    #[inline]
+    pub fn get_description(&self) -> Option<&'static str> {
+        self.description
+    }
+
+    #[inline]
+    pub fn get_aliases(&self) -> &[&'static str] {
+        &self.aliases
+    }
+
+    #[inline]
+    pub fn get_metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Determines whether the command invoked with `parameter` changes the state of the miner
+    fn is_privileged_with(&self, parameter: Option<&json::Value>) -> bool {
+        self.privileged || (self.privileged_parameter && parameter.is_some())
+    }
+
+    /// Determines whether the command invoked with `parameter` has to be refused in
+    /// multi-command requests. Other commands are offered the parameter shared by all commands
+    /// of the request.
+    fn is_multi_command_denied(&self, parameter: Option<&json::Value>) -> bool {
+        self.is_privileged_with(parameter)
+    }
+
+    /// Determines whether a client with `privilege` is allowed to invoke the command with
+    /// `parameter`. Client without any privilege is refused all commands.
+    fn is_access_denied(
+        &self,
+        parameter: Option<&json::Value>,
+        privilege: Option<Privilege>,
+    ) -> bool {
+        match privilege {
+            Some(Privilege::Write) => false,
+            Some(Privilege::Read) => self.is_privileged_with(parameter),
+            None => true,
+        }
+    }
+}
+
+/// Returns sections of responses of the standard command `name`. Commands whose responses have
+/// no body have no sections.
+fn standard_schema(name: &str) -> &'static [schema::Section] {
+    match name {
+        POOLS => schema::POOLS,
+        DEVS | EDEVS => schema::DEVS,
+        ASC => schema::ASC,
+        SUMMARY | ZERO => schema::SUMMARY,
+        VERSION => schema::VERSION,
+        CONFIG => schema::CONFIG,
+        LOCATE => schema::LOCATE,
+        RELOAD_CONFIG => schema::RELOAD_CONFIG,
+        DEVDETAILS => schema::DEV_DETAILS,
+        NOTIFY => schema::NOTIFY,
+        STATS | ESTATS => schema::STATS,
+        CHECK => schema::CHECK,
+        HELP => schema::HELP,
+        API_STATS => schema::API_STATS,
+        SCHEMA => schema::SCHEMA,
+        CONNECTIONS => schema::CONNECTIONS,
+        LAST_COMMANDS => schema::LAST_COMMANDS,
+        COIN => schema::COIN,
+        ASC_COUNT => schema::ASC_COUNT,
+        DEBUG => schema::DEBUG,
+        LCD => schema::LCD,
+        TUNERSTATUS => schema::TUNER_STATUS,
+        ASCSET => schema::ASC_SET,
+        _ => &[],
+    }
 }
 
 /// Generates a descriptor for a specified command type (`ParameterLess` or `Parameter`) that also
@@ -189,22 +977,88 @@ macro_rules! command {
         let handler = $crate::command::HandlerType::Parameter(f);
         $crate::command::Descriptor::new($name, handler, $check)
     }};
+    ($name:ident: Parsed($parse:expr) -> $handler:ident . $method:ident) => {{
+        // The parameter is parsed by the check and once again for the handler which receives
+        // the parsed value
+        let handler = $handler.clone();
+        let f: $crate::command::ParameterHandler = Box::new(move |parameter| {
+            let handler = handler.clone();
+            let parameter = $parse(parameter);
+            Box::pin(async move {
+                handler
+                    .$method(parameter?)
+                    .await
+                    .map(|response| response.into())
+            })
+        });
+        let check: $crate::command::ParameterCheckHandler =
+            Box::new(move |_command, parameter| $parse(*parameter).map(|_| ()));
+        let handler = $crate::command::HandlerType::Parameter(f);
+        $crate::command::Descriptor::new($name, handler, check)
+    }};
+    ($name:ident: Parsed($parse:expr) -> $handler:ident . $method:ident($($arg:ident),+)) => {{
+        // Same as above but the parser returns a tuple whose items are passed to the handler as
+        // separate arguments
+        let handler = $handler.clone();
+        let f: $crate::command::ParameterHandler = Box::new(move |parameter| {
+            let handler = handler.clone();
+            let parameter = $parse(parameter);
+            Box::pin(async move {
+                let ($($arg),+) = parameter?;
+                handler
+                    .$method($($arg),+)
+                    .await
+                    .map(|response| response.into())
+            })
+        });
+        let check: $crate::command::ParameterCheckHandler =
+            Box::new(move |_command, parameter| $parse(*parameter).map(|_| ()));
+        let handler = $crate::command::HandlerType::Parameter(f);
+        $crate::command::Descriptor::new($name, handler, check)
+    }};
+    ($name:ident: Parsed($parse:expr) -> $handler:ident . $get:ident | $set:ident) => {{
+        // Command with optional parameter which only reports the current state by `$get` when
+        // the parameter is missing and otherwise passes the parsed parameter to `$set`
+        let handler = $handler.clone();
+        let f: $crate::command::ParameterHandler = Box::new(move |parameter| {
+            let handler = handler.clone();
+            let parameter = parameter.map(|parameter| $parse(Some(parameter))).transpose();
+            Box::pin(async move {
+                match parameter? {
+                    None => handler.$get().await.map(|response| response.into()),
+                    Some(parameter) => handler
+                        .$set(parameter)
+                        .await
+                        .map(|response| response.into()),
+                }
+            })
+        });
+        let check: $crate::command::ParameterCheckHandler =
+            Box::new(move |_command, parameter| match parameter {
+                None => Ok(()),
+                Some(parameter) => $parse(Some(*parameter)).map(|_| ()),
+            });
+        let handler = $crate::command::HandlerType::Parameter(f);
+        $crate::command::Descriptor::new($name, handler, check).privileged_parameter()
+    }};
     ($name:ident: BuiltIn($type:ident)) => {
         $crate::command::Descriptor::new($name, $crate::command::HandlerType::$type, None)
     };
 }
 
-/// Generates a map that associated a command name with its descriptor
+/// Generates a map that associated a command name with its descriptor. Each command can be
+/// followed by its short description.
 #[macro_export]
 macro_rules! commands {
     () => (
         $crate::command::Map::new()
     );
-    ($(($name:ident: $type:ident$(($parameter:ident))? $(-> $handler:ident . $method:ident)?)),+) => {
+    ($(($name:ident: $type:ident$(($parameter:ident))? $(-> $handler:ident . $method:ident)? $(, $description:literal)?)),+) => {
         {
             let mut map = $crate::command::Map::new();
             $(
-                let descriptor = command!($name: $type $(($parameter))? $(-> $handler . $method)?);
+                let descriptor = command!($name: $type $(($parameter))? $(-> $handler . $method)?)
+                    $(.description($description))?;
                 map.insert($name, descriptor);
             )*
             map
@@ -212,23 +1066,156 @@ macro_rules! commands {
     }
 }
 
-/// Generic command receiving and processing object that dispatches command handling
-/// user provided handler methods.
-pub struct Receiver<T = UnixTime> {
-    commands: Map,
-    miner_signature: String,
-    miner_version: String,
-    description: String,
-    _marker: marker::PhantomData<T>,
+/// Response stored in the cache
+struct CachedDispatch {
+    dispatch: response::Dispatch,
+    created: Instant,
+    expires: Instant,
 }
 
-impl<T> Receiver<T>
-where
-    T: When,
-{
-    /// Builds a new command receiver that delegates processing of all standard commands to the
-    /// provided `handler`. Optional `custom_commands` must be convertible to a `command::Map` and
-    /// extend the command map created for the basic commands.
+/// Slot of a cached response which is locked while the response is being obtained
+type CacheSlot = Arc<tokio::sync::Mutex<Option<CachedDispatch>>>;
+
+/// Cache of responses of commands with `Descriptor::cache_ttl` identified by the command name and
+/// JSON encoded parameter
+#[derive(Default)]
+struct Cache {
+    slots: Mutex<HashMap<(String, Option<String>), CacheSlot>>,
+}
+
+impl Cache {
+    /// Returns slot of the response. Expired responses are removed when a new slot is created so
+    /// that the cache does not grow with every distinct parameter.
+    fn slot(&self, command: String, parameter: Option<String>) -> CacheSlot {
+        let mut slots = self.slots.lock().expect("BUG: poisoned response cache");
+        let key = (command, parameter);
+        if !slots.contains_key(&key) {
+            let now = Instant::now();
+            // Slots which are locked are being filled right now
+            slots.retain(|_, slot| match slot.try_lock() {
+                Ok(cached) => matches!(cached.as_ref(), Some(cached) if cached.expires > now),
+                Err(_) => true,
+            });
+        }
+        slots.entry(key).or_default().clone()
+    }
+}
+
+/// Identification of the miner in responses which can be updated at runtime
+struct MinerInfo {
+    /// Signature used in messages of responses
+    signature: String,
+    /// Content of the `version` response
+    version_info: response::VersionInfo,
+    /// Description reported in the status of every response
+    description: String,
+}
+
+/// Restricts the set of commands exposed by a `Receiver`. Commands which are not exposed behave
+/// exactly as unknown commands. The names are canonical names of the commands (excluding
+/// a command excludes all its aliases too) and the `check` command is always exposed.
+#[derive(Clone, Debug)]
+pub enum CommandFilter {
+    /// Only the listed commands are exposed
+    Allow(Vec<String>),
+    /// All commands except the listed ones are exposed
+    Deny(Vec<String>),
+}
+
+impl CommandFilter {
+    pub fn allow<I, S>(commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        CommandFilter::Allow(commands.into_iter().map(Into::into).collect())
+    }
+
+    pub fn deny<I, S>(commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        CommandFilter::Deny(commands.into_iter().map(Into::into).collect())
+    }
+
+    /// Returns `true` when the command with canonical `name` is exposed
+    pub fn is_exposed(&self, name: &str) -> bool {
+        let listed = |commands: &Vec<String>| commands.iter().any(|command| command == name);
+        name == CHECK
+            || match self {
+                CommandFilter::Allow(commands) => listed(commands),
+                CommandFilter::Deny(commands) => !listed(commands),
+            }
+    }
+}
+
+/// Builds descriptor of `stats` or `estats` command. The handler is told whether the receiver
+/// reports per-chip details (see `Receiver::with_chip_stats`) so that it does not collect them
+/// needlessly. Details returned by the handler anyway are removed from the typed response.
+fn stats_command<F, R>(name: &'static str, chip_stats: Arc<AtomicBool>, handle: F) -> Descriptor
+where
+    F: Fn(Option<StatsFilter>, bool) -> R + Send + Sync + 'static,
+    R: Future<Output = Result<response::Stats>> + Send + 'static,
+{
+    let parse = support::parse_stats_filter;
+    let f: ParameterHandler = Box::new(move |parameter| {
+        let chips = chip_stats.load(Ordering::Relaxed);
+        let stats = parse(parameter).map(|filter| handle(filter, chips));
+        Box::pin(async move {
+            let stats = stats?.await?;
+            Ok(if chips { stats } else { stats.without_chips() }.into())
+        })
+    });
+    let check: ParameterCheckHandler =
+        Box::new(move |_command, parameter| parse(*parameter).map(|_| ()));
+    Descriptor::new(name, HandlerType::Parameter(f), check)
+}
+
+/// Generic command receiving and processing object that dispatches command handling
+/// user provided handler methods.
+pub struct Receiver<T = UnixTime> {
+    commands: Map,
+    aliases: AliasMap,
+    shutdown_handler: Option<ShutdownHandler>,
+    event_sink: Option<EventSink>,
+    server_statistics: Option<Arc<server::Statistics>>,
+    access_control: Option<AccessControl>,
+    tokens: Option<Tokens>,
+    session_idle_timeout: Duration,
+    command_filter: Option<CommandFilter>,
+    read_only: bool,
+    millisecond_timestamps: bool,
+    /// Shared with handlers of `stats` and `estats` (see `stats_command`)
+    chip_stats: Arc<AtomicBool>,
+    command_timeout: Duration,
+    cache: Cache,
+    miner_info: RwLock<MinerInfo>,
+    version_extra: VersionExtraHandler,
+    observers: Vec<SharedObserver>,
+    audit_sink: Option<SharedAuditSink>,
+    snapshots: Option<Snapshots>,
+    history: History,
+    _marker: marker::PhantomData<T>,
+}
+
+impl<T> Receiver<T>
+where
+    T: When,
+{
+    /// Time limit of command handlers which is long enough even for a slow hardware
+    pub const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Time after which privilege granted to an idle connection by `auth` command expires
+    pub const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+    /// Number of recently handled commands reported by `lastcommands` command
+    pub const DEFAULT_COMMAND_HISTORY: usize = 64;
+
+    /// Builds a new command receiver that delegates processing of all standard commands to the
+    /// provided `handler`. Optional `custom_commands` must be convertible to a `command::Map` and
+    /// extend the command map created for the basic commands. A handler assembled from multiple
+    /// components can be built with `composite::CompositeHandler`.
     pub fn new<U, V>(
         handler: U,
         miner_signature: String,
@@ -241,156 +1228,1087 @@ where
     {
         let handler = Arc::new(handler);
 
-        let check_switch_pool: ParameterCheckHandler =
-            Box::new(|command, parameter| Self::check_pool_id(command, parameter));
-        let check_enable_pool: ParameterCheckHandler =
-            Box::new(|command, parameter| Self::check_pool_id(command, parameter));
-        let check_disable_pool: ParameterCheckHandler =
-            Box::new(|command, parameter| Self::check_pool_id(command, parameter));
-        let check_add_pool: ParameterCheckHandler =
-            Box::new(|command, parameter| Self::check_add_pool(command, parameter));
-        let check_remove_pool: ParameterCheckHandler =
-            Box::new(|command, parameter| Self::check_pool_id(command, parameter));
-        let check_asc: ParameterCheckHandler =
-            Box::new(|command, parameter| Self::check_asc(command, parameter));
+        let parse_switch_pool = support::parse_pool_id;
+        let parse_enable_pool = support::parse_pool_id;
+        let parse_disable_pool = support::parse_pool_id;
+        let parse_add_pool = AddPoolParameter::parse;
+        let parse_remove_pool = support::parse_pool_id;
+        let parse_pool_priority = support::parse_pool_priority;
+        let parse_failover_only = support::parse_bool;
+        let parse_set_config = support::parse_set_config;
+        let parse_zero = support::parse_zero;
+        let parse_asc = support::parse_asc_selection;
+        let parse_devs = support::parse_optional_asc_id;
+        let parse_asc_enable = support::parse_asc_id;
+        let parse_asc_disable = support::parse_asc_id;
+        let parse_asc_set = AscSetParameter::parse;
+        let parse_debug = support::parse_debug_flag;
+        let parse_locate = support::parse_locate;
 
         let mut commands = commands![
             // generic commands
-            (POOLS: ParameterLess -> handler.handle_pools),
-            (DEVS: ParameterLess -> handler.handle_devs),
-            (EDEVS: ParameterLess -> handler.handle_edevs),
-            (SUMMARY: ParameterLess -> handler.handle_summary),
-            (SWITCH_POOL: Parameter(check_switch_pool) -> handler.handle_switch_pool),
-            (CONFIG: ParameterLess -> handler.handle_config),
-            (ENABLE_POOL: Parameter(check_enable_pool) -> handler.handle_enable_pool),
-            (DISABLE_POOL: Parameter(check_disable_pool) -> handler.handle_disable_pool),
-            (ADD_POOL: Parameter(check_add_pool) -> handler.handle_add_pool),
-            (REMOVE_POOL: Parameter(check_remove_pool) -> handler.handle_remove_pool),
-            (STATS: ParameterLess -> handler.handle_stats),
-            (ESTATS: ParameterLess -> handler.handle_estats),
-            (COIN: ParameterLess -> handler.handle_coin),
-            (ASC_COUNT: ParameterLess -> handler.handle_asc_count),
-            (ASC: Parameter(check_asc) -> handler.handle_asc),
-            (LCD: ParameterLess -> handler.handle_lcd),
+            (POOLS: ParameterLess -> handler.handle_pools, "Pool details"),
+            (EDEVS: ParameterLess -> handler.handle_edevs, "Details of enabled devices"),
+            (SUMMARY: ParameterLess -> handler.handle_summary, "Summary of mining statistics"),
+            (CONFIG: ParameterLess -> handler.handle_config, "Miner configuration"),
+            (NOTIFY: ParameterLess -> handler.handle_notify, "Device well/not well history"),
+            (ASC_COUNT: ParameterLess -> handler.handle_asc_count, "Number of ASC devices"),
+            (ASC: Parsed(parse_asc) -> handler.handle_asc, "Details of all ASC devices or device N"),
+            (DEVS: Parsed(parse_devs) -> handler.handle_devs, "Details of all devices or device N"),
+            (LCD: ParameterLess -> handler.handle_lcd, "Summary extract for LCD display"),
+            (TUNERSTATUS: ParameterLess -> handler.handle_tuner_status, "Autotuner state of hash chains"),
             // special built-in commands
-            (VERSION: BuiltIn(Version)),
-            (CHECK: BuiltIn(Check))
+            (VERSION: BuiltIn(Version), "Miner and API version"),
+            (CHECK: BuiltIn(Check), "Check if command exists"),
+            (HELP: BuiltIn(Help), "List of all commands"),
+            (API_STATS: BuiltIn(ApiStats), "Invocation statistics of all commands"),
+            (SCHEMA: BuiltIn(Schema), "Fields of responses of all commands or only of command")
         ];
+        let chip_stats = Arc::new(AtomicBool::new(false));
+        commands.insert(
+            STATS,
+            stats_command(STATS, chip_stats.clone(), {
+                let handler = handler.clone();
+                move |filter, chips| {
+                    let handler = handler.clone();
+                    async move { handler.handle_stats(filter, chips).await }
+                }
+            })
+            .description("Device and pool statistics or only of device N|asc|pool"),
+        );
+        commands.insert(
+            ESTATS,
+            stats_command(ESTATS, chip_stats.clone(), {
+                let handler = handler.clone();
+                move |filter, chips| {
+                    let handler = handler.clone();
+                    async move { handler.handle_estats(filter, chips).await }
+                }
+            })
+            .description("Statistics of enabled devices or only of device N|asc|pool"),
+        );
+        // commands changing the state of the miner only when invoked with parameter
+        commands.insert(
+            LOCATE,
+            command!(LOCATE: Parsed(parse_locate) -> handler.handle_locate)
+                .description("Show or set locate mode true|false|N seconds")
+                .privileged_parameter(),
+        );
+        // write commands changing the state of the miner
+        commands.insert(
+            SWITCH_POOL,
+            command!(SWITCH_POOL: Parsed(parse_switch_pool) -> handler.handle_switch_pool)
+                .description("Switch to pool N")
+                .privileged(),
+        );
+        commands.insert(
+            ENABLE_POOL,
+            command!(ENABLE_POOL: Parsed(parse_enable_pool) -> handler.handle_enable_pool)
+                .description("Enable pool N")
+                .privileged(),
+        );
+        commands.insert(
+            DISABLE_POOL,
+            command!(DISABLE_POOL: Parsed(parse_disable_pool) -> handler.handle_disable_pool)
+                .description("Disable pool N")
+                .privileged(),
+        );
+        commands.insert(
+            ADD_POOL,
+            command!(ADD_POOL: Parsed(parse_add_pool) -> handler.handle_add_pool)
+                .description("Add pool URL,USR,PASS")
+                .redacted(support::redact_add_pool)
+                .privileged(),
+        );
+        commands.insert(
+            REMOVE_POOL,
+            command!(REMOVE_POOL: Parsed(parse_remove_pool) -> handler.handle_remove_pool)
+                .description("Remove pool N")
+                .privileged(),
+        );
+        commands.insert(
+            POOL_PRIORITY,
+            command!(POOL_PRIORITY: Parsed(parse_pool_priority) -> handler.handle_pool_priority)
+                .description("Change pool priorities to N,...")
+                .privileged(),
+        );
+        commands.insert(
+            ASC_ENABLE,
+            command!(ASC_ENABLE: Parsed(parse_asc_enable) -> handler.handle_asc_enable)
+                .description("Enable ASC device N")
+                .privileged(),
+        );
+        commands.insert(
+            ASC_DISABLE,
+            command!(ASC_DISABLE: Parsed(parse_asc_disable) -> handler.handle_asc_disable)
+                .description("Disable ASC device N")
+                .privileged(),
+        );
+        commands.insert(
+            FAILOVER_ONLY,
+            command!(FAILOVER_ONLY: Parsed(parse_failover_only) -> handler.handle_failover_only)
+                .description("Set failover-only mode true|false")
+                .privileged(),
+        );
+        commands.insert(
+            COIN,
+            command!(COIN: ParameterLess -> handler.handle_coin)
+                .description("Mining coin information")
+                .alias(COIN_MINE),
+        );
+        commands.insert(
+            SET_CONFIG,
+            command!(SET_CONFIG: Parsed(parse_set_config) -> handler.handle_set_config(name, value))
+                .description("Set configuration option name,N")
+                .privileged(),
+        );
+        commands.insert(
+            RELOAD_CONFIG,
+            command!(RELOAD_CONFIG: ParameterLess -> handler.handle_reload_config)
+                .description("Reload miner configuration")
+                .privileged(),
+        );
+        commands.insert(
+            ZERO,
+            command!(ZERO: Parsed(parse_zero) -> handler.handle_zero(target, return_summary))
+                .description("Zero statistics all|BestShare,true|false")
+                .privileged(),
+        );
+        commands.insert(
+            PAUSE,
+            command!(PAUSE: ParameterLess -> handler.handle_pause)
+                .description("Pause mining")
+                .privileged(),
+        );
+        commands.insert(
+            RESUME,
+            command!(RESUME: ParameterLess -> handler.handle_resume)
+                .description("Resume paused mining")
+                .privileged(),
+        );
+        commands.insert(
+            ASCSET,
+            command!(ASCSET: Parsed(parse_asc_set) -> handler.handle_asc_set)
+                .description("Set options of ASC device N,opt[=val][:opt=val]")
+                .privileged(),
+        );
+        commands.insert(
+            DEBUG,
+            command!(DEBUG: Parsed(parse_debug) -> handler.handle_debug)
+                .description("Show or change debug settings")
+                .privileged(),
+        );
+        // history of commands exposes actions of all clients
+        commands.insert(
+            LAST_COMMANDS,
+            Descriptor::new(LAST_COMMANDS, HandlerType::LastCommands, None)
+                .description("Recently handled commands")
+                .privileged(),
+        );
 
         if let Some(custom_commands) = custom_commands.into() {
             commands.extend(custom_commands.into_iter());
         }
+        let aliases = Self::collect_aliases(&commands);
+        let version_extra: VersionExtraHandler = Box::new(move || {
+            let handler = handler.clone();
+            Box::pin(async move { handler.handle_version_extra().await })
+        });
 
         let description = format!("{} {}", miner_signature.clone(), miner_version.clone());
         Self {
             commands,
-            miner_signature,
-            miner_version,
-            description,
+            aliases,
+            shutdown_handler: None,
+            event_sink: None,
+            server_statistics: None,
+            access_control: None,
+            tokens: None,
+            session_idle_timeout: Self::DEFAULT_SESSION_IDLE_TIMEOUT,
+            command_filter: None,
+            read_only: false,
+            millisecond_timestamps: false,
+            chip_stats,
+            command_timeout: Self::DEFAULT_COMMAND_TIMEOUT,
+            cache: Default::default(),
+            miner_info: RwLock::new(MinerInfo {
+                version_info: response::VersionInfo::new(miner_signature.clone(), miner_version),
+                signature: miner_signature,
+                description,
+            }),
+            version_extra,
+            observers: vec![Arc::new(LoggingObserver)],
+            audit_sink: None,
+            snapshots: None,
+            history: History::new(Self::DEFAULT_COMMAND_HISTORY),
             _marker: marker::PhantomData,
         }
     }
 
-    fn check_add_pool(_command: &str, parameter: &Option<&json::Value>) -> Result<()> {
-        const ARG_COUNT: usize = 3;
-        match parameter {
-            Some(json::Value::String(value)) => {
-                if value.splitn(ARG_COUNT, super::PARAMETER_DELIMITER).count() == ARG_COUNT {
-                    Ok(())
-                } else {
-                    Err(response::ErrorCode::InvalidAddPoolDetails(value.clone()).into())
-                }
-            }
-            Some(json::Value::Number(value)) => {
-                Err(response::ErrorCode::InvalidAddPoolDetails(value.to_string()).into())
+    /// Maps aliases of all `commands` to their canonical names
+    ///
+    /// # Panics
+    ///
+    /// Alias which is the same as a name of any command or another alias is a bug in the command
+    /// table.
+    fn collect_aliases(commands: &Map) -> AliasMap {
+        let mut aliases = AliasMap::new();
+        for (name, descriptor) in commands {
+            for alias in descriptor.get_aliases() {
+                assert!(
+                    !commands.contains_key(alias) && aliases.insert(*alias, *name).is_none(),
+                    "BUG: alias '{}' of command '{}' collides with another command",
+                    alias,
+                    name
+                );
             }
-            // CGMiner recognizes strings and integers as the same type. Other types (array, map,
-            // ..) are reported as a missing parameter. Therefore, we match anything else as
-            // missing parameter.
-            _ => Err(response::ErrorCode::MissingAddPoolDetails.into()),
         }
+        aliases
     }
 
-    fn check_pool_id(_command: &str, parameter: &Option<&json::Value>) -> Result<()> {
-        match parameter {
-            Some(value) if value.is_i32() => Ok(()),
-            _ => Err(response::ErrorCode::MissingPoolParameter.into()),
+    /// Returns alternative names of commands mapped to their canonical names
+    pub fn aliases(&self) -> &AliasMap {
+        &self.aliases
+    }
+
+    /// Returns `true` unless the command with canonical `name` is excluded by the command filter
+    fn is_exposed(&self, name: &str) -> bool {
+        match &self.command_filter {
+            Some(filter) => filter.is_exposed(name),
+            None => true,
         }
     }
 
-    fn check_asc(_command: &str, parameter: &Option<&json::Value>) -> Result<()> {
-        match parameter {
-            Some(value) if value.is_i32() => Ok(()),
-            _ => Err(response::ErrorCode::MissingAscParameter.into()),
+    fn descriptor(&self, name: &str) -> Option<&Descriptor> {
+        self.commands.get(name).filter(|_| self.is_exposed(name))
+    }
+
+    /// Returns all commands which are not excluded by the command filter
+    fn exposed_commands(&self) -> impl Iterator<Item = (&&'static str, &Descriptor)> {
+        self.commands
+            .iter()
+            .filter(move |(name, _)| self.is_exposed(name))
+    }
+
+    /// Translates an alias to the canonical name of the command. Other names are kept intact.
+    fn canonical_name<'a>(&self, command: &'a str) -> &'a str {
+        self.aliases.get(command).copied().unwrap_or(command)
+    }
+
+    /// Determines whether handling of the `request` cannot change the state of the miner so that
+    /// it can be cancelled at any point. Privileged commands are refused in multi-command
+    /// requests so only a single command can change it.
+    pub(crate) fn is_read_only(&self, request: &Request) -> bool {
+        let body = match request.body() {
+            Some(body) => body,
+            None => return true,
+        };
+        if body.command.contains(BATCH_DELIMITER) {
+            return true;
+        }
+        match self.descriptor(self.canonical_name(&body.command)) {
+            Some(descriptor) => !descriptor.is_privileged_with(body.parameter.as_ref()),
+            None => true,
+        }
+    }
+
+    /// Creates a builder of a receiver with the standard commands delegated to the `handler`
+    /// which allows registering additional custom commands
+    pub fn builder<U>(
+        handler: U,
+        miner_signature: String,
+        miner_version: String,
+    ) -> ReceiverBuilder<T>
+    where
+        U: Handler + 'static,
+    {
+        ReceiverBuilder {
+            receiver: Self::new(handler, miner_signature, miner_version, None),
+            duplicate: None,
         }
     }
 
-    fn handle_version(&self) -> Result<response::Version> {
-        Ok(response::Version {
-            signature: self.miner_signature.to_string(),
-            miner: self.miner_version.to_string(),
-            api: crate::API_VERSION.to_string(),
+    /// Enables `quit` and `restart` commands. The `shutdown_handler` provides the action which is
+    /// executed only after the response has been sent to the client.
+    pub fn with_shutdown_handler(mut self, shutdown_handler: ShutdownHandler) -> Self {
+        self.commands.insert(
+            QUIT,
+            Descriptor::new(QUIT, HandlerType::Shutdown(ShutdownKind::Quit), None)
+                .privileged()
+                .description("Quit the miner"),
+        );
+        self.commands.insert(
+            RESTART,
+            Descriptor::new(RESTART, HandlerType::Shutdown(ShutdownKind::Restart), None)
+                .privileged()
+                .description("Restart the miner"),
+        );
+        self.shutdown_handler = Some(shutdown_handler);
+        self
+    }
+
+    /// Enables `events` command subscribing the client to all events published to the
+    /// `event_sink` (see `events` module). Only transports with persistent connections support
+    /// the subscription.
+    pub fn with_event_sink(mut self, event_sink: EventSink) -> Self {
+        self.commands.insert(
+            EVENTS,
+            Descriptor::new(EVENTS, HandlerType::Events, None)
+                .description("Subscribe to miner events"),
+        );
+        self.event_sink = Some(event_sink);
+        self
+    }
+
+    /// Enables `connections` command reporting the `statistics` of API servers including recent
+    /// clients (see `server::Server::share_statistics`)
+    pub fn with_server_statistics(mut self, statistics: Arc<server::Statistics>) -> Self {
+        self.commands.insert(
+            CONNECTIONS,
+            Descriptor::new(CONNECTIONS, HandlerType::Connections, None)
+                .description("Statistics of API connections and recent clients"),
+        );
+        self.server_statistics = Some(statistics);
+        self
+    }
+
+    /// Restricts access of remote clients to the API. Clients which are not allowed at all should
+    /// be disconnected without any response (see `privilege`).
+    pub fn with_access_control(mut self, access_control: AccessControl) -> Self {
+        self.access_control = Some(access_control);
+        self
+    }
+
+    /// Exposes only commands allowed by the `command_filter`. It applies to all commands
+    /// including the ones enabled later (e.g. by `with_shutdown_handler`). Excluded commands are
+    /// responded as unknown commands and `check` reports that they do not exist.
+    pub fn with_command_filter(mut self, command_filter: CommandFilter) -> Self {
+        self.command_filter = Some(command_filter);
+        self
+    }
+
+    /// Replaces content of the `version` response which by default reports the miner signature
+    /// and version passed to the constructor. The signature in messages and descriptions of
+    /// other responses is not affected.
+    pub fn with_version_info(mut self, version_info: response::VersionInfo) -> Self {
+        self.miner_info
+            .get_mut()
+            .expect("BUG: poisoned miner info")
+            .version_info = version_info;
+        self
+    }
+
+    /// Updates identification of the miner in all subsequent responses when it changes at
+    /// runtime (e.g. after a firmware update). Unlike `with_version_info` the signature of the
+    /// `version_info` is used in messages of all responses and their descriptions consist of
+    /// the signature and the miner version.
+    pub fn set_version_info(&self, version_info: response::VersionInfo) {
+        let mut miner_info = self.miner_info.write().expect("BUG: poisoned miner info");
+        miner_info.signature = version_info.signature.clone();
+        miner_info.description = format!("{} {}", version_info.signature, version_info.miner);
+        miner_info.version_info = version_info;
+    }
+
+    fn miner_info(&self) -> RwLockReadGuard<'_, MinerInfo> {
+        self.miner_info.read().expect("BUG: poisoned miner info")
+    }
+
+    /// Registers another `observer` of all handled commands. The `LoggingObserver` is registered
+    /// by default.
+    pub fn with_observer(mut self, observer: SharedObserver) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Records every invocation of a privileged command to the `audit_sink` (see `audit` module)
+    pub fn with_audit_sink(mut self, audit_sink: SharedAuditSink) -> Self {
+        self.audit_sink = Some(audit_sink);
+        self
+    }
+
+    /// Keeps the last `capacity` handled commands for `lastcommands` command instead of
+    /// `DEFAULT_COMMAND_HISTORY`. Zero capacity disables the history.
+    pub fn with_command_history(mut self, capacity: usize) -> Self {
+        self.history = History::new(capacity);
+        self
+    }
+
+    /// Returns the `parameter` of the `command` with credentials replaced (see
+    /// `Descriptor::redacted`) as it is stored or passed outside of the receiver. The parameter of
+    /// a `batched` command is shared with the other commands of the request (e.g.
+    /// `summary+addpool`) and an unknown command may be a mistyped one (e.g. `addpol`) so their
+    /// parameters are redacted whole.
+    fn redact(
+        &self,
+        command: &str,
+        parameter: Option<&json::Value>,
+        batched: bool,
+    ) -> Option<json::Value> {
+        let parameter = parameter?;
+        Some(match self.descriptor(command) {
+            Some(descriptor) if !batched => descriptor
+                .redact
+                .map_or_else(|| parameter.clone(), |redact| redact(parameter)),
+            _ => support::redact_all(parameter),
         })
     }
 
-    fn handle_check(&self, parameter: Option<&json::Value>) -> Result<response::Check> {
+    /// Records the outcome of the `command` to the history with already redacted `parameter`
+    fn remember(
+        &self,
+        context: &Context,
+        command: &str,
+        parameter: Option<&json::Value>,
+        status: &observer::ResponseStatus,
+    ) {
+        self.history.record(history::Entry {
+            when: T::when(),
+            client: context.peer_addr(),
+            command: command.to_string(),
+            parameter: history::Entry::summary(parameter),
+            status: status.clone(),
+        });
+    }
+
+    /// Passes the outcome of a mutating `command` with already redacted `parameter` to the audit
+    /// sink if there is any
+    fn audit(
+        &self,
+        context: &Context,
+        command: &str,
+        parameter: Option<&json::Value>,
+        status: &observer::ResponseStatus,
+    ) {
+        if let Some(audit_sink) = &self.audit_sink {
+            audit_sink.record(AuditEntry::new(
+                T::when(),
+                context.peer_addr(),
+                command,
+                parameter,
+                status,
+            ));
+        }
+    }
+
+    /// Limits how long a handler of any command may take unless the command has its own timeout.
+    /// A command which does not finish in time is responded with an error. Handlers of commands
+    /// changing the state of the miner are not cancelled and they finish in the background.
+    pub fn with_command_timeout(mut self, command_timeout: Duration) -> Self {
+        self.command_timeout = command_timeout;
+        self
+    }
+
+    /// Enables caching of responses of an already registered `command` such as `stats` (see
+    /// `Descriptor::cache_ttl`)
+    ///
+    /// # Panics
+    ///
+    /// The `command` has to exist.
+    pub fn with_cache_ttl(mut self, command: &str, cache_ttl: Duration) -> Self {
+        let command = self.canonical_name(command).to_string();
+        self.commands
+            .get_mut(command.as_str())
+            .expect("BUG: cached command does not exist")
+            .cache_ttl = Some(cache_ttl);
+        self
+    }
+
+    /// Limits concurrently running handlers of an already registered `command` such as `stats`
+    /// (see `Descriptor::max_concurrency`)
+    ///
+    /// # Panics
+    ///
+    /// The `command` has to exist.
+    pub fn with_max_concurrency(mut self, command: &str, max_concurrency: usize) -> Self {
+        let command = self.canonical_name(command).to_string();
+        let semaphore = tokio::sync::Semaphore::new(max_concurrency.max(1));
+        self.commands
+            .get_mut(command.as_str())
+            .expect("BUG: limited command does not exist")
+            .concurrency = Some(Arc::new(semaphore));
+        self
+    }
+
+    /// Disables all privileged commands for all clients including the local ones. The commands
+    /// are refused as if the client had only read privilege.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Adds timestamp with millisecond precision (`WhenMs`) to the status of all responses. The
+    /// `When` field keeps reporting whole seconds for compatibility with existing clients.
+    pub fn with_millisecond_timestamps(mut self) -> Self {
+        self.millisecond_timestamps = true;
+        self
+    }
+
+    /// Reports per-chip details of ASC devices provided by the handler in `stats` and `estats`
+    /// responses (see `response::Chips`). The details are removed from the responses by default
+    /// because they make the responses of machines with many chips huge.
+    pub fn with_chip_stats(self) -> Self {
+        self.chip_stats.store(true, Ordering::Relaxed);
+        self
+    }
+
+    /// Enables delta mode of `devs` and `summary` (see `delta` module) keeping at most
+    /// `capacity` snapshots of the responses. Every client polling in delta mode holds one
+    /// snapshot per command so the capacity should exceed the number of such clients.
+    pub fn with_delta_mode(mut self, capacity: usize) -> Self {
+        self.snapshots = Some(Snapshots::new(capacity));
+        self
+    }
+
+    /// Replaces the body of successful response to `command` with its delta when the client
+    /// requested delta mode and the command supports it
+    fn apply_delta(
+        &self,
+        command: &str,
+        parameter: Option<&json::Value>,
+        context: &Context,
+        dispatch: response::Dispatch,
+    ) -> response::Dispatch {
+        let snapshots = match &self.snapshots {
+            Some(snapshots) if DELTA_COMMANDS.contains(&command) && !dispatch.is_error() => {
+                snapshots
+            }
+            _ => return dispatch,
+        };
+        let since = match delta::parse_since(context.extras().get(delta::DELTA_FIELD)) {
+            Some(since) => since,
+            None => return dispatch,
+        };
+        let body = match dispatch.body() {
+            Some(body) => body,
+            None => return dispatch,
+        };
+        let (token, previous) =
+            snapshots.exchange(Snapshots::key(command, parameter), since, body.clone());
+        let delta = previous.map(|previous| delta::diff(&previous, &body));
+        dispatch.with_delta(token, delta)
+    }
+
+    /// Requires remote clients to send a valid token (see `access::Tokens`) with any privileged
+    /// command regardless of their address. The token cannot grant more than the access control
+    /// does. Clients without a valid token keep read privilege.
+    ///
+    /// It also enables `auth` command granting privilege of the token to all following requests
+    /// on the same persistent connection (see `Context::with_session`).
+    pub fn with_tokens(mut self, tokens: Tokens) -> Self {
+        self.commands.insert(
+            AUTH,
+            Descriptor::new(AUTH, HandlerType::Auth, None)
+                .description("Grant privilege of token to the connection")
+                .redacted(support::redact_all),
+        );
+        self.tokens = Some(tokens);
+        self
+    }
+
+    /// Sets the time after which privilege granted by `auth` command expires when the client
+    /// sends no request
+    pub fn with_session_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.session_idle_timeout = timeout;
+        self
+    }
+
+    /// Returns privilege of the client described by `context` or `None` when the client is not
+    /// allowed to access the API at all. Local clients have always full access as well as all
+    /// clients when there is no access control. Remote clients are limited by the privilege
+    /// granted to their session when tokens are required. No client has more than read privilege
+    /// in read-only mode.
+    pub fn privilege(&self, context: &Context) -> Option<Privilege> {
+        self.privilege_with_token(context, None)
+    }
+
+    /// Same as `privilege` for a request carrying `token` which takes precedence over the
+    /// privilege granted to the session
+    fn privilege_with_token(&self, context: &Context, token: Option<&str>) -> Option<Privilege> {
+        let privilege = match (&self.access_control, context.peer_addr) {
+            (Some(access_control), Some(addr)) => access_control.privilege(addr),
+            _ => Some(Privilege::Write),
+        };
+        let privilege = match (&self.tokens, context.peer_addr) {
+            (Some(tokens), Some(_)) => {
+                let granted = token
+                    .and_then(|token| tokens.privilege(token))
+                    .or_else(|| {
+                        context
+                            .session()
+                            .and_then(|session| session.privilege(self.session_idle_timeout))
+                    })
+                    .unwrap_or(Privilege::Read);
+                privilege.map(|privilege| privilege.min(granted))
+            }
+            _ => privilege,
+        };
+        if self.read_only {
+            privilege.map(|privilege| privilege.min(Privilege::Read))
+        } else {
+            privilege
+        }
+    }
+
+    /// Grants privilege of the token in `parameter` to the session of the connection. An invalid
+    /// token revokes the privilege granted before.
+    fn handle_auth(
+        &self,
+        parameter: Option<&json::Value>,
+        context: &Context,
+    ) -> Result<response::Auth> {
+        let session = context.session().ok_or_else(|| {
+            response::Error::from(response::ErrorCode::PersistentConnectionRequired(
+                AUTH.to_string(),
+            ))
+        })?;
+        let tokens = self.tokens.as_ref().expect("BUG: missing tokens");
+        match parameter
+            .and_then(json::Value::as_str)
+            .and_then(|token| tokens.privilege(token))
+        {
+            Some(privilege) => {
+                session.grant(privilege);
+                Ok(response::Auth { privilege })
+            }
+            None => {
+                session.revoke();
+                Err(response::ErrorCode::InvalidToken.into())
+            }
+        }
+    }
+
+    /// Versions of components provided by the handler follow the standard fields. The `version`
+    /// response must be always available so the components are just omitted when the handler
+    /// fails or does not respond in time.
+    async fn handle_version(&self) -> Result<response::VersionInfo> {
+        let extra = match tokio::time::timeout(self.command_timeout, (self.version_extra)()).await {
+            Ok(Ok(extra)) => extra,
+            Ok(Err(_)) | Err(_) => {
+                warn!("CGMiner API: cannot get versions of miner components");
+                vec![]
+            }
+        };
+        Ok(self.miner_info().version_info.clone().merge_fields(extra))
+    }
+
+    /// Existence reflects only registration of the command (or its alias) while the access is
+    /// evaluated for `privilege` of the calling client. The existing command is accessible only
+    /// when the client is allowed to invoke it at least without parameter.
+    fn handle_check(
+        &self,
+        parameter: Option<&json::Value>,
+        privilege: Option<Privilege>,
+    ) -> Result<response::Check> {
         let command =
             parameter.ok_or_else(|| response::Error::from(response::ErrorCode::MissingCheckCmd))?;
-        let result = match command {
-            json::Value::String(command) => self.commands.get(command.as_str()).into(),
-            _ => response::Bool::N,
+        let descriptor = match command {
+            json::Value::String(command) => self.descriptor(self.canonical_name(command)),
+            _ => None,
         };
 
         Ok(response::Check {
-            exists: result,
-            access: result,
+            exists: descriptor.into(),
+            access: descriptor
+                .filter(|descriptor| !descriptor.is_access_denied(None, privilege))
+                .into(),
         })
     }
 
+    fn handle_connections(&self) -> Result<response::Connections> {
+        let statistics = self
+            .server_statistics
+            .as_ref()
+            .expect("BUG: missing server statistics");
+        let clients = statistics
+            .clients()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, client)| response::ClientConnection {
+                idx,
+                addr: client.addr.to_string(),
+                requests: client.requests,
+                errors: client.errors,
+                last_command: client.last_command.unwrap_or_default(),
+                last_seen: client.last_seen,
+            })
+            .collect();
+
+        Ok(response::Connections {
+            totals: response::ConnectionTotals {
+                total: statistics.total_connections(),
+                active: statistics.active_connections() as u64,
+                rejected: statistics.rejected_connections(),
+                read_timeouts: statistics.read_timeouts(),
+            },
+            clients,
+        })
+    }
+
+    fn handle_last_commands(&self) -> Result<response::LastCommands> {
+        let list = self
+            .history
+            .entries()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, entry)| response::LastCommand {
+                idx,
+                when: entry.when,
+                client: entry
+                    .client
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_default(),
+                command: entry.command,
+                parameter: entry.parameter,
+                status: entry.status.status,
+                code: entry.status.code,
+                msg: entry.status.msg,
+            })
+            .collect();
+
+        Ok(response::LastCommands { list })
+    }
+
+    fn handle_help(&self) -> Result<response::Help> {
+        let mut list: Vec<_> = self
+            .exposed_commands()
+            .map(|(name, descriptor)| response::HelpCommand {
+                command: name.to_string(),
+                parameter: descriptor.has_parameters().into(),
+                privileged: descriptor.is_privileged().into(),
+                privileged_parameter: descriptor.is_privileged_parameter().into(),
+                description: descriptor.get_description().unwrap_or_default().to_string(),
+            })
+            .collect();
+        list.sort_by(|a, b| a.command.cmp(&b.command));
+
+        Ok(response::Help { list })
+    }
+
+    /// Describes responses of the exposed command named by `parameter` or of all exposed
+    /// commands when it is missing
+    fn handle_schema(&self, parameter: Option<&json::Value>) -> Result<response::Schema> {
+        let mut commands: Vec<_> = match parameter {
+            None => self.exposed_commands().map(|(name, _)| *name).collect(),
+            Some(parameter) => {
+                let name = parameter
+                    .as_str()
+                    .map(|command| self.canonical_name(command))
+                    .and_then(|command| self.commands.get_key_value(command))
+                    .filter(|(name, _)| self.is_exposed(name))
+                    .map(|(name, _)| *name)
+                    .ok_or_else(|| response::Error::from(response::ErrorCode::InvalidCommand))?;
+                vec![name]
+            }
+        };
+        commands.sort_unstable();
+
+        let signature = &self.miner_info().signature;
+        let mut list = vec![];
+        for command in commands {
+            let sections = self.commands[command]
+                .schema
+                .unwrap_or_else(|| standard_schema(command));
+            for section in sections {
+                for field in section.fields {
+                    list.push(response::SchemaField {
+                        command: command.to_string(),
+                        section: section.name.to_string(),
+                        variant: section.variant.map(str::to_string),
+                        field: field.name.replace(crate::SIGNATURE_TAG, signature),
+                        kind: field.kind,
+                        optional: field.optional.into(),
+                    });
+                }
+            }
+        }
+        Ok(response::Schema { list })
+    }
+
+    fn handle_api_stats(&self) -> Result<response::ApiStats> {
+        let mut list: Vec<_> = self
+            .exposed_commands()
+            .map(|(name, descriptor)| {
+                let metrics = descriptor.get_metrics();
+                let seconds = |time: Option<Duration>| time.unwrap_or_default().as_secs_f64();
+                response::ApiStat {
+                    command: name.to_string(),
+                    calls: metrics.calls(),
+                    errors: metrics.errors(),
+                    cancelled: metrics.cancelled(),
+                    min: seconds(metrics.min_time()),
+                    avg: seconds(metrics.avg_time()),
+                    max: seconds(metrics.max_time()),
+                }
+            })
+            .collect();
+        list.sort_by(|a, b| a.command.cmp(&b.command));
+
+        Ok(response::ApiStats { list })
+    }
+
     /// Handles a single `command` with optional `parameter`. `multi_command` flag ensures that no
-    /// command with parameters can be processed in batched mode.
+    /// privileged command can be processed in batched mode while the other commands are checked
+    /// and handled one by one with the shared `parameter`. Commands not allowed for the client
+    /// with `privilege` are refused. All observers are notified about the command, it is recorded
+    /// to the history and mutating commands are audited.
     async fn handle_single(
         &self,
         command: &str,
         parameter: Option<&json::Value>,
         multi_command: bool,
+        privilege: Option<Privilege>,
+        context: &Context,
     ) -> response::Dispatch {
-        let dispatch = match self.commands.get(command) {
+        // Credentials (e.g. the token of `auth` command) are never passed to observers
+        let redacted = self.redact(command, parameter, multi_command);
+        observer::notify_all(&self.observers, |observer| {
+            observer.on_request(context, command, redacted.as_ref())
+        });
+        let start = Instant::now();
+        let mut mutating = false;
+        let dispatch = match self.descriptor(command) {
             Some(descriptor) => {
-                if multi_command && descriptor.has_parameters() {
-                    Err(response::ErrorCode::AccessDeniedCmd(command.to_string()).into())
-                } else {
-                    let check_result = descriptor
-                        .parameter_check
-                        .as_ref()
-                        .map_or(Ok(()), |check| check(command, &parameter));
-                    match check_result {
-                        Ok(_) => match &descriptor.handler {
-                            HandlerType::ParameterLess(handle) => handle().await,
-                            HandlerType::Parameter(handle) => handle(parameter).await,
-                            HandlerType::Version => {
-                                self.handle_version().map(|response| response.into())
-                            }
-                            HandlerType::Check => {
-                                self.handle_check(parameter).map(|response| response.into())
-                            }
-                        },
-                        Err(response) => Err(response),
+                mutating = descriptor.is_privileged_with(parameter);
+                let guard = CancellationGuard(Some(&descriptor.metrics));
+                let mut dispatch = self
+                    .dispatch(
+                        command,
+                        descriptor,
+                        parameter,
+                        multi_command,
+                        privilege,
+                        context,
+                    )
+                    .await
+                    .unwrap_or_else(|error| error.into());
+                guard.complete();
+                dispatch = self.apply_delta(command, parameter, context, dispatch);
+                descriptor
+                    .metrics
+                    .record(start.elapsed(), dispatch.is_error());
+                dispatch
+            }
+            None => response::ErrorCode::InvalidCommand.into(),
+        };
+        let duration = start.elapsed();
+        let status = dispatch.observed_status(&self.miner_info().signature);
+        observer::notify_all(&self.observers, |observer| {
+            observer.on_response(context, command, &status, duration)
+        });
+        self.remember(context, command, redacted.as_ref(), &status);
+        if mutating {
+            self.audit(context, command, redacted.as_ref(), &status);
+        }
+        dispatch
+    }
+
+    async fn dispatch(
+        &self,
+        command: &str,
+        descriptor: &Descriptor,
+        parameter: Option<&json::Value>,
+        multi_command: bool,
+        privilege: Option<Privilege>,
+        context: &Context,
+    ) -> Result<response::Dispatch> {
+        if (multi_command && descriptor.is_multi_command_denied(parameter))
+            || descriptor.is_access_denied(parameter, privilege)
+        {
+            Err(response::ErrorCode::AccessDeniedCmd(command.to_string()).into())
+        } else {
+            let check_result = descriptor
+                .parameter_check
+                .as_ref()
+                .map_or(Ok(()), |check| check(command, &parameter));
+            let timeout = descriptor.timeout.unwrap_or(self.command_timeout);
+            let concurrency = descriptor.concurrency.as_ref();
+            let mutating = descriptor.is_privileged_with(parameter);
+            match check_result {
+                Ok(_) => match &descriptor.handler {
+                    HandlerType::ParameterLess(handle) => {
+                        self.handle_cached(command, None, descriptor.cache_ttl, || {
+                            Self::handle_with_timeout(
+                                command,
+                                timeout,
+                                concurrency,
+                                mutating,
+                                handle,
+                            )
+                        })
+                        .await
                     }
-                }
+                    HandlerType::Parameter(handle) => {
+                        self.handle_cached(command, parameter, descriptor.cache_ttl, || {
+                            Self::handle_with_timeout(
+                                command,
+                                timeout,
+                                concurrency,
+                                mutating,
+                                || handle(parameter),
+                            )
+                        })
+                        .await
+                    }
+                    HandlerType::Version => {
+                        self.handle_version().await.map(|response| response.into())
+                    }
+                    HandlerType::Check => self
+                        .handle_check(parameter, privilege)
+                        .map(|response| response.into()),
+                    HandlerType::Help => self.handle_help().map(|response| response.into()),
+                    HandlerType::ApiStats => {
+                        self.handle_api_stats().map(|response| response.into())
+                    }
+                    HandlerType::Schema => self
+                        .handle_schema(parameter)
+                        .map(|response| response.into()),
+                    HandlerType::Connections => {
+                        self.handle_connections().map(|response| response.into())
+                    }
+                    HandlerType::LastCommands => {
+                        self.handle_last_commands().map(|response| response.into())
+                    }
+                    HandlerType::Auth => self
+                        .handle_auth(parameter, context)
+                        .map(|response| response.into()),
+                    // Shutdown is dispatched separately with deferred action
+                    HandlerType::Shutdown(_) => {
+                        Err(response::ErrorCode::AccessDeniedCmd(command.to_string()).into())
+                    }
+                    // Subscription is possible only as a standalone command
+                    HandlerType::Events => Err(response::ErrorCode::PersistentConnectionRequired(
+                        command.to_string(),
+                    )
+                    .into()),
+                },
+                Err(response) => Err(response),
+            }
+        }
+    }
+
+    /// Returns response of the `command` with `parameter` from the cache when it is younger than
+    /// `cache_ttl`. Otherwise the response is obtained from `handle` and cached unless it is an
+    /// error. Concurrent requests of the same command wait for the response of the first one
+    /// instead of invoking the handler again.
+    async fn handle_cached<F, R>(
+        &self,
+        command: &str,
+        parameter: Option<&json::Value>,
+        cache_ttl: Option<Duration>,
+        handle: F,
+    ) -> Result<response::Dispatch>
+    where
+        F: FnOnce() -> R,
+        R: Future<Output = Result<response::Dispatch>>,
+    {
+        let cache_ttl = match cache_ttl {
+            Some(cache_ttl) => cache_ttl,
+            None => return handle().await,
+        };
+        let slot = self
+            .cache
+            .slot(command.to_string(), parameter.map(ToString::to_string));
+
+        let mut cached = slot.lock().await;
+        if let Some(cached) = cached.as_ref() {
+            let age = cached.created.elapsed();
+            if age < cache_ttl {
+                return Ok(cached.dispatch.clone().with_age(age));
+            }
+        }
+        // The cached response keeps the time when the handler collected its data
+        let result = handle()
+            .await
+            .map(|dispatch| dispatch.or_collected(T::when()));
+        if let Ok(dispatch) = &result {
+            *cached = Some(CachedDispatch {
+                dispatch: dispatch.clone(),
+                created: Instant::now(),
+                expires: Instant::now() + cache_ttl,
+            });
+        }
+        result
+    }
+
+    /// Waits for the handler of the `command` at most for the `timeout` so that the client gets
+    /// a response even when the hardware stops responding. The handler is not invoked until
+    /// a permit of the `concurrency` is acquired (see `Descriptor::max_concurrency`).
+    ///
+    /// A `mutating` handler is never cancelled once it has been invoked because the miner could
+    /// be left half-configured. It keeps running in a separate task when it doesn't finish in
+    /// time.
+    async fn handle_with_timeout<F>(
+        command: &str,
+        timeout: Duration,
+        concurrency: Option<&Arc<tokio::sync::Semaphore>>,
+        mutating: bool,
+        handle: F,
+    ) -> Result<response::Dispatch>
+    where
+        F: FnOnce() -> AsyncHandler,
+    {
+        let handler = async move {
+            // The permit is held until the handler finishes or is cancelled
+            let permit = match concurrency {
+                Some(concurrency) => Some(Permit::acquire(concurrency.clone()).await),
+                None => None,
+            };
+            let handled = handle();
+            if mutating {
+                tokio::spawn(async move {
+                    let _permit = permit;
+                    handled.await
+                })
+                .await
+                .unwrap_or_else(|_| {
+                    Err(response::ErrorCode::CommandFailed(command.to_string()).into())
+                })
+            } else {
+                let _permit = permit;
+                handled.await
             }
-            None => Err(response::ErrorCode::InvalidCommand.into()),
         };
+        tokio::time::timeout(timeout, handler)
+            .await
+            .unwrap_or_else(|_| {
+                Err(response::ErrorCode::CommandTimeout(
+                    command.to_string(),
+                    timeout.as_millis() as u64,
+                )
+                .into())
+            })
+    }
 
-        dispatch.unwrap_or_else(|error| error.into())
+    /// Handles a command of multi-command request. A panic in the handler is turned into an error
+    /// response so that the other commands of the request are not affected.
+    async fn handle_batched(
+        &self,
+        command: &str,
+        parameter: Option<&json::Value>,
+        privilege: Option<Privilege>,
+        context: &Context,
+    ) -> response::Dispatch {
+        AssertUnwindSafe(self.handle_single(command, parameter, true, privilege, context))
+            .catch_unwind()
+            .await
+            .unwrap_or_else(|_| response::ErrorCode::CommandFailed(command.to_string()).into())
     }
 
     #[inline]
     fn get_single_response(&self, dispatch: response::Dispatch) -> ResponseType {
+        // Both timestamps are derived from the same reading of the clock
+        let (when, when_ms) = if self.millisecond_timestamps {
+            let when_ms = T::when_ms();
+            ((when_ms / 1000) as response::Time, Some(when_ms))
+        } else {
+            (T::when(), None)
+        };
+        let miner_info = self.miner_info();
         ResponseType::Single(dispatch.into_response(
-            T::when(),
-            &self.miner_signature,
-            &self.description,
+            when,
+            when_ms,
+            &miner_info.signature,
+            &miner_info.description,
         ))
     }
 
@@ -398,36 +2316,291 @@ where
         self.get_single_response(error_code.into())
     }
 
-    /// Handles a command request that can actually be a batched request of multiple commands
-    pub async fn handle(&self, command_request: Request) -> ResponseType {
-        let command = match command_request
-            .value
-            .get("command")
-            .and_then(json::Value::as_str)
-        {
-            None => return self.get_single_response(response::ErrorCode::MissingCommand.into()),
-            Some(value) => value,
+    /// Prepares the shutdown action when the `command` is `quit` or `restart`
+    fn shutdown(&self, command: &str) -> Option<(ShutdownKind, DeferredAction)> {
+        let kind = match self.descriptor(command)?.handler {
+            HandlerType::Shutdown(kind) => kind,
+            _ => return None,
+        };
+        let shutdown_handler = self
+            .shutdown_handler
+            .as_ref()
+            .expect("BUG: missing shutdown handler");
+        Some((kind, shutdown_handler(kind)))
+    }
+
+    /// Subscribes to events when the `command` is `events`
+    fn subscribe(&self, command: &str) -> Option<Subscription> {
+        match self.descriptor(command)?.handler {
+            HandlerType::Events => {}
+            _ => return None,
+        }
+        let event_sink = self.event_sink.as_ref().expect("BUG: missing event sink");
+        Some(event_sink.subscribe())
+    }
+
+    /// Handles a command request that can actually be a batched request of multiple commands.
+    /// The `context` describes the client which sent the request.
+    pub async fn handle(&self, command_request: Request, context: &Context) -> ResponseType {
+        self.handle_deferred(command_request, context).await.0
+    }
+
+    /// Handles a raw request (see `Request::from_bytes`) and returns the response encoded in the
+    /// format of the request including the terminating NUL byte. Deferred actions of `quit` and
+    /// `restart` are not executed (see `handle_deferred`).
+    pub async fn handle_raw(&self, data: &[u8], context: &Context) -> Vec<u8> {
+        let (response, format) = match Request::from_bytes(data) {
+            Ok((request, format)) => (self.handle(request, context).await, format),
+            Err(e) => (self.error_response(e.into()), Format::Json),
         };
-        let commands: Vec<_> = command
-            .split('+')
+        let mut buf = BytesMut::new();
+        Codec::new(format)
+            .encode(response, &mut buf)
+            .expect("BUG: cannot serialize response");
+        buf.to_vec()
+    }
+
+    /// Handles a command request as `handle` does and also returns an optional action which has
+    /// to be executed after the response has been sent to the client. The `events` command is
+    /// refused because the subscription requires a persistent connection.
+    pub async fn handle_deferred(
+        &self,
+        command_request: Request,
+        context: &Context,
+    ) -> (ResponseType, Option<DeferredAction>) {
+        self.refuse_events(self.handle_continued(command_request, context).await)
+    }
+
+    /// Turns subscription of events into an error for transports which cannot send them
+    pub(crate) fn refuse_events(
+        &self,
+        handled: (ResponseType, Option<Continuation>),
+    ) -> (ResponseType, Option<DeferredAction>) {
+        match handled {
+            (response, None) => (response, None),
+            (response, Some(Continuation::Action(action))) => (response, Some(action)),
+            (_, Some(Continuation::Events(_))) => (
+                self.error_response(response::ErrorCode::PersistentConnectionRequired(
+                    EVENTS.to_string(),
+                )),
+                None,
+            ),
+        }
+    }
+
+    /// Handles a command request as `handle_deferred` does while the continuation may be also
+    /// a subscription of events for transports with persistent connections
+    pub async fn handle_continued(
+        &self,
+        command_request: Request,
+        context: &Context,
+    ) -> (ResponseType, Option<Continuation>) {
+        let RequestBody {
+            command,
+            parameter,
+            extras,
+        } = match command_request.body {
+            None => {
+                return (
+                    self.get_single_response(response::ErrorCode::MissingCommand.into()),
+                    None,
+                )
+            }
+            Some(body) => body,
+        };
+        // The token is consumed here so that it does not reach handlers, observers or logs
+        let mut extras = extras;
+        let token = extras.remove(TOKEN_FIELD);
+        let context = &context.clone().with_extras(extras);
+        let privilege =
+            self.privilege_with_token(context, token.as_ref().and_then(json::Value::as_str));
+        // Any request with the delimiter is a batch, even with just one command name (e.g.
+        // `summary+`), so that its response is always a multi-response with one section per
+        // distinct command in order. Unknown commands get sections with their own error.
+        let batched = command.contains(BATCH_DELIMITER);
+        let names: Vec<_> = command
+            .split(BATCH_DELIMITER)
             .filter(|command| command.len() > 0)
+            .map(|command| self.canonical_name(command))
             .collect();
-        let parameter = command_request.value.get("parameter");
+        // Command present multiple times in a batch is handled only once and its response
+        // keeps the position of the first occurrence
+        let mut commands = Vec::with_capacity(names.len());
+        for name in &names {
+            if !commands.contains(name) {
+                commands.push(*name);
+            }
+        }
+        let parameter = parameter.as_ref();
 
         if commands.len() == 0 {
-            self.get_single_response(response::ErrorCode::InvalidCommand.into())
-        } else if commands.len() == 1 {
-            self.get_single_response(self.handle_single(command, parameter, false).await)
+            // There is no command to name the section of a batch after (e.g. `+`) so the request
+            // is invalid as a whole just like an empty command
+            (
+                self.get_single_response(response::ErrorCode::InvalidCommand.into()),
+                None,
+            )
+        } else if !batched {
+            let command = commands[0];
+            if let Some(subscription) = self.subscribe(command) {
+                return (
+                    self.get_single_response(response::EventsSubscribed.into()),
+                    Some(Continuation::Events(subscription)),
+                );
+            }
+            // Clients without write privilege get the same response as with the other privileged
+            // commands
+            let shutdown = match privilege {
+                Some(Privilege::Write) => self.shutdown(command),
+                _ => None,
+            };
+            match shutdown {
+                Some((kind, action)) => {
+                    // The action is executed later so the command itself takes no time
+                    let redacted = self.redact(command, parameter, false);
+                    observer::notify_all(&self.observers, |observer| {
+                        observer.on_request(context, command, redacted.as_ref())
+                    });
+                    let status = observer::ResponseStatus {
+                        status: response::Status::S,
+                        code: 0,
+                        msg: kind.action().to_string(),
+                    };
+                    observer::notify_all(&self.observers, |observer| {
+                        observer.on_response(context, command, &status, Duration::default())
+                    });
+                    self.remember(context, command, redacted.as_ref(), &status);
+                    self.audit(context, command, redacted.as_ref(), &status);
+                    (
+                        ResponseType::Action(ActionResponse::new(kind.action())),
+                        Some(Continuation::Action(action)),
+                    )
+                }
+                None => (
+                    self.get_single_response(
+                        self.handle_single(command, parameter, false, privilege, context)
+                            .await,
+                    ),
+                    None,
+                ),
+            }
         } else {
+            // Commands are handled concurrently while the responses keep the order of commands
+            let dispatches = futures::future::join_all(
+                commands
+                    .iter()
+                    .map(|command| self.handle_batched(command, parameter, privilege, context)),
+            )
+            .await;
+
             let mut responses = MultiResponse::new();
-            for command in commands {
-                if let ResponseType::Single(response) =
-                    self.get_single_response(self.handle_single(command, parameter, true).await)
-                {
+            for (command, dispatch) in commands.into_iter().zip(dispatches) {
+                if let ResponseType::Single(response) = self.get_single_response(dispatch) {
                     responses.add_response(command, response);
                 }
             }
-            ResponseType::Multi(responses)
+            (ResponseType::Multi(responses), None)
+        }
+    }
+}
+
+/// Error of building a receiver with a command which has been already registered
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct DuplicateCommand(pub &'static str);
+
+impl fmt::Display for DuplicateCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "command '{}' is already registered", self.0)
+    }
+}
+
+impl std::error::Error for DuplicateCommand {}
+
+/// Builds a receiver extended with firmware specific commands. The custom commands are handled
+/// exactly as the standard ones so they are reported by `check` and `help` and they can be part
+/// of multi-command requests.
+pub struct ReceiverBuilder<T = UnixTime> {
+    receiver: Receiver<T>,
+    /// The first command which has been registered twice
+    duplicate: Option<&'static str>,
+}
+
+impl<T> ReceiverBuilder<T>
+where
+    T: When,
+{
+    fn is_registered(&self, name: &str) -> bool {
+        self.receiver.commands.contains_key(name) || self.receiver.aliases.contains_key(name)
+    }
+
+    /// Registers a command described by the `descriptor`. Neither the command `name` nor its
+    /// aliases may be the same as any name of standard or previously registered command.
+    pub fn add(mut self, name: &'static str, descriptor: Descriptor) -> Self {
+        let names: Vec<_> = std::iter::once(name)
+            .chain(descriptor.get_aliases().iter().copied())
+            .collect();
+        let duplicate = names
+            .iter()
+            .enumerate()
+            .find(|(i, name)| self.is_registered(name) || names[..*i].contains(name))
+            .map(|(_, name)| *name);
+        match duplicate {
+            Some(duplicate) => {
+                self.duplicate.get_or_insert(duplicate);
+            }
+            None => {
+                for alias in descriptor.get_aliases() {
+                    self.receiver.aliases.insert(alias, name);
+                }
+                self.receiver.commands.insert(name, descriptor);
+            }
+        }
+        self
+    }
+
+    /// Registers a command without parameter. The response can be built from
+    /// `response::CustomResponse`.
+    pub fn add_parameterless<F, R, S>(self, name: &'static str, handler: F) -> Self
+    where
+        F: Fn() -> R + Send + Sync + 'static,
+        R: Future<Output = Result<S>> + Send + 'static,
+        S: Into<response::Dispatch> + 'static,
+    {
+        let handler: ParameterLessHandler = Box::new(move || {
+            let response = handler();
+            Box::pin(async move { response.await.map(Into::into) })
+        });
+        self.add(
+            name,
+            Descriptor::new(name, HandlerType::ParameterLess(handler), None),
+        )
+    }
+
+    /// Registers a command with optional parameter. The `check` validates the parameter before
+    /// the `handler` is invoked and it also applies to the parameter shared by multiple commands.
+    pub fn add_with_parameter<C, F, R, S>(self, name: &'static str, check: C, handler: F) -> Self
+    where
+        C: Fn(&str, &Option<&json::Value>) -> Result<()> + Send + Sync + 'static,
+        F: Fn(Option<&json::Value>) -> R + Send + Sync + 'static,
+        R: Future<Output = Result<S>> + Send + 'static,
+        S: Into<response::Dispatch> + 'static,
+    {
+        let check: ParameterCheckHandler = Box::new(check);
+        let handler: ParameterHandler = Box::new(move |parameter| {
+            let response = handler(parameter);
+            Box::pin(async move { response.await.map(Into::into) })
+        });
+        self.add(
+            name,
+            Descriptor::new(name, HandlerType::Parameter(handler), check),
+        )
+    }
+
+    /// Returns the receiver or the first command which has been registered more than once
+    pub fn build(self) -> std::result::Result<Receiver<T>, DuplicateCommand> {
+        match self.duplicate {
+            Some(name) => Err(DuplicateCommand(name)),
+            None => Ok(self.receiver),
         }
     }
 }
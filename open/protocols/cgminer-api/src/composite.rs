@@ -0,0 +1,272 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Assembling of `command::Handler` from components which provide only some of the commands
+//!
+//! The standard commands are split into groups (`PoolHandler`, `MinerHandler`, `DeviceHandler`
+//! and `TunerHandler`) and `CompositeHandler` delegates each group to exactly one component.
+//! Every `command::Handler` implements all the groups so that it can be used as a base provider
+//! whose groups are replaced by dedicated components (see `CompositeBuilder::with_base`). The
+//! last component bound to a group wins and `CompositeBuilder::build` is available only when
+//! all the groups have a provider, i.e. a missing group is reported at compile time.
+//!
+//! Extended commands (e.g. `temps` or `fans`) are not part of any group. Components providing
+//! them implement `CommandProvider` and they are registered together with the groups by
+//! `CompositeBuilder::receiver_builder`.
+
+use crate::command::{self, Handler, Result};
+use crate::support::When;
+
+use std::sync::Arc;
+
+/// Defines groups of handler methods listed by `command::handler_methods`. Every group is
+/// implemented by every `Handler` and it is forwarded through `Arc` so that one component can
+/// be shared by multiple groups. `CompositeHandler` delegates the methods of each group to its
+/// provider.
+macro_rules! handler_groups {
+    (
+        $(
+            $(#[$group_attr:meta])*
+            $group:ident($field:ident: $param:ident) {
+                $(
+                    $(#[$attr:meta])*
+                    fn $method:ident(
+                        &self $(, $arg:ident: $type:ty)* $(,)?
+                    ) -> Result<$response:ty> $(= $default:expr)?;
+                )+
+            }
+        )+
+    ) => {
+        $(
+            $(#[$group_attr])*
+            #[async_trait::async_trait]
+            pub trait $group: Send + Sync {
+                $(
+                    $(#[$attr])*
+                    async fn $method(&self $(, $arg: $type)*) -> Result<$response>;
+                )+
+            }
+
+            #[async_trait::async_trait]
+            impl<T> $group for T
+            where
+                T: Handler + ?Sized,
+            {
+                $(
+                    async fn $method(&self $(, $arg: $type)*) -> Result<$response> {
+                        Handler::$method(self $(, $arg)*).await
+                    }
+                )+
+            }
+
+            #[async_trait::async_trait]
+            impl<T> $group for Arc<T>
+            where
+                T: $group + ?Sized,
+            {
+                $(
+                    async fn $method(&self $(, $arg: $type)*) -> Result<$response> {
+                        $group::$method(&**self $(, $arg)*).await
+                    }
+                )+
+            }
+        )+
+
+        #[async_trait::async_trait]
+        impl<$($param),+> Handler for CompositeHandler<$($param),+>
+        where
+            $($param: $group,)+
+        {
+            $($(
+                async fn $method(&self $(, $arg: $type)*) -> Result<$response> {
+                    $group::$method(&self.$field $(, $arg)*).await
+                }
+            )+)+
+        }
+    };
+}
+
+command::handler_methods!(handler_groups);
+
+/// Component providing extended commands which are not part of `command::Handler` (e.g. `temps`,
+/// `fans` or `tempctrl` of a thermal controller)
+pub trait CommandProvider: Send + Sync {
+    /// Descriptors of all provided commands which usually delegate to the shared `self` (see
+    /// `command!` and `commands!` macros)
+    fn commands(self: Arc<Self>) -> command::Map;
+}
+
+/// Placeholder of a group without provider. It implements none of the groups so the builder
+/// cannot be finished until the group is bound.
+#[derive(Debug)]
+pub struct Missing;
+
+/// Handler which delegates every group of commands to its provider
+pub struct CompositeHandler<P, M, D, U> {
+    pools: P,
+    miner: M,
+    devices: D,
+    tuner: U,
+}
+
+impl CompositeHandler<Missing, Missing, Missing, Missing> {
+    /// Creates a builder with no providers bound
+    pub fn builder() -> CompositeBuilder {
+        CompositeBuilder {
+            pools: Missing,
+            miner: Missing,
+            devices: Missing,
+            tuner: Missing,
+            commands: command::Map::new(),
+        }
+    }
+}
+
+/// Builder of `CompositeHandler` tracking the bound groups in its type
+pub struct CompositeBuilder<P = Missing, M = Missing, D = Missing, U = Missing> {
+    pools: P,
+    miner: M,
+    devices: D,
+    tuner: U,
+    /// Extended commands of all components bound by `CompositeBuilder::commands`
+    commands: command::Map,
+}
+
+impl CompositeBuilder {
+    /// Binds all groups to the `handler`. The groups may be replaced by other providers later.
+    pub fn with_base<H>(handler: H) -> CompositeBuilder<Arc<H>, Arc<H>, Arc<H>, Arc<H>>
+    where
+        H: Handler,
+    {
+        let handler = Arc::new(handler);
+        CompositeBuilder {
+            pools: handler.clone(),
+            miner: handler.clone(),
+            devices: handler.clone(),
+            tuner: handler,
+            commands: command::Map::new(),
+        }
+    }
+}
+
+impl<P, M, D, U> CompositeBuilder<P, M, D, U> {
+    /// Binds pool commands to the `provider` replacing the previous one
+    pub fn pools<T: PoolHandler>(self, provider: T) -> CompositeBuilder<T, M, D, U> {
+        CompositeBuilder {
+            pools: provider,
+            miner: self.miner,
+            devices: self.devices,
+            tuner: self.tuner,
+            commands: self.commands,
+        }
+    }
+
+    /// Binds miner commands to the `provider` replacing the previous one
+    pub fn miner<T: MinerHandler>(self, provider: T) -> CompositeBuilder<P, T, D, U> {
+        CompositeBuilder {
+            pools: self.pools,
+            miner: provider,
+            devices: self.devices,
+            tuner: self.tuner,
+            commands: self.commands,
+        }
+    }
+
+    /// Binds device commands to the `provider` replacing the previous one
+    pub fn devices<T: DeviceHandler>(self, provider: T) -> CompositeBuilder<P, M, T, U> {
+        CompositeBuilder {
+            pools: self.pools,
+            miner: self.miner,
+            devices: provider,
+            tuner: self.tuner,
+            commands: self.commands,
+        }
+    }
+
+    /// Binds tuner commands to the `provider` replacing the previous one
+    pub fn tuner<T: TunerHandler>(self, provider: T) -> CompositeBuilder<P, M, D, T> {
+        CompositeBuilder {
+            pools: self.pools,
+            miner: self.miner,
+            devices: self.devices,
+            tuner: provider,
+            commands: self.commands,
+        }
+    }
+
+    /// Binds extended commands of the `provider`. A command provided by multiple components is
+    /// handled by the last one.
+    pub fn commands<T: CommandProvider>(mut self, provider: Arc<T>) -> Self {
+        self.commands.extend(provider.commands());
+        self
+    }
+}
+
+impl<P, M, D, U> CompositeBuilder<P, M, D, U>
+where
+    P: PoolHandler,
+    M: MinerHandler,
+    D: DeviceHandler,
+    U: TunerHandler,
+{
+    fn split(self) -> (CompositeHandler<P, M, D, U>, command::Map) {
+        let handler = CompositeHandler {
+            pools: self.pools,
+            miner: self.miner,
+            devices: self.devices,
+            tuner: self.tuner,
+        };
+        (handler, self.commands)
+    }
+
+    /// Builds the handler of the standard commands. Extended commands bound by
+    /// `CompositeBuilder::commands` are registered only by `CompositeBuilder::receiver_builder`.
+    pub fn build(self) -> CompositeHandler<P, M, D, U> {
+        self.split().0
+    }
+}
+
+impl<P, M, D, U> CompositeBuilder<P, M, D, U>
+where
+    P: PoolHandler + 'static,
+    M: MinerHandler + 'static,
+    D: DeviceHandler + 'static,
+    U: TunerHandler + 'static,
+{
+    /// Creates a builder of a receiver which handles the standard commands by the composite
+    /// handler and which has all the extended commands registered. Extended commands colliding
+    /// with the standard ones are reported by `command::ReceiverBuilder::build`.
+    pub fn receiver_builder<T>(
+        self,
+        miner_signature: String,
+        miner_version: String,
+    ) -> command::ReceiverBuilder<T>
+    where
+        T: When,
+    {
+        let (handler, commands) = self.split();
+        commands.into_iter().fold(
+            command::Receiver::builder(handler, miner_signature, miner_version),
+            |builder, (name, descriptor)| builder.add(name, descriptor),
+        )
+    }
+}
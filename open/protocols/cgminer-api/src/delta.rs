@@ -0,0 +1,168 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Delta mode of polled commands (see `command::Receiver::with_delta_mode`)
+//!
+//! A client opts in by sending field `delta` with the request of `devs` or `summary`. Its value
+//! is either `true` or the sequence token `Seq` from the status of the previous response to the
+//! same command and parameter. The status of every response in delta mode carries a new `Seq`
+//! and flag `Delta` telling whether the body is a delta or the full response. The full response
+//! is sent whenever the token is unknown, belongs to another command or its snapshot has been
+//! already evicted.
+//!
+//! The delta keeps the list of sections of the full response:
+//!
+//! - the list has always as many sections as the full response
+//! - unchanged section is an empty object `{}`
+//! - changed section contains only the fields whose values differ (each field is replaced as
+//!   whole) and names of removed fields listed in `$removed`
+//! - section which is new or is not an object is sent in full
+//!
+//! A field is never reported as removed just because it is missing in the delta, only the ones
+//! listed in `$removed` are gone.
+
+use crate::json;
+use crate::ordered::{self, Value};
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Field of the request enabling delta mode (see module documentation)
+pub const DELTA_FIELD: &str = "delta";
+
+/// Key of the changed section listing names of the removed fields
+pub const REMOVED_KEY: &str = "$removed";
+
+/// Computes delta of the list of sections `new` against the `old` one
+pub fn diff(old: &Value, new: &Value) -> Value {
+    let (old, new) = match (old, new) {
+        (Value::Array(old), Value::Array(new)) => (old, new),
+        _ => return new.clone(),
+    };
+    Value::Array(
+        new.iter()
+            .enumerate()
+            .map(|(i, section)| match old.get(i) {
+                Some(previous) => diff_section(previous, section),
+                None => section.clone(),
+            })
+            .collect(),
+    )
+}
+
+fn diff_section(old: &Value, new: &Value) -> Value {
+    let (old, new) = match (old, new) {
+        (Value::Object(old), Value::Object(new)) => (old, new),
+        _ => return new.clone(),
+    };
+    let mut section: ordered::Map = new
+        .iter()
+        .filter(|(key, value)| old.get(key) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    let removed: Vec<_> = old
+        .keys()
+        .filter(|key| !new.contains_key(key))
+        .cloned()
+        .map(Value::String)
+        .collect();
+    if !removed.is_empty() {
+        section.insert(REMOVED_KEY.to_string(), Value::Array(removed));
+    }
+    Value::Object(section)
+}
+
+/// Body of a response sent in delta mode identified by its sequence token
+struct Snapshot {
+    token: u64,
+    /// Command with JSON encoded parameter the body is a response to
+    key: String,
+    body: Value,
+}
+
+/// Bounded store of the last bodies sent in delta mode. The oldest snapshot is evicted when the
+/// store is full so a client which polls too rarely gets the full response.
+pub(crate) struct Snapshots {
+    capacity: usize,
+    inner: Mutex<SnapshotsInner>,
+}
+
+struct SnapshotsInner {
+    last_token: u64,
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl Snapshots {
+    /// Builds a store keeping at most `capacity` snapshots (at least one)
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            inner: Mutex::new(SnapshotsInner {
+                last_token: 0,
+                snapshots: VecDeque::with_capacity(capacity),
+            }),
+        }
+    }
+
+    /// Builds the key of snapshots of `command` with `parameter`
+    pub fn key(command: &str, parameter: Option<&json::Value>) -> String {
+        match parameter {
+            Some(parameter) => format!("{}:{}", command, parameter),
+            None => command.to_string(),
+        }
+    }
+
+    /// Stores the `body` sent for `key` and returns its token together with the body of the
+    /// snapshot identified by `since` if it is still known for the same `key`
+    pub fn exchange(&self, key: String, since: Option<u64>, body: Value) -> (u64, Option<Value>) {
+        let mut inner = self.inner.lock().expect("BUG: poisoned delta snapshots");
+        let previous = since.and_then(|since| {
+            inner
+                .snapshots
+                .iter()
+                .position(|snapshot| snapshot.token == since && snapshot.key == key)
+        });
+        // Every token is used just once so the previous snapshot is not needed anymore
+        let previous = previous
+            .and_then(|i| inner.snapshots.remove(i))
+            .map(|snapshot| snapshot.body);
+        while inner.snapshots.len() >= self.capacity {
+            inner.snapshots.pop_front();
+        }
+        inner.last_token += 1;
+        let token = inner.last_token;
+        inner.snapshots.push_back(Snapshot { token, key, body });
+        (token, previous)
+    }
+}
+
+/// Parses the value of `DELTA_FIELD`. It returns `None` when delta mode has not been requested
+/// and `Some(None)` when the client has no valid token.
+pub(crate) fn parse_since(value: Option<&json::Value>) -> Option<Option<u64>> {
+    match value? {
+        json::Value::Bool(false) | json::Value::Null => None,
+        json::Value::Number(token) => Some(token.as_u64()),
+        json::Value::String(token) => Some(token.parse().ok()),
+        _ => Some(None),
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Events pushed to subscribed clients (see `command::Receiver::with_event_sink`)
+//!
+//! A client subscribes with the `events` command over a TCP or Unix socket connection which is
+//! then kept open. After the standard response the server sends every published event as
+//! a JSON object terminated by a NUL byte:
+//!
+//! `{"EVENT":"ShareAccepted","When":1577836800,"Data":{"Pool":0}}`
+//!
+//! Each subscriber has a queue of limited capacity. When a subscriber does not read the events
+//! fast enough the oldest events are dropped and the subscriber is notified about their count:
+//!
+//! `{"EVENT":"Dropped","When":1577836800,"Count":5}`
+
+use crate::json;
+use crate::response;
+
+use ii_async_compat::tokio;
+use tokio::sync::broadcast;
+
+use serde::Serialize;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Kind of a miner event
+#[derive(Serialize, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum EventKind {
+    NewJob,
+    ShareAccepted,
+    ShareRejected,
+    TemperatureAlarm,
+    PoolState,
+}
+
+/// Event published by the miner with arbitrary `data` (typically an object)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Event {
+    pub kind: EventKind,
+    pub data: json::Value,
+}
+
+impl Event {
+    pub fn new(kind: EventKind, data: json::Value) -> Self {
+        Self { kind, data }
+    }
+}
+
+/// Item received by a subscriber
+#[derive(Clone, PartialEq, Debug)]
+pub enum Notification {
+    Event(Event),
+    /// Number of events which have been dropped because the subscriber was too slow
+    Dropped(u64),
+}
+
+impl Notification {
+    /// Encodes the notification as a JSON message stamped with time `when`
+    pub fn to_message(&self, when: response::Time) -> json::Value {
+        match self {
+            Notification::Event(event) => json::json!({
+                "EVENT": event.kind,
+                "When": when,
+                "Data": event.data,
+            }),
+            Notification::Dropped(count) => json::json!({
+                "EVENT": "Dropped",
+                "When": when,
+                "Count": count,
+            }),
+        }
+    }
+}
+
+/// Publisher of events shared by the miner and the API. Cloned sinks publish to the same
+/// subscribers.
+#[derive(Clone)]
+pub struct EventSink {
+    sender: broadcast::Sender<Event>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventSink {
+    /// Default number of events queued for each subscriber
+    pub const DEFAULT_CAPACITY: usize = 64;
+
+    /// Creates sink which queues at most `capacity` events for each subscriber
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            sender,
+            dropped: Default::default(),
+        }
+    }
+
+    /// Sends the `event` to all current subscribers. It never blocks.
+    pub fn publish(&self, event: Event) {
+        // There may be no subscriber at all
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> Subscription {
+        Subscription {
+            receiver: self.sender.subscribe(),
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    /// Number of current subscribers
+    pub fn subscribers(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Total number of events dropped for slow subscribers
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for EventSink {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+/// Stream of events for one subscriber
+pub struct Subscription {
+    receiver: broadcast::Receiver<Event>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscription {
+    /// Waits for the next notification. Returns `None` when all sinks have been dropped.
+    pub async fn next(&mut self) -> Option<Notification> {
+        match self.receiver.recv().await {
+            Ok(event) => Some(Notification::Event(event)),
+            Err(broadcast::RecvError::Lagged(count)) => {
+                self.dropped.fetch_add(count, Ordering::Relaxed);
+                Some(Notification::Dropped(count))
+            }
+            Err(broadcast::RecvError::Closed) => None,
+        }
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! History of recently handled commands reported by `lastcommands` command (see
+//! `command::Receiver::with_command_history`)
+//!
+//! Every command handled by the receiver is recorded including unknown and refused ones. The
+//! parameter is stored only as a short summary and parameters containing credentials are
+//! redacted first (see `command::Descriptor::redacted`). The history is a ring buffer of slots
+//! each guarded by its own lock so that concurrent requests contend only when the buffer wraps
+//! around.
+
+use crate::json;
+use crate::observer::ResponseStatus;
+use crate::response;
+
+use std::cmp::Reverse;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Summaries of longer parameters are truncated to this number of characters
+pub const MAX_PARAMETER_SUMMARY: usize = 64;
+
+/// Record of a single handled command
+#[derive(Clone, Debug)]
+pub(crate) struct Entry {
+    pub when: response::Time,
+    /// Address of the client or `None` for local clients
+    pub client: Option<IpAddr>,
+    pub command: String,
+    /// Summary of the (redacted) parameter or an empty string when there is none
+    pub parameter: String,
+    pub status: ResponseStatus,
+}
+
+impl Entry {
+    /// Summarizes the `parameter` as it is shown in the history. Strings are taken as they are
+    /// and the other values in their JSON form.
+    pub fn summary(parameter: Option<&json::Value>) -> String {
+        let summary = match parameter {
+            Some(json::Value::String(parameter)) => parameter.clone(),
+            Some(parameter) => parameter.to_string(),
+            None => return String::new(),
+        };
+        match summary.char_indices().nth(MAX_PARAMETER_SUMMARY) {
+            Some((end, _)) => format!("{}...", &summary[..end]),
+            None => summary,
+        }
+    }
+}
+
+/// Slot of the ring buffer with the entry and its sequence number
+type Slot = Mutex<Option<(u64, Entry)>>;
+
+/// Bounded history of the entries numbered in the order of recording
+pub(crate) struct History {
+    next: AtomicU64,
+    slots: Box<[Slot]>,
+}
+
+impl History {
+    /// Builds a history keeping the last `capacity` entries. Zero capacity disables recording.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            next: AtomicU64::new(0),
+            slots: (0..capacity).map(|_| Mutex::new(None)).collect(),
+        }
+    }
+
+    pub fn record(&self, entry: Entry) {
+        if self.slots.is_empty() {
+            return;
+        }
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut slot = self.slots[(seq % self.slots.len() as u64) as usize]
+            .lock()
+            .expect("BUG: command history lock poisoned");
+        // A slow writer must not replace a newer entry of the following round
+        if !matches!(&*slot, Some((last, _)) if *last > seq) {
+            *slot = Some((seq, entry));
+        }
+    }
+
+    /// Returns all kept entries starting with the newest one
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries: Vec<_> = self
+            .slots
+            .iter()
+            .filter_map(|slot| {
+                slot.lock()
+                    .expect("BUG: command history lock poisoned")
+                    .clone()
+            })
+            .collect();
+        entries.sort_unstable_by_key(|(seq, _)| Reverse(*seq));
+        entries.into_iter().map(|(_, entry)| entry).collect()
+    }
+}
@@ -22,25 +22,39 @@
 
 //! A generic CGMiner API server
 
+pub mod access;
+pub mod audit;
+pub mod client;
 pub mod command;
+pub mod composite;
+pub mod delta;
+pub mod events;
+pub mod history;
+pub mod observer;
+pub mod ordered;
+pub mod parameters;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
 pub mod response;
+pub mod schema;
+pub mod server;
 pub mod support;
+pub mod text;
+#[cfg(any(test, feature = "test_utils"))]
+pub mod test_utils;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 #[cfg(test)]
 mod test;
 
-use ii_logging::macros::*;
+use ii_async_compat::{bytes, tokio_util};
 
-use ii_async_compat::{bytes, futures, tokio, tokio_util};
-
-use bytes::{Buf, BufMut, BytesMut};
-use futures::{SinkExt, StreamExt};
-use serde_json::Deserializer;
+use bytes::{Buf, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 
 use std::io;
 use std::net::SocketAddr;
-use std::sync::Arc;
 
 /// Re-export json because it is required in command handlers
 pub use serde_json as json;
@@ -56,11 +70,67 @@ pub const SIGNATURE_TAG: &str = "{SIGNATURE}";
 /// Default signature of CGMiner API
 pub const PARAMETER_DELIMITER: char = ',';
 
+/// Format of a request which is also used for its response
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Format {
+    Json,
+    /// Plain-text format of CGMiner (see `text` module)
+    Text,
+}
+
+impl Format {
+    /// Detects format from the first non-whitespace byte of a request. JSON request has to
+    /// start with an object.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        data.iter()
+            .find(|byte| !byte.is_ascii_whitespace())
+            .map(|&byte| match byte {
+                b'{' => Format::Json,
+                _ => Format::Text,
+            })
+    }
+}
+
 /// Codec for the CGMiner API.
-/// The `Codec` decodes `Command`s and encodes `ResponseSet`s.
-#[derive(Default, Debug)]
+/// The `Codec` decodes `Command`s and encodes `ResponseSet`s. Responses are encoded in the format
+/// of the last decoded request.
+#[derive(Debug)]
 pub struct Codec {
-    encode_buf: Vec<u8>,
+    format: Format,
+    max_request_size: usize,
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Self::new(Format::Json)
+    }
+}
+
+impl Codec {
+    /// Creates codec encoding responses in the specified `format`
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            max_request_size: server::DEFAULT_MAX_REQUEST_SIZE,
+        }
+    }
+
+    /// Decodes a request with the same parser as `server::Server` does and remembers its format
+    fn decode_request(
+        &mut self,
+        src: &mut BytesMut,
+        complete: bool,
+    ) -> Result<Option<command::Request>, io::Error> {
+        match command::Request::parse(src, complete, self.max_request_size) {
+            Ok(Some((request, format, len))) => {
+                src.advance(len);
+                self.format = format;
+                Ok(Some(request))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
 }
 
 impl Decoder for Codec {
@@ -68,29 +138,17 @@ impl Decoder for Codec {
     type Error = io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let (res, offset) = {
-            let mut stream = Deserializer::from_slice(&*src).into_iter();
-            (stream.next(), stream.byte_offset())
-        };
-
-        match res {
-            Some(Ok(json)) => {
-                src.advance(offset);
-
-                if src.as_ref().iter().any(|byte| !byte.is_ascii_whitespace()) {
-                    // There was a non-whitespace byte following the JSON
-                    Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        "Stray data following JSON",
-                    ))
-                } else {
-                    Ok(Some(command::Request::new(json)))
-                }
-            }
-            Some(Err(err)) if err.is_eof() => Ok(None),
-            Some(Err(err)) => Err(err.into()),
-            None => Ok(None),
+        self.decode_request(src, false)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // Closing the connection terminates plain-text request as well
+        let request = self.decode_request(src, true)?;
+        if request.is_none() {
+            // Only NUL bytes and whitespace are left
+            src.clear();
         }
+        Ok(request)
     }
 }
 
@@ -98,58 +156,18 @@ impl Encoder for Codec {
     type Item = support::ResponseType;
     type Error = io::Error;
 
+    /// The response is encoded directly into `dst` (see `support::ResponseType::encode`)
     fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        self.encode_buf.clear();
-        json::to_writer(&mut self.encode_buf, &item)?;
-        dst.reserve(self.encode_buf.len() + 1);
-        dst.put_slice(&self.encode_buf);
-        // original CGMiner API returns null terminated string as a JSON response
-        dst.put_u8(0);
-        Ok(())
+        item.encode(self.format, dst)
     }
 }
 
-/// Network framing for the API server, uses `Codec`
-#[derive(Debug)]
-struct Framing;
-
-impl ii_wire::Framing for Framing {
-    type Tx = support::ResponseType;
-    type Rx = command::Request;
-    type Error = io::Error;
-    type Codec = Codec;
-}
-
-/// wire-based connection type
-type Connection = ii_wire::Connection<Framing>;
-
-async fn handle_connection_task(mut conn: Connection, command_receiver: Arc<command::Receiver>) {
-    let response = match conn.next().await {
-        Some(Ok(command)) => command_receiver.handle(command).await,
-        Some(Err(err)) if err.kind() == io::ErrorKind::InvalidData => {
-            command_receiver.error_response(response::ErrorCode::InvalidJSON)
-        }
-        _ => return, // We pretty much ignore I/O errors here
-    };
-
-    conn.send(response)
-        .await
-        .unwrap_or_else(|e| warn!("CGMiner API: cannot send response ({})", e));
-}
-
 /// Start up an API server with a `command_receiver` object, listening on `listen_addr`
 pub async fn run(command_receiver: command::Receiver, listen_addr: SocketAddr) -> io::Result<()> {
-    let mut server = ii_wire::Server::bind(&listen_addr)?;
-    let command_receiver = Arc::new(command_receiver);
-
-    while let Some(conn) = server.next().await {
-        if let Ok(conn) = conn {
-            tokio::spawn(handle_connection_task(
-                Connection::new(conn),
-                command_receiver.clone(),
-            ));
-        }
-    }
+    server::Server::bind(listen_addr, command_receiver)
+        .await?
+        .run()
+        .await;
 
     Ok(())
 }
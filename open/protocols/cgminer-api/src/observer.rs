@@ -0,0 +1,134 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Observation of handled commands (see `command::Receiver::with_observer`)
+//!
+//! Observers are notified about every command handled by the receiver including the built-in
+//! ones and each command of a batched request. They cannot change the request nor the response.
+
+use crate::command::Context;
+use crate::json;
+use crate::response;
+
+use ii_logging::macros::*;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Status of the response of an observed command
+#[derive(Clone, Debug)]
+pub struct ResponseStatus {
+    pub status: response::Status,
+    /// Status code as reported to the client
+    pub code: u32,
+    pub msg: String,
+}
+
+impl ResponseStatus {
+    #[inline]
+    pub fn is_error(&self) -> bool {
+        self.status == response::Status::E
+    }
+}
+
+/// Observer of commands handled by `command::Receiver`. The methods are invoked from the task
+/// handling the request so they should not block.
+pub trait Observer: Send + Sync {
+    /// Called before the `command` from the client described by `context` is handled. Credentials
+    /// in the `parameter` are replaced (see `command::Descriptor::redacted`).
+    fn on_request(&self, context: &Context, command: &str, parameter: Option<&json::Value>);
+
+    /// Called when the `command` has been handled after `duration` with response `status`
+    fn on_response(
+        &self,
+        context: &Context,
+        command: &str,
+        status: &ResponseStatus,
+        duration: Duration,
+    );
+}
+
+/// Shared observer as registered in the receiver
+pub type SharedObserver = Arc<dyn Observer>;
+
+/// Observer logging every command and its outcome. It is registered by default and it logs only
+/// the redacted parameters it gets.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct LoggingObserver;
+
+impl LoggingObserver {
+    fn source(context: &Context) -> String {
+        context
+            .peer_addr()
+            .map_or_else(|| "local client".to_string(), |addr| addr.to_string())
+    }
+}
+
+impl Observer for LoggingObserver {
+    fn on_request(&self, context: &Context, command: &str, parameter: Option<&json::Value>) {
+        match parameter {
+            Some(parameter) => debug!(
+                "CGMiner API: command '{}' with parameter {} from {}",
+                command,
+                parameter,
+                Self::source(context)
+            ),
+            None => debug!(
+                "CGMiner API: command '{}' from {}",
+                command,
+                Self::source(context)
+            ),
+        }
+    }
+
+    fn on_response(
+        &self,
+        context: &Context,
+        command: &str,
+        status: &ResponseStatus,
+        duration: Duration,
+    ) {
+        debug!(
+            "CGMiner API: command '{}' from {} finished in {} ms with {:?}({}): {}",
+            command,
+            Self::source(context),
+            duration.as_millis(),
+            status.status,
+            status.code,
+            status.msg
+        );
+    }
+}
+
+/// Invokes the `notify` closure on all `observers`. A panic of an observer is logged and does
+/// not affect the other observers nor the command handling.
+pub(crate) fn notify_all<F>(observers: &[SharedObserver], notify: F)
+where
+    F: Fn(&dyn Observer),
+{
+    for observer in observers {
+        if panic::catch_unwind(AssertUnwindSafe(|| notify(observer.as_ref()))).is_err() {
+            error!("CGMiner API: observer panicked");
+        }
+    }
+}
@@ -0,0 +1,829 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! JSON value keeping fields of objects in the order they have been serialized
+//!
+//! CGMiner clients (and the plain-text format) depend on the order of fields in the response.
+//! `json::Value` keeps it only with the `preserve_order` feature of `serde_json` which would
+//! change the behaviour of `serde_json` for every other crate of the workspace. Responses which
+//! have to be inspected or modified (e.g. in delta mode) are converted into this value instead.
+
+use crate::json;
+
+use serde::ser::{self, Impossible, Serialize};
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+use std::fmt;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(json::Number),
+    String(String),
+    Array(Vec<Value>),
+    Object(Map),
+}
+
+impl Value {
+    pub fn as_object(&self) -> Option<&Map> {
+        match self {
+            Value::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_mut(&mut self) -> Option<&mut Map> {
+        match self {
+            Value::Object(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, Value::Number(_))
+    }
+}
+
+/// Encodes the value as compact JSON
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoded = json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&encoded)
+    }
+}
+
+/// Fields of an object in the order of insertion. Objects of API responses have just a few dozen
+/// fields so they are looked up linearly.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct Map(Vec<(String, Value)>);
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Replaces the value of an existing field in place or appends a new field
+    pub fn insert(&mut self, key: String, value: Value) {
+        match self.0.iter_mut().find(|(name, _)| *name == key) {
+            Some((_, previous)) => *previous = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    /// Removes all fields for which `keep` returns `false` without changing order of the others
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&str, &Value) -> bool,
+    {
+        self.0.retain(|(key, value)| keep(key, value))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.0.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.0.iter().map(|(key, _)| key)
+    }
+}
+
+impl std::iter::FromIterator<(String, Value)> for Map {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (String, Value)>,
+    {
+        let mut map = Map::new();
+        iter.into_iter()
+            .for_each(|(key, value)| map.insert(key, value));
+        map
+    }
+}
+
+impl IntoIterator for Map {
+    type Item = (String, Value);
+    type IntoIter = std::vec::IntoIter<(String, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Fields of the converted objects are sorted unless `serde_json` keeps their order
+impl From<json::Value> for Value {
+    fn from(value: json::Value) -> Self {
+        match value {
+            json::Value::Null => Value::Null,
+            json::Value::Bool(value) => Value::Bool(value),
+            json::Value::Number(value) => Value::Number(value),
+            json::Value::String(value) => Value::String(value),
+            json::Value::Array(list) => Value::Array(list.into_iter().map(Into::into).collect()),
+            json::Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<Value> for json::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => json::Value::Null,
+            Value::Bool(value) => json::Value::Bool(value),
+            Value::Number(value) => json::Value::Number(value),
+            Value::String(value) => json::Value::String(value),
+            Value::Array(list) => json::Value::Array(list.into_iter().map(Into::into).collect()),
+            Value::Object(map) => json::Value::Object(
+                map.0
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Bool(value) => serializer.serialize_bool(*value),
+            Value::Number(value) => value.serialize(serializer),
+            Value::String(value) => serializer.serialize_str(value),
+            Value::Array(list) => list.serialize(serializer),
+            Value::Object(map) => map.serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for Map {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> de::Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::Number(value.into()))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::Number(value.into()))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Value, E> {
+        Ok(json::Number::from_f64(value).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Value, E> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Value, E> {
+        Ok(Value::String(value))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut list = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            list.push(value);
+        }
+        Ok(Value::Array(list))
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut map = Map::new();
+        while let Some((key, value)) = access.next_entry()? {
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+/// Serializes `value` keeping the order of fields of all objects
+pub fn to_value<T>(value: &T) -> json::Result<Value>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(ValueSerializer)
+}
+
+/// Name of the struct and its only field which `json::value::RawValue` is serialized as. It is
+/// not exported by `serde_json`.
+const RAW_VALUE_TOKEN: &str = "$serde_json::private::RawValue";
+
+/// Builds `Value` from any serializable data the same way as `json::to_value` does
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = json::Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeVariant<SerializeList>;
+    type SerializeMap = SerializeObject;
+    type SerializeStruct = SerializeObject;
+    type SerializeStructVariant = SerializeVariant<SerializeObject>;
+
+    fn serialize_bool(self, value: bool) -> json::Result<Value> {
+        Ok(Value::Bool(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> json::Result<Value> {
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_i16(self, value: i16) -> json::Result<Value> {
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_i32(self, value: i32) -> json::Result<Value> {
+        self.serialize_i64(value.into())
+    }
+
+    fn serialize_i64(self, value: i64) -> json::Result<Value> {
+        Ok(Value::Number(value.into()))
+    }
+
+    fn serialize_u8(self, value: u8) -> json::Result<Value> {
+        self.serialize_u64(value.into())
+    }
+
+    fn serialize_u16(self, value: u16) -> json::Result<Value> {
+        self.serialize_u64(value.into())
+    }
+
+    fn serialize_u32(self, value: u32) -> json::Result<Value> {
+        self.serialize_u64(value.into())
+    }
+
+    fn serialize_u64(self, value: u64) -> json::Result<Value> {
+        Ok(Value::Number(value.into()))
+    }
+
+    fn serialize_f32(self, value: f32) -> json::Result<Value> {
+        self.serialize_f64(value.into())
+    }
+
+    /// Non-finite numbers are serialized as `null` as in `json::to_value`
+    fn serialize_f64(self, value: f64) -> json::Result<Value> {
+        Ok(json::Number::from_f64(value).map_or(Value::Null, Value::Number))
+    }
+
+    fn serialize_char(self, value: char) -> json::Result<Value> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn serialize_str(self, value: &str) -> json::Result<Value> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> json::Result<Value> {
+        Ok(Value::Array(
+            value
+                .iter()
+                .map(|&byte| Value::Number(byte.into()))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> json::Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> json::Result<Value>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> json::Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> json::Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> json::Result<Value> {
+        Ok(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> json::Result<Value>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> json::Result<Value>
+    where
+        T: Serialize + ?Sized,
+    {
+        let mut map = Map::new();
+        map.insert(variant.to_string(), to_value(value)?);
+        Ok(Value::Object(map))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> json::Result<SerializeList> {
+        Ok(SerializeList(Vec::with_capacity(len.unwrap_or_default())))
+    }
+
+    fn serialize_tuple(self, len: usize) -> json::Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> json::Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> json::Result<Self::SerializeTupleVariant> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> json::Result<SerializeObject> {
+        Ok(SerializeObject {
+            map: Map::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> json::Result<SerializeObject> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> json::Result<Self::SerializeStructVariant> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SerializeList(Vec<Value>);
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Value;
+    type Error = json::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> json::Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.0.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> json::Result<Value> {
+        Ok(Value::Array(self.0))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Value;
+    type Error = json::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> json::Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> json::Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Value;
+    type Error = json::Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> json::Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> json::Result<Value> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct SerializeObject {
+    map: Map,
+    /// Key of the entry whose value is going to be serialized
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for SerializeObject {
+    type Ok = Value;
+    type Error = json::Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> json::Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> json::Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self
+            .key
+            .take()
+            .expect("BUG: value serialized without a key");
+        self.map.insert(key, to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> json::Result<Value> {
+        Ok(Value::Object(self.map))
+    }
+}
+
+impl ser::SerializeStruct for SerializeObject {
+    type Ok = Value;
+    type Error = json::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> json::Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.map.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+
+    /// Already encoded JSON (see `json::value::RawValue`) is decoded the same way as
+    /// `json::to_value` does
+    fn end(self) -> json::Result<Value> {
+        match self.map.get(RAW_VALUE_TOKEN) {
+            Some(Value::String(encoded)) if self.map.len() == 1 => json::from_str(encoded),
+            _ => ser::SerializeMap::end(self),
+        }
+    }
+}
+
+/// Enum variant with data serialized as an object with a single field named after the variant
+struct SerializeVariant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl<S> SerializeVariant<S> {
+    fn wrap(variant: &'static str, value: Value) -> Value {
+        let mut map = Map::new();
+        map.insert(variant.to_string(), value);
+        Value::Object(map)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeList> {
+    type Ok = Value;
+    type Error = json::Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> json::Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> json::Result<Value> {
+        Ok(Self::wrap(
+            self.variant,
+            ser::SerializeSeq::end(self.inner)?,
+        ))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeObject> {
+    type Ok = Value;
+    type Error = json::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> json::Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> json::Result<Value> {
+        Ok(Self::wrap(
+            self.variant,
+            ser::SerializeMap::end(self.inner)?,
+        ))
+    }
+}
+
+/// Serializes keys of maps. Numbers and booleans are converted into strings as JSON allows
+/// only string keys.
+struct KeySerializer;
+
+fn key_must_be_string() -> json::Error {
+    ser::Error::custom("key must be a string")
+}
+
+impl Serializer for KeySerializer {
+    type Ok = String;
+    type Error = json::Error;
+
+    type SerializeSeq = Impossible<String, json::Error>;
+    type SerializeTuple = Impossible<String, json::Error>;
+    type SerializeTupleStruct = Impossible<String, json::Error>;
+    type SerializeTupleVariant = Impossible<String, json::Error>;
+    type SerializeMap = Impossible<String, json::Error>;
+    type SerializeStruct = Impossible<String, json::Error>;
+    type SerializeStructVariant = Impossible<String, json::Error>;
+
+    fn serialize_bool(self, value: bool) -> json::Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i8(self, value: i8) -> json::Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i16(self, value: i16) -> json::Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i32(self, value: i32) -> json::Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i64(self, value: i64) -> json::Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u8(self, value: u8) -> json::Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u16(self, value: u16) -> json::Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u32(self, value: u32) -> json::Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_u64(self, value: u64) -> json::Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_f32(self, _value: f32) -> json::Result<String> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_f64(self, _value: f64) -> json::Result<String> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_char(self, value: char) -> json::Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_str(self, value: &str) -> json::Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> json::Result<String> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_none(self) -> json::Result<String> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> json::Result<String>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_unit(self) -> json::Result<String> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> json::Result<String> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> json::Result<String> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> json::Result<String>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> json::Result<String>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> json::Result<Self::SerializeSeq> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> json::Result<Self::SerializeTuple> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> json::Result<Self::SerializeTupleStruct> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> json::Result<Self::SerializeTupleVariant> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> json::Result<Self::SerializeMap> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> json::Result<Self::SerializeStruct> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> json::Result<Self::SerializeStructVariant> {
+        Err(key_must_be_string())
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Typed extraction of command parameters
+//!
+//! Clients send the same parameter either as a JSON value or as a string (the plain-text format
+//! knows only strings) so e.g. an integer is accepted as `1` as well as `"1"`. The same type
+//! is meant to be used by the parameter check (`check::<T>()`) and by the handler of a command
+//! (`param::<T>()`) so that both report the same `ErrorCode::InvalidParameter`.
+
+use crate::command;
+use crate::response;
+
+use serde_json as json;
+
+/// Value which can be extracted from a command parameter
+pub trait FromParameter: Sized {
+    /// Description of the expected value used in error responses
+    fn expected() -> String;
+
+    /// Converts the parameter or returns `None` when it has unexpected type or format
+    fn from_value(value: &json::Value) -> Option<Self>;
+
+    /// Value used when the parameter is missing. Only optional values accept it.
+    fn from_missing() -> Option<Self> {
+        None
+    }
+}
+
+/// Parameter which cannot be converted to the expected type
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParameterError {
+    pub expected: String,
+    /// Received parameter or `None` when it is missing
+    pub got: Option<String>,
+}
+
+impl From<ParameterError> for response::ErrorCode {
+    fn from(error: ParameterError) -> Self {
+        response::ErrorCode::InvalidParameter(error.expected, error.got.unwrap_or_default())
+    }
+}
+
+impl From<ParameterError> for response::Error {
+    fn from(error: ParameterError) -> Self {
+        response::ErrorCode::from(error).into()
+    }
+}
+
+/// Textual representation of a parameter for error responses
+pub(crate) fn describe(value: &json::Value) -> String {
+    match value {
+        json::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Converts a string item of a composite parameter
+fn from_str<T: FromParameter>(item: &str) -> Option<T> {
+    T::from_value(&json::Value::String(item.trim().to_string()))
+}
+
+/// Extracts a value of type `T` from optional `parameter`
+pub fn extract<T: FromParameter>(parameter: Option<&json::Value>) -> Result<T, ParameterError> {
+    match parameter {
+        Some(value) => T::from_value(value),
+        None => T::from_missing(),
+    }
+    .ok_or_else(|| ParameterError {
+        expected: T::expected(),
+        got: parameter.map(describe),
+    })
+}
+
+/// Returns parser of a parameter with type `T` suitable for command handlers. Errors are
+/// reported as `ErrorCode::InvalidParameter`.
+pub fn param<T>() -> impl Fn(Option<&json::Value>) -> command::Result<T> + Copy + Send + Sync
+where
+    T: FromParameter + 'static,
+{
+    |parameter| extract(parameter).map_err(Into::into)
+}
+
+/// Returns parameter check of a command expecting parameter with type `T`
+pub fn check<T>() -> command::ParameterCheckHandler
+where
+    T: FromParameter + 'static,
+{
+    Box::new(|_command, parameter| extract::<T>(*parameter).map(|_| ()).map_err(Into::into))
+}
+
+macro_rules! integer {
+    ($($type:ty: $expected:literal),+) => {$(
+        impl FromParameter for $type {
+            fn expected() -> String {
+                $expected.to_string()
+            }
+
+            fn from_value(value: &json::Value) -> Option<Self> {
+                use std::convert::TryFrom;
+                match value {
+                    json::Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+                        (Some(number), _) => Self::try_from(number).ok(),
+                        (_, Some(number)) => Self::try_from(number).ok(),
+                        // Floating point numbers are not truncated
+                        _ => None,
+                    },
+                    json::Value::String(value) => value.trim().parse().ok(),
+                    _ => None,
+                }
+            }
+        }
+    )+};
+}
+
+integer!(
+    i32: "integer",
+    i64: "integer",
+    u32: "non-negative integer",
+    u64: "non-negative integer",
+    usize: "non-negative integer"
+);
+
+impl FromParameter for f64 {
+    fn expected() -> String {
+        "number".to_string()
+    }
+
+    fn from_value(value: &json::Value) -> Option<Self> {
+        match value {
+            json::Value::Number(number) => number.as_f64(),
+            json::Value::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+        .filter(|number: &f64| number.is_finite())
+    }
+}
+
+impl FromParameter for bool {
+    fn expected() -> String {
+        "boolean".to_string()
+    }
+
+    fn from_value(value: &json::Value) -> Option<Self> {
+        match value {
+            json::Value::Bool(value) => Some(*value),
+            json::Value::String(value) => match value.trim().to_lowercase().as_str() {
+                "true" => Some(true),
+                "false" => Some(false),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl FromParameter for String {
+    fn expected() -> String {
+        "string".to_string()
+    }
+
+    fn from_value(value: &json::Value) -> Option<Self> {
+        match value {
+            json::Value::String(value) => Some(value.clone()),
+            json::Value::Number(number) => Some(number.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromParameter> FromParameter for Option<T> {
+    fn expected() -> String {
+        format!("optional {}", T::expected())
+    }
+
+    fn from_value(value: &json::Value) -> Option<Self> {
+        T::from_value(value).map(Some)
+    }
+
+    fn from_missing() -> Option<Self> {
+        Some(None)
+    }
+}
+
+/// Non-empty list of values either separated by `PARAMETER_DELIMITER` in a string or passed as
+/// a JSON array. A single value is accepted as a list with one item.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CommaList<T>(pub Vec<T>);
+
+impl<T> CommaList<T> {
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T: FromParameter> FromParameter for CommaList<T> {
+    fn expected() -> String {
+        format!("comma separated list ({})", T::expected())
+    }
+
+    fn from_value(value: &json::Value) -> Option<Self> {
+        let list = match value {
+            json::Value::String(list) => list
+                .split(crate::PARAMETER_DELIMITER)
+                .map(from_str)
+                .collect::<Option<Vec<_>>>()?,
+            json::Value::Array(list) => {
+                list.iter().map(T::from_value).collect::<Option<Vec<_>>>()?
+            }
+            value => vec![T::from_value(value)?],
+        };
+        if list.is_empty() {
+            return None;
+        }
+        Some(Self(list))
+    }
+}
+
+/// Pair of values either separated by the first `PARAMETER_DELIMITER` in a string (the second
+/// value may thus contain the delimiter) or passed as a JSON array. The second value can be
+/// left out when its type is optional.
+impl<A: FromParameter, B: FromParameter> FromParameter for (A, B) {
+    fn expected() -> String {
+        format!("'{},{}'", A::expected(), B::expected())
+    }
+
+    fn from_value(value: &json::Value) -> Option<Self> {
+        match value {
+            json::Value::String(value) => {
+                let mut args = value.splitn(2, crate::PARAMETER_DELIMITER);
+                let first = from_str(args.next()?)?;
+                let second = match args.next() {
+                    Some(second) => from_str(second)?,
+                    None => B::from_missing()?,
+                };
+                Some((first, second))
+            }
+            json::Value::Array(args) => match args.as_slice() {
+                [first] => Some((A::from_value(first)?, B::from_missing()?)),
+                [first, second] => Some((A::from_value(first)?, B::from_value(second)?)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
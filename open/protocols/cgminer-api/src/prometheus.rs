@@ -0,0 +1,196 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Export of miner data in the Prometheus text exposition format
+//!
+//! The metrics are not listed by hand. Responses of the `summary`, `devs`, `pools` and `stats`
+//! commands are serialized the same way as for the API and every numeric field becomes a gauge.
+//! Names of the metrics are stable as long as the API fields are:
+//!
+//! * the name is `miner_<section>_<field>` where the section is `summary`, `device`, `pool`
+//!   or `stats`
+//! * the field name is converted to lowercase and every run of characters other than ASCII
+//!   letters and digits is replaced with `_` (e.g. `MHS 5s` becomes `mhs_5s`)
+//! * the index field of a section (e.g. `ASC=0`) is not exported as a metric but as a label
+//!   (`device`, `pool` or `stats`)
+//!
+//! E.g. the 5 second hash rate of the first device is `miner_device_mhs_5s{device="0"}`.
+//! Result of collecting each section is reported by `miner_scrape_success{section="..."}`.
+
+use crate::command::{self, Handler};
+use crate::ordered::Value;
+use crate::response::Dispatch;
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+/// Exported sections: command name, metric section and name of the label with the index field
+const SECTIONS: &[(&str, &str, Option<&str>)] = &[
+    ("summary", "summary", None),
+    ("devs", "device", Some("device")),
+    ("pools", "pool", Some("pool")),
+    ("stats", "stats", Some("stats")),
+];
+
+/// Converts an API field name into a valid part of a metric name
+fn metric_name(section: &str, field: &str) -> String {
+    let mut name = format!("miner_{}_", section);
+    let mut delimited = true;
+    for c in field.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_lowercase());
+            delimited = false;
+        } else if !delimited {
+            name.push('_');
+            delimited = true;
+        }
+    }
+    if delimited {
+        name.pop();
+    }
+    name
+}
+
+/// Escapes a help text
+fn escape_help(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+struct Family {
+    name: String,
+    help: String,
+    samples: Vec<(String, String)>,
+}
+
+/// Metric families in the order of their first appearance. All samples of a family have to be
+/// rendered together.
+#[derive(Default)]
+struct Registry {
+    families: Vec<Family>,
+    index: HashMap<String, usize>,
+}
+
+impl Registry {
+    fn add(&mut self, name: String, help: String, labels: String, value: String) {
+        let families = &mut self.families;
+        let idx = *self.index.entry(name.clone()).or_insert_with(|| {
+            families.push(Family {
+                name,
+                help,
+                samples: vec![],
+            });
+            families.len() - 1
+        });
+        self.families[idx].samples.push((labels, value));
+    }
+
+    /// Adds all numeric fields of the response `body`
+    fn add_body(&mut self, section: &str, label: Option<&str>, body: (&str, Value)) {
+        let (body_name, list) = body;
+        let list = match list {
+            Value::Array(list) => list,
+            value => vec![value],
+        };
+        for item in list {
+            let fields = match item {
+                Value::Object(fields) => fields,
+                _ => continue,
+            };
+            let mut fields = fields.into_iter().peekable();
+            // The index field is always the first one (e.g. `POOL`)
+            let labels = match (label, fields.peek()) {
+                (Some(label), Some((_, Value::Number(idx)))) => {
+                    let labels = format!("{{{}=\"{}\"}}", label, idx);
+                    fields.next();
+                    labels
+                }
+                _ => String::new(),
+            };
+            for (field, value) in fields {
+                if let Value::Number(value) = value {
+                    self.add(
+                        metric_name(section, &field),
+                        escape_help(&format!("{} {}", body_name, field)),
+                        labels.clone(),
+                        value.to_string(),
+                    );
+                }
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut text = String::new();
+        for family in &self.families {
+            writeln!(text, "# HELP {} {}", family.name, family.help).expect("BUG: write failed");
+            writeln!(text, "# TYPE {} gauge", family.name).expect("BUG: write failed");
+            for (labels, value) in &family.samples {
+                writeln!(text, "{}{} {}", family.name, labels, value).expect("BUG: write failed");
+            }
+        }
+        text
+    }
+}
+
+async fn collect<H>(handler: &H, command: &str) -> command::Result<Dispatch>
+where
+    H: Handler + ?Sized,
+{
+    Ok(match command {
+        "summary" => handler.handle_summary().await?.into(),
+        "devs" => handler.handle_devs(None).await?.into(),
+        "pools" => handler.handle_pools().await?.into(),
+        "stats" => handler.handle_stats(None, false).await?.into(),
+        _ => panic!("BUG: unexpected section '{}'", command),
+    })
+}
+
+/// Collects data from the `handler` and renders them in the Prometheus text format. Sections
+/// which cannot be collected are skipped.
+pub async fn render<H>(handler: &H) -> String
+where
+    H: Handler + ?Sized,
+{
+    let mut registry = Registry::default();
+    let mut results = Vec::with_capacity(SECTIONS.len());
+    for &(command, section, label) in SECTIONS {
+        let success = match collect(handler, command).await {
+            Ok(dispatch) => {
+                if let Some(body) = dispatch.into_body() {
+                    registry.add_body(section, label, body);
+                }
+                true
+            }
+            Err(_) => false,
+        };
+        results.push((section, success));
+    }
+    for (section, success) in results {
+        registry.add(
+            "miner_scrape_success".to_string(),
+            "Whether the section was collected successfully".to_string(),
+            format!("{{section=\"{}\"}}", section),
+            (success as u8).to_string(),
+        );
+    }
+    registry.render()
+}
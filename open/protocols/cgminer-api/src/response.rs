@@ -21,13 +21,31 @@
 // contact us at opensource@braiins.com.
 
 //! Defines all the CGMiner API responses
+//!
+//! Some clients parse the responses positionally so the order of fields is a part of the API.
+//! The fields of each section are serialized in the order of the fields of their structures
+//! (the `preserve_order` feature of `serde_json` keeps it in the serialized bodies), every
+//! response starts with `STATUS` followed by its section and `id`, and responses of a batched
+//! request keep the order of the commands in the request. The order is locked by golden files
+//! in `test/golden`.
 
 pub mod ext;
 
+use crate::access::Privilege;
+use crate::ordered;
+use crate::schema;
 use crate::support;
 
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json as json;
+use serde_json::value::RawValue;
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub type Time = u32;
 pub type Elapsed = u64;
@@ -39,11 +57,36 @@ pub type GigaHashes = MegaHashes;
 pub type TotalMegaHashes = f64;
 pub type Utility = f64;
 pub type Temperature = f64;
+pub type MegaHertz = f64;
+
+/// Additional fields of a section which are serialized after its standard fields in the order
+/// of their keys. A field with the same key as any standard field of the section is dropped so
+/// that a vendor extension cannot change the meaning of a standard field.
+pub type Extras = BTreeMap<String, json::Value>;
+
+/// Serializes the `extras` with the exception of fields colliding with `standard` fields
+fn serialize_extras<S>(
+    extras: &Extras,
+    standard: &[schema::Section],
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let is_standard = |key: &str| {
+        standard
+            .iter()
+            .flat_map(|section| section.fields)
+            .any(|field| field.name == key)
+    };
+    serializer.collect_map(extras.iter().filter(|(key, _)| !is_standard(key)))
+}
 
 #[allow(dead_code)]
-/// CGMiner API Status indicator.
-/// (warning and info levels not currently used.)
-#[derive(Serialize, Eq, PartialEq, Copy, Clone, Debug)]
+/// CGMiner API Status indicator. Besides success and error there are warnings (the command
+/// has been handled but something deserves attention) and informational results (e.g. nothing
+/// has been done because the miner already is in the requested state).
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug)]
 pub enum Status {
     W,
     I,
@@ -52,12 +95,23 @@ pub enum Status {
 }
 
 #[allow(dead_code)]
-#[derive(Serialize, Eq, PartialEq, Copy, Clone, Debug)]
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, Debug, Default)]
 pub enum Bool {
+    #[default]
     N,
     Y,
 }
 
+impl From<bool> for Bool {
+    fn from(value: bool) -> Self {
+        if value {
+            Bool::Y
+        } else {
+            Bool::N
+        }
+    }
+}
+
 impl<T> From<Option<T>> for Bool {
     fn from(value: Option<T>) -> Self {
         match value {
@@ -68,7 +122,7 @@ impl<T> From<Option<T>> for Bool {
 }
 
 #[allow(dead_code)]
-#[derive(Serialize, Eq, PartialEq, Clone, Debug)]
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub enum PoolStatus {
     Disabled,
@@ -79,7 +133,7 @@ pub enum PoolStatus {
 }
 
 #[allow(dead_code)]
-#[derive(Serialize, Eq, PartialEq, Clone, Debug)]
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
 #[serde(rename_all = "PascalCase")]
 pub enum AscStatus {
     Alive,
@@ -116,23 +170,58 @@ pub enum StatusCode {
     EnablePool = 47,
     DisablePool = 48,
     AddPool = 55,
+    Notify = 60,
     RemovePool = 68,
     DevDetails = 69,
     Stats = 70,
     Check = 72,
+    PoolPriority = 73,
+    FailoverOnly = 77,
     Coin = 78,
+    Debug = 79,
+    SetConfig = 82,
+    ZeroSummary = 96,
+    ZeroNoSummary = 97,
     AscCount = 104,
     Asc = 106,
+    AscEnable = 110,
+    AscDisable = 111,
+    AscSet = 118,
     Lcd = 125,
 
     // extended command status codes
     TempCtrl = 200,
     Temps = 201,
     Fans = 202,
+    ShareLog = 203,
+    PoolStats = 204,
+    FanCtrl = 205,
+    Pause = 206,
+    Resume = 207,
+    Locate = 210,
+    TunerStatus = 211,
+    Help = 212,
+    ApiStats = 213,
+    Events = 214,
+    Connections = 215,
+    ReloadConfig = 216,
+    Schema = 218,
+    Auth = 220,
+    LastCommands = 221,
 
     // info status codes
     PoolAlreadyEnabled = 49,
     PoolAlreadyDisabled = 50,
+    AscAlreadyEnabled = 108,
+    AscAlreadyDisabled = 109,
+
+    // extended info status codes
+    AlreadyPaused = 208,
+    NotPaused = 209,
+    ReloadConfigPartial = 217,
+
+    // extended warning status codes
+    ResumedWithoutPool = 219,
 
     // error status codes
     InvalidCommand = 14,
@@ -144,8 +233,48 @@ pub enum StatusCode {
     AccessDeniedCmd = 45,
     MissingAddPoolDetails = 52,
     InvalidAddPoolDetails = 53,
+    RemoveLastPool = 66,
+    RemoveActivePool = 67,
     MissingCheckCmd = 71,
+    DuplicatePoolId = 74,
+    MissingBoolParameter = 75,
+    InvalidBoolParameter = 76,
+    UnknownConfig = 83,
+    InvalidConfigValue = 84,
+    MissingSetConfigParameter = 85,
+    MissingSetConfigValue = 86,
+    MissingZeroParameter = 94,
+    InvalidZeroParameter = 95,
     InvalidAscId = 107,
+    MissingAscOption = 115,
+    AscSetErr = 119,
+    DisableLastPool = 149,
+
+    // extended error status codes
+    ShareLogNotConfigured = 250,
+    InvalidShareLogParameter = 251,
+    InvalidPoolPriority = 252,
+    AscSwitchErr = 253,
+    InvalidAscOption = 254,
+    InvalidDebugFlag = 255,
+    InvalidFanSpeed = 256,
+    InvalidFanCtrlParameter = 257,
+    InvalidTempCtrlParameter = 258,
+    InvalidTargetTemp = 259,
+    InvalidHotTemp = 260,
+    InvalidLocateParameter = 261,
+    InvalidLocateDuration = 262,
+    LocateErr = 263,
+    CommandFailed = 264,
+    CommandTimeout = 265,
+    RateLimited = 266,
+    TooManyConnections = 267,
+    InvalidParameter = 268,
+    HardwareError = 269,
+    InternalError = 270,
+    PersistentConnectionRequired = 271,
+    InvalidToken = 272,
+    CommandNotSupported = 273,
 
     // special value which is added to the custom status codes
     CustomBase = 300,
@@ -165,27 +294,63 @@ impl From<StatusCode> for StatusCodeType {
     }
 }
 
+impl StatusCodeType {
+    /// Numeric code as reported to clients
+    pub fn value(&self) -> u32 {
+        match self {
+            StatusCodeType::Protocol(code) => *code as u32,
+            StatusCodeType::Custom(code) => StatusCode::CustomBase as u32 + *code,
+        }
+    }
+}
+
 impl Serialize for StatusCodeType {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let code = match self {
-            StatusCodeType::Protocol(code) => *code as u32,
-            StatusCodeType::Custom(code) => StatusCode::CustomBase as u32 + *code,
-        };
-        serializer.serialize_u32(code)
+        serializer.serialize_u32(self.value())
     }
 }
 
-pub enum InfoCode {
-    PoolAlreadyEnabled(i32, String),
-    PoolAlreadyDisabled(i32, String),
+/// Informational results of commands which have nothing to do because the miner already is in
+/// the requested state. They are reported by the outcomes of the commands (e.g.
+/// `EnablePool::AlreadyEnabled`) and not by handler errors.
+enum InfoCode {
+    PoolAlreadyEnabled(usize, String),
+    PoolAlreadyDisabled(usize, String),
+    AscAlreadyEnabled(i32),
+    AscAlreadyDisabled(i32),
+    AlreadyPaused,
+    NotPaused,
 }
 
 impl From<InfoCode> for Dispatch {
     fn from(code: InfoCode) -> Self {
-        Error::from(code).into()
+        let (code, msg) = match code {
+            InfoCode::PoolAlreadyEnabled(idx, url) => (
+                StatusCode::PoolAlreadyEnabled,
+                format!("Pool {}:'{}' already enabled", idx, url),
+            ),
+            InfoCode::PoolAlreadyDisabled(idx, url) => (
+                StatusCode::PoolAlreadyDisabled,
+                format!("Pool {}:'{}' already disabled", idx, url),
+            ),
+            InfoCode::AscAlreadyEnabled(idx) => (
+                StatusCode::AscAlreadyEnabled,
+                format!("ASC {} already enabled", idx),
+            ),
+            InfoCode::AscAlreadyDisabled(idx) => (
+                StatusCode::AscAlreadyDisabled,
+                format!("ASC {} already disabled", idx),
+            ),
+            InfoCode::AlreadyPaused => (
+                StatusCode::AlreadyPaused,
+                "Mining already paused".to_string(),
+            ),
+            InfoCode::NotPaused => (StatusCode::NotPaused, "Mining is not paused".to_string()),
+        };
+        Dispatch::from_info::<()>(code.into(), msg, None)
     }
 }
 
@@ -199,8 +364,46 @@ pub enum ErrorCode {
     AccessDeniedCmd(String),
     MissingAddPoolDetails,
     InvalidAddPoolDetails(String),
+    RemoveLastPool(i32, String),
+    RemoveActivePool(i32, String),
     MissingCheckCmd,
+    DuplicatePoolId(usize),
+    MissingBoolParameter,
+    InvalidBoolParameter,
+    UnknownConfig(String),
+    InvalidConfigValue(String, String),
+    MissingSetConfigParameter,
+    MissingSetConfigValue(String),
+    MissingZeroParameter,
+    InvalidZeroParameter(String),
     InvalidAscId(i32, i32),
+    MissingAscOption,
+    AscSetErr(i32, String),
+    DisableLastPool(i32, String),
+    ShareLogNotConfigured,
+    InvalidShareLogParameter(String),
+    InvalidPoolPriority(String),
+    AscSwitchErr(i32, String),
+    InvalidAscOption(i32, String, Vec<String>),
+    InvalidDebugFlag(String, Vec<&'static str>),
+    InvalidFanSpeed(i64),
+    InvalidFanCtrlParameter(String),
+    InvalidTempCtrlParameter(String),
+    InvalidTargetTemp(f32, f32),
+    InvalidHotTemp(f32, f32),
+    InvalidLocateParameter(String),
+    InvalidLocateDuration(i64),
+    LocateErr(String),
+    CommandFailed(String),
+    CommandTimeout(String, u64),
+    RateLimited,
+    TooManyConnections,
+    InvalidParameter(String, String),
+    HardwareError,
+    InternalError,
+    PersistentConnectionRequired(String),
+    InvalidToken,
+    CommandNotSupported(String),
 }
 
 impl From<ErrorCode> for Dispatch {
@@ -210,7 +413,6 @@ impl From<ErrorCode> for Dispatch {
 }
 
 pub struct Error {
-    status: Status,
     code: StatusCodeType,
     msg: String,
 }
@@ -227,34 +429,50 @@ impl Error {
         T: Into<u32>,
     {
         Self {
-            status: Status::E,
             code: StatusCodeType::Custom(code.into()),
             msg,
         }
     }
-}
-
-impl From<InfoCode> for Error {
-    fn from(code: InfoCode) -> Self {
-        let (code, msg) = match code {
-            InfoCode::PoolAlreadyEnabled(idx, url) => (
-                StatusCode::PoolAlreadyEnabled,
-                format!("Pool {}:'{}' already enabled", idx, url),
-            ),
-            InfoCode::PoolAlreadyDisabled(idx, url) => (
-                StatusCode::PoolAlreadyDisabled,
-                format!("Pool {}:'{}' already disabled", idx, url),
-            ),
-        };
 
+    /// Appends detail of the failure to the message while the status code is kept
+    pub fn with_context<C: fmt::Display>(self, context: C) -> Self {
         Self {
-            status: Status::I,
-            code: code.into(),
-            msg,
+            msg: format!("{} - {}", self.msg, context),
+            ..self
         }
     }
 }
 
+impl ErrorCode {
+    /// Builds an error whose standard message is extended with detail of the failure,
+    /// e.g. `ErrorCode::HardwareError.with_context("chain 2 SPI timeout")`
+    pub fn with_context<C: fmt::Display>(self, context: C) -> Error {
+        Error::from(self).with_context(context)
+    }
+}
+
+/// Unexpected failures inside of a handler are reported as an internal error so that `?` can be
+/// used for common foreign errors in handler implementations
+macro_rules! internal_error_from {
+    ($($error:ty),*) => {
+        $(
+            impl From<$error> for Error {
+                fn from(error: $error) -> Self {
+                    ErrorCode::InternalError.with_context(error)
+                }
+            }
+        )*
+    };
+}
+
+internal_error_from!(
+    anyhow::Error,
+    io::Error,
+    std::num::ParseIntError,
+    std::num::ParseFloatError,
+    json::Error
+);
+
 impl From<ErrorCode> for Error {
     fn from(code: ErrorCode) -> Self {
         let (code, msg) = match code {
@@ -275,7 +493,7 @@ impl From<ErrorCode> for Error {
                 "Missing pool id parameter".to_string(),
             ),
             ErrorCode::InvalidPoolId(idx_requested, idx_last) => (
-                StatusCode::InvalidAscId,
+                StatusCode::InvalidPoolId,
                 format!(
                     "Invalid pool id {} - range is 0 - {}",
                     idx_requested, idx_last
@@ -293,9 +511,53 @@ impl From<ErrorCode> for Error {
                 StatusCode::InvalidAddPoolDetails,
                 format!("Invalid addpool details '{}'", parameter),
             ),
+            ErrorCode::RemoveLastPool(idx, url) => (
+                StatusCode::RemoveLastPool,
+                format!("Cannot remove last pool {}:'{}'", idx, url),
+            ),
+            ErrorCode::RemoveActivePool(idx, url) => (
+                StatusCode::RemoveActivePool,
+                format!("Cannot remove active pool {}:'{}'", idx, url),
+            ),
             ErrorCode::MissingCheckCmd => {
                 (StatusCode::MissingCheckCmd, "Missing check cmd".to_string())
             }
+            ErrorCode::DuplicatePoolId(idx) => (
+                StatusCode::DuplicatePoolId,
+                format!("Duplicate pool specified {}", idx),
+            ),
+            ErrorCode::MissingBoolParameter => (
+                StatusCode::MissingBoolParameter,
+                "Missing parameter: true/false".to_string(),
+            ),
+            ErrorCode::InvalidBoolParameter => (
+                StatusCode::InvalidBoolParameter,
+                "Invalid parameter should be true or false".to_string(),
+            ),
+            ErrorCode::UnknownConfig(name) => (
+                StatusCode::UnknownConfig,
+                format!("Unknown config '{}'", name),
+            ),
+            ErrorCode::InvalidConfigValue(name, value) => (
+                StatusCode::InvalidConfigValue,
+                format!("Invalid value '{}' for '{}'", value, name),
+            ),
+            ErrorCode::MissingSetConfigParameter => (
+                StatusCode::MissingSetConfigParameter,
+                "Missing config parameters 'name,N'".to_string(),
+            ),
+            ErrorCode::MissingSetConfigValue(name) => (
+                StatusCode::MissingSetConfigValue,
+                format!("Missing config value N for '{},N'", name),
+            ),
+            ErrorCode::MissingZeroParameter => (
+                StatusCode::MissingZeroParameter,
+                "Missing zero parameters".to_string(),
+            ),
+            ErrorCode::InvalidZeroParameter(parameter) => (
+                StatusCode::InvalidZeroParameter,
+                format!("Invalid zero parameter '{}'", parameter),
+            ),
             ErrorCode::InvalidAscId(idx_requested, idx_last) => (
                 StatusCode::InvalidAscId,
                 format!(
@@ -303,10 +565,129 @@ impl From<ErrorCode> for Error {
                     idx_requested, idx_last
                 ),
             ),
+            ErrorCode::MissingAscOption => (
+                StatusCode::MissingAscOption,
+                "Missing option after device id".to_string(),
+            ),
+            ErrorCode::AscSetErr(idx, msg) => (
+                StatusCode::AscSetErr,
+                format!("ASC {} set failed: {}", idx, msg),
+            ),
+            ErrorCode::DisableLastPool(idx, url) => (
+                StatusCode::DisableLastPool,
+                format!("Cannot disable last active pool {}:'{}'", idx, url),
+            ),
+            ErrorCode::ShareLogNotConfigured => (
+                StatusCode::ShareLogNotConfigured,
+                "Share log is not configured".to_string(),
+            ),
+            ErrorCode::InvalidShareLogParameter(parameter) => (
+                StatusCode::InvalidShareLogParameter,
+                format!("Invalid sharelog parameter '{}'", parameter),
+            ),
+            ErrorCode::InvalidPoolPriority(parameter) => (
+                StatusCode::InvalidPoolPriority,
+                format!("Invalid poolpriority parameter '{}'", parameter),
+            ),
+            ErrorCode::AscSwitchErr(idx, msg) => (
+                StatusCode::AscSwitchErr,
+                format!("ASC {} state change failed: {}", idx, msg),
+            ),
+            ErrorCode::InvalidAscOption(idx, option, supported) => (
+                StatusCode::InvalidAscOption,
+                format!(
+                    "ASC {} invalid option '{}' - supported options are {}",
+                    idx,
+                    option,
+                    supported.join(", ")
+                ),
+            ),
+            ErrorCode::InvalidDebugFlag(flag, supported) => (
+                StatusCode::InvalidDebugFlag,
+                format!(
+                    "Invalid debug flag '{}' - valid flags are {}",
+                    flag,
+                    supported.join(", ")
+                ),
+            ),
+            ErrorCode::InvalidFanSpeed(speed) => (
+                StatusCode::InvalidFanSpeed,
+                format!("Invalid fan speed {}% - range is 0 - 100", speed),
+            ),
+            ErrorCode::InvalidFanCtrlParameter(parameter) => (
+                StatusCode::InvalidFanCtrlParameter,
+                format!(
+                    "Invalid fanctrl parameter '{}' - use 'auto' or speed in percent",
+                    parameter
+                ),
+            ),
+            ErrorCode::InvalidTempCtrlParameter(parameter) => (
+                StatusCode::InvalidTempCtrlParameter,
+                format!(
+                    "Invalid tempctrl parameter '{}' - use 'target,hot,dangerous'",
+                    parameter
+                ),
+            ),
+            ErrorCode::InvalidTargetTemp(target, limit) => (
+                StatusCode::InvalidTargetTemp,
+                format!("Target temperature {} must be below {}", target, limit),
+            ),
+            ErrorCode::InvalidHotTemp(hot, dangerous) => (
+                StatusCode::InvalidHotTemp,
+                format!(
+                    "Hot temperature {} must be below dangerous temperature {}",
+                    hot, dangerous
+                ),
+            ),
+            ErrorCode::InvalidLocateParameter(parameter) => (
+                StatusCode::InvalidLocateParameter,
+                format!(
+                    "Invalid locate parameter '{}' - use true, false or duration in seconds",
+                    parameter
+                ),
+            ),
+            ErrorCode::InvalidLocateDuration(duration) => (
+                StatusCode::InvalidLocateDuration,
+                format!("Invalid locate duration {}s - must be positive", duration),
+            ),
+            ErrorCode::LocateErr(reason) => (
+                StatusCode::LocateErr,
+                format!("Cannot change locate mode - {}", reason),
+            ),
+            ErrorCode::CommandFailed(name) => (
+                StatusCode::CommandFailed,
+                format!("Command '{}' failed unexpectedly", name),
+            ),
+            ErrorCode::CommandTimeout(name, timeout) => (
+                StatusCode::CommandTimeout,
+                format!("Command '{}' timed out after {}ms", name, timeout),
+            ),
+            ErrorCode::RateLimited => (
+                StatusCode::RateLimited,
+                "Too many requests - try again later".to_string(),
+            ),
+            ErrorCode::TooManyConnections => (
+                StatusCode::TooManyConnections,
+                "Too many connections - try again later".to_string(),
+            ),
+            ErrorCode::InvalidParameter(expected, got) => (
+                StatusCode::InvalidParameter,
+                format!("Invalid parameter '{}' - expected {}", got, expected),
+            ),
+            ErrorCode::HardwareError => (StatusCode::HardwareError, "Hardware error".to_string()),
+            ErrorCode::InternalError => (StatusCode::InternalError, "Internal error".to_string()),
+            ErrorCode::PersistentConnectionRequired(name) => (
+                StatusCode::PersistentConnectionRequired,
+                format!("Command '{}' requires persistent connection", name),
+            ),
+            ErrorCode::InvalidToken => (StatusCode::InvalidToken, "Invalid token".to_string()),
+            ErrorCode::CommandNotSupported(name) => (
+                StatusCode::CommandNotSupported,
+                format!("Command '{}' is not supported by the miner", name),
+            ),
         };
 
         Self {
-            status: Status::E,
             code: code.into(),
             msg,
         }
@@ -316,10 +697,13 @@ impl From<ErrorCode> for Error {
 impl From<Error> for Dispatch {
     fn from(error: Error) -> Self {
         Self {
-            status: error.status,
+            status: Status::E,
             code: error.code,
             msg: error.msg().clone(),
             body: None,
+            age: None,
+            collected: None,
+            delta: None,
         }
     }
 }
@@ -331,12 +715,32 @@ pub struct StatusInfo {
     #[serde(rename = "STATUS")]
     pub status: Status,
     pub when: Time,
+    /// Timestamp in milliseconds which is present only when the receiver is configured to
+    /// report it (see `command::Receiver::with_millisecond_timestamps`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub when_ms: Option<u64>,
     pub code: StatusCodeType,
     pub msg: String,
     pub description: String,
-}
-
-#[derive(Serialize, PartialEq, Clone, Debug)]
+    /// Number of seconds since the response has been cached. It is present only in cached
+    /// responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age: Option<f64>,
+    /// Time when the data of the response has been collected by the handler while `When` is the
+    /// time when the response has been generated. It is present only when it differs from `When`
+    /// (e.g. in cached responses).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collected: Option<Time>,
+    /// Sequence token of the response which is present only in delta mode (see `delta` module)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    /// Tells whether the body is a delta against the response identified by the token sent by
+    /// the client. It is present only in delta mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct Pool {
     #[serde(rename = "POOL")]
     pub idx: i32,
@@ -410,8 +814,8 @@ pub struct Pool {
     pub current_block_height: u32,
     #[serde(rename = "Current Block Version")]
     pub current_block_version: u32,
-    // Follows attribute extensions
-    #[serde(rename = "AsicBoost")]
+    // Follows attribute extensions which are missing in responses of other miners
+    #[serde(rename = "AsicBoost", default)]
     pub asic_boost: bool,
 }
 
@@ -434,7 +838,7 @@ impl From<Pools> for Dispatch {
     }
 }
 
-#[derive(Serialize, PartialEq, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct Asc {
     #[serde(rename = "ASC")]
     pub idx: i32,
@@ -488,10 +892,10 @@ pub struct Asc {
     pub device_rejected_ratio: Percent,
     #[serde(rename = "Device Elapsed")]
     pub device_elapsed: Elapsed,
-    // Follows attribute extensions
-    #[serde(rename = "Hardware Error MHS 15m")]
+    // Follows attribute extensions which are missing in responses of other miners
+    #[serde(rename = "Hardware Error MHS 15m", default)]
     pub hardware_error_mhs_15m: MegaHashes,
-    #[serde(rename = "Nominal MHS")]
+    #[serde(rename = "Nominal MHS", default)]
     pub nominal_mhs: MegaHashes,
 }
 
@@ -509,6 +913,35 @@ impl From<Asc> for Dispatch {
     }
 }
 
+/// Response of `asc` command with a single device or with all devices
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct Ascs {
+    pub list: Vec<Asc>,
+}
+
+impl From<Asc> for Ascs {
+    fn from(asc: Asc) -> Self {
+        Self { list: vec![asc] }
+    }
+}
+
+impl From<Ascs> for Dispatch {
+    fn from(ascs: Ascs) -> Self {
+        let msg = match ascs.list.as_slice() {
+            [asc] => format!("ASC{}", asc.idx),
+            list => format!("{} ASC(s)", list.len()),
+        };
+        Dispatch::from_success(
+            StatusCode::Asc.into(),
+            msg,
+            Some(Body {
+                name: "ASC",
+                list: ascs.list,
+            }),
+        )
+    }
+}
+
 #[derive(Serialize, PartialEq, Clone, Debug)]
 pub struct Devs {
     pub list: Vec<Asc>,
@@ -528,7 +961,7 @@ impl From<Devs> for Dispatch {
     }
 }
 
-#[derive(Serialize, PartialEq, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct Summary {
     #[serde(rename = "Elapsed")]
     pub elapsed: Elapsed,
@@ -588,9 +1021,24 @@ pub struct Summary {
     pub pool_stale_ratio: Percent,
     #[serde(rename = "Last getwork")]
     pub last_getwork: Time,
-    // Follows attribute extensions
-    #[serde(rename = "MHS 24h")]
+    // Follows attribute extensions which are missing in responses of other miners
+    #[serde(rename = "MHS 24h", default)]
     pub mhs_24h: MegaHashes,
+    /// Hash chains are parked because all pools are dead
+    #[serde(rename = "Pool Dead Park", default)]
+    pub pool_dead_park: Bool,
+    /// Mining has been paused by `pause` command
+    #[serde(rename = "Paused", default)]
+    pub paused: Bool,
+    /// Vendor specific fields (see `Extras`)
+    #[serde(flatten, serialize_with = "Summary::serialize_extras")]
+    pub extras: Extras,
+}
+
+impl Summary {
+    fn serialize_extras<S: Serializer>(extras: &Extras, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_extras(extras, schema::SUMMARY, serializer)
+    }
 }
 
 impl From<Summary> for Dispatch {
@@ -606,28 +1054,79 @@ impl From<Summary> for Dispatch {
     }
 }
 
+/// Content of the `version` response. The miner version is reported under the key `signature`
+/// followed by the API version and extra fields (e.g. `Type`) in the order of their insertion.
+/// Extra fields should not use the same key as the standard ones.
 #[derive(PartialEq, Clone, Debug)]
-pub(crate) struct Version {
+pub struct VersionInfo {
     pub signature: String,
     pub miner: String,
     pub api: String,
+    pub extra: Vec<(String, String)>,
+}
+
+impl VersionInfo {
+    /// Builds the standard response with the default API version
+    pub fn new(signature: String, miner: String) -> Self {
+        Self {
+            signature,
+            miner,
+            api: crate::API_VERSION.to_string(),
+            extra: vec![],
+        }
+    }
+
+    /// Overrides the reported compatibility version of the API
+    pub fn api(mut self, api: String) -> Self {
+        self.api = api;
+        self
+    }
+
+    /// Appends an extra field to the response
+    pub fn field(mut self, name: String, value: String) -> Self {
+        self.extra.push((name, value));
+        self
+    }
+
+    /// Appends `fields` (e.g. versions of components from `command::Handler::handle_version_extra`)
+    /// after the present extra fields keeping their order. A field whose key is already present
+    /// is dropped, i.e. the signature, `API` and the fields appended before always win and the
+    /// first of duplicate keys is kept.
+    pub fn merge_fields<I>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        for (name, value) in fields {
+            if !self.contains_key(&name) {
+                self.extra.push((name, value));
+            }
+        }
+        self
+    }
+
+    fn contains_key(&self, name: &str) -> bool {
+        name == self.signature || name == "API" || self.extra.iter().any(|(key, _)| key == name)
+    }
 }
 
-impl Serialize for Version {
+impl Serialize for VersionInfo {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
         use serde::ser::SerializeMap;
-        let mut map = serializer.serialize_map(Some(2))?;
+        let mut map = serializer.serialize_map(Some(2 + self.extra.len()))?;
         map.serialize_entry(&self.signature, &self.miner)?;
         map.serialize_entry("API", &self.api)?;
+        for (name, value) in &self.extra {
+            map.serialize_entry(name, value)?;
+        }
         map.end()
     }
 }
 
-impl From<Version> for Dispatch {
-    fn from(version: Version) -> Self {
+impl From<VersionInfo> for Dispatch {
+    fn from(version: VersionInfo) -> Self {
         Dispatch::from_success(
             StatusCode::Version.into(),
             format!("{} versions", crate::SIGNATURE_TAG),
@@ -667,6 +1166,8 @@ pub struct Config {
     pub pool_count: i32,
     #[serde(rename = "Strategy")]
     pub strategy: MultipoolStrategy,
+    #[serde(rename = "Failover-Only")]
+    pub failover_only: bool,
     #[serde(rename = "Log Interval")]
     pub log_interval: i32,
     #[serde(rename = "Device Code")]
@@ -675,6 +1176,18 @@ pub struct Config {
     pub os: String,
     #[serde(rename = "Hotplug")]
     pub hotplug: String,
+    #[serde(rename = "Hardware Profile")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hw_profile: Option<String>,
+    /// Vendor specific fields (e.g. `PSU Model`, see `Extras`)
+    #[serde(flatten, serialize_with = "Config::serialize_extras")]
+    pub extras: Extras,
+}
+
+impl Config {
+    fn serialize_extras<S: Serializer>(extras: &Extras, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_extras(extras, schema::CONFIG, serializer)
+    }
 }
 
 impl From<Config> for Dispatch {
@@ -690,33 +1203,57 @@ impl From<Config> for Dispatch {
     }
 }
 
-pub struct EnablePool {
-    pub idx: usize,
-    pub url: String,
+pub enum EnablePool {
+    Enabled {
+        idx: usize,
+        url: String,
+    },
+    /// Informs the client that the pool has been enabled before
+    AlreadyEnabled {
+        idx: usize,
+        url: String,
+    },
 }
 
 impl From<EnablePool> for Dispatch {
     fn from(enable_pool: EnablePool) -> Self {
-        Dispatch::from_success::<()>(
-            StatusCode::EnablePool.into(),
-            format!("Enabling pool {}:'{}'", enable_pool.idx, enable_pool.url),
-            None,
-        )
+        match enable_pool {
+            EnablePool::Enabled { idx, url } => Dispatch::from_success::<()>(
+                StatusCode::EnablePool.into(),
+                format!("Enabling pool {}:'{}'", idx, url),
+                None,
+            ),
+            EnablePool::AlreadyEnabled { idx, url } => {
+                InfoCode::PoolAlreadyEnabled(idx, url).into()
+            }
+        }
     }
 }
 
-pub struct DisablePool {
-    pub idx: usize,
-    pub url: String,
+pub enum DisablePool {
+    Disabled {
+        idx: usize,
+        url: String,
+    },
+    /// Informs the client that the pool has been disabled before
+    AlreadyDisabled {
+        idx: usize,
+        url: String,
+    },
 }
 
 impl From<DisablePool> for Dispatch {
     fn from(disable_pool: DisablePool) -> Self {
-        Dispatch::from_success::<()>(
-            StatusCode::DisablePool.into(),
-            format!("Disabling pool {}:'{}'", disable_pool.idx, disable_pool.url),
-            None,
-        )
+        match disable_pool {
+            DisablePool::Disabled { idx, url } => Dispatch::from_success::<()>(
+                StatusCode::DisablePool.into(),
+                format!("Disabling pool {}:'{}'", idx, url),
+                None,
+            ),
+            DisablePool::AlreadyDisabled { idx, url } => {
+                InfoCode::PoolAlreadyDisabled(idx, url).into()
+            }
+        }
     }
 }
 
@@ -750,6 +1287,181 @@ impl From<RemovePool> for Dispatch {
     }
 }
 
+pub struct PoolPriority;
+
+impl From<PoolPriority> for Dispatch {
+    fn from(_: PoolPriority) -> Self {
+        Dispatch::from_success::<()>(
+            StatusCode::PoolPriority.into(),
+            "Changed pool priorities".to_string(),
+            None,
+        )
+    }
+}
+
+pub struct FailoverOnly {
+    pub enabled: bool,
+}
+
+impl From<FailoverOnly> for Dispatch {
+    fn from(failover_only: FailoverOnly) -> Self {
+        Dispatch::from_success::<()>(
+            StatusCode::FailoverOnly.into(),
+            format!("Failover-Only set to {}", failover_only.enabled),
+            None,
+        )
+    }
+}
+
+pub enum Pause {
+    Paused,
+    /// Informs the client that the mining has been paused before
+    AlreadyPaused,
+}
+
+impl From<Pause> for Dispatch {
+    fn from(pause: Pause) -> Self {
+        match pause {
+            Pause::Paused => Dispatch::from_success::<()>(
+                StatusCode::Pause.into(),
+                "Mining paused".to_string(),
+                None,
+            ),
+            Pause::AlreadyPaused => InfoCode::AlreadyPaused.into(),
+        }
+    }
+}
+
+pub enum Resume {
+    Resumed,
+    /// Informs the client that the mining has not been paused
+    NotPaused,
+    /// Warns the client that the mining has been resumed but there is no enabled pool to mine on
+    ResumedWithoutPool,
+}
+
+impl From<Resume> for Dispatch {
+    fn from(resume: Resume) -> Self {
+        match resume {
+            Resume::Resumed => Dispatch::from_success::<()>(
+                StatusCode::Resume.into(),
+                "Mining resumed".to_string(),
+                None,
+            ),
+            Resume::NotPaused => InfoCode::NotPaused.into(),
+            Resume::ResumedWithoutPool => Dispatch::from_warning::<()>(
+                StatusCode::ResumedWithoutPool.into(),
+                "Mining resumed but no pool is enabled".to_string(),
+                None,
+            ),
+        }
+    }
+}
+
+/// State of locate mode with remaining time in seconds which is missing when the mode is active
+/// until stopped explicitly
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct Locate {
+    #[serde(rename = "Active")]
+    pub active: Bool,
+    #[serde(rename = "Remaining")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining: Option<u32>,
+}
+
+impl From<Locate> for Dispatch {
+    fn from(locate: Locate) -> Self {
+        let msg = match locate.active {
+            Bool::Y => "Locate mode is active",
+            Bool::N => "Locate mode is inactive",
+        };
+        Dispatch::from_success(
+            StatusCode::Locate.into(),
+            msg.to_string(),
+            Some(Body {
+                name: "LOCATE",
+                list: vec![locate],
+            }),
+        )
+    }
+}
+
+/// Configuration option changed by `setconfig` command with the value actually applied
+pub struct SetConfig {
+    pub name: String,
+    pub value: String,
+}
+
+impl From<SetConfig> for Dispatch {
+    fn from(set_config: SetConfig) -> Self {
+        Dispatch::from_success::<()>(
+            StatusCode::SetConfig.into(),
+            format!("Set config '{}' to {}", set_config.name, set_config.value),
+            None,
+        )
+    }
+}
+
+/// Result of `reloadconfig` command. Settings of the new configuration which cannot be applied
+/// are rejected without affecting the other ones and the response is then only informational.
+#[derive(Serialize, PartialEq, Clone, Debug, Default)]
+pub struct ReloadConfig {
+    /// Settings which have been applied
+    #[serde(rename = "Changed")]
+    pub changed: Vec<String>,
+    /// Settings which have been changed but take effect only after restart of the miner
+    #[serde(rename = "RestartRequired")]
+    pub restart_required: Vec<String>,
+    /// Settings which have been rejected, e.g. because of invalid value
+    #[serde(rename = "Rejected")]
+    pub rejected: Vec<String>,
+}
+
+impl From<ReloadConfig> for Dispatch {
+    fn from(reload_config: ReloadConfig) -> Self {
+        let rejected = reload_config.rejected.len();
+        let body = Some(Body {
+            name: "RELOADCONFIG",
+            list: vec![reload_config],
+        });
+        if rejected == 0 {
+            Dispatch::from_success(
+                StatusCode::ReloadConfig.into(),
+                "Config reloaded".to_string(),
+                body,
+            )
+        } else {
+            Dispatch::from_info(
+                StatusCode::ReloadConfigPartial.into(),
+                format!("Config reloaded with {} rejected setting(s)", rejected),
+                body,
+            )
+        }
+    }
+}
+
+/// Result of `zero` command with optional summary taken before the statistics were zeroed
+pub struct Zero {
+    pub target: support::ZeroTarget,
+    pub summary: Option<Summary>,
+}
+
+impl From<Zero> for Dispatch {
+    fn from(zero: Zero) -> Self {
+        match zero.summary {
+            Some(summary) => Dispatch::from(summary).with_status(
+                StatusCode::ZeroSummary.into(),
+                format!("Zeroed {} stats with summary", zero.target),
+            ),
+            None => Dispatch::from_success::<()>(
+                StatusCode::ZeroNoSummary.into(),
+                format!("Zeroed {} stats without summary", zero.target),
+                None,
+            ),
+        }
+    }
+}
+
 #[derive(Serialize, PartialEq, Clone, Debug)]
 pub struct DevDetail<T> {
     #[serde(rename = "DEVDETAILS")]
@@ -777,7 +1489,7 @@ pub struct DevDetails<T> {
 
 impl<T> From<DevDetails<T>> for Dispatch
 where
-    T: serde::Serialize,
+    T: serde::Serialize + Send + Sync + 'static,
 {
     fn from(dev_details: DevDetails<T>) -> Self {
         Dispatch::from_success(
@@ -792,23 +1504,77 @@ where
 }
 
 #[derive(Serialize, PartialEq, Clone, Debug)]
-pub struct PoolStats {
-    #[serde(flatten)]
-    pub header: StatsHeader,
-    #[serde(rename = "Pool Calls")]
-    pub pool_calls: u32,
-    #[serde(rename = "Pool Attempts")]
-    pub pool_attempts: u32,
-    #[serde(rename = "Pool Wait")]
-    pub pool_wait: Interval,
-    #[serde(rename = "Pool Max")]
-    pub pool_max: Interval,
-    #[serde(rename = "Pool Min")]
-    pub pool_min: Interval,
-    #[serde(rename = "Pool Av")]
-    pub pool_av: f64,
-    #[serde(rename = "Work Had Roll Time")]
-    pub work_had_roll_time: bool,
+pub struct Notify {
+    #[serde(rename = "NOTIFY")]
+    pub idx: i32,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "ID")]
+    pub id: i32,
+    #[serde(rename = "Last Well")]
+    pub last_well: Time,
+    #[serde(rename = "Last Not Well")]
+    pub last_not_well: Time,
+    #[serde(rename = "Reason Not Well")]
+    pub reason_not_well: String,
+    #[serde(rename = "*Thread Fail Init")]
+    pub thread_fail_init: i32,
+    #[serde(rename = "*Thread Zero Hash")]
+    pub thread_zero_hash: i32,
+    #[serde(rename = "*Thread Fail Queue")]
+    pub thread_fail_queue: i32,
+    #[serde(rename = "*Dev Sick Idle 60s")]
+    pub dev_sick_idle_60s: i32,
+    #[serde(rename = "*Dev Dead Idle 600s")]
+    pub dev_dead_idle_600s: i32,
+    #[serde(rename = "*Dev Nostart")]
+    pub dev_nostart: i32,
+    #[serde(rename = "*Dev Over Heat")]
+    pub dev_over_heat: i32,
+    #[serde(rename = "*Dev Thermal Cutoff")]
+    pub dev_thermal_cutoff: i32,
+    #[serde(rename = "*Dev Comms Error")]
+    pub dev_comms_error: i32,
+    #[serde(rename = "*Dev Throttle")]
+    pub dev_throttle: i32,
+}
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct Notifies {
+    pub list: Vec<Notify>,
+}
+
+impl From<Notifies> for Dispatch {
+    fn from(notifies: Notifies) -> Self {
+        Dispatch::from_success(
+            StatusCode::Notify.into(),
+            "Notify".to_string(),
+            Some(Body {
+                name: "NOTIFY",
+                list: notifies.list,
+            }),
+        )
+    }
+}
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct PoolStats {
+    #[serde(flatten)]
+    pub header: StatsHeader,
+    #[serde(rename = "Pool Calls")]
+    pub pool_calls: u32,
+    #[serde(rename = "Pool Attempts")]
+    pub pool_attempts: u32,
+    #[serde(rename = "Pool Wait")]
+    pub pool_wait: Interval,
+    #[serde(rename = "Pool Max")]
+    pub pool_max: Interval,
+    #[serde(rename = "Pool Min")]
+    pub pool_min: Interval,
+    #[serde(rename = "Pool Av")]
+    pub pool_av: f64,
+    #[serde(rename = "Work Had Roll Time")]
+    pub work_had_roll_time: bool,
     #[serde(rename = "Work Can Roll")]
     pub work_can_roll: bool,
     #[serde(rename = "Work Had Expire")]
@@ -837,12 +1603,57 @@ pub struct PoolStats {
     pub net_bytes_sent: u64,
     #[serde(rename = "Net Bytes Recv")]
     pub net_bytes_recv: u64,
+    /// BOSminer extension: job notifications skipped as redundant
+    #[serde(rename = "Redundant Jobs")]
+    pub redundant_jobs: u64,
+}
+
+/// Details of a single chip of an ASC device
+#[derive(PartialEq, Clone, Debug)]
+pub struct ChipStats {
+    pub frequency: MegaHertz,
+    pub mhs: MegaHashes,
+    pub hardware_errors: u32,
+    pub temperature: Option<Temperature>,
+}
+
+/// Per-chip details of an ASC device. They are serialized as fields of the ASC section whose
+/// keys are indexed by position of the chip in the same way as CGMiner does for other per-item
+/// values: `chip_count` followed by `chip_freq_N`, `chip_mhs_N`, `chip_hw_N` and `chip_temp_N`
+/// (only when the temperature is known). Empty details are not serialized at all.
+#[derive(Default, PartialEq, Clone, Debug)]
+pub struct Chips(pub Vec<ChipStats>);
+
+impl Serialize for Chips {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(None)?;
+        if !self.0.is_empty() {
+            map.serialize_entry("chip_count", &self.0.len())?;
+        }
+        for (i, chip) in self.0.iter().enumerate() {
+            map.serialize_entry(&format!("chip_freq_{}", i), &chip.frequency)?;
+            map.serialize_entry(&format!("chip_mhs_{}", i), &chip.mhs)?;
+            map.serialize_entry(&format!("chip_hw_{}", i), &chip.hardware_errors)?;
+            if let Some(temperature) = chip.temperature {
+                map.serialize_entry(&format!("chip_temp_{}", i), &temperature)?;
+            }
+        }
+        map.end()
+    }
 }
 
 #[derive(Serialize, PartialEq, Clone, Debug)]
 pub struct AscStats {
     #[serde(flatten)]
     pub header: StatsHeader,
+    /// Reported only by receivers with enabled chip details (see
+    /// `command::Receiver::with_chip_stats`)
+    #[serde(flatten)]
+    pub chips: Chips,
 }
 
 #[derive(Serialize, PartialEq, Clone, Debug)]
@@ -877,6 +1688,48 @@ pub struct Stats {
 }
 
 impl Stats {
+    /// Keeps only sections selected by the `filter`. ASC device N is the N-th item of
+    /// `asc_stats` and missing device is reported as an invalid ASC index. No filter keeps the
+    /// statistics intact.
+    pub fn select(self, filter: Option<support::StatsFilter>) -> Result<Self, Error> {
+        let Stats {
+            mut asc_stats,
+            pool_stats,
+        } = self;
+        Ok(match filter {
+            None => Stats {
+                asc_stats,
+                pool_stats,
+            },
+            Some(support::StatsFilter::Asc(idx)) => {
+                let last = asc_stats.len() as i32 - 1;
+                if idx < 0 || idx > last {
+                    return Err(ErrorCode::InvalidAscId(idx, last).into());
+                }
+                Stats {
+                    asc_stats: vec![asc_stats.swap_remove(idx as usize)],
+                    pool_stats: vec![],
+                }
+            }
+            Some(support::StatsFilter::Ascs) => Stats {
+                asc_stats,
+                pool_stats: vec![],
+            },
+            Some(support::StatsFilter::Pools) => Stats {
+                asc_stats: vec![],
+                pool_stats,
+            },
+        })
+    }
+
+    /// Removes per-chip details of all ASC devices (see `Chips`)
+    pub fn without_chips(mut self) -> Self {
+        for asc_stats in &mut self.asc_stats {
+            asc_stats.chips = Default::default();
+        }
+        self
+    }
+
     fn into_list(self) -> Vec<StatsType> {
         self.asc_stats
             .into_iter()
@@ -924,6 +1777,245 @@ impl From<Check> for Dispatch {
     }
 }
 
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub(crate) struct HelpCommand {
+    #[serde(rename = "Command")]
+    pub command: String,
+    #[serde(rename = "Parameter")]
+    pub parameter: Bool,
+    #[serde(rename = "Privileged")]
+    pub privileged: Bool,
+    /// The command is privileged only when it is invoked with a parameter
+    #[serde(rename = "PrivilegedParameter")]
+    pub privileged_parameter: Bool,
+    #[serde(rename = "Description")]
+    pub description: String,
+}
+
+/// All registered commands sorted by name
+pub(crate) struct Help {
+    pub list: Vec<HelpCommand>,
+}
+
+impl From<Help> for Dispatch {
+    fn from(help: Help) -> Self {
+        let command_count = help.list.len();
+        Dispatch::from_success(
+            StatusCode::Help.into(),
+            format!("{} command(s)", command_count),
+            Some(Body {
+                name: "HELP",
+                list: help.list,
+            }),
+        )
+    }
+}
+
+/// Describes a single field of a response section (see `schema` module)
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub(crate) struct SchemaField {
+    #[serde(rename = "Command")]
+    pub command: String,
+    #[serde(rename = "Section")]
+    pub section: String,
+    #[serde(rename = "Variant")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    #[serde(rename = "Field")]
+    pub field: String,
+    #[serde(rename = "Type")]
+    pub kind: crate::schema::FieldType,
+    #[serde(rename = "Optional")]
+    pub optional: Bool,
+}
+
+pub(crate) struct Schema {
+    pub list: Vec<SchemaField>,
+}
+
+impl From<Schema> for Dispatch {
+    fn from(schema: Schema) -> Self {
+        let field_count = schema.list.len();
+        Dispatch::from_success(
+            StatusCode::Schema.into(),
+            format!("{} field(s)", field_count),
+            Some(Body {
+                name: "SCHEMA",
+                list: schema.list,
+            }),
+        )
+    }
+}
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub(crate) struct ApiStat {
+    #[serde(rename = "Command")]
+    pub command: String,
+    #[serde(rename = "Calls")]
+    pub calls: u64,
+    #[serde(rename = "Errors")]
+    pub errors: u64,
+    #[serde(rename = "Cancelled")]
+    pub cancelled: u64,
+    /// Minimal, average and maximal time of handling the command in seconds
+    #[serde(rename = "Min")]
+    pub min: Interval,
+    #[serde(rename = "Avg")]
+    pub avg: Interval,
+    #[serde(rename = "Max")]
+    pub max: Interval,
+}
+
+/// Invocation statistics of all registered commands sorted by name
+pub(crate) struct ApiStats {
+    pub list: Vec<ApiStat>,
+}
+
+impl From<ApiStats> for Dispatch {
+    fn from(api_stats: ApiStats) -> Self {
+        Dispatch::from_success(
+            StatusCode::ApiStats.into(),
+            "API statistics".to_string(),
+            Some(Body {
+                name: "APISTATS",
+                list: api_stats.list,
+            }),
+        )
+    }
+}
+
+/// Connection counters of API servers as the first item of the `connections` response
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub(crate) struct ConnectionTotals {
+    #[serde(rename = "Total")]
+    pub total: u64,
+    #[serde(rename = "Active")]
+    pub active: u64,
+    #[serde(rename = "Rejected")]
+    pub rejected: u64,
+    #[serde(rename = "Read Timeouts")]
+    pub read_timeouts: u64,
+}
+
+/// Requests of a single recent client
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub(crate) struct ClientConnection {
+    #[serde(rename = "CLIENT")]
+    pub idx: usize,
+    #[serde(rename = "Address")]
+    pub addr: String,
+    #[serde(rename = "Requests")]
+    pub requests: u64,
+    #[serde(rename = "Errors")]
+    pub errors: u64,
+    #[serde(rename = "Last Command")]
+    pub last_command: String,
+    #[serde(rename = "Last Seen")]
+    pub last_seen: Time,
+}
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+#[serde(untagged)]
+enum ConnectionsType {
+    Totals(ConnectionTotals),
+    Client(ClientConnection),
+}
+
+/// Connection counters followed by statistics of the most recent clients
+pub(crate) struct Connections {
+    pub totals: ConnectionTotals,
+    pub clients: Vec<ClientConnection>,
+}
+
+impl From<Connections> for Dispatch {
+    fn from(connections: Connections) -> Self {
+        let msg = format!("{} API client(s)", connections.clients.len());
+        Dispatch::from_success(
+            StatusCode::Connections.into(),
+            msg,
+            Some(Body {
+                name: "CONNECTIONS",
+                list: std::iter::once(ConnectionsType::Totals(connections.totals))
+                    .chain(connections.clients.into_iter().map(ConnectionsType::Client))
+                    .collect::<Vec<_>>(),
+            }),
+        )
+    }
+}
+
+/// Command recently handled by the receiver (see `history` module)
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub(crate) struct LastCommand {
+    #[serde(rename = "LASTCOMMAND")]
+    pub idx: usize,
+    #[serde(rename = "When")]
+    pub when: Time,
+    /// Address of the client or an empty string for local clients
+    #[serde(rename = "Client")]
+    pub client: String,
+    #[serde(rename = "Command")]
+    pub command: String,
+    #[serde(rename = "Parameter")]
+    pub parameter: String,
+    #[serde(rename = "Status")]
+    pub status: Status,
+    #[serde(rename = "Code")]
+    pub code: u32,
+    #[serde(rename = "Msg")]
+    pub msg: String,
+}
+
+/// History of handled commands starting with the newest one
+pub(crate) struct LastCommands {
+    pub list: Vec<LastCommand>,
+}
+
+impl From<LastCommands> for Dispatch {
+    fn from(last_commands: LastCommands) -> Self {
+        let msg = format!("{} recent command(s)", last_commands.list.len());
+        Dispatch::from_success(
+            StatusCode::LastCommands.into(),
+            msg,
+            Some(Body {
+                name: "LASTCOMMANDS",
+                list: last_commands.list,
+            }),
+        )
+    }
+}
+
+/// Response of `events` command which is followed by the events (see `events` module)
+pub(crate) struct EventsSubscribed;
+
+impl From<EventsSubscribed> for Dispatch {
+    fn from(_: EventsSubscribed) -> Self {
+        Dispatch::from_success::<()>(
+            StatusCode::Events.into(),
+            "Subscribed to events".to_string(),
+            None,
+        )
+    }
+}
+
+/// Response of `auth` command granting the `privilege` to the connection
+pub(crate) struct Auth {
+    pub privilege: Privilege,
+}
+
+impl From<Auth> for Dispatch {
+    fn from(auth: Auth) -> Self {
+        let privilege = match auth.privilege {
+            Privilege::Read => "read",
+            Privilege::Write => "write",
+        };
+        Dispatch::from_success::<()>(
+            StatusCode::Auth.into(),
+            format!("Granted {} privilege", privilege),
+            None,
+        )
+    }
+}
+
 #[derive(Serialize, PartialEq, Clone, Debug)]
 pub struct Coin {
     #[serde(rename = "Hash Method")]
@@ -970,6 +2062,97 @@ impl From<AscCount> for Dispatch {
     }
 }
 
+pub enum AscEnable {
+    Enabled {
+        idx: i32,
+    },
+    /// Informs the client that the device has been enabled before
+    AlreadyEnabled {
+        idx: i32,
+    },
+}
+
+impl From<AscEnable> for Dispatch {
+    fn from(asc_enable: AscEnable) -> Self {
+        match asc_enable {
+            AscEnable::Enabled { idx } => Dispatch::from_success::<()>(
+                StatusCode::AscEnable.into(),
+                format!("ASC {} sent enable message", idx),
+                None,
+            ),
+            AscEnable::AlreadyEnabled { idx } => InfoCode::AscAlreadyEnabled(idx).into(),
+        }
+    }
+}
+
+pub enum AscDisable {
+    Disabled {
+        idx: i32,
+    },
+    /// Informs the client that the device has been disabled before
+    AlreadyDisabled {
+        idx: i32,
+    },
+}
+
+impl From<AscDisable> for Dispatch {
+    fn from(asc_disable: AscDisable) -> Self {
+        match asc_disable {
+            AscDisable::Disabled { idx } => Dispatch::from_success::<()>(
+                StatusCode::AscDisable.into(),
+                format!("ASC {} set disable flag", idx),
+                None,
+            ),
+            AscDisable::AlreadyDisabled { idx } => InfoCode::AscAlreadyDisabled(idx).into(),
+        }
+    }
+}
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct Debug {
+    #[serde(rename = "Silent")]
+    pub silent: Bool,
+    #[serde(rename = "Quiet")]
+    pub quiet: Bool,
+    #[serde(rename = "Verbose")]
+    pub verbose: Bool,
+    #[serde(rename = "Debug")]
+    pub debug: Bool,
+    #[serde(rename = "RPCProto")]
+    pub rpc_proto: Bool,
+    #[serde(rename = "PerDevice")]
+    pub per_device: Bool,
+    #[serde(rename = "WorkTime")]
+    pub work_time: Bool,
+}
+
+impl From<support::DebugSettings> for Debug {
+    fn from(settings: support::DebugSettings) -> Self {
+        Self {
+            silent: settings.silent.into(),
+            quiet: settings.quiet.into(),
+            verbose: settings.verbose.into(),
+            debug: settings.debug.into(),
+            rpc_proto: settings.rpc_proto.into(),
+            per_device: settings.per_device.into(),
+            work_time: settings.work_time.into(),
+        }
+    }
+}
+
+impl From<Debug> for Dispatch {
+    fn from(debug: Debug) -> Self {
+        Dispatch::from_success(
+            StatusCode::Debug.into(),
+            "Debug settings".to_string(),
+            Some(Body {
+                name: "DEBUG",
+                list: vec![debug],
+            }),
+        )
+    }
+}
+
 #[derive(Serialize, PartialEq, Clone, Debug)]
 pub struct Lcd {
     #[serde(rename = "Elapsed")]
@@ -998,6 +2181,61 @@ pub struct Lcd {
     pub user: String,
 }
 
+impl Lcd {
+    /// Assembles the condensed status from the responses of other commands. The fields are
+    /// mapped as follows:
+    ///
+    /// - `Elapsed`, `Best Share` and `Found Blocks` from `summary`
+    /// - `GHS av`, `GHS 5m` and `GHS 5s` from `MHS av`, `MHS 5m` and `MHS 5s` of `summary`
+    /// - `Temperature` is the highest `Temperature` of `devs`
+    /// - `Last Valid Work` is the latest `Last Valid Work` of `devs`
+    /// - `Current Pool`, `User`, `Last Share Time` and `Last Share Difficulty` from `URL`, `User`,
+    ///   `Last Share Time` and `Last Share Difficulty` of the current pool
+    ///
+    /// The current pool is the one with active stratum connection or the alive pool with the
+    /// highest priority (the lowest `Priority`). Without any such pool the pool fields are empty.
+    pub fn from_responses(summary: &Summary, pools: &Pools, devs: &Devs) -> Self {
+        let current_pool = pools
+            .list
+            .iter()
+            .find(|pool| pool.stratum_active)
+            .or_else(|| {
+                pools
+                    .list
+                    .iter()
+                    .filter(|pool| pool.status == PoolStatus::Alive)
+                    .min_by_key(|pool| pool.priority)
+            });
+        Self {
+            elapsed: summary.elapsed,
+            ghs_av: summary.mhs_av / 1000.0,
+            ghs_5m: summary.mhs_5m / 1000.0,
+            ghs_5s: summary.mhs_5s / 1000.0,
+            temperature: devs
+                .list
+                .iter()
+                .map(|asc| asc.temperature)
+                .fold(0.0, Temperature::max),
+            last_share_difficulty: current_pool.map_or(0.0, |pool| pool.last_share_difficulty),
+            last_share_time: current_pool.map_or(0, |pool| pool.last_share_time),
+            best_share: summary.best_share,
+            last_valid_work: devs
+                .list
+                .iter()
+                .map(|asc| asc.last_valid_work)
+                .max()
+                .unwrap_or_default(),
+            found_blocks: summary.found_blocks,
+            current_pool: current_pool
+                .map(|pool| pool.url.clone())
+                .unwrap_or_default(),
+            user: current_pool
+                .map(|pool| pool.user.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
 impl From<Lcd> for Dispatch {
     fn from(lcd: Lcd) -> Self {
         Dispatch::from_success(
@@ -1011,72 +2249,450 @@ impl From<Lcd> for Dispatch {
     }
 }
 
+#[derive(Serialize, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum TunerStage {
+    Disabled,
+    Pending,
+    Tuning,
+    Stable,
+    Failed,
+}
+
+/// Autotuner state of one hash chain
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct TunerChain {
+    #[serde(rename = "TUNERCHAIN")]
+    pub idx: i32,
+    #[serde(rename = "ID")]
+    pub id: i32,
+    #[serde(rename = "Stage")]
+    pub stage: TunerStage,
+    #[serde(rename = "Iterations Done")]
+    pub iterations_done: u32,
+    #[serde(rename = "Iterations Total")]
+    pub iterations_total: u32,
+    /// Estimated UNIX time of finishing the tuning (zero when the chain isn't being tuned)
+    #[serde(rename = "Estimated Finish")]
+    pub estimated_finish: Time,
+    /// Frequency in MHz of the best operating point found so far
+    #[serde(rename = "Best Frequency")]
+    pub best_frequency: f64,
+    /// Voltage in V of the best operating point found so far
+    #[serde(rename = "Best Voltage")]
+    pub best_voltage: f64,
+}
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct TunerStatus {
+    /// Power limit of the whole miner in watts (zero when it is not limited)
+    #[serde(rename = "PowerLimit")]
+    pub power_limit: u32,
+    #[serde(rename = "DynamicPowerScaling")]
+    pub dynamic_power_scaling: Bool,
+    /// Chains are nested as an array of objects in the same way as the top level lists
+    #[serde(rename = "TUNERCHAIN")]
+    pub chains: Vec<TunerChain>,
+}
+
+impl From<TunerStatus> for Dispatch {
+    fn from(tuner_status: TunerStatus) -> Self {
+        Dispatch::from_success(
+            StatusCode::TunerStatus.into(),
+            "Tuner status".to_string(),
+            Some(Body {
+                name: "TUNERSTATUS",
+                list: vec![tuner_status],
+            }),
+        )
+    }
+}
+
+#[derive(Serialize, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum AscSetResult {
+    Applied,
+    Failed,
+    #[serde(rename = "Rolled Back")]
+    RolledBack,
+    Skipped,
+}
+
+/// Outcome of one option of `ascset` command
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct AscSetOption {
+    #[serde(rename = "Option")]
+    pub option: String,
+    #[serde(rename = "Value")]
+    pub value: String,
+    /// Position in the order actually used for applying the options (starting from 1) or zero
+    /// when the option hasn't been applied at all
+    #[serde(rename = "Order")]
+    pub order: u32,
+    #[serde(rename = "Result")]
+    pub result: AscSetResult,
+}
+
+/// Result of possibly multiple options set at once. All options are either applied or the
+/// `error` describes why the whole request failed.
+pub struct AscSet {
+    pub idx: i32,
+    pub options: Vec<AscSetOption>,
+    pub error: Option<String>,
+}
+
+impl From<AscSet> for Dispatch {
+    fn from(asc_set: AscSet) -> Self {
+        let body = Some(Body {
+            name: "ASCSET",
+            list: asc_set.options,
+        });
+        match asc_set.error {
+            None => Dispatch::from_success(
+                StatusCode::AscSet.into(),
+                format!("ASC {} set OK", asc_set.idx),
+                body,
+            ),
+            Some(msg) => {
+                let error: Error = ErrorCode::AscSetErr(asc_set.idx, msg).into();
+                Self {
+                    body: Self::encoded_body(body),
+                    ..error.into()
+                }
+            }
+        }
+    }
+}
+
 pub struct Body<S: Serialize> {
     pub name: &'static str,
     pub list: Vec<S>,
 }
 
+#[derive(Clone)]
+enum SectionList {
+    Encoded(Arc<Vec<Box<RawValue>>>),
+    Value(Vec<ordered::Value>),
+}
+
+/// List of sections of a response body. Sections built by the handlers are serialized into JSON
+/// once when the response is built and they are copied into the encoded response as they are so
+/// that no `json::Value` tree is built for them. They are converted into `ordered::Value` only
+/// when the body has to be inspected or modified (e.g. in delta mode or for the plain-text
+/// format).
+#[derive(Clone)]
+pub struct Sections(SectionList);
+
+impl Sections {
+    fn encode<S: Serialize>(list: Vec<S>) -> Self {
+        let list = list
+            .iter()
+            .map(|section| {
+                json::to_string(section)
+                    .and_then(RawValue::from_string)
+                    .expect("BUG: response serialization failed")
+            })
+            .collect();
+        Self(SectionList::Encoded(Arc::new(list)))
+    }
+
+    /// Takes sections of the JSON array `value`
+    fn from_value(value: ordered::Value) -> Self {
+        match value {
+            ordered::Value::Array(list) => Self(SectionList::Value(list)),
+            _ => panic!("BUG: list of sections is not an array"),
+        }
+    }
+
+    pub(crate) fn to_value(&self) -> Cow<'_, [ordered::Value]> {
+        match &self.0 {
+            SectionList::Encoded(list) => Cow::Owned(Self::decode(list)),
+            SectionList::Value(list) => Cow::Borrowed(list),
+        }
+    }
+
+    #[cfg(feature = "prometheus")]
+    fn into_value(self) -> ordered::Value {
+        match self.0 {
+            SectionList::Encoded(list) => ordered::Value::Array(Self::decode(&list)),
+            SectionList::Value(list) => ordered::Value::Array(list),
+        }
+    }
+
+    fn decode(list: &[Box<RawValue>]) -> Vec<ordered::Value> {
+        list.iter()
+            .map(|section| json::from_str(section.get()).expect("BUG: invalid encoded section"))
+            .collect()
+    }
+}
+
+/// Encoded sections are written as they are by `serde_json` and they are decoded by
+/// `ordered::to_value`
+impl Serialize for Sections {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match &self.0 {
+            SectionList::Encoded(list) => list.serialize(serializer),
+            SectionList::Value(list) => list.serialize(serializer),
+        }
+    }
+}
+
+impl fmt::Debug for Sections {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.to_value().iter()).finish()
+    }
+}
+
+/// Response of a custom command registered with `command::ReceiverBuilder`. The body is reported
+/// under the `name` and the status code is offset by `StatusCode::CustomBase`.
+pub struct CustomResponse<S: Serialize> {
+    name: &'static str,
+    status: Status,
+    code: u32,
+    msg: String,
+    list: Vec<S>,
+}
+
+impl<S: Serialize> CustomResponse<S> {
+    /// Builds a successful response with body `name` (e.g. `CHAINS`) containing all items in
+    /// the `list`. The status message is derived from the name (e.g. `BOSminer chains`).
+    pub fn new<T>(name: &'static str, code: T, list: Vec<S>) -> Self
+    where
+        T: Into<u32>,
+    {
+        Self {
+            name,
+            status: Status::S,
+            code: code.into(),
+            msg: format!("{} {}", crate::SIGNATURE_TAG, name.to_lowercase()),
+            list,
+        }
+    }
+
+    /// Replaces the default status message
+    pub fn msg(mut self, msg: String) -> Self {
+        self.msg = msg;
+        self
+    }
+
+    /// Reports the response with status `I` while the body is kept
+    pub fn info(mut self) -> Self {
+        self.status = Status::I;
+        self
+    }
+
+    /// Reports the response with status `W` while the body is kept
+    pub fn warning(mut self) -> Self {
+        self.status = Status::W;
+        self
+    }
+}
+
+impl<S: Serialize + Send + Sync + 'static> From<CustomResponse<S>> for Dispatch {
+    fn from(response: CustomResponse<S>) -> Self {
+        Dispatch::from_status(
+            response.status,
+            StatusCodeType::Custom(response.code),
+            response.msg,
+            Some(Body {
+                name: response.name,
+                list: response.list,
+            }),
+        )
+    }
+}
+
 /// Generic container for any response, ensures conforming serialization
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Dispatch {
     status: Status,
     code: StatusCodeType,
     msg: String,
-    body: Option<(&'static str, json::Value)>,
+    body: Option<(&'static str, Sections)>,
+    /// Age of the response taken from cache
+    age: Option<Duration>,
+    /// Time when the handler collected the data of the response
+    collected: Option<Time>,
+    /// Sequence token and delta flag of the response in delta mode
+    delta: Option<(u64, bool)>,
 }
 
 impl Dispatch {
-    fn from_success<S: Serialize>(
+    fn encoded_body<S>(body: Option<Body<S>>) -> Option<(&'static str, Sections)>
+    where
+        S: Serialize + Send + Sync + 'static,
+    {
+        body.map(|body| (body.name, Sections::encode(body.list)))
+    }
+
+    fn from_status<S: Serialize + Send + Sync + 'static>(
+        status: Status,
         code: StatusCodeType,
         msg: String,
         body: Option<Body<S>>,
     ) -> Self {
-        let body = body.map(|body| {
-            (
-                body.name,
-                json::to_value(body.list).expect("BUG: response serialization failed"),
-            )
-        });
-
         Self {
-            status: Status::S,
+            status,
             code,
             msg,
-            body,
+            body: Self::encoded_body(body),
+            age: None,
+            collected: None,
+            delta: None,
         }
     }
 
+    fn from_success<S: Serialize + Send + Sync + 'static>(
+        code: StatusCodeType,
+        msg: String,
+        body: Option<Body<S>>,
+    ) -> Self {
+        Self::from_status(Status::S, code, msg, body)
+    }
+
+    /// Builds a response with status `I` reporting e.g. that nothing had to be done
+    fn from_info<S: Serialize + Send + Sync + 'static>(
+        code: StatusCodeType,
+        msg: String,
+        body: Option<Body<S>>,
+    ) -> Self {
+        Self::from_status(Status::I, code, msg, body)
+    }
+
+    /// Builds a response with status `W` of a command which has been handled but its outcome
+    /// deserves attention of the client
+    fn from_warning<S: Serialize + Send + Sync + 'static>(
+        code: StatusCodeType,
+        msg: String,
+        body: Option<Body<S>>,
+    ) -> Self {
+        Self::from_status(Status::W, code, msg, body)
+    }
+
+    /// Replaces status of already built response while the body is kept. It allows reusing
+    /// a body of another command in a response with a different status.
+    fn with_status(self, code: StatusCodeType, msg: String) -> Self {
+        Self { code, msg, ..self }
+    }
+
+    #[inline]
+    pub(crate) fn is_error(&self) -> bool {
+        self.status == Status::E
+    }
+
+    /// Marks the response as cached `age` ago
+    pub(crate) fn with_age(self, age: Duration) -> Self {
+        Self {
+            age: Some(age),
+            ..self
+        }
+    }
+
+    /// Reports that the data of the response have been `collected` at another time than the
+    /// response is generated. Handlers caching the data themselves should set it.
+    pub fn with_collected(self, collected: Time) -> Self {
+        Self {
+            collected: Some(collected),
+            ..self
+        }
+    }
+
+    /// Sets the time of collecting the data unless the handler has set it already
+    pub(crate) fn or_collected(mut self, collected: Time) -> Self {
+        self.collected.get_or_insert(collected);
+        self
+    }
+
+    /// Serialized list of sections of the body
+    pub(crate) fn body(&self) -> Option<ordered::Value> {
+        self.body
+            .as_ref()
+            .map(|(_, list)| ordered::Value::Array(list.to_value().into_owned()))
+    }
+
+    /// Marks the response with sequence `token` of delta mode. The body is replaced with the
+    /// `delta` when there is one.
+    pub(crate) fn with_delta(mut self, token: u64, delta: Option<ordered::Value>) -> Self {
+        self.delta = Some((token, delta.is_some()));
+        if let (Some((_, list)), Some(delta)) = (&mut self.body, delta) {
+            *list = Sections::from_value(delta);
+        }
+        self
+    }
+
+    /// Describes the status of the response for observers
+    pub(crate) fn observed_status(&self, signature: &str) -> crate::observer::ResponseStatus {
+        crate::observer::ResponseStatus {
+            status: self.status,
+            code: self.code.value(),
+            msg: self.msg.replace(crate::SIGNATURE_TAG, signature),
+        }
+    }
+
+    /// Takes the serialized body (its name and list of sections) out of the response
+    #[cfg(feature = "prometheus")]
+    pub(crate) fn into_body(self) -> Option<(&'static str, ordered::Value)> {
+        self.body.map(|(name, list)| (name, list.into_value()))
+    }
+
     pub fn from_custom_success<S, T>(code: T, msg: String, body: Option<Body<S>>) -> Self
     where
-        S: Serialize,
+        S: Serialize + Send + Sync + 'static,
         T: Into<u32>,
     {
         Self::from_success(StatusCodeType::Custom(code.into()), msg, body)
     }
 
+    /// Same as `from_custom_success` but the response has status `I`
+    pub fn from_custom_info<S, T>(code: T, msg: String, body: Option<Body<S>>) -> Self
+    where
+        S: Serialize + Send + Sync + 'static,
+        T: Into<u32>,
+    {
+        Self::from_info(StatusCodeType::Custom(code.into()), msg, body)
+    }
+
+    /// Same as `from_custom_success` but the response has status `W`
+    pub fn from_custom_warning<S, T>(code: T, msg: String, body: Option<Body<S>>) -> Self
+    where
+        S: Serialize + Send + Sync + 'static,
+        T: Into<u32>,
+    {
+        Self::from_warning(StatusCodeType::Custom(code.into()), msg, body)
+    }
+
     fn create_status_info(
         &self,
         when: Time,
+        when_ms: Option<u64>,
         signature: &String,
         description: &String,
     ) -> StatusInfo {
         StatusInfo {
             status: self.status,
             when,
+            when_ms,
             code: self.code,
             msg: self.msg.replace(crate::SIGNATURE_TAG, signature.as_str()),
             description: description.clone(),
+            age: self.age.map(|age| age.as_secs_f64()),
+            // Strict parsers are not bothered by the field when the data are fresh
+            collected: self.collected.filter(|collected| *collected != when),
+            seq: self.delta.map(|(token, _)| token),
+            delta: self.delta.map(|(_, delta)| delta),
         }
     }
 
     pub fn into_response(
         self,
         when: Time,
+        when_ms: Option<u64>,
         signature: &String,
         description: &String,
     ) -> support::SingleResponse {
         support::SingleResponse {
-            status_info: self.create_status_info(when, signature, description),
+            status_info: self.create_status_info(when, when_ms, signature, description),
             body: self.body,
         }
     }
@@ -81,7 +81,7 @@ pub struct Temps<T> {
 
 impl<T> From<Temps<T>> for Dispatch
 where
-    T: serde::Serialize,
+    T: serde::Serialize + Send + Sync + 'static,
 {
     fn from(temps: Temps<T>) -> Self {
         let temp_count = temps.list.len();
@@ -125,3 +125,116 @@ impl From<Fans> for Dispatch {
         )
     }
 }
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub enum FanCtrlMode {
+    Auto,
+    Manual,
+    Disabled,
+}
+
+/// Fan control settings along with the measured fan speeds
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct FanCtrl {
+    #[serde(rename = "Mode")]
+    pub mode: FanCtrlMode,
+    /// Requested fan speed in percent (in automatic mode it is the speed currently set by the
+    /// temperature controller)
+    #[serde(rename = "Speed")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u32>,
+    /// Measured speed of each fan
+    #[serde(rename = "RPM")]
+    pub rpm: Vec<u32>,
+}
+
+impl From<FanCtrl> for Dispatch {
+    fn from(fan_ctrl: FanCtrl) -> Self {
+        Dispatch::from_success(
+            StatusCode::FanCtrl.into(),
+            "Fan control".to_string(),
+            Some(Body {
+                name: "FANCTRL",
+                list: vec![fan_ctrl],
+            }),
+        )
+    }
+}
+
+/// Share log state
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct ShareLog {
+    /// Path to the current file (empty when the share log isn't configured)
+    #[serde(rename = "File")]
+    pub file: String,
+    /// Size of the current file in bytes
+    #[serde(rename = "Size")]
+    pub size: u64,
+    /// Number of lines which haven't been written due to full queue or write error
+    #[serde(rename = "Dropped")]
+    pub dropped: u64,
+    #[serde(rename = "Enabled")]
+    pub enabled: Bool,
+}
+
+impl From<ShareLog> for Dispatch {
+    fn from(share_log: ShareLog) -> Self {
+        Dispatch::from_success(
+            StatusCode::ShareLog.into(),
+            "Share log".to_string(),
+            Some(Body {
+                name: "SHARELOG",
+                list: vec![share_log],
+            }),
+        )
+    }
+}
+
+/// Accepted shares of one pool within one reconciliation bucket (aligned to the hour in UTC)
+#[derive(Serialize, PartialEq, Clone, Debug)]
+pub struct PoolStat {
+    #[serde(rename = "POOL")]
+    pub idx: i32,
+    #[serde(rename = "URL")]
+    pub url: String,
+    #[serde(rename = "User")]
+    pub user: String,
+    /// UNIX time of the bucket start
+    #[serde(rename = "Bucket Start")]
+    pub bucket_start: u32,
+    /// Bucket duration in seconds
+    #[serde(rename = "Bucket Duration")]
+    pub bucket_duration: u32,
+    #[serde(rename = "Accepted")]
+    pub accepted: u64,
+    /// Sum of difficulties of accepted shares
+    #[serde(rename = "Difficulty Accepted")]
+    pub difficulty_accepted: u64,
+    /// Sum of difficulties of accepted shares reported by the pool
+    #[serde(rename = "Pool Difficulty Accepted")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_difficulty_accepted: Option<u64>,
+    /// Local minus pool reported sum of difficulties
+    #[serde(rename = "Delta")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<i64>,
+}
+
+pub struct PoolStats {
+    pub list: Vec<PoolStat>,
+}
+
+impl From<PoolStats> for Dispatch {
+    fn from(pool_stats: PoolStats) -> Self {
+        let stat_count = pool_stats.list.len();
+        Dispatch::from_success(
+            StatusCode::PoolStats.into(),
+            format!("{} Pool stat(s)", stat_count),
+            Some(Body {
+                name: "POOLSTATS",
+                list: pool_stats.list,
+            }),
+        )
+    }
+}
@@ -0,0 +1,504 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Description of response sections reported by the `schema` command
+//!
+//! The tables mirror serialization of the structures in the `response` module and have to be
+//! updated together with them. Tests check the tables against the actual responses.
+//!
+//! A field name ending with `*` stands for any number of fields starting with the preceding
+//! prefix (e.g. `chip_freq_*`) and a lone `*` for any other field. The field which is named by
+//! the miner signature (see `crate::SIGNATURE_TAG`) is reported with the actual signature.
+
+use serde::Serialize;
+
+/// JSON type of a field value
+#[derive(Serialize, Eq, PartialEq, Copy, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    /// Any JSON value
+    Any,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Field {
+    pub name: &'static str,
+    pub kind: FieldType,
+    /// Optional fields may be missing in some responses
+    pub optional: bool,
+}
+
+impl Field {
+    /// Determines whether the field describes the field `name` of a serialized section
+    pub fn matches(&self, name: &str) -> bool {
+        match self.name.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => self.name == name,
+        }
+    }
+}
+
+/// Describes one kind of sections in the list of a response
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct Section {
+    /// Name of the list (e.g. `DEVS`)
+    pub name: &'static str,
+    /// Distinguishes different kinds of sections in the same list (e.g. `ASC` and `POOL`
+    /// sections of `STATS`)
+    pub variant: Option<&'static str>,
+    pub fields: &'static [Field],
+}
+
+const fn field(name: &'static str, kind: FieldType) -> Field {
+    Field {
+        name,
+        kind,
+        optional: false,
+    }
+}
+
+const fn optional(name: &'static str, kind: FieldType) -> Field {
+    Field {
+        name,
+        kind,
+        optional: true,
+    }
+}
+
+const fn section(name: &'static str, fields: &'static [Field]) -> Section {
+    Section {
+        name,
+        variant: None,
+        fields,
+    }
+}
+
+const fn variant(name: &'static str, variant: &'static str, fields: &'static [Field]) -> Section {
+    Section {
+        name,
+        variant: Some(variant),
+        fields,
+    }
+}
+
+use FieldType as T;
+
+const POOL_FIELDS: &[Field] = &[
+    field("POOL", T::Integer),
+    field("URL", T::String),
+    field("Status", T::String),
+    field("Priority", T::Integer),
+    field("Quota", T::Integer),
+    field("Long Poll", T::String),
+    field("Getworks", T::Integer),
+    field("Accepted", T::Integer),
+    field("Rejected", T::Integer),
+    field("Works", T::Integer),
+    field("Discarded", T::Integer),
+    field("Stale", T::Integer),
+    field("Get Failures", T::Integer),
+    field("Remote Failures", T::Integer),
+    field("User", T::String),
+    field("Last Share Time", T::Integer),
+    field("Diff1 Shares", T::Integer),
+    field("Proxy Type", T::String),
+    field("Proxy", T::String),
+    field("Difficulty Accepted", T::Number),
+    field("Difficulty Rejected", T::Number),
+    field("Difficulty Stale", T::Number),
+    field("Last Share Difficulty", T::Number),
+    field("Work Difficulty", T::Number),
+    field("Has Stratum", T::Boolean),
+    field("Stratum Active", T::Boolean),
+    field("Stratum URL", T::String),
+    field("Stratum Difficulty", T::Number),
+    field("Has Vmask", T::Boolean),
+    field("Has GBT", T::Boolean),
+    field("Best Share", T::Integer),
+    field("Pool Rejected%", T::Number),
+    field("Pool Stale%", T::Number),
+    field("Bad Work", T::Integer),
+    field("Current Block Height", T::Integer),
+    field("Current Block Version", T::Integer),
+    field("AsicBoost", T::Boolean),
+];
+
+const ASC_FIELDS: &[Field] = &[
+    field("ASC", T::Integer),
+    field("Name", T::String),
+    field("ID", T::Integer),
+    field("Enabled", T::String),
+    field("Status", T::String),
+    field("Temperature", T::Number),
+    field("MHS av", T::Number),
+    field("MHS 5s", T::Number),
+    field("MHS 1m", T::Number),
+    field("MHS 5m", T::Number),
+    field("MHS 15m", T::Number),
+    field("Accepted", T::Integer),
+    field("Rejected", T::Integer),
+    field("Hardware Errors", T::Integer),
+    field("Utility", T::Number),
+    field("Last Share Pool", T::Integer),
+    field("Last Share Time", T::Integer),
+    field("Total MH", T::Number),
+    field("Diff1 Work", T::Integer),
+    field("Difficulty Accepted", T::Number),
+    field("Difficulty Rejected", T::Number),
+    field("Last Share Difficulty", T::Number),
+    field("Last Valid Work", T::Integer),
+    field("Device Hardware%", T::Number),
+    field("Device Rejected%", T::Number),
+    field("Device Elapsed", T::Integer),
+    field("Hardware Error MHS 15m", T::Number),
+    field("Nominal MHS", T::Number),
+];
+
+const SUMMARY_FIELDS: &[Field] = &[
+    field("Elapsed", T::Integer),
+    field("MHS av", T::Number),
+    field("MHS 5s", T::Number),
+    field("MHS 1m", T::Number),
+    field("MHS 5m", T::Number),
+    field("MHS 15m", T::Number),
+    field("Found Blocks", T::Integer),
+    field("Getworks", T::Integer),
+    field("Accepted", T::Integer),
+    field("Rejected", T::Integer),
+    field("Hardware Errors", T::Integer),
+    field("Utility", T::Number),
+    field("Discarded", T::Integer),
+    field("Stale", T::Integer),
+    field("Get Failures", T::Integer),
+    field("Local Work", T::Integer),
+    field("Remote Failures", T::Integer),
+    field("Network Blocks", T::Integer),
+    field("Total MH", T::Number),
+    field("Work Utility", T::Number),
+    field("Difficulty Accepted", T::Number),
+    field("Difficulty Rejected", T::Number),
+    field("Difficulty Stale", T::Number),
+    field("Best Share", T::Integer),
+    field("Device Hardware%", T::Number),
+    field("Device Rejected%", T::Number),
+    field("Pool Rejected%", T::Number),
+    field("Pool Stale%", T::Number),
+    field("Last getwork", T::Integer),
+    field("MHS 24h", T::Number),
+    field("Pool Dead Park", T::String),
+    field("Paused", T::String),
+    optional("*", T::Any),
+];
+
+const NOTIFY_FIELDS: &[Field] = &[
+    field("NOTIFY", T::Integer),
+    field("Name", T::String),
+    field("ID", T::Integer),
+    field("Last Well", T::Integer),
+    field("Last Not Well", T::Integer),
+    field("Reason Not Well", T::String),
+    field("*Thread Fail Init", T::Integer),
+    field("*Thread Zero Hash", T::Integer),
+    field("*Thread Fail Queue", T::Integer),
+    field("*Dev Sick Idle 60s", T::Integer),
+    field("*Dev Dead Idle 600s", T::Integer),
+    field("*Dev Nostart", T::Integer),
+    field("*Dev Over Heat", T::Integer),
+    field("*Dev Thermal Cutoff", T::Integer),
+    field("*Dev Comms Error", T::Integer),
+    field("*Dev Throttle", T::Integer),
+];
+
+/// Fields of `response::AscStats` including the header shared with pool statistics
+const ASC_STATS_FIELDS: &[Field] = &[
+    field("STATS", T::Integer),
+    field("ID", T::String),
+    field("Elapsed", T::Integer),
+    field("Calls", T::Integer),
+    field("Wait", T::Number),
+    field("Max", T::Number),
+    field("Min", T::Number),
+    optional("chip_count", T::Integer),
+    optional("chip_freq_*", T::Number),
+    optional("chip_mhs_*", T::Number),
+    optional("chip_hw_*", T::Integer),
+    optional("chip_temp_*", T::Number),
+];
+
+/// Fields of `response::PoolStats` including the header shared with ASC statistics
+const POOL_STATS_FIELDS: &[Field] = &[
+    field("STATS", T::Integer),
+    field("ID", T::String),
+    field("Elapsed", T::Integer),
+    field("Calls", T::Integer),
+    field("Wait", T::Number),
+    field("Max", T::Number),
+    field("Min", T::Number),
+    field("Pool Calls", T::Integer),
+    field("Pool Attempts", T::Integer),
+    field("Pool Wait", T::Number),
+    field("Pool Max", T::Number),
+    field("Pool Min", T::Number),
+    field("Pool Av", T::Number),
+    field("Work Had Roll Time", T::Boolean),
+    field("Work Can Roll", T::Boolean),
+    field("Work Had Expire", T::Boolean),
+    field("Work Roll Time", T::Integer),
+    field("Work Diff", T::Number),
+    field("Min Diff", T::Number),
+    field("Max Diff", T::Number),
+    field("Min Diff Count", T::Integer),
+    field("Max Diff Count", T::Integer),
+    field("Times Sent", T::Integer),
+    field("Bytes Sent", T::Integer),
+    field("Times Recv", T::Integer),
+    field("Bytes Recv", T::Integer),
+    field("Net Bytes Sent", T::Integer),
+    field("Net Bytes Recv", T::Integer),
+    field("Redundant Jobs", T::Integer),
+];
+
+pub const POOLS: &[Section] = &[section("POOLS", POOL_FIELDS)];
+
+pub const DEVS: &[Section] = &[section("DEVS", ASC_FIELDS)];
+
+pub const ASC: &[Section] = &[section("ASC", ASC_FIELDS)];
+
+/// Vendor specific fields may follow (see `response::Extras`)
+pub const SUMMARY: &[Section] = &[section("SUMMARY", SUMMARY_FIELDS)];
+
+/// Extra fields added with `response::VersionInfo::field` are strings
+pub const VERSION: &[Section] = &[section(
+    "VERSION",
+    &[
+        field(crate::SIGNATURE_TAG, T::String),
+        field("API", T::String),
+        optional("*", T::String),
+    ],
+)];
+
+/// Vendor specific fields may follow (see `response::Extras`)
+pub const CONFIG: &[Section] = &[section(
+    "CONFIG",
+    &[
+        field("ASC Count", T::Integer),
+        field("PGA Count", T::Integer),
+        field("Pool Count", T::Integer),
+        field("Strategy", T::String),
+        field("Failover-Only", T::Boolean),
+        field("Log Interval", T::Integer),
+        field("Device Code", T::String),
+        field("OS", T::String),
+        field("Hotplug", T::String),
+        optional("Hardware Profile", T::String),
+        optional("*", T::Any),
+    ],
+)];
+
+pub const LOCATE: &[Section] = &[section(
+    "LOCATE",
+    &[
+        field("Active", T::String),
+        optional("Remaining", T::Integer),
+    ],
+)];
+
+pub const RELOAD_CONFIG: &[Section] = &[section(
+    "RELOADCONFIG",
+    &[
+        field("Changed", T::Array),
+        field("RestartRequired", T::Array),
+        field("Rejected", T::Array),
+    ],
+)];
+
+/// The miner specific details (see `response::DevDetail::info`) are not known
+pub const DEV_DETAILS: &[Section] = &[section(
+    "DEVDETAILS",
+    &[
+        field("DEVDETAILS", T::Integer),
+        field("Name", T::String),
+        field("ID", T::Integer),
+        field("Driver", T::String),
+        field("Kernel", T::String),
+        field("Model", T::String),
+        field("Device Path", T::String),
+        optional("*", T::Any),
+    ],
+)];
+
+pub const NOTIFY: &[Section] = &[section("NOTIFY", NOTIFY_FIELDS)];
+
+pub const STATS: &[Section] = &[
+    variant("STATS", "ASC", ASC_STATS_FIELDS),
+    variant("STATS", "POOL", POOL_STATS_FIELDS),
+];
+
+pub const CHECK: &[Section] = &[section(
+    "CHECK",
+    &[field("Exists", T::String), field("Access", T::String)],
+)];
+
+pub const HELP: &[Section] = &[section(
+    "HELP",
+    &[
+        field("Command", T::String),
+        field("Parameter", T::String),
+        field("Privileged", T::String),
+        field("PrivilegedParameter", T::String),
+        field("Description", T::String),
+    ],
+)];
+
+pub const API_STATS: &[Section] = &[section(
+    "APISTATS",
+    &[
+        field("Command", T::String),
+        field("Calls", T::Integer),
+        field("Errors", T::Integer),
+        field("Cancelled", T::Integer),
+        field("Min", T::Number),
+        field("Avg", T::Number),
+        field("Max", T::Number),
+    ],
+)];
+
+pub const CONNECTIONS: &[Section] = &[
+    variant(
+        "CONNECTIONS",
+        "TOTALS",
+        &[
+            field("Total", T::Integer),
+            field("Active", T::Integer),
+            field("Rejected", T::Integer),
+            field("Read Timeouts", T::Integer),
+        ],
+    ),
+    variant(
+        "CONNECTIONS",
+        "CLIENT",
+        &[
+            field("CLIENT", T::Integer),
+            field("Address", T::String),
+            field("Requests", T::Integer),
+            field("Errors", T::Integer),
+            field("Last Command", T::String),
+            field("Last Seen", T::Integer),
+        ],
+    ),
+];
+
+pub const LAST_COMMANDS: &[Section] = &[section(
+    "LASTCOMMANDS",
+    &[
+        field("LASTCOMMAND", T::Integer),
+        field("When", T::Integer),
+        field("Client", T::String),
+        field("Command", T::String),
+        field("Parameter", T::String),
+        field("Status", T::String),
+        field("Code", T::Integer),
+        field("Msg", T::String),
+    ],
+)];
+
+pub const COIN: &[Section] = &[section(
+    "COIN",
+    &[
+        field("Hash Method", T::String),
+        field("Current Block Time", T::Number),
+        field("Current Block Hash", T::String),
+        field("LP", T::Boolean),
+        field("Network Difficulty", T::Number),
+    ],
+)];
+
+pub const ASC_COUNT: &[Section] = &[section("ASCS", &[field("Count", T::Integer)])];
+
+pub const DEBUG: &[Section] = &[section(
+    "DEBUG",
+    &[
+        field("Silent", T::String),
+        field("Quiet", T::String),
+        field("Verbose", T::String),
+        field("Debug", T::String),
+        field("RPCProto", T::String),
+        field("PerDevice", T::String),
+        field("WorkTime", T::String),
+    ],
+)];
+
+pub const LCD: &[Section] = &[section(
+    "LCD",
+    &[
+        field("Elapsed", T::Integer),
+        field("GHS av", T::Number),
+        field("GHS 5m", T::Number),
+        field("GHS 5s", T::Number),
+        field("Temperature", T::Number),
+        field("Last Share Difficulty", T::Number),
+        field("Last Share Time", T::Integer),
+        field("Best Share", T::Integer),
+        field("Last Valid Work", T::Integer),
+        field("Found Blocks", T::Integer),
+        field("Current Pool", T::String),
+        field("User", T::String),
+    ],
+)];
+
+/// Chains are nested objects with fields of `response::TunerChain`
+pub const TUNER_STATUS: &[Section] = &[section(
+    "TUNERSTATUS",
+    &[
+        field("PowerLimit", T::Integer),
+        field("DynamicPowerScaling", T::String),
+        field("TUNERCHAIN", T::Array),
+    ],
+)];
+
+pub const ASC_SET: &[Section] = &[section(
+    "ASCSET",
+    &[
+        field("Option", T::String),
+        field("Value", T::String),
+        field("Order", T::Integer),
+        field("Result", T::String),
+    ],
+)];
+
+pub const SCHEMA: &[Section] = &[section(
+    "SCHEMA",
+    &[
+        field("Command", T::String),
+        field("Section", T::String),
+        optional("Variant", T::String),
+        field("Field", T::String),
+        field("Type", T::String),
+        field("Optional", T::String),
+    ],
+)];
@@ -0,0 +1,1474 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Server accepting API connections and dispatching their requests to the command `Receiver`
+//!
+//! The API can be served over TCP or over Unix domain socket. Each connection carries exactly one
+//! request as in CGMiner. The response is terminated by a NUL byte and the connection is closed
+//! right after it has been sent. Both JSON and plain-text requests are accepted and the response
+//! is sent in the format of the request.
+//!
+//! The only exception is the `events` command (see `events` module). Its connection is kept
+//! open and the events are sent until the client disconnects or the server is shut down.
+//!
+//! Clients refused by access control of the `Receiver` are disconnected right after the
+//! connection has been accepted without any response as in CGMiner.
+//!
+//! The number of simultaneous connections can be limited and a client which does not send its
+//! request in time is disconnected. Connections which have not been served are counted in
+//! server `Statistics` together with requests of recent TCP clients. The statistics can be shared
+//! with the `Receiver` which then reports them in response to the `connections` command.
+//!
+//! Handling of a read-only request is cancelled when the connection is reset by the client before
+//! the response has been sent. A client closing the connection can cancel the request as well
+//! when it is enabled with `Server::cancel_on_hangup`. Commands changing the state of the miner
+//! are never cancelled.
+//!
+//! Requests of each TCP client can be limited by a `RateLimiter` shared by all its connections.
+//! Requests over the limit are responded with an error instead of being handled.
+//!
+//! With the `tls` feature enabled, TCP connections can be encrypted with TLS. The handshake is
+//! performed before the request is read and everything else stays the same.
+
+use ii_logging::macros::*;
+
+use ii_async_compat::{futures, tokio};
+
+use futures::future::{self, Either, Future, FutureExt as _, Shared};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::os::unix::fs::PermissionsExt as _;
+use std::os::unix::net;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::command;
+use crate::events::Subscription;
+use crate::response;
+use crate::support::{BufferPool, ResponseBuffer, ResponseType, UnixTime, When};
+use crate::Format;
+
+#[cfg(feature = "tls")]
+use tokio_rustls::{rustls, TlsAcceptor};
+
+/// Default limit of the request size in bytes
+pub const DEFAULT_MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// Default time limit for receiving a complete request after the connection has been accepted
+pub const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+const READ_CHUNK_SIZE: usize = 1024;
+
+/// Result of reading a request from a connection
+enum Request {
+    Complete(command::Request, Format),
+    Invalid,
+    /// The connection has been closed without sending anything
+    Closed,
+}
+
+/// Reads a request from the `stream` (see `command::Request::parse`).
+///
+/// Plain-text request has no terminator so it is processed as soon as anything arrives as CGMiner
+/// does. The clients send such short requests at once.
+async fn read_request<S>(stream: &mut S, max_request_size: usize) -> io::Result<Request>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buf = Vec::with_capacity(READ_CHUNK_SIZE);
+    let mut chunk = [0u8; READ_CHUNK_SIZE];
+    let mut closed = false;
+
+    loop {
+        let complete = closed || Format::detect(&buf) == Some(Format::Text);
+        match command::Request::parse(&buf, complete, max_request_size) {
+            Ok(Some((request, format, _))) => return Ok(Request::Complete(request, format)),
+            Ok(None) if closed => return Ok(Request::Closed),
+            Ok(None) => {}
+            Err(command::ParseError::TooLarge(_)) => {
+                warn!(
+                    "CGMiner API: request exceeds maximum size of {} bytes",
+                    max_request_size
+                );
+                return Ok(Request::Invalid);
+            }
+            Err(_) => return Ok(Request::Invalid),
+        }
+
+        let len = READ_CHUNK_SIZE.min(max_request_size - buf.len());
+        let len = stream.read(&mut chunk[..len]).await?;
+        closed = len == 0;
+        buf.extend_from_slice(&chunk[..len]);
+    }
+}
+
+/// Token bucket of a single client
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+struct RateLimiterState {
+    buckets: HashMap<IpAddr, Bucket>,
+    cleaned: Instant,
+}
+
+/// Limits the rate of requests of each client identified by its IP address. A client can send
+/// a `burst` of requests at once and then `rate` requests per second on average. Local clients
+/// are never limited.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    state: Mutex<RateLimiterState>,
+}
+
+impl RateLimiter {
+    /// # Panics
+    ///
+    /// The `rate` has to be positive and the `burst` has to allow at least one request.
+    pub fn new(rate: f64, burst: u32) -> Self {
+        assert!(rate > 0.0, "BUG: rate limit has to be positive");
+        assert!(burst > 0, "BUG: burst of requests has to be positive");
+        Self {
+            rate,
+            burst: burst.into(),
+            state: Mutex::new(RateLimiterState {
+                buckets: HashMap::new(),
+                cleaned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Time after which an empty bucket is full again
+    fn refill_time(&self) -> Duration {
+        Duration::from_secs_f64(self.burst / self.rate)
+    }
+
+    /// Takes a token from the bucket of the client at `addr`. Returns `false` when the client has
+    /// exceeded the limit.
+    pub fn acquire(&self, addr: IpAddr) -> bool {
+        self.acquire_at(addr, Instant::now())
+    }
+
+    pub(crate) fn acquire_at(&self, addr: IpAddr, now: Instant) -> bool {
+        let refill_time = self.refill_time();
+        let mut state = self.state.lock().expect("BUG: poisoned rate limiter");
+        // Buckets of idle clients are full so they are the same as missing ones
+        if now.saturating_duration_since(state.cleaned) >= refill_time {
+            state
+                .buckets
+                .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < refill_time);
+            state.cleaned = now;
+        }
+
+        let burst = self.burst;
+        let bucket = state.buckets.entry(addr).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Number of clients tracked by the limiter
+    pub fn client_count(&self) -> usize {
+        self.state
+            .lock()
+            .expect("BUG: poisoned rate limiter")
+            .buckets
+            .len()
+    }
+}
+
+/// Requests of a single TCP client
+#[derive(Clone, Debug)]
+pub struct ClientStatistics {
+    pub addr: IpAddr,
+    pub requests: u64,
+    /// Number of requests responded with an error (including invalid and refused requests)
+    pub errors: u64,
+    /// Command of the last request as sent by the client (e.g. `pools+summary`)
+    pub last_command: Option<String>,
+    pub last_seen: response::Time,
+    /// Order of the last request for eviction of the least recently seen client
+    sequence: u64,
+}
+
+#[derive(Default, Debug)]
+struct ClientRegistry {
+    clients: HashMap<IpAddr, ClientStatistics>,
+    sequence: u64,
+}
+
+/// Counters of connections of the server and requests of a limited number of the most recent
+/// TCP clients. Local clients are counted only in the connection counters.
+#[derive(Debug)]
+pub struct Statistics {
+    total_connections: AtomicU64,
+    active_connections: AtomicUsize,
+    rejected_connections: AtomicU64,
+    read_timeouts: AtomicU64,
+    max_clients: usize,
+    registry: Mutex<ClientRegistry>,
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_CLIENTS)
+    }
+}
+
+impl Statistics {
+    /// Default number of clients whose requests are tracked
+    pub const DEFAULT_MAX_CLIENTS: usize = 32;
+
+    /// Creates statistics tracking requests of at most `max_clients` clients. The least recently
+    /// seen client is forgotten to make room for a new one. No client is tracked when the limit
+    /// is zero.
+    pub fn new(max_clients: usize) -> Self {
+        Self {
+            total_connections: Default::default(),
+            active_connections: Default::default(),
+            rejected_connections: Default::default(),
+            read_timeouts: Default::default(),
+            max_clients,
+            registry: Default::default(),
+        }
+    }
+
+    /// Number of all accepted connections including the rejected ones
+    pub fn total_connections(&self) -> u64 {
+        self.total_connections.load(Ordering::Relaxed)
+    }
+
+    /// Number of connections which are being served right now
+    pub fn active_connections(&self) -> usize {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+
+    /// Number of connections closed because of the connection limit
+    pub fn rejected_connections(&self) -> u64 {
+        self.rejected_connections.load(Ordering::Relaxed)
+    }
+
+    /// Number of clients disconnected because they have not sent a request in time
+    pub fn read_timeouts(&self) -> u64 {
+        self.read_timeouts.load(Ordering::Relaxed)
+    }
+
+    /// Returns statistics of the tracked clients starting with the most recently seen one
+    pub fn clients(&self) -> Vec<ClientStatistics> {
+        let registry = self
+            .registry
+            .lock()
+            .expect("BUG: poisoned client statistics");
+        let mut clients: Vec<_> = registry.clients.values().cloned().collect();
+        clients.sort_by_key(|client| std::cmp::Reverse(client.sequence));
+        clients
+    }
+
+    /// Records a request of the client at `addr` seen at time `when`. The `command` is missing
+    /// when the request is invalid.
+    pub(crate) fn record_request(
+        &self,
+        addr: IpAddr,
+        command: Option<&str>,
+        error: bool,
+        when: response::Time,
+    ) {
+        if self.max_clients == 0 {
+            return;
+        }
+        let mut registry = self
+            .registry
+            .lock()
+            .expect("BUG: poisoned client statistics");
+        registry.sequence += 1;
+        let sequence = registry.sequence;
+        if !registry.clients.contains_key(&addr) && registry.clients.len() >= self.max_clients {
+            let oldest = registry
+                .clients
+                .values()
+                .min_by_key(|client| client.sequence)
+                .map(|client| client.addr);
+            if let Some(oldest) = oldest {
+                registry.clients.remove(&oldest);
+            }
+        }
+        let client = registry
+            .clients
+            .entry(addr)
+            .or_insert_with(|| ClientStatistics {
+                addr,
+                requests: 0,
+                errors: 0,
+                last_command: None,
+                last_seen: when,
+                sequence,
+            });
+        client.requests += 1;
+        if error {
+            client.errors += 1;
+        }
+        if let Some(command) = command {
+            client.last_command = Some(command.to_string());
+        }
+        client.last_seen = when;
+        client.sequence = sequence;
+    }
+}
+
+/// Keeps the connection counted as active in all statistics of the transport until it is dropped
+/// at the end of the connection task
+pub(crate) struct ConnectionGuard(Vec<Arc<Statistics>>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        for statistics in &self.0 {
+            statistics.active_connections.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Statistics and rate limiter applied to all connections of a transport. Both can be shared with
+/// other transports (see `ServerGroup`). The transport always updates its own statistics and
+/// the shared ones are updated as well.
+#[derive(Clone, Default)]
+pub(crate) struct SharedState {
+    statistics: Arc<Statistics>,
+    shared_statistics: Vec<Arc<Statistics>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl SharedState {
+    /// Statistics of the transport itself
+    pub(crate) fn statistics(&self) -> Arc<Statistics> {
+        self.statistics.clone()
+    }
+
+    fn all_statistics(&self) -> impl Iterator<Item = &Arc<Statistics>> {
+        std::iter::once(&self.statistics).chain(&self.shared_statistics)
+    }
+
+    /// Updates the `statistics` too unless they are updated already
+    pub(crate) fn share_statistics(&mut self, statistics: Arc<Statistics>) {
+        if !self
+            .all_statistics()
+            .any(|other| Arc::ptr_eq(other, &statistics))
+        {
+            self.shared_statistics.push(statistics);
+        }
+    }
+
+    pub(crate) fn set_rate_limiter(&mut self, rate_limiter: Arc<RateLimiter>) {
+        self.rate_limiter = Some(rate_limiter);
+    }
+
+    /// Takes the state of a server group (see `Transport::share_state`)
+    pub(crate) fn share(
+        &mut self,
+        statistics: Arc<Statistics>,
+        rate_limiter: Option<Arc<RateLimiter>>,
+    ) {
+        self.share_statistics(statistics);
+        if self.rate_limiter.is_none() {
+            self.rate_limiter = rate_limiter;
+        }
+    }
+
+    /// Counts a new connection. Returns `None` when the transport already serves
+    /// `max_connections` connections.
+    pub(crate) fn acquire_connection(
+        &self,
+        max_connections: Option<usize>,
+    ) -> Option<ConnectionGuard> {
+        let guard = ConnectionGuard(self.all_statistics().cloned().collect());
+        for statistics in &guard.0 {
+            statistics.total_connections.fetch_add(1, Ordering::Relaxed);
+        }
+        let active = self
+            .statistics
+            .active_connections
+            .fetch_add(1, Ordering::SeqCst);
+        for statistics in &self.shared_statistics {
+            statistics.active_connections.fetch_add(1, Ordering::SeqCst);
+        }
+        // The limit applies to the connections of this transport only
+        if matches!(max_connections, Some(max_connections) if active >= max_connections) {
+            for statistics in &guard.0 {
+                statistics
+                    .rejected_connections
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+            return None;
+        }
+        Some(guard)
+    }
+
+    fn count_read_timeout(&self) {
+        debug!("CGMiner API: client has not sent request in time");
+        for statistics in self.all_statistics() {
+            statistics.read_timeouts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Records a request of a remote client (see `Statistics::record_request`)
+    pub(crate) fn record_request<T: When>(
+        &self,
+        context: &command::Context,
+        command: Option<&str>,
+        error: bool,
+    ) {
+        if let Some(addr) = context.peer_addr() {
+            for statistics in self.all_statistics() {
+                statistics.record_request(addr, command, error, T::when());
+            }
+        }
+    }
+
+    /// Handles a complete `request` of the client described by `context`. A request over the rate
+    /// limit is responded with an error instead of being handled. Handling of a read-only request
+    /// is cancelled and `None` is returned when `hangup` resolves first. Commands changing the state
+    /// of the miner are always finished so that they are audited and they do not leave the miner
+    /// half-configured. Every request is recorded in the statistics, the cancelled one as
+    /// successful because it has been cancelled by the client rather than failed. Subscription of
+    /// events is refused unless the transport `sends_events`.
+    pub(crate) async fn handle_request<T, F>(
+        &self,
+        receiver: &command::Receiver<T>,
+        request: command::Request,
+        context: &command::Context,
+        hangup: F,
+        sends_events: bool,
+    ) -> Option<(ResponseType, Option<command::Continuation>)>
+    where
+        T: When,
+        F: Future<Output = ()>,
+    {
+        let command = request.body().map(|body| body.command.clone());
+        let command = command.as_deref();
+        let limited = match (&self.rate_limiter, context.peer_addr()) {
+            (Some(rate_limiter), Some(addr)) => !rate_limiter.acquire(addr),
+            _ => false,
+        };
+        let handled = if limited {
+            (
+                receiver.error_response(response::ErrorCode::RateLimited),
+                None,
+            )
+        } else if receiver.is_read_only(&request) {
+            let handled = receiver.handle_continued(request, context);
+            futures::pin_mut!(handled);
+            futures::pin_mut!(hangup);
+            match future::select(handled, hangup).await {
+                Either::Left((handled, _)) => handled,
+                Either::Right(_) => {
+                    debug!("CGMiner API: client hung up before the response was sent");
+                    self.record_request::<T>(context, command, false);
+                    return None;
+                }
+            }
+        } else {
+            receiver.handle_continued(request, context).await
+        };
+        let handled = if sends_events {
+            handled
+        } else {
+            let (response, action) = receiver.refuse_events(handled);
+            (response, action.map(command::Continuation::Action))
+        };
+        self.record_request::<T>(context, command, handled.0.is_error());
+        Some(handled)
+    }
+}
+
+/// Tracks connections of a started server so that they can be drained on shutdown. Each
+/// connection holds a copy and the server waits until all of them are dropped.
+#[derive(Clone)]
+struct Drain {
+    _sender: mpsc::Sender<()>,
+    /// Resolves when the connections which have not finished in time have to be aborted
+    abort: Shared<futures::channel::oneshot::Receiver<()>>,
+    /// Resolves when the server stops accepting connections
+    stopped: Shared<futures::channel::oneshot::Receiver<()>>,
+}
+
+/// Settings shared by all connections of a server
+#[derive(Clone)]
+struct Settings {
+    max_request_size: usize,
+    read_timeout: Duration,
+    state: SharedState,
+    drain: Option<Drain>,
+    cancel_on_hangup: bool,
+    /// Buffers for encoding responses reused by the connections
+    buffers: Arc<BufferPool>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_request_size: DEFAULT_MAX_REQUEST_SIZE,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            state: Default::default(),
+            drain: None,
+            cancel_on_hangup: false,
+            buffers: Default::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Resolves when the server has been shut down. It never resolves for a server which has
+    /// not been started with a handle.
+    fn stopped(&self) -> impl Future<Output = ()> {
+        let stopped = self.drain.as_ref().map(|drain| drain.stopped.clone());
+        async move {
+            match stopped {
+                // Nothing is ever sent so the receiver resolves once the sender is dropped
+                Some(stopped) => {
+                    let _ = stopped.await;
+                }
+                None => future::pending::<()>().await,
+            }
+        }
+    }
+
+    /// Resolves only when the server has been shut down gracefully and the deadline for
+    /// finishing its connections has passed
+    fn aborted(&self) -> impl Future<Output = ()> {
+        let abort = self.drain.as_ref().map(|drain| drain.abort.clone());
+        async move {
+            let aborted = match abort {
+                Some(abort) => abort.await.is_ok(),
+                None => false,
+            };
+            // The server has been shut down without draining or it is running forever
+            if !aborted {
+                future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+/// Runs the `connection` until it finishes or until it is aborted by shutdown of the server
+async fn run_connection<F>(connection: F, aborted: impl Future<Output = ()>)
+where
+    F: Future<Output = ()>,
+{
+    futures::pin_mut!(connection);
+    futures::pin_mut!(aborted);
+    if let Either::Right(_) = future::select(connection, aborted).await {
+        info!("CGMiner API: connection aborted by shutdown");
+    }
+}
+
+/// Resolves when the client hangs up so that handling of its request can be cancelled (see
+/// `SharedState::handle_request`). Anything sent by the client in the meantime is ignored. End of
+/// the stream is considered a hang-up only when `cancel_on_eof` is set because some clients shut
+/// down the write half after the request.
+async fn hangup<S>(stream: &mut S, cancel_on_eof: bool)
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buf = [0u8; READ_CHUNK_SIZE];
+    loop {
+        match stream.read(&mut buf).await {
+            Ok(0) if cancel_on_eof => break,
+            Ok(0) => future::pending::<()>().await,
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+}
+
+/// Sends the `response` encoded in the `format` while the `stream` is kept open. The response
+/// is encoded in the `buffer` of the connection.
+async fn write_response<S>(
+    stream: &mut S,
+    format: Format,
+    response: ResponseType,
+    buffer: &mut ResponseBuffer,
+) -> io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let encoded = buffer.encode(&response, format)?;
+    stream.write_all(&encoded).await
+}
+
+/// Sends the `response` encoded in the `format` and closes the `stream`
+async fn send_response<S>(
+    stream: &mut S,
+    format: Format,
+    response: ResponseType,
+    buffer: &mut ResponseBuffer,
+) -> io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    write_response(stream, format, response, buffer).await?;
+    let _ = stream.shutdown().await;
+    Ok(())
+}
+
+/// Sends events of the `subscription` to the `stream` until the client disconnects or until
+/// the server is `stopped`. Events which cannot be sent in time because the client does not read
+/// them are dropped by the subscription.
+async fn stream_events<S, T>(
+    stream: &mut S,
+    mut subscription: Subscription,
+    stopped: impl Future<Output = ()>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: When,
+{
+    let (mut reader, mut writer) = tokio::io::split(&mut *stream);
+    // Anything sent by the client is ignored
+    let disconnected = async move {
+        let mut buf = [0u8; READ_CHUNK_SIZE];
+        while let Ok(len) = reader.read(&mut buf).await {
+            if len == 0 {
+                break;
+            }
+        }
+    };
+    let send = async move {
+        while let Some(notification) = subscription.next().await {
+            let mut message = notification.to_message(T::when()).to_string().into_bytes();
+            message.push(0);
+            if let Err(e) = writer.write_all(&message).await {
+                debug!("CGMiner API: cannot send event ({})", e);
+                break;
+            }
+        }
+    };
+    {
+        futures::pin_mut!(disconnected);
+        futures::pin_mut!(send);
+        futures::pin_mut!(stopped);
+        future::select(future::select(disconnected, send), stopped).await;
+    }
+    let _ = stream.shutdown().await;
+}
+
+/// Handles a single request on the `stream` and closes it afterwards
+async fn handle_connection<S, T>(
+    mut stream: S,
+    receiver: Arc<command::Receiver<T>>,
+    settings: Settings,
+    context: command::Context,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: When,
+{
+    let request = match tokio::time::timeout(
+        settings.read_timeout,
+        read_request(&mut stream, settings.max_request_size),
+    )
+    .await
+    {
+        Ok(request) => request,
+        Err(_) => {
+            settings.state.count_read_timeout();
+            return;
+        }
+    };
+    let (format, (response, action)) = match request {
+        Ok(Request::Complete(request, format)) => {
+            let hangup = hangup(&mut stream, settings.cancel_on_hangup);
+            let handled = settings
+                .state
+                .handle_request(&receiver, request, &context, hangup, true)
+                .await;
+            match handled {
+                Some(handled) => (format, handled),
+                None => return,
+            }
+        }
+        Ok(Request::Invalid) => {
+            settings.state.record_request::<T>(&context, None, true);
+            (
+                Format::Json,
+                (
+                    receiver.error_response(response::ErrorCode::InvalidJSON),
+                    None,
+                ),
+            )
+        }
+        // We pretty much ignore I/O errors here
+        Ok(Request::Closed) | Err(_) => return,
+    };
+
+    let mut buffer = settings.buffers.acquire();
+    match action {
+        Some(command::Continuation::Events(subscription)) => {
+            if let Err(e) = write_response(&mut stream, format, response, &mut buffer).await {
+                warn!("CGMiner API: cannot send response ({})", e);
+                return;
+            }
+            stream_events::<_, T>(&mut stream, subscription, settings.stopped()).await;
+        }
+        Some(command::Continuation::Action(action)) => {
+            if let Err(e) = send_response(&mut stream, format, response, &mut buffer).await {
+                warn!("CGMiner API: cannot send response ({})", e);
+                return;
+            }
+            // The response has been flushed so the action cannot prevent the client from
+            // receiving it
+            action.await;
+        }
+        None => {
+            if let Err(e) = send_response(&mut stream, format, response, &mut buffer).await {
+                warn!("CGMiner API: cannot send response ({})", e);
+            }
+        }
+    }
+}
+
+/// Determines whether the client has to be disconnected because it is not allowed to access
+/// the API at all
+pub(crate) fn is_refused<T: When>(
+    receiver: &command::Receiver<T>,
+    context: &command::Context,
+) -> bool {
+    let refused = receiver.privilege(context).is_none();
+    if let Some(addr) = context.peer_addr().filter(|_| refused) {
+        info!("CGMiner API: connection from {} refused", addr);
+    }
+    refused
+}
+
+/// Waits for connections of a server which is being shut down
+struct DrainHandle {
+    receiver: mpsc::Receiver<()>,
+    abort_sender: futures::channel::oneshot::Sender<()>,
+}
+
+/// Handle of a started server
+pub struct Handle {
+    shutdown_sender: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+    drain: Option<DrainHandle>,
+}
+
+impl Handle {
+    /// Spawns a task running future returned by `serve` which has to finish once the provided
+    /// shutdown receiver resolves
+    pub(crate) fn spawn<F, U>(serve: F) -> Self
+    where
+        F: FnOnce(oneshot::Receiver<()>) -> U,
+        U: Future<Output = ()> + Send + 'static,
+    {
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel();
+        Self {
+            shutdown_sender,
+            task: tokio::spawn(serve(shutdown_receiver)),
+            drain: None,
+        }
+    }
+
+    fn with_drain(self, drain: DrainHandle) -> Self {
+        Self {
+            drain: Some(drain),
+            ..self
+        }
+    }
+
+    /// Stops accepting new connections and waits until the listening socket is closed. Requests
+    /// which are already being processed are finished in the background.
+    pub async fn shutdown(self) {
+        // The server task may have already finished
+        let _ = self.shutdown_sender.send(());
+        let _ = self.task.await;
+    }
+
+    /// Stops accepting new connections and waits until requests which are already being
+    /// processed are finished. Connections still open after the `deadline` are closed without
+    /// any response. Returns `false` when some connection had to be closed.
+    pub async fn shutdown_graceful(self, deadline: Duration) -> bool {
+        let _ = self.shutdown_sender.send(());
+        let _ = self.task.await;
+        let mut drain = match self.drain {
+            Some(drain) => drain,
+            None => return true,
+        };
+        // Nothing is ever sent so the channel is closed once all connections are dropped
+        match tokio::time::timeout(deadline, drain.receiver.recv()).await {
+            Ok(_) => true,
+            Err(_) => {
+                let _ = drain.abort_sender.send(());
+                false
+            }
+        }
+    }
+}
+
+/// Unix domain socket whose file is removed when the listener is closed
+struct UnixSocket {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!(
+                "CGMiner API: cannot remove socket file '{}' ({})",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Certificate chain and private key identifying the server to TLS clients
+#[cfg(feature = "tls")]
+pub struct TlsIdentity {
+    certificates: Vec<rustls::Certificate>,
+    key: rustls::PrivateKey,
+}
+
+#[cfg(feature = "tls")]
+impl TlsIdentity {
+    /// Loads PEM encoded certificate chain and PKCS#8 or RSA private key
+    pub fn from_pem(certificates: &[u8], key: &[u8]) -> io::Result<Self> {
+        use rustls::internal::pemfile;
+
+        let invalid = |what| io::Error::new(io::ErrorKind::InvalidData, what);
+        let certificates =
+            pemfile::certs(&mut &certificates[..]).map_err(|_| invalid("invalid certificate"))?;
+        if certificates.is_empty() {
+            return Err(invalid("missing certificate"));
+        }
+        let mut keys = pemfile::pkcs8_private_keys(&mut &key[..])
+            .map_err(|_| invalid("invalid private key"))?;
+        if keys.is_empty() {
+            keys = pemfile::rsa_private_keys(&mut &key[..])
+                .map_err(|_| invalid("invalid private key"))?;
+        }
+        let key = keys
+            .into_iter()
+            .next()
+            .ok_or_else(|| invalid("missing private key"))?;
+
+        Ok(Self { certificates, key })
+    }
+
+    fn into_acceptor(self) -> io::Result<TlsAcceptor> {
+        let mut config = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+        config
+            .set_single_cert(self.certificates, self.key)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(Arc::new(config).into())
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixSocket),
+    #[cfg(feature = "tls")]
+    Tls {
+        listener: TcpListener,
+        acceptor: TlsAcceptor,
+    },
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+    /// Connection which has yet to perform TLS handshake
+    #[cfg(feature = "tls")]
+    Tls(TcpStream, TlsAcceptor),
+}
+
+/// Listening socket of a transport
+#[async_trait::async_trait]
+pub(crate) trait Accept: Send {
+    type Stream: Send;
+
+    /// Returns accepted connection together with the context of the client
+    async fn accept(&mut self) -> io::Result<(Self::Stream, command::Context)>;
+}
+
+#[async_trait::async_trait]
+impl Accept for TcpListener {
+    type Stream = TcpStream;
+
+    async fn accept(&mut self) -> io::Result<(TcpStream, command::Context)> {
+        TcpListener::accept(self)
+            .await
+            .map(|(stream, addr)| (stream, command::Context::new(addr.ip())))
+    }
+}
+
+#[async_trait::async_trait]
+impl Accept for Listener {
+    type Stream = Stream;
+
+    async fn accept(&mut self) -> io::Result<(Stream, command::Context)> {
+        match self {
+            Listener::Tcp(listener) => Accept::accept(listener)
+                .await
+                .map(|(stream, context)| (Stream::Tcp(stream), context)),
+            Listener::Unix(socket) => socket
+                .listener
+                .accept()
+                .await
+                .map(|(stream, _)| (Stream::Unix(stream), command::Context::local())),
+            #[cfg(feature = "tls")]
+            Listener::Tls { listener, acceptor } => Accept::accept(listener)
+                .await
+                .map(|(stream, context)| (Stream::Tls(stream, acceptor.clone()), context)),
+        }
+    }
+}
+
+/// Accept loop shared by all transports. Waits for the next connection of a client which is not
+/// refused by the `receiver`. Connections of refused clients are closed right away by dropping
+/// them. Returns `None` once the `shutdown` future resolves.
+pub(crate) async fn next_connection<L, T, F>(
+    listener: &mut L,
+    receiver: &command::Receiver<T>,
+    mut shutdown: Pin<&mut F>,
+) -> Option<(L::Stream, command::Context)>
+where
+    L: Accept,
+    T: When,
+    F: Future<Output = ()>,
+{
+    loop {
+        let accepted = {
+            let accept = listener.accept();
+            futures::pin_mut!(accept);
+            match future::select(shutdown.as_mut(), accept).await {
+                Either::Left(_) => return None,
+                Either::Right((accepted, _)) => accepted,
+            }
+        };
+        match accepted {
+            Ok((_, context)) if is_refused(receiver, &context) => {}
+            Ok(connection) => return Some(connection),
+            Err(e) => warn!("CGMiner API: cannot accept connection ({})", e),
+        }
+    }
+}
+
+/// Server of CGMiner API listening either on TCP or on Unix domain socket. The same `Receiver`
+/// can be shared by multiple servers.
+pub struct Server<T = UnixTime> {
+    listener: Listener,
+    receiver: Arc<command::Receiver<T>>,
+    settings: Settings,
+    max_connections: Option<usize>,
+    notify_rejected: bool,
+}
+
+impl<T> Server<T>
+where
+    T: When + 'static,
+{
+    fn new<R>(listener: Listener, receiver: R) -> Self
+    where
+        R: Into<Arc<command::Receiver<T>>>,
+    {
+        Self {
+            listener,
+            receiver: receiver.into(),
+            settings: Default::default(),
+            max_connections: None,
+            notify_rejected: false,
+        }
+    }
+
+    /// Binds a listening socket to `addr`. No connection is accepted until the server is run or
+    /// started.
+    pub async fn bind<R>(addr: SocketAddr, receiver: R) -> io::Result<Self>
+    where
+        R: Into<Arc<command::Receiver<T>>>,
+    {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(Self::new(Listener::Tcp(listener), receiver))
+    }
+
+    /// Binds a listening socket to `addr` encrypting all connections with TLS. Clients failing
+    /// the handshake are disconnected without affecting the other ones.
+    #[cfg(feature = "tls")]
+    pub async fn bind_tls<R>(
+        addr: SocketAddr,
+        receiver: R,
+        identity: TlsIdentity,
+    ) -> io::Result<Self>
+    where
+        R: Into<Arc<command::Receiver<T>>>,
+    {
+        let acceptor = identity.into_acceptor()?;
+        let listener = TcpListener::bind(addr).await?;
+        Ok(Self::new(Listener::Tls { listener, acceptor }, receiver))
+    }
+
+    /// Binds a Unix domain socket to `path`. A stale socket file left behind by a previous
+    /// instance is replaced while a socket which still accepts connections is not. The socket
+    /// file is removed when the server is shut down.
+    pub fn bind_unix<P, R>(path: P, receiver: R) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        R: Into<Arc<command::Receiver<T>>>,
+    {
+        Self::bind_unix_socket(path.as_ref(), None, receiver)
+    }
+
+    /// Binds a Unix domain socket to `path` like `bind_unix` with permission `mode` (e.g. `0o660`)
+    /// of the socket file to restrict local clients allowed to connect to the API. The socket
+    /// appears at `path` only after the mode has been set.
+    pub fn bind_unix_with_mode<P, R>(path: P, mode: u32, receiver: R) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        R: Into<Arc<command::Receiver<T>>>,
+    {
+        Self::bind_unix_socket(path.as_ref(), Some(mode), receiver)
+    }
+
+    fn bind_unix_socket<R>(path: &Path, mode: Option<u32>, receiver: R) -> io::Result<Self>
+    where
+        R: Into<Arc<command::Receiver<T>>>,
+    {
+        Self::remove_stale_socket(path)?;
+        let listener = match mode {
+            Some(mode) => UnixListener::from_std(Self::bind_restricted(path, mode)?)?,
+            None => UnixListener::bind(path)?,
+        };
+        let socket = UnixSocket {
+            listener,
+            path: path.to_path_buf(),
+        };
+        Ok(Self::new(Listener::Unix(socket), receiver))
+    }
+
+    /// Binds the socket in a private directory next to `path` and moves it to `path` once its
+    /// permissions have been set. Changing the mode after binding at `path` would leave the socket
+    /// open to any local client in the meantime.
+    fn bind_restricted(path: &Path, mode: u32) -> io::Result<net::UnixListener> {
+        use std::os::unix::fs::DirBuilderExt as _;
+
+        let file_name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "missing socket file name")
+        })?;
+        let private_dir = path.with_file_name(format!(
+            ".{}.{}",
+            file_name.to_string_lossy(),
+            std::process::id()
+        ));
+        fs::DirBuilder::new().mode(0o700).create(&private_dir)?;
+        let private_path = private_dir.join(file_name);
+
+        let result = net::UnixListener::bind(&private_path).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            fs::set_permissions(&private_path, fs::Permissions::from_mode(mode))?;
+            fs::rename(&private_path, path)?;
+            Ok(listener)
+        });
+        // The socket file is still in the directory when it couldn't be moved to `path`
+        let _ = fs::remove_file(&private_path);
+        fs::remove_dir(&private_dir)?;
+        result
+    }
+
+    fn remove_stale_socket(path: &Path) -> io::Result<()> {
+        use std::os::unix::fs::FileTypeExt as _;
+
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => {
+                match net::UnixStream::connect(path) {
+                    Ok(_) => Err(io::Error::new(
+                        io::ErrorKind::AddrInUse,
+                        format!("socket '{}' is already in use", path.display()),
+                    )),
+                    Err(_) => fs::remove_file(path),
+                }
+            }
+            // Binding fails on any other existing file
+            _ => Ok(()),
+        }
+    }
+
+    /// Limits the size of a request. The client receives invalid JSON error when the limit is
+    /// exceeded before a complete request has been received.
+    pub fn max_request_size(mut self, max_request_size: usize) -> Self {
+        self.settings.max_request_size = max_request_size;
+        self
+    }
+
+    /// Limits the time for receiving a complete request. The client is disconnected without any
+    /// response when the limit is exceeded.
+    pub fn read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.settings.read_timeout = read_timeout;
+        self
+    }
+
+    /// Limits the number of connections served at the same time. Any connection over the limit
+    /// is closed right after it has been accepted.
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Sends an error response to clients rejected because of the connection limit instead of
+    /// closing the connection silently. TLS clients are always disconnected without response.
+    pub fn notify_rejected(mut self) -> Self {
+        self.notify_rejected = true;
+        self
+    }
+
+    /// Cancels handling of a read-only request also when the client closes the connection before
+    /// the response has been sent. Clients which shut down just the write half after sending the
+    /// request (e.g. `nc -N`) do not get any response then.
+    pub fn cancel_on_hangup(mut self) -> Self {
+        self.settings.cancel_on_hangup = true;
+        self
+    }
+
+    /// Returns counters of the server which are updated while it is running
+    pub fn statistics(&self) -> Arc<Statistics> {
+        self.settings.state.statistics()
+    }
+
+    /// Updates the `statistics` besides the server's own ones. The statistics can be shared
+    /// by multiple servers and passed to `command::Receiver::with_server_statistics` so that
+    /// they are reported by the `connections` command. Statistics of a `ServerGroup` are added
+    /// to them when the server is started in the group.
+    pub fn share_statistics(mut self, statistics: Arc<Statistics>) -> Self {
+        self.settings.state.share_statistics(statistics);
+        self
+    }
+
+    /// Limits the rate of requests of TCP clients. The `rate_limiter` can be shared by multiple
+    /// servers so that the limit applies to all their connections.
+    pub fn rate_limiter<L>(mut self, rate_limiter: L) -> Self
+    where
+        L: Into<Arc<RateLimiter>>,
+    {
+        self.settings.state.set_rate_limiter(rate_limiter.into());
+        self
+    }
+
+    /// Returns local address of TCP listening socket
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match &self.listener {
+            Listener::Tcp(listener) => listener.local_addr(),
+            #[cfg(feature = "tls")]
+            Listener::Tls { listener, .. } => listener.local_addr(),
+            Listener::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "server is not listening on TCP socket",
+            )),
+        }
+    }
+
+    /// Serves all incoming connections forever
+    pub async fn run(self) {
+        self.run_until(future::pending()).await
+    }
+
+    /// Spawns a task serving all incoming connections until the returned handle is used for
+    /// shutting the server down
+    pub fn start(mut self) -> Handle {
+        let (sender, receiver) = mpsc::channel(1);
+        let (abort_sender, abort_receiver) = futures::channel::oneshot::channel();
+        let (stopped_sender, stopped_receiver) = futures::channel::oneshot::channel::<()>();
+        self.settings.drain = Some(Drain {
+            _sender: sender,
+            abort: abort_receiver.shared(),
+            stopped: stopped_receiver.shared(),
+        });
+        Handle::spawn(|shutdown_receiver| async move {
+            self.run_until(async {
+                let _ = shutdown_receiver.await;
+            })
+            .await;
+            drop(stopped_sender);
+        })
+        .with_drain(DrainHandle {
+            receiver,
+            abort_sender,
+        })
+    }
+
+    fn spawn_connection<S>(&self, stream: S, context: command::Context, guard: ConnectionGuard)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let connection = handle_connection(
+            stream,
+            self.receiver.clone(),
+            self.settings.clone(),
+            context,
+        );
+        let aborted = self.settings.aborted();
+        tokio::spawn(async move {
+            run_connection(connection, aborted).await;
+            drop(guard);
+        });
+    }
+
+    /// The handshake runs in the connection task so that a slow client cannot block the accept
+    /// loop
+    #[cfg(feature = "tls")]
+    fn spawn_tls_connection(
+        &self,
+        stream: TcpStream,
+        acceptor: TlsAcceptor,
+        context: command::Context,
+        guard: ConnectionGuard,
+    ) {
+        let receiver = self.receiver.clone();
+        let settings = self.settings.clone();
+        let aborted = self.settings.aborted();
+        let connection = async move {
+            // The handshake is a part of receiving the request
+            match tokio::time::timeout(settings.read_timeout, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => handle_connection(stream, receiver, settings, context).await,
+                Ok(Err(e)) => warn!("CGMiner API: TLS handshake failed ({})", e),
+                Err(_) => settings.state.count_read_timeout(),
+            }
+        };
+        tokio::spawn(async move {
+            run_connection(connection, aborted).await;
+            drop(guard);
+        });
+    }
+
+    /// Closes a connection over the connection limit. The client is told the reason only when
+    /// requested (see `notify_rejected`).
+    fn reject(&self, stream: Stream) {
+        info!("CGMiner API: connection limit reached");
+        if !self.notify_rejected {
+            return;
+        }
+        let response = self
+            .receiver
+            .error_response(response::ErrorCode::TooManyConnections);
+        let mut buffer = self.settings.buffers.acquire();
+        match stream {
+            Stream::Tcp(mut stream) => {
+                tokio::spawn(async move {
+                    let _ = send_response(&mut stream, Format::Json, response, &mut buffer).await;
+                });
+            }
+            Stream::Unix(mut stream) => {
+                tokio::spawn(async move {
+                    let _ = send_response(&mut stream, Format::Json, response, &mut buffer).await;
+                });
+            }
+            // Nothing can be sent before the handshake
+            #[cfg(feature = "tls")]
+            Stream::Tls(..) => {}
+        }
+    }
+
+    /// The listening socket is closed (and possibly removed) when the server is dropped at the end
+    async fn run_until<F>(mut self, shutdown: F)
+    where
+        F: Future<Output = ()>,
+    {
+        futures::pin_mut!(shutdown);
+        while let Some((stream, context)) =
+            next_connection(&mut self.listener, &self.receiver, shutdown.as_mut()).await
+        {
+            let guard = match self.settings.state.acquire_connection(self.max_connections) {
+                Some(guard) => guard,
+                None => {
+                    self.reject(stream);
+                    continue;
+                }
+            };
+            // Each client is served concurrently
+            match stream {
+                Stream::Tcp(stream) => self.spawn_connection(stream, context, guard),
+                Stream::Unix(stream) => self.spawn_connection(stream, context, guard),
+                #[cfg(feature = "tls")]
+                Stream::Tls(stream, acceptor) => {
+                    self.spawn_tls_connection(stream, acceptor, context, guard)
+                }
+            }
+        }
+    }
+}
+
+/// Front-end serving requests of a receiver over a particular transport such as TCP, Unix domain
+/// socket or WebSocket (see `ServerGroup`)
+pub trait Transport<T>: Send + 'static {
+    /// Returns the receiver handling requests of the transport
+    fn receiver(&self) -> &Arc<command::Receiver<T>>;
+
+    /// Makes the transport update the shared `statistics` as well. The shared `rate_limiter`
+    /// applies only when the transport has no rate limiter of its own.
+    fn share_state(&mut self, statistics: Arc<Statistics>, rate_limiter: Option<Arc<RateLimiter>>);
+
+    /// Spawns a task serving the transport (see `Server::start`)
+    fn start(self: Box<Self>) -> Handle;
+}
+
+impl<T> Transport<T> for Server<T>
+where
+    T: When + 'static,
+{
+    fn receiver(&self) -> &Arc<command::Receiver<T>> {
+        &self.receiver
+    }
+
+    fn share_state(&mut self, statistics: Arc<Statistics>, rate_limiter: Option<Arc<RateLimiter>>) {
+        self.settings.state.share(statistics, rate_limiter);
+    }
+
+    fn start(self: Box<Self>) -> Handle {
+        Server::start(*self)
+    }
+}
+
+/// Transports serving one receiver which share the connection statistics and the rate limiter
+/// and which are started and shut down together. Each transport keeps its own settings such as
+/// the request size or connection limits and TLS.
+pub struct ServerGroup<T = UnixTime> {
+    receiver: Arc<command::Receiver<T>>,
+    statistics: Arc<Statistics>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    transports: Vec<Box<dyn Transport<T>>>,
+}
+
+impl<T> ServerGroup<T>
+where
+    T: When + 'static,
+{
+    pub fn new<R>(receiver: R) -> Self
+    where
+        R: Into<Arc<command::Receiver<T>>>,
+    {
+        Self {
+            receiver: receiver.into(),
+            statistics: Default::default(),
+            rate_limiter: None,
+            transports: vec![],
+        }
+    }
+
+    /// Returns the receiver which the transports of the group have to be bound with
+    pub fn receiver(&self) -> Arc<command::Receiver<T>> {
+        self.receiver.clone()
+    }
+
+    /// Returns counters of all transports of the group
+    pub fn statistics(&self) -> Arc<Statistics> {
+        self.statistics.clone()
+    }
+
+    /// Updates the `statistics` instead of the group's own ones (see `Server::share_statistics`)
+    pub fn share_statistics(mut self, statistics: Arc<Statistics>) -> Self {
+        self.statistics = statistics;
+        self
+    }
+
+    /// Limits the rate of requests of TCP clients over all transports of the group together
+    pub fn rate_limiter<L>(mut self, rate_limiter: L) -> Self
+    where
+        L: Into<Arc<RateLimiter>>,
+    {
+        self.rate_limiter = Some(rate_limiter.into());
+        self
+    }
+
+    /// Adds a configured `transport`. The shared state is passed to the transport when the group
+    /// is started.
+    ///
+    /// # Panics
+    ///
+    /// The `transport` has to be bound with the receiver of the group (see `receiver`).
+    pub fn transport<S>(mut self, transport: S) -> Self
+    where
+        S: Transport<T>,
+    {
+        assert!(
+            Arc::ptr_eq(transport.receiver(), &self.receiver),
+            "BUG: transport does not serve the receiver of the server group"
+        );
+        self.transports.push(Box::new(transport));
+        self
+    }
+
+    /// Starts all transports of the group
+    pub fn start(self) -> GroupHandle {
+        let statistics = self.statistics;
+        let rate_limiter = self.rate_limiter;
+        let handles = self
+            .transports
+            .into_iter()
+            .map(|mut transport| {
+                transport.share_state(statistics.clone(), rate_limiter.clone());
+                transport.start()
+            })
+            .collect();
+        GroupHandle { handles }
+    }
+}
+
+/// Handle of a started server group shutting down all its transports at once
+pub struct GroupHandle {
+    handles: Vec<Handle>,
+}
+
+impl GroupHandle {
+    /// Shuts down all transports (see `Handle::shutdown`)
+    pub async fn shutdown(self) {
+        future::join_all(self.handles.into_iter().map(Handle::shutdown)).await;
+    }
+
+    /// Shuts down all transports gracefully with the same `deadline` (see
+    /// `Handle::shutdown_graceful`). Returns `false` when a connection of any transport had to be
+    /// closed.
+    pub async fn shutdown_graceful(self, deadline: Duration) -> bool {
+        future::join_all(
+            self.handles
+                .into_iter()
+                .map(|handle| handle.shutdown_graceful(deadline)),
+        )
+        .await
+        .into_iter()
+        .all(|finished| finished)
+    }
+}
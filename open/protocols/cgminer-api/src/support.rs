@@ -22,26 +22,69 @@
 
 //! Defines support structures for API responses serialization
 
+use crate::parameters::{self, FromParameter as _, ParameterError};
 use crate::response;
+use crate::{text, Format};
+
+use bytes::buf::BufMutExt as _;
+use bytes::{BufMut as _, Bytes, BytesMut};
+use ii_async_compat::bytes;
 
 use serde::{Serialize, Serializer};
 use serde_json as json;
 
-use std::collections::HashMap;
-use std::time::SystemTime;
+use std::fmt;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
+/// Clock providing the `When` timestamp of responses
 pub trait When: Send + Sync {
     fn when() -> response::Time;
+
+    /// Timestamp in milliseconds used when the receiver reports sub-second precision
+    fn when_ms() -> u64 {
+        u64::from(Self::when()) * 1000
+    }
 }
 
 pub struct UnixTime;
 
-impl When for UnixTime {
-    fn when() -> response::Time {
+impl UnixTime {
+    fn since_epoch() -> Duration {
         SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
-            .map(|duration| duration.as_secs() as u32)
-            .unwrap_or(0)
+            .unwrap_or_default()
+    }
+}
+
+impl When for UnixTime {
+    fn when() -> response::Time {
+        Self::since_epoch().as_secs() as u32
+    }
+
+    fn when_ms() -> u64 {
+        Self::since_epoch().as_millis() as u64
+    }
+}
+
+/// Clock which always reports the same time `FixedTime::WHEN_MS`. It makes responses
+/// deterministic in tests.
+pub struct FixedTime;
+
+impl FixedTime {
+    /// 2020-01-01 00:00:00.250 UTC
+    pub const WHEN_MS: u64 = 1_577_836_800_250;
+}
+
+impl When for FixedTime {
+    fn when() -> response::Time {
+        (Self::WHEN_MS / 1000) as response::Time
+    }
+
+    fn when_ms() -> u64 {
+        Self::WHEN_MS
     }
 }
 
@@ -51,35 +94,161 @@ pub trait ValueExt {
     fn is_i32(&self) -> bool {
         self.to_i32().is_some()
     }
+
+    /// Checked conversions fail with error describing the expected value instead of
+    /// truncating or rounding it
+    fn as_checked_i32(&self) -> Result<i32, ParameterError>;
+    fn as_checked_u32(&self) -> Result<u32, ParameterError>;
+    fn as_checked_u64(&self) -> Result<u64, ParameterError>;
+    fn as_checked_f64(&self) -> Result<f64, ParameterError>;
 }
 
-/// Support CGMiner specific type conversions
+/// Support CGMiner specific type conversions. Integers are accepted also as numeric strings
+/// because clients often send all parameters as strings.
 impl ValueExt for json::Value {
     fn to_i32(&self) -> Option<i32> {
-        match self {
-            json::Value::Number(value) if value.is_u64() => {
-                let number = value.as_u64().expect("BUG: cannot convert json number");
-                Some(number as i32)
-            }
-            json::Value::Number(value) if value.is_i64() => {
-                let number = value.as_i64().expect("BUG: cannot convert json number");
-                Some(number as i32)
-            }
-            json::Value::Number(value) if value.is_f64() => {
-                let number = value.as_f64().expect("BUG: cannot convert json number");
-                Some(number as i32)
-            }
-            json::Value::String(value) => value.parse().ok(),
-            // TODO: cgminer tries to parse all possible types if they contains some number
-            _ => None,
+        self.as_checked_i32().ok()
+    }
+
+    // Keep the conversions consistent with the parameter checks of all commands
+    fn as_checked_i32(&self) -> Result<i32, ParameterError> {
+        parameters::extract(Some(self))
+    }
+
+    fn as_checked_u32(&self) -> Result<u32, ParameterError> {
+        parameters::extract(Some(self))
+    }
+
+    fn as_checked_u64(&self) -> Result<u64, ParameterError> {
+        parameters::extract(Some(self))
+    }
+
+    fn as_checked_f64(&self) -> Result<f64, ParameterError> {
+        parameters::extract(Some(self))
+    }
+}
+
+/// Restricts result of a checked conversion (see `ValueExt`) to a range
+pub trait RangeExt<T> {
+    /// Fails when the value is not in the inclusive range `min..=max`
+    fn in_range(self, min: T, max: T) -> Result<T, ParameterError>;
+}
+
+impl<T> RangeExt<T> for Result<T, ParameterError>
+where
+    T: PartialOrd + fmt::Display,
+{
+    fn in_range(self, min: T, max: T) -> Result<T, ParameterError> {
+        let value = self?;
+        if value < min || value > max {
+            return Err(ParameterError {
+                expected: format!("value from {} to {}", min, max),
+                got: Some(value.to_string()),
+            });
         }
+        Ok(value)
     }
 }
 
+/// Parses pool index parameter used by all pool commands. Missing parameter is reported as
+/// CGMiner does and malformed one as `ErrorCode::InvalidParameter` (see `parameters::extract`).
+pub fn parse_pool_id(parameter: Option<&json::Value>) -> Result<i32, response::Error> {
+    match parameter {
+        Some(value) => value.as_checked_i32().map_err(Into::into),
+        None => Err(response::ErrorCode::MissingPoolParameter.into()),
+    }
+}
+
+/// Parses device index parameter used by all ASC commands (see `parse_pool_id`)
+pub fn parse_asc_id(parameter: Option<&json::Value>) -> Result<i32, response::Error> {
+    match parameter {
+        Some(value) => value.as_checked_i32().map_err(Into::into),
+        None => Err(response::ErrorCode::MissingAscParameter.into()),
+    }
+}
+
+/// Parses ASC index of commands which optionally restrict the response to a single device
+pub fn parse_optional_asc_id(
+    parameter: Option<&json::Value>,
+) -> Result<Option<i32>, response::Error> {
+    parameters::extract(parameter).map_err(Into::into)
+}
+
+/// Index of ASC device selecting all devices
+pub const ALL_ASCS: i32 = -1;
+
+/// Parses parameter of `asc` command. Missing parameter or `ALL_ASCS` selects all devices.
+pub fn parse_asc_selection(
+    parameter: Option<&json::Value>,
+) -> Result<Option<i32>, response::Error> {
+    parse_optional_asc_id(parameter).map(|idx| idx.filter(|&idx| idx != ALL_ASCS))
+}
+
+/// Selection of sections of `stats` and `estats` responses
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum StatsFilter {
+    /// Statistics of ASC device N only
+    Asc(i32),
+    /// Statistics of all ASC devices
+    Ascs,
+    /// Statistics of all pools
+    Pools,
+}
+
+impl StatsFilter {
+    const ASCS: &'static str = "asc";
+    const POOLS: &'static str = "pool";
+}
+
+/// Parses optional parameter of `stats` and `estats` commands which is either ASC index (with
+/// the same semantics as in `devs`) or a name of sections `asc|pool`. Missing or empty parameter
+/// selects all sections as the commands used to ignore their parameter.
+pub fn parse_stats_filter(
+    parameter: Option<&json::Value>,
+) -> Result<Option<StatsFilter>, response::Error> {
+    if let Some(json::Value::String(name)) = parameter {
+        match name.trim().to_lowercase().as_str() {
+            "" => return Ok(None),
+            StatsFilter::ASCS => return Ok(Some(StatsFilter::Ascs)),
+            StatsFilter::POOLS => return Ok(Some(StatsFilter::Pools)),
+            _ => {}
+        }
+    }
+    parse_optional_asc_id(parameter).map(|idx| idx.map(StatsFilter::Asc))
+}
+
+/// Parses comma separated list of pool indices for the poolpriority command. Pools are listed
+/// from the highest priority and each pool can be specified only once.
+pub fn parse_pool_priority(parameter: Option<&json::Value>) -> Result<Vec<usize>, response::Error> {
+    let list = match parameter {
+        Some(json::Value::String(list)) if !list.is_empty() => list,
+        Some(json::Value::Number(idx)) => {
+            return idx
+                .as_u64()
+                .map(|idx| vec![idx as usize])
+                .ok_or_else(|| response::ErrorCode::InvalidPoolPriority(idx.to_string()).into())
+        }
+        _ => return Err(response::ErrorCode::MissingPoolParameter.into()),
+    };
+
+    let mut order = Vec::new();
+    for item in list.split(',') {
+        let idx: usize = item
+            .trim()
+            .parse()
+            .map_err(|_| response::ErrorCode::InvalidPoolPriority(list.clone()))?;
+        if order.contains(&idx) {
+            Err(response::ErrorCode::DuplicatePoolId(idx))?;
+        }
+        order.push(idx);
+    }
+    Ok(order)
+}
+
 #[derive(Debug)]
 pub struct SingleResponse {
     pub status_info: response::StatusInfo,
-    pub body: Option<(&'static str, json::Value)>,
+    pub body: Option<(&'static str, response::Sections)>,
 }
 
 impl Serialize for SingleResponse {
@@ -98,24 +267,58 @@ impl Serialize for SingleResponse {
     }
 }
 
-/// Container for a multi-response
-#[derive(Serialize, Debug)]
+/// Container for a multi-response which keeps responses in the order of commands
+#[derive(Debug)]
 pub struct MultiResponse {
-    #[serde(flatten)]
-    responses: HashMap<String, Vec<SingleResponse>>,
+    pub(crate) responses: Vec<(String, Vec<SingleResponse>)>,
     id: usize,
 }
 
 impl MultiResponse {
     pub fn new() -> Self {
         Self {
-            responses: HashMap::new(),
+            responses: Vec::new(),
             id: 1,
         }
     }
 
+    /// Response of a command which is present multiple times replaces the previous one while
+    /// the position of the first one is kept
     pub fn add_response(&mut self, name: &str, response: SingleResponse) {
-        self.responses.insert(name.to_string(), vec![response]);
+        match self.responses.iter_mut().find(|(other, _)| other == name) {
+            Some((_, responses)) => *responses = vec![response],
+            None => self.responses.push((name.to_string(), vec![response])),
+        }
+    }
+}
+
+impl Serialize for MultiResponse {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(self.responses.len() + 1))?;
+        for (name, responses) in &self.responses {
+            map.serialize_entry(name, responses)?;
+        }
+        map.serialize_entry("id", &self.id)?;
+        map.end()
+    }
+}
+
+/// Bare acknowledgement of an action (e.g. `quit`) which is sent instead of the status
+/// information
+#[derive(Serialize, Debug)]
+pub struct ActionResponse {
+    #[serde(rename = "STATUS")]
+    pub(crate) status: &'static str,
+    id: usize,
+}
+
+impl ActionResponse {
+    pub fn new(status: &'static str) -> Self {
+        Self { status, id: 1 }
     }
 }
 
@@ -126,4 +329,713 @@ impl MultiResponse {
 pub enum ResponseType {
     Single(SingleResponse),
     Multi(MultiResponse),
+    Action(ActionResponse),
+}
+
+impl ResponseType {
+    /// Returns `true` when the response or any response of a batched request is an error
+    pub fn is_error(&self) -> bool {
+        let is_error =
+            |response: &SingleResponse| response.status_info.status == response::Status::E;
+        match self {
+            ResponseType::Single(response) => is_error(response),
+            ResponseType::Multi(responses) => responses
+                .responses
+                .iter()
+                .flat_map(|(_, responses)| responses)
+                .any(is_error),
+            ResponseType::Action(_) => false,
+        }
+    }
+
+    /// Writes the response encoded as JSON into the `writer`
+    pub fn encode_json<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        Ok(json::to_writer(writer, self)?)
+    }
+
+    /// Appends the response encoded in the `format` including the terminating NUL byte to `dst`
+    pub fn encode(&self, format: Format, dst: &mut BytesMut) -> io::Result<()> {
+        match format {
+            Format::Json => self.encode_json(dst.writer())?,
+            Format::Text => dst.extend_from_slice(text::encode_response(self).as_bytes()),
+        }
+        // original CGMiner API returns null terminated string in both formats
+        dst.put_u8(0);
+        Ok(())
+    }
+}
+
+/// Buffer for encoding responses which keeps its allocation between the responses. The encoded
+/// response is handed out as `Bytes` and its memory is reclaimed by the following responses once
+/// the `Bytes` have been dropped.
+#[derive(Debug, Default)]
+pub struct ResponseBuffer {
+    buf: BytesMut,
+}
+
+impl ResponseBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: BytesMut::with_capacity(capacity),
+        }
+    }
+
+    /// Allocated capacity which is available for the next response
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Encodes the whole `response` in the `format` including the terminating NUL byte
+    pub fn encode(&mut self, response: &ResponseType, format: Format) -> io::Result<Bytes> {
+        self.buf.clear();
+        let result = response.encode(format, &mut self.buf);
+        let encoded = if self.buf.len() > MAX_POOLED_BUFFER_CAPACITY {
+            // Memory of a huge response is released together with the encoded response
+            std::mem::take(&mut self.buf).freeze()
+        } else {
+            self.buf.split().freeze()
+        };
+        result.map(|_| encoded)
+    }
+}
+
+/// Default maximum number of idle buffers kept by the `BufferPool`
+pub const DEFAULT_POOLED_BUFFERS: usize = 16;
+/// Capacity of buffers allocated by the `BufferPool`
+pub const POOLED_BUFFER_CAPACITY: usize = 16 * 1024;
+/// Buffers which have grown above this size (e.g. by a huge `estats` response) are dropped
+/// instead of being returned to the pool
+pub const MAX_POOLED_BUFFER_CAPACITY: usize = 4 * POOLED_BUFFER_CAPACITY;
+
+/// Pool of response buffers shared by the connections of a server. Each connection acquires its
+/// buffer for its whole lifetime and returns it when the connection is closed.
+#[derive(Debug)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<BytesMut>>,
+    max_buffers: usize,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_POOLED_BUFFERS)
+    }
+}
+
+impl BufferPool {
+    pub fn new(max_buffers: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            max_buffers,
+        }
+    }
+
+    /// Takes an idle buffer from the pool or allocates a new one
+    pub fn acquire(self: &Arc<Self>) -> PooledBuffer {
+        let buf = self
+            .buffers
+            .lock()
+            .expect("BUG: buffer pool lock poisoned")
+            .pop()
+            .unwrap_or_else(|| BytesMut::with_capacity(POOLED_BUFFER_CAPACITY));
+        PooledBuffer {
+            buffer: ResponseBuffer { buf },
+            pool: self.clone(),
+        }
+    }
+
+    /// Number of idle buffers
+    pub fn idle(&self) -> usize {
+        self.buffers
+            .lock()
+            .expect("BUG: buffer pool lock poisoned")
+            .len()
+    }
+
+    fn release(&self, mut buf: BytesMut) {
+        buf.clear();
+        if buf.capacity() > MAX_POOLED_BUFFER_CAPACITY {
+            return;
+        }
+        let mut buffers = self.buffers.lock().expect("BUG: buffer pool lock poisoned");
+        if buffers.len() < self.max_buffers {
+            buffers.push(buf);
+        }
+    }
+}
+
+/// Response buffer which is returned to its `BufferPool` when it is dropped
+#[derive(Debug)]
+pub struct PooledBuffer {
+    buffer: ResponseBuffer,
+    pool: Arc<BufferPool>,
+}
+
+impl Deref for PooledBuffer {
+    type Target = ResponseBuffer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        let buf = std::mem::take(&mut self.buffer.buf);
+        self.pool.release(buf);
+    }
+}
+
+/// Prefix of characters with special meaning in values of the plain-text format
+pub const TEXT_ESCAPE: char = '\\';
+
+/// Characters escaped in the plain-text format exactly as CGMiner does. It is the section
+/// separator, the field and value delimiters and the escape itself (see `text`). Values in JSON
+/// are escaped only by the JSON serializer so they never contain these escapes.
+pub const TEXT_ESCAPED: [char; 4] = [
+    text::FIELD_DELIMITER,
+    text::SEPARATOR,
+    text::VALUE_DELIMITER,
+    TEXT_ESCAPE,
+];
+
+/// Appends the `value` escaped for the plain-text format to `text`
+pub fn escape_text(value: &str, text: &mut String) {
+    for c in value.chars() {
+        if TEXT_ESCAPED.contains(&c) {
+            text.push(TEXT_ESCAPE);
+        }
+        text.push(c);
+    }
+}
+
+/// Reverts `escape_text`. Escape followed by any character stands for the character itself and
+/// a trailing escape is kept as it is.
+pub fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            TEXT_ESCAPE => unescaped.push(chars.next().unwrap_or(TEXT_ESCAPE)),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Splits escaped plain-text at every `delimiter` which is not escaped. The parts are kept
+/// escaped.
+pub fn split_text(text: &str, delimiter: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == TEXT_ESCAPE {
+            escaped = true;
+        } else if c == delimiter {
+            parts.push(&text[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Parsed parameter of `ascset` command. Besides the classic CGMiner form `N,opt[,val]` also
+/// multiple options joined by `:` are accepted (`N,opt=val:opt=val`) to be applied at once.
+/// Only the classic form may omit the option value.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct AscSetParameter {
+    pub idx: i32,
+    pub options: Vec<(String, Option<String>)>,
+}
+
+impl AscSetParameter {
+    const OPTION_DELIMITER: char = ':';
+    const VALUE_DELIMITER: char = '=';
+
+    pub fn parse(parameter: Option<&json::Value>) -> Result<Self, response::Error> {
+        let parameter = match parameter {
+            Some(json::Value::String(value)) => value,
+            _ => return Err(response::ErrorCode::MissingAscParameter.into()),
+        };
+        let mut args = parameter.splitn(2, crate::PARAMETER_DELIMITER);
+        let idx = args
+            .next()
+            .and_then(|idx| idx.trim().parse().ok())
+            .ok_or_else(|| response::Error::from(response::ErrorCode::MissingAscParameter))?;
+        let options = args
+            .next()
+            .filter(|options| !options.trim().is_empty())
+            .ok_or_else(|| response::Error::from(response::ErrorCode::MissingAscOption))?;
+
+        let options = if options.contains(Self::VALUE_DELIMITER) {
+            options
+                .split(Self::OPTION_DELIMITER)
+                .map(|option| {
+                    let mut pair = option.splitn(2, Self::VALUE_DELIMITER);
+                    match (pair.next(), pair.next()) {
+                        (Some(name), Some(value)) if !name.trim().is_empty() => {
+                            Ok((name.trim().to_string(), Some(value.trim().to_string())))
+                        }
+                        _ => Err(response::ErrorCode::AscSetErr(
+                            idx,
+                            format!("invalid option '{}'", option),
+                        )
+                        .into()),
+                    }
+                })
+                .collect::<Result<_, response::Error>>()?
+        } else {
+            let mut pair = options.splitn(2, crate::PARAMETER_DELIMITER);
+            let name = pair.next().unwrap_or_default().trim().to_string();
+            if name.is_empty() {
+                return Err(response::ErrorCode::MissingAscOption.into());
+            }
+            let value = pair
+                .next()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty());
+            vec![(name, value)]
+        };
+
+        Ok(Self { idx, options })
+    }
+
+    /// Parameter check compatible with `command::ParameterCheckHandler`
+    pub fn check(_command: &str, parameter: &Option<&json::Value>) -> Result<(), response::Error> {
+        Self::parse(*parameter).map(|_| ())
+    }
+}
+
+/// Parses boolean parameter given either as a JSON boolean or as `true`/`false` string (see
+/// `parameters::FromParameter` for `bool`)
+pub fn parse_bool(parameter: Option<&json::Value>) -> Result<bool, response::Error> {
+    match parameter {
+        None => Err(response::ErrorCode::MissingBoolParameter.into()),
+        Some(value) => {
+            bool::from_value(value).ok_or_else(|| response::ErrorCode::InvalidBoolParameter.into())
+        }
+    }
+}
+
+/// Parameter of `tempctrl` command in the form `target,hot,dangerous`. Trailing fields can be
+/// omitted and empty fields leave the corresponding threshold unchanged.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct TempCtrlParameter {
+    pub target: Option<f32>,
+    pub hot: Option<f32>,
+    pub dangerous: Option<f32>,
+}
+
+impl TempCtrlParameter {
+    const MAX_FIELDS: usize = 3;
+
+    pub fn parse(parameter: Option<&json::Value>) -> Result<Self, response::Error> {
+        let parameter = match parameter {
+            Some(json::Value::String(value)) => value,
+            Some(value) => {
+                return Err(response::ErrorCode::InvalidTempCtrlParameter(value.to_string()).into())
+            }
+            None => {
+                return Err(response::ErrorCode::InvalidTempCtrlParameter("".to_string()).into())
+            }
+        };
+        let invalid = || response::ErrorCode::InvalidTempCtrlParameter(parameter.clone()).into();
+
+        let fields = parameter
+            .split(crate::PARAMETER_DELIMITER)
+            .map(str::trim)
+            .map(|field| match field {
+                "" => Ok(None),
+                field => field
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|temp| temp.is_finite())
+                    .map(Some)
+                    .ok_or_else(invalid),
+            })
+            .collect::<Result<Vec<_>, response::Error>>()?;
+        if fields.len() > Self::MAX_FIELDS || fields.iter().all(Option::is_none) {
+            return Err(invalid());
+        }
+
+        let field = |i: usize| fields.get(i).cloned().flatten();
+        let parameter = Self {
+            target: field(0),
+            hot: field(1),
+            dangerous: field(2),
+        };
+        parameter.check()?;
+        Ok(parameter)
+    }
+
+    /// Verifies that the specified thresholds are ordered as `target < hot < dangerous`
+    pub fn check(&self) -> Result<(), response::Error> {
+        if let Some(target) = self.target {
+            if let Some(limit) = self.hot.or(self.dangerous).filter(|limit| target >= *limit) {
+                return Err(response::ErrorCode::InvalidTargetTemp(target, limit).into());
+            }
+        }
+        if let (Some(hot), Some(dangerous)) = (self.hot, self.dangerous) {
+            if hot >= dangerous {
+                return Err(response::ErrorCode::InvalidHotTemp(hot, dangerous).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Requested fan control of `fanctrl` command
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum FanCtrlSetting {
+    /// Fan speed is driven by the temperature controller
+    Auto,
+    /// Fixed fan speed in percent
+    Manual(u32),
+}
+
+/// Parses parameter of `fanctrl` command which is either `auto` keyword or fan speed in percent
+/// given as a number or a string
+pub fn parse_fan_ctrl(parameter: Option<&json::Value>) -> Result<FanCtrlSetting, response::Error> {
+    let invalid = |parameter: String| {
+        response::Error::from(response::ErrorCode::InvalidFanCtrlParameter(parameter))
+    };
+    let speed = match parameter {
+        Some(json::Value::String(value)) if value.trim().eq_ignore_ascii_case("auto") => {
+            return Ok(FanCtrlSetting::Auto)
+        }
+        Some(value @ json::Value::String(_)) | Some(value @ json::Value::Number(_)) => value
+            .as_checked_i32()
+            .map_err(|_| invalid(parameters::describe(value).trim().to_string()))?,
+        Some(value) => return Err(invalid(value.to_string())),
+        None => return Err(invalid(String::new())),
+    };
+
+    Ok(speed)
+        .in_range(0, 100)
+        .map(|speed| FanCtrlSetting::Manual(speed as u32))
+        .map_err(|_| response::ErrorCode::InvalidFanSpeed(speed.into()).into())
+}
+
+/// Requested locate mode of `locate` command
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum LocateSetting {
+    /// Blink until stopped explicitly
+    On,
+    Off,
+    /// Blink for given number of seconds
+    Timed(u32),
+}
+
+/// Parses optional parameter of `locate` command which is either a boolean or a duration in
+/// seconds. Without the parameter the command only reports current state.
+pub fn parse_locate(
+    parameter: Option<&json::Value>,
+) -> Result<Option<LocateSetting>, response::Error> {
+    let invalid = |parameter: String| {
+        response::Error::from(response::ErrorCode::InvalidLocateParameter(parameter))
+    };
+    if let Some(on) = parameter.and_then(bool::from_value) {
+        return Ok(Some(if on {
+            LocateSetting::On
+        } else {
+            LocateSetting::Off
+        }));
+    }
+    let duration = match parameter {
+        None => return Ok(None),
+        Some(value @ json::Value::String(_)) | Some(value @ json::Value::Number(_)) => {
+            parameters::extract::<i64>(Some(value))
+                .map_err(|_| invalid(parameters::describe(value).trim().to_string()))?
+        }
+        Some(value) => return Err(invalid(value.to_string())),
+    };
+
+    Ok(duration)
+        .in_range(1, u32::MAX.into())
+        .map(|duration| Some(LocateSetting::Timed(duration as u32)))
+        .map_err(|_| response::ErrorCode::InvalidLocateDuration(duration).into())
+}
+
+/// Parses parameter of `setconfig` command in the form `name,value`. Only the first comma
+/// separates the name so the value itself may contain commas.
+pub fn parse_set_config(
+    parameter: Option<&json::Value>,
+) -> Result<(String, String), response::Error> {
+    let parameter = match parameter {
+        Some(json::Value::String(value)) => value,
+        _ => return Err(response::ErrorCode::MissingSetConfigParameter.into()),
+    };
+
+    let mut args = parameter.splitn(2, crate::PARAMETER_DELIMITER);
+    let name = args.next().unwrap_or_default().trim();
+    if name.is_empty() {
+        return Err(response::ErrorCode::MissingSetConfigParameter.into());
+    }
+    match args.next() {
+        Some(value) => Ok((name.to_string(), value.trim().to_string())),
+        None => Err(response::ErrorCode::MissingSetConfigValue(name.to_string()).into()),
+    }
+}
+
+/// Flags of `debug` command. A flag can be specified either by its name or by its first letter.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum DebugFlag {
+    Silent,
+    Quiet,
+    Verbose,
+    Debug,
+    RpcProto,
+    PerDevice,
+    WorkTime,
+    Normal,
+}
+
+impl DebugFlag {
+    pub const ALL: [DebugFlag; 8] = [
+        DebugFlag::Silent,
+        DebugFlag::Quiet,
+        DebugFlag::Verbose,
+        DebugFlag::Debug,
+        DebugFlag::RpcProto,
+        DebugFlag::PerDevice,
+        DebugFlag::WorkTime,
+        DebugFlag::Normal,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugFlag::Silent => "silent",
+            DebugFlag::Quiet => "quiet",
+            DebugFlag::Verbose => "verbose",
+            DebugFlag::Debug => "debug",
+            DebugFlag::RpcProto => "rpcproto",
+            DebugFlag::PerDevice => "perdevice",
+            DebugFlag::WorkTime => "worktime",
+            DebugFlag::Normal => "normal",
+        }
+    }
+
+    fn from_str(flag: &str) -> Option<Self> {
+        let flag = flag.trim().to_lowercase();
+        Self::ALL
+            .iter()
+            .find(|known| flag == known.name() || flag == known.name()[..1])
+            .cloned()
+    }
+}
+
+/// State of all debug flags with the same semantics as cgminer
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default)]
+pub struct DebugSettings {
+    pub silent: bool,
+    pub quiet: bool,
+    pub verbose: bool,
+    pub debug: bool,
+    pub rpc_proto: bool,
+    pub per_device: bool,
+    pub work_time: bool,
+}
+
+impl DebugSettings {
+    /// Toggles given `flag`. Enabling any flag that increases verbosity disables the quiet mode
+    /// while `normal` resets all flags except `silent`.
+    pub fn apply(&mut self, flag: DebugFlag) {
+        match flag {
+            DebugFlag::Silent => self.silent = true,
+            DebugFlag::Quiet => self.quiet = !self.quiet,
+            DebugFlag::Verbose => {
+                self.verbose = !self.verbose;
+                self.quiet &= !self.verbose;
+            }
+            DebugFlag::Debug => {
+                self.debug = !self.debug;
+                self.verbose = self.debug;
+                self.quiet &= !self.debug;
+            }
+            DebugFlag::RpcProto => {
+                self.rpc_proto = !self.rpc_proto;
+                self.quiet &= !self.rpc_proto;
+            }
+            DebugFlag::PerDevice => {
+                self.per_device = !self.per_device;
+                self.verbose = self.per_device;
+            }
+            DebugFlag::WorkTime => self.work_time = !self.work_time,
+            DebugFlag::Normal => {
+                *self = Self {
+                    silent: self.silent,
+                    ..Default::default()
+                }
+            }
+        }
+    }
+}
+
+/// Parses optional flag of `debug` command. Without the flag the command only reports current
+/// settings.
+pub fn parse_debug_flag(
+    parameter: Option<&json::Value>,
+) -> Result<Option<DebugFlag>, response::Error> {
+    let flag = match parameter {
+        None => return Ok(None),
+        Some(json::Value::String(flag)) => flag.clone(),
+        Some(value) => value.to_string(),
+    };
+    DebugFlag::from_str(&flag).map(Some).ok_or_else(|| {
+        response::ErrorCode::InvalidDebugFlag(
+            flag,
+            DebugFlag::ALL.iter().map(|flag| flag.name()).collect(),
+        )
+        .into()
+    })
+}
+
+/// Statistics reset by `zero` command
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ZeroTarget {
+    All,
+    BestShare,
+}
+
+impl fmt::Display for ZeroTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZeroTarget::All => write!(f, "all"),
+            ZeroTarget::BestShare => write!(f, "BestShare"),
+        }
+    }
+}
+
+/// Parses parameter of `zero` command in the form `all|bestshare[,true|false]`. The optional
+/// boolean tells whether a summary (taken before zeroing) should be returned.
+pub fn parse_zero(parameter: Option<&json::Value>) -> Result<(ZeroTarget, bool), response::Error> {
+    let parameter = match parameter {
+        Some(json::Value::String(value)) if !value.trim().is_empty() => value,
+        _ => return Err(response::ErrorCode::MissingZeroParameter.into()),
+    };
+    let invalid = || response::ErrorCode::InvalidZeroParameter(parameter.clone()).into();
+
+    let mut args = parameter.splitn(2, crate::PARAMETER_DELIMITER);
+    let target = match args
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+        .as_str()
+    {
+        "all" => ZeroTarget::All,
+        "bestshare" => ZeroTarget::BestShare,
+        _ => return Err(invalid()),
+    };
+    let return_summary = match args.next() {
+        None => false,
+        Some(value) => {
+            bool::from_value(&json::Value::String(value.to_string())).ok_or_else(invalid)?
+        }
+    };
+
+    Ok((target, return_summary))
+}
+
+/// Parsed parameter of `addpool` command in the form `url,user,pass`. Like in CGMiner a character
+/// preceded by `\` is taken literally, e.g. `\,` stands for a comma inside of a field. The last
+/// field takes the rest of the parameter.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct AddPoolParameter {
+    pub url: String,
+    pub user: String,
+    pub password: String,
+}
+
+impl AddPoolParameter {
+    const ESCAPE: char = '\\';
+    const FIELD_COUNT: usize = 3;
+
+    pub fn parse(parameter: Option<&json::Value>) -> Result<Self, response::Error> {
+        let parameter = match parameter {
+            Some(json::Value::String(value)) => value,
+            Some(json::Value::Number(value)) => {
+                return Err(response::ErrorCode::InvalidAddPoolDetails(value.to_string()).into())
+            }
+            // CGMiner recognizes strings and integers as the same type. Other types (array, map,
+            // ..) are reported as a missing parameter. Therefore, we match anything else as
+            // missing parameter.
+            _ => return Err(response::ErrorCode::MissingAddPoolDetails.into()),
+        };
+
+        let mut fields = vec![String::new()];
+        let mut chars = parameter.chars();
+        while let Some(c) = chars.next() {
+            let is_last_field = fields.len() == Self::FIELD_COUNT;
+            let field = fields.last_mut().expect("BUG: missing addpool field");
+            match c {
+                // Dangling escape character at the end is ignored
+                Self::ESCAPE => field.extend(chars.next()),
+                crate::PARAMETER_DELIMITER if !is_last_field => fields.push(String::new()),
+                c => field.push(c),
+            }
+        }
+        if fields.len() != Self::FIELD_COUNT {
+            return Err(response::ErrorCode::InvalidAddPoolDetails(parameter.clone()).into());
+        }
+
+        let mut fields = fields.drain(..);
+        Ok(Self {
+            url: fields.next().expect("BUG: missing addpool url"),
+            user: fields.next().expect("BUG: missing addpool user"),
+            password: fields.next().expect("BUG: missing addpool password"),
+        })
+    }
+
+    /// Parameter with the password replaced by `REDACTED` which can be reported back to clients
+    pub fn redacted(&self) -> String {
+        format!(
+            "{},{},{}",
+            Self::escape(&self.url),
+            Self::escape(&self.user),
+            REDACTED
+        )
+    }
+
+    /// Escapes `field` so that it is parsed back as a single field
+    fn escape(field: &str) -> String {
+        let mut escaped = String::with_capacity(field.len());
+        for c in field.chars() {
+            if c == Self::ESCAPE || c == crate::PARAMETER_DELIMITER {
+                escaped.push(Self::ESCAPE);
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+}
+
+/// Replacement of redacted credentials (see `command::Descriptor::redacted`)
+pub const REDACTED: &str = "***";
+
+/// Redacts the password of `addpool` parameter. A parameter which cannot be parsed is redacted
+/// whole because it may still contain the password.
+pub fn redact_add_pool(parameter: &json::Value) -> json::Value {
+    match AddPoolParameter::parse(Some(parameter)) {
+        Ok(pool) => pool.redacted().into(),
+        Err(_) => redact_all(parameter),
+    }
+}
+
+/// Redacts the whole parameter (e.g. the token of `auth` command)
+pub fn redact_all(_parameter: &json::Value) -> json::Value {
+    REDACTED.into()
 }
@@ -22,21 +22,44 @@
 
 //! Tests for the CGMiner API module
 
+mod access;
+mod audit;
+mod client;
+mod compat;
+mod delta;
+mod golden;
 mod handler;
+mod history;
+mod ordered;
+mod parameters;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+mod schema;
+mod server;
+mod text;
 mod utils;
+#[cfg(feature = "websocket")]
+mod websocket;
 
+use crate::access::{Privilege, Session, Tokens};
 use crate::command;
 use crate::commands;
+use crate::composite;
+use crate::observer::{self, Observer};
 use crate::response;
+use crate::test_utils::{self, MockHandler};
+use crate::Format;
 
-use utils::{assert_json_eq, codec_roundtrip};
+use utils::{assert_json_eq, codec_roundtrip, field_names, ordered_roundtrip, ZeroTime};
 
-use ii_async_compat::tokio;
+use ii_async_compat::{futures, tokio};
 
 use serde::Serialize;
 use serde_json as json;
 
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 #[repr(u32)]
@@ -159,6 +182,211 @@ async fn test_single() {
     assert_json_eq(&response, &expected);
 }
 
+#[tokio::test]
+async fn test_version_info() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_version_info(
+        response::VersionInfo::new("BOSminer".to_string(), "2020-01-01".to_string())
+            .api("3.1".to_string())
+            .field("Type".to_string(), "Antminer S9".to_string()),
+    );
+
+    let response = handle_ordered(&receiver, json::json!({ "command": "version" })).await;
+    // Only the body is changed
+    let status = json::Value::from(response.clone());
+    assert_eq!(status["STATUS"][0]["Msg"], "TestMiner versions");
+    assert_eq!(status["STATUS"][0]["Description"], "TestMiner v1.0");
+    let fields = version_fields(&response);
+    let fields: Vec<_> = fields
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    assert_eq!(
+        fields,
+        vec![
+            ("BOSminer", "2020-01-01"),
+            ("API", "3.1"),
+            ("Type", "Antminer S9")
+        ]
+    );
+}
+
+/// Names and values of all fields of the VERSION section in their order
+fn version_fields(response: &crate::ordered::Value) -> Vec<(String, String)> {
+    response
+        .as_object()
+        .and_then(|response| response.get("VERSION"))
+        .and_then(crate::ordered::Value::as_array)
+        .and_then(|sections| sections.first())
+        .and_then(crate::ordered::Value::as_object)
+        .expect("BUG: missing version")
+        .iter()
+        .map(|(name, value)| {
+            let value = value.as_str().unwrap_or_default();
+            (name.clone(), value.to_string())
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_version_extra() {
+    let fields = |fields: &[(&str, &str)]| -> Vec<(String, String)> {
+        fields
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    };
+    let build = |handler| {
+        command::Receiver::<ZeroTime>::new(
+            handler,
+            "TestMiner".to_string(),
+            "v1.0".to_string(),
+            None,
+        )
+        .with_version_info(
+            response::VersionInfo::new("BOSminer".to_string(), "2020-01-01".to_string())
+                .field("Type".to_string(), "Antminer S9".to_string()),
+        )
+    };
+    let components = fields(&[
+        ("Firmware", "2020-05-01"),
+        ("FPGA", "0x0000_0901"),
+        // Collisions with the standard fields, extra fields and other components are dropped
+        ("API", "0.0"),
+        ("BOSminer", "0.0"),
+        ("Type", "Antminer S17"),
+        ("FPGA", "0x0000_0000"),
+        ("PSU", "1.2"),
+    ]);
+    let receiver = build(
+        MockHandler::builder()
+            .response("version", components)
+            .build(),
+    );
+    let response = handle_ordered(&receiver, json::json!({ "command": "version" })).await;
+    assert_eq!(json::Value::from(response.clone())["STATUS"][0]["Code"], 22);
+    assert_eq!(
+        version_fields(&response),
+        fields(&[
+            ("BOSminer", "2020-01-01"),
+            ("API", crate::API_VERSION),
+            ("Type", "Antminer S9"),
+            ("Firmware", "2020-05-01"),
+            ("FPGA", "0x0000_0901"),
+            ("PSU", "1.2"),
+        ])
+    );
+
+    // The version is reported even when the components are not available
+    let receiver = build(
+        MockHandler::builder()
+            .error("version", || response::ErrorCode::HardwareError.into())
+            .build(),
+    );
+    let response = handle_ordered(&receiver, json::json!({ "command": "version" })).await;
+    assert_eq!(json::Value::from(response.clone())["STATUS"][0]["Code"], 22);
+    assert_eq!(
+        version_fields(&response),
+        fields(&[
+            ("BOSminer", "2020-01-01"),
+            ("API", crate::API_VERSION),
+            ("Type", "Antminer S9"),
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_request_from_bytes() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let context = command::Context::local();
+    let handle = |request| receiver.handle(request, &context);
+
+    // Terminators appended by CGMiner clients are ignored
+    for data in &[
+        &b"{\"command\": \"version\"}"[..],
+        b"{\"command\": \"version\"}\0",
+        b"{\"command\": \"version\"}\n",
+        b" {\"command\": \"version\"}\r\n\0\0",
+    ] {
+        let (request, format) =
+            command::Request::from_bytes(data).expect("BUG: cannot parse request");
+        assert_eq!(format, Format::Json);
+        let response = json::to_value(&handle(request).await).unwrap();
+        assert_eq!(response["STATUS"][0]["Code"], 22);
+    }
+    for data in &[&b"version"[..], b"version|\n", b"\0version\0\0"] {
+        let (request, format) =
+            command::Request::from_bytes(data).expect("BUG: cannot parse request");
+        assert_eq!(format, Format::Text);
+        let response = json::to_value(&handle(request).await).unwrap();
+        assert_eq!(response["STATUS"][0]["Code"], 22);
+    }
+
+    // Valid JSON without command is handled by the receiver
+    let (request, _) = command::Request::from_bytes(b"{\"parameter\": 1}").unwrap();
+    let response = json::to_value(&handle(request).await).unwrap();
+    assert_eq!(response["STATUS"][0]["Code"], 24);
+
+    let error = command::Request::from_bytes(b"{\"command\": \"version\"").unwrap_err();
+    assert!(matches!(error, command::ParseError::InvalidJson(_)));
+    let response = json::to_value(receiver.error_response(error.into())).unwrap();
+    assert_eq!(response["STATUS"][0]["STATUS"], "E");
+    assert_eq!(response["STATUS"][0]["Code"], 23);
+
+    // Exactly one request is accepted
+    for data in &[
+        &b"{\"command\": \"version\"}garbage"[..],
+        b"{\"command\": \"version\"}{\"command\": \"version\"}",
+        b"version\nversion",
+    ] {
+        let error = command::Request::from_bytes(data).unwrap_err();
+        assert!(matches!(error, command::ParseError::StrayData));
+    }
+    let error = command::Request::from_bytes(b" \0\n").unwrap_err();
+    assert!(matches!(error, command::ParseError::Empty));
+
+    let data = json::json!({ "command": "version", "parameter": "x".repeat(100) }).to_string();
+    assert!(command::Request::from_bytes_limited(data.as_bytes(), data.len()).is_ok());
+    match command::Request::from_bytes_limited(data.as_bytes(), data.len() - 1) {
+        Err(command::ParseError::TooLarge(max_size)) => assert_eq!(max_size, data.len() - 1),
+        _ => panic!("BUG: too large request accepted"),
+    }
+}
+
+#[test]
+fn test_request_from_bytes_garbage() {
+    let data = br#"{"command": "summary+pools", "parameter": "0,1"}"#;
+    // Every truncated request is refused without a panic
+    for len in 0..data.len() {
+        assert!(command::Request::from_bytes(&data[..len]).is_err());
+    }
+
+    // Pseudo-random binary data generated by a linear congruential generator
+    let mut state = 0x1234_5678u32;
+    let mut next = || {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        (state >> 16) as u8
+    };
+    for len in 0..256 {
+        let garbage: Vec<u8> = (0..len).map(|_| next()).collect();
+        let _ = command::Request::from_bytes(&garbage);
+        // Valid prefix followed by garbage
+        let mut request = data[..data.len() / 2].to_vec();
+        request.extend_from_slice(&garbage);
+        let _ = command::Request::from_bytes(&request);
+    }
+}
+
 #[tokio::test]
 async fn test_multiple() {
     let command: json::Value = json::json!({
@@ -177,6 +405,7 @@ async fn test_multiple() {
             "CONFIG": [{
                 "ASC Count": 0,
                 "Device Code": "",
+                "Failover-Only": false,
                 "Hotplug": "None",
                 "Log Interval": 0,
                 "OS": "Braiins OS",
@@ -325,9 +554,9 @@ async fn test_multiple_custom_commands() {
         }],
         "custom_command_two": [{
             "STATUS": [{
-                "Code": 45,
+                "Code": 310,
                 "Description": "TestMiner v1.0",
-                "Msg": "Access denied to 'custom_command_two' command",
+                "Msg": "Missing parameter 'value'",
                 "STATUS": "E",
                 "When": 0
             }],
@@ -338,3 +567,3654 @@ async fn test_multiple_custom_commands() {
 
     assert_json_eq(&response, &expected);
 }
+
+#[derive(Serialize, PartialEq, Clone, Debug)]
+struct Chain {
+    #[serde(rename = "CHAIN")]
+    idx: u32,
+    #[serde(rename = "Voltage")]
+    voltage: f64,
+}
+
+fn build_custom_receiver() -> command::ReceiverBuilder<ZeroTime> {
+    command::Receiver::builder(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add_parameterless("chains", || async {
+        let chains = vec![
+            Chain {
+                idx: 0,
+                voltage: 8.5,
+            },
+            Chain {
+                idx: 1,
+                voltage: 9.0,
+            },
+        ];
+        Ok(response::CustomResponse::new(
+            "CHAINS",
+            CustomStatusCode::CustomCommandOne,
+            chains,
+        ))
+    })
+    .add_with_parameter(
+        "psu",
+        |_, parameter| match parameter {
+            Some(json::Value::Number(_)) | None => Ok(()),
+            Some(_) => Err(CustomErrorCode::MissingParameter("voltage".to_string()).into()),
+        },
+        |parameter| {
+            let voltage = parameter.and_then(json::Value::as_f64).unwrap_or(12.0);
+            async move {
+                Ok(response::CustomResponse::new(
+                    "PSU",
+                    CustomStatusCode::CustomCommandTwo,
+                    vec![json::json!({ "Voltage": voltage })],
+                )
+                .msg("PSU set".to_string()))
+            }
+        },
+    )
+}
+
+async fn handle_custom(
+    receiver: &command::Receiver<ZeroTime>,
+    command: json::Value,
+) -> json::Value {
+    handle_ordered(receiver, command).await.into()
+}
+
+/// Same as `handle_custom` but the order of fields of the response is kept
+async fn handle_ordered(
+    receiver: &command::Receiver<ZeroTime>,
+    command: json::Value,
+) -> crate::ordered::Value {
+    let response = receiver
+        .handle(command::Request::new(command), &command::Context::local())
+        .await;
+    crate::ordered::to_value(&response).unwrap()
+}
+
+/// Handler failing with an error of a foreign type
+async fn handle_foreign_error() -> command::Result<response::CustomResponse<json::Value>> {
+    let voltage: u32 = "high".parse()?;
+    Ok(response::CustomResponse::new(
+        "PSU",
+        CustomStatusCode::CustomCommandTwo,
+        vec![json::json!({ "Voltage": voltage })],
+    ))
+}
+
+#[tokio::test]
+async fn test_error_context() {
+    let receiver = command::Receiver::<ZeroTime>::builder(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add_parameterless("chains", || async {
+        Err::<response::CustomResponse<json::Value>, _>(
+            response::ErrorCode::HardwareError.with_context("chain 2 SPI timeout"),
+        )
+    })
+    .add_parameterless("psu", handle_foreign_error)
+    .add_parameterless("fans", || async {
+        let error = anyhow::anyhow!("fan 1 stopped").context("cannot read fans");
+        Err::<response::CustomResponse<json::Value>, _>(response::Error::from(error))
+    })
+    .build()
+    .expect("BUG: cannot build receiver");
+
+    // The context extends the message while the code is kept
+    let response = test_utils::handle(&receiver, "chains", None).await;
+    assert!(response.is_error());
+    response.assert_status(269, "Hardware error - chain 2 SPI timeout");
+
+    test_utils::handle(&receiver, "psu", None)
+        .await
+        .assert_status(270, "Internal error - invalid digit found in string");
+    test_utils::handle(&receiver, "fans", None)
+        .await
+        .assert_status(270, "Internal error - cannot read fans");
+
+    // Standard errors are not affected
+    test_utils::handle(&receiver, "unknown", None)
+        .await
+        .assert_status(14, "Invalid command");
+}
+
+#[tokio::test]
+async fn test_receiver_builder() {
+    let receiver = build_custom_receiver()
+        .build()
+        .expect("BUG: cannot build receiver");
+
+    let response = handle_custom(&receiver, json::json!({ "command": "chains" })).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 301,
+            "Msg": "TestMiner chains",
+            "Description": "TestMiner v1.0",
+        }],
+        "CHAINS": [
+            { "CHAIN": 0, "Voltage": 8.5 },
+            { "CHAIN": 1, "Voltage": 9.0 },
+        ],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    let response = handle_custom(
+        &receiver,
+        json::json!({ "command": "psu", "parameter": 11.5 }),
+    )
+    .await;
+    assert_eq!(response["STATUS"][0]["Code"], 302);
+    assert_eq!(response["STATUS"][0]["Msg"], "PSU set");
+    assert_eq!(response["PSU"][0]["Voltage"], 11.5);
+    let response = handle_custom(
+        &receiver,
+        json::json!({ "command": "psu", "parameter": "high" }),
+    )
+    .await;
+    assert_eq!(response["STATUS"][0]["Code"], 310);
+
+    // Custom commands are part of multi-command requests as the standard ones
+    let response = handle_custom(&receiver, json::json!({ "command": "version+chains+psu" })).await;
+    assert_eq!(response["version"][0]["STATUS"][0]["Code"], 22);
+    assert_eq!(response["chains"][0]["CHAINS"][1]["CHAIN"], 1);
+    assert_eq!(response["psu"][0]["PSU"][0]["Voltage"], 12.0);
+
+    let response = handle_custom(
+        &receiver,
+        json::json!({ "command": "check", "parameter": "chains" }),
+    )
+    .await;
+    assert_eq!(
+        response["CHECK"][0],
+        json::json!({ "Exists": "Y", "Access": "Y" })
+    );
+    let response = handle_custom(&receiver, json::json!({ "command": "help" })).await;
+    let help = response["HELP"].as_array().unwrap();
+    for command in &["chains", "psu"] {
+        assert!(
+            help.iter().any(|entry| entry["Command"] == *command),
+            "BUG: missing '{}' in help",
+            command
+        );
+    }
+}
+
+#[test]
+fn test_receiver_builder_duplicate() {
+    let error = build_custom_receiver()
+        .add_parameterless("summary", || async {
+            Ok(response::CustomResponse::<()>::new("SUMMARY", 0u32, vec![]))
+        })
+        .build()
+        .err()
+        .expect("BUG: duplicate of standard command accepted");
+    assert_eq!(error, command::DuplicateCommand("summary"));
+
+    let error = build_custom_receiver()
+        .add_with_parameter(
+            "psu",
+            |_, _| Ok(()),
+            |_| async { Ok(response::CustomResponse::<()>::new("PSU", 0u32, vec![])) },
+        )
+        .build()
+        .err()
+        .expect("BUG: duplicate of custom command accepted");
+    assert_eq!(error.to_string(), "command 'psu' is already registered");
+}
+
+#[tokio::test]
+async fn test_alias() {
+    let receiver = build_custom_receiver()
+        .add(
+            "voltages",
+            command::Descriptor::new("voltages", command::HandlerType::Version, None)
+                .alias("volt")
+                .alias("v"),
+        )
+        .build()
+        .expect("BUG: cannot build receiver");
+    assert_eq!(receiver.aliases().get("coinmine"), Some(&"coin"));
+    assert_eq!(receiver.aliases().get("volt"), Some(&"voltages"));
+    assert_eq!(receiver.aliases().get("v"), Some(&"voltages"));
+
+    let response = handle_custom(&receiver, json::json!({ "command": "coinmine" })).await;
+    assert_eq!(response["STATUS"][0]["Code"], 78);
+
+    // Responses of batched commands are reported under the canonical name
+    let response = handle_custom(&receiver, json::json!({ "command": "volt+coinmine" })).await;
+    assert_eq!(response["voltages"][0]["STATUS"][0]["Code"], 22);
+    assert_eq!(response["coin"][0]["STATUS"][0]["Code"], 78);
+    assert!(response.get("volt").is_none());
+    assert!(response.get("coinmine").is_none());
+
+    let response = handle_custom(
+        &receiver,
+        json::json!({ "command": "check", "parameter": "coinmine" }),
+    )
+    .await;
+    assert_eq!(
+        response["CHECK"][0],
+        json::json!({ "Exists": "Y", "Access": "Y" })
+    );
+}
+
+#[test]
+fn test_alias_duplicate() {
+    let descriptor = |alias| {
+        command::Descriptor::new("voltages", command::HandlerType::Version, None).alias(alias)
+    };
+    for alias in &["coinmine", "summary", "chains", "voltages"] {
+        let error = build_custom_receiver()
+            .add("voltages", descriptor(alias))
+            .build()
+            .err()
+            .expect("BUG: colliding alias accepted");
+        assert_eq!(error, command::DuplicateCommand(alias));
+    }
+    let error = build_custom_receiver()
+        .add("voltages", descriptor("volt").alias("volt"))
+        .build()
+        .err()
+        .expect("BUG: repeated alias accepted");
+    assert_eq!(error, command::DuplicateCommand("volt"));
+}
+
+#[test]
+#[should_panic(expected = "alias 'summary' of command 'voltages' collides")]
+fn test_alias_collision() {
+    let mut custom_commands = command::Map::new();
+    custom_commands.insert(
+        "voltages",
+        command::Descriptor::new("voltages", command::HandlerType::Version, None).alias("summary"),
+    );
+    command::Receiver::<ZeroTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        custom_commands,
+    );
+}
+
+#[tokio::test]
+async fn test_multiple_with_parameter() {
+    let handler = Arc::new(TestCustomHandler);
+
+    const CUSTOM_COMMAND: &str = "custom_command";
+    let custom_commands = || {
+        commands![
+            (CUSTOM_COMMAND: Parameter(None) -> handler.handle_command_two)
+        ]
+    };
+
+    // The parameter is offered to each command which accepts one
+    let command: json::Value = json::json!({
+        "command": "summary+custom_command+devs+asc+switchpool",
+        "parameter": 0
+    });
+    let response = codec_roundtrip(command, custom_commands()).await;
+    assert_eq!(response["summary"][0]["STATUS"][0]["Code"], 11);
+    let custom = &response["custom_command"][0];
+    assert_eq!(custom["STATUS"][0]["Code"], 302);
+    assert_eq!(custom["CUSTOM_COMMAND_TWO"][0]["Value"], 0);
+    assert_eq!(response["devs"][0]["STATUS"][0]["Code"], 9);
+    assert_eq!(response["devs"][0]["DEVS"][0]["ASC"], 0);
+    assert_eq!(response["asc"][0]["STATUS"][0]["Code"], 106);
+    // Privileged commands are still refused
+    assert_eq!(response["switchpool"][0]["STATUS"][0]["Code"], 45);
+
+    // Only the commands refusing the parameter fail
+    let command: json::Value = json::json!({
+        "command": "summary+custom_command+devs",
+        "parameter": 1
+    });
+    let response = codec_roundtrip(command, custom_commands()).await;
+    assert_eq!(response["summary"][0]["STATUS"][0]["Code"], 11);
+    let custom = &response["custom_command"][0];
+    assert_eq!(custom["CUSTOM_COMMAND_TWO"][0]["Value"], 1);
+    assert_eq!(response["devs"][0]["STATUS"][0]["Code"], 107);
+
+    // Parameter check of each command is performed separately
+    let command: json::Value = json::json!({
+        "command": "version+asc+check",
+        "parameter": "first"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["version"][0]["STATUS"][0]["Code"], 22);
+    assert_eq!(response["asc"][0]["STATUS"][0]["Code"], 268);
+    assert_eq!(response["check"][0]["STATUS"][0]["Code"], 72);
+}
+
+/// Handler of commands taking a long time to finish or failing unexpectedly
+struct DelayedHandler;
+
+impl DelayedHandler {
+    const DELAY: Duration = Duration::from_millis(200);
+
+    async fn handle_delayed(&self) -> command::Result<CustomCommandOne> {
+        tokio::time::delay_for(Self::DELAY).await;
+        Ok(CustomCommandOne {
+            attribute: "delayed".to_string(),
+        })
+    }
+
+    async fn handle_delayed_parameter(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> command::Result<CustomCommandTwo> {
+        let value = parameter.and_then(json::Value::as_u64).unwrap_or_default() as u32;
+        tokio::time::delay_for(Self::DELAY).await;
+        Ok(CustomCommandTwo { value })
+    }
+
+    async fn handle_panicking(&self) -> command::Result<CustomCommandOne> {
+        tokio::time::delay_for(Self::DELAY / 2).await;
+        panic!("handler failure");
+    }
+}
+
+/// Handler of commands which finish only once all of them are being handled at the same time
+struct RendezvousHandler {
+    barrier: tokio::sync::Barrier,
+}
+
+impl RendezvousHandler {
+    fn new(commands: usize) -> Self {
+        Self {
+            barrier: tokio::sync::Barrier::new(commands),
+        }
+    }
+
+    async fn handle_rendezvous(&self) -> command::Result<CustomCommandOne> {
+        self.barrier.wait().await;
+        Ok(CustomCommandOne {
+            attribute: "delayed".to_string(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_multiple_concurrent() {
+    let handler = Arc::new(RendezvousHandler::new(3));
+    let failing = Arc::new(DelayedHandler);
+
+    const DELAYED_ONE: &str = "delayed_one";
+    const DELAYED_TWO: &str = "delayed_two";
+    const DELAYED_THREE: &str = "delayed_three";
+    const PANICKING: &str = "panicking";
+    let custom_commands = commands![
+        (DELAYED_ONE: ParameterLess -> handler.handle_rendezvous),
+        (DELAYED_TWO: ParameterLess -> handler.handle_rendezvous),
+        (DELAYED_THREE: ParameterLess -> handler.handle_rendezvous),
+        (PANICKING: ParameterLess -> failing.handle_panicking)
+    ];
+
+    let command: json::Value = json::json!({
+        "command": "delayed_three+summary+panicking+delayed_one+delayed_two"
+    });
+    // None of the handlers could finish if they did not run concurrently. The timeout only keeps
+    // the test from hanging.
+    let response = tokio::time::timeout(
+        Duration::from_secs(10),
+        ordered_roundtrip(command, custom_commands),
+    )
+    .await
+    .expect("BUG: handlers of batched commands do not run concurrently");
+
+    // Responses keep the order of commands regardless of which handler finished first
+    assert_eq!(
+        field_names(&response),
+        vec![
+            "delayed_three",
+            "summary",
+            "panicking",
+            "delayed_one",
+            "delayed_two",
+            "id"
+        ]
+    );
+
+    let response = json::Value::from(response);
+    for name in &[DELAYED_ONE, DELAYED_TWO, DELAYED_THREE] {
+        assert_eq!(response[name][0]["STATUS"][0]["Code"], 301);
+    }
+    assert_eq!(response["summary"][0]["STATUS"][0]["Code"], 11);
+    // The panic affects only the response of the failing command
+    assert_json_eq(
+        &response["panicking"][0]["STATUS"][0],
+        &json::json!({
+            "STATUS": "E",
+            "When": 0,
+            "Code": 264,
+            "Msg": "Command 'panicking' failed unexpectedly",
+            "Description": "TestMiner v1.0",
+        }),
+    );
+}
+
+#[tokio::test]
+async fn test_command_timeout() {
+    let handler = Arc::new(DelayedHandler);
+
+    const DELAYED: &str = "delayed";
+    const DELAYED_PARAMETER: &str = "delayed_parameter";
+    const PATIENT: &str = "patient";
+    let receiver = command::Receiver::builder(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add(
+        DELAYED,
+        command!(DELAYED: ParameterLess -> handler.handle_delayed),
+    )
+    .add(
+        DELAYED_PARAMETER,
+        command!(DELAYED_PARAMETER: Parameter(None) -> handler.handle_delayed_parameter),
+    )
+    .add(
+        PATIENT,
+        command!(PATIENT: ParameterLess -> handler.handle_delayed)
+            .timeout(DelayedHandler::DELAY * 2),
+    )
+    .build()
+    .expect("BUG: cannot build receiver")
+    .with_command_timeout(DelayedHandler::DELAY / 4);
+
+    let response = handle_custom(&receiver, json::json!({ "command": DELAYED })).await;
+    assert_json_eq(
+        &response["STATUS"][0],
+        &json::json!({
+            "STATUS": "E",
+            "When": 0,
+            "Code": 265,
+            "Msg": "Command 'delayed' timed out after 50ms",
+            "Description": "TestMiner v1.0",
+        }),
+    );
+    let response = handle_custom(
+        &receiver,
+        json::json!({ "command": DELAYED_PARAMETER, "parameter": 1 }),
+    )
+    .await;
+    assert_eq!(response["STATUS"][0]["Code"], 265);
+
+    // The command with its own timeout is not affected by the default one
+    let response = handle_custom(&receiver, json::json!({ "command": PATIENT })).await;
+    assert_eq!(response["STATUS"][0]["Code"], 301);
+
+    // Commands of a batch time out independently
+    let response = handle_custom(
+        &receiver,
+        json::json!({ "command": "delayed+summary+patient" }),
+    )
+    .await;
+    assert_eq!(response["delayed"][0]["STATUS"][0]["Code"], 265);
+    assert_eq!(response["summary"][0]["STATUS"][0]["Code"], 11);
+    assert_eq!(response["patient"][0]["STATUS"][0]["Code"], 301);
+}
+
+/// Handler counting invocations of its commands
+#[derive(Default)]
+struct CountingHandler {
+    invocations: AtomicUsize,
+}
+
+impl CountingHandler {
+    const DELAY: Duration = Duration::from_millis(50);
+
+    fn invocations(&self) -> usize {
+        self.invocations.load(Ordering::SeqCst)
+    }
+
+    async fn handle_counted(&self) -> command::Result<CustomCommandOne> {
+        self.invocations.fetch_add(1, Ordering::SeqCst);
+        tokio::time::delay_for(Self::DELAY).await;
+        Ok(CustomCommandOne {
+            attribute: "counted".to_string(),
+        })
+    }
+
+    async fn handle_counted_parameter(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> command::Result<CustomCommandTwo> {
+        self.invocations.fetch_add(1, Ordering::SeqCst);
+        let value = parameter
+            .and_then(json::Value::as_u64)
+            .ok_or_else(|| CustomErrorCode::MissingParameter("value".to_string()))?;
+        Ok(CustomCommandTwo {
+            value: value as u32,
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_cache() {
+    let handler = Arc::new(CountingHandler::default());
+
+    const COUNTED: &str = "counted";
+    const COUNTED_PARAMETER: &str = "counted_parameter";
+    const CACHE_TTL: Duration = Duration::from_millis(300);
+    let receiver = command::Receiver::builder(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add(
+        COUNTED,
+        command!(COUNTED: ParameterLess -> handler.handle_counted).cache_ttl(CACHE_TTL),
+    )
+    .add(
+        COUNTED_PARAMETER,
+        command!(COUNTED_PARAMETER: Parameter(None) -> handler.handle_counted_parameter)
+            .cache_ttl(CACHE_TTL),
+    )
+    .build()
+    .expect("BUG: cannot build receiver");
+
+    let response = handle_custom(&receiver, json::json!({ "command": COUNTED })).await;
+    assert_eq!(response["CUSTOM_COMMAND_ONE"][0]["Attribute"], "counted");
+    assert!(response["STATUS"][0].get("Age").is_none());
+    assert_eq!(handler.invocations(), 1);
+
+    // Cached response reports its age
+    let response = handle_custom(&receiver, json::json!({ "command": COUNTED })).await;
+    assert_eq!(response["CUSTOM_COMMAND_ONE"][0]["Attribute"], "counted");
+    let age = response["STATUS"][0]["Age"]
+        .as_f64()
+        .expect("BUG: missing age of cached response");
+    assert!(age < CACHE_TTL.as_secs_f64());
+    // Batched commands share the cache
+    let response = handle_custom(&receiver, json::json!({ "command": "summary+counted" })).await;
+    assert_eq!(response["counted"][0]["STATUS"][0]["Code"], 301);
+    assert_eq!(handler.invocations(), 1);
+
+    tokio::time::delay_for(CACHE_TTL).await;
+    let response = handle_custom(&receiver, json::json!({ "command": COUNTED })).await;
+    assert!(response["STATUS"][0].get("Age").is_none());
+    assert_eq!(handler.invocations(), 2);
+
+    // Responses are cached for each parameter separately and errors are not cached at all
+    let request = |parameter: json::Value| json::json!({ "command": COUNTED_PARAMETER, "parameter": parameter });
+    for &(parameter, invocations) in &[(1, 3), (2, 4), (1, 4), (2, 4)] {
+        let response = handle_custom(&receiver, request(parameter.into())).await;
+        assert_eq!(response["CUSTOM_COMMAND_TWO"][0]["Value"], parameter);
+        assert_eq!(handler.invocations(), invocations);
+    }
+    for invocations in 5..7 {
+        let response = handle_custom(&receiver, request("invalid".into())).await;
+        assert_eq!(response["STATUS"][0]["Code"], 310);
+        assert_eq!(handler.invocations(), invocations);
+    }
+}
+
+/// Current time of `ManualTime` which is set by `test_collected`
+static MANUAL_TIME: AtomicU32 = AtomicU32::new(0);
+
+struct ManualTime;
+
+impl crate::support::When for ManualTime {
+    fn when() -> response::Time {
+        MANUAL_TIME.load(Ordering::SeqCst)
+    }
+}
+
+#[tokio::test]
+async fn test_collected() {
+    const COUNTED: &str = "counted";
+    const PREFETCHED: &str = "prefetched";
+    let handler = Arc::new(CountingHandler::default());
+    let prefetched: command::ParameterLessHandler = Box::new(|| {
+        Box::pin(async {
+            // The handler reports when it has obtained the data itself
+            Ok(response::Dispatch::from_custom_success::<(), _>(
+                CustomStatusCode::CustomCommandOne,
+                "Prefetched".to_string(),
+                None,
+            )
+            .with_collected(900))
+        })
+    });
+    let receiver = command::Receiver::<ManualTime>::builder(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add(
+        COUNTED,
+        command!(COUNTED: ParameterLess -> handler.handle_counted)
+            .cache_ttl(Duration::from_secs(10)),
+    )
+    .add(
+        PREFETCHED,
+        command::Descriptor::new(
+            PREFETCHED,
+            command::HandlerType::ParameterLess(prefetched),
+            None,
+        ),
+    )
+    .build()
+    .expect("BUG: cannot build receiver");
+    let status = |command: &'static str| {
+        let receiver = &receiver;
+        async move {
+            let request = command::Request::new(json::json!({ "command": command }));
+            let response = receiver.handle(request, &command::Context::local()).await;
+            json::to_value(&response).unwrap()["STATUS"][0].clone()
+        }
+    };
+
+    // Fresh data are collected at the time of the response
+    MANUAL_TIME.store(1000, Ordering::SeqCst);
+    let fresh = status(COUNTED).await;
+    assert_eq!(fresh["When"], 1000);
+    assert!(fresh.get("Collected").is_none());
+
+    // Cached response keeps the time of the handler invocation
+    MANUAL_TIME.store(1003, Ordering::SeqCst);
+    let cached = status(COUNTED).await;
+    assert_eq!(cached["When"], 1003);
+    assert_eq!(cached["Collected"], 1000);
+    assert!(cached["Age"].is_f64());
+    assert_eq!(handler.invocations(), 1);
+    assert!(status("summary").await.get("Collected").is_none());
+
+    // The time set by the handler is kept and omitted when it is the same as `When`
+    assert_eq!(status(PREFETCHED).await["Collected"], 900);
+    MANUAL_TIME.store(900, Ordering::SeqCst);
+    let prefetched = status(PREFETCHED).await;
+    assert_eq!(prefetched["When"], 900);
+    assert!(prefetched.get("Collected").is_none());
+}
+
+#[tokio::test]
+async fn test_cache_standard_command() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_cache_ttl("stats", Duration::from_secs(10));
+
+    let response = handle_custom(&receiver, json::json!({ "command": "stats" })).await;
+    assert!(response["STATUS"][0].get("Age").is_none());
+    let response = handle_custom(&receiver, json::json!({ "command": "stats" })).await;
+    assert!(response["STATUS"][0]["Age"].is_f64());
+    // Other commands are not cached
+    handle_custom(&receiver, json::json!({ "command": "estats" })).await;
+    let response = handle_custom(&receiver, json::json!({ "command": "estats" })).await;
+    assert!(response["STATUS"][0].get("Age").is_none());
+}
+
+#[tokio::test]
+async fn test_cache_single_flight() {
+    let handler = Arc::new(CountingHandler::default());
+
+    const COUNTED: &str = "counted";
+    let receiver = command::Receiver::builder(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add(
+        COUNTED,
+        command!(COUNTED: ParameterLess -> handler.handle_counted)
+            .cache_ttl(Duration::from_secs(10)),
+    )
+    .build()
+    .expect("BUG: cannot build receiver");
+
+    // Concurrent cache misses invoke the handler only once
+    let responses = futures::future::join_all(
+        (0..5).map(|_| handle_custom(&receiver, json::json!({ "command": COUNTED }))),
+    )
+    .await;
+    assert_eq!(handler.invocations(), 1);
+    for response in responses {
+        assert_eq!(response["CUSTOM_COMMAND_ONE"][0]["Attribute"], "counted");
+    }
+}
+
+/// Handler tracking how many invocations of its command run at once and the order in which they
+/// finish
+#[derive(Default)]
+struct TrackingHandler {
+    running: AtomicUsize,
+    max_running: AtomicUsize,
+    finished: std::sync::Mutex<Vec<u64>>,
+}
+
+impl TrackingHandler {
+    const DELAY: Duration = Duration::from_millis(50);
+
+    fn max_running(&self) -> usize {
+        self.max_running.load(Ordering::SeqCst)
+    }
+
+    fn finished(&self) -> Vec<u64> {
+        self.finished.lock().unwrap().clone()
+    }
+
+    async fn handle_tracked(
+        &self,
+        parameter: Option<&json::Value>,
+    ) -> command::Result<CustomCommandTwo> {
+        let value = parameter.and_then(json::Value::as_u64).unwrap_or_default();
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_running.fetch_max(running, Ordering::SeqCst);
+        tokio::time::delay_for(Self::DELAY).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        self.finished.lock().unwrap().push(value);
+        Ok(CustomCommandTwo {
+            value: value as u32,
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_max_concurrency() {
+    let serialized = Arc::new(TrackingHandler::default());
+    let limited = Arc::new(TrackingHandler::default());
+    let impatient = Arc::new(TrackingHandler::default());
+
+    const SERIALIZED: &str = "serialized";
+    const LIMITED: &str = "limited";
+    const IMPATIENT: &str = "impatient";
+    let receiver = command::Receiver::builder(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add(
+        SERIALIZED,
+        command!(SERIALIZED: Parameter(None) -> serialized.handle_tracked).max_concurrency(1),
+    )
+    .add(
+        LIMITED,
+        command!(LIMITED: Parameter(None) -> limited.handle_tracked).max_concurrency(2),
+    )
+    .add(
+        IMPATIENT,
+        command!(IMPATIENT: Parameter(None) -> impatient.handle_tracked)
+            .max_concurrency(1)
+            .timeout(TrackingHandler::DELAY * 3 / 2),
+    )
+    .build()
+    .expect("BUG: cannot build receiver");
+    let request = |command: &str, parameter: u64| {
+        handle_custom(
+            &receiver,
+            json::json!({ "command": command, "parameter": parameter }),
+        )
+    };
+
+    // Invocations of the same command wait for each other in the order of arrival while the
+    // other commands run in parallel
+    let start = Instant::now();
+    let (serialized_responses, limited_responses) = futures::future::join(
+        futures::future::join_all((0..3).map(|i| request(SERIALIZED, i))),
+        futures::future::join_all((0..4).map(|i| request(LIMITED, i))),
+    )
+    .await;
+    assert!(start.elapsed() < TrackingHandler::DELAY * 4);
+    assert_eq!(serialized.max_running(), 1);
+    assert_eq!(serialized.finished(), vec![0, 1, 2]);
+    assert_eq!(limited.max_running(), 2);
+    let mut finished = limited.finished();
+    finished.sort();
+    assert_eq!(finished, vec![0, 1, 2, 3]);
+    for responses in &[serialized_responses, limited_responses] {
+        for (i, response) in responses.iter().enumerate() {
+            assert_eq!(response["STATUS"][0]["Code"], 302);
+            assert_eq!(response["CUSTOM_COMMAND_TWO"][0]["Value"], i);
+        }
+    }
+
+    // Waiting for the permit counts into the timeout
+    let responses = futures::future::join_all((0..2).map(|i| request(IMPATIENT, i))).await;
+    assert_eq!(responses[0]["STATUS"][0]["Code"], 302);
+    assert_eq!(responses[1]["STATUS"][0]["Code"], 265);
+    assert_eq!(impatient.finished(), vec![0]);
+}
+
+/// Handlers of commands changing the state of the miner are not cancelled by the timeout
+#[tokio::test]
+async fn test_command_timeout_mutating() {
+    let mutating = Arc::new(TrackingHandler::default());
+    let reading = Arc::new(TrackingHandler::default());
+
+    const MUTATING: &str = "mutating";
+    const READING: &str = "reading";
+    let receiver = command::Receiver::builder(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add(
+        MUTATING,
+        command!(MUTATING: Parameter(None) -> mutating.handle_tracked)
+            .privileged()
+            .max_concurrency(1),
+    )
+    .add(
+        READING,
+        command!(READING: Parameter(None) -> reading.handle_tracked),
+    )
+    .build()
+    .expect("BUG: cannot build receiver")
+    .with_command_timeout(TrackingHandler::DELAY / 4);
+    let request = |command: &str, parameter: u64| {
+        handle_custom(
+            &receiver,
+            json::json!({ "command": command, "parameter": parameter }),
+        )
+    };
+
+    let response = request(READING, 0).await;
+    assert_eq!(response["STATUS"][0]["Code"], 265);
+    let response = request(MUTATING, 0).await;
+    assert_eq!(response["STATUS"][0]["Code"], 265);
+    // The timed out handler still holds its permit so the next one is not invoked at all
+    let response = request(MUTATING, 1).await;
+    assert_eq!(response["STATUS"][0]["Code"], 265);
+
+    tokio::time::delay_for(TrackingHandler::DELAY * 2).await;
+    assert!(reading.finished().is_empty());
+    assert_eq!(mutating.finished(), vec![0]);
+    let response = request(MUTATING, 2).await;
+    assert_eq!(response["STATUS"][0]["Code"], 265);
+    tokio::time::delay_for(TrackingHandler::DELAY * 2).await;
+    assert_eq!(mutating.finished(), vec![0, 2]);
+}
+
+#[tokio::test]
+async fn test_max_concurrency_standard_command() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_max_concurrency("stats", 1);
+
+    let responses = futures::future::join_all(
+        (0..3).map(|_| handle_custom(&receiver, json::json!({ "command": "stats" }))),
+    )
+    .await;
+    for response in responses {
+        assert_eq!(response["STATUS"][0]["Code"], 70);
+    }
+}
+
+#[tokio::test]
+async fn test_multiple_duplicate() {
+    let handler = Arc::new(CountingHandler::default());
+
+    const COUNTED: &str = "counted";
+    let receiver = command::Receiver::builder(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add(
+        COUNTED,
+        command!(COUNTED: ParameterLess -> handler.handle_counted),
+    )
+    .build()
+    .expect("BUG: cannot build receiver");
+    let sections = |response: &crate::ordered::Value| -> Vec<String> {
+        field_names(response)
+            .into_iter()
+            .filter(|name| *name != "id")
+            .map(str::to_string)
+            .collect()
+    };
+
+    // Duplicate commands are handled once in the order of their first occurrence
+    let request = json::json!({ "command": "counted+summary+counted+coinmine+coin+summary" });
+    let response = handle_ordered(&receiver, request).await;
+    assert_eq!(handler.invocations(), 1);
+    assert_eq!(sections(&response), vec!["counted", "summary", "coin"]);
+    let response = json::Value::from(response);
+    assert_eq!(response["counted"].as_array().map(Vec::len), Some(1));
+
+    // Batch stays a batch even when all its commands are the same
+    let response = handle_ordered(&receiver, json::json!({ "command": "summary+summary" })).await;
+    assert_eq!(sections(&response), vec!["summary"]);
+    let response = json::Value::from(response);
+    assert_eq!(response["summary"][0]["STATUS"][0]["Code"], 11);
+
+    // Empty names are ignored while the request stays a batch
+    let response = handle_ordered(&receiver, json::json!({ "command": "+++summary+" })).await;
+    assert_eq!(sections(&response), vec!["summary"]);
+    let response = json::Value::from(response);
+    assert_eq!(response["summary"][0]["STATUS"][0]["Code"], 11);
+    let response = handle_ordered(&receiver, json::json!({ "command": "+summary++devs+" })).await;
+    assert_eq!(sections(&response), vec!["summary", "devs"]);
+
+    // Request without any command name is invalid as a whole
+    for command in &["+", "+++", ""] {
+        let response = handle_custom(&receiver, json::json!({ "command": command })).await;
+        assert_eq!(response["STATUS"][0]["Code"], 14);
+        assert!(response.get("id").is_some());
+    }
+
+    // Each unknown command gets its own error section
+    let response = handle_ordered(&receiver, json::json!({ "command": "foo+bar+foo" })).await;
+    assert_eq!(sections(&response), vec!["foo", "bar"]);
+    let response = json::Value::from(response);
+    assert_eq!(response["foo"][0]["STATUS"][0]["Code"], 14);
+    assert_eq!(response["bar"][0]["STATUS"][0]["Code"], 14);
+    let response = handle_ordered(&receiver, json::json!({ "command": "foo+" })).await;
+    assert_eq!(sections(&response), vec!["foo"]);
+    let response = json::Value::from(response);
+    assert_eq!(response["foo"][0]["STATUS"][0]["Msg"], "Invalid command");
+    let response = handle_ordered(&receiver, json::json!({ "command": "summary+foo+coin" })).await;
+    assert_eq!(sections(&response), vec!["summary", "foo", "coin"]);
+    let response = json::Value::from(response);
+    assert_eq!(response["foo"][0]["STATUS"][0]["Code"], 14);
+}
+
+#[tokio::test]
+async fn test_api_stats() {
+    let handler = Arc::new(CountingHandler::default());
+
+    const COUNTED: &str = "counted";
+    let receiver = command::Receiver::builder(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add(
+        COUNTED,
+        command!(COUNTED: ParameterLess -> handler.handle_counted),
+    )
+    .build()
+    .expect("BUG: cannot build receiver");
+
+    for command in &[
+        json::json!({ "command": "summary" }),
+        json::json!({ "command": "summary+counted" }),
+        json::json!({ "command": "asc", "parameter": "invalid" }),
+        json::json!({ "command": "unknown" }),
+    ] {
+        handle_custom(&receiver, command.clone()).await;
+    }
+
+    let response = handle_custom(&receiver, json::json!({ "command": "apistats" })).await;
+    assert_eq!(response["STATUS"][0]["Code"], 213);
+    let list = response["APISTATS"]
+        .as_array()
+        .expect("BUG: missing API statistics");
+    let stats = |name: &str| {
+        list.iter()
+            .find(|stat| stat["Command"] == name)
+            .unwrap_or_else(|| panic!("BUG: missing statistics of '{}'", name))
+            .clone()
+    };
+
+    let summary = stats("summary");
+    assert_eq!(summary["Calls"], 2);
+    assert_eq!(summary["Errors"], 0);
+    let asc = stats("asc");
+    assert_eq!(asc["Calls"], 1);
+    assert_eq!(asc["Errors"], 1);
+    let counted = stats(COUNTED);
+    assert_eq!(counted["Calls"], 1);
+    let delay = CountingHandler::DELAY.as_secs_f64();
+    for field in &["Min", "Avg", "Max"] {
+        assert!(counted[field].as_f64().unwrap() >= delay);
+    }
+    assert_json_eq(
+        &stats("pools"),
+        &json::json!({
+            "Command": "pools",
+            "Calls": 0,
+            "Errors": 0,
+            "Cancelled": 0,
+            "Min": 0.0,
+            "Avg": 0.0,
+            "Max": 0.0,
+        }),
+    );
+    // Unknown commands are not tracked
+    assert!(list.iter().all(|stat| stat["Command"] != "unknown"));
+}
+
+#[tokio::test]
+async fn test_switch_pool() {
+    let command: json::Value = json::json!({
+        "command": "switchpool",
+        "parameter": "0"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 27,
+            "Msg": "Switching to pool 0: ''",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    let command: json::Value = json::json!({
+        "command": "switchpool",
+        "parameter": -1
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "E",
+            "When": 0,
+            "Code": 26,
+            "Msg": "Invalid pool id -1 - range is 0 - 0",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    let command: json::Value = json::json!({
+        "command": "switchpool",
+        "parameter": "first"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "E",
+            "When": 0,
+            "Code": 268,
+            "Msg": "Invalid parameter 'first' - expected integer",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    // Commands with parameters are refused in batched mode
+    let command: json::Value = json::json!({ "command": "version+switchpool" });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["switchpool"][0]["STATUS"][0]["Code"], 45);
+}
+
+#[tokio::test]
+async fn test_remove_pool() {
+    let command: json::Value = json::json!({
+        "command": "removepool",
+        "parameter": 0
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "E",
+            "When": 0,
+            "Code": 66,
+            "Msg": "Cannot remove last pool 0:''",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    let command: json::Value = json::json!({
+        "command": "removepool",
+        "parameter": "1"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 26);
+
+    let command: json::Value = json::json!({ "command": "removepool" });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 25);
+
+    // Commands with parameters are refused in batched mode
+    let command: json::Value = json::json!({ "command": "pools+removepool" });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["removepool"][0]["STATUS"][0]["Code"], 45);
+}
+
+#[tokio::test]
+async fn test_enable_disable_pool() {
+    let command: json::Value = json::json!({
+        "command": "enablepool",
+        "parameter": 0
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "I",
+            "When": 0,
+            "Code": 49,
+            "Msg": "Pool 0:'' already enabled",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    let command: json::Value = json::json!({
+        "command": "disablepool",
+        "parameter": "0"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "E",
+            "When": 0,
+            "Code": 149,
+            "Msg": "Cannot disable last active pool 0:''",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    for command in &["enablepool", "disablepool"] {
+        let request: json::Value = json::json!({
+            "command": command,
+            "parameter": 2
+        });
+        let response = codec_roundtrip(request, None).await;
+        assert_eq!(response["STATUS"][0]["Code"], 26);
+
+        let request: json::Value = json::json!({
+            "command": command,
+            "parameter": "pool"
+        });
+        let response = codec_roundtrip(request, None).await;
+        assert_eq!(response["STATUS"][0]["Code"], 268);
+    }
+}
+
+#[tokio::test]
+async fn test_devs() {
+    let command: json::Value = json::json!({ "command": "devs" });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 9);
+    assert_eq!(response["DEVS"].as_array().map(|list| list.len()), Some(1));
+
+    let command: json::Value = json::json!({
+        "command": "devs",
+        "parameter": 0
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["DEVS"][0]["ASC"], 0);
+
+    let command: json::Value = json::json!({
+        "command": "devs",
+        "parameter": "1"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 107);
+
+    // The optional parameter is accepted in batched mode as well
+    let command: json::Value = json::json!({ "command": "version+devs" });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["devs"][0]["STATUS"][0]["Code"], 9);
+
+    let command: json::Value = json::json!({
+        "command": "version+devs",
+        "parameter": "1"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["devs"][0]["STATUS"][0]["Code"], 107);
+}
+
+#[tokio::test]
+async fn test_stats_filter() {
+    let stats = |command: &str, parameter: Option<json::Value>| {
+        let mut request = json::json!({ "command": command });
+        if let Some(parameter) = parameter {
+            request["parameter"] = parameter;
+        }
+        codec_roundtrip(request, None)
+    };
+    let indices = |response: &json::Value| -> Vec<_> {
+        response["STATS"]
+            .as_array()
+            .expect("BUG: missing stats")
+            .iter()
+            .map(|section| section["STATS"].as_i64().unwrap())
+            .collect()
+    };
+
+    let response = stats("stats", None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 70);
+    assert_eq!(indices(&response), vec![0, 1]);
+    // Empty parameter is the same as missing one
+    assert_eq!(stats("stats", Some(json::json!(""))).await, response);
+    for (parameter, expected) in &[
+        (json::json!(0), vec![0]),
+        (json::json!("0"), vec![0]),
+        (json::json!("asc"), vec![0]),
+        (json::json!("POOL"), vec![1]),
+    ] {
+        let response = stats("stats", Some(parameter.clone())).await;
+        assert_eq!(indices(&response), *expected, "{}", parameter);
+    }
+    let response = stats("estats", Some(json::json!("pool"))).await;
+    assert!(indices(&response).is_empty());
+
+    let response = stats("estats", Some(json::json!(1))).await;
+    assert_eq!(response["STATUS"][0]["Code"], 107);
+    let response = stats("stats", Some(json::json!("chain"))).await;
+    assert_eq!(response["STATUS"][0]["Code"], 268);
+}
+
+#[tokio::test]
+async fn test_check() {
+    for command in &["enablepool", "disablepool", "switchpool", "removepool"] {
+        let request: json::Value = json::json!({
+            "command": "check",
+            "parameter": command
+        });
+        let response = codec_roundtrip(request, None).await;
+        let expected = json::json!({
+            "STATUS": [{
+                "STATUS": "S",
+                "When": 0,
+                "Code": 72,
+                "Msg": "Check command",
+                "Description": "TestMiner v1.0",
+            }],
+            "CHECK": [{
+                "Exists": "Y",
+                "Access": "Y",
+            }],
+            "id": 1
+        });
+        assert_json_eq(&response, &expected);
+    }
+
+    let command: json::Value = json::json!({
+        "command": "check",
+        "parameter": "unknownpool"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["CHECK"][0]["Exists"], "N");
+}
+
+#[tokio::test]
+async fn test_pool_priority() {
+    let command: json::Value = json::json!({
+        "command": "poolpriority",
+        "parameter": "0"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 73,
+            "Msg": "Changed pool priorities",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    let command: json::Value = json::json!({
+        "command": "poolpriority",
+        "parameter": "0,1"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 26);
+
+    let command: json::Value = json::json!({
+        "command": "poolpriority",
+        "parameter": "0,0"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 74);
+
+    // Commands with parameters are refused in batched mode
+    let command: json::Value = json::json!({ "command": "pools+poolpriority" });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["poolpriority"][0]["STATUS"][0]["Code"], 45);
+}
+
+/// Notify keys must match cgminer exactly (including spaces and asterisks)
+#[tokio::test]
+async fn test_notify() {
+    let command: json::Value = json::json!({ "command": "notify" });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 60,
+            "Msg": "Notify",
+            "Description": "TestMiner v1.0",
+        }],
+        "NOTIFY": [{
+            "NOTIFY": 0,
+            "Name": "",
+            "ID": 0,
+            "Last Well": 0,
+            "Last Not Well": 0,
+            "Reason Not Well": "None",
+            "*Thread Fail Init": 0,
+            "*Thread Zero Hash": 0,
+            "*Thread Fail Queue": 0,
+            "*Dev Sick Idle 60s": 0,
+            "*Dev Dead Idle 600s": 0,
+            "*Dev Nostart": 0,
+            "*Dev Over Heat": 0,
+            "*Dev Thermal Cutoff": 0,
+            "*Dev Comms Error": 0,
+            "*Dev Throttle": 0,
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+}
+
+/// The value applied by `setconfig` is reported by the following `config` command
+#[tokio::test]
+async fn test_set_config() {
+    let receiver = command::Receiver::<crate::support::UnixTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let handle = |command: json::Value| {
+        let request = command::Request::new(command);
+        let receiver = &receiver;
+        async move {
+            json::to_value(&receiver.handle(request, &command::Context::local()).await).unwrap()
+        }
+    };
+
+    let response = handle(json::json!({
+        "command": "setconfig",
+        "parameter": "log,10"
+    }))
+    .await;
+    assert_eq!(response["STATUS"][0]["Code"], 82);
+    assert_eq!(response["STATUS"][0]["Msg"], "Set config 'log' to 10");
+
+    let response = handle(json::json!({ "command": "config" })).await;
+    assert_eq!(response["CONFIG"][0]["Log Interval"], 10);
+
+    for (parameter, code) in &[("queue,1", 83), ("log,-1", 84), (",1", 85), ("log", 86)] {
+        let response = handle(json::json!({
+            "command": "setconfig",
+            "parameter": parameter
+        }))
+        .await;
+        assert_eq!(response["STATUS"][0]["Code"], *code);
+    }
+    let response = handle(json::json!({ "command": "config" })).await;
+    assert_eq!(response["CONFIG"][0]["Log Interval"], 10);
+}
+
+#[tokio::test]
+async fn test_response_extras() {
+    use command::Handler as _;
+
+    let mut config = handler::BasicTest::default()
+        .handle_config()
+        .await
+        .unwrap_or_else(|_| panic!("BUG: cannot get config"));
+    config
+        .extras
+        .insert("PSU Model".to_string(), json::json!("APW3"));
+    config
+        .extras
+        .insert("Board Count".to_string(), json::json!(3));
+    // Standard field takes precedence
+    config
+        .extras
+        .insert("Hotplug".to_string(), json::json!("Override"));
+    let mut summary = handler::BasicTest::default()
+        .handle_summary()
+        .await
+        .unwrap_or_else(|_| panic!("BUG: cannot get summary"));
+    summary
+        .extras
+        .insert("Elapsed".to_string(), json::json!(-1));
+    summary
+        .extras
+        .insert("Pool Rejected Rate".to_string(), json::json!(0.5));
+    let receiver = command::Receiver::<ZeroTime>::new(
+        MockHandler::builder()
+            .response("config", config)
+            .response("summary", summary)
+            .build(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+
+    let response = handle_ordered(&receiver, json::json!({ "command": "config" })).await;
+    let config = response
+        .as_object()
+        .and_then(|response| response.get("CONFIG"))
+        .and_then(crate::ordered::Value::as_array)
+        .and_then(|sections| sections.first())
+        .expect("BUG: missing config");
+    let keys = field_names(config);
+    assert_eq!(keys[keys.len() - 2..], ["Board Count", "PSU Model"]);
+    let response = test_utils::handle(&receiver, "config", None).await;
+    let config = response.section("CONFIG")[0]
+        .as_object()
+        .expect("BUG: config is not an object");
+    assert_eq!(config["Hotplug"], "None");
+    assert_eq!(config["PSU Model"], "APW3");
+    assert_eq!(config["Board Count"], 3);
+    assert_eq!(keys.iter().filter(|key| **key == "Hotplug").count(), 1);
+
+    let response = test_utils::handle(&receiver, "summary", None).await;
+    let summary = &response.section("SUMMARY")[0];
+    assert_eq!(summary["Elapsed"], 0);
+    assert_eq!(summary["Pool Rejected Rate"], 0.5);
+    let response = handle_ordered(&receiver, json::json!({ "command": "summary" })).await;
+    let summary = response
+        .as_object()
+        .and_then(|response| response.get("SUMMARY"))
+        .and_then(crate::ordered::Value::as_array)
+        .and_then(|sections| sections.first())
+        .expect("BUG: missing summary");
+    assert_eq!(field_names(summary).last(), Some(&"Pool Rejected Rate"));
+}
+
+#[tokio::test]
+async fn test_reload_config() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        MockHandler::builder()
+            .response(
+                "reloadconfig",
+                response::ReloadConfig {
+                    changed: vec!["log".to_string()],
+                    restart_required: vec![],
+                    rejected: vec!["fans".to_string(), "pools".to_string()],
+                },
+            )
+            .build(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+
+    // Rejected settings do not turn the response into an error
+    let response = test_utils::handle(&receiver, "reloadconfig", None).await;
+    response.assert_status(217, "Config reloaded with 2 rejected setting(s)");
+    assert_eq!(response.status()["STATUS"], "I");
+    assert_json_eq(
+        &response.section("RELOADCONFIG")[0],
+        &json::json!({
+            "Changed": ["log"],
+            "RestartRequired": [],
+            "Rejected": ["fans", "pools"],
+        }),
+    );
+
+    // The command changes the state of the miner
+    let response = test_utils::handle(&receiver, "summary+reloadconfig", None).await;
+    assert_eq!(response.command("reloadconfig").code(), 45);
+
+    // Handlers which do not implement the command report it as not supported
+    let receiver = command::Receiver::<ZeroTime>::new(
+        MockHandler::builder().build(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let response = test_utils::handle(&receiver, "reloadconfig", None).await;
+    response.assert_status(273, "Command 'reloadconfig' is not supported by the miner");
+    assert_eq!(response.status()["STATUS"], "E");
+}
+
+#[tokio::test]
+async fn test_failover_only() {
+    let receiver = command::Receiver::<crate::support::UnixTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let handle = |command: json::Value| {
+        let request = command::Request::new(command);
+        let receiver = &receiver;
+        async move {
+            json::to_value(&receiver.handle(request, &command::Context::local()).await).unwrap()
+        }
+    };
+
+    for (parameter, enabled) in &[
+        (json::json!("true"), true),
+        (json::json!("False"), false),
+        (json::json!(true), true),
+    ] {
+        let response = handle(json::json!({
+            "command": "failover-only",
+            "parameter": parameter
+        }))
+        .await;
+        assert_eq!(response["STATUS"][0]["Code"], 77);
+        assert_eq!(
+            response["STATUS"][0]["Msg"],
+            format!("Failover-Only set to {}", enabled)
+        );
+        // The config reflects the new mode
+        let response = handle(json::json!({ "command": "config" })).await;
+        assert_eq!(response["CONFIG"][0]["Failover-Only"], *enabled);
+    }
+
+    let response = handle(json::json!({ "command": "failover-only" })).await;
+    assert_eq!(response["STATUS"][0]["Code"], 75);
+    for parameter in &[json::json!("yes"), json::json!(1)] {
+        let response = handle(json::json!({
+            "command": "failover-only",
+            "parameter": parameter
+        }))
+        .await;
+        assert_eq!(response["STATUS"][0]["Code"], 76);
+    }
+}
+
+#[tokio::test]
+async fn test_default_lcd() {
+    use command::Handler as _;
+
+    let basic = handler::BasicTest::default();
+    let mut summary = basic
+        .handle_summary()
+        .await
+        .unwrap_or_else(|_| panic!("BUG: cannot get summary"));
+    summary.elapsed = 3600;
+    summary.mhs_av = 13_500_000.0;
+    summary.mhs_5m = 13_000_000.0;
+    summary.mhs_5s = 14_000_000.0;
+    summary.best_share = 123_456;
+    summary.found_blocks = 2;
+    let pool = basic
+        .handle_pools()
+        .await
+        .unwrap_or_else(|_| panic!("BUG: cannot get pools"))
+        .list
+        .remove(0);
+    let pool = |idx, status, priority, stratum_active| response::Pool {
+        idx,
+        url: format!("stratum+tcp://pool{}.example.com:3333", idx),
+        status,
+        priority,
+        stratum_active,
+        user: format!("user.{}", idx),
+        last_share_time: 1000 + idx as u32,
+        last_share_difficulty: 512.0 * (idx + 1) as f64,
+        ..pool.clone()
+    };
+    let asc = basic
+        .handle_devs(None)
+        .await
+        .unwrap_or_else(|_| panic!("BUG: cannot get devs"))
+        .list
+        .remove(0);
+    let asc = |idx, temperature, last_valid_work| response::Asc {
+        idx,
+        temperature,
+        last_valid_work,
+        ..asc.clone()
+    };
+    let devs = response::Devs {
+        list: vec![asc(0, 61.5, 1500), asc(1, 72.0, 1400), asc(2, 65.0, 1600)],
+    };
+    let handle_lcd = |pools: Vec<response::Pool>| {
+        let receiver = command::Receiver::<ZeroTime>::new(
+            MockHandler::builder()
+                .response("summary", summary.clone())
+                .response("pools", response::Pools { list: pools })
+                .response("devs", devs.clone())
+                .build(),
+            "TestMiner".to_string(),
+            "v1.0".to_string(),
+            None,
+        );
+        async move { test_utils::handle(&receiver, "lcd", None).await }
+    };
+
+    // Pool with active stratum connection is the current one
+    let response = handle_lcd(vec![
+        pool(0, response::PoolStatus::Alive, 0, false),
+        pool(1, response::PoolStatus::Alive, 1, true),
+    ])
+    .await;
+    response.assert_status(125, "LCD");
+    assert_eq!(
+        response.section("LCD")[0],
+        json::json!({
+            "Elapsed": 3600,
+            "GHS av": 13500.0,
+            "GHS 5m": 13000.0,
+            "GHS 5s": 14000.0,
+            "Temperature": 72.0,
+            "Last Share Difficulty": 1024.0,
+            "Last Share Time": 1001,
+            "Best Share": 123_456,
+            "Last Valid Work": 1600,
+            "Found Blocks": 2,
+            "Current Pool": "stratum+tcp://pool1.example.com:3333",
+            "User": "user.1",
+        })
+    );
+
+    // Otherwise it is the alive pool with the highest priority
+    let response = handle_lcd(vec![
+        pool(0, response::PoolStatus::Dead, 0, false),
+        pool(1, response::PoolStatus::Alive, 2, false),
+        pool(2, response::PoolStatus::Alive, 1, false),
+    ])
+    .await;
+    assert_eq!(response.section("LCD")[0]["User"], "user.2");
+    let response = handle_lcd(vec![pool(0, response::PoolStatus::Disabled, 0, false)]).await;
+    let lcd = &response.section("LCD")[0];
+    assert_eq!(lcd["Current Pool"], "");
+    assert_eq!(lcd["Last Share Time"], 0);
+    assert_eq!(lcd["Elapsed"], 3600);
+
+    // Failure of any source fails the command
+    let receiver = command::Receiver::<ZeroTime>::new(
+        MockHandler::builder()
+            .response("summary", summary.clone())
+            .error("pools", || response::ErrorCode::HardwareError.into())
+            .build(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    test_utils::handle(&receiver, "lcd", None)
+        .await
+        .assert_status(269, "Hardware error");
+}
+
+#[tokio::test]
+async fn test_tuner_status() {
+    let command: json::Value = json::json!({
+        "command": "tunerstatus"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 211,
+            "Msg": "Tuner status",
+            "Description": "TestMiner v1.0",
+        }],
+        "TUNERSTATUS": [{
+            "PowerLimit": 1200,
+            "DynamicPowerScaling": "N",
+            "TUNERCHAIN": [
+                {
+                    "TUNERCHAIN": 0,
+                    "ID": 6,
+                    "Stage": "Tuning",
+                    "Iterations Done": 3,
+                    "Iterations Total": 10,
+                    "Estimated Finish": 1000,
+                    "Best Frequency": 650.0,
+                    "Best Voltage": 8.8,
+                },
+                {
+                    "TUNERCHAIN": 1,
+                    "ID": 7,
+                    "Stage": "Stable",
+                    "Iterations Done": 10,
+                    "Iterations Total": 10,
+                    "Estimated Finish": 0,
+                    "Best Frequency": 675.0,
+                    "Best Voltage": 8.9,
+                },
+            ],
+        }],
+        "id": 1
+    });
+
+    assert_json_eq(&response, &expected);
+}
+
+#[tokio::test]
+async fn test_help() {
+    let handler = Arc::new(TestCustomHandler);
+
+    const CUSTOM_COMMAND: &str = "custom_command";
+    let custom_commands = commands![
+        (CUSTOM_COMMAND: Parameter(None) -> handler.handle_command_two)
+    ];
+
+    let command: json::Value = json::json!({ "command": "help" });
+    let response = codec_roundtrip(command, custom_commands).await;
+    assert_eq!(response["STATUS"][0]["Code"], 212);
+    assert_eq!(response["STATUS"][0]["Msg"], "37 command(s)");
+
+    let list = response["HELP"].as_array().expect("BUG: missing help list");
+    let names: Vec<_> = list
+        .iter()
+        .map(|command| command["Command"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "addpool",
+            "apistats",
+            "asc",
+            "asccount",
+            "ascdisable",
+            "ascenable",
+            "ascset",
+            "check",
+            "coin",
+            "config",
+            "custom_command",
+            "debug",
+            "devs",
+            "disablepool",
+            "edevs",
+            "enablepool",
+            "estats",
+            "failover-only",
+            "help",
+            "lastcommands",
+            "lcd",
+            "locate",
+            "notify",
+            "pause",
+            "poolpriority",
+            "pools",
+            "reloadconfig",
+            "removepool",
+            "resume",
+            "schema",
+            "setconfig",
+            "stats",
+            "summary",
+            "switchpool",
+            "tunerstatus",
+            "version",
+            "zero",
+        ]
+    );
+
+    let find = |name: &str| {
+        list.iter()
+            .find(|command| command["Command"] == name)
+            .expect("BUG: missing command")
+            .clone()
+    };
+    assert_json_eq(
+        &find("ascset"),
+        &json::json!({
+            "Command": "ascset",
+            "Parameter": "Y",
+            "Privileged": "Y",
+            "PrivilegedParameter": "N",
+            "Description": "Set options of ASC device N,opt[=val][:opt=val]",
+        }),
+    );
+    assert_json_eq(
+        &find("summary"),
+        &json::json!({
+            "Command": "summary",
+            "Parameter": "N",
+            "Privileged": "N",
+            "PrivilegedParameter": "N",
+            "Description": "Summary of mining statistics",
+        }),
+    );
+    // Custom commands without description are listed as well
+    assert_json_eq(
+        &find(CUSTOM_COMMAND),
+        &json::json!({
+            "Command": CUSTOM_COMMAND,
+            "Parameter": "Y",
+            "Privileged": "N",
+            "PrivilegedParameter": "N",
+            "Description": "",
+        }),
+    );
+}
+
+#[tokio::test]
+async fn test_pause_resume() {
+    let receiver = command::Receiver::<crate::support::UnixTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let handle = |command: json::Value| {
+        let request = command::Request::new(command);
+        let receiver = &receiver;
+        async move {
+            json::to_value(&receiver.handle(request, &command::Context::local()).await).unwrap()
+        }
+    };
+    let status = |response: &json::Value| {
+        let status = &response["STATUS"][0];
+        (
+            status["STATUS"].as_str().unwrap().to_string(),
+            status["Code"].as_u64().unwrap(),
+            status["Msg"].as_str().unwrap().to_string(),
+        )
+    };
+
+    let response = handle(json::json!({ "command": "summary" })).await;
+    assert_eq!(response["SUMMARY"][0]["Paused"], "N");
+
+    let response = handle(json::json!({ "command": "pause" })).await;
+    assert_eq!(
+        status(&response),
+        ("S".to_string(), 206, "Mining paused".to_string())
+    );
+    let response = handle(json::json!({ "command": "summary" })).await;
+    assert_eq!(response["SUMMARY"][0]["Paused"], "Y");
+    // Repeated pause is not an error
+    let response = handle(json::json!({ "command": "pause" })).await;
+    assert_eq!(
+        status(&response),
+        ("I".to_string(), 208, "Mining already paused".to_string())
+    );
+
+    let response = handle(json::json!({ "command": "resume" })).await;
+    assert_eq!(
+        status(&response),
+        ("S".to_string(), 207, "Mining resumed".to_string())
+    );
+    let response = handle(json::json!({ "command": "summary" })).await;
+    assert_eq!(response["SUMMARY"][0]["Paused"], "N");
+    let response = handle(json::json!({ "command": "resume" })).await;
+    assert_eq!(
+        status(&response),
+        ("I".to_string(), 209, "Mining is not paused".to_string())
+    );
+
+    // Both commands change the state of the miner
+    let response = handle(json::json!({ "command": "summary+pause" })).await;
+    assert_eq!(response["pause"][0]["STATUS"][0]["Code"], 45);
+}
+
+#[tokio::test]
+async fn test_status_severities() {
+    for (status, letter) in &[
+        (response::Status::W, "W"),
+        (response::Status::I, "I"),
+        (response::Status::S, "S"),
+        (response::Status::E, "E"),
+    ] {
+        assert_eq!(json::to_value(status).unwrap(), json::json!(letter));
+    }
+
+    let handler = MockHandler::builder()
+        .respond_with("enablepool", || {
+            Ok(response::EnablePool::AlreadyEnabled {
+                idx: 1,
+                url: "stratum+tcp://pool:3333".to_string(),
+            })
+        })
+        .respond_with("disablepool", || {
+            Ok(response::DisablePool::Disabled {
+                idx: 1,
+                url: "stratum+tcp://pool:3333".to_string(),
+            })
+        })
+        .respond_with("pause", || Ok(response::Pause::Paused))
+        .respond_with("resume", || Ok(response::Resume::ResumedWithoutPool))
+        .build();
+    let receiver = command::Receiver::<ZeroTime>::builder(
+        handler,
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add_parameterless("chains", || async {
+        Ok(response::CustomResponse::new(
+            "CHAINS",
+            CustomStatusCode::CustomCommandOne,
+            vec![Chain {
+                idx: 0,
+                voltage: 8.5,
+            }],
+        )
+        .msg("Chain 1 is missing".to_string())
+        .warning())
+    })
+    .build()
+    .expect("BUG: cannot build receiver");
+
+    let response = test_utils::handle(&receiver, "enablepool", Some(json::json!(1))).await;
+    assert_eq!(response.status()["STATUS"], "I");
+    response.assert_status(49, "Pool 1:'stratum+tcp://pool:3333' already enabled");
+    let response = test_utils::handle(&receiver, "disablepool", Some(json::json!(1))).await;
+    assert_eq!(response.status()["STATUS"], "S");
+    response.assert_status(48, "Disabling pool 1:'stratum+tcp://pool:3333'");
+    let response = test_utils::handle(&receiver, "resume", None).await;
+    assert_eq!(response.status()["STATUS"], "W");
+    response.assert_status(219, "Mining resumed but no pool is enabled");
+    assert!(!response.is_error());
+    let response = test_utils::handle(&receiver, "pause", None).await;
+    assert_eq!(response.status()["STATUS"], "S");
+    // Commands without configured response fail
+    let response = test_utils::handle(&receiver, "ascenable", Some(json::json!(0))).await;
+    assert_eq!(response.status()["STATUS"], "E");
+
+    // Warnings keep the body of the response
+    let response = test_utils::handle(&receiver, "chains", None).await;
+    assert_eq!(response.status()["STATUS"], "W");
+    response.assert_status(301, "Chain 1 is missing");
+    assert_eq!(
+        response.section("CHAINS"),
+        &[json::json!({ "CHAIN": 0, "Voltage": 8.5 })][..]
+    );
+}
+
+#[tokio::test]
+async fn test_locate() {
+    let receiver = command::Receiver::<crate::support::UnixTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let handle = |command: json::Value| {
+        let request = command::Request::new(command);
+        let receiver = &receiver;
+        async move {
+            json::to_value(&receiver.handle(request, &command::Context::local()).await).unwrap()
+        }
+    };
+    let locate = |response: &json::Value| {
+        assert_eq!(response["STATUS"][0]["Code"], 210);
+        response["LOCATE"][0].clone()
+    };
+
+    let response = handle(json::json!({ "command": "locate" })).await;
+    assert_eq!(locate(&response), json::json!({ "Active": "N" }));
+
+    for parameter in &[json::json!(true), json::json!("true")] {
+        let response = handle(json::json!({ "command": "locate", "parameter": parameter })).await;
+        assert_eq!(
+            response["STATUS"][0]["Msg"].as_str(),
+            Some("Locate mode is active")
+        );
+        assert_eq!(locate(&response), json::json!({ "Active": "Y" }));
+    }
+
+    for parameter in &[json::json!(30), json::json!("30")] {
+        let response = handle(json::json!({ "command": "locate", "parameter": parameter })).await;
+        assert_eq!(
+            locate(&response),
+            json::json!({ "Active": "Y", "Remaining": 30 })
+        );
+    }
+
+    for parameter in &[json::json!(false), json::json!("false")] {
+        let response = handle(json::json!({ "command": "locate", "parameter": parameter })).await;
+        assert_eq!(
+            response["STATUS"][0]["Msg"].as_str(),
+            Some("Locate mode is inactive")
+        );
+        assert_eq!(locate(&response), json::json!({ "Active": "N" }));
+    }
+
+    // Only reporting the state is allowed in multi-command requests
+    let response = handle(json::json!({ "command": "summary+locate" })).await;
+    assert_eq!(response["locate"][0]["STATUS"][0]["Code"], 210);
+    let response = handle(json::json!({ "command": "summary+locate", "parameter": true })).await;
+    assert_eq!(response["locate"][0]["STATUS"][0]["Code"], 45);
+}
+
+#[test]
+fn test_locate_parameter() {
+    use crate::support::{parse_locate, LocateSetting};
+
+    let parse = |parameter: json::Value| parse_locate(Some(&parameter));
+    let error = |parameter: json::Value| {
+        parse(parameter)
+            .expect_err("BUG: invalid parameter accepted")
+            .msg()
+            .clone()
+    };
+
+    assert_eq!(parse_locate(None).ok(), Some(None));
+    assert_eq!(parse(json::json!(true)).ok(), Some(Some(LocateSetting::On)));
+    assert_eq!(
+        parse(json::json!(" TRUE ")).ok(),
+        Some(Some(LocateSetting::On))
+    );
+    assert_eq!(
+        parse(json::json!(false)).ok(),
+        Some(Some(LocateSetting::Off))
+    );
+    assert_eq!(
+        parse(json::json!("false")).ok(),
+        Some(Some(LocateSetting::Off))
+    );
+    assert_eq!(
+        parse(json::json!(60)).ok(),
+        Some(Some(LocateSetting::Timed(60)))
+    );
+    assert_eq!(
+        parse(json::json!("60")).ok(),
+        Some(Some(LocateSetting::Timed(60)))
+    );
+
+    assert_eq!(
+        error(json::json!(-5)),
+        "Invalid locate duration -5s - must be positive"
+    );
+    assert_eq!(
+        error(json::json!("0")),
+        "Invalid locate duration 0s - must be positive"
+    );
+    assert_eq!(
+        error(json::json!("blink")),
+        "Invalid locate parameter 'blink' - use true, false or duration in seconds"
+    );
+    assert_eq!(
+        error(json::json!(1.5)),
+        "Invalid locate parameter '1.5' - use true, false or duration in seconds"
+    );
+}
+
+/// Fan controller which starts in automatic mode
+#[derive(Default)]
+struct TestFanCtrlHandler {
+    speed: std::sync::Mutex<Option<u32>>,
+}
+
+impl TestFanCtrlHandler {
+    async fn handle_fan_ctrl_get(&self) -> command::Result<response::ext::FanCtrl> {
+        let speed = *self.speed.lock().unwrap();
+        Ok(response::ext::FanCtrl {
+            mode: match speed {
+                Some(_) => response::ext::FanCtrlMode::Manual,
+                None => response::ext::FanCtrlMode::Auto,
+            },
+            speed,
+            rpm: vec![speed.unwrap_or(50) * 60; 2],
+        })
+    }
+
+    async fn handle_fan_ctrl_set(
+        &self,
+        setting: crate::support::FanCtrlSetting,
+    ) -> command::Result<response::ext::FanCtrl> {
+        *self.speed.lock().unwrap() = match setting {
+            crate::support::FanCtrlSetting::Auto => None,
+            crate::support::FanCtrlSetting::Manual(speed) => Some(speed),
+        };
+        self.handle_fan_ctrl_get().await
+    }
+}
+
+#[tokio::test]
+async fn test_fan_ctrl() {
+    use crate::command::FANCTRL;
+
+    let handler = Arc::new(TestFanCtrlHandler::default());
+    let handle = |command: json::Value| {
+        let parse_fan_ctrl = crate::support::parse_fan_ctrl;
+        let mut custom_commands = commands![];
+        custom_commands.insert(
+            FANCTRL,
+            command!(FANCTRL: Parsed(parse_fan_ctrl) -> handler.handle_fan_ctrl_get | handle_fan_ctrl_set),
+        );
+        codec_roundtrip(command, custom_commands)
+    };
+
+    let response = handle(json::json!({ "command": "fanctrl" })).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 205,
+            "Msg": "Fan control",
+            "Description": "TestMiner v1.0",
+        }],
+        "FANCTRL": [{
+            "Mode": "Auto",
+            "RPM": [3000, 3000],
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    for (parameter, speed) in &[(json::json!(70), 70), (json::json!(" 100"), 100)] {
+        let response = handle(json::json!({
+            "command": "fanctrl",
+            "parameter": parameter
+        }))
+        .await;
+        assert_eq!(response["FANCTRL"][0]["Mode"], "Manual");
+        assert_eq!(response["FANCTRL"][0]["Speed"], *speed);
+    }
+
+    // Invalid parameters are rejected before the handler is invoked
+    for (parameter, code) in &[
+        (json::json!(101), 256),
+        (json::json!("-1"), 256),
+        (json::json!("fast"), 257),
+        (json::json!(70.5), 257),
+        (json::json!(true), 257),
+    ] {
+        let response = handle(json::json!({
+            "command": "fanctrl",
+            "parameter": parameter
+        }))
+        .await;
+        assert_eq!(response["STATUS"][0]["Code"], *code);
+    }
+    let response = handle(json::json!({ "command": "fanctrl" })).await;
+    assert_eq!(response["FANCTRL"][0]["Speed"], 100);
+
+    let response = handle(json::json!({
+        "command": "fanctrl",
+        "parameter": "AUTO"
+    }))
+    .await;
+    assert_eq!(response["FANCTRL"][0]["Mode"], "Auto");
+    assert!(response["FANCTRL"][0].get("Speed").is_none());
+
+    // Help tells that only setting of the fan control is privileged
+    let response = handle(json::json!({ "command": "help" })).await;
+    let fan_ctrl = response["HELP"]
+        .as_array()
+        .expect("BUG: missing help list")
+        .iter()
+        .find(|command| command["Command"] == FANCTRL)
+        .expect("BUG: missing fanctrl command");
+    assert_json_eq(
+        fan_ctrl,
+        &json::json!({
+            "Command": FANCTRL,
+            "Parameter": "Y",
+            "Privileged": "N",
+            "PrivilegedParameter": "Y",
+            "Description": "",
+        }),
+    );
+}
+
+struct TestTempCtrlHandler {
+    temp_ctrl: std::sync::Mutex<response::ext::TempCtrl>,
+}
+
+impl TestTempCtrlHandler {
+    async fn handle_temp_ctrl_get(&self) -> command::Result<response::ext::TempCtrl> {
+        Ok(self.temp_ctrl.lock().unwrap().clone())
+    }
+
+    async fn handle_temp_ctrl_set(
+        &self,
+        parameter: crate::support::TempCtrlParameter,
+    ) -> command::Result<response::ext::TempCtrl> {
+        let mut temp_ctrl = self.temp_ctrl.lock().unwrap();
+        let parameter = crate::support::TempCtrlParameter {
+            target: parameter.target.or(temp_ctrl.target),
+            hot: parameter.hot.or(temp_ctrl.hot),
+            dangerous: parameter.dangerous.or(temp_ctrl.dangerous),
+        };
+        parameter.check()?;
+
+        temp_ctrl.target = parameter.target;
+        temp_ctrl.hot = parameter.hot;
+        temp_ctrl.dangerous = parameter.dangerous;
+        Ok(temp_ctrl.clone())
+    }
+}
+
+#[tokio::test]
+async fn test_temp_ctrl() {
+    use crate::command::TEMPCTRL;
+
+    let handler = Arc::new(TestTempCtrlHandler {
+        temp_ctrl: std::sync::Mutex::new(response::ext::TempCtrl {
+            mode: response::ext::TempCtrlMode::Automatic,
+            target: Some(89.0),
+            hot: Some(100.0),
+            dangerous: Some(110.0),
+        }),
+    });
+    let handle = |command: json::Value| {
+        let parse_temp_ctrl = crate::support::TempCtrlParameter::parse;
+        let mut custom_commands = commands![];
+        custom_commands.insert(
+            TEMPCTRL,
+            command!(TEMPCTRL: Parsed(parse_temp_ctrl) -> handler.handle_temp_ctrl_get | handle_temp_ctrl_set),
+        );
+        codec_roundtrip(command, custom_commands)
+    };
+
+    let response = handle(json::json!({
+        "command": "tempctrl",
+        "parameter": "80,,105"
+    }))
+    .await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 200,
+            "Msg": "Temperature control",
+            "Description": "TestMiner v1.0",
+        }],
+        "TEMPCTRL": [{
+            "Mode": "Automatic",
+            "Target": 80.0,
+            "Hot": 100.0,
+            "Dangerous": 105.0,
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    // The ordering is checked against the current thresholds when some of them are omitted
+    for (parameter, code) in &[("100", 259), (",106", 260), ("90,95,95", 260), ("a,b", 258)] {
+        let response = handle(json::json!({
+            "command": "tempctrl",
+            "parameter": parameter
+        }))
+        .await;
+        assert_eq!(response["STATUS"][0]["Code"], *code);
+    }
+
+    let response = handle(json::json!({ "command": "tempctrl" })).await;
+    assert_eq!(response["TEMPCTRL"], expected["TEMPCTRL"]);
+}
+
+#[test]
+fn test_temp_ctrl_parameter() {
+    use crate::support::TempCtrlParameter;
+
+    let parse = |parameter: json::Value| TempCtrlParameter::parse(Some(&parameter));
+    let error = |parameter: json::Value| {
+        parse(parameter)
+            .expect_err("BUG: invalid parameter accepted")
+            .msg()
+            .clone()
+    };
+
+    assert_eq!(
+        parse(json::json!("80, 95, 105")).ok(),
+        Some(TempCtrlParameter {
+            target: Some(80.0),
+            hot: Some(95.0),
+            dangerous: Some(105.0),
+        })
+    );
+    assert_eq!(
+        parse(json::json!(",90")).ok(),
+        Some(TempCtrlParameter {
+            hot: Some(90.0),
+            ..Default::default()
+        })
+    );
+    assert_eq!(
+        parse(json::json!("75.5")).ok(),
+        Some(TempCtrlParameter {
+            target: Some(75.5),
+            ..Default::default()
+        })
+    );
+
+    assert!(TempCtrlParameter::parse(None).is_err());
+    for parameter in &["", ",,", "80,90,100,110", "hot", "NaN"] {
+        assert_eq!(
+            error(json::json!(parameter)),
+            format!(
+                "Invalid tempctrl parameter '{}' - use 'target,hot,dangerous'",
+                parameter
+            )
+        );
+    }
+    assert!(parse(json::json!(80)).is_err());
+    // Offending field is identified by the error
+    assert_eq!(
+        error(json::json!("90,90")),
+        "Target temperature 90 must be below 90"
+    );
+    assert_eq!(
+        error(json::json!("90,,85")),
+        "Target temperature 90 must be below 85"
+    );
+    assert_eq!(
+        error(json::json!(",100,100")),
+        "Hot temperature 100 must be below dangerous temperature 100"
+    );
+}
+
+#[test]
+fn test_set_config_parameter() {
+    use crate::support::parse_set_config;
+
+    let parse = |parameter: json::Value| parse_set_config(Some(&parameter)).ok();
+    assert_eq!(
+        parse(json::json!("log, 5")),
+        Some(("log".to_string(), "5".to_string()))
+    );
+    // Only the first comma separates the name
+    assert_eq!(
+        parse(json::json!("pools,a,b")),
+        Some(("pools".to_string(), "a,b".to_string()))
+    );
+    assert_eq!(
+        parse(json::json!("log,")),
+        Some(("log".to_string(), "".to_string()))
+    );
+    assert_eq!(parse(json::json!(" ,5")), None);
+    assert_eq!(parse(json::json!(5)), None);
+    assert!(parse_set_config(None).is_err());
+}
+
+#[tokio::test]
+async fn test_zero() {
+    let command: json::Value = json::json!({
+        "command": "zero",
+        "parameter": "all,false"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 97,
+            "Msg": "Zeroed all stats without summary",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    // The summary is returned along with the zero status
+    let command: json::Value = json::json!({
+        "command": "zero",
+        "parameter": "BestShare,true"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 96);
+    assert_eq!(
+        response["STATUS"][0]["Msg"],
+        "Zeroed BestShare stats with summary"
+    );
+    assert_eq!(response["SUMMARY"][0]["Best Share"], 0);
+
+    let command: json::Value = json::json!({ "command": "zero" });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 94);
+
+    let command: json::Value = json::json!({
+        "command": "zero",
+        "parameter": "all,maybe"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 95);
+}
+
+#[test]
+fn test_zero_parameter() {
+    use crate::support::{parse_zero, ZeroTarget};
+
+    let parse = |parameter: json::Value| parse_zero(Some(&parameter)).ok();
+
+    assert_eq!(parse(json::json!("all")), Some((ZeroTarget::All, false)));
+    assert_eq!(
+        parse(json::json!("All,True")),
+        Some((ZeroTarget::All, true))
+    );
+    assert_eq!(
+        parse(json::json!("bestshare, false")),
+        Some((ZeroTarget::BestShare, false))
+    );
+
+    assert!(parse_zero(None).is_err());
+    assert_eq!(parse(json::json!("")), None);
+    assert_eq!(parse(json::json!("summary,true")), None);
+    assert_eq!(parse(json::json!("all,")), None);
+    assert_eq!(parse(json::json!(1)), None);
+}
+
+#[test]
+fn test_pool_priority_parameter() {
+    use crate::support::parse_pool_priority;
+
+    let parse = |parameter: json::Value| parse_pool_priority(Some(&parameter));
+    let error = |parameter: json::Value| {
+        parse(parameter)
+            .expect_err("BUG: invalid parameter accepted")
+            .msg()
+            .clone()
+    };
+
+    assert_eq!(parse(json::json!("0,2,1")).ok(), Some(vec![0, 2, 1]));
+    assert_eq!(parse(json::json!("3, 1")).ok(), Some(vec![3, 1]));
+    assert_eq!(parse(json::json!(1)).ok(), Some(vec![1]));
+
+    assert!(parse_pool_priority(None).is_err());
+    // Empty list
+    assert_eq!(error(json::json!("")), "Missing pool id parameter");
+    // Trailing comma
+    assert_eq!(
+        error(json::json!("0,1,")),
+        "Invalid poolpriority parameter '0,1,'"
+    );
+    // Non-numeric and negative entries
+    assert_eq!(
+        error(json::json!("0,first")),
+        "Invalid poolpriority parameter '0,first'"
+    );
+    assert_eq!(
+        error(json::json!("-1")),
+        "Invalid poolpriority parameter '-1'"
+    );
+    assert_eq!(
+        error(json::json!(-1)),
+        "Invalid poolpriority parameter '-1'"
+    );
+    // Duplicates
+    assert_eq!(error(json::json!("1,0,1")), "Duplicate pool specified 1");
+}
+
+#[tokio::test]
+async fn test_shutdown() {
+    use std::sync::Mutex;
+
+    let executed = Arc::new(Mutex::new(Vec::new()));
+    let shutdown_executed = executed.clone();
+    let recording = Arc::new(RecordingObserver::default());
+    let receiver = command::Receiver::<crate::support::UnixTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_shutdown_handler(Box::new(move |kind| {
+        let executed = shutdown_executed.clone();
+        Box::pin(async move { executed.lock().unwrap().push(kind) })
+    }))
+    .with_observer(recording.clone());
+
+    for (command, status, kind) in &[
+        ("quit", "BYE", command::ShutdownKind::Quit),
+        ("restart", "RESTART", command::ShutdownKind::Restart),
+    ] {
+        let request = command::Request::new(json::json!({ "command": command }));
+        let (response, action) = receiver
+            .handle_deferred(request, &command::Context::local())
+            .await;
+        let expected = json::json!({
+            "STATUS": status,
+            "id": 1
+        });
+        assert_json_eq(&json::to_value(&response).unwrap(), &expected);
+
+        // The shutdown is performed only when the deferred action is executed
+        assert!(executed.lock().unwrap().is_empty());
+        action.expect("BUG: missing shutdown action").await;
+        assert_eq!(executed.lock().unwrap().pop(), Some(*kind));
+    }
+    // Observers are notified although the commands bypass the handler
+    assert_eq!(
+        recording.events(),
+        vec![
+            "request quit",
+            "response quit 0",
+            "request restart",
+            "response restart 0"
+        ]
+    );
+
+    // Privileged commands are refused in batched mode
+    let request = command::Request::new(json::json!({ "command": "version+quit" }));
+    let (response, action) = receiver
+        .handle_deferred(request, &command::Context::local())
+        .await;
+    let response = json::to_value(&response).unwrap();
+    assert_eq!(response["quit"][0]["STATUS"][0]["Code"], 45);
+    assert!(action.is_none());
+
+    // Shutdown commands are not available without the shutdown handler
+    let command: json::Value = json::json!({ "command": "quit" });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 14);
+}
+
+#[tokio::test]
+async fn test_asc() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let response = test_utils::handle(&receiver, "asc", Some(json::json!(0))).await;
+    response.assert_status(106, "ASC0");
+    assert_eq!(response.section("ASC")[0]["ASC"], 0);
+
+    // Missing parameter or -1 selects all devices
+    for parameter in &[None, Some(json::json!(-1)), Some(json::json!("-1"))] {
+        let response = test_utils::handle(&receiver, "asc", parameter.clone()).await;
+        assert_eq!(response.code(), 106);
+        assert_eq!(response.section("ASC").len(), 1);
+    }
+
+    for (parameter, code) in &[
+        (json::json!(1), 107),
+        (json::json!(-2), 107),
+        (json::json!("x"), 268),
+    ] {
+        let response = test_utils::handle(&receiver, "asc", Some(parameter.clone())).await;
+        assert_eq!(response.code(), *code);
+    }
+}
+
+#[tokio::test]
+async fn test_asc_enable_disable() {
+    let command: json::Value = json::json!({
+        "command": "ascdisable",
+        "parameter": 0
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 111,
+            "Msg": "ASC 0 set disable flag",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    let command: json::Value = json::json!({
+        "command": "ascenable",
+        "parameter": "0"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "I",
+            "When": 0,
+            "Code": 108,
+            "Msg": "ASC 0 already enabled",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    for command in &["ascenable", "ascdisable"] {
+        let request: json::Value = json::json!({
+            "command": command,
+            "parameter": 1
+        });
+        let response = codec_roundtrip(request, None).await;
+        assert_eq!(response["STATUS"][0]["Code"], 107);
+
+        let request: json::Value = json::json!({ "command": command });
+        let response = codec_roundtrip(request, None).await;
+        assert_eq!(response["STATUS"][0]["Code"], 15);
+
+        // Write commands are refused in batched mode
+        let request: json::Value = json::json!({ "command": format!("asccount+{}", command) });
+        let response = codec_roundtrip(request, None).await;
+        assert_eq!(response[command][0]["STATUS"][0]["Code"], 45);
+    }
+}
+
+#[tokio::test]
+async fn test_integer_parameter() {
+    use crate::support::ValueExt as _;
+
+    // Numeric strings are accepted wherever an integer parameter is expected
+    for (parameter, code) in &[
+        (json::json!("0"), 108),
+        (json::json!(" 7 "), 107),
+        (json::json!("-1"), 107),
+        (json::json!("abc"), 268),
+        (json::json!(0.5), 268),
+    ] {
+        let request = json::json!({ "command": "ascenable", "parameter": parameter });
+        let response = codec_roundtrip(request, None).await;
+        assert_eq!(
+            response["STATUS"][0]["Code"], *code,
+            "parameter {}",
+            parameter
+        );
+    }
+
+    assert_eq!(json::json!(" 7 ").to_i32(), Some(7));
+    assert_eq!(json::json!("-1").to_i32(), Some(-1));
+    assert_eq!(json::json!("abc").to_i32(), None);
+    assert_eq!(json::json!(1.5).to_i32(), None);
+    // Values out of range are not truncated
+    assert_eq!(json::json!(u64::MAX).to_i32(), None);
+    assert_eq!(json::json!("4294967296").to_i32(), None);
+}
+
+#[test]
+fn test_checked_conversions() {
+    use crate::support::{RangeExt as _, ValueExt as _};
+
+    // Boundaries of each type
+    assert_eq!(json::json!(i32::MIN).as_checked_i32(), Ok(i32::MIN));
+    assert_eq!(json::json!(i32::MAX).as_checked_i32(), Ok(i32::MAX));
+    assert!(json::json!(i64::from(i32::MAX) + 1)
+        .as_checked_i32()
+        .is_err());
+    assert_eq!(json::json!(u32::MAX).as_checked_u32(), Ok(u32::MAX));
+    assert!(json::json!(-1).as_checked_u32().is_err());
+    assert!(json::json!(u64::from(u32::MAX) + 1)
+        .as_checked_u32()
+        .is_err());
+    assert_eq!(json::json!(u64::MAX).as_checked_u64(), Ok(u64::MAX));
+    assert!(json::json!(-1).as_checked_u64().is_err());
+
+    // Fractional numbers are not accepted where an integer is expected
+    assert!(json::json!(1.5).as_checked_i32().is_err());
+    assert!(json::json!(1.5).as_checked_u32().is_err());
+    assert!(json::json!(1.5).as_checked_u64().is_err());
+    assert_eq!(json::json!(1.5).as_checked_f64(), Ok(1.5));
+    assert_eq!(json::json!(2).as_checked_f64(), Ok(2.0));
+
+    // Numeric strings
+    assert_eq!(json::json!(" 42 ").as_checked_u32(), Ok(42));
+    assert_eq!(json::json!("-42").as_checked_i32(), Ok(-42));
+    assert_eq!(
+        json::json!("18446744073709551615").as_checked_u64(),
+        Ok(u64::MAX)
+    );
+    assert_eq!(json::json!("0.25").as_checked_f64(), Ok(0.25));
+    assert!(json::json!("1.5").as_checked_i32().is_err());
+    assert!(json::json!("abc").as_checked_f64().is_err());
+
+    // Ranges are inclusive
+    assert_eq!(json::json!(1).as_checked_u32().in_range(1, 10), Ok(1));
+    assert_eq!(json::json!("10").as_checked_u32().in_range(1, 10), Ok(10));
+    assert_eq!(
+        json::json!(0.5).as_checked_f64().in_range(0.0, 1.0),
+        Ok(0.5)
+    );
+    let error = json::json!(11)
+        .as_checked_u32()
+        .in_range(1, 10)
+        .expect_err("value out of range");
+    assert_eq!(error.expected, "value from 1 to 10");
+    assert_eq!(error.got.as_deref(), Some("11"));
+    // Conversion errors are passed through
+    let error = json::json!("abc")
+        .as_checked_i32()
+        .in_range(0, 1)
+        .expect_err("invalid value");
+    assert_ne!(error.expected, "value from 0 to 1");
+
+    let error: response::Error = json::json!(-1)
+        .as_checked_i32()
+        .in_range(0, 1)
+        .expect_err("value out of range")
+        .into();
+    assert_eq!(
+        error.msg(),
+        "Invalid parameter '-1' - expected value from 0 to 1"
+    );
+}
+
+#[tokio::test]
+async fn test_asc_set() {
+    let command: json::Value = json::json!({
+        "command": "ascset",
+        "parameter": "0,freq,650"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 118,
+            "Msg": "ASC 0 set OK",
+            "Description": "TestMiner v1.0",
+        }],
+        "ASCSET": [{
+            "Option": "freq",
+            "Value": "650",
+            "Order": 1,
+            "Result": "Applied",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    let command: json::Value = json::json!({
+        "command": "ascset",
+        "parameter": "0,fan,50"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "E",
+            "When": 0,
+            "Code": 254,
+            "Msg": "ASC 0 invalid option 'fan' - supported options are freq",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    // Missing value
+    let command: json::Value = json::json!({
+        "command": "ascset",
+        "parameter": "0,freq"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 119);
+
+    // Empty option
+    let command: json::Value = json::json!({
+        "command": "ascset",
+        "parameter": "0,,650"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 115);
+
+    // Non-numeric index
+    let command: json::Value = json::json!({
+        "command": "ascset",
+        "parameter": "zero,freq,650"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 15);
+}
+
+#[tokio::test]
+async fn test_debug() {
+    let command: json::Value = json::json!({
+        "command": "debug",
+        "parameter": "D"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 79,
+            "Msg": "Debug settings",
+            "Description": "TestMiner v1.0",
+        }],
+        "DEBUG": [{
+            "Silent": "N",
+            "Quiet": "N",
+            "Verbose": "Y",
+            "Debug": "Y",
+            "RPCProto": "N",
+            "PerDevice": "N",
+            "WorkTime": "N",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    // Without a flag only the settings are reported
+    let command: json::Value = json::json!({ "command": "debug" });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["DEBUG"][0]["Debug"], "N");
+
+    let command: json::Value = json::json!({
+        "command": "debug",
+        "parameter": "loud"
+    });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 255);
+    assert_eq!(
+        response["STATUS"][0]["Msg"],
+        "Invalid debug flag 'loud' - valid flags are silent, quiet, verbose, debug, rpcproto, \
+         perdevice, worktime, normal"
+    );
+
+    let command: json::Value = json::json!({ "command": "version+debug" });
+    let response = codec_roundtrip(command, None).await;
+    assert_eq!(response["debug"][0]["STATUS"][0]["Code"], 45);
+}
+
+#[test]
+fn test_debug_settings() {
+    use crate::support::{parse_debug_flag, DebugFlag, DebugSettings};
+
+    let parse = |parameter: json::Value| parse_debug_flag(Some(&parameter)).ok();
+    assert_eq!(parse(json::json!("silent")), Some(Some(DebugFlag::Silent)));
+    assert_eq!(parse(json::json!("R")), Some(Some(DebugFlag::RpcProto)));
+    assert_eq!(
+        parse(json::json!("WorkTime")),
+        Some(Some(DebugFlag::WorkTime))
+    );
+    assert_eq!(parse(json::json!("")), None);
+    assert_eq!(parse(json::json!("sil")), None);
+    assert_eq!(parse_debug_flag(None).ok(), Some(None));
+
+    let mut settings = DebugSettings::default();
+    settings.apply(DebugFlag::Quiet);
+    assert!(settings.quiet);
+    // Increasing verbosity leaves the quiet mode
+    settings.apply(DebugFlag::RpcProto);
+    assert!(settings.rpc_proto && !settings.quiet);
+    settings.apply(DebugFlag::RpcProto);
+    assert!(!settings.rpc_proto);
+
+    settings.apply(DebugFlag::Silent);
+    settings.apply(DebugFlag::Debug);
+    settings.apply(DebugFlag::Normal);
+    assert_eq!(
+        settings,
+        DebugSettings {
+            silent: true,
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_add_pool() {
+    let command: json::Value = json::json!({
+        "command": "addpool",
+        "parameter": "stratum+tcp://pool.example.com:3333,user.worker,x"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "S",
+            "When": 0,
+            "Code": 55,
+            "Msg": "Added pool 1: 'stratum+tcp://pool.example.com:3333'",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+
+    let command: json::Value = json::json!({
+        "command": "addpool",
+        "parameter": "stratum+tcp://pool.example.com:3333,user.worker"
+    });
+    let response = codec_roundtrip(command, None).await;
+    let expected = json::json!({
+        "STATUS": [{
+            "STATUS": "E",
+            "When": 0,
+            "Code": 53,
+            "Msg": "Invalid addpool details 'stratum+tcp://pool.example.com:3333,user.worker'",
+            "Description": "TestMiner v1.0",
+        }],
+        "id": 1
+    });
+    assert_json_eq(&response, &expected);
+}
+
+#[test]
+fn test_add_pool_parameter() {
+    use crate::support::AddPoolParameter;
+
+    let parse = |parameter: &str| AddPoolParameter::parse(Some(&json::json!(parameter)));
+    let expected = |url: &str, user: &str, password: &str| {
+        Some(AddPoolParameter {
+            url: url.to_string(),
+            user: user.to_string(),
+            password: password.to_string(),
+        })
+    };
+
+    assert_eq!(
+        parse("stratum+tcp://pool:3333,user,x").ok(),
+        expected("stratum+tcp://pool:3333", "user", "x")
+    );
+    // Escaped commas and backslashes
+    assert_eq!(
+        parse(r"stratum+tcp://pool:3333,us\\er,pa\,ss\,word").ok(),
+        expected("stratum+tcp://pool:3333", r"us\er", "pa,ss,word")
+    );
+    // Password takes the rest of the parameter
+    assert_eq!(
+        parse("stratum+tcp://pool:3333,user,pa,ss").ok(),
+        expected("stratum+tcp://pool:3333", "user", "pa,ss")
+    );
+    // Empty password is a present field
+    assert_eq!(
+        parse("stratum+tcp://pool:3333,user,").ok(),
+        expected("stratum+tcp://pool:3333", "user", "")
+    );
+    // Missing third field
+    assert!(parse("stratum+tcp://pool:3333,user").is_err());
+    // Escaped delimiter doesn't separate fields
+    assert!(parse(r"stratum+tcp://pool:3333,user\,x").is_err());
+    assert!(AddPoolParameter::parse(Some(&json::json!(1))).is_err());
+    assert!(AddPoolParameter::parse(None).is_err());
+
+    assert_eq!(
+        parse(r"stratum+tcp://pool:3333,us\,er,secret")
+            .map(|parameter| parameter.redacted())
+            .ok(),
+        Some(r"stratum+tcp://pool:3333,us\,er,***".to_string())
+    );
+}
+
+#[test]
+fn test_asc_set_parameter() {
+    use crate::support::AscSetParameter;
+
+    let parse = |parameter: &str| AscSetParameter::parse(Some(&json::json!(parameter)));
+    let option = |name: &str, value: Option<&str>| (name.to_string(), value.map(str::to_string));
+
+    assert_eq!(
+        parse("0,freq=650:volt=8.9").ok(),
+        Some(AscSetParameter {
+            idx: 0,
+            options: vec![option("freq", Some("650")), option("volt", Some("8.9"))],
+        })
+    );
+    assert_eq!(
+        parse("1,freq,600").ok(),
+        Some(AscSetParameter {
+            idx: 1,
+            options: vec![option("freq", Some("600"))],
+        })
+    );
+    // Missing value
+    assert_eq!(
+        parse("1,help").ok(),
+        Some(AscSetParameter {
+            idx: 1,
+            options: vec![option("help", None)],
+        })
+    );
+    assert_eq!(
+        parse("1,help,").ok(),
+        Some(AscSetParameter {
+            idx: 1,
+            options: vec![option("help", None)],
+        })
+    );
+    // Empty option
+    assert!(parse("0,,600").is_err());
+    assert!(parse("0, ").is_err());
+    // Non-numeric index
+    assert!(parse("x,freq=600").is_err());
+    assert!(parse("first,freq,600").is_err());
+    assert!(parse("0").is_err());
+    assert!(parse("0,freq=600:volt").is_err());
+    assert!(AscSetParameter::parse(None).is_err());
+}
+
+/// Observer recording all notifications as `<event> <command> [<code>]`
+#[derive(Default)]
+struct RecordingObserver {
+    events: std::sync::Mutex<Vec<String>>,
+}
+
+impl RecordingObserver {
+    fn events(&self) -> Vec<String> {
+        self.events.lock().expect("BUG: poisoned events").clone()
+    }
+}
+
+impl Observer for RecordingObserver {
+    fn on_request(
+        &self,
+        context: &command::Context,
+        command: &str,
+        parameter: Option<&json::Value>,
+    ) {
+        assert_eq!(context.peer_addr(), None);
+        let event = match parameter {
+            Some(parameter) => format!("request {} {}", command, parameter),
+            None => format!("request {}", command),
+        };
+        self.events
+            .lock()
+            .expect("BUG: poisoned events")
+            .push(event);
+    }
+
+    fn on_response(
+        &self,
+        _context: &command::Context,
+        command: &str,
+        status: &observer::ResponseStatus,
+        _duration: Duration,
+    ) {
+        self.events
+            .lock()
+            .expect("BUG: poisoned events")
+            .push(format!("response {} {}", command, status.code));
+    }
+}
+
+/// Observer which panics on every notification
+struct PanickingObserver;
+
+impl Observer for PanickingObserver {
+    fn on_request(&self, _: &command::Context, _: &str, _: Option<&json::Value>) {
+        panic!("on_request");
+    }
+
+    fn on_response(
+        &self,
+        _: &command::Context,
+        _: &str,
+        _: &observer::ResponseStatus,
+        _: Duration,
+    ) {
+        panic!("on_response");
+    }
+}
+
+#[tokio::test]
+async fn test_observer() {
+    let recording = Arc::new(RecordingObserver::default());
+    let receiver = build_custom_receiver()
+        .build()
+        .expect("BUG: cannot build receiver")
+        .with_observer(Arc::new(PanickingObserver))
+        .with_observer(recording.clone());
+
+    // Panicking observer affects neither the other observers nor the response
+    let response = handle_custom(&receiver, json::json!({ "command": "version" })).await;
+    assert_eq!(response["STATUS"][0]["Code"], 22);
+    assert_eq!(
+        recording.events(),
+        vec!["request version", "response version 22"]
+    );
+
+    let response = handle_custom(
+        &receiver,
+        json::json!({ "command": "chains+check", "parameter": "pools" }),
+    )
+    .await;
+    assert_eq!(response["check"][0]["STATUS"][0]["Code"], 72);
+    // Commands of a batch are handled concurrently so only their own events are ordered. The
+    // shared parameter is redacted.
+    let events = recording.events();
+    assert_eq!(events.len(), 6);
+    for command in &["chains", "check"] {
+        let request = format!("request {} \"***\"", command);
+        let position = |event: &str| events.iter().position(|other| other.as_str() == event);
+        let response = events
+            .iter()
+            .find(|event| event.starts_with(&format!("response {} ", command)))
+            .expect("BUG: missing response event");
+        assert!(
+            position(&request).expect("BUG: missing request event") < position(response).unwrap()
+        );
+    }
+    assert!(events.contains(&"response chains 301".to_string()));
+
+    // Unknown command is observed with its error
+    handle_custom(&receiver, json::json!({ "command": "unknown" })).await;
+    assert_eq!(recording.events()[7], "response unknown 14");
+
+    // The token of `auth` command is redacted
+    let recording = Arc::new(RecordingObserver::default());
+    let receiver = build_custom_receiver()
+        .build()
+        .expect("BUG: cannot build receiver")
+        .with_tokens(Tokens::new().add("secret", Privilege::Write))
+        .with_observer(recording.clone());
+    let context = command::Context::local().with_session(Arc::new(Session::new()));
+    let request = json::json!({ "command": "auth", "parameter": "secret" });
+    receiver
+        .handle(command::Request::new(request), &context)
+        .await;
+    assert_eq!(
+        recording.events(),
+        vec!["request auth \"***\"", "response auth 220"]
+    );
+}
+
+#[tokio::test]
+async fn test_fixed_time() {
+    let build = || {
+        command::Receiver::<crate::support::FixedTime>::new(
+            handler::BasicTest::default(),
+            "TestMiner".to_string(),
+            "v1.0".to_string(),
+            None,
+        )
+    };
+    let handle = |receiver: command::Receiver<crate::support::FixedTime>| async move {
+        let response = receiver
+            .handle(
+                command::Request::new(json::json!({ "command": "version" })),
+                &command::Context::local(),
+            )
+            .await;
+        json::to_string(&response).expect("BUG: cannot serialize response")
+    };
+
+    // Default timestamp has whole seconds only
+    assert_eq!(
+        handle(build()).await,
+        r#"{"STATUS":[{"STATUS":"S","When":1577836800,"Code":22,"Msg":"TestMiner versions","Description":"TestMiner v1.0"}],"VERSION":[{"TestMiner":"v1.0","API":"3.7"}],"id":1}"#
+    );
+    assert_eq!(
+        handle(build().with_millisecond_timestamps()).await,
+        r#"{"STATUS":[{"STATUS":"S","When":1577836800,"WhenMs":1577836800250,"Code":22,"Msg":"TestMiner versions","Description":"TestMiner v1.0"}],"VERSION":[{"TestMiner":"v1.0","API":"3.7"}],"id":1}"#
+    );
+}
+
+#[tokio::test]
+async fn test_mock_handler() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        MockHandler::builder()
+            .response("pools", response::Pools { list: vec![] })
+            .error("summary", || {
+                response::ErrorCode::HardwareError.with_context("no chains")
+            })
+            .response("edevs", response::Pools { list: vec![] })
+            .build(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+
+    test_utils::handle(&receiver, "pools", None)
+        .await
+        .assert_status(7, "0 Pool(s)");
+    test_utils::handle(&receiver, "summary", None)
+        .await
+        .assert_status(269, "Hardware error - no chains");
+    // Command without configured response fails
+    let response = test_utils::handle(&receiver, "devs", None).await;
+    assert!(response.is_error());
+    assert_eq!(response.code(), 264);
+    // Response of a wrong type is reported instead of panicking
+    test_utils::handle(&receiver, "edevs", None)
+        .await
+        .assert_status(
+            270,
+            "Internal error - mock response of 'edevs' has type \
+             ii_cgminer_api::response::Pools instead of ii_cgminer_api::response::Devs",
+        );
+
+    let response = test_utils::handle(&receiver, "pools+summary", None).await;
+    assert!(response.command("pools").section("POOLS").is_empty());
+    assert!(response.command("summary").is_error());
+}
+
+/// Component providing only the tuner commands
+struct Tuner;
+
+#[async_trait::async_trait]
+impl composite::TunerHandler for Tuner {
+    async fn handle_tuner_status(&self) -> command::Result<response::TunerStatus> {
+        Ok(response::TunerStatus {
+            power_limit: 900,
+            dynamic_power_scaling: response::Bool::Y,
+            chains: vec![],
+        })
+    }
+}
+
+/// Component providing only extended commands of a thermal controller
+struct Cooling;
+
+impl Cooling {
+    async fn handle_fans(&self) -> command::Result<response::ext::Fans> {
+        Ok(response::ext::Fans {
+            list: vec![response::ext::Fan {
+                idx: 0,
+                id: 1,
+                speed: 70,
+                rpm: 3000,
+            }],
+        })
+    }
+}
+
+impl composite::CommandProvider for Cooling {
+    fn commands(self: Arc<Self>) -> command::Map {
+        use command::FANS;
+
+        let handler = self;
+        commands![(FANS: ParameterLess -> handler.handle_fans, "Fan speeds")]
+    }
+}
+
+/// Component providing extended command which collides with a standard one
+struct Locator;
+
+impl Locator {
+    async fn handle_locate(&self) -> command::Result<response::Locate> {
+        Ok(response::Locate {
+            active: response::Bool::N,
+            remaining: None,
+        })
+    }
+}
+
+impl composite::CommandProvider for Locator {
+    fn commands(self: Arc<Self>) -> command::Map {
+        const LOCATE: &str = "locate";
+
+        let handler = self;
+        commands![(LOCATE: ParameterLess -> handler.handle_locate)]
+    }
+}
+
+#[tokio::test]
+async fn test_composite_handler() {
+    let base = MockHandler::builder()
+        .response("pools", response::Pools { list: vec![] })
+        .error("devs", || response::ErrorCode::HardwareError.into())
+        .error("tunerstatus", || response::ErrorCode::HardwareError.into())
+        .build();
+    let devices = MockHandler::builder()
+        .error("devs", || {
+            response::ErrorCode::HardwareError.with_context("devices")
+        })
+        .build();
+    let handler = composite::CompositeBuilder::with_base(base)
+        .devices(devices)
+        .tuner(Tuner)
+        .build();
+    let receiver = command::Receiver::<ZeroTime>::new(
+        handler,
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+
+    // Groups which have not been replaced are handled by the base
+    test_utils::handle(&receiver, "pools", None)
+        .await
+        .assert_status(7, "0 Pool(s)");
+    assert_eq!(
+        test_utils::handle(&receiver, "summary", None).await.code(),
+        264
+    );
+    // The last provider bound to a group wins
+    test_utils::handle(&receiver, "devs", None)
+        .await
+        .assert_status(269, "Hardware error - devices");
+    let response = test_utils::handle(&receiver, "tunerstatus", None).await;
+    assert!(!response.is_error());
+    assert_eq!(response.section("TUNERSTATUS")[0]["PowerLimit"], 900);
+
+    // Components can be shared by multiple groups
+    let core = Arc::new(
+        MockHandler::builder()
+            .response("pools", response::Pools { list: vec![] })
+            .build(),
+    );
+    let handler = composite::CompositeHandler::builder()
+        .pools(core.clone())
+        .miner(core.clone())
+        .devices(core)
+        .tuner(Tuner)
+        .build();
+    let receiver = command::Receiver::<ZeroTime>::new(
+        handler,
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let response = test_utils::handle(&receiver, "pools+tunerstatus", None).await;
+    assert!(response.command("pools").section("POOLS").is_empty());
+    assert!(!response.command("tunerstatus").is_error());
+
+    // Extended commands are registered together with the groups
+    let receiver = composite::CompositeBuilder::with_base(MockHandler::builder().build())
+        .tuner(Tuner)
+        .commands(Arc::new(Cooling))
+        .receiver_builder::<ZeroTime>("TestMiner".to_string(), "v1.0".to_string())
+        .build()
+        .expect("BUG: duplicate command");
+    let response = test_utils::handle(&receiver, "fans+tunerstatus", None).await;
+    assert_eq!(response.command("fans").section("FANS")[0]["RPM"], 3000);
+    assert!(!response.command("tunerstatus").is_error());
+    let response = test_utils::handle(&receiver, "check", Some(json::json!("fans"))).await;
+    assert_eq!(response.section("CHECK")[0]["Exists"], "Y");
+
+    // Standard commands cannot be replaced by extended ones
+    let result = composite::CompositeBuilder::with_base(MockHandler::builder().build())
+        .commands(Arc::new(Cooling))
+        .commands(Arc::new(Locator))
+        .receiver_builder::<ZeroTime>("TestMiner".to_string(), "v1.0".to_string())
+        .build();
+    assert_eq!(result.err(), Some(command::DuplicateCommand("locate")));
+}
+
+#[tokio::test]
+async fn test_request_body() {
+    let request = command::Request::new(json::json!({
+        "command": "pools",
+        "parameter": null,
+        "token": "secret"
+    }));
+    let body = request.body().expect("BUG: missing request body");
+    assert_eq!(body.command, "pools");
+    // Explicit `null` is passed to the handler as is
+    assert_eq!(body.parameter, Some(json::Value::Null));
+    assert_eq!(body.extras.get("token"), Some(&json::json!("secret")));
+    assert!(!format!("{:?}", request).contains("secret"));
+
+    // Fields other than the command and parameter are available in the dispatch context
+    #[derive(Default)]
+    struct ExtrasObserver(std::sync::Mutex<Vec<json::Map<String, json::Value>>>);
+
+    impl Observer for ExtrasObserver {
+        fn on_request(&self, context: &command::Context, _: &str, _: Option<&json::Value>) {
+            self.0.lock().unwrap().push(context.extras().clone());
+        }
+
+        fn on_response(
+            &self,
+            _: &command::Context,
+            _: &str,
+            _: &observer::ResponseStatus,
+            _: Duration,
+        ) {
+        }
+    }
+
+    let extras = Arc::new(ExtrasObserver::default());
+    let receiver = build_custom_receiver()
+        .build()
+        .expect("BUG: cannot build receiver")
+        .with_observer(extras.clone());
+    handle_custom(&receiver, json::json!({ "command": "version", "id": 7 })).await;
+    // The token is not passed to the context
+    handle_custom(
+        &receiver,
+        json::json!({ "command": "version", "id": 8, "token": "secret" }),
+    )
+    .await;
+    assert_eq!(
+        extras.0.lock().unwrap().as_slice(),
+        &[
+            json::json!({ "id": 7 }).as_object().unwrap().clone(),
+            json::json!({ "id": 8 }).as_object().unwrap().clone()
+        ]
+    );
+
+    // Request without a string command
+    for request in &[
+        json::json!({ "command": 1 }),
+        json::json!({ "parameter": "pools" }),
+        json::json!(["pools"]),
+    ] {
+        assert!(command::Request::new(request.clone()).body().is_none());
+        let response = handle_custom(&receiver, request.clone()).await;
+        assert_eq!(response["STATUS"][0]["Code"], 24);
+    }
+}
+
+#[tokio::test]
+async fn test_handle_raw() {
+    let receiver = build_custom_receiver()
+        .build()
+        .expect("BUG: cannot build receiver");
+    let handle_raw = |data: &[u8]| {
+        let receiver = &receiver;
+        let data = data.to_vec();
+        async move {
+            let response = receiver.handle_raw(&data, &command::Context::local()).await;
+            assert_eq!(response.last(), Some(&0), "BUG: missing NUL terminator");
+            String::from_utf8(response[..response.len() - 1].to_vec())
+                .expect("BUG: invalid UTF-8 response")
+        }
+    };
+
+    // Request of `bosminer_monitor.lua` and `cgminer_monitor.lua` of Braiins OS and the request
+    // of `client::Client` as they are written to the socket
+    let response: json::Value = json::from_str(&handle_raw(br#"{ "command":"devs+pools" }"#).await)
+        .expect("BUG: invalid JSON response");
+    assert_eq!(response["pools"][0]["STATUS"][0]["Code"], 7);
+    let response: json::Value = json::from_str(
+        &handle_raw(json::json!({ "command": "version" }).to_string().as_bytes()).await,
+    )
+    .expect("BUG: invalid JSON response");
+    assert_eq!(response["STATUS"][0]["Code"], 22);
+    assert_eq!(response["VERSION"][0]["TestMiner"], "v1.0");
+
+    // Plain-text requests in the `command|parameter` form of CGMiner `API-README` are responded
+    // in the same format
+    for data in &[&b"version"[..], &b"version|"[..]] {
+        let response = handle_raw(data).await;
+        assert!(
+            response.starts_with("STATUS=S,When=0,Code=22,"),
+            "request {:?}",
+            data
+        );
+        assert!(response.ends_with("VERSION,TestMiner=v1.0,API=3.7|"));
+    }
+
+    // Malformed requests
+    let response: json::Value =
+        json::from_str(&handle_raw(b"{\"command\":").await).expect("BUG: invalid JSON response");
+    assert_eq!(response["STATUS"][0]["Code"], 23);
+    let response: json::Value =
+        json::from_str(&handle_raw(b"\0\n").await).expect("BUG: invalid JSON response");
+    assert_eq!(response["STATUS"][0]["Code"], 23);
+}
+
+fn build_filtered_receiver(command_filter: command::CommandFilter) -> command::Receiver<ZeroTime> {
+    command::Receiver::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_command_filter(command_filter)
+}
+
+#[tokio::test]
+async fn test_command_filter() {
+    let deny = build_filtered_receiver(command::CommandFilter::deny(vec!["stats", "estats"]));
+    let allow = build_filtered_receiver(command::CommandFilter::allow(vec!["pools", "coin"]));
+
+    for (receiver, exposed, excluded) in &[
+        (&deny, "pools", "stats"),
+        (&deny, "version", "estats"),
+        (&allow, "pools", "version"),
+        (&allow, "coinmine", "stats"),
+    ] {
+        let response = test_utils::handle(receiver, exposed, None).await;
+        assert!(!response.is_error(), "BUG: '{}' is not exposed", exposed);
+        // Excluded command is the same as unknown command
+        test_utils::handle(receiver, excluded, None)
+            .await
+            .assert_status(14, "Invalid command");
+        let response = test_utils::handle(receiver, &format!("pools+{}", excluded), None).await;
+        assert_eq!(response.command(excluded).code(), 14);
+    }
+
+    // The `check` command cannot be excluded while it reports excluded commands as unknown
+    let check =
+        |receiver, command: &str| test_utils::handle(receiver, "check", Some(json::json!(command)));
+    for (receiver, command, exists) in &[
+        (&deny, "stats", "N"),
+        (&deny, "summary", "Y"),
+        (&allow, "summary", "N"),
+        (&allow, "coinmine", "Y"),
+        (&allow, "check", "Y"),
+    ] {
+        let response = check(*receiver, command).await;
+        assert_eq!(
+            response.section("CHECK")[0]["Exists"],
+            *exists,
+            "{}",
+            command
+        );
+    }
+
+    // Only the exposed commands are listed
+    let response = test_utils::handle(&allow, "help", None).await;
+    assert_eq!(response.code(), 14);
+    let response = test_utils::handle(&deny, "help", None).await;
+    let names: Vec<_> = response
+        .section("HELP")
+        .iter()
+        .map(|command| command["Command"].as_str().unwrap())
+        .collect();
+    assert!(names.contains(&"check") && names.contains(&"pools"));
+    assert!(!names.contains(&"stats") && !names.contains(&"estats"));
+}
+
+#[tokio::test]
+async fn test_set_version_info() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+
+    let response = test_utils::handle(&receiver, "version", None).await;
+    response.assert_status(22, "TestMiner versions");
+    assert_eq!(response.status()["Description"], "TestMiner v1.0");
+    assert_eq!(response.section("VERSION")[0]["TestMiner"], "v1.0");
+
+    receiver.set_version_info(
+        response::VersionInfo::new("TestMiner".to_string(), "v1.1-eco".to_string())
+            .field("Profile".to_string(), "eco".to_string()),
+    );
+    let response = test_utils::handle(&receiver, "version", None).await;
+    assert_eq!(response.status()["Description"], "TestMiner v1.1-eco");
+    assert_eq!(response.section("VERSION")[0]["TestMiner"], "v1.1-eco");
+    assert_eq!(response.section("VERSION")[0]["Profile"], "eco");
+
+    // The signature is updated in messages of all responses too
+    receiver.set_version_info(response::VersionInfo::new(
+        "OtherMiner".to_string(),
+        "v2.0".to_string(),
+    ));
+    let response = test_utils::handle(&receiver, "version", None).await;
+    response.assert_status(22, "OtherMiner versions");
+    assert_eq!(response.status()["Description"], "OtherMiner v2.0");
+    assert_eq!(response.section("VERSION")[0]["OtherMiner"], "v2.0");
+}
+
+fn stats_with_chips() -> response::Stats {
+    let chip = |frequency, temperature| response::ChipStats {
+        frequency,
+        mhs: 1000.5,
+        hardware_errors: 2,
+        temperature,
+    };
+    response::Stats {
+        asc_stats: vec![response::AscStats {
+            header: response::StatsHeader {
+                idx: 0,
+                id: "BC50".to_string(),
+                elapsed: 0,
+                calls: 0,
+                wait: 0.0,
+                max: 0.0,
+                min: 0.0,
+            },
+            chips: response::Chips(vec![chip(650.0, Some(61.5)), chip(625.0, None)]),
+        }],
+        pool_stats: vec![],
+    }
+}
+
+#[test]
+fn test_chip_stats_serialization() {
+    let asc_stats = &stats_with_chips().asc_stats[0];
+    // External parsers depend on the naming of the keys
+    assert_json_eq(
+        &json::to_value(asc_stats).unwrap(),
+        &json::json!({
+            "STATS": 0,
+            "ID": "BC50",
+            "Elapsed": 0,
+            "Calls": 0,
+            "Wait": 0.0,
+            "Max": 0.0,
+            "Min": 0.0,
+            "chip_count": 2,
+            "chip_freq_0": 650.0,
+            "chip_mhs_0": 1000.5,
+            "chip_hw_0": 2,
+            "chip_temp_0": 61.5,
+            "chip_freq_1": 625.0,
+            "chip_mhs_1": 1000.5,
+            "chip_hw_1": 2,
+        }),
+    );
+
+    // No chip details are serialized when the handler does not provide them
+    let asc_stats = response::AscStats {
+        chips: Default::default(),
+        ..asc_stats.clone()
+    };
+    let value = json::to_value(&asc_stats).unwrap();
+    assert_eq!(value.as_object().map(|section| section.len()), Some(7));
+}
+
+#[tokio::test]
+async fn test_chip_stats() {
+    let build_receiver = || {
+        command::Receiver::<ZeroTime>::new(
+            MockHandler::builder()
+                .respond_with("stats", || Ok(stats_with_chips()))
+                .respond_with("estats", || Ok(stats_with_chips()))
+                .response("pools", response::Pools { list: vec![] })
+                .build(),
+            "TestMiner".to_string(),
+            "v1.0".to_string(),
+            None,
+        )
+    };
+    let chip_keys = |section: &json::Value| {
+        section
+            .as_object()
+            .expect("BUG: invalid stats section")
+            .keys()
+            .filter(|key| key.starts_with("chip_"))
+            .count()
+    };
+
+    // The details are removed by default
+    let receiver = build_receiver();
+    for command in &["stats", "estats"] {
+        let response = test_utils::handle(&receiver, command, None).await;
+        assert_eq!(response.section("STATS")[0]["ID"], "BC50");
+        assert_eq!(chip_keys(&response.section("STATS")[0]), 0);
+    }
+
+    let receiver = build_receiver().with_chip_stats();
+    let response = test_utils::handle(&receiver, "stats", None).await;
+    assert_eq!(chip_keys(&response.section("STATS")[0]), 8);
+    let response = test_utils::handle(&receiver, "estats+pools", None).await;
+    let response = response.command("estats");
+    let section = &response.section("STATS")[0];
+    assert_eq!(section["chip_count"], 2);
+    assert_eq!(section["chip_temp_0"], 61.5);
+
+    // The handler is told whether to collect the details at all
+    let receiver = command::Receiver::<ZeroTime>::new(
+        handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let response = test_utils::handle(&receiver, "stats", None).await;
+    assert_eq!(chip_keys(&response.section("STATS")[0]), 0);
+    let receiver = receiver.with_chip_stats();
+    let response = test_utils::handle(&receiver, "stats", None).await;
+    assert_eq!(response.section("STATS")[0]["chip_freq_0"], 650.0);
+}
@@ -0,0 +1,589 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Tests of IP based access control
+
+use super::utils::ZeroTime;
+use crate::access::{AccessControl, Network, Privilege, Session, Tokens};
+use crate::command;
+
+use ii_async_compat::tokio;
+
+use serde_json as json;
+
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+fn addr(addr: &str) -> IpAddr {
+    addr.parse().expect("BUG: invalid address")
+}
+
+fn network(network: &str) -> Network {
+    network.parse().expect("BUG: invalid network")
+}
+
+#[test]
+fn test_network_parse() {
+    assert_eq!(network("10.0.0.5"), network("10.0.0.5/32"));
+    assert_eq!(network("::1"), network("::1/128"));
+    // Host bits are ignored
+    assert_eq!(network("192.168.0.77/24"), network("192.168.0.0/24"));
+    assert_eq!(network("fe80::1:2/10"), network("fe80::/10"));
+    assert_eq!(network("10.1.2.3/0"), network("0.0.0.0/0"));
+
+    for invalid in &[
+        "",
+        "10.0.0.0/",
+        "10.0.0.0/33",
+        "10.0.0.0/-1",
+        "10.0.0.256",
+        "::/129",
+        "localhost",
+        "10.0.0.0/8/8",
+    ] {
+        assert!(
+            invalid.parse::<Network>().is_err(),
+            "BUG: '{}' has been accepted",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn test_network_contains() {
+    let lan = network("192.168.0.0/24");
+    assert!(lan.contains(addr("192.168.0.0")));
+    assert!(lan.contains(addr("192.168.0.255")));
+    assert!(!lan.contains(addr("192.168.1.0")));
+    assert!(!lan.contains(addr("192.167.255.255")));
+
+    // Prefix which is not aligned to octets
+    let odd = network("10.0.0.16/31");
+    assert!(odd.contains(addr("10.0.0.16")));
+    assert!(odd.contains(addr("10.0.0.17")));
+    assert!(!odd.contains(addr("10.0.0.15")));
+    assert!(!odd.contains(addr("10.0.0.18")));
+
+    let host = network("10.0.0.5");
+    assert!(host.contains(addr("10.0.0.5")));
+    assert!(!host.contains(addr("10.0.0.4")));
+
+    // Zero prefix matches all addresses of the same family
+    let any = network("0.0.0.0/0");
+    assert!(any.contains(addr("0.0.0.0")));
+    assert!(any.contains(addr("255.255.255.255")));
+    assert!(!any.contains(addr("::1")));
+
+    let link_local = network("fe80::/10");
+    assert!(link_local.contains(addr("fe80::1")));
+    assert!(link_local.contains(addr("febf:ffff::1")));
+    assert!(!link_local.contains(addr("fec0::1")));
+    assert!(!link_local.contains(addr("10.0.0.1")));
+    let host = network("2001:db8::1");
+    assert!(host.contains(addr("2001:db8::1")));
+    assert!(!host.contains(addr("2001:db8::2")));
+
+    // IPv4 clients of IPv6 socket match IPv4 networks
+    assert!(lan.contains(addr("::ffff:192.168.0.10")));
+    assert!(!lan.contains(addr("::ffff:192.168.1.10")));
+    assert!(!link_local.contains(addr("::ffff:192.168.0.10")));
+}
+
+#[test]
+fn test_access_control_parse() {
+    let access_control: AccessControl = "W:192.168.0.0/24, R:10.0.0.5,127.0.0.1,W:fd00::/8"
+        .parse()
+        .expect("BUG: cannot parse access control");
+    assert_eq!(
+        access_control.privilege(addr("192.168.0.1")),
+        Some(Privilege::Write)
+    );
+    assert_eq!(
+        access_control.privilege(addr("10.0.0.5")),
+        Some(Privilege::Read)
+    );
+    // Network without group grants read privilege
+    assert_eq!(
+        access_control.privilege(addr("127.0.0.1")),
+        Some(Privilege::Read)
+    );
+    assert_eq!(
+        access_control.privilege(addr("fd12::1")),
+        Some(Privilege::Write)
+    );
+    assert_eq!(access_control.privilege(addr("10.0.0.6")), None);
+    assert_eq!(access_control.privilege(addr("::1")), None);
+
+    // The highest privilege of all matching networks is granted
+    let access_control: AccessControl = "R:10.0.0.0/8,W:10.1.0.0/16,R:10.1.2.3".parse().unwrap();
+    assert_eq!(
+        access_control.privilege(addr("10.1.2.3")),
+        Some(Privilege::Write)
+    );
+    assert_eq!(
+        access_control.privilege(addr("10.2.0.1")),
+        Some(Privilege::Read)
+    );
+
+    // Special network of CGMiner matches all addresses of both families
+    let access_control: AccessControl = "W:0/0".parse().unwrap();
+    for client in &["1.2.3.4", "::1", "2001:db8::1"] {
+        assert_eq!(
+            access_control.privilege(addr(client)),
+            Some(Privilege::Write)
+        );
+    }
+
+    // Empty access control refuses everyone
+    assert_eq!(AccessControl::new().privilege(addr("127.0.0.1")), None);
+
+    for invalid in &[
+        "W:10.0.0.300",
+        "X:10.0.0.1",
+        "10.0.0.1,",
+        "W:",
+        "R:10.0.0.0/40",
+    ] {
+        assert!(
+            invalid.parse::<AccessControl>().is_err(),
+            "BUG: '{}' has been accepted",
+            invalid
+        );
+    }
+    let error = "R:10.0.0.1,W:host".parse::<AccessControl>().unwrap_err();
+    assert_eq!(error.to_string(), "invalid access rule 'W:host'");
+}
+
+fn build_receiver() -> command::Receiver<ZeroTime> {
+    command::Receiver::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_access_control(
+        "W:10.0.0.1,R:10.0.0.0/24"
+            .parse()
+            .expect("BUG: cannot parse access control"),
+    )
+}
+
+async fn handle(
+    receiver: &command::Receiver<ZeroTime>,
+    request: json::Value,
+    client: &str,
+) -> json::Value {
+    let context = command::Context::new(addr(client));
+    let response = receiver
+        .handle(command::Request::new(request), &context)
+        .await;
+    json::to_value(&response).unwrap()
+}
+
+const WRITER: &str = "10.0.0.1";
+const READER: &str = "10.0.0.2";
+const STRANGER: &str = "10.0.1.1";
+
+#[tokio::test]
+async fn test_receiver_privilege() {
+    let receiver = build_receiver();
+
+    assert_eq!(
+        receiver.privilege(&command::Context::new(addr(WRITER))),
+        Some(Privilege::Write)
+    );
+    assert_eq!(
+        receiver.privilege(&command::Context::new(addr(READER))),
+        Some(Privilege::Read)
+    );
+    assert_eq!(
+        receiver.privilege(&command::Context::new(addr(STRANGER))),
+        None
+    );
+    // Local clients are not subject to access control
+    assert_eq!(
+        receiver.privilege(&command::Context::local()),
+        Some(Privilege::Write)
+    );
+
+    let response = handle(&receiver, json::json!({ "command": "summary" }), READER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 11);
+    let response = handle(&receiver, json::json!({ "command": "pause" }), READER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+    assert_eq!(
+        response["STATUS"][0]["Msg"],
+        "Access denied to 'pause' command"
+    );
+    let response = handle(&receiver, json::json!({ "command": "pause" }), WRITER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 206);
+
+    // The command is privileged only with parameter
+    let response = handle(&receiver, json::json!({ "command": "locate" }), READER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 210);
+    let request = json::json!({ "command": "locate", "parameter": true });
+    let response = handle(&receiver, request.clone(), READER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+    let response = handle(&receiver, request, WRITER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 210);
+
+    // The check reports whether the client can access the command
+    let request = json::json!({ "command": "check", "parameter": "pause" });
+    let response = handle(&receiver, request.clone(), READER).await;
+    assert_eq!(
+        response["CHECK"][0],
+        json::json!({ "Exists": "Y", "Access": "N" })
+    );
+    let response = handle(&receiver, request, WRITER).await;
+    assert_eq!(
+        response["CHECK"][0],
+        json::json!({ "Exists": "Y", "Access": "Y" })
+    );
+
+    // Requests of refused clients are never handled
+    let response = handle(&receiver, json::json!({ "command": "summary" }), STRANGER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+}
+
+#[tokio::test]
+async fn test_receiver_privilege_multiple() {
+    let receiver = build_receiver();
+
+    let request = json::json!({ "command": "summary+pause+locate", "parameter": true });
+    for client in &[READER, WRITER] {
+        let response = handle(&receiver, request.clone(), client).await;
+        assert_eq!(response["summary"][0]["STATUS"][0]["Code"], 11);
+        // Privileged commands are refused in batched mode regardless of the client
+        assert_eq!(response["pause"][0]["STATUS"][0]["Code"], 45);
+        assert_eq!(response["locate"][0]["STATUS"][0]["Code"], 45);
+    }
+
+    let request = json::json!({ "command": "version+check", "parameter": "resume" });
+    let response = handle(&receiver, request.clone(), READER).await;
+    assert_eq!(response["version"][0]["STATUS"][0]["Code"], 22);
+    assert_eq!(response["check"][0]["CHECK"][0]["Access"], "N");
+    let response = handle(&receiver, request, WRITER).await;
+    assert_eq!(response["check"][0]["CHECK"][0]["Access"], "Y");
+
+    let request = json::json!({ "command": "summary+pools" });
+    let response = handle(&receiver, request.clone(), READER).await;
+    assert_eq!(response["pools"][0]["STATUS"][0]["Code"], 7);
+    let response = handle(&receiver, request, STRANGER).await;
+    assert_eq!(response["summary"][0]["STATUS"][0]["Code"], 45);
+    assert_eq!(response["pools"][0]["STATUS"][0]["Code"], 45);
+}
+
+#[tokio::test]
+async fn test_receiver_privilege_shutdown() {
+    let executed = Arc::new(Mutex::new(false));
+    let shutdown_executed = executed.clone();
+    let receiver = build_receiver().with_shutdown_handler(Box::new(move |_| {
+        let executed = shutdown_executed.clone();
+        Box::pin(async move { *executed.lock().unwrap() = true })
+    }));
+
+    let request = || command::Request::new(json::json!({ "command": "quit" }));
+    let context = command::Context::new(addr(READER));
+    let (response, action) = receiver.handle_deferred(request(), &context).await;
+    assert_eq!(json::to_value(&response).unwrap()["STATUS"][0]["Code"], 45);
+    assert!(action.is_none());
+
+    let context = command::Context::new(addr(WRITER));
+    let (response, action) = receiver.handle_deferred(request(), &context).await;
+    assert_eq!(json::to_value(&response).unwrap()["STATUS"], "BYE");
+    action.expect("BUG: missing shutdown action").await;
+    assert!(*executed.lock().unwrap());
+}
+
+#[tokio::test]
+async fn test_receiver_read_only() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_shutdown_handler(Box::new(|_| Box::pin(async {})))
+    .read_only();
+
+    // Even local clients have only read privilege
+    let context = command::Context::local();
+    assert_eq!(receiver.privilege(&context), Some(Privilege::Read));
+
+    let handle_local = |command: &'static str, parameter: Option<&'static str>| {
+        let mut request = json::json!({ "command": command });
+        if let Some(parameter) = parameter {
+            request["parameter"] = parameter.into();
+        }
+        let context = command::Context::local();
+        let receiver = &receiver;
+        async move {
+            let (response, action) = receiver
+                .handle_deferred(command::Request::new(request), &context)
+                .await;
+            assert!(action.is_none(), "BUG: shutdown in read-only mode");
+            json::to_value(&response).unwrap()
+        }
+    };
+
+    let response = handle_local("summary", None).await;
+    assert_eq!(response["STATUS"][0]["Code"], 11);
+    for command in &["pause", "quit", "restart"] {
+        let response = handle_local(command, None).await;
+        assert_eq!(response["STATUS"][0]["Code"], 45);
+    }
+
+    let response = handle_local("check", Some("summary")).await;
+    assert_eq!(
+        response["CHECK"][0],
+        json::json!({ "Exists": "Y", "Access": "Y" })
+    );
+    for command in &["pause", "quit"] {
+        let response = handle_local("check", Some(command)).await;
+        assert_eq!(
+            response["CHECK"][0],
+            json::json!({ "Exists": "Y", "Access": "N" })
+        );
+    }
+
+    // Remote writers are restricted as well
+    let receiver = build_receiver().read_only();
+    assert_eq!(
+        receiver.privilege(&command::Context::new(addr(WRITER))),
+        Some(Privilege::Read)
+    );
+    assert_eq!(
+        receiver.privilege(&command::Context::new(addr(STRANGER))),
+        None
+    );
+    let response = handle(&receiver, json::json!({ "command": "pause" }), WRITER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+}
+
+#[test]
+fn test_tokens() {
+    let tokens = Tokens::new()
+        .add("secret", Privilege::Write)
+        .add("viewer", Privilege::Read)
+        .add("viewer", Privilege::Write);
+
+    assert_eq!(tokens.privilege("secret"), Some(Privilege::Write));
+    // The highest privilege of the same token is granted
+    assert_eq!(tokens.privilege("viewer"), Some(Privilege::Write));
+    for token in &["", "secre", "secret ", "SECRET", "secrei"] {
+        assert_eq!(tokens.privilege(token), None, "token '{}'", token);
+    }
+    // The secrets are not printed
+    assert!(!format!("{:?}", tokens).contains("secret"));
+}
+
+fn build_token_receiver() -> command::Receiver<ZeroTime> {
+    command::Receiver::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_tokens(
+        Tokens::new()
+            .add("writer-secret", Privilege::Write)
+            .add("reader-secret", Privilege::Read),
+    )
+}
+
+#[tokio::test]
+async fn test_receiver_tokens() {
+    let receiver = build_token_receiver();
+
+    // Read commands stay open
+    let response = handle(&receiver, json::json!({ "command": "summary" }), STRANGER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 11);
+    let request = json::json!({ "command": "summary", "token": "invalid" });
+    let response = handle(&receiver, request, STRANGER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 11);
+
+    // Privileged commands require a valid token of a writer regardless of the address
+    for token in &[None, Some("invalid"), Some("reader-secret")] {
+        let mut request = json::json!({ "command": "pause" });
+        if let Some(token) = token {
+            request["token"] = json::json!(token);
+        }
+        let response = handle(&receiver, request, WRITER).await;
+        assert_eq!(response["STATUS"][0]["Code"], 45, "token {:?}", token);
+    }
+    let request = json::json!({ "command": "pause", "token": "writer-secret" });
+    let response = handle(&receiver, request, STRANGER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 206);
+
+    // Local clients do not need any token
+    assert_eq!(
+        receiver.privilege(&command::Context::local()),
+        Some(Privilege::Write)
+    );
+
+    // The check reports access granted by the token
+    let request = json::json!({ "command": "check", "parameter": "pause" });
+    let response = handle(&receiver, request, STRANGER).await;
+    assert_eq!(response["CHECK"][0]["Access"], "N");
+    let request = json::json!({
+        "command": "check",
+        "parameter": "pause",
+        "token": "writer-secret",
+    });
+    let response = handle(&receiver, request, STRANGER).await;
+    assert_eq!(response["CHECK"][0]["Access"], "Y");
+
+    // The token cannot grant more than the access control
+    let receiver = build_token_receiver().with_access_control(
+        "W:10.0.0.1,R:10.0.0.0/24"
+            .parse()
+            .expect("BUG: cannot parse access control"),
+    );
+    let request = json::json!({ "command": "pause", "token": "writer-secret" });
+    let response = handle(&receiver, request.clone(), READER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+    let response = handle(&receiver, request.clone(), WRITER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 206);
+    let response = handle(&receiver, request, STRANGER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+}
+
+#[tokio::test]
+async fn test_receiver_tokens_multiple() {
+    let receiver = build_token_receiver();
+
+    let request = json::json!({
+        "command": "summary+check+locate",
+        "parameter": "pause",
+        "token": "writer-secret",
+    });
+    let response = handle(&receiver, request, STRANGER).await;
+    assert_eq!(response["summary"][0]["STATUS"][0]["Code"], 11);
+    assert_eq!(response["check"][0]["CHECK"][0]["Access"], "Y");
+    // Privileged commands are refused in batched mode even with a valid token
+    assert_eq!(response["locate"][0]["STATUS"][0]["Code"], 45);
+
+    let request = json::json!({
+        "command": "summary+check",
+        "parameter": "pause",
+        "token": "invalid",
+    });
+    let response = handle(&receiver, request, STRANGER).await;
+    assert_eq!(response["summary"][0]["STATUS"][0]["Code"], 11);
+    assert_eq!(response["check"][0]["CHECK"][0]["Access"], "N");
+}
+
+#[test]
+fn test_session() {
+    const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+    let session = Session::new();
+    let start = Instant::now();
+    assert_eq!(session.privilege_at(IDLE_TIMEOUT, start), None);
+
+    session.grant(Privilege::Write);
+    let now = Instant::now();
+    assert_eq!(
+        session.privilege_at(IDLE_TIMEOUT, now),
+        Some(Privilege::Write)
+    );
+    // Every use postpones the expiration
+    let now = now + Duration::from_secs(9);
+    assert_eq!(
+        session.privilege_at(IDLE_TIMEOUT, now),
+        Some(Privilege::Write)
+    );
+    let now = now + Duration::from_secs(9);
+    assert_eq!(
+        session.privilege_at(IDLE_TIMEOUT, now),
+        Some(Privilege::Write)
+    );
+    // The privilege is gone for good once it has expired
+    let now = now + IDLE_TIMEOUT;
+    assert_eq!(session.privilege_at(IDLE_TIMEOUT, now), None);
+    assert_eq!(session.privilege_at(IDLE_TIMEOUT, start), None);
+
+    session.grant(Privilege::Read);
+    session.revoke();
+    assert_eq!(session.privilege(IDLE_TIMEOUT), None);
+}
+
+async fn handle_in(
+    receiver: &command::Receiver<ZeroTime>,
+    request: json::Value,
+    context: &command::Context,
+) -> json::Value {
+    let response = receiver
+        .handle(command::Request::new(request), context)
+        .await;
+    json::to_value(&response).unwrap()
+}
+
+#[tokio::test]
+async fn test_receiver_session() {
+    let receiver = build_token_receiver();
+    let session = Arc::new(Session::new());
+    let context = command::Context::new(addr(STRANGER)).with_session(session.clone());
+    let pause = json::json!({ "command": "pause" });
+
+    let response = handle_in(&receiver, pause.clone(), &context).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+
+    // Reader token does not grant write privilege
+    let request = json::json!({ "command": "auth", "parameter": "reader-secret" });
+    let response = handle_in(&receiver, request, &context).await;
+    assert_eq!(response["STATUS"][0]["Code"], 220);
+    assert_eq!(response["STATUS"][0]["Msg"], "Granted read privilege");
+    let response = handle_in(&receiver, pause.clone(), &context).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+
+    let request = json::json!({ "command": "auth", "parameter": "writer-secret" });
+    let response = handle_in(&receiver, request, &context).await;
+    assert_eq!(response["STATUS"][0]["Msg"], "Granted write privilege");
+    let response = handle_in(&receiver, pause.clone(), &context).await;
+    assert_eq!(response["STATUS"][0]["Code"], 206);
+    // Another connection of the same client has its own session
+    let other = command::Context::new(addr(STRANGER)).with_session(Arc::new(Session::new()));
+    let response = handle_in(&receiver, pause.clone(), &other).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+
+    // Invalid token revokes the privilege
+    for parameter in &[json::json!("invalid"), json::json!(1)] {
+        let request = json::json!({ "command": "auth", "parameter": parameter });
+        let response = handle_in(&receiver, request, &context).await;
+        assert_eq!(response["STATUS"][0]["Code"], 272);
+    }
+    let response = handle_in(&receiver, pause.clone(), &context).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+
+    // Privilege of an idle session expires
+    let receiver = build_token_receiver().with_session_idle_timeout(Duration::from_millis(10));
+    let request = json::json!({ "command": "auth", "parameter": "writer-secret" });
+    handle_in(&receiver, request, &context).await;
+    tokio::time::delay_for(Duration::from_millis(20)).await;
+    let response = handle_in(&receiver, pause, &context).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+
+    // Single-shot connections have no session
+    let request = json::json!({ "command": "auth", "parameter": "writer-secret" });
+    let response = handle(&receiver, request, STRANGER).await;
+    assert_eq!(response["STATUS"][0]["Code"], 271);
+}
@@ -0,0 +1,266 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Tests of the audit log of mutating commands
+
+use super::utils::ZeroTime;
+use crate::audit::{AuditEntry, AuditSink, FileAuditSink, Rotation};
+use crate::command;
+use crate::response;
+
+use ii_async_compat::tokio;
+
+use serde_json as json;
+
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Sink keeping all entries in memory
+#[derive(Default)]
+struct CollectingSink(Mutex<Vec<AuditEntry>>);
+
+impl CollectingSink {
+    fn take(&self) -> Vec<AuditEntry> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl AuditSink for CollectingSink {
+    fn record(&self, entry: AuditEntry) {
+        self.0.lock().unwrap().push(entry);
+    }
+}
+
+fn addr(addr: &str) -> IpAddr {
+    addr.parse().expect("BUG: invalid address")
+}
+
+fn entry(command: &str) -> AuditEntry {
+    AuditEntry {
+        when: 0,
+        client: Some(addr("10.0.0.1")),
+        command: command.to_string(),
+        parameter: Some(json::json!("0")),
+        status: response::Status::S,
+        code: 0,
+        msg: "ok".to_string(),
+    }
+}
+
+/// Unique path of an audit log for each test
+fn log_path(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("cgminer-api-{}-{}.log", name, std::process::id()));
+    remove_logs(&path);
+    path
+}
+
+fn remove_logs(path: &Path) {
+    for suffix in &["", ".1", ".2", ".3"] {
+        let _ = fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
+
+fn read_entries(path: &str) -> Vec<json::Value> {
+    fs::read_to_string(path)
+        .expect("BUG: cannot read audit log")
+        .lines()
+        .map(|line| json::from_str(line).expect("BUG: invalid audit entry"))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_audit_mutating_commands() {
+    let sink = Arc::new(CollectingSink::default());
+    let receiver = command::Receiver::<ZeroTime>::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_access_control(
+        "W:10.0.0.1,R:10.0.0.0/24"
+            .parse()
+            .expect("BUG: cannot parse access control"),
+    )
+    .with_audit_sink(sink.clone());
+    let handle = |request: json::Value, client: &str| {
+        let context = command::Context::new(addr(client));
+        let receiver = &receiver;
+        async move {
+            receiver
+                .handle(command::Request::new(request), &context)
+                .await
+        }
+    };
+
+    // Read commands are not recorded
+    for request in &[
+        json::json!({ "command": "summary" }),
+        json::json!({ "command": "pools+devs" }),
+        json::json!({ "command": "asc", "parameter": 0 }),
+    ] {
+        handle(request.clone(), "10.0.0.1").await;
+    }
+    assert!(sink.take().is_empty());
+
+    handle(
+        json::json!({ "command": "ascenable", "parameter": "0" }),
+        "10.0.0.1",
+    )
+    .await;
+    assert_eq!(
+        sink.take(),
+        vec![AuditEntry {
+            when: 0,
+            client: Some(addr("10.0.0.1")),
+            command: "ascenable".to_string(),
+            parameter: Some(json::json!("0")),
+            status: response::Status::I,
+            code: 108,
+            msg: "ASC 0 already enabled".to_string(),
+        }]
+    );
+
+    // Credentials are not recorded
+    handle(
+        json::json!({
+            "command": "addpool",
+            "parameter": "stratum+tcp://pool:3333,user,secret",
+        }),
+        "10.0.0.1",
+    )
+    .await;
+    let entries = sink.take();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].parameter,
+        Some(json::json!("stratum+tcp://pool:3333,user,***"))
+    );
+
+    // Refused invocations are recorded as well
+    handle(
+        json::json!({ "command": "switchpool", "parameter": 1 }),
+        "10.0.0.2",
+    )
+    .await;
+    let entries = sink.take();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].command, "switchpool");
+    assert_eq!(entries[0].client, Some(addr("10.0.0.2")));
+    assert_eq!(entries[0].status, response::Status::E);
+    assert_eq!(entries[0].code, 45);
+}
+
+#[tokio::test]
+async fn test_file_audit_sink() {
+    let path = log_path("audit");
+    let sink = FileAuditSink::open(&path, Rotation::default(), 16)
+        .await
+        .expect("BUG: cannot open audit log");
+    sink.record(entry("ascenable"));
+    sink.record(entry("ascdisable"));
+    sink.close().await;
+
+    let entries = read_entries(&path.display().to_string());
+    assert_eq!(
+        entries,
+        vec![
+            json::json!({
+                "when": 0,
+                "client": "10.0.0.1",
+                "command": "ascenable",
+                "parameter": "0",
+                "status": "S",
+                "code": 0,
+                "msg": "ok",
+            }),
+            json::json!({
+                "when": 0,
+                "client": "10.0.0.1",
+                "command": "ascdisable",
+                "parameter": "0",
+                "status": "S",
+                "code": 0,
+                "msg": "ok",
+            }),
+        ]
+    );
+
+    // Entries are appended to an existing log
+    let sink = FileAuditSink::open(&path, Rotation::default(), 16)
+        .await
+        .expect("BUG: cannot open audit log");
+    sink.record(entry("pause"));
+    sink.close().await;
+    assert_eq!(read_entries(&path.display().to_string()).len(), 3);
+
+    remove_logs(&path);
+}
+
+#[tokio::test]
+async fn test_file_audit_sink_rotation() {
+    let path = log_path("audit-rotation");
+    let line_len = json::to_vec(&entry("pause")).unwrap().len() as u64 + 1;
+    let rotation = Rotation {
+        max_size: 2 * line_len,
+        max_files: 2,
+    };
+    let sink = FileAuditSink::open(&path, rotation, 16)
+        .await
+        .expect("BUG: cannot open audit log");
+    for _ in 0..7 {
+        sink.record(entry("pause"));
+    }
+    sink.close().await;
+
+    // Two entries fit into each file and the oldest ones are dropped
+    let path = path.display().to_string();
+    assert_eq!(read_entries(&path).len(), 1);
+    assert_eq!(read_entries(&format!("{}.1", path)).len(), 2);
+    assert_eq!(read_entries(&format!("{}.2", path)).len(), 2);
+    assert!(!Path::new(&format!("{}.3", path)).exists());
+
+    remove_logs(Path::new(&path));
+}
+
+#[tokio::test]
+async fn test_file_audit_sink_overflow() {
+    let path = log_path("audit-overflow");
+    let sink = FileAuditSink::open(&path, Rotation::default(), 2)
+        .await
+        .expect("BUG: cannot open audit log");
+    // The writer does not get a chance to run so the queue overflows
+    for _ in 0..10 {
+        sink.record(entry("pause"));
+    }
+    let dropped = sink.dropped();
+    assert!(dropped >= 7, "BUG: only {} entries dropped", dropped);
+    sink.close().await;
+
+    let written = read_entries(&path.display().to_string()).len() as u64;
+    assert_eq!(written + dropped, 10);
+
+    remove_logs(&path);
+}
@@ -0,0 +1,232 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Tests of the API client against the API server
+
+use super::handler::BasicTest;
+use super::utils::ZeroTime;
+use crate::client::{Client, Error};
+use crate::command::{self, Handler as _};
+use crate::response;
+use crate::server::{Handle, Server};
+use crate::test_utils::MockHandler;
+
+use ii_async_compat::tokio;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::TcpListener;
+
+use serde_json as json;
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Starts server answering with the data of `BasicTest` except for `summary` which fails
+async fn start_server() -> (SocketAddr, Handle, BasicTest) {
+    let basic = BasicTest::default();
+    let handler = MockHandler::builder()
+        .response("pools", basic.handle_pools().await.ok().unwrap())
+        .response("devs", basic.handle_devs(None).await.ok().unwrap())
+        .response("asc", basic.handle_asc(Some(0)).await.ok().unwrap())
+        .error("summary", || {
+            response::ErrorCode::HardwareError.with_context("no chains")
+        })
+        .build();
+    let receiver = command::Receiver::<ZeroTime>::new(
+        handler,
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server");
+    let addr = server.local_addr().expect("BUG: missing local address");
+
+    (addr, server.start(), basic)
+}
+
+#[tokio::test]
+async fn test_client() {
+    let (addr, handle, basic) = start_server().await;
+    let mut client = Client::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+
+    // Typed responses are the same as the ones of the handler
+    let pools = client.pools().await.expect("BUG: pools failed");
+    assert_eq!(pools, basic.handle_pools().await.ok().unwrap().list);
+    let devs = client.devs().await.expect("BUG: devs failed");
+    assert_eq!(devs, basic.handle_devs(None).await.ok().unwrap().list);
+    let asc = client.asc(0).await.expect("BUG: asc failed");
+    assert_eq!(asc, devs[0]);
+
+    match client.summary().await {
+        Err(Error::Status { code, msg }) => {
+            assert_eq!(code, 269);
+            assert_eq!(msg, "Hardware error - no chains");
+        }
+        result => panic!("BUG: unexpected result {:?}", result),
+    }
+
+    let reply = client
+        .command("version", None)
+        .await
+        .expect("BUG: version failed");
+    assert_eq!(reply.code(), 22);
+    assert_eq!(reply.value()["VERSION"][0]["TestMiner"], "v1.0");
+
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_client_batch() {
+    let (addr, handle, _) = start_server().await;
+    let mut client = Client::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+
+    let replies = client
+        .batch(&["pools", "summary", "version"], None)
+        .await
+        .expect("BUG: batch failed");
+    assert_eq!(replies.len(), 3);
+    let pools: Vec<response::Pool> = replies[0]
+        .as_ref()
+        .expect("BUG: pools failed")
+        .section("POOLS")
+        .expect("BUG: invalid pools");
+    assert_eq!(pools.len(), 1);
+    assert!(matches!(replies[1], Err(Error::Status { code: 269, .. })));
+    assert_eq!(replies[2].as_ref().map(|reply| reply.code()).ok(), Some(22));
+
+    // Single command is responded without the batch structure
+    let replies = client
+        .batch(&["edevs"], None)
+        .await
+        .expect("BUG: batch failed");
+    assert!(matches!(
+        replies.as_slice(),
+        [Err(Error::Status { code: 264, .. })]
+    ));
+
+    handle.shutdown().await;
+}
+
+/// Behavior of a scripted server for a single connection
+#[derive(Copy, Clone)]
+enum Script {
+    /// Closes the connection right after it has been accepted
+    Close,
+    /// Reads the request and closes the connection without a response
+    Drop,
+    /// Reads the request and never responds
+    Stall,
+    /// Responds `version`
+    Respond,
+}
+
+/// Starts a server following the `scripts` for the accepted connections in order and returns its
+/// address with the number of accepted connections
+async fn start_scripted(scripts: Vec<Script>) -> (SocketAddr, Arc<AtomicUsize>) {
+    let mut listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("BUG: cannot bind server");
+    let addr = listener.local_addr().expect("BUG: missing local address");
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    tokio::spawn(async move {
+        for script in scripts {
+            let (mut stream, _) = listener.accept().await.expect("BUG: cannot accept");
+            counter.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                if let Script::Close = script {
+                    return;
+                }
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request).await;
+                match script {
+                    Script::Stall => tokio::time::delay_for(Duration::from_secs(60)).await,
+                    Script::Respond => {
+                        let response = json::json!({
+                            "STATUS": [{
+                                "STATUS": "S",
+                                "When": 0,
+                                "Code": 22,
+                                "Msg": "TestMiner versions",
+                                "Description": "TestMiner v1.0",
+                            }],
+                            "VERSION": [{ "TestMiner": "v1.0", "API": "3.7" }],
+                            "id": 1,
+                        });
+                        let mut response = response.to_string().into_bytes();
+                        response.push(0);
+                        let _ = stream.write_all(&response).await;
+                    }
+                    _ => {}
+                }
+            });
+        }
+    });
+
+    (addr, accepted)
+}
+
+#[tokio::test]
+async fn test_client_reconnect() {
+    // Connection established in advance and closed by the server is replaced
+    let (addr, accepted) = start_scripted(vec![Script::Close, Script::Respond]).await;
+    let mut client = Client::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    tokio::time::delay_for(Duration::from_millis(100)).await;
+    let reply = client
+        .command("version", None)
+        .await
+        .expect("BUG: version failed");
+    assert_eq!(reply.code(), 22);
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+
+    // The request is not repeated once the server could have got it
+    let (addr, accepted) = start_scripted(vec![Script::Drop, Script::Respond]).await;
+    let mut client = Client::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    assert!(matches!(
+        client.command("version", None).await,
+        Err(Error::InvalidResponse(_))
+    ));
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+    let (addr, accepted) = start_scripted(vec![Script::Stall, Script::Respond]).await;
+    let mut client = Client::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server")
+        .with_timeout(Duration::from_millis(100));
+    match client.command("version", None).await {
+        Err(Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::TimedOut),
+        result => panic!("BUG: unexpected result {:?}", result),
+    }
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,437 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Compatibility of responses with responses of a real CGMiner
+//!
+//! Each file in directory `compat/<miner>` contains the response of the miner to the request
+//! named by the file with the NUL terminator stripped and the JSON pretty printed keeping the
+//! order of fields and the numbers as printed by CGMiner. The mock handler is configured with the
+//! values of the `version`, `summary`, `pools` and `devs` responses so that any difference of our
+//! response is a difference of format. Intentional differences are listed in `ALLOWED` together
+//! with the reason and each of them has to be still present.
+//!
+//! **The files of `cgminer-4.11.1` are synthetic.** They have not been captured from a running
+//! miner but written by hand after `api.c` of CGMiner 4.11.1: the field names, their order, the
+//! JSON types and the number formatting follow the `api_add_*` calls of the corresponding command
+//! and the values are made up. They are a baseline to be replaced by real captures, which are
+//! the only way to discover formatting the source does not make obvious. A capture is taken by
+//! sending the request to port 4028 of the miner (e.g.
+//! `printf '{"command":"summary"}' | nc <miner> 4028 | tr -d '\0'`) for all files at the same
+//! moment and pretty printing the output without reordering the fields. Update `MINER` and
+//! `SYNTHETIC` when the directory is replaced.
+
+use super::utils::ZeroTime;
+use crate::command;
+use crate::response;
+use crate::test_utils::MockHandler;
+
+use ii_async_compat::tokio;
+
+use serde::de::DeserializeOwned;
+use serde_json as json;
+
+use std::fmt;
+
+/// Miner the responses belong to
+const MINER: &str = "cgminer-4.11.1";
+
+/// The responses of `MINER` have been written after its source rather than captured
+const SYNTHETIC: bool = true;
+
+/// Pairs each named request with the response of `MINER`
+macro_rules! captures {
+    ($(($name:literal, $request:literal $(,)?),)+) => {
+        &[$((
+            $name,
+            $request,
+            include_str!(concat!("compat/cgminer-4.11.1/", $name, ".json")),
+        ),)+]
+    };
+}
+
+const CAPTURES: &[(&str, &str, &str)] = captures![
+    ("version", r#"{"command": "version"}"#),
+    ("summary", r#"{"command": "summary"}"#),
+    ("pools", r#"{"command": "pools"}"#),
+    ("devs", r#"{"command": "devs"}"#),
+    ("check", r#"{"command": "check", "parameter": "summary"}"#),
+    ("batched", r#"{"command": "summary+pools"}"#),
+    ("invalid-command", r#"{"command": "unknown"}"#),
+    ("missing-parameter", r#"{"command": "enablepool"}"#),
+];
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+enum Kind {
+    /// Field of the captured response is missing in our response
+    Missing,
+    /// Field is present only in our response
+    Unexpected,
+    /// Values have different JSON types (e.g. `true` vs. `"true"` or `1` vs. `1.0`)
+    Type,
+    /// Values of the same type differ
+    Value,
+}
+
+/// Difference of our response from the captured one
+#[derive(Clone, Debug)]
+struct Difference {
+    /// Segments of the path are separated with `/` (e.g. `SUMMARY/0/MHS av`)
+    path: String,
+    kind: Kind,
+    captured: Option<json::Value>,
+    actual: Option<json::Value>,
+}
+
+impl Difference {
+    fn new(
+        path: String,
+        kind: Kind,
+        captured: Option<&json::Value>,
+        actual: Option<&json::Value>,
+    ) -> Self {
+        Self {
+            path,
+            kind,
+            captured: captured.cloned(),
+            actual: actual.cloned(),
+        }
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<json::Value>| match value {
+            Some(value) => value.to_string(),
+            None => "-".to_string(),
+        };
+        write!(
+            f,
+            "{}: {:?} (captured: {}, ours: {})",
+            self.path,
+            self.kind,
+            show(&self.captured),
+            show(&self.actual)
+        )
+    }
+}
+
+/// Intentional difference of `kind` in all captures matching `capture` (`*` matches any)
+struct Allowed {
+    /// Pattern of the path where `*` matches one segment and `**` any number of segments
+    path: &'static str,
+    capture: &'static str,
+    kind: Kind,
+    reason: &'static str,
+}
+
+const ALLOWED: &[Allowed] = &[
+    Allowed {
+        path: "**/STATUS/*/When",
+        capture: "*",
+        kind: Kind::Value,
+        reason: "time of the response",
+    },
+    Allowed {
+        path: "**/STATUS/*/Description",
+        capture: "*",
+        kind: Kind::Value,
+        reason: "description consists of the signature and version of the miner",
+    },
+    Allowed {
+        path: "**/SUMMARY/*/MHS 24h",
+        capture: "*",
+        kind: Kind::Unexpected,
+        reason: "extension of summary",
+    },
+    Allowed {
+        path: "**/SUMMARY/*/Pool Dead Park",
+        capture: "*",
+        kind: Kind::Unexpected,
+        reason: "extension of summary",
+    },
+    Allowed {
+        path: "**/SUMMARY/*/Paused",
+        capture: "*",
+        kind: Kind::Unexpected,
+        reason: "extension of summary",
+    },
+    Allowed {
+        path: "**/POOLS/*/AsicBoost",
+        capture: "*",
+        kind: Kind::Unexpected,
+        reason: "extension of pools",
+    },
+    Allowed {
+        path: "DEVS/*/Hardware Error MHS 15m",
+        capture: "devs",
+        kind: Kind::Unexpected,
+        reason: "extension of devs",
+    },
+    Allowed {
+        path: "DEVS/*/Nominal MHS",
+        capture: "devs",
+        kind: Kind::Unexpected,
+        reason: "extension of devs",
+    },
+];
+
+fn type_name(value: &json::Value) -> &'static str {
+    match value {
+        json::Value::Null => "null",
+        json::Value::Bool(_) => "boolean",
+        json::Value::Number(number) if number.is_f64() => "number",
+        json::Value::Number(_) => "integer",
+        json::Value::String(_) => "string",
+        json::Value::Array(_) => "array",
+        json::Value::Object(_) => "object",
+    }
+}
+
+fn join(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{}/{}", path, segment)
+    }
+}
+
+/// Compares `actual` value with the `captured` one field by field
+fn diff(path: &str, captured: &json::Value, actual: &json::Value, out: &mut Vec<Difference>) {
+    match (captured, actual) {
+        (json::Value::Object(captured), json::Value::Object(actual)) => {
+            for (key, value) in captured {
+                match actual.get(key) {
+                    Some(other) => diff(&join(path, key), value, other, out),
+                    None => out.push(Difference::new(
+                        join(path, key),
+                        Kind::Missing,
+                        Some(value),
+                        None,
+                    )),
+                }
+            }
+            for (key, value) in actual
+                .iter()
+                .filter(|(key, _)| !captured.contains_key(*key))
+            {
+                out.push(Difference::new(
+                    join(path, key),
+                    Kind::Unexpected,
+                    None,
+                    Some(value),
+                ));
+            }
+        }
+        (json::Value::Array(captured), json::Value::Array(actual)) => {
+            for i in 0..captured.len().max(actual.len()) {
+                let path = join(path, &i.to_string());
+                match (captured.get(i), actual.get(i)) {
+                    (Some(value), Some(other)) => diff(&path, value, other, out),
+                    (Some(value), None) => {
+                        out.push(Difference::new(path, Kind::Missing, Some(value), None))
+                    }
+                    (None, other) => out.push(Difference::new(path, Kind::Unexpected, None, other)),
+                }
+            }
+        }
+        _ => {
+            let kind = if type_name(captured) != type_name(actual) {
+                Kind::Type
+            } else if captured != actual {
+                Kind::Value
+            } else {
+                return;
+            };
+            out.push(Difference::new(
+                path.to_string(),
+                kind,
+                Some(captured),
+                Some(actual),
+            ));
+        }
+    }
+}
+
+fn matches_path(pattern: &[&str], path: &[&str]) -> bool {
+    match (pattern.split_first(), path.split_first()) {
+        (Some((&"**", rest)), _) => {
+            matches_path(rest, path) || (!path.is_empty() && matches_path(pattern, &path[1..]))
+        }
+        (Some((segment, rest)), Some((name, tail))) => {
+            (*segment == "*" || segment == name) && matches_path(rest, tail)
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+impl Allowed {
+    fn allows(&self, capture: &str, difference: &Difference) -> bool {
+        let pattern: Vec<_> = self.path.split('/').collect();
+        let path: Vec<_> = difference.path.split('/').collect();
+        (self.capture == "*" || self.capture == capture)
+            && self.kind == difference.kind
+            && matches_path(&pattern, &path)
+    }
+}
+
+fn captured(name: &str) -> json::Value {
+    let (_, _, content) = CAPTURES
+        .iter()
+        .find(|(capture, _, _)| *capture == name)
+        .expect("BUG: missing capture");
+    json::from_str(content).expect("BUG: invalid capture")
+}
+
+/// Deserializes all sections `section` of the `capture`
+fn captured_sections<T: DeserializeOwned>(capture: &str, section: &str) -> Vec<T> {
+    json::from_value(captured(capture)[section].clone())
+        .unwrap_or_else(|e| panic!("BUG: invalid '{}' in capture '{}': {}", section, capture, e))
+}
+
+/// Receiver reporting the same values as the captured miner
+fn receiver() -> command::Receiver<ZeroTime> {
+    let summary = captured_sections::<response::Summary>("summary", "SUMMARY").remove(0);
+    let handler = MockHandler::builder()
+        .response("summary", summary)
+        .response(
+            "pools",
+            response::Pools {
+                list: captured_sections("pools", "POOLS"),
+            },
+        )
+        .response(
+            "devs",
+            response::Devs {
+                list: captured_sections("devs", "DEVS"),
+            },
+        )
+        .build();
+    let version = &captured("version")["VERSION"][0];
+    let field = |name: &str| {
+        version[name]
+            .as_str()
+            .expect("BUG: missing version field")
+            .to_string()
+    };
+    command::Receiver::new(handler, "CGMiner".to_string(), field("CGMiner"), None)
+        .with_version_info(
+            response::VersionInfo::new("CGMiner".to_string(), field("CGMiner")).api(field("API")),
+        )
+}
+
+#[test]
+fn test_compat_diff() {
+    let captured = json::json!({
+        "SECTION": [{ "A": 1, "B": "true", "C": 1.5, "D": 0.0 }],
+        "id": 1,
+    });
+    let actual = json::json!({
+        "SECTION": [{ "A": 1, "B": true, "C": 2.5, "D": 0, "E": 0 }, {}],
+    });
+    let mut differences = vec![];
+    diff("", &captured, &actual, &mut differences);
+    let found: Vec<_> = differences
+        .iter()
+        .map(|difference| (difference.path.as_str(), difference.kind))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("SECTION/0/B", Kind::Type),
+            ("SECTION/0/C", Kind::Value),
+            ("SECTION/0/D", Kind::Type),
+            ("SECTION/0/E", Kind::Unexpected),
+            ("SECTION/1", Kind::Unexpected),
+            ("id", Kind::Missing),
+        ]
+    );
+    assert_eq!(
+        differences[1].to_string(),
+        "SECTION/0/C: Value (captured: 1.5, ours: 2.5)"
+    );
+
+    let allowed = Allowed {
+        path: "**/SECTION/*/C",
+        capture: "summary",
+        kind: Kind::Value,
+        reason: "test",
+    };
+    assert!(allowed.allows("summary", &differences[1]));
+    let mut nested = differences[1].clone();
+    nested.path = "summary/0/SECTION/0/C".to_string();
+    assert!(allowed.allows("summary", &nested));
+    assert!(!allowed.allows("pools", &differences[1]));
+    assert!(!allowed.allows("summary", &differences[0]));
+}
+
+#[tokio::test]
+async fn test_compat_responses() {
+    let receiver = receiver();
+    let mut used = vec![false; ALLOWED.len()];
+    let mut report = String::new();
+    for (name, request, capture) in CAPTURES {
+        let capture: json::Value = json::from_str(capture).expect("BUG: invalid capture");
+        let request = command::Request::new(json::from_str(request).expect("BUG: invalid request"));
+        let response = receiver.handle(request, &command::Context::local()).await;
+        let response = json::to_value(&response).expect("BUG: cannot serialize response");
+
+        let mut differences = vec![];
+        diff("", &capture, &response, &mut differences);
+        let unexpected: Vec<_> = differences
+            .into_iter()
+            .filter(|difference| {
+                match ALLOWED
+                    .iter()
+                    .position(|allowed| allowed.allows(name, difference))
+                {
+                    Some(i) => {
+                        used[i] = true;
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .collect();
+        if !unexpected.is_empty() {
+            let origin = if SYNTHETIC { " (synthetic)" } else { "" };
+            report += &format!(
+                "\nresponse to '{}' differs from {}{}:\n",
+                name, MINER, origin
+            );
+            for difference in unexpected {
+                report += &format!("    {}\n", difference);
+            }
+        }
+    }
+    for (allowed, _) in ALLOWED.iter().zip(used).filter(|(_, used)| !used) {
+        report += &format!(
+            "\nallowed difference not found anymore: {} {:?} in '{}' ({})\n",
+            allowed.path, allowed.kind, allowed.capture, allowed.reason
+        );
+    }
+    assert!(
+        report.is_empty(),
+        "BUG: responses are not compatible:\n{}",
+        report
+    );
+}
@@ -0,0 +1,212 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Tests of the delta mode of polled commands
+
+use super::utils::ZeroTime;
+use crate::command::{self, Handler as _};
+use crate::delta;
+use crate::response;
+use crate::test_utils::{MockHandler, ParsedResponse};
+
+use ii_async_compat::tokio;
+
+use serde_json as json;
+
+use std::sync::{Arc, Mutex};
+
+/// Computes the delta of plain JSON values
+fn diff(old: &json::Value, new: &json::Value) -> json::Value {
+    delta::diff(&old.clone().into(), &new.clone().into()).into()
+}
+
+#[test]
+fn test_diff() {
+    let old = json::json!([
+        { "ASC": 0, "Accepted": 1, "Temperature": 60.0, "Chain": { "Id": 6 } },
+        { "ASC": 1, "Accepted": 2 },
+        { "ASC": 2, "Accepted": 3 },
+    ]);
+    let new = json::json!([
+        // Changed value and added field
+        { "ASC": 0, "Accepted": 2, "Temperature": 60.0, "Chain": { "Id": 6 }, "Voltage": 9.0 },
+        // Unchanged section
+        { "ASC": 1, "Accepted": 2 },
+        // Removed field
+        { "ASC": 2 },
+        // New section
+        { "ASC": 3, "Accepted": 0 },
+    ]);
+    assert_eq!(
+        diff(&old, &new),
+        json::json!([
+            { "Accepted": 2, "Voltage": 9.0 },
+            {},
+            { "$removed": ["Accepted"] },
+            { "ASC": 3, "Accepted": 0 },
+        ])
+    );
+
+    // Nested values are replaced as whole
+    let new = json::json!([{ "ASC": 0, "Chain": { "Id": 7 } }]);
+    assert_eq!(
+        diff(&old, &new),
+        json::json!([
+            { "Chain": { "Id": 7 }, "$removed": ["Accepted", "Temperature"] },
+        ])
+    );
+
+    // Removed sections just shorten the list
+    assert_eq!(diff(&old, &json::json!([])), json::json!([]));
+}
+
+/// Receiver in delta mode responding `devs` with the shared `devs`
+fn receiver(devs: Arc<Mutex<response::Devs>>, capacity: usize) -> command::Receiver<ZeroTime> {
+    let handler = MockHandler::builder()
+        .respond_with("devs", move || Ok(devs.lock().unwrap().clone()))
+        .build();
+    command::Receiver::new(handler, "TestMiner".to_string(), "v1.0".to_string(), None)
+        .with_delta_mode(capacity)
+}
+
+async fn devs() -> Arc<Mutex<response::Devs>> {
+    let devs = super::handler::BasicTest::default()
+        .handle_devs(None)
+        .await
+        .unwrap_or_else(|_| panic!("BUG: cannot get devs"));
+    Arc::new(Mutex::new(devs))
+}
+
+async fn handle_delta(
+    receiver: &command::Receiver<ZeroTime>,
+    command: &str,
+    since: json::Value,
+) -> ParsedResponse {
+    handle_delta_with(
+        receiver,
+        json::json!({ "command": command, "delta": since }),
+    )
+    .await
+}
+
+async fn handle_delta_with(
+    receiver: &command::Receiver<ZeroTime>,
+    request: json::Value,
+) -> ParsedResponse {
+    let response = receiver
+        .handle(command::Request::new(request), &command::Context::local())
+        .await;
+    ParsedResponse::new(&response)
+}
+
+fn seq(response: &ParsedResponse) -> json::Value {
+    response.status()["Seq"].clone()
+}
+
+#[tokio::test]
+async fn test_delta_mode() {
+    let devs = devs().await;
+    let receiver = receiver(devs.clone(), 16);
+
+    // The first response is full
+    let full = handle_delta(&receiver, "devs", json::json!(true)).await;
+    full.assert_status(9, "1 ASC(s)");
+    assert_eq!(full.status()["Delta"], json::json!(false));
+    assert_eq!(full.section("DEVS").len(), 1);
+    assert_eq!(full.section("DEVS")[0]["Accepted"], json::json!(0));
+
+    // Unchanged section is empty
+    let unchanged = handle_delta(&receiver, "devs", seq(&full)).await;
+    unchanged.assert_status(9, "1 ASC(s)");
+    assert_eq!(unchanged.status()["Delta"], json::json!(true));
+    assert_ne!(seq(&unchanged), seq(&full));
+    assert_eq!(unchanged.section("DEVS"), &[json::json!({})][..]);
+
+    // Only changed fields are sent and new sections in full
+    {
+        let mut devs = devs.lock().unwrap();
+        devs.list[0].accepted = 5;
+        let mut asc = devs.list[0].clone();
+        asc.idx = 1;
+        devs.list.push(asc);
+    }
+    let changed = handle_delta(&receiver, "devs", seq(&unchanged)).await;
+    assert_eq!(changed.status()["Delta"], json::json!(true));
+    let sections = changed.section("DEVS");
+    assert_eq!(sections[0], json::json!({ "Accepted": 5 }));
+    let mut new_asc = full.section("DEVS")[0].clone();
+    new_asc["ASC"] = 1.into();
+    new_asc["Accepted"] = 5.into();
+    assert_eq!(sections[1], new_asc);
+
+    // Token is used just once
+    let again = handle_delta(&receiver, "devs", seq(&unchanged)).await;
+    assert_eq!(again.status()["Delta"], json::json!(false));
+    assert_eq!(again.section("DEVS").len(), 2);
+
+    // Tokens of another parameter and invalid tokens result in full response
+    let other = handle_delta_with(
+        &receiver,
+        json::json!({ "command": "devs", "parameter": 0, "delta": seq(&again) }),
+    )
+    .await;
+    assert_eq!(other.status()["Delta"], json::json!(false));
+    for since in &[json::json!("invalid"), json::json!(0), json::json!(-1)] {
+        let invalid = handle_delta(&receiver, "devs", since.clone()).await;
+        assert_eq!(invalid.status()["Delta"], json::json!(false));
+    }
+
+    // Commands without delta mode and requests without the field are not affected
+    let plain = crate::test_utils::handle(&receiver, "devs", None).await;
+    assert!(plain.status().get("Seq").is_none());
+    assert!(plain.status().get("Delta").is_none());
+    let version = handle_delta(&receiver, "version", json::json!(true)).await;
+    assert!(version.status().get("Seq").is_none());
+}
+
+#[tokio::test]
+async fn test_delta_mode_eviction() {
+    let receiver = receiver(devs().await, 2);
+
+    let first = handle_delta(&receiver, "devs", json::json!(true)).await;
+    // Other clients fill the store
+    handle_delta(&receiver, "devs", json::json!(true)).await;
+    handle_delta(&receiver, "devs", json::json!(true)).await;
+
+    let evicted = handle_delta(&receiver, "devs", seq(&first)).await;
+    assert_eq!(evicted.status()["Delta"], json::json!(false));
+    assert_eq!(evicted.section("DEVS").len(), 1);
+}
+
+#[tokio::test]
+async fn test_delta_mode_disabled() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let response = handle_delta(&receiver, "summary", json::json!(true)).await;
+    response.assert_status(11, "Summary");
+    assert!(response.status().get("Seq").is_none());
+    assert!(response.status().get("Delta").is_none());
+}
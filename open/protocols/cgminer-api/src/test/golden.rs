@@ -0,0 +1,181 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Golden files locking the order of fields of all responses (see `response` module). Each file
+//! contains the pretty printed response of the basic test handler to the request named by the
+//! file.
+
+use super::utils::ZeroTime;
+use crate::command;
+use crate::ordered;
+use crate::support::{BufferPool, ResponseType};
+use crate::{Codec, Format};
+
+use ii_async_compat::{bytes, tokio, tokio_util};
+use tokio_util::codec::Encoder as _;
+
+use serde_json as json;
+
+/// Pairs each named request with the content of its golden file
+macro_rules! golden {
+    ($(($name:literal, $request:literal $(,)?),)+) => {
+        &[$(($name, $request, include_str!(concat!("golden/", $name, ".json"))),)+]
+    };
+}
+
+const REQUESTS: &[(&str, &str, &str)] = golden![
+    ("pools", r#"{"command": "pools"}"#),
+    ("devs", r#"{"command": "devs"}"#),
+    ("edevs", r#"{"command": "edevs"}"#),
+    ("summary", r#"{"command": "summary"}"#),
+    ("config", r#"{"command": "config"}"#),
+    ("notify", r#"{"command": "notify"}"#),
+    ("stats", r#"{"command": "stats"}"#),
+    ("estats", r#"{"command": "estats"}"#),
+    ("coin", r#"{"command": "coin"}"#),
+    ("asccount", r#"{"command": "asccount"}"#),
+    ("asc", r#"{"command": "asc", "parameter": 0}"#),
+    ("lcd", r#"{"command": "lcd"}"#),
+    ("tunerstatus", r#"{"command": "tunerstatus"}"#),
+    ("version", r#"{"command": "version"}"#),
+    ("check", r#"{"command": "check", "parameter": "pools"}"#),
+    ("help", r#"{"command": "help"}"#),
+    ("apistats", r#"{"command": "apistats"}"#),
+    (
+        "schema",
+        r#"{"command": "schema", "parameter": "asccount"}"#,
+    ),
+    ("switchpool", r#"{"command": "switchpool", "parameter": 0}"#),
+    ("enablepool", r#"{"command": "enablepool", "parameter": 0}"#),
+    (
+        "disablepool",
+        r#"{"command": "disablepool", "parameter": 0}"#,
+    ),
+    (
+        "addpool",
+        r#"{"command": "addpool", "parameter": "stratum+tcp://pool:3333,user,pass"}"#,
+    ),
+    ("removepool", r#"{"command": "removepool", "parameter": 0}"#),
+    (
+        "poolpriority",
+        r#"{"command": "poolpriority", "parameter": "0"}"#,
+    ),
+    (
+        "failover-only",
+        r#"{"command": "failover-only", "parameter": true}"#,
+    ),
+    ("zero", r#"{"command": "zero", "parameter": "all,true"}"#),
+    ("pause", r#"{"command": "pause"}"#),
+    ("resume", r#"{"command": "resume"}"#),
+    ("locate", r#"{"command": "locate", "parameter": "true"}"#),
+    ("ascenable", r#"{"command": "ascenable", "parameter": 0}"#),
+    ("ascdisable", r#"{"command": "ascdisable", "parameter": 0}"#),
+    (
+        "ascset",
+        r#"{"command": "ascset", "parameter": "0,freq,650"}"#,
+    ),
+    ("debug", r#"{"command": "debug", "parameter": "verbose"}"#),
+    (
+        "setconfig",
+        r#"{"command": "setconfig", "parameter": "queue,1"}"#,
+    ),
+    ("reloadconfig", r#"{"command": "reloadconfig"}"#),
+    ("quit", r#"{"command": "quit"}"#),
+    ("batched", r#"{"command": "summary+pools+devs"}"#),
+    ("batched-error", r#"{"command": "pools+enablepool"}"#),
+    ("batched-unknown", r#"{"command": "bogus+summary+bogus"}"#),
+    ("batched-single", r#"{"command": "summary+"}"#),
+    ("batched-empty", r#"{"command": "+"}"#),
+    ("invalid-command", r#"{"command": "unknown"}"#),
+    ("missing-command", r#"{"parameter": 0}"#),
+    ("missing-parameter", r#"{"command": "enablepool"}"#),
+];
+
+async fn respond(request: &str) -> ResponseType {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_shutdown_handler(Box::new(|_| Box::pin(async {})));
+    let request = command::Request::new(json::from_str(request).expect("BUG: invalid request"));
+    receiver.handle(request, &command::Context::local()).await
+}
+
+/// The response is converted into `ordered::Value` first because encoded sections of the
+/// response are not pretty printed
+async fn render(request: &str) -> String {
+    let response = respond(request).await;
+    let response = ordered::to_value(&response).expect("BUG: cannot convert response");
+    json::to_string_pretty(&response).expect("BUG: cannot serialize response") + "\n"
+}
+
+#[tokio::test]
+async fn test_golden_responses() {
+    for (name, request, expected) in REQUESTS {
+        assert_eq!(
+            render(request).await,
+            *expected,
+            "BUG: response to '{}' differs from golden file",
+            name
+        );
+    }
+}
+
+/// Responses with sections encoded when the response is built have to be identical to the
+/// responses serialized from the whole `ordered::Value` tree
+#[tokio::test]
+async fn test_encoded_responses() {
+    let pool = std::sync::Arc::new(BufferPool::new(1));
+    let mut pooled = pool.acquire();
+    for (name, request, _) in REQUESTS {
+        let response = respond(request).await;
+        let tree = ordered::to_value(&response).expect("BUG: cannot convert response");
+        let mut expected = json::to_vec(&tree).expect("BUG: cannot serialize response");
+        expected.push(0);
+
+        let mut serialized = json::to_vec(&response).expect("BUG: cannot serialize response");
+        serialized.push(0);
+        let buffered = pooled
+            .encode(&response, Format::Json)
+            .expect("BUG: cannot encode response");
+        let mut encoded = bytes::BytesMut::new();
+        Codec::new(Format::Json)
+            .encode(response, &mut encoded)
+            .expect("BUG: cannot encode response");
+
+        for (path, actual) in &[
+            ("json::to_vec", &serialized[..]),
+            ("ResponseBuffer", &buffered[..]),
+            ("Codec", &encoded[..]),
+        ] {
+            assert_eq!(
+                String::from_utf8_lossy(actual),
+                String::from_utf8_lossy(&expected),
+                "BUG: response to '{}' encoded by {} differs",
+                name,
+                path
+            );
+        }
+    }
+}
@@ -20,12 +20,25 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
-pub struct BasicTest;
+/// Test miner with the only writable configuration option `log`, switchable failover-only mode
+/// pausable mining and locate mode
+#[derive(Default)]
+pub struct BasicTest {
+    log_interval: AtomicI32,
+    failover_only: AtomicBool,
+    paused: AtomicBool,
+    locate: Mutex<Option<LocateSetting>>,
+}
 
 use crate::command;
 use crate::response;
+use crate::support::{
+    AddPoolParameter, AscSetParameter, DebugFlag, DebugSettings, LocateSetting, StatsFilter,
+    ZeroTarget,
+};
 
-use serde_json as json;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::Mutex;
 
 #[async_trait::async_trait]
 impl command::Handler for BasicTest {
@@ -73,7 +86,12 @@ impl command::Handler for BasicTest {
         })
     }
 
-    async fn handle_devs(&self) -> command::Result<response::Devs> {
+    async fn handle_devs(&self, idx: Option<i32>) -> command::Result<response::Devs> {
+        // The test miner has just one ASC
+        match idx {
+            Some(idx) if idx != 0 => Err(response::ErrorCode::InvalidAscId(idx, 0))?,
+            _ => {}
+        }
         Ok(response::Devs {
             list: vec![response::Asc {
                 idx: 0,
@@ -109,7 +127,7 @@ impl command::Handler for BasicTest {
     }
 
     async fn handle_edevs(&self) -> command::Result<response::Devs> {
-        self.handle_devs().await
+        self.handle_devs(None).await
     }
 
     async fn handle_summary(&self) -> command::Result<response::Summary> {
@@ -121,6 +139,9 @@ impl command::Handler for BasicTest {
             mhs_5m: 0.0,
             mhs_15m: 0.0,
             mhs_24h: 0.0,
+            pool_dead_park: response::Bool::N,
+            paused: self.paused.load(Ordering::Relaxed).into(),
+            extras: Default::default(),
             found_blocks: 0,
             getworks: 0,
             accepted: 0,
@@ -147,10 +168,11 @@ impl command::Handler for BasicTest {
         })
     }
 
-    async fn handle_switch_pool(
-        &self,
-        _parameter: Option<&json::Value>,
-    ) -> command::Result<response::SwitchPool> {
+    async fn handle_switch_pool(&self, idx: i32) -> command::Result<response::SwitchPool> {
+        // The test miner has just one pool
+        if idx != 0 {
+            Err(response::ErrorCode::InvalidPoolId(idx, 0))?;
+        }
         Ok(response::SwitchPool {
             idx: 0,
             url: "".to_string(),
@@ -163,55 +185,178 @@ impl command::Handler for BasicTest {
             pga_count: 0,
             pool_count: 0,
             strategy: response::MultipoolStrategy::Failover,
-            log_interval: 0,
+            failover_only: self.failover_only.load(Ordering::Relaxed),
+            log_interval: self.log_interval.load(Ordering::Relaxed),
             device_code: String::new(),
             os: "Braiins OS".to_string(),
             hotplug: "None".to_string(),
+            hw_profile: None,
+            extras: Default::default(),
         })
     }
 
-    async fn handle_enable_pool(
+    async fn handle_set_config(
         &self,
-        _parameter: Option<&json::Value>,
-    ) -> command::Result<response::EnablePool> {
-        Ok(response::EnablePool {
-            idx: 0,
-            url: "".to_string(),
+        name: String,
+        value: String,
+    ) -> command::Result<response::SetConfig> {
+        if name != "log" {
+            return Err(response::ErrorCode::UnknownConfig(name).into());
+        }
+        match value.parse::<i32>() {
+            Ok(log_interval) if log_interval >= 0 => {
+                self.log_interval.store(log_interval, Ordering::Relaxed);
+                Ok(response::SetConfig {
+                    name,
+                    value: log_interval.to_string(),
+                })
+            }
+            _ => Err(response::ErrorCode::InvalidConfigValue(name, value))?,
+        }
+    }
+
+    async fn handle_reload_config(&self) -> command::Result<response::ReloadConfig> {
+        Ok(response::ReloadConfig {
+            changed: vec!["log".to_string()],
+            restart_required: vec!["api-listen".to_string()],
+            rejected: vec![],
         })
     }
 
-    async fn handle_disable_pool(
-        &self,
-        _parameter: Option<&json::Value>,
-    ) -> command::Result<response::DisablePool> {
-        Ok(response::DisablePool {
-            idx: 0,
+    async fn handle_enable_pool(&self, idx: i32) -> command::Result<response::EnablePool> {
+        // The only pool of the test miner is always enabled
+        if idx != 0 {
+            Err(response::ErrorCode::InvalidPoolId(idx, 0))?;
+        }
+        Ok(response::EnablePool::AlreadyEnabled {
+            idx: idx as usize,
             url: "".to_string(),
         })
     }
 
+    async fn handle_disable_pool(&self, idx: i32) -> command::Result<response::DisablePool> {
+        if idx != 0 {
+            Err(response::ErrorCode::InvalidPoolId(idx, 0))?;
+        }
+        Err(response::ErrorCode::DisableLastPool(idx, "".to_string()))?
+    }
+
     async fn handle_add_pool(
         &self,
-        _parameter: Option<&json::Value>,
+        parameter: AddPoolParameter,
     ) -> command::Result<response::AddPool> {
+        // New pool is appended after the only existing one
         Ok(response::AddPool {
-            idx: 0,
-            url: "".to_string(),
+            idx: 1,
+            url: parameter.url,
         })
     }
 
-    async fn handle_remove_pool(
+    async fn handle_remove_pool(&self, idx: i32) -> command::Result<response::RemovePool> {
+        // The only pool of the test miner can never be removed
+        if idx != 0 {
+            Err(response::ErrorCode::InvalidPoolId(idx, 0))?;
+        }
+        Err(response::ErrorCode::RemoveLastPool(idx, "".to_string()))?
+    }
+
+    async fn handle_pool_priority(
         &self,
-        _parameter: Option<&json::Value>,
-    ) -> command::Result<response::RemovePool> {
-        Ok(response::RemovePool {
-            idx: 0,
-            url: "".to_string(),
+        order: Vec<usize>,
+    ) -> command::Result<response::PoolPriority> {
+        if let Some(idx) = order.into_iter().find(|idx| *idx != 0) {
+            Err(response::ErrorCode::InvalidPoolId(idx as i32, 0))?;
+        }
+        Ok(response::PoolPriority)
+    }
+
+    async fn handle_zero(
+        &self,
+        target: ZeroTarget,
+        return_summary: bool,
+    ) -> command::Result<response::Zero> {
+        let summary = if return_summary {
+            Some(self.handle_summary().await?)
+        } else {
+            None
+        };
+        Ok(response::Zero { target, summary })
+    }
+
+    async fn handle_notify(&self) -> command::Result<response::Notifies> {
+        Ok(response::Notifies {
+            list: vec![response::Notify {
+                idx: 0,
+                name: "".to_string(),
+                id: 0,
+                last_well: 0,
+                last_not_well: 0,
+                reason_not_well: "None".to_string(),
+                thread_fail_init: 0,
+                thread_zero_hash: 0,
+                thread_fail_queue: 0,
+                dev_sick_idle_60s: 0,
+                dev_dead_idle_600s: 0,
+                dev_nostart: 0,
+                dev_over_heat: 0,
+                dev_thermal_cutoff: 0,
+                dev_comms_error: 0,
+                dev_throttle: 0,
+            }],
+        })
+    }
+
+    async fn handle_failover_only(&self, enabled: bool) -> command::Result<response::FailoverOnly> {
+        self.failover_only.store(enabled, Ordering::Relaxed);
+        Ok(response::FailoverOnly { enabled })
+    }
+
+    async fn handle_pause(&self) -> command::Result<response::Pause> {
+        Ok(if self.paused.swap(true, Ordering::Relaxed) {
+            response::Pause::AlreadyPaused
+        } else {
+            response::Pause::Paused
         })
     }
 
-    async fn handle_stats(&self) -> command::Result<response::Stats> {
-        Ok(response::Stats {
+    async fn handle_resume(&self) -> command::Result<response::Resume> {
+        Ok(if self.paused.swap(false, Ordering::Relaxed) {
+            response::Resume::Resumed
+        } else {
+            response::Resume::NotPaused
+        })
+    }
+
+    async fn handle_locate(
+        &self,
+        setting: Option<LocateSetting>,
+    ) -> command::Result<response::Locate> {
+        let mut locate = self.locate.lock().expect("BUG: cannot lock locate mode");
+        if let Some(setting) = setting {
+            *locate = Some(setting);
+        }
+        Ok(match *locate {
+            Some(LocateSetting::On) => response::Locate {
+                active: response::Bool::Y,
+                remaining: None,
+            },
+            Some(LocateSetting::Timed(duration)) => response::Locate {
+                active: response::Bool::Y,
+                remaining: Some(duration),
+            },
+            Some(LocateSetting::Off) | None => response::Locate {
+                active: response::Bool::N,
+                remaining: None,
+            },
+        })
+    }
+
+    async fn handle_stats(
+        &self,
+        filter: Option<StatsFilter>,
+        chips: bool,
+    ) -> command::Result<response::Stats> {
+        response::Stats {
             asc_stats: vec![response::AscStats {
                 header: response::StatsHeader {
                     idx: 0,
@@ -222,10 +367,20 @@ impl command::Handler for BasicTest {
                     max: 0.0,
                     min: 0.0,
                 },
+                chips: if chips {
+                    response::Chips(vec![response::ChipStats {
+                        frequency: 650.0,
+                        mhs: 0.0,
+                        hardware_errors: 0,
+                        temperature: None,
+                    }])
+                } else {
+                    Default::default()
+                },
             }],
             pool_stats: vec![response::PoolStats {
                 header: response::StatsHeader {
-                    idx: 0,
+                    idx: 1,
                     id: "".to_string(),
                     elapsed: 0,
                     calls: 0,
@@ -254,12 +409,18 @@ impl command::Handler for BasicTest {
                 bytes_recv: 0,
                 net_bytes_sent: 0,
                 net_bytes_recv: 0,
+                redundant_jobs: 0,
             }],
-        })
+        }
+        .select(filter)
     }
 
-    async fn handle_estats(&self) -> command::Result<response::Stats> {
-        Ok(response::Stats {
+    async fn handle_estats(
+        &self,
+        filter: Option<StatsFilter>,
+        _chips: bool,
+    ) -> command::Result<response::Stats> {
+        response::Stats {
             asc_stats: vec![response::AscStats {
                 header: response::StatsHeader {
                     idx: 0,
@@ -270,9 +431,11 @@ impl command::Handler for BasicTest {
                     max: 0.0,
                     min: 0.0,
                 },
+                chips: Default::default(),
             }],
             pool_stats: vec![],
-        })
+        }
+        .select(filter)
     }
 
     async fn handle_coin(&self) -> command::Result<response::Coin> {
@@ -289,39 +452,67 @@ impl command::Handler for BasicTest {
         Ok(response::AscCount { count: 0 })
     }
 
-    async fn handle_asc(&self, _parameter: Option<&json::Value>) -> command::Result<response::Asc> {
-        Ok(response::Asc {
-            idx: 0,
-            name: "BC5".to_string(),
-            id: 0,
-            enabled: response::Bool::Y,
-            status: response::AscStatus::Alive,
-            temperature: 0.0,
-            mhs_av: 0.0,
-            mhs_5s: 0.0,
-            mhs_1m: 0.0,
-            mhs_5m: 0.0,
-            mhs_15m: 0.0,
-            accepted: 0,
-            rejected: 0,
-            hardware_errors: 0,
-            utility: 0.0,
-            last_share_pool: 0,
-            last_share_time: 0,
-            total_mega_hashes: 0.0,
-            diff1_work: 0,
-            difficulty_accepted: 0.0,
-            difficulty_rejected: 0.0,
-            last_share_difficulty: 0.0,
-            last_valid_work: 0,
-            device_hardware_ratio: 0.0,
-            device_rejected_ratio: 0.0,
-            device_elapsed: 0,
-            hardware_error_mhs_15m: 0.0,
-            nominal_mhs: 0.0,
+    async fn handle_asc(&self, idx: Option<i32>) -> command::Result<response::Ascs> {
+        let list = self.handle_devs(idx).await?.list;
+        Ok(response::Ascs { list })
+    }
+
+    async fn handle_asc_enable(&self, idx: i32) -> command::Result<response::AscEnable> {
+        // The test miner has just one device which is enabled
+        if idx != 0 {
+            Err(response::ErrorCode::InvalidAscId(idx, 0))?;
+        }
+        Ok(response::AscEnable::AlreadyEnabled { idx })
+    }
+
+    async fn handle_asc_disable(&self, idx: i32) -> command::Result<response::AscDisable> {
+        if idx != 0 {
+            Err(response::ErrorCode::InvalidAscId(idx, 0))?;
+        }
+        Ok(response::AscDisable::Disabled { idx })
+    }
+
+    async fn handle_asc_set(
+        &self,
+        parameter: AscSetParameter,
+    ) -> command::Result<response::AscSet> {
+        // The only device of the test miner supports setting of its frequency
+        let idx = parameter.idx;
+        if idx != 0 {
+            Err(response::ErrorCode::InvalidAscId(idx, 0))?;
+        }
+        let mut options = vec![];
+        for ((option, value), order) in parameter.options.into_iter().zip(1..) {
+            if option != "freq" {
+                let supported = vec!["freq".to_string()];
+                return Err(response::ErrorCode::InvalidAscOption(idx, option, supported).into());
+            }
+            let value = value.ok_or_else(|| {
+                response::ErrorCode::AscSetErr(idx, format!("missing value of '{}'", option))
+            })?;
+            options.push(response::AscSetOption {
+                option,
+                value,
+                order,
+                result: response::AscSetResult::Applied,
+            });
+        }
+        Ok(response::AscSet {
+            idx,
+            options,
+            error: None,
         })
     }
 
+    async fn handle_debug(&self, flag: Option<DebugFlag>) -> command::Result<response::Debug> {
+        // The test miner does not keep the settings between requests
+        let mut settings = DebugSettings::default();
+        if let Some(flag) = flag {
+            settings.apply(flag);
+        }
+        Ok(settings.into())
+    }
+
     async fn handle_lcd(&self) -> command::Result<response::Lcd> {
         Ok(response::Lcd {
             elapsed: 0,
@@ -338,4 +529,33 @@ impl command::Handler for BasicTest {
             user: "".to_string(),
         })
     }
+
+    async fn handle_tuner_status(&self) -> command::Result<response::TunerStatus> {
+        Ok(response::TunerStatus {
+            power_limit: 1200,
+            dynamic_power_scaling: response::Bool::N,
+            chains: vec![
+                response::TunerChain {
+                    idx: 0,
+                    id: 6,
+                    stage: response::TunerStage::Tuning,
+                    iterations_done: 3,
+                    iterations_total: 10,
+                    estimated_finish: 1000,
+                    best_frequency: 650.0,
+                    best_voltage: 8.8,
+                },
+                response::TunerChain {
+                    idx: 1,
+                    id: 7,
+                    stage: response::TunerStage::Stable,
+                    iterations_done: 10,
+                    iterations_total: 10,
+                    estimated_finish: 0,
+                    best_frequency: 675.0,
+                    best_voltage: 8.9,
+                },
+            ],
+        })
+    }
 }
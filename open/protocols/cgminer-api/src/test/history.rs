@@ -0,0 +1,282 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Tests of the history of commands reported by `lastcommands` command
+
+use super::utils::ZeroTime;
+use crate::access::{Privilege, Session, Tokens};
+use crate::command;
+use crate::history::{Entry, History, MAX_PARAMETER_SUMMARY};
+use crate::observer::ResponseStatus;
+use crate::response;
+use crate::support;
+use crate::test_utils::{self, ParsedResponse};
+
+use ii_async_compat::tokio;
+
+use serde_json as json;
+
+use std::net::IpAddr;
+use std::sync::Arc;
+
+fn addr(addr: &str) -> IpAddr {
+    addr.parse().expect("BUG: invalid address")
+}
+
+fn entry(command: &str) -> Entry {
+    Entry {
+        when: 0,
+        client: None,
+        command: command.to_string(),
+        parameter: String::new(),
+        status: ResponseStatus {
+            status: response::Status::S,
+            code: 0,
+            msg: "ok".to_string(),
+        },
+    }
+}
+
+fn commands(history: &History) -> Vec<String> {
+    history
+        .entries()
+        .into_iter()
+        .map(|entry| entry.command)
+        .collect()
+}
+
+#[test]
+fn test_history() {
+    let history = History::new(3);
+    assert!(history.entries().is_empty());
+    history.record(entry("pools"));
+    history.record(entry("devs"));
+    assert_eq!(commands(&history), vec!["devs", "pools"]);
+
+    // The oldest entries are overwritten
+    for command in &["summary", "stats", "version"] {
+        history.record(entry(command));
+    }
+    assert_eq!(commands(&history), vec!["version", "stats", "summary"]);
+
+    let disabled = History::new(0);
+    disabled.record(entry("pools"));
+    assert!(disabled.entries().is_empty());
+}
+
+#[test]
+fn test_history_parameter_summary() {
+    assert_eq!(Entry::summary(None), "");
+    assert_eq!(
+        Entry::summary(Some(&json::json!("0,freq,650"))),
+        "0,freq,650"
+    );
+    assert_eq!(Entry::summary(Some(&json::json!(1))), "1");
+    assert_eq!(Entry::summary(Some(&json::json!([true]))), "[true]");
+
+    let long = "é".repeat(MAX_PARAMETER_SUMMARY + 1);
+    let summary = Entry::summary(Some(&json::json!(long)));
+    assert_eq!(summary, "é".repeat(MAX_PARAMETER_SUMMARY) + "...");
+    let exact = "é".repeat(MAX_PARAMETER_SUMMARY);
+    assert_eq!(Entry::summary(Some(&json::json!(exact))), exact);
+}
+
+#[test]
+fn test_redact_add_pool() {
+    assert_eq!(
+        support::redact_add_pool(&json::json!("stratum+tcp://pool:3333,user,secret")),
+        json::json!("stratum+tcp://pool:3333,user,***")
+    );
+    // Escaped fields stay the same
+    assert_eq!(
+        support::redact_add_pool(&json::json!(r"pool,us\,er,pa\,ss,word")),
+        json::json!(r"pool,us\,er,***")
+    );
+    // Parameter which cannot be parsed is redacted whole
+    for parameter in &[json::json!("pool,secret"), json::json!(1)] {
+        assert_eq!(support::redact_add_pool(parameter), json::json!("***"));
+    }
+}
+
+fn build_receiver() -> command::Receiver<ZeroTime> {
+    command::Receiver::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_tokens(Tokens::new().add("writer-secret", Privilege::Write))
+}
+
+async fn handle_in(
+    receiver: &command::Receiver<ZeroTime>,
+    request: json::Value,
+    context: &command::Context,
+) -> ParsedResponse {
+    let response = receiver
+        .handle(command::Request::new(request), context)
+        .await;
+    ParsedResponse::new(&response)
+}
+
+#[tokio::test]
+async fn test_last_commands() {
+    let receiver = build_receiver();
+    let writer = command::Context::new(addr("10.0.0.1"));
+    let reader = command::Context::new(addr("10.0.0.2"));
+
+    handle_in(&receiver, json::json!({ "command": "summary" }), &writer).await;
+    let request = json::json!({
+        "command": "addpool",
+        "parameter": "stratum+tcp://pool:3333,user,secret",
+        "token": "writer-secret",
+    });
+    handle_in(&receiver, request, &writer).await;
+    handle_in(&receiver, json::json!({ "command": "pause" }), &reader).await;
+    handle_in(&receiver, json::json!({ "command": "pools+devs" }), &reader).await;
+    handle_in(&receiver, json::json!({ "command": "bogus" }), &reader).await;
+    let session = command::Context::new(addr("10.0.0.2")).with_session(Arc::new(Session::new()));
+    let request = json::json!({ "command": "auth", "parameter": "writer-secret" });
+    handle_in(&receiver, request, &session).await;
+
+    // History is privileged
+    let response = handle_in(
+        &receiver,
+        json::json!({ "command": "lastcommands" }),
+        &reader,
+    )
+    .await;
+    response.assert_status(45, "Access denied to 'lastcommands' command");
+
+    let response = test_utils::handle(&receiver, "lastcommands", None).await;
+    response.assert_status(221, "8 recent command(s)");
+    let sections = response.section("LASTCOMMANDS");
+    let summary = |section: &json::Value| {
+        (
+            section["Client"].as_str().unwrap().to_string(),
+            section["Command"].as_str().unwrap().to_string(),
+            section["Parameter"].as_str().unwrap().to_string(),
+            section["Code"].as_u64().unwrap(),
+        )
+    };
+    let expected = |client: &str, command: &str, parameter: &str, code| {
+        (
+            client.to_string(),
+            command.to_string(),
+            parameter.to_string(),
+            code,
+        )
+    };
+    assert_eq!(
+        sections.iter().map(summary).collect::<Vec<_>>(),
+        vec![
+            expected("10.0.0.2", "lastcommands", "", 45),
+            expected("10.0.0.2", "auth", "***", 220),
+            expected("10.0.0.2", "bogus", "", 14),
+            expected("10.0.0.2", "devs", "", 9),
+            expected("10.0.0.2", "pools", "", 7),
+            expected("10.0.0.2", "pause", "", 45),
+            expected(
+                "10.0.0.1",
+                "addpool",
+                "stratum+tcp://pool:3333,user,***",
+                55
+            ),
+            expected("10.0.0.1", "summary", "", 11),
+        ]
+    );
+    assert_eq!(
+        sections[0],
+        json::json!({
+            "LASTCOMMAND": 0,
+            "When": 0,
+            "Client": "10.0.0.2",
+            "Command": "lastcommands",
+            "Parameter": "",
+            "Status": "E",
+            "Code": 45,
+            "Msg": "Access denied to 'lastcommands' command",
+        })
+    );
+
+    // A command is recorded only once it has been handled and local clients have no address
+    let response = test_utils::handle(&receiver, "lastcommands", None).await;
+    let section = &response.section("LASTCOMMANDS")[0];
+    assert_eq!(section["Command"], "lastcommands");
+    assert_eq!(section["Client"], "");
+    assert_eq!(section["Code"], 221);
+}
+
+#[tokio::test]
+async fn test_last_commands_redacted() {
+    let receiver = build_receiver();
+    let parameter = "stratum+tcp://pool:3333,user,secret";
+    // Parameters of batched and unknown commands are redacted whole
+    for command in &["summary+addpool", "addpol"] {
+        let request = json::json!({ "command": command, "parameter": parameter });
+        handle_in(&receiver, request, &command::Context::local()).await;
+    }
+
+    let response = test_utils::handle(&receiver, "lastcommands", None).await;
+    let parameters: Vec<_> = response
+        .section("LASTCOMMANDS")
+        .iter()
+        .map(|section| {
+            (
+                section["Command"].as_str().unwrap().to_string(),
+                section["Parameter"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    let mut batched = parameters[1..].to_vec();
+    batched.sort();
+    assert_eq!(parameters[0], ("addpol".to_string(), "***".to_string()));
+    assert_eq!(
+        batched,
+        vec![
+            ("addpool".to_string(), "***".to_string()),
+            ("summary".to_string(), "***".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_last_commands_capacity() {
+    let receiver = build_receiver().with_command_history(2);
+    for command in &["pools", "devs", "summary"] {
+        test_utils::handle(&receiver, command, None).await;
+    }
+    let response = test_utils::handle(&receiver, "lastcommands", None).await;
+    response.assert_status(221, "2 recent command(s)");
+    let commands: Vec<_> = response
+        .section("LASTCOMMANDS")
+        .iter()
+        .map(|section| section["Command"].clone())
+        .collect();
+    assert_eq!(commands, vec!["summary", "devs"]);
+
+    let receiver = build_receiver().with_command_history(0);
+    test_utils::handle(&receiver, "pools", None).await;
+    test_utils::handle(&receiver, "lastcommands", None)
+        .await
+        .assert_status(221, "0 recent command(s)");
+}
@@ -0,0 +1,94 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+//! Tests of the JSON value keeping order of fields
+
+use crate::ordered::{self, Value};
+
+use serde::Serialize;
+use serde_json as json;
+
+use std::collections::BTreeMap;
+
+#[derive(Serialize)]
+enum Chain {
+    Idle,
+    Mining { id: u32, frequency: f64 },
+    Failed(String),
+}
+
+#[derive(Serialize)]
+struct Section {
+    #[serde(rename = "ASC")]
+    asc: u32,
+    #[serde(rename = "Temperature")]
+    temperature: f64,
+    #[serde(rename = "Enabled")]
+    enabled: Option<bool>,
+    #[serde(rename = "Chains")]
+    chains: Vec<Chain>,
+    #[serde(rename = "Boards")]
+    boards: BTreeMap<u32, &'static str>,
+}
+
+#[test]
+fn test_ordered_value() {
+    let section = Section {
+        asc: 1,
+        temperature: f64::NAN,
+        enabled: None,
+        chains: vec![
+            Chain::Idle,
+            Chain::Mining {
+                id: 6,
+                frequency: 650.0,
+            },
+            Chain::Failed("reset".to_string()),
+        ],
+        boards: vec![(6, "S9")].into_iter().collect(),
+    };
+    let value = ordered::to_value(&section).expect("BUG: cannot serialize section");
+    let names: Vec<_> = value
+        .as_object()
+        .expect("BUG: section is not an object")
+        .keys()
+        .collect();
+    // Fields are in the order of declaration instead of alphabetical one
+    assert_eq!(names, ["ASC", "Temperature", "Enabled", "Chains", "Boards"]);
+    // The same value as `json::to_value` builds apart from the order
+    assert_eq!(
+        json::Value::from(value.clone()),
+        json::to_value(&section).expect("BUG: cannot serialize section")
+    );
+
+    // Encoding and parsing keep the order too
+    let encoded = value.to_string();
+    assert!(encoded.starts_with(r#"{"ASC":1,"Temperature":null,"Enabled":null,"#));
+    let parsed: Value = json::from_str(&encoded).expect("BUG: cannot parse value");
+    assert_eq!(parsed, value);
+
+    // Already encoded JSON is decoded in order
+    let raw = json::value::RawValue::from_string(encoded).expect("BUG: invalid raw value");
+    assert_eq!(
+        ordered::to_value(&raw).expect("BUG: cannot serialize raw value"),
+        value
+    );
+}
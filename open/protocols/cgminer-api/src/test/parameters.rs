@@ -0,0 +1,154 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Tests of typed extraction of command parameters
+
+use crate::parameters::{self, CommaList, FromParameter, ParameterError};
+
+use serde_json as json;
+
+fn extract<T: FromParameter>(parameter: json::Value) -> Result<T, ParameterError> {
+    parameters::extract(Some(&parameter))
+}
+
+#[test]
+fn test_integer() {
+    assert_eq!(extract::<i32>(json::json!(5)), Ok(5));
+    assert_eq!(extract::<i32>(json::json!(-5)), Ok(-5));
+    assert_eq!(extract::<i32>(json::json!("5")), Ok(5));
+    assert_eq!(extract::<i32>(json::json!(" -5 ")), Ok(-5));
+    assert_eq!(extract::<u64>(json::json!(u64::MAX)), Ok(u64::MAX));
+
+    for invalid in &[
+        json::json!(1.5),
+        json::json!("1.5"),
+        json::json!(""),
+        json::json!("five"),
+        json::json!(true),
+        json::json!([1]),
+        json::json!(i64::MAX),
+    ] {
+        let error = extract::<i32>(invalid.clone()).expect_err("BUG: invalid integer accepted");
+        assert_eq!(error.expected, "integer");
+    }
+    assert!(extract::<u32>(json::json!(-1)).is_err());
+    assert!(extract::<u32>(json::json!("-1")).is_err());
+
+    // Missing parameter is accepted only by optional types
+    let error = parameters::extract::<i32>(None).expect_err("BUG: missing integer accepted");
+    assert_eq!(error.got, None);
+    assert_eq!(parameters::extract::<Option<i32>>(None), Ok(None));
+    assert_eq!(extract::<Option<i32>>(json::json!("7")), Ok(Some(7)));
+    assert!(extract::<Option<i32>>(json::json!("x")).is_err());
+}
+
+#[test]
+fn test_scalars() {
+    assert_eq!(extract::<f64>(json::json!(1.5)), Ok(1.5));
+    assert_eq!(extract::<f64>(json::json!("2")), Ok(2.0));
+    assert!(extract::<f64>(json::json!("NaN")).is_err());
+
+    assert_eq!(extract::<bool>(json::json!(true)), Ok(true));
+    assert_eq!(extract::<bool>(json::json!("False")), Ok(false));
+    assert!(extract::<bool>(json::json!(1)).is_err());
+
+    assert_eq!(extract::<String>(json::json!("a,b")), Ok("a,b".to_string()));
+    assert_eq!(extract::<String>(json::json!(12)), Ok("12".to_string()));
+    assert!(extract::<String>(json::json!(["a"])).is_err());
+}
+
+#[test]
+fn test_comma_list() {
+    let expected = Ok(CommaList(vec![1u32, 2, 3]));
+    assert_eq!(extract(json::json!("1,2,3")), expected);
+    assert_eq!(extract(json::json!(" 1 , 2,3 ")), expected);
+    assert_eq!(extract(json::json!([1, "2", 3])), expected);
+    // Single value is a list with one item
+    assert_eq!(extract(json::json!(4)), Ok(CommaList(vec![4u32])));
+    assert_eq!(extract(json::json!("4")), Ok(CommaList(vec![4u32])));
+
+    for invalid in &[
+        json::json!(""),
+        json::json!("1,,3"),
+        json::json!("1,x"),
+        json::json!([]),
+        json::json!([1, [2]]),
+        json::json!({ "list": 1 }),
+    ] {
+        let error =
+            extract::<CommaList<u32>>(invalid.clone()).expect_err("BUG: invalid list accepted");
+        assert_eq!(
+            error.expected,
+            "comma separated list (non-negative integer)"
+        );
+    }
+}
+
+#[test]
+fn test_pair() {
+    type Pair = (String, Option<String>);
+
+    let pair = |first: &str, second: Option<&str>| -> Result<Pair, ParameterError> {
+        Ok((first.to_string(), second.map(str::to_string)))
+    };
+    assert_eq!(extract::<Pair>(json::json!("name")), pair("name", None));
+    // Only the first delimiter separates the values
+    assert_eq!(
+        extract::<Pair>(json::json!("name, a,b")),
+        pair("name", Some("a,b"))
+    );
+    assert_eq!(extract::<Pair>(json::json!(["name"])), pair("name", None));
+    assert_eq!(
+        extract::<Pair>(json::json!(["name", 1])),
+        pair("name", Some("1"))
+    );
+
+    assert_eq!(extract::<(i32, bool)>(json::json!("1,true")), Ok((1, true)));
+    assert_eq!(
+        extract::<(i32, bool)>(json::json!([1, true])),
+        Ok((1, true))
+    );
+    for invalid in &[
+        json::json!("1"),
+        json::json!("x,true"),
+        json::json!([1]),
+        json::json!([1, true, 2]),
+        json::json!(1),
+    ] {
+        assert!(extract::<(i32, bool)>(invalid.clone()).is_err());
+    }
+}
+
+#[test]
+fn test_parameter_error() {
+    let parse = parameters::param::<i32>();
+    assert_eq!(parse(Some(&json::json!("3"))).ok(), Some(3));
+
+    let error = parse(Some(&json::json!("three"))).expect_err("BUG: invalid parameter accepted");
+    assert_eq!(error.msg(), "Invalid parameter 'three' - expected integer");
+
+    // The check reports the same error as the extractor
+    let check = parameters::check::<i32>();
+    let error = check("custom", &Some(&json::json!([3]))).expect_err("BUG: check passed");
+    assert_eq!(error.msg(), "Invalid parameter '[3]' - expected integer");
+    assert!(check("custom", &Some(&json::json!(3))).is_ok());
+}
@@ -0,0 +1,315 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Tests of the `schema` command checking the schema against the actual responses
+
+use super::utils::ZeroTime;
+use crate::command::{self, Handler as _};
+use crate::response;
+use crate::schema;
+use crate::server;
+use crate::test_utils::{self, MockHandler, ParsedResponse};
+
+use ii_async_compat::tokio;
+
+use serde_json as json;
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Requests of all standard commands responding with a body
+const SAMPLES: &[(&str, Option<&str>)] = &[
+    ("pools", None),
+    ("devs", None),
+    ("edevs", None),
+    ("summary", None),
+    ("config", None),
+    ("notify", None),
+    ("stats", None),
+    ("estats", None),
+    ("coin", None),
+    ("asccount", None),
+    ("asc", Some("0")),
+    ("lcd", None),
+    ("tunerstatus", None),
+    ("version", None),
+    ("check", Some("pools")),
+    ("help", None),
+    ("apistats", None),
+    ("schema", None),
+    ("connections", None),
+    ("lastcommands", None),
+    ("zero", Some("all,true")),
+    ("locate", Some("true")),
+    ("ascset", Some("0,freq,650")),
+    ("debug", Some("verbose")),
+    ("reloadconfig", None),
+];
+
+fn receiver<U: command::Handler + 'static>(handler: U) -> command::Receiver<ZeroTime> {
+    let statistics = Arc::new(server::Statistics::new(4));
+    statistics.record_request(
+        "10.0.0.1".parse().expect("BUG: invalid address"),
+        Some("summary"),
+        false,
+        0,
+    );
+    command::Receiver::new(handler, "TestMiner".to_string(), "v1.0".to_string(), None)
+        .with_version_info(
+            response::VersionInfo::new("TestMiner".to_string(), "v1.0".to_string())
+                .field("Type".to_string(), "Antminer S9".to_string()),
+        )
+        .with_server_statistics(statistics)
+        .with_chip_stats()
+}
+
+/// Receiver responding `stats` with per-chip details
+async fn chip_stats_receiver() -> command::Receiver<ZeroTime> {
+    let mut stats = super::handler::BasicTest::default()
+        .handle_stats(None, false)
+        .await
+        .unwrap_or_else(|_| panic!("BUG: cannot get stats"));
+    stats.asc_stats[0].chips = response::Chips(vec![
+        response::ChipStats {
+            frequency: 650.0,
+            mhs: 200.0,
+            hardware_errors: 1,
+            temperature: Some(60.0),
+        },
+        response::ChipStats {
+            frequency: 650.0,
+            mhs: 210.0,
+            hardware_errors: 0,
+            temperature: None,
+        },
+    ]);
+    receiver(MockHandler::builder().response("stats", stats).build())
+}
+
+fn matches_type(kind: &str, value: &json::Value) -> bool {
+    match kind {
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_f64(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "any" => true,
+        _ => panic!("BUG: unknown field type '{}'", kind),
+    }
+}
+
+/// Determines whether all fields of the `section` are described by the `fields` of one
+/// section variant and no mandatory field is missing
+fn matches_variant(fields: &[&json::Value], section: &json::Map<String, json::Value>) -> bool {
+    let describes = |field: &json::Value, name: &str| {
+        let pattern = field["Field"].as_str().expect("BUG: missing field name");
+        match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => pattern == name,
+        }
+    };
+    let known = section.iter().all(|(name, value)| {
+        matches!(
+            fields.iter().find(|field| describes(field, name)),
+            Some(field) if matches_type(field["Type"].as_str().unwrap_or_default(), value)
+        )
+    });
+    let complete = fields
+        .iter()
+        .filter(|field| field["Optional"] == "N")
+        .all(|field| section.contains_key(field["Field"].as_str().unwrap_or_default()));
+    known && complete
+}
+
+/// Checks every section of the `response` to `command` against the `schema` reported for it
+fn check_response(command: &str, schema: &ParsedResponse, response: &ParsedResponse) {
+    let rows: Vec<_> = schema.section("SCHEMA").iter().collect();
+    let sections = response
+        .value()
+        .as_object()
+        .expect("BUG: response is not an object")
+        .iter()
+        .filter(|(name, _)| *name != "STATUS" && *name != "id");
+    let mut checked = 0;
+    for (name, list) in sections {
+        let variants: HashSet<_> = rows
+            .iter()
+            .filter(|row| row["Section"] == name.as_str())
+            .map(|row| row["Variant"].as_str())
+            .collect();
+        assert!(
+            !variants.is_empty(),
+            "BUG: section '{}' of '{}' has no schema",
+            name,
+            command
+        );
+        for section in list.as_array().expect("BUG: section list is not an array") {
+            let section = section.as_object().expect("BUG: section is not an object");
+            let matched = variants.iter().any(|variant| {
+                let fields: Vec<_> = rows
+                    .iter()
+                    .copied()
+                    .filter(|row| {
+                        row["Section"] == name.as_str() && row["Variant"].as_str() == *variant
+                    })
+                    .collect();
+                matches_variant(&fields, section)
+            });
+            assert!(
+                matched,
+                "BUG: section '{}' of '{}' does not match schema: {:?}",
+                name, command, section
+            );
+            checked += 1;
+        }
+    }
+    assert!(checked > 0, "BUG: response to '{}' has no section", command);
+}
+
+async fn check_command(
+    receiver: &command::Receiver<ZeroTime>,
+    command: &str,
+    parameter: Option<&str>,
+) {
+    let schema = test_utils::handle(receiver, "schema", Some(json::json!(command))).await;
+    schema.assert_status(218, &format!("{} field(s)", schema.section("SCHEMA").len()));
+    let response = test_utils::handle(receiver, command, parameter.map(|p| json::json!(p))).await;
+    assert!(
+        !response.is_error(),
+        "BUG: sample request of '{}' failed",
+        command
+    );
+    check_response(command, &schema, &response);
+}
+
+#[tokio::test]
+async fn test_schema_matches_responses() {
+    let receiver = receiver(super::handler::BasicTest::default());
+    for (command, parameter) in SAMPLES {
+        check_command(&receiver, command, *parameter).await;
+    }
+    check_command(&chip_stats_receiver().await, "stats", None).await;
+
+    // All standard commands with a body are covered by the samples
+    let schema = test_utils::handle(&receiver, "schema", None).await;
+    let described: HashSet<_> = schema
+        .section("SCHEMA")
+        .iter()
+        .filter_map(|row| row["Command"].as_str())
+        .collect();
+    let sampled: HashSet<_> = SAMPLES.iter().map(|(command, _)| *command).collect();
+    assert_eq!(described, sampled);
+}
+
+#[tokio::test]
+async fn test_schema() {
+    let receiver = receiver(super::handler::BasicTest::default());
+
+    let response = test_utils::handle(&receiver, "schema", Some(json::json!("version"))).await;
+    assert_eq!(
+        response.section("SCHEMA"),
+        &[
+            json::json!({
+                "Command": "version",
+                "Section": "VERSION",
+                "Field": "TestMiner",
+                "Type": "string",
+                "Optional": "N",
+            }),
+            json::json!({
+                "Command": "version",
+                "Section": "VERSION",
+                "Field": "API",
+                "Type": "string",
+                "Optional": "N",
+            }),
+            json::json!({
+                "Command": "version",
+                "Section": "VERSION",
+                "Field": "*",
+                "Type": "string",
+                "Optional": "Y",
+            }),
+        ][..]
+    );
+
+    // Aliases are resolved and commands without body have no fields
+    let alias = test_utils::handle(&receiver, "schema", Some(json::json!("coinmine"))).await;
+    assert!(alias
+        .section("SCHEMA")
+        .iter()
+        .all(|row| row["Command"] == "coin"));
+    test_utils::handle(&receiver, "schema", Some(json::json!("pause")))
+        .await
+        .assert_status(218, "0 field(s)");
+
+    for parameter in &[json::json!("unknown"), json::json!(1)] {
+        test_utils::handle(&receiver, "schema", Some(parameter.clone()))
+            .await
+            .assert_status(14, "Invalid command");
+    }
+}
+
+#[tokio::test]
+async fn test_schema_custom_command() {
+    const VOLTAGES: &[schema::Section] = &[schema::Section {
+        name: "VOLTAGES",
+        variant: None,
+        fields: &[schema::Field {
+            name: "Voltage",
+            kind: schema::FieldType::Number,
+            optional: false,
+        }],
+    }];
+    let receiver = command::Receiver::<ZeroTime>::builder(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add(
+        "voltages",
+        command::Descriptor::new("voltages", command::HandlerType::Version, None).schema(VOLTAGES),
+    )
+    .add(
+        "volt",
+        command::Descriptor::new("volt", command::HandlerType::Version, None),
+    )
+    .build()
+    .expect("BUG: cannot build receiver");
+
+    let response = test_utils::handle(&receiver, "schema", Some(json::json!("voltages"))).await;
+    assert_eq!(
+        response.section("SCHEMA"),
+        &[json::json!({
+            "Command": "voltages",
+            "Section": "VOLTAGES",
+            "Field": "Voltage",
+            "Type": "number",
+            "Optional": "N",
+        })][..]
+    );
+    // Custom commands are not described by default
+    test_utils::handle(&receiver, "schema", Some(json::json!("volt")))
+        .await
+        .assert_status(218, "0 field(s)");
+}
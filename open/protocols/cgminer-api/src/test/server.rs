@@ -0,0 +1,1200 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Tests of the API server talking to real sockets
+
+use super::utils::ZeroTime;
+use crate::command::{self, Handler as _};
+use crate::events::{Event, EventKind, EventSink};
+use crate::response;
+use crate::server::{Handle, RateLimiter, Server, ServerGroup, Statistics};
+use crate::support::{BufferPool, ResponseBuffer, MAX_POOLED_BUFFER_CAPACITY};
+use crate::test_utils::{self, MockHandler};
+use crate::{Codec, Format};
+
+use ii_async_compat::{bytes, futures, tokio, tokio_util};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+
+use serde_json as json;
+
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tls")]
+use tokio_rustls::{rustls, webpki};
+
+fn build_receiver() -> command::Receiver<ZeroTime> {
+    command::Receiver::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+}
+
+/// Unique path of a socket file for each test
+fn socket_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cgminer-api-{}-{}.sock", name, std::process::id()))
+}
+
+async fn start_server(max_request_size: Option<usize>) -> (SocketAddr, Handle) {
+    let receiver = build_receiver();
+    let mut server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server");
+    if let Some(max_request_size) = max_request_size {
+        server = server.max_request_size(max_request_size);
+    }
+    let addr = server.local_addr().expect("BUG: missing local address");
+
+    (addr, server.start())
+}
+
+/// Reads the whole response and checks that it is terminated by NUL byte
+async fn read_response<S>(stream: &mut S) -> json::Value
+where
+    S: tokio::io::AsyncRead + Unpin,
+{
+    let mut response = vec![];
+    stream
+        .read_to_end(&mut response)
+        .await
+        .expect("BUG: cannot read response");
+    assert_eq!(
+        response.pop(),
+        Some(0),
+        "BUG: response is not NUL terminated"
+    );
+    json::from_slice(&response).expect("BUG: invalid response")
+}
+
+async fn send_request<S>(mut stream: S, request: &[u8]) -> json::Value
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    stream
+        .write_all(request)
+        .await
+        .expect("BUG: cannot send request");
+    // Signal end of the request so that incomplete requests are detected
+    AsyncWriteExt::shutdown(&mut stream)
+        .await
+        .expect("BUG: cannot shutdown write half");
+    read_response(&mut stream).await
+}
+
+async fn request(addr: SocketAddr, request: &[u8]) -> json::Value {
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    send_request(stream, request).await
+}
+
+async fn request_unix(path: &Path, request: &[u8]) -> json::Value {
+    let stream = UnixStream::connect(path)
+        .await
+        .expect("BUG: cannot connect to server");
+    send_request(stream, request).await
+}
+
+#[tokio::test]
+async fn test_server_request() {
+    let (addr, handle) = start_server(None).await;
+
+    let response = request(addr, br#"{"command": "version"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 22);
+    assert_eq!(response["VERSION"][0]["TestMiner"], "v1.0");
+
+    // Terminators appended by some clients are ignored but other data is not
+    let response = request(addr, b"{\"command\": \"summary\"}\n\0").await;
+    assert_eq!(response["STATUS"][0]["Code"], 11);
+    let response = request(addr, b"{\"command\": \"summary\"}\n\0garbage").await;
+    assert_eq!(response["STATUS"][0]["Code"], 23);
+
+    let response = request(addr, br#"{"command": "#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 23);
+
+    handle.shutdown().await;
+}
+
+/// Exact request sent by `bosminer_monitor.lua` and `cgminer_monitor.lua` of Braiins OS
+/// (`braiins-os/package/mining/*_monitor/files`)
+const MONITOR_REQUEST: &[u8] = br#"{ "command":"devs+pools" }"#;
+
+#[tokio::test]
+async fn test_server_monitor_request() {
+    let (addr, handle) = start_server(None).await;
+
+    // The monitor neither terminates the request nor closes its half of the connection. It reads
+    // the response until the server closes the connection and strips the trailing NUL byte.
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    stream
+        .write_all(MONITOR_REQUEST)
+        .await
+        .expect("BUG: cannot send request");
+    let response = read_response(&mut stream).await;
+    assert_eq!(response["devs"][0]["STATUS"][0]["Code"], 9);
+    assert_eq!(response["pools"][0]["STATUS"][0]["Code"], 7);
+
+    handle.shutdown().await;
+}
+
+#[test]
+fn test_codec_monitor_request() {
+    use tokio_util::codec::Decoder as _;
+
+    let mut codec = Codec::default();
+    let mut buf = bytes::BytesMut::from(MONITOR_REQUEST);
+    let request = codec
+        .decode(&mut buf)
+        .expect("BUG: cannot decode request")
+        .expect("BUG: incomplete request");
+    assert_eq!(
+        request.body().map(|body| body.command.as_str()),
+        Some("devs+pools")
+    );
+    assert!(buf.is_empty());
+
+    let (request, format) =
+        command::Request::from_bytes(MONITOR_REQUEST).expect("BUG: cannot parse request");
+    assert_eq!(format, Format::Json);
+    assert_eq!(
+        request.body().map(|body| body.command.as_str()),
+        Some("devs+pools")
+    );
+}
+
+/// Receiver responding `stats` and `estats` of a machine with many chips
+async fn big_stats_receiver() -> command::Receiver<ZeroTime> {
+    let mut stats = super::handler::BasicTest::default()
+        .handle_stats(None, false)
+        .await
+        .unwrap_or_else(|_| panic!("BUG: cannot get stats"));
+    let asc = stats.asc_stats.pop().expect("BUG: missing ASC stats");
+    stats.asc_stats = (0..3)
+        .map(|idx| {
+            let mut asc = asc.clone();
+            asc.header.idx = idx;
+            asc.header.id = format!("BC50 \"chain\" {}", idx);
+            asc.chips = response::Chips(
+                (0..1000)
+                    .map(|chip| response::ChipStats {
+                        frequency: 650.0 + chip as f64 / 8.0,
+                        mhs: 210.5,
+                        hardware_errors: chip,
+                        temperature: if chip % 2 == 0 { Some(60.25) } else { None },
+                    })
+                    .collect(),
+            );
+            asc
+        })
+        .collect();
+    command::Receiver::new(
+        MockHandler::builder()
+            .response("stats", stats.clone())
+            .response("estats", stats)
+            .build(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_chip_stats()
+}
+
+#[tokio::test]
+async fn test_server_big_response() {
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), big_stats_receiver().await)
+        .await
+        .expect("BUG: cannot bind server");
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let handle = server.start();
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(br#"{"command": "estats"}"#).await.unwrap();
+    let response = read_all(&mut stream).await;
+    assert!(response.len() > MAX_POOLED_BUFFER_CAPACITY);
+    assert_eq!(response.last(), Some(&0));
+    let response: json::Value =
+        json::from_slice(&response[..response.len() - 1]).expect("BUG: invalid response");
+    assert_eq!(response["STATS"][2]["chip_count"], 1000);
+
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_response_buffer() {
+    let receiver = build_receiver();
+    let context = command::Context::local();
+    let respond = || {
+        let request = command::Request::new(json::json!({ "command": "version" }));
+        receiver.handle(request, &context)
+    };
+    let expected = ResponseBuffer::new()
+        .encode(&respond().await, Format::Json)
+        .expect("BUG: cannot encode response");
+
+    let capacity = 4 * expected.len();
+    let mut buffer = ResponseBuffer::with_capacity(capacity);
+    let first = buffer
+        .encode(&respond().await, Format::Json)
+        .expect("BUG: cannot encode response");
+    let start = first.as_ptr() as usize;
+    drop(first);
+    // All following responses are encoded within the same allocation once they are dropped
+    for _ in 0..10 {
+        let encoded = buffer
+            .encode(&respond().await, Format::Json)
+            .expect("BUG: cannot encode response");
+        assert_eq!(encoded, expected);
+        let address = encoded.as_ptr() as usize;
+        assert!(address >= start && address + encoded.len() <= start + capacity);
+    }
+    // Response which is still referenced is never overwritten
+    let response = respond().await;
+    let held: Vec<_> = (0..10)
+        .map(|_| {
+            buffer
+                .encode(&response, Format::Json)
+                .expect("BUG: cannot encode response")
+        })
+        .collect();
+    assert!(held.iter().all(|encoded| *encoded == expected));
+}
+
+#[tokio::test]
+async fn test_buffer_pool() {
+    let pool = Arc::new(BufferPool::new(1));
+    let (first, second) = (pool.acquire(), pool.acquire());
+    assert_eq!(pool.idle(), 0);
+    drop(first);
+    drop(second);
+    // Only one idle buffer is kept
+    assert_eq!(pool.idle(), 1);
+    let buffer = pool.acquire();
+    assert_eq!(pool.idle(), 0);
+    drop(buffer);
+    assert_eq!(pool.idle(), 1);
+
+    // Huge responses do not grow the buffer beyond the limit
+    let receiver = big_stats_receiver().await;
+    let request = command::Request::new(json::json!({ "command": "estats" }));
+    let response = receiver.handle(request, &command::Context::local()).await;
+    let mut buffer = pool.acquire();
+    let encoded = buffer
+        .encode(&response, Format::Json)
+        .expect("BUG: cannot encode response");
+    assert!(encoded.len() > MAX_POOLED_BUFFER_CAPACITY);
+    assert!(buffer.capacity() <= MAX_POOLED_BUFFER_CAPACITY);
+    drop(buffer);
+    assert_eq!(pool.idle(), 1);
+}
+
+#[tokio::test]
+async fn test_server_text_request() {
+    let (addr, handle) = start_server(None).await;
+
+    // Like `miner.php` the client sends the request without closing the connection
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    stream
+        .write_all(b"version|")
+        .await
+        .expect("BUG: cannot send request");
+    let mut response = vec![];
+    stream
+        .read_to_end(&mut response)
+        .await
+        .expect("BUG: cannot read response");
+    assert_eq!(
+        &response[..],
+        &b"STATUS=S,When=0,Code=22,Msg=TestMiner versions,Description=TestMiner v1.0|\
+           VERSION,TestMiner=v1.0,API=3.7|\0"[..]
+    );
+
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_server_max_request_size() {
+    let (addr, handle) = start_server(Some(32)).await;
+
+    let response = request(addr, br#"{"command": "pools"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 7);
+    let response = request(
+        addr,
+        br#"{"command": "pools", "parameter": "too long request"}"#,
+    )
+    .await;
+    assert_eq!(response["STATUS"][0]["Code"], 23);
+
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_server_concurrent_clients() {
+    let (addr, handle) = start_server(None).await;
+
+    // The first client sends only a part of its request and waits
+    let mut slow_stream = TcpStream::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    slow_stream
+        .write_all(br#"{"command":"#)
+        .await
+        .expect("BUG: cannot send request");
+
+    // Other clients are served in the meantime
+    let responses =
+        futures::future::join_all((0..4).map(|_| request(addr, br#"{"command": "summary"}"#)))
+            .await;
+    for response in responses {
+        assert_eq!(response["STATUS"][0]["Code"], 11);
+    }
+
+    slow_stream
+        .write_all(br#" "config"}"#)
+        .await
+        .expect("BUG: cannot send request");
+    let response = read_response(&mut slow_stream).await;
+    assert_eq!(response["STATUS"][0]["Code"], 33);
+
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_server_access_control() {
+    let receiver = Arc::new(build_receiver().with_access_control("R:127.0.0.1".parse().unwrap()));
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver.clone())
+        .await
+        .expect("BUG: cannot bind server");
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let handle = server.start();
+
+    let response = request(addr, br#"{"command": "summary"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 11);
+    let response = request(addr, br#"{"command": "pause"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+    // Access reported by the check follows privilege of the connected client
+    let response = request(addr, br#"{"command": "check", "parameter": "pause"}"#).await;
+    assert_eq!(
+        response["CHECK"][0],
+        json::json!({ "Exists": "Y", "Access": "N" })
+    );
+    let response = request(addr, br#"{"command": "check", "parameter": "summary"}"#).await;
+    assert_eq!(
+        response["CHECK"][0],
+        json::json!({ "Exists": "Y", "Access": "Y" })
+    );
+    handle.shutdown().await;
+
+    // Client which is not allowed at all is disconnected without any response
+    let server = Server::bind("[::1]:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server");
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let handle = server.start();
+
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    // The request may not be sent at all when the server closes the connection quickly
+    let _ = stream.write_all(br#"{"command": "summary"}"#).await;
+    let mut response = vec![];
+    let _ = stream.read_to_end(&mut response).await;
+    assert!(response.is_empty(), "BUG: refused client got a response");
+
+    handle.shutdown().await;
+}
+
+#[test]
+fn test_rate_limiter() {
+    const BURST: u32 = 3;
+    let rate_limiter = RateLimiter::new(2.0, BURST);
+    let client = "10.0.0.1".parse().unwrap();
+    let other_client = "10.0.0.2".parse().unwrap();
+    let start = Instant::now();
+
+    for _ in 0..BURST {
+        assert!(rate_limiter.acquire_at(client, start));
+    }
+    assert!(!rate_limiter.acquire_at(client, start));
+    // Other clients have their own limit
+    assert!(rate_limiter.acquire_at(other_client, start));
+
+    // A single request is allowed after a token has been refilled
+    let now = start + Duration::from_millis(400);
+    assert!(!rate_limiter.acquire_at(client, now));
+    let now = start + Duration::from_millis(500);
+    assert!(rate_limiter.acquire_at(client, now));
+    assert!(!rate_limiter.acquire_at(client, now));
+
+    // The whole burst is allowed again after the bucket has been refilled
+    let now = now + Duration::from_secs(10);
+    for _ in 0..BURST {
+        assert!(rate_limiter.acquire_at(client, now));
+    }
+    assert!(!rate_limiter.acquire_at(client, now));
+}
+
+#[test]
+fn test_rate_limiter_cleanup() {
+    let rate_limiter = RateLimiter::new(1.0, 2);
+    let start = Instant::now();
+    for i in 0..10u8 {
+        assert!(rate_limiter.acquire_at([10, 0, 0, i].into(), start));
+    }
+    assert_eq!(rate_limiter.client_count(), 10);
+
+    // Only the active client is kept once the others have been idle for the refill time
+    let now = start + Duration::from_secs(2);
+    assert!(rate_limiter.acquire_at([10, 0, 1, 0].into(), now));
+    assert_eq!(rate_limiter.client_count(), 1);
+}
+
+#[tokio::test]
+async fn test_server_rate_limit() {
+    const BURST: u32 = 2;
+    let rate_limiter = Arc::new(RateLimiter::new(0.001, BURST));
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), build_receiver())
+        .await
+        .expect("BUG: cannot bind server")
+        .rate_limiter(rate_limiter.clone());
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let handle = server.start();
+
+    // The limit is shared by all connections of the client
+    for _ in 0..BURST {
+        let response = request(addr, br#"{"command": "summary"}"#).await;
+        assert_eq!(response["STATUS"][0]["Code"], 11);
+    }
+    let response = request(addr, br#"{"command": "summary"}"#).await;
+    assert_eq!(response["STATUS"][0]["STATUS"], "E");
+    assert_eq!(response["STATUS"][0]["Code"], 266);
+    assert_eq!(rate_limiter.client_count(), 1);
+
+    handle.shutdown().await;
+}
+
+/// Waits until the `condition` holds because the server updates its statistics asynchronously
+pub(super) async fn wait_until<F>(condition: F)
+where
+    F: Fn() -> bool,
+{
+    for _ in 0..100 {
+        if condition() {
+            return;
+        }
+        tokio::time::delay_for(Duration::from_millis(10)).await;
+    }
+    panic!("BUG: condition has not been met in time");
+}
+
+async fn read_all(stream: &mut TcpStream) -> Vec<u8> {
+    let mut response = vec![];
+    stream
+        .read_to_end(&mut response)
+        .await
+        .expect("BUG: cannot read response");
+    response
+}
+
+#[tokio::test]
+async fn test_server_max_connections() {
+    const MAX_CONNECTIONS: usize = 2;
+    for &notify_rejected in &[false, true] {
+        let mut server = Server::bind("127.0.0.1:0".parse().unwrap(), build_receiver())
+            .await
+            .expect("BUG: cannot bind server")
+            .max_connections(MAX_CONNECTIONS);
+        if notify_rejected {
+            server = server.notify_rejected();
+        }
+        let addr = server.local_addr().expect("BUG: missing local address");
+        let statistics = server.statistics();
+        let handle = server.start();
+
+        // Clients which have not sent their requests yet keep the connections open
+        let mut stalled = vec![];
+        for _ in 0..MAX_CONNECTIONS {
+            stalled.push(
+                TcpStream::connect(addr)
+                    .await
+                    .expect("BUG: cannot connect to server"),
+            );
+        }
+        wait_until(|| statistics.active_connections() == MAX_CONNECTIONS).await;
+
+        let mut stream = TcpStream::connect(addr)
+            .await
+            .expect("BUG: cannot connect to server");
+        if notify_rejected {
+            let response = read_response(&mut stream).await;
+            assert_eq!(response["STATUS"][0]["STATUS"], "E");
+            assert_eq!(response["STATUS"][0]["Code"], 267);
+        } else {
+            assert!(read_all(&mut stream).await.is_empty());
+        }
+        assert_eq!(statistics.rejected_connections(), 1);
+
+        // Closed connection makes room for another one
+        drop(stalled.pop());
+        wait_until(|| statistics.active_connections() < MAX_CONNECTIONS).await;
+        let response = request(addr, br#"{"command": "summary"}"#).await;
+        assert_eq!(response["STATUS"][0]["Code"], 11);
+        assert_eq!(statistics.rejected_connections(), 1);
+
+        handle.shutdown().await;
+    }
+}
+
+#[tokio::test]
+async fn test_server_read_timeout() {
+    const READ_TIMEOUT: Duration = Duration::from_millis(100);
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), build_receiver())
+        .await
+        .expect("BUG: cannot bind server")
+        .read_timeout(READ_TIMEOUT);
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let statistics = server.statistics();
+    let handle = server.start();
+
+    let start = Instant::now();
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    // Incomplete request does not prevent the timeout
+    stream
+        .write_all(br#"{"command": "#)
+        .await
+        .expect("BUG: cannot send request");
+    assert!(read_all(&mut stream).await.is_empty());
+    assert!(start.elapsed() >= READ_TIMEOUT);
+    assert_eq!(statistics.read_timeouts(), 1);
+    wait_until(|| statistics.active_connections() == 0).await;
+
+    // Client sending its request in time is not affected
+    let response = request(addr, br#"{"command": "summary"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 11);
+    assert_eq!(statistics.read_timeouts(), 1);
+
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_server_shutdown() {
+    let (addr, handle) = start_server(None).await;
+    handle.shutdown().await;
+
+    assert!(
+        TcpStream::connect(addr).await.is_err(),
+        "BUG: server still accepts connections"
+    );
+}
+
+/// Duration of the slow command which is long enough to shut down the server meanwhile
+const SLOW_DELAY: Duration = Duration::from_millis(200);
+
+async fn start_slow_server() -> (SocketAddr, Arc<Statistics>, Handle) {
+    let receiver = command::Receiver::<ZeroTime>::builder(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add_parameterless("slow", || async {
+        tokio::time::delay_for(SLOW_DELAY).await;
+        Ok(response::CustomResponse::new(
+            "SLOW",
+            1u32,
+            vec![json::json!({ "Finished": true })],
+        ))
+    })
+    .build()
+    .expect("BUG: cannot build receiver");
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server");
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let statistics = server.statistics();
+
+    (addr, statistics, server.start())
+}
+
+#[tokio::test]
+async fn test_server_graceful_shutdown() {
+    let (addr, statistics, handle) = start_slow_server().await;
+
+    let client = tokio::spawn(request(addr, br#"{"command": "slow"}"#));
+    wait_until(|| statistics.active_connections() == 1).await;
+    let shutdown = tokio::spawn(handle.shutdown_graceful(Duration::from_secs(5)));
+
+    // The request being processed is finished while new connections are refused
+    wait_until(|| std::net::TcpStream::connect(addr).is_err()).await;
+    let response = client.await.expect("BUG: client failed");
+    assert_eq!(response["STATUS"][0]["Code"], 301);
+    assert_eq!(response["SLOW"][0]["Finished"], true);
+    assert!(shutdown.await.expect("BUG: shutdown failed"));
+    assert_eq!(statistics.active_connections(), 0);
+}
+
+#[tokio::test]
+async fn test_server_graceful_shutdown_deadline() {
+    let (addr, statistics, handle) = start_slow_server().await;
+
+    let client = tokio::spawn(async move {
+        let mut stream = TcpStream::connect(addr)
+            .await
+            .expect("BUG: cannot connect to server");
+        stream
+            .write_all(br#"{"command": "slow"}"#)
+            .await
+            .expect("BUG: cannot send request");
+        read_all(&mut stream).await
+    });
+    wait_until(|| statistics.active_connections() == 1).await;
+
+    // Connection which is not finished in time is closed without response
+    let start = Instant::now();
+    assert!(!handle.shutdown_graceful(Duration::from_millis(20)).await);
+    assert!(start.elapsed() < SLOW_DELAY);
+    let response = client.await.expect("BUG: client failed");
+    assert!(response.is_empty(), "BUG: aborted client got a response");
+    wait_until(|| statistics.active_connections() == 0).await;
+}
+
+async fn start_events_server(sink: &EventSink) -> (SocketAddr, Handle) {
+    let receiver = build_receiver().with_event_sink(sink.clone());
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server");
+    let addr = server.local_addr().expect("BUG: missing local address");
+
+    (addr, server.start())
+}
+
+/// Reads one NUL terminated message from a connection which stays open
+async fn read_message(stream: &mut TcpStream) -> json::Value {
+    let mut message = vec![];
+    let mut byte = [0u8; 1];
+    loop {
+        stream
+            .read_exact(&mut byte)
+            .await
+            .expect("BUG: cannot read message");
+        if byte[0] == 0 {
+            break;
+        }
+        message.push(byte[0]);
+    }
+    json::from_slice(&message).expect("BUG: invalid message")
+}
+
+async fn subscribe(addr: SocketAddr, sink: &EventSink) -> TcpStream {
+    let subscribers = sink.subscribers();
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    stream
+        .write_all(br#"{"command": "events"}"#)
+        .await
+        .expect("BUG: cannot send request");
+    let response = read_message(&mut stream).await;
+    assert_eq!(response["STATUS"][0]["Code"], 214);
+    assert_eq!(response["STATUS"][0]["Msg"], "Subscribed to events");
+    wait_until(|| sink.subscribers() == subscribers + 1).await;
+    stream
+}
+
+#[tokio::test]
+async fn test_server_events() {
+    let sink = EventSink::default();
+    let (addr, handle) = start_events_server(&sink).await;
+
+    let mut first = subscribe(addr, &sink).await;
+    let mut second = subscribe(addr, &sink).await;
+    sink.publish(Event::new(
+        EventKind::ShareAccepted,
+        json::json!({ "Pool": 0 }),
+    ));
+    sink.publish(Event::new(EventKind::NewJob, json::json!({ "Pool": 1 })));
+    for stream in &mut [&mut first, &mut second] {
+        let message = read_message(stream).await;
+        assert_eq!(
+            message,
+            json::json!({ "EVENT": "ShareAccepted", "When": 0, "Data": { "Pool": 0 } })
+        );
+        assert_eq!(read_message(stream).await["EVENT"], "NewJob");
+    }
+
+    // Disconnected client is unsubscribed
+    drop(first);
+    wait_until(|| sink.subscribers() == 1).await;
+
+    // Other commands are still served over separate connections
+    let response = request(addr, br#"{"command": "events+pools"}"#).await;
+    assert_eq!(response["events"][0]["STATUS"][0]["Code"], 271);
+    assert_eq!(response["pools"][0]["STATUS"][0]["Code"], 7);
+
+    // Shutdown closes the remaining subscription
+    handle.shutdown().await;
+    assert!(read_all(&mut second).await.is_empty());
+    wait_until(|| sink.subscribers() == 0).await;
+}
+
+#[tokio::test]
+async fn test_server_events_dropped() {
+    let sink = EventSink::new(2);
+    let (addr, handle) = start_events_server(&sink).await;
+
+    // The client does not read so the events are queued in the subscription
+    let mut stream = subscribe(addr, &sink).await;
+    for pool in 0..5 {
+        sink.publish(Event::new(
+            EventKind::PoolState,
+            json::json!({ "Pool": pool }),
+        ));
+    }
+    let message = read_message(&mut stream).await;
+    assert_eq!(message["EVENT"], "Dropped");
+    assert_eq!(message["Count"], 3);
+    assert_eq!(sink.dropped(), 3);
+    assert_eq!(read_message(&mut stream).await["Data"]["Pool"], 3);
+    assert_eq!(read_message(&mut stream).await["Data"]["Pool"], 4);
+
+    handle.shutdown().await;
+}
+
+#[test]
+fn test_statistics_clients() {
+    let statistics = Statistics::new(2);
+    let addr = |last: u8| std::net::IpAddr::from([10, 0, 0, last]);
+    statistics.record_request(addr(1), Some("pools"), false, 1);
+    statistics.record_request(addr(2), Some("summary"), true, 2);
+    statistics.record_request(addr(1), None, true, 3);
+    // The least recently seen client is forgotten
+    statistics.record_request(addr(3), Some("devs"), false, 4);
+
+    let clients = statistics.clients();
+    let summary: Vec<_> = clients
+        .iter()
+        .map(|client| {
+            (
+                client.addr,
+                client.requests,
+                client.errors,
+                client.last_command.as_deref(),
+                client.last_seen,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (addr(3), 1, 0, Some("devs"), 4),
+            (addr(1), 2, 1, Some("pools"), 3)
+        ]
+    );
+
+    // Clients are not tracked at all without a limit
+    let statistics = Statistics::new(0);
+    statistics.record_request(addr(1), Some("pools"), false, 1);
+    assert!(statistics.clients().is_empty());
+}
+
+#[tokio::test]
+async fn test_server_connections() {
+    let statistics = Arc::new(Statistics::new(Statistics::DEFAULT_MAX_CLIENTS));
+    let receiver = build_receiver().with_server_statistics(statistics.clone());
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server")
+        .share_statistics(statistics.clone());
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let handle = server.start();
+
+    request(addr, br#"{"command": "pools+summary"}"#).await;
+    request(addr, br#"{"command": "unknown"}"#).await;
+    request(addr, b"{invalid").await;
+    let response = request(addr, br#"{"command": "connections"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 215);
+    assert_eq!(response["STATUS"][0]["Msg"], "1 API client(s)");
+    assert_eq!(
+        response["CONNECTIONS"],
+        json::json!([
+            {
+                "Total": 4,
+                "Active": 1,
+                "Rejected": 0,
+                "Read Timeouts": 0,
+            },
+            {
+                "CLIENT": 0,
+                "Address": "127.0.0.1",
+                "Requests": 3,
+                "Errors": 2,
+                "Last Command": "unknown",
+                "Last Seen": 0,
+            }
+        ])
+    );
+    wait_until(|| statistics.clients()[0].requests == 4).await;
+
+    handle.shutdown().await;
+}
+
+/// Receiver counting invocations of the `summary` handler
+async fn counting_receiver(
+    statistics: Arc<Statistics>,
+) -> (command::Receiver<ZeroTime>, Arc<AtomicUsize>) {
+    let summary = super::handler::BasicTest::default()
+        .handle_summary()
+        .await
+        .unwrap_or_else(|_| panic!("BUG: cannot get summary"));
+    let calls = Arc::new(AtomicUsize::new(0));
+    let handler_calls = calls.clone();
+    let handler = MockHandler::builder()
+        .respond_with("summary", move || {
+            handler_calls.fetch_add(1, Ordering::SeqCst);
+            Ok(summary.clone())
+        })
+        .build();
+    let receiver =
+        command::Receiver::new(handler, "TestMiner".to_string(), "v1.0".to_string(), None)
+            .with_server_statistics(statistics);
+    (receiver, calls)
+}
+
+#[tokio::test]
+async fn test_server_group() {
+    const BURST: u32 = 4;
+    let statistics = Arc::new(Statistics::new(Statistics::DEFAULT_MAX_CLIENTS));
+    let (receiver, calls) = counting_receiver(statistics.clone()).await;
+    let group = ServerGroup::new(receiver)
+        .share_statistics(statistics.clone())
+        .rate_limiter(RateLimiter::new(0.001, BURST));
+
+    // Each transport keeps its own limits
+    let first = Server::bind("127.0.0.1:0".parse().unwrap(), group.receiver())
+        .await
+        .expect("BUG: cannot bind server")
+        .max_connections(8);
+    let second = Server::bind("127.0.0.1:0".parse().unwrap(), group.receiver())
+        .await
+        .expect("BUG: cannot bind server")
+        .max_request_size(32);
+    let receiver = group.receiver();
+    let addrs = [
+        first.local_addr().expect("BUG: missing local address"),
+        second.local_addr().expect("BUG: missing local address"),
+    ];
+    let handle = group.transport(first).transport(second).start();
+
+    let response = request(
+        addrs[1],
+        br#"{"command": "summary", "parameter": "too long request"}"#,
+    )
+    .await;
+    assert_eq!(response["STATUS"][0]["Code"], 23);
+
+    // Both transports serve the same handler concurrently
+    let responses = futures::future::join_all(
+        addrs
+            .iter()
+            .cycle()
+            .take(BURST as usize)
+            .map(|addr| request(*addr, br#"{"command": "summary"}"#)),
+    )
+    .await;
+    for response in responses {
+        assert_eq!(response["STATUS"][0]["Code"], 11);
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), BURST as usize);
+
+    // The rate limit is shared by the transports
+    for addr in &addrs {
+        let response = request(*addr, br#"{"command": "summary"}"#).await;
+        assert_eq!(response["STATUS"][0]["Code"], 266);
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), BURST as usize);
+
+    // So are the statistics reported by the receiver
+    wait_until(|| statistics.total_connections() == 7 && statistics.clients()[0].requests == 7)
+        .await;
+    let response = test_utils::handle(&receiver, "connections", None).await;
+    assert_eq!(response.section("CONNECTIONS")[0]["Total"], 7);
+    assert_eq!(response.section("CONNECTIONS")[1]["Requests"], 7);
+    assert_eq!(response.section("CONNECTIONS")[1]["Errors"], 3);
+
+    assert!(handle.shutdown_graceful(Duration::from_secs(1)).await);
+    for addr in &addrs {
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+}
+
+#[tokio::test]
+#[should_panic(expected = "transport does not serve the receiver")]
+async fn test_server_group_foreign_receiver() {
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), build_receiver())
+        .await
+        .expect("BUG: cannot bind server");
+    let _ = ServerGroup::new(build_receiver()).transport(server);
+}
+
+#[tokio::test]
+async fn test_server_cancel_on_hangup() {
+    const SLOW: &str = "slow";
+    const SLOW_WRITE: &str = "slowwrite";
+    const DELAY: Duration = Duration::from_millis(200);
+
+    let slow = |finished: Arc<AtomicBool>| -> command::ParameterLessHandler {
+        Box::new(move || {
+            let finished = finished.clone();
+            Box::pin(async move {
+                tokio::time::delay_for(DELAY).await;
+                finished.store(true, Ordering::SeqCst);
+                Ok(response::CustomResponse::<json::Value>::new(
+                    "SLOW",
+                    response::StatusCode::Stats as u32,
+                    vec![],
+                )
+                .into())
+            })
+        })
+    };
+    let finished = Arc::new(AtomicBool::new(false));
+    let write_finished = Arc::new(AtomicBool::new(false));
+    let receiver = command::Receiver::<ZeroTime>::builder(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+    )
+    .add(
+        SLOW,
+        command::Descriptor::new(
+            SLOW,
+            command::HandlerType::ParameterLess(slow(finished.clone())),
+            None,
+        ),
+    )
+    .add(
+        SLOW_WRITE,
+        command::Descriptor::new(
+            SLOW_WRITE,
+            command::HandlerType::ParameterLess(slow(write_finished.clone())),
+            None,
+        )
+        .privileged(),
+    )
+    .build()
+    .expect("BUG: cannot build receiver");
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server")
+        .cancel_on_hangup();
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let statistics = server.statistics();
+    let handle = server.start();
+
+    // The client disconnects without waiting for the response
+    for request in &[
+        &br#"{"command": "slow"}"#[..],
+        br#"{"command": "slowwrite"}"#,
+    ] {
+        let mut stream = TcpStream::connect(addr)
+            .await
+            .expect("BUG: cannot connect to server");
+        stream
+            .write_all(request)
+            .await
+            .expect("BUG: cannot send request");
+        tokio::time::delay_for(DELAY / 4).await;
+        drop(stream);
+    }
+
+    tokio::time::delay_for(DELAY * 2).await;
+    assert!(
+        !finished.load(Ordering::SeqCst),
+        "BUG: handler has not been cancelled"
+    );
+    // Commands changing the state of the miner are finished anyway
+    assert!(
+        write_finished.load(Ordering::SeqCst),
+        "BUG: privileged handler has been cancelled"
+    );
+    let response = request(addr, br#"{"command": "apistats"}"#).await;
+    let stats = |command: &str| {
+        response["APISTATS"]
+            .as_array()
+            .expect("BUG: missing API statistics")
+            .iter()
+            .find(|stat| stat["Command"] == command)
+            .expect("BUG: missing statistics of slow command")
+            .clone()
+    };
+    assert_eq!(stats(SLOW)["Calls"], 0);
+    assert_eq!(stats(SLOW)["Cancelled"], 1);
+    assert_eq!(stats(SLOW_WRITE)["Calls"], 1);
+    assert_eq!(stats(SLOW_WRITE)["Cancelled"], 0);
+    // Cancelled requests are recorded as well
+    let client = &statistics.clients()[0];
+    assert_eq!(client.requests, 3);
+    assert_eq!(client.errors, 0);
+
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_server_unix_socket() {
+    let path = socket_path("round-trip");
+    let receiver = Arc::new(build_receiver());
+
+    let server = Server::bind_unix_with_mode(&path, 0o660, receiver.clone())
+        .expect("BUG: cannot bind server");
+    let mode = fs::metadata(&path)
+        .expect("BUG: missing socket file")
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o660);
+    // The private directory used for binding is removed
+    let parent = path.parent().expect("BUG: missing socket directory");
+    let file_name = path.file_name().expect("BUG: missing socket file name");
+    assert!(!parent
+        .join(format!(
+            ".{}.{}",
+            file_name.to_string_lossy(),
+            std::process::id()
+        ))
+        .exists());
+    let unix_handle = server.start();
+
+    // The same receiver is served over TCP at the same time
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server");
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let tcp_handle = server.start();
+
+    let response = request_unix(&path, br#"{"command": "version"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 22);
+    assert_eq!(response["VERSION"][0]["TestMiner"], "v1.0");
+    let response = request_unix(&path, br#"{"command": "#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 23);
+    let response = request(addr, br#"{"command": "version"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 22);
+
+    unix_handle.shutdown().await;
+    assert!(!path.exists(), "BUG: socket file has not been removed");
+    tcp_handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_server_unix_stale_socket() {
+    let path = socket_path("stale");
+
+    // Socket file is left behind when the listener is closed without a cleanup
+    drop(std::os::unix::net::UnixListener::bind(&path).expect("BUG: cannot bind socket"));
+    assert!(path.exists());
+
+    let handle = Server::bind_unix(&path, build_receiver())
+        .expect("BUG: stale socket has not been replaced")
+        .start();
+    let response = request_unix(&path, br#"{"command": "summary"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 11);
+
+    // Socket of running server cannot be taken over
+    match Server::bind_unix(&path, build_receiver()) {
+        Err(e) => assert_eq!(e.kind(), io::ErrorKind::AddrInUse),
+        Ok(_) => panic!("BUG: socket in use has been replaced"),
+    }
+
+    handle.shutdown().await;
+    assert!(!path.exists(), "BUG: socket file has not been removed");
+}
+
+/// Self-signed certificate valid for `localhost`
+#[cfg(feature = "tls")]
+const TLS_CERTIFICATE: &[u8] = include_bytes!("tls/localhost.crt");
+#[cfg(feature = "tls")]
+const TLS_KEY: &[u8] = include_bytes!("tls/localhost.key");
+
+#[cfg(feature = "tls")]
+async fn request_tls(addr: SocketAddr, request: &[u8]) -> json::Value {
+    let mut config = rustls::ClientConfig::new();
+    config
+        .root_store
+        .add_pem_file(&mut &TLS_CERTIFICATE[..])
+        .expect("BUG: cannot load certificate");
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+    let domain = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+
+    let stream = TcpStream::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    let stream = connector
+        .connect(domain, stream)
+        .await
+        .expect("BUG: TLS handshake failed");
+    send_request(stream, request).await
+}
+
+#[cfg(feature = "tls")]
+#[tokio::test]
+async fn test_server_tls() {
+    let identity = crate::server::TlsIdentity::from_pem(TLS_CERTIFICATE, TLS_KEY)
+        .expect("BUG: cannot load TLS identity");
+    let server = Server::bind_tls("127.0.0.1:0".parse().unwrap(), build_receiver(), identity)
+        .await
+        .expect("BUG: cannot bind server");
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let handle = server.start();
+
+    let response = request_tls(addr, br#"{"command": "version"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 22);
+    assert_eq!(response["VERSION"][0]["TestMiner"], "v1.0");
+
+    // Plain text client fails the handshake and is disconnected
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    stream
+        .write_all(br#"{"command": "version"}"#)
+        .await
+        .expect("BUG: cannot send request");
+    let mut response = vec![];
+    let _ = stream.read_to_end(&mut response).await;
+    assert!(json::from_slice::<json::Value>(&response).is_err());
+
+    // The server keeps serving other clients
+    let response = request_tls(addr, br#"{"command": "summary"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 11);
+
+    handle.shutdown().await;
+}
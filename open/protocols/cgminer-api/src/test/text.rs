@@ -0,0 +1,293 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Tests of the plain-text format of requests and responses
+
+use super::utils::ZeroTime;
+use crate::command::{self, Handler as _};
+use crate::response;
+use crate::support;
+use crate::test_utils::MockHandler;
+use crate::text;
+use crate::Codec;
+
+use ii_async_compat::{bytes, tokio, tokio_util};
+use tokio_util::codec::{Decoder, Encoder};
+
+use bytes::BytesMut;
+use serde_json as json;
+
+/// Sends plain-text `request` through the codec and returns the response without the terminating
+/// NUL byte
+async fn text_roundtrip(request: &[u8]) -> String {
+    let command_receiver = command::Receiver::<ZeroTime>::new(
+        super::handler::BasicTest::default(),
+        "CGMiner".to_string(),
+        "4.11.1".to_string(),
+        None,
+    );
+    let mut codec = Codec::default();
+
+    let mut buf = BytesMut::from(request);
+    let request = codec
+        .decode_eof(&mut buf)
+        .expect("BUG: cannot decode request")
+        .expect("BUG: missing request");
+    let response = command_receiver
+        .handle(request, &command::Context::local())
+        .await;
+
+    codec
+        .encode(response, &mut buf)
+        .expect("BUG: cannot encode response");
+    assert_eq!(buf.last(), Some(&0), "BUG: response is not NUL terminated");
+    String::from_utf8(buf[..buf.len() - 1].to_vec()).expect("BUG: invalid response")
+}
+
+#[tokio::test]
+async fn test_text_response() {
+    // Expected responses are written by hand after the plain-text format of CGMiner 4.11.1
+    // (`api.c`), they have not been captured from a running miner
+    assert_eq!(
+        text_roundtrip(b"version").await,
+        "STATUS=S,When=0,Code=22,Msg=CGMiner versions,Description=CGMiner 4.11.1|\
+         VERSION,CGMiner=4.11.1,API=3.7|"
+    );
+    assert_eq!(
+        text_roundtrip(b"locate|").await,
+        "STATUS=S,When=0,Code=210,Msg=Locate mode is inactive,Description=CGMiner 4.11.1|\
+         LOCATE,Active=N|"
+    );
+    assert_eq!(
+        text_roundtrip(b"pool").await,
+        "STATUS=E,When=0,Code=14,Msg=Invalid command,Description=CGMiner 4.11.1|"
+    );
+
+    // Fields follow the order of the JSON response
+    let response = text_roundtrip(b"summary").await;
+    assert!(response.starts_with(
+        "STATUS=S,When=0,Code=11,Msg=Summary,Description=CGMiner 4.11.1|\
+         SUMMARY,Elapsed=0,MHS av=0.0,MHS 5s=0.0,MHS 1m=0.0,MHS 5m=0.0,MHS 15m=0.0,\
+         Found Blocks=0,Getworks=0,Accepted=0,"
+    ));
+    assert!(response.ends_with(",Pool Dead Park=N,Paused=N|"));
+
+    // Indexed items start with the index instead of the body name
+    let response = text_roundtrip(b"pools").await;
+    assert!(
+        response.contains("|POOL=0,URL=,Status=Alive,Priority=0,Quota=0,Long Poll=N,Getworks=0,")
+    );
+    assert!(response.ends_with(
+        ",Has Stratum=false,Stratum Active=false,Stratum URL=,\
+         Stratum Difficulty=0.0,Has Vmask=false,Has GBT=false,Best Share=0,Pool Rejected%=0.0,\
+         Pool Stale%=0.0,Bad Work=0,Current Block Height=0,Current Block Version=0,\
+         AsicBoost=false|"
+    ));
+    let response = text_roundtrip(b"devs").await;
+    assert!(response.contains("|ASC=0,Name=BC5,ID=0,Enabled=Y,Status=Alive,"));
+}
+
+#[tokio::test]
+async fn test_text_escaping() {
+    assert_eq!(
+        text_roundtrip(b"poolpriority|1,x").await,
+        "STATUS=E,When=0,Code=252,Msg=Invalid poolpriority parameter '1\\,x',\
+         Description=CGMiner 4.11.1|"
+    );
+    // Only the first separator splits the command from its parameter
+    assert_eq!(
+        text_roundtrip(b"poolpriority|a=\\|b").await,
+        "STATUS=E,When=0,Code=252,Msg=Invalid poolpriority parameter 'a\\=\\\\\\|b',\
+         Description=CGMiner 4.11.1|"
+    );
+}
+
+#[tokio::test]
+async fn test_text_multiple() {
+    // Batched responses are serialized one by one in the order of commands
+    let response = text_roundtrip(b"version+locate").await;
+    assert!(response.starts_with("STATUS=S,When=0,Code=22,"));
+    assert!(response.contains("VERSION,CGMiner=4.11.1,API=3.7|STATUS=S,When=0,Code=210,"));
+    assert!(response.ends_with("LOCATE,Active=N|"));
+}
+
+/// Encodes bare acknowledgement which has a different serialization in each format
+fn encode_bye(codec: &mut Codec) -> BytesMut {
+    let mut buf = BytesMut::new();
+    codec
+        .encode(
+            support::ResponseType::Action(support::ActionResponse::new("BYE")),
+            &mut buf,
+        )
+        .expect("BUG: cannot encode response");
+    buf
+}
+
+#[test]
+fn test_text_codec() {
+    let mut codec = Codec::default();
+    let mut buf = BytesMut::new();
+
+    // Plain-text request is incomplete until terminated
+    buf.extend_from_slice(b" quit|");
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    buf.extend_from_slice(b"\n{\"command\": \"quit\"}");
+    assert!(codec.decode(&mut buf).unwrap().is_some());
+    assert_eq!(&encode_bye(&mut codec)[..], b"BYE\0");
+
+    // The format is detected for each request
+    assert!(codec.decode(&mut buf).unwrap().is_some());
+    assert_eq!(
+        &encode_bye(&mut codec)[..],
+        b"{\"STATUS\":\"BYE\",\"id\":1}\0"
+    );
+
+    // Closing the connection terminates plain-text request
+    buf.extend_from_slice(b"quit");
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    assert!(codec.decode_eof(&mut buf).unwrap().is_some());
+    assert!(buf.is_empty());
+    assert_eq!(&encode_bye(&mut codec)[..], b"BYE\0");
+}
+
+#[test]
+fn test_escape_text() {
+    let mut text = String::new();
+    support::escape_text("user.rig|1,a=b\\c \"d\" ž", &mut text);
+    assert_eq!(text, "user.rig\\|1\\,a\\=b\\\\c \"d\" ž");
+    assert_eq!(support::unescape_text(&text), "user.rig|1,a=b\\c \"d\" ž");
+    // Unnecessary and trailing escapes are tolerated
+    assert_eq!(support::unescape_text("\\a\\"), "a\\");
+
+    assert_eq!(support::split_text("a\\|b|c|", '|'), vec!["a\\|b", "c", ""]);
+    assert_eq!(support::split_text("a\\\\|b", '|'), vec!["a\\\\", "b"]);
+    assert_eq!(support::split_text("", '|'), vec![""]);
+}
+
+#[test]
+fn test_parse_response() {
+    let sections = text::parse_response(
+        "STATUS=S,When=0,Code=7,Msg=1 Pool(s),Description=CGMiner 4.11.1|\
+         POOL=0,URL=stratum+tcp://pool\\|1,User=a\\=b\\,c,Empty=,Flag|\
+         SUMMARY,Elapsed=10|\0",
+    );
+    assert_eq!(sections.len(), 3);
+    assert_eq!(sections[0].name, None);
+    assert_eq!(sections[0].get("Msg"), Some("1 Pool(s)"));
+    assert_eq!(sections[1].get("POOL"), Some("0"));
+    assert_eq!(sections[1].get("URL"), Some("stratum+tcp://pool|1"));
+    assert_eq!(sections[1].get("User"), Some("a=b,c"));
+    assert_eq!(sections[1].get("Empty"), Some(""));
+    assert_eq!(sections[1].get("Flag"), Some(""));
+    assert_eq!(sections[2].name.as_deref(), Some("SUMMARY"));
+    assert_eq!(
+        sections[2].fields,
+        vec![("Elapsed".to_string(), "10".to_string())]
+    );
+}
+
+/// Deterministic generator of strings mixing characters with special meaning in both formats
+struct StringGenerator(u64);
+
+impl StringGenerator {
+    const ALPHABET: &'static [char] = &[
+        'a', 'Z', '0', '.', ' ', ',', '|', '=', '\\', '"', '\'', ':', '/', '\n', '\t', 'ž', '€',
+        '+',
+    ];
+
+    /// Xorshift pseudo-random numbers are good enough to cover the combinations
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn next_string(&mut self) -> String {
+        let len = self.next_u64() % 12;
+        (0..len)
+            .map(|_| Self::ALPHABET[(self.next_u64() % Self::ALPHABET.len() as u64) as usize])
+            .collect()
+    }
+}
+
+/// Sends `request` to a receiver with pool of the `user` and `url` and returns the encoded
+/// response without the terminating NUL byte
+async fn encode_with_pool(user: &str, url: &str, request: &[u8]) -> String {
+    let mut pools = super::handler::BasicTest::default()
+        .handle_pools()
+        .await
+        .unwrap_or_else(|_| panic!("BUG: cannot get pools"));
+    pools.list[0].user = user.to_string();
+    pools.list[0].url = url.to_string();
+    let enabled_url = url.to_string();
+    let receiver = command::Receiver::<ZeroTime>::new(
+        MockHandler::builder()
+            .response("pools", pools)
+            .respond_with("enablepool", move || {
+                Ok(response::EnablePool::Enabled {
+                    idx: 0,
+                    url: enabled_url.clone(),
+                })
+            })
+            .build(),
+        "CGMiner".to_string(),
+        "4.11.1".to_string(),
+        None,
+    );
+    let (request, format) = command::Request::from_bytes(request).expect("BUG: invalid request");
+    let mut buf = BytesMut::new();
+    Codec::new(format)
+        .encode(
+            receiver.handle(request, &command::Context::local()).await,
+            &mut buf,
+        )
+        .expect("BUG: cannot encode response");
+    assert_eq!(buf.last(), Some(&0), "BUG: response is not NUL terminated");
+    String::from_utf8(buf[..buf.len() - 1].to_vec()).expect("BUG: invalid response")
+}
+
+#[tokio::test]
+async fn test_escaping_roundtrip() {
+    let mut generator = StringGenerator(0x2545_f491_4f6c_dd1d);
+    for _ in 0..200 {
+        let user = generator.next_string();
+        let url = generator.next_string();
+        let msg = format!("Enabling pool 0:'{}'", url);
+
+        let response = encode_with_pool(&user, &url, br#"{"command": "pools"}"#).await;
+        let response: json::Value = json::from_str(&response).expect("BUG: invalid JSON");
+        assert_eq!(response["POOLS"][0]["User"], user.as_str());
+        assert_eq!(response["POOLS"][0]["URL"], url.as_str());
+        let response =
+            encode_with_pool(&user, &url, br#"{"command": "enablepool", "parameter": 0}"#).await;
+        let response: json::Value = json::from_str(&response).expect("BUG: invalid JSON");
+        assert_eq!(response["STATUS"][0]["Msg"], msg.as_str());
+
+        let sections = text::parse_response(&encode_with_pool(&user, &url, b"pools").await);
+        assert_eq!(sections.len(), 2, "BUG: invalid sections of {:?}", user);
+        assert_eq!(sections[1].get("User"), Some(user.as_str()));
+        assert_eq!(sections[1].get("URL"), Some(url.as_str()));
+        let sections = text::parse_response(&encode_with_pool(&user, &url, b"enablepool|0").await);
+        assert_eq!(sections[0].get("Msg"), Some(msg.as_str()));
+    }
+}
@@ -21,6 +21,7 @@
 // contact us at opensource@braiins.com.
 
 use crate::command;
+use crate::ordered;
 use crate::response;
 use crate::support;
 use crate::Codec;
@@ -33,7 +34,7 @@ use bytes::BytesMut;
 use json::Value;
 use serde_json as json;
 
-struct ZeroTime;
+pub struct ZeroTime;
 
 impl support::When for ZeroTime {
     fn when() -> response::Time {
@@ -41,12 +42,13 @@ impl support::When for ZeroTime {
     }
 }
 
-pub async fn codec_roundtrip<T>(command: json::Value, custom_commands: T) -> Value
+/// Same as `codec_roundtrip` but the order of fields of the response is kept
+pub async fn ordered_roundtrip<T>(command: json::Value, custom_commands: T) -> ordered::Value
 where
     T: Into<Option<command::Map>>,
 {
     let command_receiver = command::Receiver::<ZeroTime>::new(
-        super::handler::BasicTest,
+        super::handler::BasicTest::default(),
         "TestMiner".to_string(),
         "v1.0".to_string(),
         custom_commands,
@@ -57,8 +59,27 @@ where
     command_buf.extend_from_slice(command.to_string().as_bytes());
 
     let command = codec.decode(&mut command_buf).unwrap().unwrap();
-    let response = command_receiver.handle(command).await;
-    json::to_value(&response).unwrap()
+    let response = command_receiver
+        .handle(command, &command::Context::local())
+        .await;
+    ordered::to_value(&response).unwrap()
+}
+
+pub async fn codec_roundtrip<T>(command: json::Value, custom_commands: T) -> Value
+where
+    T: Into<Option<command::Map>>,
+{
+    ordered_roundtrip(command, custom_commands).await.into()
+}
+
+/// Names of fields of the `object` in their order
+pub fn field_names(object: &ordered::Value) -> Vec<&str> {
+    object
+        .as_object()
+        .expect("BUG: value is not an object")
+        .keys()
+        .map(String::as_str)
+        .collect()
 }
 
 type JsonMap = json::Map<String, Value>;
@@ -0,0 +1,339 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Tests of the WebSocket transport with a real WebSocket client
+
+use super::server::wait_until;
+use super::utils::ZeroTime;
+use crate::access::{Privilege, Tokens};
+use crate::command;
+use crate::server::{self, RateLimiter, ServerGroup, Statistics};
+use crate::websocket::Server;
+
+use futures::{SinkExt as _, StreamExt as _};
+use ii_async_compat::{futures, tokio};
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::http;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use serde_json as json;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn request(stream: &mut WebSocketStream<TcpStream>, request: &str) -> json::Value {
+    stream
+        .send(Message::Text(request.to_string()))
+        .await
+        .expect("BUG: cannot send request");
+    match stream.next().await {
+        Some(Ok(Message::Text(response))) => {
+            json::from_str(&response).expect("BUG: invalid response")
+        }
+        message => panic!("BUG: unexpected message {:?}", message),
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_sequential_requests() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server");
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let handle = server.start();
+
+    let (mut stream, _) = tokio_tungstenite::connect_async(format!("ws://{}/", addr).as_str())
+        .await
+        .expect("BUG: cannot connect to server");
+
+    // All requests are served over the same connection
+    let response = request(&mut stream, r#"{"command": "version"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 22);
+    assert_eq!(response["VERSION"][0]["TestMiner"], "v1.0");
+
+    let response = request(&mut stream, r#"{"command": "summary"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 11);
+
+    // Malformed request does not close the connection
+    let response = request(&mut stream, r#"{"command": "#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 23);
+
+    let response = request(&mut stream, r#"{"command": "summary+pools"}"#).await;
+    assert_eq!(response["summary"][0]["STATUS"][0]["Code"], 11);
+    assert_eq!(response["pools"][0]["STATUS"][0]["Code"], 7);
+
+    stream
+        .close(None)
+        .await
+        .expect("BUG: cannot close connection");
+    handle.shutdown().await;
+}
+
+async fn tcp_request(addr: std::net::SocketAddr, request: &str) -> json::Value {
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .expect("BUG: cannot send request");
+    let mut response = vec![];
+    stream
+        .read_to_end(&mut response)
+        .await
+        .expect("BUG: cannot read response");
+    assert_eq!(response.pop(), Some(0));
+    json::from_slice(&response).expect("BUG: invalid response")
+}
+
+#[tokio::test]
+async fn test_websocket_server_group() {
+    const BURST: u32 = 3;
+    let statistics = Arc::new(Statistics::new(Statistics::DEFAULT_MAX_CLIENTS));
+    let receiver = command::Receiver::<ZeroTime>::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let websocket_statistics = Arc::new(Statistics::new(Statistics::DEFAULT_MAX_CLIENTS));
+    let group = ServerGroup::new(receiver)
+        .share_statistics(statistics.clone())
+        .rate_limiter(RateLimiter::new(0.001, BURST));
+    let websocket = Server::bind("127.0.0.1:0".parse().unwrap(), group.receiver())
+        .await
+        .expect("BUG: cannot bind server")
+        .share_statistics(websocket_statistics.clone());
+    let own_statistics = websocket.statistics();
+    let tcp = server::Server::bind("127.0.0.1:0".parse().unwrap(), group.receiver())
+        .await
+        .expect("BUG: cannot bind server");
+    let websocket_addr = websocket.local_addr().expect("BUG: missing local address");
+    let tcp_addr = tcp.local_addr().expect("BUG: missing local address");
+    let handle = group.transport(websocket).transport(tcp).start();
+
+    let (mut stream, _) =
+        tokio_tungstenite::connect_async(format!("ws://{}/", websocket_addr).as_str())
+            .await
+            .expect("BUG: cannot connect to server");
+
+    // Requests over both transports take tokens from the same bucket
+    let (first, second) = futures::future::join(
+        request(&mut stream, r#"{"command": "summary"}"#),
+        tcp_request(tcp_addr, r#"{"command": "summary"}"#),
+    )
+    .await;
+    assert_eq!(first["STATUS"][0]["Code"], 11);
+    assert_eq!(second["STATUS"][0]["Code"], 11);
+    let response = request(&mut stream, r#"{"command": "pools"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 7);
+    let response = tcp_request(tcp_addr, r#"{"command": "pools"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 266);
+    let response = request(&mut stream, r#"{"command": "pools"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 266);
+
+    assert_eq!(statistics.total_connections(), 3);
+    let clients = statistics.clients();
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].requests, 5);
+    assert_eq!(clients[0].errors, 2);
+
+    // Statistics shared by the WebSocket transport alone are updated besides the group ones
+    for statistics in &[websocket_statistics, own_statistics] {
+        assert_eq!(statistics.total_connections(), 1);
+        let clients = statistics.clients();
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].requests, 3);
+        assert_eq!(clients[0].errors, 1);
+    }
+
+    stream
+        .close(None)
+        .await
+        .expect("BUG: cannot close connection");
+    assert!(handle.shutdown_graceful(Duration::from_secs(1)).await);
+}
+
+#[tokio::test]
+async fn test_websocket_max_connections() {
+    const MAX_CONNECTIONS: usize = 2;
+    let receiver = command::Receiver::<ZeroTime>::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server")
+        .max_connections(MAX_CONNECTIONS);
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let statistics = server.statistics();
+    let handle = server.start();
+
+    let url = format!("ws://{}/", addr);
+    let mut streams = vec![];
+    for _ in 0..MAX_CONNECTIONS {
+        let (stream, _) = tokio_tungstenite::connect_async(url.as_str())
+            .await
+            .expect("BUG: cannot connect to server");
+        streams.push(stream);
+    }
+
+    // Connection over the limit is closed even before the handshake
+    assert!(tokio_tungstenite::connect_async(url.as_str())
+        .await
+        .is_err());
+    assert_eq!(statistics.rejected_connections(), 1);
+
+    // Closed connection makes room for another one
+    let mut stream = streams.pop().expect("BUG: missing stream");
+    stream
+        .close(None)
+        .await
+        .expect("BUG: cannot close connection");
+    drop(stream);
+    wait_until(|| statistics.active_connections() < MAX_CONNECTIONS).await;
+    let (mut stream, _) = tokio_tungstenite::connect_async(url.as_str())
+        .await
+        .expect("BUG: cannot connect to server");
+    let response = request(&mut stream, r#"{"command": "summary"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 11);
+    assert_eq!(statistics.rejected_connections(), 1);
+
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_websocket_auth() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    )
+    .with_tokens(Tokens::new().add("secret", Privilege::Write));
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server");
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let handle = server.start();
+    let url = format!("ws://{}/", addr);
+
+    let (mut stream, _) = tokio_tungstenite::connect_async(url.as_str())
+        .await
+        .expect("BUG: cannot connect to server");
+    let response = request(&mut stream, r#"{"command": "pause"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+    let response = request(&mut stream, r#"{"command": "auth", "parameter": "secret"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 220);
+    // The following requests of the connection are privileged without any token
+    let response = request(&mut stream, r#"{"command": "pause"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 206);
+    let response = request(&mut stream, r#"{"command": "resume"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 207);
+
+    // Fresh connection is not privileged
+    let (mut fresh, _) = tokio_tungstenite::connect_async(url.as_str())
+        .await
+        .expect("BUG: cannot connect to server");
+    let response = request(&mut fresh, r#"{"command": "pause"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 45);
+
+    for stream in &mut [stream, fresh] {
+        stream
+            .close(None)
+            .await
+            .expect("BUG: cannot close connection");
+    }
+    handle.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_websocket_origin() {
+    let receiver = command::Receiver::<ZeroTime>::new(
+        super::handler::BasicTest::default(),
+        "TestMiner".to_string(),
+        "v1.0".to_string(),
+        None,
+    );
+    let server = Server::bind("127.0.0.1:0".parse().unwrap(), receiver)
+        .await
+        .expect("BUG: cannot bind server")
+        .allow_origin("http://Miner.local");
+    let addr = server.local_addr().expect("BUG: missing local address");
+    let handle = server.start();
+    let connect = |origin: Option<&str>| {
+        let mut request = http::Request::builder().uri(format!("ws://{}/", addr));
+        if let Some(origin) = origin {
+            request = request.header(http::header::ORIGIN, origin);
+        }
+        tokio_tungstenite::connect_async(request.body(()).expect("BUG: invalid request"))
+    };
+
+    // Clients other than browsers do not send any origin
+    let (mut stream, _) = connect(None).await.expect("BUG: cannot connect to server");
+    let response = request(&mut stream, r#"{"command": "version"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 22);
+    let (mut allowed, _) = connect(Some("http://miner.local"))
+        .await
+        .expect("BUG: cannot connect to server");
+    let response = request(&mut allowed, r#"{"command": "version"}"#).await;
+    assert_eq!(response["STATUS"][0]["Code"], 22);
+
+    // Handshake of a web page from elsewhere is refused
+    let mut refused = TcpStream::connect(addr)
+        .await
+        .expect("BUG: cannot connect to server");
+    let handshake = format!(
+        "GET / HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\
+         Origin: http://evil.example\r\n\r\n",
+        addr
+    );
+    refused
+        .write_all(handshake.as_bytes())
+        .await
+        .expect("BUG: cannot send handshake");
+    let mut response = vec![];
+    refused
+        .read_to_end(&mut response)
+        .await
+        .expect("BUG: cannot read response");
+    assert!(response.starts_with(b"HTTP/1.1 403"));
+
+    for stream in &mut [stream, allowed] {
+        stream
+            .close(None)
+            .await
+            .expect("BUG: cannot close connection");
+    }
+    handle.shutdown().await;
+}
@@ -0,0 +1,317 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Utilities for testing of API integrations without real hardware
+//!
+//! `MockHandler` answers each command with a response configured by its builder and
+//! `ParsedResponse` gives access to sections of a serialized response:
+//!
+//! ```
+//! use ii_async_compat::tokio;
+//! use ii_cgminer_api::test_utils::{self, MockHandler};
+//! use ii_cgminer_api::{command, response, support};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let receiver = command::Receiver::<support::UnixTime>::new(
+//!     MockHandler::builder()
+//!         .response("pools", response::Pools { list: vec![] })
+//!         .error("summary", || response::ErrorCode::HardwareError.into())
+//!         .build(),
+//!     "Miner".to_string(),
+//!     "v1.0".to_string(),
+//!     None,
+//! );
+//! let response = test_utils::handle(&receiver, "pools", None).await;
+//! response.assert_status(7, "0 Pool(s)");
+//! # }
+//! ```
+
+use crate::command::{self, Handler};
+use crate::json;
+use crate::response;
+use crate::support::{
+    AddPoolParameter, AscSetParameter, DebugFlag, LocateSetting, ResponseType, StatsFilter, When,
+    ZeroTarget,
+};
+
+use std::any::Any;
+use std::collections::HashMap;
+
+type Respond = Box<dyn Fn() -> command::Result<Box<dyn Any + Send>> + Send + Sync>;
+
+/// Configured response of a command together with the name of its type
+struct MockResponse {
+    respond: Respond,
+    type_name: &'static str,
+}
+
+/// Handler answering commands with canned responses. Commands without a configured response fail
+/// with `ErrorCode::CommandFailed` except `lcd` which is assembled from the other responses by
+/// default (see `command::default_lcd`) and `reloadconfig` which is not supported by default. The response of `version` configures versions of miner
+/// components (see `command::Handler::handle_version_extra`) and there are none by default.
+pub struct MockHandler {
+    responses: HashMap<&'static str, MockResponse>,
+}
+
+impl MockHandler {
+    pub fn builder() -> MockHandlerBuilder {
+        MockHandlerBuilder {
+            responses: HashMap::new(),
+        }
+    }
+
+    /// Returns the configured response of the `command`. A response which does not have the type
+    /// expected by the handler of the `command` fails with `ErrorCode::InternalError` naming both
+    /// types.
+    fn respond<R: 'static>(&self, command: &str) -> command::Result<R> {
+        let mock = self
+            .responses
+            .get(command)
+            .ok_or_else(|| response::ErrorCode::CommandFailed(command.to_string()))?;
+        let response = (mock.respond)()?;
+        response.downcast().map(|response| *response).map_err(|_| {
+            response::ErrorCode::InternalError.with_context(format!(
+                "mock response of '{}' has type {} instead of {}",
+                command,
+                mock.type_name,
+                std::any::type_name::<R>()
+            ))
+        })
+    }
+}
+
+/// Configures responses of `MockHandler`. The commands are identified by their names such as
+/// `pools` or `asc` (aliases are not supported).
+pub struct MockHandlerBuilder {
+    responses: HashMap<&'static str, MockResponse>,
+}
+
+impl MockHandlerBuilder {
+    /// Answers the `command` with result of the `respond` closure which is called on every
+    /// invocation of the command
+    pub fn respond_with<F, R>(mut self, command: &'static str, respond: F) -> Self
+    where
+        F: Fn() -> command::Result<R> + Send + Sync + 'static,
+        R: Send + 'static,
+    {
+        self.responses.insert(
+            command,
+            MockResponse {
+                respond: Box::new(move || {
+                    respond().map(|response| Box::new(response) as Box<dyn Any + Send>)
+                }),
+                type_name: std::any::type_name::<R>(),
+            },
+        );
+        self
+    }
+
+    /// Answers the `command` with a copy of the `response`
+    pub fn response<R>(self, command: &'static str, response: R) -> Self
+    where
+        R: Clone + Send + Sync + 'static,
+    {
+        self.respond_with(command, move || Ok(response.clone()))
+    }
+
+    /// Fails the `command` with the `error`
+    pub fn error<F>(self, command: &'static str, error: F) -> Self
+    where
+        F: Fn() -> response::Error + Send + Sync + 'static,
+    {
+        self.respond_with::<_, ()>(command, move || Err(error()))
+    }
+
+    pub fn build(self) -> MockHandler {
+        MockHandler {
+            responses: self.responses,
+        }
+    }
+}
+
+/// Implements all handler methods by the configured responses of the commands. The parameters
+/// are ignored.
+macro_rules! mock_handler {
+    ($($method:ident($($arg:ident: $type:ty),*) -> $command:literal: $response:ty;)+) => {
+        #[async_trait::async_trait]
+        impl Handler for MockHandler {
+            $(
+                async fn $method(&self, $($arg: $type),*) -> command::Result<$response> {
+                    $(let _ = $arg;)*
+                    self.respond($command)
+                }
+            )+
+
+            async fn handle_lcd(&self) -> command::Result<response::Lcd> {
+                if self.responses.contains_key("lcd") {
+                    self.respond("lcd")
+                } else {
+                    command::default_lcd(self).await
+                }
+            }
+
+            async fn handle_reload_config(&self) -> command::Result<response::ReloadConfig> {
+                if self.responses.contains_key("reloadconfig") {
+                    self.respond("reloadconfig")
+                } else {
+                    command::default_reload_config(self).await
+                }
+            }
+
+            async fn handle_version_extra(&self) -> command::Result<Vec<(String, String)>> {
+                if self.responses.contains_key("version") {
+                    self.respond("version")
+                } else {
+                    Ok(vec![])
+                }
+            }
+        }
+    };
+}
+
+mock_handler!(
+    handle_pools() -> "pools": response::Pools;
+    handle_devs(idx: Option<i32>) -> "devs": response::Devs;
+    handle_edevs() -> "edevs": response::Devs;
+    handle_summary() -> "summary": response::Summary;
+    handle_switch_pool(idx: i32) -> "switchpool": response::SwitchPool;
+    handle_config() -> "config": response::Config;
+    handle_set_config(name: String, value: String) -> "setconfig": response::SetConfig;
+    handle_add_pool(parameter: AddPoolParameter) -> "addpool": response::AddPool;
+    handle_enable_pool(idx: i32) -> "enablepool": response::EnablePool;
+    handle_disable_pool(idx: i32) -> "disablepool": response::DisablePool;
+    handle_remove_pool(idx: i32) -> "removepool": response::RemovePool;
+    handle_pool_priority(order: Vec<usize>) -> "poolpriority": response::PoolPriority;
+    handle_failover_only(enabled: bool) -> "failover-only": response::FailoverOnly;
+    handle_zero(target: ZeroTarget, return_summary: bool) -> "zero": response::Zero;
+    handle_pause() -> "pause": response::Pause;
+    handle_resume() -> "resume": response::Resume;
+    handle_locate(setting: Option<LocateSetting>) -> "locate": response::Locate;
+    handle_notify() -> "notify": response::Notifies;
+    handle_stats(filter: Option<StatsFilter>, chips: bool) -> "stats": response::Stats;
+    handle_estats(filter: Option<StatsFilter>, chips: bool) -> "estats": response::Stats;
+    handle_coin() -> "coin": response::Coin;
+    handle_asc_count() -> "asccount": response::AscCount;
+    handle_asc(idx: Option<i32>) -> "asc": response::Ascs;
+    handle_asc_enable(idx: i32) -> "ascenable": response::AscEnable;
+    handle_asc_disable(idx: i32) -> "ascdisable": response::AscDisable;
+    handle_asc_set(parameter: AscSetParameter) -> "ascset": response::AscSet;
+    handle_tuner_status() -> "tunerstatus": response::TunerStatus;
+    handle_debug(flag: Option<DebugFlag>) -> "debug": response::Debug;
+);
+
+/// Serialized response with access to its sections. A response of a batched request consists
+/// of responses of the individual commands (see `command`).
+#[derive(Clone, Debug)]
+pub struct ParsedResponse {
+    value: json::Value,
+}
+
+impl ParsedResponse {
+    pub fn new(response: &ResponseType) -> Self {
+        Self {
+            value: json::to_value(response).expect("BUG: cannot serialize response"),
+        }
+    }
+
+    /// The whole serialized response
+    pub fn value(&self) -> &json::Value {
+        &self.value
+    }
+
+    /// Returns items of the section `name` (e.g. `POOLS`) or an empty list when it is missing
+    pub fn section(&self, name: &str) -> &[json::Value] {
+        self.value[name]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns the only item of the `STATUS` section
+    ///
+    /// # Panics
+    ///
+    /// The response has to have a status (i.e. it must not be a batched response).
+    pub fn status(&self) -> &json::Value {
+        match self.section("STATUS") {
+            [status] => status,
+            _ => panic!("BUG: response has no status: {}", self.value),
+        }
+    }
+
+    pub fn code(&self) -> u64 {
+        self.status()["Code"]
+            .as_u64()
+            .expect("BUG: invalid status code")
+    }
+
+    pub fn msg(&self) -> &str {
+        self.status()["Msg"]
+            .as_str()
+            .expect("BUG: invalid status message")
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.status()["STATUS"] == "E"
+    }
+
+    /// Returns response of the `command` from a batched response
+    ///
+    /// # Panics
+    ///
+    /// The batched response has to contain the `command`.
+    pub fn command(&self, command: &str) -> Self {
+        match self.value[command].as_array().map(Vec::as_slice) {
+            Some([value]) => Self {
+                value: value.clone(),
+            },
+            _ => panic!("BUG: response of '{}' is missing: {}", command, self.value),
+        }
+    }
+
+    /// Asserts that the status has the `code` and `msg`
+    pub fn assert_status(&self, code: u32, msg: &str) {
+        assert_eq!(
+            (self.code(), self.msg()),
+            (u64::from(code), msg),
+            "unexpected status of response {}",
+            self.value
+        );
+    }
+}
+
+/// Handles the `command` with optional `parameter` sent by a local client and parses the response
+pub async fn handle<T: When>(
+    receiver: &command::Receiver<T>,
+    command: &str,
+    parameter: Option<json::Value>,
+) -> ParsedResponse {
+    let mut request = json::json!({ "command": command });
+    if let Some(parameter) = parameter {
+        request["parameter"] = parameter;
+    }
+    let response = receiver
+        .handle(command::Request::new(request), &command::Context::local())
+        .await;
+    ParsedResponse::new(&response)
+}
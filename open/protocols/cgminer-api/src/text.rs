@@ -0,0 +1,189 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Plain-text (non-JSON) format of CGMiner API still used by legacy tools such as `miner.php`
+//!
+//! A request has the form `command|parameter` where the parameter is optional. A response
+//! consists of sections terminated by `|`. Each section is a comma separated list of `key=value`
+//! pairs with the same keys in the same order as in the JSON response. Sections of a body start
+//! with the body name (e.g. `SUMMARY,Elapsed=10,...|`) unless they start with an index of the
+//! item (e.g. `POOL=0,URL=...|`) exactly as in CGMiner. Keys and values are escaped by
+//! `support::escape_text`.
+
+use crate::command;
+use crate::ordered::{self, Value};
+use crate::support::{self, ResponseType, SingleResponse};
+
+use serde_json as json;
+
+/// Terminates sections of a response and separates the command from its parameter
+pub const SEPARATOR: char = '|';
+pub const FIELD_DELIMITER: char = ',';
+pub const VALUE_DELIMITER: char = '=';
+
+/// Parses plain-text request. Anything following a NUL byte or a new line is ignored.
+pub fn parse_request(data: &[u8]) -> command::Request {
+    let data = String::from_utf8_lossy(data);
+    let data = data
+        .split(&['\0', '\n'][..])
+        .next()
+        .unwrap_or_default()
+        .trim();
+
+    let mut args = data.splitn(2, SEPARATOR);
+    let mut value = json::json!({ "command": args.next().unwrap_or_default() });
+    // Empty parameter (e.g. `summary|`) is the same as missing one
+    if let Some(parameter) = args.next().filter(|parameter| !parameter.is_empty()) {
+        value["parameter"] = parameter.into();
+    }
+    command::Request::new(value)
+}
+
+fn write_value(value: &Value, text: &mut String) {
+    match value {
+        Value::Null => {}
+        Value::String(value) => support::escape_text(value, text),
+        // Nested structures have no plain-text representation so they are passed as JSON
+        Value::Array(_) | Value::Object(_) => support::escape_text(&value.to_string(), text),
+        value => text.push_str(&value.to_string()),
+    }
+}
+
+/// Sections starting with an index field (e.g. `POOL=0`) are not prefixed with the body name
+fn is_indexed(fields: &ordered::Map) -> bool {
+    match fields.iter().next() {
+        Some((key, value)) => value.is_number() && key.chars().all(|c| c.is_ascii_uppercase()),
+        None => false,
+    }
+}
+
+fn write_section(name: Option<&str>, section: &Value, text: &mut String) {
+    let fields = match section {
+        Value::Object(fields) => fields,
+        value => {
+            write_value(value, text);
+            text.push(SEPARATOR);
+            return;
+        }
+    };
+
+    let mut first = true;
+    if let Some(name) = name.filter(|_| !is_indexed(fields)) {
+        text.push_str(name);
+        first = false;
+    }
+    for (key, value) in fields.iter() {
+        if !first {
+            text.push(FIELD_DELIMITER);
+        }
+        first = false;
+        support::escape_text(key, text);
+        text.push(VALUE_DELIMITER);
+        write_value(value, text);
+    }
+    text.push(SEPARATOR);
+}
+
+fn write_single_response(response: &SingleResponse, text: &mut String) {
+    let status =
+        ordered::to_value(&response.status_info).expect("BUG: response serialization failed");
+    write_section(None, &status, text);
+
+    if let Some((name, body)) = &response.body {
+        body.to_value()
+            .iter()
+            .for_each(|section| write_section(Some(name), section, text));
+    }
+}
+
+/// Serializes `response` into the plain-text format. CGMiner supports batched commands only in
+/// JSON so their responses are simply concatenated in the order of the commands.
+pub fn encode_response(response: &ResponseType) -> String {
+    let mut text = String::new();
+    match response {
+        ResponseType::Single(response) => write_single_response(response, &mut text),
+        ResponseType::Multi(responses) => responses
+            .responses
+            .iter()
+            .flat_map(|(_, responses)| responses)
+            .for_each(|response| write_single_response(response, &mut text)),
+        ResponseType::Action(action) => text.push_str(action.status),
+    }
+    text
+}
+
+/// Section of a plain-text response parsed by `parse_response`
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Section {
+    /// Name of the body the section belongs to (e.g. `SUMMARY`). Sections of the status and of
+    /// indexed items have no name.
+    pub name: Option<String>,
+    /// Unescaped keys and values in the order of the response
+    pub fields: Vec<(String, String)>,
+}
+
+impl Section {
+    /// Returns the value of the first field named `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parses plain-text response as a client does. The terminating NUL byte is optional. Field
+/// without a value delimiter is taken as the name of the section when it is the first one and
+/// as a key with an empty value otherwise.
+pub fn parse_response(text: &str) -> Vec<Section> {
+    let text = text.trim_end_matches('\0');
+    let mut sections = support::split_text(text, SEPARATOR);
+    // Every section is terminated by the separator so the last part is empty
+    if sections.last() == Some(&"") {
+        sections.pop();
+    }
+    sections
+        .into_iter()
+        .map(|section| {
+            let mut name = None;
+            let mut fields = Vec::new();
+            for (i, field) in support::split_text(section, FIELD_DELIMITER)
+                .into_iter()
+                .enumerate()
+            {
+                let mut pair = support::split_text(field, VALUE_DELIMITER).into_iter();
+                let key = support::unescape_text(pair.next().unwrap_or_default());
+                // Only the first value delimiter separates the key so the rest is kept as it is
+                let value: Vec<_> = pair.collect();
+                match (i, value.is_empty()) {
+                    (0, true) => name = Some(key),
+                    (_, true) => fields.push((key, String::new())),
+                    (_, false) => fields.push((
+                        key,
+                        support::unescape_text(&value.join(&VALUE_DELIMITER.to_string())),
+                    )),
+                }
+            }
+            Section { name, fields }
+        })
+        .collect()
+}
@@ -0,0 +1,331 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! WebSocket transport of CGMiner API for web clients
+//!
+//! Unlike the TCP transport the connection is kept open for multiple requests. Each text frame
+//! carries a single request and the response is sent back as a text frame without the NUL
+//! terminator.
+//!
+//! Requests are handled the same way as by the TCP transport: connections and requests are
+//! counted in `server::Statistics`, the rate limiter applies to every request and the server can
+//! be grouped with other transports in `server::ServerGroup`. The `events` command is not
+//! supported.
+//!
+//! Each connection has its own `access::Session` so that a client can send its token just once
+//! with the `auth` command.
+//!
+//! Browsers let any web page open WebSocket connections to any server so the handshake of
+//! a browser is rejected unless its `Origin` is explicitly allowed by `Server::allow_origin`.
+//! Clients which do not send the `Origin` header (i.e. anything but browsers) are not affected.
+
+use ii_logging::macros::*;
+
+use ii_async_compat::{futures, tokio};
+
+use futures::future::{self, Future};
+use futures::{SinkExt as _, StreamExt as _};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::handshake::server::{
+    Callback, ErrorResponse, Request, Response,
+};
+use tokio_tungstenite::tungstenite::http::{header, StatusCode};
+use tokio_tungstenite::tungstenite::protocol::{Message, WebSocketConfig};
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::access::Session;
+use crate::command;
+use crate::server::DEFAULT_MAX_REQUEST_SIZE;
+use crate::server::{self, ConnectionGuard, Handle, RateLimiter, SharedState, Statistics};
+use crate::support::{UnixTime, When};
+
+/// Origins of web pages allowed to connect to the server
+#[derive(Clone, Debug)]
+enum Origins {
+    Any,
+    /// Allowed origins in lowercase (an empty list allows only clients without origin)
+    Listed(Arc<Vec<String>>),
+}
+
+/// Checks `Origin` header of the handshake request
+impl Callback for Origins {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        let allowed = match (&self, request.headers().get(header::ORIGIN)) {
+            (Origins::Any, _) | (_, None) => true,
+            (Origins::Listed(origins), Some(origin)) => origin
+                .to_str()
+                .map(|origin| {
+                    origins
+                        .iter()
+                        .any(|allowed| origin.eq_ignore_ascii_case(allowed))
+                })
+                .unwrap_or(false),
+        };
+        if allowed {
+            return Ok(response);
+        }
+        info!(
+            "CGMiner API: WebSocket connection from origin {:?} refused",
+            request.headers().get(header::ORIGIN)
+        );
+        let mut error = ErrorResponse::new(Some("Origin not allowed".to_string()));
+        *error.status_mut() = StatusCode::FORBIDDEN;
+        Err(error)
+    }
+}
+
+/// Settings shared by all connections of a server
+#[derive(Clone)]
+struct Settings {
+    max_request_size: usize,
+    state: SharedState,
+    origins: Origins,
+}
+
+/// Handles all requests received over the WebSocket `stream` until the client closes it
+async fn handle_connection<T>(
+    stream: TcpStream,
+    receiver: Arc<command::Receiver<T>>,
+    settings: Settings,
+    context: command::Context,
+    _guard: ConnectionGuard,
+) where
+    T: When,
+{
+    let context = context.with_session(Arc::new(Session::new()));
+    let max_request_size = settings.max_request_size;
+    let config = WebSocketConfig {
+        max_message_size: Some(max_request_size),
+        max_frame_size: Some(max_request_size),
+        ..Default::default()
+    };
+    let mut stream = match tokio_tungstenite::accept_hdr_async_with_config(
+        stream,
+        settings.origins.clone(),
+        Some(config),
+    )
+    .await
+    {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!("CGMiner API: WebSocket handshake failed ({})", e);
+            return;
+        }
+    };
+
+    while let Some(message) = stream.next().await {
+        let request = match message {
+            Ok(Message::Text(request)) => request.into_bytes(),
+            Ok(Message::Binary(request)) => request,
+            // Pings are answered and closing handshake is completed by the stream itself
+            Ok(Message::Ping(_)) | Ok(Message::Pong(_)) => continue,
+            Ok(Message::Close(_)) => break,
+            Err(e) => {
+                warn!("CGMiner API: WebSocket connection failed ({})", e);
+                break;
+            }
+        };
+
+        let (response, action) =
+            match command::Request::from_bytes_limited(&request, max_request_size) {
+                // The response is always sent as JSON text message regardless of request format.
+                // The client cannot hang up while its request is handled because the stream is
+                // not read in the meantime.
+                Ok((request, _)) => settings
+                    .state
+                    .handle_request(&receiver, request, &context, future::pending(), false)
+                    .await
+                    .expect("BUG: request cancelled without hang-up"),
+                // Malformed request does not close the connection
+                Err(e) => {
+                    settings.state.record_request::<T>(&context, None, true);
+                    (receiver.error_response(e.into()), None)
+                }
+            };
+        let mut encoded = Vec::new();
+        response
+            .encode_json(&mut encoded)
+            .expect("BUG: cannot serialize response");
+        let response = String::from_utf8(encoded).expect("BUG: response is not valid UTF-8");
+        if let Err(e) = stream.send(Message::Text(response)).await {
+            warn!("CGMiner API: cannot send response ({})", e);
+            break;
+        }
+        if let Some(command::Continuation::Action(action)) = action {
+            action.await;
+        }
+    }
+}
+
+/// WebSocket server of CGMiner API
+pub struct Server<T = UnixTime> {
+    listener: TcpListener,
+    receiver: Arc<command::Receiver<T>>,
+    settings: Settings,
+    max_connections: Option<usize>,
+}
+
+impl<T> Server<T>
+where
+    T: When + 'static,
+{
+    /// Binds a listening socket to `addr`. The `receiver` can be shared with other servers.
+    pub async fn bind<R>(addr: SocketAddr, receiver: R) -> io::Result<Self>
+    where
+        R: Into<Arc<command::Receiver<T>>>,
+    {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            receiver: receiver.into(),
+            settings: Settings {
+                max_request_size: DEFAULT_MAX_REQUEST_SIZE,
+                state: Default::default(),
+                origins: Origins::Listed(Default::default()),
+            },
+            max_connections: None,
+        })
+    }
+
+    /// Limits the size of a request. The connection is closed when a larger message is received.
+    pub fn max_request_size(mut self, max_request_size: usize) -> Self {
+        self.settings.max_request_size = max_request_size;
+        self
+    }
+
+    /// Limits the number of connections served at once (see `server::Server::max_connections`)
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Allows web pages from `origin` (e.g. `http://192.168.1.10`) to connect to the server.
+    /// The origin is compared case-insensitively with the `Origin` header sent by the browser.
+    pub fn allow_origin<O>(mut self, origin: O) -> Self
+    where
+        O: Into<String>,
+    {
+        let origin = origin.into().to_ascii_lowercase();
+        match &mut self.settings.origins {
+            Origins::Listed(origins) => Arc::make_mut(origins).push(origin),
+            // Any origin is allowed already
+            Origins::Any => {}
+        }
+        self
+    }
+
+    /// Disables the check of the `Origin` header so that web pages from anywhere can connect to
+    /// the server. It is only safe when the API is protected otherwise (e.g. by access tokens).
+    pub fn allow_any_origin(mut self) -> Self {
+        self.settings.origins = Origins::Any;
+        self
+    }
+
+    /// Returns counters of the server (see `server::Server::statistics`)
+    pub fn statistics(&self) -> Arc<Statistics> {
+        self.settings.state.statistics()
+    }
+
+    /// Updates the `statistics` besides the server's own ones (see
+    /// `server::Server::share_statistics`)
+    pub fn share_statistics(mut self, statistics: Arc<Statistics>) -> Self {
+        self.settings.state.share_statistics(statistics);
+        self
+    }
+
+    /// Limits the rate of requests. Unlike the TCP transport every request of the open
+    /// connection takes a token.
+    pub fn rate_limiter<L>(mut self, rate_limiter: L) -> Self
+    where
+        L: Into<Arc<RateLimiter>>,
+    {
+        self.settings.state.set_rate_limiter(rate_limiter.into());
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves all incoming connections forever
+    pub async fn run(self) {
+        self.run_until(future::pending()).await
+    }
+
+    /// Spawns a task serving all incoming connections until the returned handle is used for
+    /// shutting the server down. Connections which are already open are kept.
+    pub fn start(self) -> Handle {
+        Handle::spawn(|shutdown_receiver| {
+            self.run_until(async {
+                let _ = shutdown_receiver.await;
+            })
+        })
+    }
+
+    async fn run_until<F>(mut self, shutdown: F)
+    where
+        F: Future<Output = ()>,
+    {
+        futures::pin_mut!(shutdown);
+        // Refused client is disconnected even before the WebSocket handshake
+        while let Some((stream, context)) =
+            server::next_connection(&mut self.listener, &self.receiver, shutdown.as_mut()).await
+        {
+            let guard = match self.settings.state.acquire_connection(self.max_connections) {
+                Some(guard) => guard,
+                None => {
+                    info!(
+                        "CGMiner API: WebSocket connection from {:?} refused, connection limit reached",
+                        context.peer_addr()
+                    );
+                    continue;
+                }
+            };
+            tokio::spawn(handle_connection(
+                stream,
+                self.receiver.clone(),
+                self.settings.clone(),
+                context,
+                guard,
+            ));
+        }
+    }
+}
+
+impl<T> server::Transport<T> for Server<T>
+where
+    T: When + 'static,
+{
+    fn receiver(&self) -> &Arc<command::Receiver<T>> {
+        &self.receiver
+    }
+
+    fn share_state(&mut self, statistics: Arc<Statistics>, rate_limiter: Option<Arc<RateLimiter>>) {
+        self.settings.state.share(statistics, rate_limiter);
+    }
+
+    fn start(self: Box<Self>) -> Handle {
+        Server::start(*self)
+    }
+}
@@ -88,6 +88,22 @@ impl Handler for TestIdentityHandler {
         self.visit_and_check(header, payload, build_open_channel_success);
     }
 
+    async fn visit_open_extended_mining_channel(
+        &mut self,
+        header: &framing::Header,
+        payload: &OpenExtendedMiningChannel,
+    ) {
+        self.visit_and_check(header, payload, build_open_extended_channel);
+    }
+
+    async fn visit_open_extended_mining_channel_success(
+        &mut self,
+        header: &framing::Header,
+        payload: &OpenExtendedMiningChannelSuccess,
+    ) {
+        self.visit_and_check(header, payload, build_open_extended_channel_success);
+    }
+
     async fn visit_new_mining_job(&mut self, header: &framing::Header, payload: &NewMiningJob) {
         self.visit_and_check(header, payload, build_new_mining_job);
     }
@@ -171,6 +187,26 @@ pub fn build_open_channel_success() -> OpenStandardMiningChannelSuccess {
     }
 }
 
+pub fn build_open_extended_channel() -> OpenExtendedMiningChannel {
+    OpenExtendedMiningChannel {
+        req_id: 11,
+        user: USER_CREDENTIALS.try_into().unwrap(),
+        nominal_hashrate: 1e12,
+        max_target: ii_bitcoin::Target::default().into(),
+        min_extranonce_size: 4,
+    }
+}
+
+pub fn build_open_extended_channel_success() -> OpenExtendedMiningChannelSuccess {
+    OpenExtendedMiningChannelSuccess {
+        req_id: 11,
+        channel_id: 1,
+        target: build_open_channel_success().target,
+        extranonce_size: 8,
+        extranonce_prefix: Bytes0_32::from_slice(&[0x00, 0x00, 0x00, 0x01]),
+    }
+}
+
 /// TODO: see test_utils::v1::MINING_NOTIFY_JSON that defines a stratum v1 job.
 /// The merkle root below has been calculated by the integration test and cannot be trusted...
 /// We need a V1 mining job with verified merkle root that is to be copied
@@ -213,3 +249,70 @@ pub fn build_submit_shares() -> SubmitSharesStandard {
         version: MINING_WORK_VERSION,
     }
 }
+
+pub fn build_submit_shares_extended() -> SubmitSharesExtended {
+    SubmitSharesExtended {
+        share: build_submit_shares(),
+        extranonce: Bytes0_32::from_slice(&[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]),
+    }
+}
+
+/// Coinbase transaction of block 170 (the first block with a transaction other than the coinbase)
+/// split around the part of the coinbase script that is used as the extranonce
+pub const BLOCK_170_COINBASE_TX_PREFIX: &str =
+    "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0704";
+pub const BLOCK_170_EXTRANONCE: &str = "ffff001d";
+pub const BLOCK_170_COINBASE_TX_SUFFIX: &str = concat!(
+    "0102ffffffff0100f2052a01000000434104d46c4968bde02899d2aa0963367c7a6ce34eec332b32e42e5f3407e0",
+    "52d64ac625da6f0718e7b302140434bd725706957c092db53805b821a85b23a7ac61725bac00000000",
+);
+/// The only other transaction of block 170
+pub const BLOCK_170_TX_HASH: &str =
+    "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+pub const BLOCK_170_MERKLE_ROOT: &str =
+    "7dac2c5666815c17a3b36427de37bb9d2e2c5ccec3f8633eb91a4205cb4c10ff";
+
+/// Extended job of block 170 (see `BLOCK_170_EXTRANONCE`)
+pub fn build_new_extended_mining_job() -> NewExtendedMiningJob {
+    let tx_hash = sha256d::Hash::from_hex(BLOCK_170_TX_HASH).expect("from_hex");
+
+    NewExtendedMiningJob {
+        channel_id: 0,
+        job_id: 170,
+        future_job: false,
+        version: 1,
+        merkle_path: Seq0_255::from_vec(vec![Uint256Bytes(tx_hash.into_inner())]),
+        coinbase_tx_prefix: Bytes0_64k::from_vec(
+            hex::decode(BLOCK_170_COINBASE_TX_PREFIX).expect("hex"),
+        ),
+        coinbase_tx_suffix: Bytes0_64k::from_vec(
+            hex::decode(BLOCK_170_COINBASE_TX_SUFFIX).expect("hex"),
+        ),
+    }
+}
+
+/// Custom job built from the extended job of block 170
+pub fn build_set_custom_mining_job() -> SetCustomMiningJob {
+    let job = build_new_extended_mining_job();
+
+    SetCustomMiningJob {
+        channel_id: 1,
+        req_id: 2,
+        mining_job_token: Bytes0_255::from_slice(&[0xca, 0xfe]),
+        version: job.version,
+        prev_hash: Uint256Bytes([0x11; 32]),
+        min_ntime: MINING_WORK_NTIME,
+        nbits: 0x1d00ffff,
+        merkle_path: job.merkle_path,
+        coinbase_tx_prefix: job.coinbase_tx_prefix,
+        coinbase_tx_suffix: job.coinbase_tx_suffix,
+    }
+}
+
+pub fn build_reconnect() -> Reconnect {
+    Reconnect {
+        new_host: Str0_255::from_str("backup.pool.example.com"),
+        new_port: 3336,
+        keep_connection: true,
+    }
+}
@@ -25,6 +25,7 @@ pub mod error;
 pub mod framing;
 #[macro_use]
 pub mod macros;
+pub mod channel;
 pub mod extensions;
 pub mod messages;
 pub mod noise;
@@ -121,6 +122,13 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_channel_endpoint_changed(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::ChannelEndpointChanged,
+    ) {
+    }
+
     async fn visit_open_standard_mining_channel(
         &mut self,
         _header: &framing::Header,
@@ -143,6 +151,27 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_open_extended_mining_channel(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::OpenExtendedMiningChannel,
+    ) {
+    }
+
+    async fn visit_open_extended_mining_channel_success(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::OpenExtendedMiningChannelSuccess,
+    ) {
+    }
+
+    async fn visit_open_extended_mining_channel_error(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::OpenExtendedMiningChannelError,
+    ) {
+    }
+
     async fn visit_update_channel(
         &mut self,
         _header: &framing::Header,
@@ -157,6 +186,20 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_close_channel(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::CloseChannel,
+    ) {
+    }
+
+    async fn visit_set_extranonce_prefix(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SetExtranoncePrefix,
+    ) {
+    }
+
     async fn visit_submit_shares_standard(
         &mut self,
         _header: &framing::Header,
@@ -164,6 +207,13 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_submit_shares_extended(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SubmitSharesExtended,
+    ) {
+    }
+
     async fn visit_submit_shares_success(
         &mut self,
         _header: &framing::Header,
@@ -185,6 +235,13 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_new_extended_mining_job(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::NewExtendedMiningJob,
+    ) {
+    }
+
     async fn visit_set_new_prev_hash(
         &mut self,
         _header: &framing::Header,
@@ -192,6 +249,27 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_set_custom_mining_job(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SetCustomMiningJob,
+    ) {
+    }
+
+    async fn visit_set_custom_mining_job_success(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SetCustomMiningJobSuccess,
+    ) {
+    }
+
+    async fn visit_set_custom_mining_job_error(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SetCustomMiningJobError,
+    ) {
+    }
+
     async fn visit_set_target(
         &mut self,
         _header: &framing::Header,
@@ -199,6 +277,16 @@ pub trait Handler: 'static + Send {
     ) {
     }
 
+    async fn visit_reconnect(&mut self, _header: &framing::Header, _payload: &messages::Reconnect) {
+    }
+
+    async fn visit_set_group_channel(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &messages::SetGroupChannel,
+    ) {
+    }
+
     // TODO the methods below will be removed once we will split off a separate handler
     //  type for the telemetry extension and refactor message handling completely
     async fn visit_open_telemetry_channel(
@@ -242,6 +330,14 @@ pub trait Handler: 'static + Send {
         _payload: &telemetry::messages::SubmitTelemetryDataError,
     ) {
     }
+
+    /// Frames of unknown extensions are skipped unless the handler relays them
+    async fn visit_unknown_extension(
+        &mut self,
+        _header: &framing::Header,
+        _payload: &extensions::UnknownExtension,
+    ) {
+    }
 }
 
 /// Consumes `frame` and produces a Message object based on the payload type
@@ -260,6 +356,14 @@ pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protoco
             payload: serializable_payload,
         });
     }
+    // Message types of other extensions have different meaning
+    if frame.header.extension_type != extensions::BASE {
+        let (header, payload) = extensions::UnknownExtension::from_frame(frame)?;
+        return Ok(Message {
+            header,
+            payload: Box::new(payload),
+        });
+    }
     // Header will be consumed by the subsequent transformation of the frame into the actual
     // payload for further handling. Therefore we create a copy for constructing a
     // Message<Protocol >
@@ -278,6 +382,9 @@ pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protoco
         MessageType::SetupConnectionError => {
             Box::new(messages::SetupConnectionError::try_from(frame)?)
         }
+        MessageType::ChannelEndpointChanged => {
+            Box::new(messages::ChannelEndpointChanged::try_from(frame)?)
+        }
         MessageType::OpenStandardMiningChannel => {
             Box::new(messages::OpenStandardMiningChannel::try_from(frame)?)
         }
@@ -287,12 +394,40 @@ pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protoco
         MessageType::OpenStandardMiningChannelError => {
             Box::new(messages::OpenStandardMiningChannelError::try_from(frame)?)
         }
+        MessageType::CloseChannel => Box::new(messages::CloseChannel::try_from(frame)?),
+        MessageType::SetExtranoncePrefix => {
+            Box::new(messages::SetExtranoncePrefix::try_from(frame)?)
+        }
+        MessageType::OpenExtendedMiningChannel => {
+            Box::new(messages::OpenExtendedMiningChannel::try_from(frame)?)
+        }
+        MessageType::OpenExtendedMiningChannelSuccess => {
+            Box::new(messages::OpenExtendedMiningChannelSuccess::try_from(frame)?)
+        }
+        MessageType::OpenExtendedMiningChannelError => {
+            Box::new(messages::OpenExtendedMiningChannelError::try_from(frame)?)
+        }
         MessageType::NewMiningJob => Box::new(messages::NewMiningJob::try_from(frame)?),
+        MessageType::NewExtendedMiningJob => {
+            Box::new(messages::NewExtendedMiningJob::try_from(frame)?)
+        }
         MessageType::SetNewPrevHash => Box::new(messages::SetNewPrevHash::try_from(frame)?),
+        MessageType::SetCustomMiningJob => Box::new(messages::SetCustomMiningJob::try_from(frame)?),
+        MessageType::SetCustomMiningJobSuccess => {
+            Box::new(messages::SetCustomMiningJobSuccess::try_from(frame)?)
+        }
+        MessageType::SetCustomMiningJobError => {
+            Box::new(messages::SetCustomMiningJobError::try_from(frame)?)
+        }
         MessageType::SetTarget => Box::new(messages::SetTarget::try_from(frame)?),
+        MessageType::Reconnect => Box::new(messages::Reconnect::try_from(frame)?),
+        MessageType::SetGroupChannel => Box::new(messages::SetGroupChannel::try_from(frame)?),
         MessageType::SubmitSharesStandard => {
             Box::new(messages::SubmitSharesStandard::try_from(frame)?)
         }
+        MessageType::SubmitSharesExtended => {
+            Box::new(messages::SubmitSharesExtended::try_from(frame)?)
+        }
         MessageType::SubmitSharesSuccess => {
             Box::new(messages::SubmitSharesSuccess::try_from(frame)?)
         }
@@ -330,4 +465,62 @@ pub mod test {
             build_message_from_frame(frame).expect("Message payload deserialization failed");
         message.accept(&mut TestIdentityHandler).await;
     }
+
+    /// Records frames of unknown extensions
+    struct UnknownExtensionHandler(Vec<(framing::Header, extensions::UnknownExtension)>);
+
+    #[async_trait]
+    impl Handler for UnknownExtensionHandler {
+        async fn visit_setup_connection(
+            &mut self,
+            _header: &framing::Header,
+            _payload: &messages::SetupConnection,
+        ) {
+            panic!("BUG: frame of unknown extension handled as a base protocol message");
+        }
+
+        async fn visit_unknown_extension(
+            &mut self,
+            header: &framing::Header,
+            payload: &extensions::UnknownExtension,
+        ) {
+            let payload = extensions::UnknownExtension {
+                payload: payload.payload.clone(),
+            };
+            self.0.push((header.clone(), payload));
+        }
+    }
+
+    /// Frame of an unknown extension is not an error and its payload is kept intact even though
+    /// its message type matches a base protocol message
+    #[tokio::test]
+    async fn test_build_message_from_unknown_extension_frame() {
+        let payload = bytes::BytesMut::from(&[0xde, 0xad, 0xbe, 0xef][..]);
+        let frame = Frame::from_serialized_payload(
+            true,
+            0x7123,
+            MessageType::SetupConnection as framing::MsgType,
+            payload.clone(),
+        );
+        let expected_header = frame.header.clone();
+
+        let message = build_message_from_frame(frame).expect("BUG: unknown extension refused");
+        let mut handler = UnknownExtensionHandler(Vec::new());
+        message.accept(&mut handler).await;
+        assert_eq!(
+            handler.0,
+            vec![(expected_header, extensions::UnknownExtension { payload })]
+        );
+
+        // Telemetry messages are unknown to the base protocol as well
+        let frame = Frame::from_serialized_payload(
+            false,
+            extensions::TELEMETRY,
+            MessageType::SetupConnection as framing::MsgType,
+            bytes::BytesMut::new(),
+        );
+        let message = build_message_from_frame(frame).expect("BUG: telemetry frame refused");
+        message.accept(&mut handler).await;
+        assert_eq!(handler.0.len(), 2);
+    }
 }
@@ -0,0 +1,214 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Bookkeeping of jobs of channels open on a single connection
+
+use std::collections::HashMap;
+
+use super::messages::{ChannelEndpointChanged, ChannelMessage, SubmitSharesStandard};
+use super::types::ChannelId;
+
+/// State of a job referenced by a share
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobStatus {
+    Active,
+    /// The job has been issued before its channel changed the endpoint, shares of such job are
+    /// known to be stale and shouldn't be submitted
+    Stale,
+    Unknown,
+}
+
+/// Registry of jobs issued on each channel of a connection. Once `ChannelEndpointChanged` arrives
+/// for a channel (`handle_channel_endpoint_changed`), all its jobs known so far become stale. The
+/// new endpoint may issue the same job ids again and such jobs are active again.
+#[derive(Debug, Default)]
+pub struct JobRegistry {
+    /// Jobs of each channel with a flag whether the job is stale
+    jobs: HashMap<ChannelId, HashMap<u32, bool>>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a new active job of the channel
+    pub fn insert(&mut self, channel_id: ChannelId, job_id: u32) {
+        self.jobs
+            .entry(channel_id)
+            .or_default()
+            .insert(job_id, false);
+    }
+
+    /// Marks all jobs of the channel stale and returns their number
+    pub fn mark_stale(&mut self, channel_id: ChannelId) -> usize {
+        self.jobs.get_mut(&channel_id).map_or(0, |jobs| {
+            jobs.values_mut().for_each(|stale| *stale = true);
+            jobs.len()
+        })
+    }
+
+    /// Marks all jobs of the channel specified by `ChannelEndpointChanged` stale (see
+    /// `mark_stale`)
+    pub fn handle_channel_endpoint_changed(&mut self, msg: &ChannelEndpointChanged) -> usize {
+        self.mark_stale(msg.channel_id())
+    }
+
+    /// Forgets all jobs of the channel except `job_id` (e.g. when `SetNewPrevHash` switches the
+    /// channel to the job and no other job can be mined any more)
+    pub fn prune(&mut self, channel_id: ChannelId, job_id: u32) {
+        if let Some(jobs) = self.jobs.get_mut(&channel_id) {
+            jobs.retain(|id, _| *id == job_id);
+        }
+    }
+
+    /// Forgets all jobs of the channel (e.g. when it is closed)
+    pub fn remove_channel(&mut self, channel_id: ChannelId) {
+        self.jobs.remove(&channel_id);
+    }
+
+    pub fn status(&self, channel_id: ChannelId, job_id: u32) -> JobStatus {
+        match self
+            .jobs
+            .get(&channel_id)
+            .and_then(|jobs| jobs.get(&job_id))
+        {
+            Some(false) => JobStatus::Active,
+            Some(true) => JobStatus::Stale,
+            None => JobStatus::Unknown,
+        }
+    }
+
+    /// Checks the job referenced by the share before it is submitted
+    pub fn check_share(&self, share: &SubmitSharesStandard) -> JobStatus {
+        self.status(share.channel_id(), share.job_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::v2::{build_message_from_frame, framing, Handler};
+
+    use async_trait::async_trait;
+    use ii_async_compat::tokio;
+    use std::convert::TryInto;
+
+    #[test]
+    fn test_job_registry_channel_endpoint_changed() {
+        let mut registry = JobRegistry::new();
+        let (channel, other_channel) = (ChannelId(1), ChannelId(2));
+        registry.insert(channel, 10);
+        registry.insert(channel, 11);
+        registry.insert(other_channel, 10);
+
+        let share = |channel_id: ChannelId, job_id| SubmitSharesStandard {
+            channel_id: channel_id.0,
+            seq_num: 0,
+            job_id,
+            nonce: 0,
+            ntime: 0,
+            version: 0,
+        };
+        assert_eq!(registry.check_share(&share(channel, 10)), JobStatus::Active);
+
+        let msg = ChannelEndpointChanged { channel_id: 1 };
+        assert_eq!(registry.handle_channel_endpoint_changed(&msg), 2);
+        assert_eq!(registry.check_share(&share(channel, 10)), JobStatus::Stale);
+        assert_eq!(registry.check_share(&share(channel, 11)), JobStatus::Stale);
+        // Other channels are not affected
+        assert_eq!(
+            registry.check_share(&share(other_channel, 10)),
+            JobStatus::Active
+        );
+
+        // Jobs of the new endpoint are active even when their ids repeat
+        registry.insert(channel, 11);
+        registry.insert(channel, 12);
+        assert_eq!(registry.check_share(&share(channel, 10)), JobStatus::Stale);
+        assert_eq!(registry.check_share(&share(channel, 11)), JobStatus::Active);
+        assert_eq!(registry.check_share(&share(channel, 12)), JobStatus::Active);
+        assert_eq!(
+            registry.check_share(&share(channel, 13)),
+            JobStatus::Unknown
+        );
+
+        // Unknown channel is tolerated
+        assert_eq!(
+            registry.handle_channel_endpoint_changed(&ChannelEndpointChanged { channel_id: 7 }),
+            0
+        );
+        registry.remove_channel(channel);
+        assert_eq!(
+            registry.check_share(&share(channel, 12)),
+            JobStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn test_job_registry_prune() {
+        let mut registry = JobRegistry::new();
+        let (channel, other_channel) = (ChannelId(1), ChannelId(2));
+        registry.insert(channel, 10);
+        registry.insert(channel, 11);
+        registry.insert(other_channel, 10);
+
+        registry.prune(channel, 11);
+        assert_eq!(registry.status(channel, 10), JobStatus::Unknown);
+        assert_eq!(registry.status(channel, 11), JobStatus::Active);
+        assert_eq!(registry.status(other_channel, 10), JobStatus::Active);
+
+        // The kept job stays stale and unknown channel is tolerated
+        registry.mark_stale(channel);
+        registry.prune(channel, 11);
+        assert_eq!(registry.status(channel, 11), JobStatus::Stale);
+        registry.prune(ChannelId(7), 10);
+    }
+
+    struct JobRegistryHandler(JobRegistry);
+
+    #[async_trait]
+    impl Handler for JobRegistryHandler {
+        async fn visit_channel_endpoint_changed(
+            &mut self,
+            _header: &framing::Header,
+            payload: &ChannelEndpointChanged,
+        ) {
+            self.0.handle_channel_endpoint_changed(payload);
+        }
+    }
+
+    /// Message received from the network marks the jobs stale
+    #[tokio::test]
+    async fn test_job_registry_handle_message() {
+        let mut handler = JobRegistryHandler(JobRegistry::new());
+        handler.0.insert(ChannelId(3), 1);
+
+        let frame: framing::Frame = ChannelEndpointChanged { channel_id: 3 }
+            .try_into()
+            .expect("BUG: cannot build frame");
+        let message = build_message_from_frame(frame).expect("BUG: cannot build message");
+        message.accept(&mut handler).await;
+        assert_eq!(handler.0.status(ChannelId(3), 1), JobStatus::Stale);
+    }
+}
@@ -31,4 +31,7 @@ pub enum ErrorKind {
 
     #[fail(display = "Channel not operational: {}", _0)]
     ChannelNotOperational(String),
+
+    #[fail(display = "Unknown error code: {}", _0)]
+    UnknownErrorCode(String),
 }
@@ -22,7 +22,49 @@
 
 //! This module lists all official extensions
 
+use async_trait::async_trait;
+use bytes::BytesMut;
+
+use ii_async_compat::bytes;
+
+use super::{framing, Protocol};
+use crate::error::Result;
+use crate::AnyPayload;
+
 /// Base protocol covered in the main specification
 pub const BASE: u16 = 0x0000;
 /// Telemetry extension
 pub const TELEMETRY: u16 = 0x0001;
+
+/// Payload of a frame that belongs to an extension the message builder doesn't know. Such frame
+/// is not an error, it is passed to `Handler::visit_unknown_extension` that skips it by default.
+/// The payload is kept as received so that the frame can be relayed.
+#[derive(Debug, PartialEq)]
+pub struct UnknownExtension {
+    pub payload: BytesMut,
+}
+
+impl UnknownExtension {
+    /// Takes the payload of the `frame` without deserializing it
+    pub fn from_frame(frame: framing::Frame) -> Result<(framing::Header, Self)> {
+        let (header, payload) = frame.split();
+        let payload = payload.into_bytes_mut()?;
+        Ok((header, Self { payload }))
+    }
+}
+
+#[async_trait]
+impl AnyPayload<Protocol> for UnknownExtension {
+    async fn accept(
+        &self,
+        header: &<Protocol as crate::Protocol>::Header,
+        handler: &mut <Protocol as crate::Protocol>::Handler,
+    ) {
+        handler.visit_unknown_extension(header, self).await;
+    }
+
+    fn serialize_to_writer(&self, writer: &mut dyn std::io::Write) -> Result<()> {
+        writer.write_all(&self.payload)?;
+        Ok(())
+    }
+}
@@ -30,11 +30,15 @@ use bytes::{
 use ii_async_compat::bytes;
 use ii_logging::macros::*;
 
+use super::types::ChannelId;
 use super::Protocol;
 use crate::error::{Error, Result};
 use crate::payload::{Payload, SerializablePayload};
 
+use std::convert::TryInto;
+
 pub mod codec;
+pub mod dialect;
 
 /// Message type field in the frame header
 pub type MsgType = u8;
@@ -76,6 +80,12 @@ impl Header {
             msg_length.unwrap(),
             Self::MAX_LEN
         );
+        assert_eq!(
+            extension_type & Self::CHANNEL_MSG_MASK,
+            0,
+            "BUG: extension type {:#06x} overlaps the channel message flag",
+            extension_type
+        );
         Self {
             is_channel_message,
             extension_type: extension_type.into(),
@@ -213,6 +223,19 @@ impl Frame {
     pub fn split(self) -> (Header, Payload<Protocol>) {
         (self.header, self.payload)
     }
+
+    /// Provides the channel id of a received channel message without deserializing its payload
+    /// (see `channel_id`). There is none when the frame is not a channel message or when the
+    /// frame has been built from a message that hasn't been serialized yet.
+    #[cfg(not(feature = "v2json"))]
+    pub fn channel_id(&self) -> Option<ChannelId> {
+        match &self.payload {
+            Payload::SerializedBytes(payload) if self.header.is_channel_message => {
+                channel_id(payload)
+            }
+            _ => None,
+        }
+    }
 }
 
 /// Helper struct that groups all framing related associated types (Frame + Error +
@@ -227,12 +250,25 @@ impl ii_wire::Framing for Framing {
     type Codec = codec::Codec;
 }
 
-pub const PAYLOAD_CHANNEL_OFFSET: usize = 4;
+/// Offset of the channel id in the serialized payload of a channel message
+pub const PAYLOAD_CHANNEL_OFFSET: usize = 0;
+const CHANNEL_ID_SIZE: usize = std::mem::size_of::<u32>();
+
+/// Reads the channel id from the binary serialized `payload` of a channel message (see
+/// `messages::ChannelMessage`) so that frames can be dispatched to channels cheaply. Returns
+/// `None` when the payload is too short to contain the channel id.
+#[cfg(not(feature = "v2json"))]
+pub fn channel_id(payload: &[u8]) -> Option<ChannelId> {
+    payload
+        .get(PAYLOAD_CHANNEL_OFFSET..PAYLOAD_CHANNEL_OFFSET + CHANNEL_ID_SIZE)
+        .map(|bytes| ChannelId(u32::from_le_bytes(bytes.try_into().expect("BUG: size"))))
+}
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::error::ResultExt;
+    use crate::v2::extensions;
     use async_trait::async_trait;
 
     #[test]
@@ -253,6 +289,47 @@ mod test {
         );
     }
 
+    /// The channel message flag is the most significant bit of the extension type field and it
+    /// is independent of the extension type
+    #[test]
+    fn test_header_channel_message_bit() {
+        let cases: &[(bool, ExtType, [u8; Header::SIZE])] = &[
+            (
+                false,
+                extensions::BASE,
+                [0x00, 0x00, 0x16, 0x04, 0x00, 0x00],
+            ),
+            (true, extensions::BASE, [0x00, 0x80, 0x16, 0x04, 0x00, 0x00]),
+            (
+                false,
+                extensions::TELEMETRY,
+                [0x01, 0x00, 0x16, 0x04, 0x00, 0x00],
+            ),
+            (
+                true,
+                extensions::TELEMETRY,
+                [0x01, 0x80, 0x16, 0x04, 0x00, 0x00],
+            ),
+            (true, 0x7fff, [0xff, 0xff, 0x16, 0x04, 0x00, 0x00]),
+        ];
+        for (is_channel_message, extension_type, expected_bytes) in cases {
+            let header = Header::new(*is_channel_message, *extension_type, 0x16, Some(4));
+            let mut header_bytes = BytesMut::new();
+            header.serialize(&mut header_bytes, None);
+            assert_eq!(&header_bytes[..], &expected_bytes[..]);
+
+            let deserialized = Header::deserialize(&mut header_bytes);
+            assert_eq!(deserialized, header);
+            assert!(header_bytes.is_empty());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_header_extension_type_overlapping_channel_bit() {
+        let _header = Header::new(false, 0x8001, 0x16, Some(0));
+    }
+
     /// Verify invalid header with empty length field panics upon serialization if we don't
     /// specify the length field explicitely during serialization
     #[test]
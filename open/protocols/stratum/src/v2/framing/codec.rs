@@ -26,6 +26,7 @@ use tokio_util::codec::{Decoder, Encoder};
 
 use ii_async_compat::{bytes, tokio_util};
 
+use super::dialect;
 use super::{Frame, Header};
 use crate::error::Error;
 use crate::v2::noise;
@@ -35,6 +36,8 @@ pub struct Codec {
     /// Optional noise codec that handles encryption/decryption of messages
     noise_codec: Option<noise::Codec>,
     stratum_codec: LengthDelimitedCodec,
+    /// Translates message types between the wire and logical representation
+    dialect: dialect::Selector,
 }
 
 impl Codec {
@@ -61,8 +64,15 @@ impl Codec {
                 // Actual header length is not counted in the length field
                 .length_adjustment(Header::SIZE as isize)
                 .new_codec(),
+            dialect: Default::default(),
         }
     }
+
+    /// Provides a handle for selecting the dialect of this codec. The handle remains usable after
+    /// the codec has been moved into a framed stream.
+    pub fn dialect(&self) -> dialect::Selector {
+        self.dialect.clone()
+    }
 }
 
 impl Default for Codec {
@@ -94,7 +104,9 @@ impl Decoder for Codec {
             Some(bytes) => bytes,
             None => return Ok(None),
         };
-        Frame::deserialize(&mut bytes).map(Some)
+        let mut frame = Frame::deserialize(&mut bytes)?;
+        self.dialect.decode_header(&mut frame.header)?;
+        Ok(Some(frame))
     }
 }
 
@@ -104,9 +116,10 @@ impl Encoder for Codec {
 
     fn encode(
         &mut self,
-        item: Self::Item,
+        mut item: Self::Item,
         dst: &mut BytesMut,
     ) -> std::result::Result<(), Self::Error> {
+        self.dialect.encode_header(&mut item.header)?;
         let mut encoded_frame = BytesMut::new();
         item.serialize(&mut encoded_frame)?;
         match self.noise_codec {
@@ -148,6 +161,31 @@ mod test {
         );
     }
 
+    /// Frames are translated to the wire message types of the selected dialect and back
+    #[test]
+    fn test_codec_legacy_dialect() {
+        use crate::v2::messages::MessageType;
+
+        let mut codec = Codec::default();
+        codec.dialect().select(dialect::Dialect::Legacy);
+        let payload = BytesMut::from(&[1u8, 2, 3, 4][..]);
+        let msg_type = MessageType::NewMiningJob as u8;
+        let frame = Frame::from_serialized_payload(true, 0, msg_type, payload.clone());
+        let expected_frame = Frame::from_serialized_payload(true, 0, msg_type, payload);
+
+        let mut buffer = BytesMut::new();
+        codec
+            .encode(frame, &mut buffer)
+            .expect("BUG: Codec failed to encode message");
+        assert_eq!(buffer[2], 0x19, "BUG: Legacy wire message type not used");
+
+        let decoded_frame = codec
+            .decode(&mut buffer)
+            .expect("BUG: Codec failed to decode message")
+            .expect("BUG: No frame provided");
+        assert_eq!(expected_frame, decoded_frame);
+    }
+
     /// Attempt to build a V2 codec with noise Codec that is still in handshake mode (=contains
     /// no noise transport) must result in panic
     #[test]
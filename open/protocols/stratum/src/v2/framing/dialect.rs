@@ -0,0 +1,415 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+//! Protocol dialects translate wire message types into logical message types and back
+//!
+//! Message type assignments have shifted between drafts of the specification. Handlers and
+//! sessions work with logical message types (`MessageType`) only. The codec translates the message
+//! type of each base protocol frame according to the dialect that has been selected based on the
+//! protocol version negotiated by `SetupConnection`.
+//!
+//! Supporting another dialect only requires a new `Dialect` variant, its mapping table and an
+//! entry in `SPECS`.
+
+use std::sync::{Arc, Mutex};
+
+use packed_struct::PrimitiveEnum;
+
+use super::{Header, MsgType};
+use crate::error::Result;
+use crate::v2::error::ErrorKind;
+use crate::v2::extensions;
+use crate::v2::messages::MessageType;
+
+/// Protocol version of the legacy draft
+pub const LEGACY_VERSION: u16 = 1;
+/// Protocol version of the current specification that defines the numbering of `MessageType`
+pub const CURRENT_VERSION: u16 = 2;
+
+/// Mapping of logical message types to message types on the wire
+type Table = [(MessageType, MsgType)];
+
+/// Message types of the current specification (protocol version 2)
+const CURRENT: &Table = &[
+    (MessageType::SetupConnection, 0x00),
+    (MessageType::SetupConnectionSuccess, 0x01),
+    (MessageType::SetupConnectionError, 0x02),
+    (MessageType::ChannelEndpointChanged, 0x03),
+    (MessageType::OpenStandardMiningChannel, 0x10),
+    (MessageType::OpenStandardMiningChannelSuccess, 0x11),
+    (MessageType::OpenStandardMiningChannelError, 0x12),
+    (MessageType::OpenExtendedMiningChannel, 0x13),
+    (MessageType::OpenExtendedMiningChannelSuccess, 0x14),
+    (MessageType::OpenExtendedMiningChannelError, 0x15),
+    (MessageType::UpdateChannel, 0x16),
+    (MessageType::UpdateChannelError, 0x17),
+    (MessageType::CloseChannel, 0x18),
+    (MessageType::SetExtranoncePrefix, 0x19),
+    (MessageType::SubmitSharesStandard, 0x1a),
+    (MessageType::SubmitSharesExtended, 0x1b),
+    (MessageType::SubmitSharesSuccess, 0x1c),
+    (MessageType::SubmitSharesError, 0x1d),
+    (MessageType::NewMiningJob, 0x1e),
+    (MessageType::NewExtendedMiningJob, 0x1f),
+    (MessageType::SetNewPrevHash, 0x20),
+    (MessageType::SetTarget, 0x21),
+    (MessageType::SetCustomMiningJob, 0x22),
+    (MessageType::SetCustomMiningJobSuccess, 0x23),
+    (MessageType::SetCustomMiningJobError, 0x24),
+    (MessageType::Reconnect, 0x25),
+    (MessageType::SetGroupChannel, 0x26),
+];
+
+/// Message types of the legacy draft (protocol version 1). The draft has no extended channels,
+/// custom jobs nor group channels and mining messages are numbered consecutively.
+const LEGACY: &Table = &[
+    (MessageType::SetupConnection, 0x00),
+    (MessageType::SetupConnectionSuccess, 0x01),
+    (MessageType::SetupConnectionError, 0x02),
+    (MessageType::OpenStandardMiningChannel, 0x10),
+    (MessageType::OpenStandardMiningChannelSuccess, 0x11),
+    (MessageType::OpenStandardMiningChannelError, 0x12),
+    (MessageType::UpdateChannel, 0x13),
+    (MessageType::UpdateChannelError, 0x14),
+    (MessageType::CloseChannel, 0x15),
+    (MessageType::SubmitSharesStandard, 0x16),
+    (MessageType::SubmitSharesSuccess, 0x17),
+    (MessageType::SubmitSharesError, 0x18),
+    (MessageType::NewMiningJob, 0x19),
+    (MessageType::SetNewPrevHash, 0x1a),
+    (MessageType::SetTarget, 0x1b),
+    (MessageType::Reconnect, 0x1c),
+];
+
+/// Describes a single dialect
+struct Spec {
+    dialect: Dialect,
+    /// Protocol version negotiated by `SetupConnection` that selects this dialect
+    version: u16,
+    table: &'static Table,
+}
+
+/// All supported dialects ordered by protocol version
+const SPECS: &[Spec] = &[
+    Spec {
+        dialect: Dialect::Legacy,
+        version: LEGACY_VERSION,
+        table: LEGACY,
+    },
+    Spec {
+        dialect: Dialect::Current,
+        version: CURRENT_VERSION,
+        table: CURRENT,
+    },
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Dialect {
+    /// Legacy draft still deployed by some pools
+    Legacy,
+    /// Current specification
+    #[default]
+    Current,
+}
+
+impl Dialect {
+    fn spec(self) -> &'static Spec {
+        SPECS
+            .iter()
+            .find(|spec| spec.dialect == self)
+            .expect("BUG: missing specification for dialect")
+    }
+
+    /// Selects dialect based on negotiated protocol `version`
+    pub fn from_version(version: u16) -> Option<Self> {
+        SPECS
+            .iter()
+            .find(|spec| spec.version == version)
+            .map(|spec| spec.dialect)
+    }
+
+    /// Protocol version that corresponds to the dialect
+    pub fn version(self) -> u16 {
+        self.spec().version
+    }
+
+    /// Lowest protocol version supported by any dialect
+    pub fn min_version() -> u16 {
+        SPECS.first().expect("BUG: no dialects").version
+    }
+
+    /// Highest protocol version supported by any dialect
+    pub fn max_version() -> u16 {
+        SPECS.last().expect("BUG: no dialects").version
+    }
+
+    /// Translates logical message type into the wire message type
+    pub fn to_wire(self, msg_type: MessageType) -> Option<MsgType> {
+        self.spec()
+            .table
+            .iter()
+            .find(|(logical, _)| *logical == msg_type)
+            .map(|(_, wire)| *wire)
+    }
+
+    /// Translates wire message type into the logical message type
+    pub fn from_wire(self, msg_type: MsgType) -> Option<MessageType> {
+        self.spec()
+            .table
+            .iter()
+            .find(|(_, wire)| *wire == msg_type)
+            .map(|(logical, _)| *logical)
+    }
+}
+
+/// Policy for base protocol message types that are unknown in the selected dialect
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum UnknownPolicy {
+    /// Pass the frame untranslated unless its message type can be mistaken for a known message
+    #[default]
+    Relay,
+    /// Reject the frame
+    Strict,
+}
+
+#[derive(Debug)]
+struct Settings {
+    dialect: Dialect,
+    policy: UnknownPolicy,
+}
+
+/// Shared handle for selecting the dialect of a codec. It allows switching the dialect once the
+/// connection setup has been negotiated even when the framed stream has already been split.
+#[derive(Clone, Debug)]
+pub struct Selector {
+    inner: Arc<Mutex<Settings>>,
+}
+
+impl Selector {
+    pub fn new(dialect: Dialect, policy: UnknownPolicy) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Settings { dialect, policy })),
+        }
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        self.inner.lock().expect("BUG: cannot lock dialect").dialect
+    }
+
+    pub fn policy(&self) -> UnknownPolicy {
+        self.inner.lock().expect("BUG: cannot lock dialect").policy
+    }
+
+    pub fn select(&self, dialect: Dialect) {
+        self.inner.lock().expect("BUG: cannot lock dialect").dialect = dialect;
+    }
+
+    /// Selects dialect based on negotiated protocol `version`. Returns `None` when no dialect
+    /// supports the version and the dialect stays unchanged.
+    pub fn select_version(&self, version: u16) -> Option<Dialect> {
+        let dialect = Dialect::from_version(version)?;
+        self.select(dialect);
+        Some(dialect)
+    }
+
+    pub fn set_policy(&self, policy: UnknownPolicy) {
+        self.inner.lock().expect("BUG: cannot lock dialect").policy = policy;
+    }
+
+    fn unknown(&self, header: &Header, policy: UnknownPolicy, dialect: Dialect) -> Result<()> {
+        match policy {
+            UnknownPolicy::Relay if MessageType::from_primitive(header.msg_type).is_none() => {
+                Ok(())
+            }
+            _ => Err(ErrorKind::UnknownMessage(format!(
+                "Message type unknown in dialect {:?}, full header: {:x?}",
+                dialect, header
+            ))
+            .into()),
+        }
+    }
+
+    /// Translates message type of a received frame into the logical message type
+    pub(crate) fn decode_header(&self, header: &mut Header) -> Result<()> {
+        if header.extension_type != extensions::BASE {
+            return Ok(());
+        }
+        let (dialect, policy) = {
+            let settings = self.inner.lock().expect("BUG: cannot lock dialect");
+            (settings.dialect, settings.policy)
+        };
+        match dialect.from_wire(header.msg_type) {
+            Some(msg_type) => {
+                header.msg_type = msg_type as MsgType;
+                Ok(())
+            }
+            None => self.unknown(header, policy, dialect),
+        }
+    }
+
+    /// Translates logical message type of a frame that is to be sent into the wire message type
+    pub(crate) fn encode_header(&self, header: &mut Header) -> Result<()> {
+        if header.extension_type != extensions::BASE {
+            return Ok(());
+        }
+        let (dialect, policy) = {
+            let settings = self.inner.lock().expect("BUG: cannot lock dialect");
+            (settings.dialect, settings.policy)
+        };
+        match MessageType::from_primitive(header.msg_type).and_then(|t| dialect.to_wire(t)) {
+            Some(msg_type) => {
+                header.msg_type = msg_type;
+                Ok(())
+            }
+            None => self.unknown(header, policy, dialect),
+        }
+    }
+}
+
+impl Default for Selector {
+    fn default() -> Self {
+        Self::new(Default::default(), Default::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Every table must be a bijection otherwise translation would be ambiguous
+    #[test]
+    fn test_tables_unambiguous() {
+        for spec in SPECS {
+            let logical: HashSet<_> = spec.table.iter().map(|(l, _)| *l as MsgType).collect();
+            let wire: HashSet<_> = spec.table.iter().map(|(_, w)| *w).collect();
+            assert_eq!(logical.len(), spec.table.len(), "{:?}", spec.dialect);
+            assert_eq!(wire.len(), spec.table.len(), "{:?}", spec.dialect);
+        }
+    }
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(Dialect::from_version(1), Some(Dialect::Legacy));
+        assert_eq!(Dialect::from_version(2), Some(Dialect::Current));
+        assert_eq!(Dialect::from_version(3), None);
+        assert_eq!(Dialect::min_version(), 1);
+        assert_eq!(Dialect::max_version(), 2);
+        assert_eq!(Dialect::Current.version(), CURRENT_VERSION);
+        assert_eq!(Dialect::Legacy.version(), LEGACY_VERSION);
+        for spec in SPECS {
+            assert_eq!(spec.dialect.version(), spec.version);
+        }
+    }
+
+    /// Current specification uses the logical numbering as is
+    #[test]
+    fn test_current_identity() {
+        for (logical, wire) in CURRENT {
+            assert_eq!(*logical as MsgType, *wire);
+        }
+    }
+
+    #[test]
+    fn test_legacy_mapping() {
+        let dialect = Dialect::Legacy;
+        assert_eq!(dialect.to_wire(MessageType::SetupConnection), Some(0x00));
+        assert_eq!(
+            dialect.to_wire(MessageType::SubmitSharesStandard),
+            Some(0x16)
+        );
+        assert_eq!(dialect.to_wire(MessageType::NewMiningJob), Some(0x19));
+        assert_eq!(dialect.to_wire(MessageType::SetTarget), Some(0x1b));
+        assert_eq!(dialect.to_wire(MessageType::SetGroupChannel), None);
+        // The legacy draft has no extended channels
+        for msg_type in &[
+            MessageType::OpenExtendedMiningChannel,
+            MessageType::OpenExtendedMiningChannelSuccess,
+            MessageType::OpenExtendedMiningChannelError,
+        ] {
+            assert_eq!(dialect.to_wire(*msg_type), None);
+        }
+        assert_eq!(dialect.from_wire(0x15), Some(MessageType::CloseChannel));
+        assert_eq!(
+            dialect.from_wire(0x17),
+            Some(MessageType::SubmitSharesSuccess)
+        );
+        assert_eq!(dialect.from_wire(0x1a), Some(MessageType::SetNewPrevHash));
+        assert_eq!(dialect.from_wire(0x1d), None);
+    }
+
+    #[test]
+    fn test_header_translation() {
+        let selector = Selector::new(Dialect::Legacy, UnknownPolicy::Strict);
+
+        let mut header = Header::new(true, extensions::BASE, 0x19, Some(0));
+        selector
+            .decode_header(&mut header)
+            .expect("BUG: decode failed");
+        assert_eq!(header.msg_type, MessageType::NewMiningJob as MsgType);
+        selector
+            .encode_header(&mut header)
+            .expect("BUG: encode failed");
+        assert_eq!(header.msg_type, 0x19);
+
+        // Extension messages are not subject to translation
+        let mut header = Header::new(true, extensions::TELEMETRY, 0x19, Some(0));
+        selector
+            .decode_header(&mut header)
+            .expect("BUG: decode failed");
+        assert_eq!(header.msg_type, 0x19);
+    }
+
+    #[test]
+    fn test_unknown_policy() {
+        let selector = Selector::new(Dialect::Legacy, UnknownPolicy::Strict);
+        // Unknown in the legacy draft as well as in the logical numbering
+        let mut header = Header::new(false, extensions::BASE, 0x7f, Some(0));
+        assert!(selector.decode_header(&mut header).is_err());
+        // Known logical message that doesn't exist in the legacy draft
+        header.msg_type = MessageType::SetGroupChannel as MsgType;
+        assert!(selector.encode_header(&mut header).is_err());
+
+        selector.set_policy(UnknownPolicy::Relay);
+        let mut header = Header::new(false, extensions::BASE, 0x7f, Some(0));
+        selector
+            .decode_header(&mut header)
+            .expect("BUG: relay failed");
+        assert_eq!(header.msg_type, 0x7f);
+        selector
+            .encode_header(&mut header)
+            .expect("BUG: relay failed");
+        assert_eq!(header.msg_type, 0x7f);
+        // Relaying 0x1d untranslated would turn it into a logical SubmitSharesError
+        header.msg_type = 0x1d;
+        assert!(selector.decode_header(&mut header).is_err());
+    }
+
+    #[test]
+    fn test_select_version() {
+        let selector = Selector::default();
+        assert_eq!(selector.dialect(), Dialect::Current);
+        assert_eq!(selector.select_version(1), Some(Dialect::Legacy));
+        assert_eq!(selector.dialect(), Dialect::Legacy);
+        assert_eq!(selector.select_version(7), None);
+        assert_eq!(selector.dialect(), Dialect::Legacy);
+    }
+}
@@ -83,3 +83,33 @@ macro_rules! impl_message_conversion {
         }
     };
 }
+
+/// Implements `ChannelMessage` for messages that carry the `channel_id` field. The field has to
+/// be the first one so that it can be read from the serialized payload directly (see
+/// `framing::channel_id`).
+#[macro_export]
+macro_rules! impl_channel_message {
+    ($($message:ty),+ $(,)?) => {
+        $(
+            impl ChannelMessage for $message {
+                fn channel_id(&self) -> ChannelId {
+                    ChannelId(self.channel_id)
+                }
+            }
+        )+
+    };
+}
+
+/// Implements `RequestMessage` for requests and responses that carry the `req_id` field
+#[macro_export]
+macro_rules! impl_request_message {
+    ($($message:ty),+ $(,)?) => {
+        $(
+            impl RequestMessage for $message {
+                fn req_id(&self) -> u32 {
+                    self.req_id
+                }
+            }
+        )+
+    };
+}
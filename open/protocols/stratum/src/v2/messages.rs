@@ -23,11 +23,15 @@
 //! All stratum V2 protocol messages
 
 use async_trait::async_trait;
+use bitcoin_hashes::{sha256d, Hash, HashEngine};
 use packed_struct_codegen::PrimitiveEnum_u8;
 use serde;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
 
+use super::error::ErrorKind;
 use super::extensions;
 use super::framing;
 #[cfg(not(feature = "v2json"))]
@@ -49,7 +53,19 @@ macro_rules! impl_base_message_conversion {
     };
 }
 
-/// All message recognized by the protocol
+/// Message addressed to a specific channel
+pub trait ChannelMessage {
+    fn channel_id(&self) -> ChannelId;
+}
+
+/// Request or its response paired by the request id
+pub trait RequestMessage {
+    fn req_id(&self) -> u32;
+}
+
+/// All message recognized by the protocol. The values are message types of the current
+/// specification (`dialect::CURRENT_VERSION`), other versions of the protocol are translated by
+/// the codec (see `dialect`).
 #[derive(PrimitiveEnum_u8, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum MessageType {
     SetupConnection = 0x00,
@@ -78,7 +94,7 @@ pub enum MessageType {
     SetTarget = 0x21,
     SetCustomMiningJob = 0x22,
     SetCustomMiningJobSuccess = 0x23,
-    SetCustomMiningError = 0x24,
+    SetCustomMiningJobError = 0x24,
     Reconnect = 0x25,
     SetGroupChannel = 0x26,
 }
@@ -108,6 +124,13 @@ pub struct SetupConnectionError {
     pub code: Str0_255,
 }
 
+/// Notifies that the channel now terminates at a different upstream endpoint (e.g. after a proxy
+/// has reconnected). All jobs of the channel are stale (see `channel::JobRegistry`).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChannelEndpointChanged {
+    pub channel_id: u32,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct OpenStandardMiningChannel {
     pub req_id: u32,
@@ -130,16 +153,111 @@ pub struct OpenStandardMiningChannelSuccess {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct OpenStandardMiningChannelError {
     pub req_id: u32,
+    /// See `OpenMiningChannelErrorCode` for the defined values
     pub code: Str0_32,
 }
 
+impl OpenStandardMiningChannelError {
+    /// Returns the reason of the failure unless the code is not defined by the protocol
+    pub fn error_code(&self) -> Option<OpenMiningChannelErrorCode> {
+        self.code.parse().ok()
+    }
+}
+
+/// Extended channel leaves rolling of the extranonce (except of its prefix) and the construction
+/// of the coinbase transaction to the miner (see `NewExtendedMiningJob`)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpenExtendedMiningChannel {
+    pub req_id: u32,
+    pub user: Str1_255,
+    pub nominal_hashrate: f32,
+    pub max_target: Uint256Bytes,
+    /// Minimum size of the extranonce (not including the prefix) needed by the miner
+    pub min_extranonce_size: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpenExtendedMiningChannelSuccess {
+    pub req_id: u32,
+    pub channel_id: u32,
+    /// Initial target for mining
+    pub target: Uint256Bytes,
+    /// Size of the extranonce rolled by the miner
+    pub extranonce_size: u16,
+    pub extranonce_prefix: Bytes0_32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpenExtendedMiningChannelError {
+    pub req_id: u32,
+    /// See `OpenMiningChannelErrorCode` for the defined values
+    pub code: Str0_32,
+}
+
+impl OpenExtendedMiningChannelError {
+    /// Returns the reason of the failure unless the code is not defined by the protocol
+    pub fn error_code(&self) -> Option<OpenMiningChannelErrorCode> {
+        self.code.parse().ok()
+    }
+}
+
+/// Failure reasons of opening both standard and extended channels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpenMiningChannelErrorCode {
+    UnknownUser,
+    MaxTargetOutOfRange,
+}
+
+impl fmt::Display for OpenMiningChannelErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownUser => write!(f, "unknown-user"),
+            Self::MaxTargetOutOfRange => write!(f, "max-target-out-of-range"),
+        }
+    }
+}
+
+impl FromStr for OpenMiningChannelErrorCode {
+    type Err = Error;
+
+    fn from_str(code: &str) -> Result<Self> {
+        match code {
+            "unknown-user" => Ok(Self::UnknownUser),
+            "max-target-out-of-range" => Ok(Self::MaxTargetOutOfRange),
+            _ => Err(ErrorKind::UnknownErrorCode(code.to_string()).into()),
+        }
+    }
+}
+
+impl From<OpenMiningChannelErrorCode> for Str0_32 {
+    fn from(code: OpenMiningChannelErrorCode) -> Self {
+        Self::from_string(code.to_string())
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct UpdateChannel;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct UpdateChannelError;
 
-pub struct CloseChannel;
+/// Closes the channel, the channel id may be assigned to another channel afterwards
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CloseChannel {
+    pub channel_id: u32,
+    /// Human-readable reason for closing the channel
+    pub reason_code: Str0_32,
+}
+
+/// Changes the extranonce prefix of an extended channel. The new prefix applies to jobs sent after
+/// this message, jobs sent before it still use the previous prefix. Messages of a connection are
+/// handled in the order they have been received so the consumer only has to store the current
+/// prefix with each job when the job arrives.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetExtranoncePrefix {
+    pub channel_id: u32,
+    pub extranonce_prefix: Bytes0_32,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SubmitSharesStandard {
@@ -152,6 +270,15 @@ pub struct SubmitSharesStandard {
     pub version: u32,
 }
 
+/// Share of an extended channel. The fields shared with standard channels are serialized
+/// first so the same validation applies to both kinds of submits.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SubmitSharesExtended {
+    pub share: SubmitSharesStandard,
+    /// Full extranonce selected by the miner
+    pub extranonce: Bytes0_32,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SubmitSharesSuccess {
     pub channel_id: u32,
@@ -176,7 +303,41 @@ pub struct NewMiningJob {
     pub merkle_root: Uint256Bytes,
 }
 
-pub struct NewExtendedMiningJob;
+/// Job of an extended channel. The miner builds the coinbase transaction from its prefix, the full
+/// extranonce and its suffix and computes the merkle root itself (see `merkle_root`).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NewExtendedMiningJob {
+    pub channel_id: u32,
+    pub job_id: u32,
+    pub future_job: bool,
+    pub version: u32,
+    /// Hashes of transactions needed to compute the merkle root from the coinbase transaction
+    pub merkle_path: Seq0_255<Uint256Bytes>,
+    pub coinbase_tx_prefix: Bytes0_64k,
+    pub coinbase_tx_suffix: Bytes0_64k,
+}
+
+impl NewExtendedMiningJob {
+    /// Computes merkle root of the job with coinbase transaction containing the full `extranonce`
+    pub fn merkle_root(&self, extranonce: &[u8]) -> Uint256Bytes {
+        let mut engine = sha256d::Hash::engine();
+        engine.input(&self.coinbase_tx_prefix);
+        engine.input(extranonce);
+        engine.input(&self.coinbase_tx_suffix);
+        let coinbase_tx_hash = sha256d::Hash::from_engine(engine);
+
+        let merkle_root = self
+            .merkle_path
+            .iter()
+            .fold(coinbase_tx_hash, |merkle_root, tx_hash| {
+                let mut engine = sha256d::Hash::engine();
+                engine.input(&merkle_root.into_inner());
+                engine.input(tx_hash.as_ref());
+                sha256d::Hash::from_engine(engine)
+            });
+        Uint256Bytes(merkle_root.into_inner())
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SetNewPrevHash {
@@ -189,9 +350,105 @@ pub struct SetNewPrevHash {
     //pub signature: ??,
 }
 
-pub struct SetCustomMiningJob;
-pub struct SetCustomMiningJobSuccess;
-pub struct Reconnect;
+/// Job with a transaction set chosen by the miner and negotiated with the pool beforehand
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetCustomMiningJob {
+    pub channel_id: u32,
+    pub req_id: u32,
+    /// Token of the negotiated transaction set allocated by the pool
+    pub mining_job_token: Bytes0_255,
+    pub version: u32,
+    pub prev_hash: Uint256Bytes,
+    pub min_ntime: u32,
+    pub nbits: u32,
+    pub merkle_path: Seq0_255<Uint256Bytes>,
+    pub coinbase_tx_prefix: Bytes0_64k,
+    pub coinbase_tx_suffix: Bytes0_64k,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetCustomMiningJobSuccess {
+    pub channel_id: u32,
+    pub req_id: u32,
+    /// Id of the job assigned by the pool that is used when submitting shares
+    pub job_id: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetCustomMiningJobError {
+    pub channel_id: u32,
+    pub req_id: u32,
+    /// See `SetCustomMiningJobErrorCode` for the defined values
+    pub code: Str0_32,
+}
+
+impl SetCustomMiningJobError {
+    /// Returns the reason of the failure unless the code is not defined by the protocol
+    pub fn error_code(&self) -> Option<SetCustomMiningJobErrorCode> {
+        self.code.parse().ok()
+    }
+}
+
+/// Failure reasons of `SetCustomMiningJob` request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SetCustomMiningJobErrorCode {
+    InvalidChannelId,
+    InvalidMiningJobToken,
+    /// Value of the named field of the job has been refused
+    InvalidJobParamValue(String),
+}
+
+impl SetCustomMiningJobErrorCode {
+    const INVALID_JOB_PARAM_VALUE: &'static str = "invalid-job-param-value-";
+}
+
+impl fmt::Display for SetCustomMiningJobErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidChannelId => write!(f, "invalid-channel-id"),
+            Self::InvalidMiningJobToken => write!(f, "invalid-mining-job-token"),
+            Self::InvalidJobParamValue(field) => {
+                write!(f, "{}{}", Self::INVALID_JOB_PARAM_VALUE, field)
+            }
+        }
+    }
+}
+
+impl FromStr for SetCustomMiningJobErrorCode {
+    type Err = Error;
+
+    fn from_str(code: &str) -> Result<Self> {
+        match code {
+            "invalid-channel-id" => Ok(Self::InvalidChannelId),
+            "invalid-mining-job-token" => Ok(Self::InvalidMiningJobToken),
+            _ if code.starts_with(Self::INVALID_JOB_PARAM_VALUE)
+                && code.len() > Self::INVALID_JOB_PARAM_VALUE.len() =>
+            {
+                Ok(Self::InvalidJobParamValue(
+                    code[Self::INVALID_JOB_PARAM_VALUE.len()..].to_string(),
+                ))
+            }
+            _ => Err(ErrorKind::UnknownErrorCode(code.to_string()).into()),
+        }
+    }
+}
+
+impl TryFrom<SetCustomMiningJobErrorCode> for Str0_32 {
+    type Error = ();
+
+    fn try_from(code: SetCustomMiningJobErrorCode) -> std::result::Result<Self, ()> {
+        Self::try_from(code.to_string())
+    }
+}
+
+/// Redirects the client to a different endpoint
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Reconnect {
+    pub new_host: Str0_255,
+    pub new_port: u16,
+    /// The current connection should be kept until the new one is established
+    pub keep_connection: bool,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SetTarget {
@@ -199,7 +456,13 @@ pub struct SetTarget {
     pub max_target: Uint256Bytes,
 }
 
-pub struct SetGroupChannel;
+/// Groups standard channels so that a message sent to `group_channel_id` addresses all of them.
+/// The message replaces all previous members of the group.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SetGroupChannel {
+    pub group_channel_id: u32,
+    pub channel_ids: Seq0_64k<u32>,
+}
 
 impl_base_message_conversion!(SetupConnection, false, visit_setup_connection);
 impl_base_message_conversion!(
@@ -208,6 +471,7 @@ impl_base_message_conversion!(
     visit_setup_connection_success
 );
 impl_base_message_conversion!(SetupConnectionError, false, visit_setup_connection_error);
+impl_base_message_conversion!(ChannelEndpointChanged, true, visit_channel_endpoint_changed);
 impl_base_message_conversion!(
     OpenStandardMiningChannel,
     false,
@@ -223,11 +487,77 @@ impl_base_message_conversion!(
     false,
     visit_open_standard_mining_channel_error
 );
+impl_base_message_conversion!(
+    OpenExtendedMiningChannel,
+    false,
+    visit_open_extended_mining_channel
+);
+impl_base_message_conversion!(
+    OpenExtendedMiningChannelSuccess,
+    false,
+    visit_open_extended_mining_channel_success
+);
+impl_base_message_conversion!(
+    OpenExtendedMiningChannelError,
+    false,
+    visit_open_extended_mining_channel_error
+);
 impl_base_message_conversion!(UpdateChannel, true, visit_update_channel);
 impl_base_message_conversion!(UpdateChannelError, true, visit_update_channel_error);
+impl_base_message_conversion!(CloseChannel, true, visit_close_channel);
+impl_base_message_conversion!(SetExtranoncePrefix, true, visit_set_extranonce_prefix);
 impl_base_message_conversion!(SubmitSharesStandard, true, visit_submit_shares_standard);
+impl_base_message_conversion!(SubmitSharesExtended, true, visit_submit_shares_extended);
 impl_base_message_conversion!(SubmitSharesSuccess, true, visit_submit_shares_success);
 impl_base_message_conversion!(SubmitSharesError, true, visit_submit_shares_error);
 impl_base_message_conversion!(NewMiningJob, true, visit_new_mining_job);
+impl_base_message_conversion!(NewExtendedMiningJob, true, visit_new_extended_mining_job);
 impl_base_message_conversion!(SetNewPrevHash, true, visit_set_new_prev_hash);
+impl_base_message_conversion!(SetCustomMiningJob, true, visit_set_custom_mining_job);
+impl_base_message_conversion!(
+    SetCustomMiningJobSuccess,
+    true,
+    visit_set_custom_mining_job_success
+);
+impl_base_message_conversion!(
+    SetCustomMiningJobError,
+    true,
+    visit_set_custom_mining_job_error
+);
 impl_base_message_conversion!(SetTarget, true, visit_set_target);
+impl_base_message_conversion!(Reconnect, false, visit_reconnect);
+impl_base_message_conversion!(SetGroupChannel, false, visit_set_group_channel);
+
+impl_channel_message!(
+    ChannelEndpointChanged,
+    CloseChannel,
+    SetExtranoncePrefix,
+    SubmitSharesStandard,
+    SubmitSharesSuccess,
+    SubmitSharesError,
+    NewMiningJob,
+    NewExtendedMiningJob,
+    SetNewPrevHash,
+    SetCustomMiningJob,
+    SetCustomMiningJobSuccess,
+    SetCustomMiningJobError,
+    SetTarget,
+);
+
+impl_request_message!(
+    OpenStandardMiningChannel,
+    OpenStandardMiningChannelSuccess,
+    OpenStandardMiningChannelError,
+    OpenExtendedMiningChannel,
+    OpenExtendedMiningChannelSuccess,
+    OpenExtendedMiningChannelError,
+    SetCustomMiningJob,
+    SetCustomMiningJobSuccess,
+    SetCustomMiningJobError,
+);
+
+impl ChannelMessage for SubmitSharesExtended {
+    fn channel_id(&self) -> ChannelId {
+        self.share.channel_id()
+    }
+}
@@ -20,6 +20,7 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+use bitcoin_hashes::{hex::FromHex, sha256d, Hash};
 use bytes::{buf::BufMutExt, BytesMut};
 
 use ii_async_compat::bytes;
@@ -27,6 +28,9 @@ use ii_async_compat::bytes;
 use super::*;
 use crate::payload::SerializablePayload;
 use crate::test_utils::v2::*;
+use crate::v2::telemetry::messages::{
+    SubmitTelemetryData, SubmitTelemetryDataError, SubmitTelemetryDataSuccess,
+};
 
 #[test]
 fn test_deserialize_setup_connection() {
@@ -55,3 +59,507 @@ fn test_serialize_setup_connection() {
         serialized_message
     );
 }
+
+#[test]
+fn test_channel_endpoint_changed_round_trip() {
+    let message = ChannelEndpointChanged {
+        channel_id: 0x0102_0304,
+    };
+    let mut writer = BytesMut::new().writer();
+    message
+        .serialize_to_writer(&mut writer)
+        .expect("BUG: cannot serialize message");
+    let payload = writer.into_inner();
+    assert_eq!(payload, BytesMut::from(&[0x04, 0x03, 0x02, 0x01][..]));
+
+    let deserialized =
+        ChannelEndpointChanged::try_from(&payload[..]).expect("BUG: cannot deserialize");
+    assert_eq!(deserialized, message);
+}
+
+#[test]
+fn test_close_channel_round_trip() {
+    let message = CloseChannel {
+        channel_id: 0x0102_0304,
+        reason_code: Str0_32::from_str("pool-shutdown"),
+    };
+    let mut writer = BytesMut::new().writer();
+    message
+        .serialize_to_writer(&mut writer)
+        .expect("BUG: cannot serialize message");
+    let payload = writer.into_inner();
+
+    let deserialized = CloseChannel::try_from(&payload[..]).expect("BUG: cannot deserialize");
+    assert_eq!(deserialized, message);
+
+    // The frame is recognized as a channel message regardless of its channel being open
+    let frame: framing::Frame = message.try_into().expect("BUG: cannot build frame");
+    assert_eq!(
+        frame.header.msg_type,
+        MessageType::CloseChannel as framing::MsgType
+    );
+    assert!(frame.header.is_channel_message);
+}
+
+/// Prefixes of both the maximum and zero length are transferred intact, longer ones are refused
+#[test]
+fn test_set_extranonce_prefix_length() {
+    for len in &[0, 32] {
+        let message = SetExtranoncePrefix {
+            channel_id: 7,
+            extranonce_prefix: Bytes0_32::try_from(vec![0xa5; *len]).expect("BUG: prefix"),
+        };
+        let mut writer = BytesMut::new().writer();
+        message
+            .serialize_to_writer(&mut writer)
+            .expect("BUG: cannot serialize message");
+        let payload = writer.into_inner();
+        // Channel id and the length of the prefix precede the prefix itself
+        assert_eq!(payload.len(), 4 + 1 + len);
+
+        let deserialized =
+            SetExtranoncePrefix::try_from(&payload[..]).expect("BUG: cannot deserialize");
+        assert_eq!(deserialized, message);
+    }
+
+    let mut payload = vec![7, 0, 0, 0, 33];
+    payload.extend_from_slice(&[0xa5; 33]);
+    SetExtranoncePrefix::try_from(&payload[..]).expect_err("BUG: too long prefix accepted");
+}
+
+#[test]
+fn test_submit_shares_extended_round_trip() {
+    let message = build_submit_shares_extended();
+    let mut writer = BytesMut::new().writer();
+    message
+        .serialize_to_writer(&mut writer)
+        .expect("BUG: cannot serialize message");
+    let payload = writer.into_inner();
+
+    // The extended submit is the standard one followed by the extranonce
+    let mut writer = BytesMut::new().writer();
+    message
+        .share
+        .serialize_to_writer(&mut writer)
+        .expect("BUG: cannot serialize message");
+    let mut expected = writer.into_inner();
+    expected.extend_from_slice(&[8, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+    assert_eq!(payload, expected);
+
+    let deserialized =
+        SubmitSharesExtended::try_from(&payload[..]).expect("BUG: cannot deserialize");
+    assert_eq!(deserialized, message);
+    // Standard submit without the extranonce is not a valid extended submit
+    SubmitSharesExtended::try_from(&payload[..payload.len() - 9])
+        .expect_err("BUG: truncated extended submit accepted");
+}
+
+#[test]
+fn test_new_extended_mining_job_round_trip() {
+    let message = build_new_extended_mining_job();
+    let mut writer = BytesMut::new().writer();
+    message
+        .serialize_to_writer(&mut writer)
+        .expect("BUG: cannot serialize message");
+    let payload = writer.into_inner();
+
+    let deserialized =
+        NewExtendedMiningJob::try_from(&payload[..]).expect("BUG: cannot deserialize");
+    assert_eq!(deserialized, message);
+}
+
+#[test]
+fn test_new_extended_mining_job_merkle_root() {
+    let job = build_new_extended_mining_job();
+    let extranonce = hex::decode(BLOCK_170_EXTRANONCE).expect("BUG: invalid extranonce");
+    let expected = sha256d::Hash::from_hex(BLOCK_170_MERKLE_ROOT).expect("BUG: invalid hash");
+    assert_eq!(
+        job.merkle_root(&extranonce),
+        Uint256Bytes(expected.into_inner())
+    );
+    // A different extranonce results in a different coinbase transaction
+    assert_ne!(
+        job.merkle_root(&[0; 4]),
+        Uint256Bytes(expected.into_inner())
+    );
+
+    // Merkle root of a job with the coinbase transaction only is its hash
+    let coinbase_tx = [
+        &job.coinbase_tx_prefix[..],
+        &extranonce,
+        &job.coinbase_tx_suffix[..],
+    ]
+    .concat();
+    let job = NewExtendedMiningJob {
+        merkle_path: Seq0_255::new(),
+        ..job
+    };
+    assert_eq!(
+        job.merkle_root(&extranonce),
+        Uint256Bytes(sha256d::Hash::hash(&coinbase_tx).into_inner())
+    );
+}
+
+/// Serializes the `message` and verifies that it deserializes back to the same message
+fn check_round_trip<T>(message: T)
+where
+    T: SerializablePayload<Protocol>
+        + for<'a> TryFrom<&'a [u8], Error = Error>
+        + PartialEq
+        + std::fmt::Debug,
+{
+    let mut writer = BytesMut::new().writer();
+    message
+        .serialize_to_writer(&mut writer)
+        .expect("BUG: cannot serialize message");
+    let payload = writer.into_inner();
+
+    let deserialized = T::try_from(&payload[..]).expect("BUG: cannot deserialize message");
+    assert_eq!(deserialized, message);
+}
+
+#[test]
+fn test_open_channel_round_trip() {
+    check_round_trip(build_open_channel());
+    check_round_trip(build_open_channel_success());
+    check_round_trip(OpenStandardMiningChannelError {
+        req_id: 10,
+        code: OpenMiningChannelErrorCode::UnknownUser.into(),
+    });
+    check_round_trip(build_open_extended_channel());
+    check_round_trip(build_open_extended_channel_success());
+    check_round_trip(OpenExtendedMiningChannelError {
+        req_id: 11,
+        code: OpenMiningChannelErrorCode::MaxTargetOutOfRange.into(),
+    });
+}
+
+/// Standard and extended variants are distinct messages on the wire
+#[test]
+fn test_open_extended_channel_frames() {
+    let frame: framing::Frame = build_open_extended_channel()
+        .try_into()
+        .expect("BUG: cannot build frame");
+    assert_eq!(
+        frame.header.msg_type,
+        MessageType::OpenExtendedMiningChannel as framing::MsgType
+    );
+    assert!(!frame.header.is_channel_message);
+
+    // The request differs from the standard one only by the trailing extranonce size
+    let standard = build_open_channel();
+    let extended = OpenExtendedMiningChannel {
+        req_id: standard.req_id,
+        user: standard.user.clone(),
+        nominal_hashrate: standard.nominal_hashrate,
+        max_target: standard.max_target,
+        min_extranonce_size: 0x0201,
+    };
+    let serialize = |message: &dyn SerializablePayload<Protocol>| {
+        let mut writer = BytesMut::new().writer();
+        message
+            .serialize_to_writer(&mut writer)
+            .expect("BUG: cannot serialize message");
+        writer.into_inner()
+    };
+    let mut expected = serialize(&standard);
+    expected.extend_from_slice(&[0x01, 0x02]);
+    assert_eq!(serialize(&extended), expected);
+}
+
+#[test]
+fn test_open_channel_error_code() {
+    for code in &[
+        OpenMiningChannelErrorCode::UnknownUser,
+        OpenMiningChannelErrorCode::MaxTargetOutOfRange,
+    ] {
+        let standard = OpenStandardMiningChannelError {
+            req_id: 1,
+            code: (*code).into(),
+        };
+        assert_eq!(standard.error_code(), Some(*code));
+        let extended = OpenExtendedMiningChannelError {
+            req_id: 1,
+            code: standard.code.clone(),
+        };
+        assert_eq!(extended.error_code(), Some(*code));
+    }
+    assert_eq!(
+        OpenMiningChannelErrorCode::MaxTargetOutOfRange.to_string(),
+        "max-target-out-of-range"
+    );
+    let error = OpenStandardMiningChannelError {
+        req_id: 1,
+        code: Str0_32::from_str("Out of sequence"),
+    };
+    assert_eq!(error.error_code(), None);
+}
+
+#[test]
+fn test_set_custom_mining_job_round_trip() {
+    let message = build_set_custom_mining_job();
+    assert_eq!(message.req_id(), 2);
+    check_round_trip(message);
+
+    let message = SetCustomMiningJobSuccess {
+        channel_id: 1,
+        req_id: 2,
+        job_id: 3,
+    };
+    assert_eq!(message.req_id(), 2);
+    check_round_trip(message);
+
+    let message = SetCustomMiningJobError {
+        channel_id: 1,
+        req_id: 2,
+        code: Str0_32::from_str("invalid-job-param-value-nbits"),
+    };
+    assert_eq!(message.req_id(), 2);
+    assert_eq!(
+        message.error_code(),
+        Some(SetCustomMiningJobErrorCode::InvalidJobParamValue(
+            "nbits".to_string()
+        ))
+    );
+    check_round_trip(message);
+}
+
+#[test]
+fn test_set_custom_mining_job_error_code() {
+    for code in &[
+        SetCustomMiningJobErrorCode::InvalidChannelId,
+        SetCustomMiningJobErrorCode::InvalidMiningJobToken,
+        SetCustomMiningJobErrorCode::InvalidJobParamValue("min_ntime".to_string()),
+    ] {
+        let parsed: SetCustomMiningJobErrorCode =
+            code.to_string().parse().expect("BUG: cannot parse code");
+        assert_eq!(&parsed, code);
+    }
+    assert_eq!(
+        SetCustomMiningJobErrorCode::InvalidChannelId.to_string(),
+        "invalid-channel-id"
+    );
+
+    for code in &["", "invalid", "invalid-job-param-value-"] {
+        code.parse::<SetCustomMiningJobErrorCode>()
+            .expect_err("BUG: unknown code parsed");
+    }
+    let message = SetCustomMiningJobError {
+        channel_id: 1,
+        req_id: 2,
+        code: Str0_32::from_str("out-of-luck"),
+    };
+    assert_eq!(message.error_code(), None);
+
+    // Code with too long name of the field doesn't fit the message
+    let code = SetCustomMiningJobErrorCode::InvalidJobParamValue("x".repeat(9));
+    assert!(Str0_32::try_from(code).is_err());
+}
+
+#[test]
+fn test_reconnect_round_trip() {
+    check_round_trip(build_reconnect());
+    check_round_trip(Reconnect {
+        new_host: Str0_255::new(),
+        new_port: 0,
+        keep_connection: false,
+    });
+
+    let frame: framing::Frame = build_reconnect()
+        .try_into()
+        .expect("BUG: cannot build frame");
+    assert!(!frame.header.is_channel_message);
+}
+
+#[test]
+fn test_set_group_channel_round_trip() {
+    for channel_ids in &[vec![], vec![1, 2, 3], vec![7; 65535]] {
+        let message = SetGroupChannel {
+            group_channel_id: 100,
+            channel_ids: Seq0_64k::from_slice(channel_ids),
+        };
+        let mut writer = BytesMut::new().writer();
+        message
+            .serialize_to_writer(&mut writer)
+            .expect("BUG: cannot serialize message");
+        let payload = writer.into_inner();
+        // Group id and the length of the sequence precede the channel ids
+        assert_eq!(payload.len(), 4 + 2 + 4 * channel_ids.len());
+
+        let deserialized =
+            SetGroupChannel::try_from(&payload[..]).expect("BUG: cannot deserialize message");
+        assert_eq!(deserialized, message);
+    }
+    assert!(Seq0_64k::try_from(vec![7u32; 65536]).is_err());
+}
+
+/// Serializes the `message` and verifies that the channel id read directly from the payload
+/// matches the deserialized message
+fn check_channel_id<T>(message: T, msg_type: framing::MsgType)
+where
+    T: ChannelMessage + SerializablePayload<Protocol> + for<'a> TryFrom<&'a [u8], Error = Error>,
+{
+    let expected = message.channel_id();
+    let mut writer = BytesMut::new().writer();
+    message
+        .serialize_to_writer(&mut writer)
+        .expect("BUG: cannot serialize message");
+    let payload = writer.into_inner();
+
+    let deserialized = T::try_from(&payload[..]).expect("BUG: cannot deserialize message");
+    assert_eq!(deserialized.channel_id(), expected);
+    assert_eq!(framing::channel_id(&payload), Some(expected));
+
+    let frame = framing::Frame::from_serialized_payload(true, 0, msg_type, payload.clone());
+    assert_eq!(frame.channel_id(), Some(expected));
+    // The flag of the header decides whether the payload starts with the channel id
+    let frame = framing::Frame::from_serialized_payload(false, 0, msg_type, payload);
+    assert_eq!(frame.channel_id(), None);
+}
+
+#[test]
+fn test_channel_id() {
+    let channel_id = 0x1234_5678;
+    check_channel_id(
+        SubmitSharesStandard {
+            channel_id,
+            ..build_submit_shares()
+        },
+        MessageType::SubmitSharesStandard as framing::MsgType,
+    );
+    check_channel_id(
+        ChannelEndpointChanged { channel_id },
+        MessageType::ChannelEndpointChanged as framing::MsgType,
+    );
+    check_channel_id(
+        CloseChannel {
+            channel_id,
+            reason_code: Str0_32::from_str("shutdown"),
+        },
+        MessageType::CloseChannel as framing::MsgType,
+    );
+    check_channel_id(
+        SetExtranoncePrefix {
+            channel_id,
+            extranonce_prefix: Bytes0_32::from_slice(&[1, 2, 3]),
+        },
+        MessageType::SetExtranoncePrefix as framing::MsgType,
+    );
+    check_channel_id(
+        SubmitSharesExtended {
+            share: SubmitSharesStandard {
+                channel_id,
+                ..build_submit_shares()
+            },
+            extranonce: Bytes0_32::new(),
+        },
+        MessageType::SubmitSharesExtended as framing::MsgType,
+    );
+    check_channel_id(
+        SubmitSharesSuccess {
+            channel_id,
+            last_seq_num: 1,
+            new_submits_accepted_count: 2,
+            new_shares_sum: 3,
+        },
+        MessageType::SubmitSharesSuccess as framing::MsgType,
+    );
+    check_channel_id(
+        SubmitSharesError {
+            channel_id,
+            seq_num: 1,
+            code: Str0_32::from_str("stale-share"),
+        },
+        MessageType::SubmitSharesError as framing::MsgType,
+    );
+    check_channel_id(
+        NewMiningJob {
+            channel_id,
+            ..build_new_mining_job()
+        },
+        MessageType::NewMiningJob as framing::MsgType,
+    );
+    check_channel_id(
+        NewExtendedMiningJob {
+            channel_id,
+            ..build_new_extended_mining_job()
+        },
+        MessageType::NewExtendedMiningJob as framing::MsgType,
+    );
+    check_channel_id(
+        SetNewPrevHash {
+            channel_id,
+            ..build_set_new_prev_hash()
+        },
+        MessageType::SetNewPrevHash as framing::MsgType,
+    );
+    check_channel_id(
+        SetCustomMiningJob {
+            channel_id,
+            ..build_set_custom_mining_job()
+        },
+        MessageType::SetCustomMiningJob as framing::MsgType,
+    );
+    check_channel_id(
+        SetCustomMiningJobSuccess {
+            channel_id,
+            req_id: 1,
+            job_id: 2,
+        },
+        MessageType::SetCustomMiningJobSuccess as framing::MsgType,
+    );
+    check_channel_id(
+        SetCustomMiningJobError {
+            channel_id,
+            req_id: 1,
+            code: Str0_32::from_str("invalid-channel-id"),
+        },
+        MessageType::SetCustomMiningJobError as framing::MsgType,
+    );
+    check_channel_id(
+        SetTarget {
+            channel_id,
+            max_target: Uint256Bytes([0xff; 32]),
+        },
+        MessageType::SetTarget as framing::MsgType,
+    );
+    check_channel_id(
+        SubmitTelemetryData {
+            channel_id,
+            seq_num: 1,
+            telemetry_payload: Bytes0_64k::try_from(vec![1, 2, 3]).expect("BUG: payload"),
+        },
+        0x03,
+    );
+    check_channel_id(
+        SubmitTelemetryDataSuccess {
+            channel_id,
+            last_seq_num: 1,
+        },
+        0x04,
+    );
+    check_channel_id(
+        SubmitTelemetryDataError {
+            channel_id,
+            seq_num: 1,
+            code: Str0_32::from_str("invalid"),
+        },
+        0x05,
+    );
+}
+
+#[test]
+fn test_channel_id_short_payload() {
+    assert_eq!(framing::channel_id(&[]), None);
+    assert_eq!(framing::channel_id(&[0x01, 0x02, 0x03]), None);
+    assert_eq!(
+        framing::channel_id(&[0x78, 0x56, 0x34, 0x12]),
+        Some(ChannelId(0x1234_5678))
+    );
+
+    // Frames of messages that haven't been serialized don't provide the channel id
+    let frame: framing::Frame = build_submit_shares()
+        .try_into()
+        .expect("BUG: cannot build frame");
+    assert_eq!(frame.channel_id(), None);
+}
@@ -24,7 +24,7 @@
 use crate::v2::serialization;
 use crate::{
     error::{Error, Result},
-    v2::{error, extensions, framing, types::*, Protocol},
+    v2::{error, extensions, framing, messages::ChannelMessage, types::*, Protocol},
     AnyPayload, Message,
 };
 use async_trait::async_trait;
@@ -123,6 +123,12 @@ impl_telemetry_message_conversion!(
     visit_submit_telemetry_data_error
 );
 
+impl_channel_message!(
+    SubmitTelemetryData,
+    SubmitTelemetryDataSuccess,
+    SubmitTelemetryDataError,
+);
+
 /// Consumes `frame` and produces a Message object based on the payload type
 pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protocol>> {
     trace!("V2: building telemetry message from frame {:x?}", frame);
@@ -140,6 +146,13 @@ pub fn build_message_from_frame(frame: framing::Frame) -> Result<Message<Protoco
             payload: serializable_payload,
         });
     }
+    if frame.header.extension_type != extensions::TELEMETRY {
+        let (header, payload) = extensions::UnknownExtension::from_frame(frame)?;
+        return Ok(Message {
+            header,
+            payload: Box::new(payload),
+        });
+    }
     // Header will be consumed by the subsequent transformation of the frame into the actual
     // payload for further handling. Therefore we create a copy for constructing a
     // Message<Protocol >
@@ -29,6 +29,29 @@ use std::ops::Deref;
 use serde;
 use serde::{Deserialize, Serialize};
 
+/// Identifier of the channel a channel message is addressed to (see
+/// `messages::ChannelMessage`)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChannelId(pub u32);
+
+impl From<u32> for ChannelId {
+    fn from(channel_id: u32) -> Self {
+        Self(channel_id)
+    }
+}
+
+impl From<ChannelId> for u32 {
+    fn from(channel_id: ChannelId) -> Self {
+        channel_id.0
+    }
+}
+
+impl fmt::Display for ChannelId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 // TODO consolidate the u8;32 copied all over the place into an alias
 //type Uint256Inner = [u8; 32];
 
@@ -373,6 +396,15 @@ macro_rules! sized_seq_type {
             }
         }
 
+        impl<T> Clone for $name<T>
+        where
+            T: Serialize + for<'dx> Deserialize<'dx> + Clone,
+        {
+            fn clone(&self) -> Self {
+                Self(self.0.clone())
+            }
+        }
+
         impl<T> PartialEq for $name<T>
         where
             T: Serialize + for<'dx> Deserialize<'dx> + PartialEq,
@@ -70,12 +70,59 @@ impl SeqId {
 pub struct V2ToV1TranslationOptions {
     /// Try to send `extranonce.subscribe` during handshake
     pub try_enable_xnsub: bool,
+    /// Skip `mining.notify` messages that don't bring any material change of the current job
+    pub notify_filter: Option<NotifyFilter>,
 }
 
 impl Default for V2ToV1TranslationOptions {
     fn default() -> Self {
         Self {
             try_enable_xnsub: false,
+            notify_filter: None,
+        }
+    }
+}
+
+/// Freshness filter for `mining.notify` messages. Some pools resend the same job several times
+/// per second (possibly with slightly rolled `ntime`) and each such notification would result
+/// in a new mining job downstream.
+pub struct NotifyFilter {
+    /// Maximal forward shift of `ntime` in an otherwise identical job that is still considered
+    /// redundant
+    pub ntime_tolerance: u32,
+    /// Called for every skipped notification
+    pub skip_hook: Option<Box<dyn Fn() + Send + Sync>>,
+}
+
+impl NotifyFilter {
+    pub const DEFAULT_NTIME_TOLERANCE: u32 = 30;
+
+    /// Returns true when the `new` notification doesn't materially differ from the `current`
+    /// one. Clean jobs flag and prev hash change are always honored.
+    pub fn is_redundant(&self, current: &v1::messages::Notify, new: &v1::messages::Notify) -> bool {
+        !new.clean_jobs()
+            && new.prev_hash() == current.prev_hash()
+            && new.coin_base_1() == current.coin_base_1()
+            && new.coin_base_2() == current.coin_base_2()
+            && new.merkle_branch() == current.merkle_branch()
+            && new.version() == current.version()
+            && new.bits() == current.bits()
+            && new.time() >= current.time()
+            && new.time() - current.time() <= self.ntime_tolerance
+    }
+
+    fn skip(&self) {
+        if let Some(skip_hook) = self.skip_hook.as_ref() {
+            skip_hook();
+        }
+    }
+}
+
+impl Default for NotifyFilter {
+    fn default() -> Self {
+        Self {
+            ntime_tolerance: Self::DEFAULT_NTIME_TOLERANCE,
+            skip_hook: None,
         }
     }
 }
@@ -155,6 +202,10 @@ pub struct V2ToV1Translation {
     /// This allows immediate completion of channel open on V2.
     v1_deferred_notify: Option<v1::messages::Notify>,
 
+    /// Latest mining.notify payload that has been translated into a mining job. It is used for
+    /// detection of redundant notifications.
+    v1_last_notify: Option<v1::messages::Notify>,
+
     /// Channel for sending out V2 responses
     v2_tx: mpsc::Sender<v2::Frame>,
     #[allow(dead_code)] // TODO: unused as of now
@@ -208,6 +259,7 @@ impl V2ToV1Translation {
             v1_force_future_jobs: true,
             v1_xnsub_enabled: false,
             v1_deferred_notify: None,
+            v1_last_notify: None,
             v2_tx,
             v2_req_id: SeqId::new(),
             v2_job_id: SeqId::new(),
@@ -807,9 +859,20 @@ impl V2ToV1Translation {
         if let Some(set_new_prev_hash) = maybe_set_new_prev_hash {
             util::submit_message(&mut self.v2_tx, set_new_prev_hash)?
         }
+        self.v1_last_notify = Some(payload.clone());
         Ok(())
     }
 
+    /// Checks whether the notification `payload` can be skipped according to notify filter
+    fn is_redundant_notify(&self, payload: &v1::messages::Notify) -> bool {
+        match (&self.options.notify_filter, &self.v1_last_notify) {
+            (Some(notify_filter), Some(last_notify)) => {
+                notify_filter.is_redundant(last_notify, payload)
+            }
+            _ => false,
+        }
+    }
+
     /// The result visitor takes care of detecting a spurious response without matching request
     /// and passes processing further
     /// TODO write a solid unit test covering all 3 scenarios that can go wrong
@@ -957,6 +1020,16 @@ impl v1::Handler for V2ToV1Translation {
             info!("Channel not yet operational, caching latest mining.notify from upstream");
             return;
         }
+        if self.is_redundant_notify(payload) {
+            trace!(
+                "visit_notify: skipping redundant job id={}",
+                payload.job_id()
+            );
+            if let Some(notify_filter) = self.options.notify_filter.as_ref() {
+                notify_filter.skip();
+            }
+            return;
+        }
         self.perform_notify(payload)
             .map_err(|e| {
                 info!(
@@ -29,6 +29,10 @@ use ii_stratum::test_utils;
 use ii_stratum::v1;
 use ii_stratum::v2;
 
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
 /// Simulates incoming message by converting it into a `Frame` and running the deserialization
 /// chain from that point on
 async fn v2_simulate_incoming_message<M>(translation: &mut V2ToV1Translation, message: M)
@@ -184,3 +188,137 @@ fn test_diff_1_bitcoin_target() {
         V2ToV1Translation::DIFF1_TARGET
     );
 }
+
+/// Builds `mining.notify` for the redundant notification tests. Only the fields which matter for
+/// the notify filter are parametrized.
+fn build_notify(
+    job_id: usize,
+    prev_hash_suffix: &str,
+    coin_base_1: &str,
+    ntime: u32,
+    clean_jobs: bool,
+) -> v1::rpc::Rpc {
+    let json = format!(
+        r#"{{"id":null,"method":"mining.notify","params":["{}","13f46cc7bf03a16697170dbb9d15680b7e75fcf10846037f171d7f6b0000{}","{}","e91d012f736c7573682f00000000",[],"20000000","1d00ffff","{:08x}",{}]}}"#,
+        job_id, prev_hash_suffix, coin_base_1, ntime, clean_jobs
+    );
+    v1::rpc::Rpc::from_str(json.as_str()).expect("Cannot parse mining notify")
+}
+
+/// Synthetic notification trace modelled after a pool which resends the current job several
+/// times per second, rolling only `ntime` or not changing anything at all. Every fifth
+/// notification brings a material change, the rest (80%) is redundant.
+///
+/// NOTE: the trace is generated, it is not a capture from a real pool. Replace it with a recorded
+/// session once one is available.
+fn build_redundant_notify_trace() -> Vec<v1::rpc::Rpc> {
+    const BASE_NTIME: u32 = 0x5d10bc0a;
+
+    let mut trace = vec![];
+    for block in 0..10u32 {
+        let prev_hash_suffix = format!("{:04x}", block);
+        let ntime = BASE_NTIME + block * 100;
+        // New block with clean jobs
+        trace.push(build_notify(
+            trace.len(),
+            prev_hash_suffix.as_str(),
+            "01000000",
+            ntime,
+            true,
+        ));
+        // Identical job resent
+        trace.push(build_notify(
+            trace.len(),
+            prev_hash_suffix.as_str(),
+            "01000000",
+            ntime,
+            false,
+        ));
+        // Rolled ntime within tolerance
+        for shift in 1..=3 {
+            trace.push(build_notify(
+                trace.len(),
+                prev_hash_suffix.as_str(),
+                "01000000",
+                ntime + shift,
+                false,
+            ));
+        }
+    }
+    trace
+}
+
+#[test]
+fn test_notify_filter() {
+    let filter = NotifyFilter::default();
+    let notify = |rpc| {
+        if let v1::rpc::Rpc::Request(request) = rpc {
+            v1::messages::Notify::try_from(request).expect("Cannot build mining notify")
+        } else {
+            panic!("Wrong notification message");
+        }
+    };
+    let current = notify(build_notify(0, "0000", "01000000", 100, false));
+
+    // Nothing has changed or ntime is within tolerance
+    let new = notify(build_notify(1, "0000", "01000000", 100, false));
+    assert!(filter.is_redundant(&current, &new));
+    let new = notify(build_notify(1, "0000", "01000000", 130, false));
+    assert!(filter.is_redundant(&current, &new));
+
+    // Material changes
+    let new = notify(build_notify(1, "0000", "01000000", 100, true));
+    assert!(!filter.is_redundant(&current, &new));
+    let new = notify(build_notify(1, "0001", "01000000", 100, false));
+    assert!(!filter.is_redundant(&current, &new));
+    let new = notify(build_notify(1, "0000", "02000000", 100, false));
+    assert!(!filter.is_redundant(&current, &new));
+    let new = notify(build_notify(1, "0000", "01000000", 131, false));
+    assert!(!filter.is_redundant(&current, &new));
+    let new = notify(build_notify(1, "0000", "01000000", 99, false));
+    assert!(!filter.is_redundant(&current, &new));
+}
+
+/// Feeds the synthetic redundant notification trace to operational translation and verifies that only
+/// new jobs are sent downstream
+#[tokio::test]
+async fn test_redundant_notify_trace() {
+    let trace = build_redundant_notify_trace();
+    let (v1_tx, _v1_rx) = mpsc::channel(1);
+    let (v2_tx, mut v2_rx) = mpsc::channel(2 * trace.len());
+
+    let skipped = Arc::new(AtomicUsize::new(0));
+    let skipped_hook = skipped.clone();
+    let options = V2ToV1TranslationOptions {
+        notify_filter: Some(NotifyFilter {
+            skip_hook: Some(Box::new(move || {
+                skipped_hook.fetch_add(1, Ordering::Relaxed);
+            })),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let mut translation = V2ToV1Translation::new(v1_tx, v2_tx, options);
+    // Shortcut the channel setup
+    translation.state = V2ToV1TranslationState::Operational;
+    translation.v1_extra_nonce1 = Some(v1::ExtraNonce1(
+        v1::HexBytes::try_from("00000000").expect("Cannot build extra nonce 1"),
+    ));
+    translation.v1_extra_nonce2_size = 4;
+
+    let trace_len = trace.len();
+    for notify in trace {
+        v1_simulate_incoming_message(&mut translation, notify).await;
+    }
+    drop(translation);
+
+    let mut new_jobs = 0;
+    while let Some(frame) = v2_rx.next().await {
+        let msg = v2::build_message_from_frame(frame).expect("Deserialization failed");
+        if msg.header.msg_type == v2::messages::MessageType::NewMiningJob as u8 {
+            new_jobs += 1;
+        }
+    }
+    assert_eq!(skipped.load(Ordering::Relaxed), trace_len * 4 / 5);
+    assert_eq!(new_jobs, trace_len / 5);
+}
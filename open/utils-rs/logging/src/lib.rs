@@ -44,6 +44,9 @@
 //! Refer to the [`env_logger` documentation](https://docs.rs/env_logger/0.6.2/env_logger/)
 //! for more information.
 //!
+//! The filters can be temporarily replaced in runtime with a single logging level
+//! using `set_level_override()`.
+//!
 //! If no configuration is set with `set_logger_config()` et al.,
 //! the global logger will by default use `LoggingConfig::for_testing()`,
 //! ie. configuration suitable for testing. This is because as of now
@@ -55,8 +58,10 @@ use std::fmt;
 use std::fs::OpenOptions;
 use std::mem;
 use std::ops::Deref;
+use std::panic;
 use std::path::{Path, PathBuf};
-use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use lazy_static::lazy_static;
 use slog::{o, Discard, Drain, FilterLevel, Logger};
@@ -208,6 +213,67 @@ fn get_envlogger_drain<D: Drain>(drain: D, default_level: Level) -> EnvLogger<D>
     }
 }
 
+/// Value of `LEVEL_OVERRIDE` when no override is set (slog levels start from 1)
+const NO_LEVEL_OVERRIDE: usize = 0;
+
+/// Logging level which replaces the configured filters in runtime
+static LEVEL_OVERRIDE: AtomicUsize = AtomicUsize::new(NO_LEVEL_OVERRIDE);
+
+/// Replaces the configured logging level and `RUST_LOG` filters with a single `level` in runtime.
+/// Passing `None` restores the original filters.
+pub fn set_level_override(level: Option<Level>) {
+    LEVEL_OVERRIDE.store(
+        level.map_or(NO_LEVEL_OVERRIDE, |level| level.as_usize()),
+        Ordering::Relaxed,
+    );
+}
+
+/// Returns the logging level set by `set_level_override()`
+pub fn get_level_override() -> Option<Level> {
+    Level::from_usize(LEVEL_OVERRIDE.load(Ordering::Relaxed))
+}
+
+/// Drain that applies the runtime level override when it is set and the configured filters
+/// otherwise. The inner drain is shared by both paths.
+struct OverrideDrain<D: Drain> {
+    filtered: EnvLogger<Arc<D>>,
+    drain: Arc<D>,
+}
+
+impl<D> OverrideDrain<D>
+where
+    D: Drain<Ok = ()>,
+{
+    fn new(drain: D, default_level: Level) -> Self {
+        let drain = Arc::new(drain);
+        Self {
+            filtered: get_envlogger_drain(drain.clone(), default_level),
+            drain,
+        }
+    }
+}
+
+// The drain is used outside of the asynchronous drain thread where the logger requires it to be
+// unwind safe. The only offender is the regex cache of the message filter which is safe to reuse
+// after a panic.
+impl<D: Drain> panic::RefUnwindSafe for OverrideDrain<D> {}
+
+impl<D> Drain for OverrideDrain<D>
+where
+    D: Drain<Ok = ()>,
+{
+    type Ok = ();
+    type Err = D::Err;
+
+    fn log(&self, record: &slog::Record, values: &slog::OwnedKVList) -> Result<(), Self::Err> {
+        match get_level_override() {
+            Some(level) if record.level().is_at_least(level) => self.drain.log(record, values),
+            Some(_) => Ok(()),
+            None => self.filtered.log(record, values),
+        }
+    }
+}
+
 /// Create terminal drain for logger, logging to either stderr or stdout
 fn get_terminal_drain(stderr: bool) -> impl Drain<Ok = (), Err = impl fmt::Debug> {
     let builder = slog_term::TermDecorator::new();
@@ -278,10 +344,12 @@ impl GuardedLogger {
         E: fmt::Debug,
         D: Drain<Ok = (), Err = E> + Send + 'static,
     {
-        let drain = get_envlogger_drain(drain, config.level);
         let (drain, guard) = Async::new(drain.fuse())
             .chan_size(config.drain_channel_size)
             .build_with_guard();
+        // Filter messages before they are sent to the asynchronous drain so that the level
+        // override takes effect immediately
+        let drain = OverrideDrain::new(drain, config.level);
         Self {
             logger: Logger::root(drain.fuse(), o!()),
             guard: Mutex::new(FlushGuard(Some(guard))),
@@ -0,0 +1,63 @@
+// Copyright (C) 2019  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Test of overriding the configured logging level in runtime.
+//!
+//! **Warning**: Each logging test needs to be in a separate files
+//! due to global LOGGER initialization
+
+use std::env;
+use std::fs;
+
+use ii_logging::macros::*;
+use ii_logging::{self, Level, LoggingConfig, LoggingTarget, LOGGER};
+
+use tempfile::NamedTempFile;
+
+#[test]
+fn test_logging_level_override() {
+    env::set_var("RUST_LOG", "");
+
+    let temp_file = NamedTempFile::new().expect("Could not create temporary file");
+    let config = LoggingConfig {
+        target: LoggingTarget::File(temp_file.path().into()),
+        level: Level::Info,
+        drain_channel_size: LoggingConfig::ASYNC_LOGGER_DRAIN_CHANNEL_SIZE,
+    };
+    ii_logging::set_logger_config(config);
+    let flush_guard = LOGGER.take_guard();
+
+    debug!("filtered by configured level");
+    ii_logging::set_level_override(Some(Level::Debug));
+    assert_eq!(ii_logging::get_level_override(), Some(Level::Debug));
+    debug!("passed by override");
+    ii_logging::set_level_override(Some(Level::Error));
+    info!("filtered by override");
+    ii_logging::set_level_override(None);
+    info!("passed by configured level");
+    drop(flush_guard);
+
+    let log_contents = fs::read_to_string(temp_file.path()).expect("Could not read back log file");
+    assert!(log_contents.find("filtered").is_none());
+    assert!(log_contents.find("passed by override").is_some());
+    assert!(log_contents.find("passed by configured level").is_some());
+}